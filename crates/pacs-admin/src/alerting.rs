@@ -25,14 +25,16 @@ pub struct AlertManager {
     notification_sender: Arc<dyn NotificationSender + Send + Sync>,
     /// 指标获取器
     metric_provider: Arc<dyn MetricProvider + Send + Sync>,
+    /// 规则告警的通知配置
+    notification_config: NotificationConfig,
 }
 
 impl std::fmt::Debug for AlertManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AlertManager")
-            .field("rules_count", &self.rules.read().await.len())
-            .field("active_alerts_count", &self.active_alerts.read().await.len())
-            .field("alert_history_count", &self.alert_history.read().await.len())
+            .field("rules_count", &self.rules.try_read().map(|r| r.len()).ok())
+            .field("active_alerts_count", &self.active_alerts.try_read().map(|a| a.len()).ok())
+            .field("alert_history_count", &self.alert_history.try_read().map(|h| h.len()).ok())
             .finish()
     }
 }
@@ -142,14 +144,21 @@ impl AlertManager {
             alert_history: Arc::new(RwLock::new(Vec::new())),
             notification_sender,
             metric_provider,
+            notification_config: NotificationConfig::default(),
         }
     }
 
+    /// 设置规则告警的通知配置
+    pub fn with_notification_config(mut self, notification_config: NotificationConfig) -> Self {
+        self.notification_config = notification_config;
+        self
+    }
+
     /// 添加告警规则
     pub async fn add_rule(&self, rule: AlertRule) -> Result<()> {
         let mut rules = self.rules.write().await;
-        rules.insert(rule.name.clone(), rule);
         info!("Added alert rule: {}", rule.name);
+        rules.insert(rule.name.clone(), rule);
         Ok(())
     }
 
@@ -261,7 +270,7 @@ impl AlertManager {
 
                 // 检查是否需要再次发送通知（重试逻辑）
                 if self.should_resend_notification(active_alert, rule).await {
-                    self.send_alert_notification(alert, &rule.notifications).await?;
+                    self.send_alert_notification(alert, &self.notification_config).await?;
                 }
             }
             None => {
@@ -275,7 +284,7 @@ impl AlertManager {
                 active_alerts.insert(alert_key.to_string(), active_alert);
 
                 // 发送新告警通知
-                self.send_alert_notification(alert, &rule.notifications).await?;
+                self.send_alert_notification(alert, &self.notification_config).await?;
             }
         }
 
@@ -285,6 +294,16 @@ impl AlertManager {
         Ok(())
     }
 
+    /// 触发外部事件告警（不经指标评估，例如剂量超过参考水平）
+    pub async fn raise_alert(&self, alert: AlertEvent, notification_config: &NotificationConfig) -> Result<()> {
+        warn!("Alert raised: {} - {}", alert.rule_name, alert.message);
+
+        self.send_alert_notification(&alert, notification_config).await?;
+        self.record_alert_event(&alert).await;
+
+        Ok(())
+    }
+
     /// 判断是否需要重新发送通知
     async fn should_resend_notification(&self, active_alert: &ActiveAlert, rule: &AlertRule) -> bool {
         // 简单的重试策略：每10分钟重试一次，最多重试3次
@@ -409,20 +428,22 @@ impl AlertManager {
     pub async fn manually_resolve_alert(&self, alert_id: &str) -> Result<bool> {
         let mut active_alerts = self.active_alerts.write().await;
 
-        for (rule_name, active_alert) in active_alerts.iter_mut() {
-            if active_alert.event.id == alert_id {
+        let rule_name = active_alerts
+            .iter()
+            .find(|(_, active_alert)| active_alert.event.id == alert_id)
+            .map(|(rule_name, _)| rule_name.clone());
+
+        match rule_name.and_then(|rule_name| active_alerts.remove(&rule_name)) {
+            Some(mut active_alert) => {
                 active_alert.event.resolved = true;
                 active_alert.event.timestamp = chrono::Utc::now();
 
                 info!("Manually resolved alert: {}", alert_id);
                 self.record_alert_event(&active_alert.event).await;
-
-                active_alerts.remove(rule_name);
-                return Ok(true);
+                Ok(true)
             }
+            None => Ok(false),
         }
-
-        Ok(false)
     }
 }
 
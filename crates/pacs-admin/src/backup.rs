@@ -1 +1,426 @@
 //! 备份管理
+//!
+//! 协调存储备份与数据库逻辑快照，支持按时间点恢复：选取目标时间之前最近一次
+//! 带数据库快照的备份，恢复其对象集合与数据库索引，再执行对账使索引与对象一致。
+//! 数据库索引先在未提交的事务中写入，对象恢复成功后才提交，任一步失败时数据库保持原样。
+//! 恢复前可以先以演练模式（dry-run）查看将要恢复的内容。
+//!
+//! 恢复期间应暂停影像接收，否则新入库的实例可能在对账时被隔离。
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use pacs_database::{DatabasePool, DatabaseQueries, DatabaseSnapshot};
+use pacs_storage::{
+    BackupConfig, BackupManager, BackupType, KeyRing, RestoreInfo, ScheduledJob, StorageManager,
+};
+
+/// 数据库快照在备份目标存储中的目录名
+const SNAPSHOT_DIR: &str = "db";
+
+/// 对账时被隔离的对象所在前缀
+pub const QUARANTINE_PREFIX: &str = "quarantine/";
+
+/// 影像对象的文件后缀，对账只处理此类对象
+const OBJECT_SUFFIX: &str = ".dcm";
+
+/// 时间点恢复计划（演练模式的输出）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestorePlan {
+    /// 目标时间
+    pub target_time: DateTime<Utc>,
+    /// 选用的备份ID
+    pub backup_id: String,
+    /// 备份完成时间
+    pub backup_completed_at: Option<DateTime<Utc>>,
+    /// 数据库快照时间
+    pub snapshot_taken_at: DateTime<Utc>,
+    /// 将恢复的对象数量
+    pub object_count: u64,
+    /// 将恢复的数据大小
+    pub total_size: u64,
+    /// 在线存储中缺失或大小不同、将被覆盖的对象
+    pub changed_objects: Vec<String>,
+    /// 各表将恢复的行数
+    pub table_rows: BTreeMap<String, usize>,
+    /// 快照中有索引但备份中没有对象的实例（恢复后将删除索引）
+    pub instances_without_objects: Vec<String>,
+    /// 在线存储中没有索引的对象（恢复后将被隔离）
+    pub orphan_objects: Vec<String>,
+}
+
+/// 对账结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconciliationReport {
+    /// 已删除索引的实例（对象不存在）
+    pub removed_index_entries: Vec<String>,
+    /// 已隔离的对象（没有索引），值为隔离后的路径
+    pub quarantined_objects: Vec<String>,
+}
+
+/// 时间点恢复结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointInTimeRestoreReport {
+    /// 恢复计划
+    pub plan: RestorePlan,
+    /// 是否为演练模式
+    pub dry_run: bool,
+    /// 对象恢复信息（演练模式为None）
+    pub restore_info: Option<RestoreInfo>,
+    /// 对账结果（演练模式为None）
+    pub reconciliation: Option<ReconciliationReport>,
+}
+
+/// 时间点恢复服务
+pub struct PointInTimeRestoreService {
+    /// 存储备份管理器
+    backup_manager: Arc<Mutex<BackupManager>>,
+    /// 数据库连接池
+    database: Arc<DatabasePool>,
+    /// 数据库快照加密密钥环
+    key_ring: Option<KeyRing>,
+}
+
+impl PointInTimeRestoreService {
+    /// 创建时间点恢复服务
+    pub fn new(backup_manager: Arc<Mutex<BackupManager>>, database: Arc<DatabasePool>) -> Self {
+        Self {
+            backup_manager,
+            database,
+            key_ring: None,
+        }
+    }
+
+    /// 设置数据库快照加密密钥环
+    pub fn with_key_ring(mut self, key_ring: KeyRing) -> Self {
+        self.key_ring = Some(key_ring);
+        self
+    }
+
+    /// 执行带数据库快照的备份，返回备份ID
+    ///
+    /// 先备份对象再导出数据库快照，快照中的索引不会指向备份开始前已删除的对象；
+    /// 复制期间新入库、未进入备份的实例在恢复时由对账删除其索引
+    pub async fn backup(&self, config_name: &str, backup_type: BackupType) -> Result<String> {
+        let config = self
+            .backup_manager
+            .lock()
+            .await
+            .get_config(config_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Backup configuration not found: {}", config_name))?;
+        let backup_id =
+            BackupManager::execute_shared_backup(&self.backup_manager, config_name, backup_type)
+                .await?;
+
+        let snapshot = DatabaseSnapshot::capture(&self.database)
+            .await
+            .with_context(|| format!("Backup {} completed without a database snapshot", backup_id))?;
+        self.store_snapshot(&config, &backup_id, &snapshot).await?;
+        info!("Stored database snapshot for backup {}", backup_id);
+        Ok(backup_id)
+    }
+
+    /// 恢复到指定时间点
+    ///
+    /// 演练模式只生成恢复计划；否则先在事务中写入数据库索引，恢复对象后提交并对账
+    pub async fn restore_to_point_in_time(
+        &self,
+        config_name: &str,
+        target_time: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<PointInTimeRestoreReport> {
+        let mut manager = self.backup_manager.lock().await;
+        let config = manager
+            .get_config(config_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Backup configuration not found: {}", config_name))?;
+        let backup_storage = StorageManager::new(config.target_storage.clone()).await?;
+        let live_storage = StorageManager::new(config.source_storage.clone()).await?;
+
+        // 选取目标时间之前最近一次带数据库快照的备份
+        let candidates: Vec<_> = manager
+            .backups_completed_before(config_name, target_time)
+            .into_iter()
+            .cloned()
+            .collect();
+        let mut selected = None;
+        for backup in candidates {
+            match self.load_snapshot(&config, &backup_storage, &backup.id).await {
+                Ok(snapshot) => {
+                    selected = Some((backup, snapshot));
+                    break;
+                }
+                Err(e) => warn!("Backup {} has no usable database snapshot: {}", backup.id, e),
+            }
+        }
+        let (backup, snapshot) = selected.ok_or_else(|| {
+            anyhow!("No backup with a database snapshot completed before {}", target_time)
+        })?;
+
+        let state = manager.backup_state(&backup.id)?;
+        let live_objects = live_storage.list_files(None).await?;
+        let live_sizes: BTreeMap<String, u64> = live_objects
+            .iter()
+            .filter(|object| is_indexed_object(&config, &object.key))
+            .map(|object| (object.key.clone(), object.size))
+            .collect();
+        let indexed: HashSet<String> = snapshot
+            .instance_files()
+            .into_iter()
+            .map(|(_, file_path)| config.source_storage.object_key(&file_path))
+            .collect();
+
+        let plan = RestorePlan {
+            target_time,
+            backup_id: backup.id.clone(),
+            backup_completed_at: backup.end_time,
+            snapshot_taken_at: snapshot.taken_at,
+            object_count: state.len() as u64,
+            total_size: state.values().map(|entry| entry.size).sum(),
+            changed_objects: state
+                .values()
+                .filter(|entry| live_sizes.get(&entry.original_path) != Some(&entry.size))
+                .map(|entry| entry.original_path.clone())
+                .collect(),
+            table_rows: snapshot
+                .tables
+                .iter()
+                .map(|table| (table.name.clone(), table.rows.len()))
+                .collect(),
+            instances_without_objects: snapshot
+                .instance_files()
+                .into_iter()
+                .filter(|(_, file_path)| {
+                    !state.contains_key(&config.source_storage.object_key(file_path))
+                })
+                .map(|(sop_instance_uid, _)| sop_instance_uid)
+                .collect(),
+            orphan_objects: state
+                .keys()
+                .chain(live_sizes.keys())
+                .filter(|key| is_indexed_object(&config, key) && !indexed.contains(*key))
+                .cloned()
+                .collect::<std::collections::BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+        };
+
+        if dry_run {
+            info!(
+                "Dry run of point-in-time restore to {}: backup {}, {} objects ({} changed), {} orphans",
+                target_time,
+                plan.backup_id,
+                plan.object_count,
+                plan.changed_objects.len(),
+                plan.orphan_objects.len()
+            );
+            return Ok(PointInTimeRestoreReport {
+                plan,
+                dry_run: true,
+                restore_info: None,
+                reconciliation: None,
+            });
+        }
+
+        info!(
+            "Restoring to point in time {} from backup {}",
+            target_time, plan.backup_id
+        );
+        // 索引先写入未提交的事务，对象恢复失败时回滚，成功后才提交
+        let staged = snapshot
+            .stage_restore(&self.database)
+            .await
+            .context("Database restore failed, objects were left unchanged")?;
+        let restore_info = match manager.restore_backup_to(&backup.id, &live_storage).await {
+            Ok(restore_info) => restore_info,
+            Err(e) => {
+                if let Err(rollback_error) = staged.rollback().await {
+                    warn!("Failed to roll back database restore: {}", rollback_error);
+                }
+                return Err(anyhow!(e).context("Object restore failed, database was left unchanged"));
+            }
+        };
+        drop(manager);
+
+        staged.commit().await.with_context(|| {
+            format!(
+                "Objects of backup {} were restored but the database restore could not be committed",
+                backup.id
+            )
+        })?;
+        let reconciliation = self.reconcile(&config, &live_storage).await?;
+
+        info!(
+            "Point-in-time restore completed: {} objects restored, {} index entries removed, {} objects quarantined",
+            restore_info.file_count,
+            reconciliation.removed_index_entries.len(),
+            reconciliation.quarantined_objects.len()
+        );
+        Ok(PointInTimeRestoreReport {
+            plan,
+            dry_run: false,
+            restore_info: Some(restore_info),
+            reconciliation: Some(reconciliation),
+        })
+    }
+
+    /// 对账：删除对象不存在的实例索引，并隔离没有索引的对象
+    pub async fn reconcile(
+        &self,
+        config: &BackupConfig,
+        live_storage: &StorageManager,
+    ) -> Result<ReconciliationReport> {
+        let queries = DatabaseQueries::new(&self.database);
+        let live_keys: HashSet<String> = live_storage
+            .list_files(None)
+            .await?
+            .into_iter()
+            .map(|object| object.key)
+            .filter(|key| is_indexed_object(config, key))
+            .collect();
+
+        let mut report = ReconciliationReport::default();
+        let mut indexed = HashSet::new();
+        for (sop_instance_uid, file_path) in queries.list_instance_files().await? {
+            let key = config.source_storage.object_key(&file_path);
+            if live_keys.contains(&key) {
+                indexed.insert(key);
+                continue;
+            }
+
+            warn!(
+                "Removing index entry of {} without object {}",
+                sop_instance_uid, key
+            );
+            queries.delete_instance_by_uid(&sop_instance_uid).await?;
+            report.removed_index_entries.push(sop_instance_uid);
+        }
+
+        let reconcile_id = Utc::now().format("%Y%m%dT%H%M%S").to_string();
+        for key in live_keys.difference(&indexed) {
+            let quarantine_key = format!("{}{}/{}", QUARANTINE_PREFIX, reconcile_id, key);
+            warn!("Quarantining unindexed object {} to {}", key, quarantine_key);
+            live_storage.backend().copy(key, &quarantine_key).await?;
+            live_storage.delete_file(key).await?;
+            report.quarantined_objects.push(quarantine_key);
+        }
+
+        report.quarantined_objects.sort();
+        Ok(report)
+    }
+
+    /// 写入备份对应的数据库快照
+    async fn store_snapshot(
+        &self,
+        config: &BackupConfig,
+        backup_id: &str,
+        snapshot: &DatabaseSnapshot,
+    ) -> Result<()> {
+        let storage = StorageManager::new(config.target_storage.clone()).await?;
+        let key = snapshot_key(config, backup_id);
+        let mut data = serde_json::to_vec(snapshot)?;
+        if config.encryption_enabled {
+            let key_ring = self
+                .key_ring
+                .as_ref()
+                .ok_or_else(|| anyhow!("Backup encryption requires a key ring for database snapshots"))?;
+            let sealed = key_ring.seal(&data, key.as_bytes())?;
+            data = serde_json::to_vec(&SealedSnapshot {
+                key_id: sealed.key_id,
+                data: sealed.data,
+            })?;
+        }
+        storage.store_file(&data, &key).await?;
+        Ok(())
+    }
+
+    /// 读取备份对应的数据库快照
+    async fn load_snapshot(
+        &self,
+        config: &BackupConfig,
+        storage: &StorageManager,
+        backup_id: &str,
+    ) -> Result<DatabaseSnapshot> {
+        let key = snapshot_key(config, backup_id);
+        let data = storage.get_file(&key).await?;
+        if let Ok(sealed) = serde_json::from_slice::<SealedSnapshot>(&data) {
+            let key_ring = self
+                .key_ring
+                .as_ref()
+                .ok_or_else(|| anyhow!("Database snapshot {} is encrypted but no key ring is set", key))?;
+            let plaintext = key_ring.open(&sealed.key_id, &sealed.data, key.as_bytes())?;
+            return Ok(serde_json::from_slice(&plaintext)?);
+        }
+        Ok(serde_json::from_slice(&data)?)
+    }
+}
+
+/// 加密后的数据库快照
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedSnapshot {
+    /// 加密所用的密钥ID
+    key_id: String,
+    /// nonce与密文
+    data: Vec<u8>,
+}
+
+/// 定时执行带数据库快照的备份
+pub struct SnapshotBackupJob {
+    /// 时间点恢复服务
+    service: Arc<PointInTimeRestoreService>,
+    /// 备份配置名称
+    config_name: String,
+    /// 备份类型
+    backup_type: BackupType,
+}
+
+impl SnapshotBackupJob {
+    /// 创建定时备份任务
+    pub fn new(
+        service: Arc<PointInTimeRestoreService>,
+        config_name: &str,
+        backup_type: BackupType,
+    ) -> Self {
+        Self {
+            service,
+            config_name: config_name.to_string(),
+            backup_type,
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduledJob for SnapshotBackupJob {
+    async fn run(&self) -> pacs_core::Result<String> {
+        let backup_id = self
+            .service
+            .backup(&self.config_name, self.backup_type.clone())
+            .await
+            .map_err(|e| pacs_core::PacsError::Storage(e.to_string()))?;
+        Ok(format!("backup {} with database snapshot completed", backup_id))
+    }
+}
+
+/// 数据库快照的存储路径
+fn snapshot_key(config: &BackupConfig, backup_id: &str) -> String {
+    format!(
+        "{}/{}/{}.json",
+        config.backup_prefix.trim_matches('/'),
+        SNAPSHOT_DIR,
+        backup_id
+    )
+}
+
+/// 存储键是否为需要与索引对账的影像对象
+fn is_indexed_object(config: &BackupConfig, key: &str) -> bool {
+    key.ends_with(OBJECT_SUFFIX)
+        && !key.starts_with(QUARANTINE_PREFIX)
+        && !key.starts_with(&format!("{}/", config.backup_prefix.trim_matches('/')))
+}
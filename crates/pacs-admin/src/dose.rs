@@ -0,0 +1,686 @@
+//! 辐射剂量登记
+//!
+//! 汇总CT/XA剂量SR（RDSR）与图像头中的剂量字段，按检查存储剂量记录（数据库剂量表），
+//! 统计患者累计剂量，按协议的诊断参考水平（DRL）触发告警，并支持CSV导出。
+//! 剂量登记作为入库监听器注册到入库管道，新入库的剂量对象自动登记。
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use pacs_core::PacsError;
+use pacs_database::{DatabasePool, DatabaseQueries, DbRadiationDose, NewRadiationDose};
+use pacs_dicom::{
+    IngestListener, IngestSource, IngestedInstance, ParsedDicomObject, RadiationDoseSummary,
+    SrParser,
+};
+
+use super::alerting::AlertManager;
+use super::monitoring::{AlertEvent, AlertSeverity, NotificationConfig};
+
+/// 剂量数据来源
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DoseSource {
+    /// 辐射剂量结构化报告
+    Rdsr,
+    /// 图像头字段
+    Header,
+}
+
+impl DoseSource {
+    /// 数据库中的来源标识
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DoseSource::Rdsr => "RDSR",
+            DoseSource::Header => "HEADER",
+        }
+    }
+}
+
+/// 剂量记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoseRecord {
+    /// 记录ID
+    pub id: Uuid,
+    /// 患者ID
+    pub patient_id: String,
+    /// 检查实例UID
+    pub study_uid: String,
+    /// 剂量来源对象的SOP实例UID
+    pub sop_instance_uid: String,
+    /// 模态
+    pub modality: Option<String>,
+    /// 采集协议
+    pub protocol: Option<String>,
+    /// 数据来源
+    pub source: DoseSource,
+    /// CTDIvol（mGy）
+    pub ctdi_vol: Option<f64>,
+    /// 剂量长度乘积DLP（mGy·cm）
+    pub dlp: Option<f64>,
+    /// 入射剂量（Gy）
+    pub entrance_dose: Option<f64>,
+    /// 剂量面积乘积KAP（Gy·m²）
+    pub kap: Option<f64>,
+    /// 照射事件数（图像头记录为0）
+    #[serde(default)]
+    pub irradiation_events: i32,
+    /// 记录时间
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl DoseRecord {
+    /// 从RDSR剂量汇总创建记录
+    pub fn from_rdsr(summary: &RadiationDoseSummary, modality: Option<String>) -> Result<Self> {
+        let patient_id = summary
+            .patient_id
+            .clone()
+            .ok_or_else(|| anyhow!("Dose report {} has no patient id", summary.sop_instance_uid))?;
+        let study_uid = summary
+            .study_instance_uid
+            .clone()
+            .ok_or_else(|| anyhow!("Dose report {} has no study uid", summary.sop_instance_uid))?;
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            patient_id,
+            study_uid,
+            sop_instance_uid: summary.sop_instance_uid.clone(),
+            modality,
+            protocol: summary.events.iter().find_map(|e| e.protocol.clone()),
+            source: DoseSource::Rdsr,
+            ctdi_vol: summary.max_ctdi_vol(),
+            dlp: summary.dlp_total,
+            entrance_dose: summary.dose_rp_total,
+            kap: summary.dose_area_product_total,
+            irradiation_events: summary.events.len() as i32,
+            recorded_at: Utc::now(),
+        })
+    }
+
+    /// 从图像头剂量字段创建记录，不含剂量字段时返回None
+    pub fn from_header(parsed: &ParsedDicomObject) -> Option<Self> {
+        if !parsed.has_dose_fields() {
+            return None;
+        }
+
+        Some(Self {
+            id: Uuid::new_v4(),
+            patient_id: parsed.patient_id.clone()?,
+            study_uid: parsed.study_instance_uid.clone()?,
+            sop_instance_uid: parsed.sop_instance_uid.clone()?,
+            modality: parsed.modality.clone(),
+            protocol: parsed.protocol_name.clone(),
+            source: DoseSource::Header,
+            ctdi_vol: parsed.ctdi_vol,
+            dlp: None,
+            // 图像头中入射剂量为mGy，KAP为dGy·cm²
+            entrance_dose: parsed.entrance_dose_mgy.map(|v| v / 1000.0),
+            kap: parsed.area_dose_product.map(|v| v * 1e-5),
+            irradiation_events: 0,
+            recorded_at: Utc::now(),
+        })
+    }
+
+    /// 转换为数据库插入模型
+    pub fn to_new_radiation_dose(&self) -> NewRadiationDose {
+        NewRadiationDose {
+            id: self.id,
+            study_uid: self.study_uid.clone(),
+            sop_instance_uid: self.sop_instance_uid.clone(),
+            patient_id: Some(self.patient_id.clone()),
+            modality: self.modality.clone(),
+            source: self.source.as_str().to_string(),
+            protocol: self.protocol.clone(),
+            irradiation_events: self.irradiation_events,
+            ctdi_vol: self.ctdi_vol,
+            dlp: self.dlp,
+            dose_area_product: self.kap,
+            entrance_dose: self.entrance_dose,
+        }
+    }
+}
+
+impl From<DbRadiationDose> for DoseRecord {
+    fn from(db_dose: DbRadiationDose) -> Self {
+        Self {
+            id: db_dose.id,
+            patient_id: db_dose.patient_id.unwrap_or_default(),
+            study_uid: db_dose.study_uid,
+            sop_instance_uid: db_dose.sop_instance_uid,
+            modality: db_dose.modality,
+            protocol: db_dose.protocol,
+            source: match db_dose.source.as_str() {
+                "HEADER" => DoseSource::Header,
+                _ => DoseSource::Rdsr,
+            },
+            ctdi_vol: db_dose.ctdi_vol,
+            dlp: db_dose.dlp,
+            entrance_dose: db_dose.entrance_dose,
+            kap: db_dose.dose_area_product,
+            irradiation_events: db_dose.irradiation_events,
+            recorded_at: db_dose.created_at,
+        }
+    }
+}
+
+/// 诊断参考水平（按协议配置）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticReferenceLevel {
+    /// 协议名称
+    pub protocol: String,
+    /// 适用模态，为None时适用于所有模态
+    pub modality: Option<String>,
+    /// CTDIvol阈值（mGy）
+    pub ctdi_vol: Option<f64>,
+    /// DLP阈值（mGy·cm）
+    pub dlp: Option<f64>,
+    /// 入射剂量阈值（Gy）
+    pub entrance_dose: Option<f64>,
+    /// KAP阈值（Gy·m²）
+    pub kap: Option<f64>,
+    /// 超限告警级别
+    pub severity: AlertSeverity,
+}
+
+/// 患者累计剂量
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PatientCumulativeDose {
+    /// 患者ID
+    pub patient_id: String,
+    /// 检查数
+    pub study_count: usize,
+    /// 剂量记录数
+    pub record_count: usize,
+    /// 累计DLP（mGy·cm）
+    pub total_dlp: f64,
+    /// 累计入射剂量（Gy）
+    pub total_entrance_dose: f64,
+    /// 累计KAP（Gy·m²）
+    pub total_kap: f64,
+    /// 最大CTDIvol（mGy）
+    pub max_ctdi_vol: Option<f64>,
+}
+
+/// 剂量记录存储
+#[async_trait]
+pub trait DoseStore: Send + Sync {
+    /// 保存剂量记录，同一对象重复保存时覆盖旧记录
+    async fn save(&self, record: &DoseRecord) -> Result<()>;
+
+    /// 获取检查的剂量记录
+    async fn study_records(&self, study_uid: &str) -> Result<Vec<DoseRecord>>;
+
+    /// 获取剂量记录（按记录时间排序），可按患者过滤
+    async fn records(&self, patient_id: Option<&str>) -> Result<Vec<DoseRecord>>;
+}
+
+/// 基于数据库剂量表的存储
+pub struct DatabaseDoseStore {
+    /// 数据库连接池
+    database: Arc<DatabasePool>,
+}
+
+impl DatabaseDoseStore {
+    /// 创建数据库剂量存储
+    pub fn new(database: Arc<DatabasePool>) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait]
+impl DoseStore for DatabaseDoseStore {
+    async fn save(&self, record: &DoseRecord) -> Result<()> {
+        DatabaseQueries::new(&self.database)
+            .upsert_radiation_dose(&record.to_new_radiation_dose())
+            .await?;
+        Ok(())
+    }
+
+    async fn study_records(&self, study_uid: &str) -> Result<Vec<DoseRecord>> {
+        let doses = DatabaseQueries::new(&self.database)
+            .get_radiation_doses_by_study_uid(study_uid)
+            .await?;
+        Ok(doses.into_iter().map(DoseRecord::from).collect())
+    }
+
+    async fn records(&self, patient_id: Option<&str>) -> Result<Vec<DoseRecord>> {
+        let doses = DatabaseQueries::new(&self.database)
+            .list_radiation_doses(patient_id)
+            .await?;
+        Ok(doses.into_iter().map(DoseRecord::from).collect())
+    }
+}
+
+/// 内存剂量存储（未配置数据库时使用）
+#[derive(Default)]
+pub struct MemoryDoseStore {
+    /// 按检查UID存储的剂量记录
+    records: RwLock<HashMap<String, Vec<DoseRecord>>>,
+}
+
+#[async_trait]
+impl DoseStore for MemoryDoseStore {
+    async fn save(&self, record: &DoseRecord) -> Result<()> {
+        let mut records = self.records.write().await;
+        let study_records = records.entry(record.study_uid.clone()).or_default();
+        study_records.retain(|r| r.sop_instance_uid != record.sop_instance_uid);
+        study_records.push(record.clone());
+        Ok(())
+    }
+
+    async fn study_records(&self, study_uid: &str) -> Result<Vec<DoseRecord>> {
+        let records = self.records.read().await;
+        Ok(records.get(study_uid).cloned().unwrap_or_default())
+    }
+
+    async fn records(&self, patient_id: Option<&str>) -> Result<Vec<DoseRecord>> {
+        let records = self.records.read().await;
+        let mut rows: Vec<DoseRecord> = records
+            .values()
+            .flatten()
+            .filter(|r| patient_id.is_none_or(|id| r.patient_id == id))
+            .cloned()
+            .collect();
+        rows.sort_by_key(|r| r.recorded_at);
+        Ok(rows)
+    }
+}
+
+/// 辐射剂量登记
+pub struct DoseRegistry {
+    /// 剂量记录存储
+    store: Arc<dyn DoseStore>,
+    /// 按协议名称（小写）存储的诊断参考水平
+    reference_levels: Arc<RwLock<HashMap<String, DiagnosticReferenceLevel>>>,
+    /// 告警管理器
+    alert_manager: Arc<AlertManager>,
+    /// 超限告警通知配置
+    notification_config: NotificationConfig,
+}
+
+impl DoseRegistry {
+    /// 创建新的剂量登记
+    pub fn new(alert_manager: Arc<AlertManager>, notification_config: NotificationConfig) -> Self {
+        Self {
+            store: Arc::new(MemoryDoseStore::default()),
+            reference_levels: Arc::new(RwLock::new(HashMap::new())),
+            alert_manager,
+            notification_config,
+        }
+    }
+
+    /// 使用数据库剂量表存储剂量记录
+    pub fn with_database(self, database: Arc<DatabasePool>) -> Self {
+        self.with_store(Arc::new(DatabaseDoseStore::new(database)))
+    }
+
+    /// 设置剂量记录存储
+    pub fn with_store(mut self, store: Arc<dyn DoseStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// 设置协议的诊断参考水平
+    pub async fn set_reference_level(&self, level: DiagnosticReferenceLevel) {
+        let mut levels = self.reference_levels.write().await;
+        info!(
+            "Set diagnostic reference level for protocol: {}",
+            level.protocol
+        );
+        levels.insert(level.protocol.trim().to_lowercase(), level);
+    }
+
+    /// 删除协议的诊断参考水平
+    pub async fn remove_reference_level(&self, protocol: &str) -> bool {
+        let mut levels = self.reference_levels.write().await;
+        levels.remove(&protocol.trim().to_lowercase()).is_some()
+    }
+
+    /// 获取所有诊断参考水平
+    pub async fn get_reference_levels(&self) -> Vec<DiagnosticReferenceLevel> {
+        let levels = self.reference_levels.read().await;
+        levels.values().cloned().collect()
+    }
+
+    /// 登记RDSR剂量汇总
+    pub async fn record_rdsr(
+        &self,
+        summary: &RadiationDoseSummary,
+        modality: Option<String>,
+    ) -> Result<Vec<AlertEvent>> {
+        let record = DoseRecord::from_rdsr(summary, modality)?;
+        self.record(record).await
+    }
+
+    /// 登记图像头中的剂量字段，对象不含剂量字段时忽略
+    pub async fn record_header(&self, parsed: &ParsedDicomObject) -> Result<Vec<AlertEvent>> {
+        match DoseRecord::from_header(parsed) {
+            Some(record) => self.record(record).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// 登记剂量记录并检查诊断参考水平，返回触发的告警
+    pub async fn record(&self, record: DoseRecord) -> Result<Vec<AlertEvent>> {
+        let alerts = self.check_reference_levels(&record).await;
+
+        self.store.save(&record).await?;
+        debug!(
+            "Recorded dose for study {} from {}",
+            record.study_uid, record.sop_instance_uid
+        );
+
+        for alert in &alerts {
+            self.alert_manager
+                .raise_alert(alert.clone(), &self.notification_config)
+                .await?;
+        }
+
+        Ok(alerts)
+    }
+
+    /// 检查剂量记录是否超过诊断参考水平
+    async fn check_reference_levels(&self, record: &DoseRecord) -> Vec<AlertEvent> {
+        let protocol = match &record.protocol {
+            Some(protocol) => protocol.trim().to_lowercase(),
+            None => return Vec::new(),
+        };
+
+        let levels = self.reference_levels.read().await;
+        let level = match levels.get(&protocol) {
+            Some(level) => level,
+            None => return Vec::new(),
+        };
+
+        if let (Some(expected), Some(actual)) = (&level.modality, &record.modality) {
+            if !expected.eq_ignore_ascii_case(actual) {
+                return Vec::new();
+            }
+        }
+
+        let checks = [
+            ("CTDIvol", "mGy", record.ctdi_vol, level.ctdi_vol),
+            ("DLP", "mGy·cm", record.dlp, level.dlp),
+            (
+                "Entrance Dose",
+                "Gy",
+                record.entrance_dose,
+                level.entrance_dose,
+            ),
+            ("KAP", "Gy·m²", record.kap, level.kap),
+        ];
+
+        let mut alerts = Vec::new();
+        for (quantity, unit, value, threshold) in checks {
+            if let (Some(value), Some(threshold)) = (value, threshold) {
+                if value > threshold {
+                    warn!(
+                        "Dose exceeds DRL: study {} protocol {} {} {} > {}",
+                        record.study_uid, level.protocol, quantity, value, threshold
+                    );
+                    alerts.push(AlertEvent {
+                        id: Uuid::new_v4().to_string(),
+                        rule_name: format!("dose_drl:{}:{}", level.protocol, quantity),
+                        severity: level.severity.clone(),
+                        current_value: value,
+                        threshold,
+                        message: format!(
+                            "Patient {} study {} ({}) {} {} {} exceeds DRL {} {}",
+                            record.patient_id,
+                            record.study_uid,
+                            level.protocol,
+                            quantity,
+                            value,
+                            unit,
+                            threshold,
+                            unit
+                        ),
+                        timestamp: Utc::now(),
+                        resolved: false,
+                    });
+                }
+            }
+        }
+
+        alerts
+    }
+
+    /// 获取检查的剂量记录
+    pub async fn get_study_doses(&self, study_uid: &str) -> Result<Vec<DoseRecord>> {
+        self.store.study_records(study_uid).await
+    }
+
+    /// 计算患者累计剂量
+    pub async fn get_patient_cumulative_dose(
+        &self,
+        patient_id: &str,
+    ) -> Result<PatientCumulativeDose> {
+        let records = self.store.records(Some(patient_id)).await?;
+        let mut cumulative = PatientCumulativeDose {
+            patient_id: patient_id.to_string(),
+            ..Default::default()
+        };
+
+        let mut studies = HashSet::new();
+        for record in &records {
+            studies.insert(record.study_uid.as_str());
+            cumulative.record_count += 1;
+            cumulative.total_dlp += record.dlp.unwrap_or(0.0);
+            cumulative.total_entrance_dose += record.entrance_dose.unwrap_or(0.0);
+            cumulative.total_kap += record.kap.unwrap_or(0.0);
+            if let Some(ctdi_vol) = record.ctdi_vol {
+                cumulative.max_ctdi_vol = Some(
+                    cumulative
+                        .max_ctdi_vol
+                        .map_or(ctdi_vol, |max| max.max(ctdi_vol)),
+                );
+            }
+        }
+        cumulative.study_count = studies.len();
+
+        Ok(cumulative)
+    }
+
+    /// 导出剂量数据为CSV，可按患者过滤
+    pub async fn export_csv(&self, patient_id: Option<&str>) -> Result<String> {
+        let rows = self.store.records(patient_id).await?;
+
+        let mut csv = String::from(
+            "patient_id,study_uid,sop_instance_uid,modality,protocol,source,ctdi_vol_mgy,dlp_mgy_cm,entrance_dose_gy,kap_gy_m2,recorded_at\n",
+        );
+        for record in &rows {
+            let fields = [
+                escape_csv(&record.patient_id),
+                escape_csv(&record.study_uid),
+                escape_csv(&record.sop_instance_uid),
+                escape_csv(record.modality.as_deref().unwrap_or("")),
+                escape_csv(record.protocol.as_deref().unwrap_or("")),
+                record.source.as_str().to_string(),
+                format_optional(record.ctdi_vol),
+                format_optional(record.dlp),
+                format_optional(record.entrance_dose),
+                format_optional(record.kap),
+                record.recorded_at.to_rfc3339(),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+
+        Ok(csv)
+    }
+
+    /// 从入库实例登记剂量：辐射剂量SR按报告内容登记，其他对象按图像头剂量字段登记
+    pub async fn record_instance(&self, instance: &IngestedInstance) -> Result<Vec<AlertEvent>> {
+        let metadata = &instance.metadata;
+        let is_sr = metadata
+            .sop_class_uid
+            .as_deref()
+            .is_some_and(SrParser::is_structured_report);
+        if !is_sr {
+            return self.record_header(metadata).await;
+        }
+
+        let data = tokio::fs::read(&instance.file_path).await?;
+        let report = SrParser::parse_bytes(&data)?;
+        match RadiationDoseSummary::from_report(&report) {
+            Some(summary) => self.record_rdsr(&summary, metadata.modality.clone()).await,
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl IngestListener for DoseRegistry {
+    async fn on_instance_stored(
+        &self,
+        instance: &IngestedInstance,
+        _source: &IngestSource,
+    ) -> pacs_core::Result<()> {
+        self.record_instance(instance)
+            .await
+            .map(|_| ())
+            .map_err(|e| PacsError::Internal(format!("Dose registration failed: {}", e)))
+    }
+}
+
+/// CSV字段转义
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 格式化可选数值
+fn format_optional(value: Option<f64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerting::{DefaultNotificationSender, MetricProvider};
+    use std::path::PathBuf;
+
+    struct NoMetrics;
+
+    #[async_trait]
+    impl MetricProvider for NoMetrics {
+        async fn get_metric_value(&self, metric_name: &str) -> Result<f64> {
+            Err(anyhow!("Unknown metric: {}", metric_name))
+        }
+
+        async fn get_all_metrics(&self) -> Result<HashMap<String, f64>> {
+            Ok(HashMap::new())
+        }
+    }
+
+    fn registry() -> (DoseRegistry, Arc<AlertManager>) {
+        let alert_manager = Arc::new(AlertManager::new(
+            Arc::new(DefaultNotificationSender),
+            Arc::new(NoMetrics),
+        ));
+        (
+            DoseRegistry::new(alert_manager.clone(), NotificationConfig::default()),
+            alert_manager,
+        )
+    }
+
+    fn ct_instance(sop_instance_uid: &str, study_uid: &str, ctdi_vol: f64) -> IngestedInstance {
+        IngestedInstance {
+            sop_instance_uid: sop_instance_uid.to_string(),
+            file_path: PathBuf::from(format!("/nonexistent/{}.dcm", sop_instance_uid)),
+            file_size: 0,
+            original_patient_id: None,
+            metadata: ParsedDicomObject {
+                patient_id: Some("PAT001".to_string()),
+                study_instance_uid: Some(study_uid.to_string()),
+                sop_instance_uid: Some(sop_instance_uid.to_string()),
+                sop_class_uid: Some("1.2.840.10008.5.1.4.1.1.2".to_string()),
+                modality: Some("CT".to_string()),
+                protocol_name: Some("Head Routine".to_string()),
+                ctdi_vol: Some(ctdi_vol),
+                ..ParsedDicomObject::new()
+            },
+            references: vec![],
+            document: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingest_records_header_dose_and_raises_drl_alert() {
+        let (registry, alert_manager) = registry();
+        registry
+            .set_reference_level(DiagnosticReferenceLevel {
+                protocol: "Head Routine".to_string(),
+                modality: Some("CT".to_string()),
+                ctdi_vol: Some(60.0),
+                dlp: None,
+                entrance_dose: None,
+                kap: None,
+                severity: AlertSeverity::Warning,
+            })
+            .await;
+        let source = IngestSource::CStore {
+            calling_ae_title: Some("CT01".to_string()),
+        };
+
+        registry
+            .on_instance_stored(&ct_instance("1.2.3.1.1", "1.2.3.1", 45.0), &source)
+            .await
+            .unwrap();
+        registry
+            .on_instance_stored(&ct_instance("1.2.3.1.2", "1.2.3.1", 75.0), &source)
+            .await
+            .unwrap();
+        registry
+            .on_instance_stored(&ct_instance("1.2.3.2.1", "1.2.3.2", 30.0), &source)
+            .await
+            .unwrap();
+        // 同一对象重复入库时覆盖旧记录
+        registry
+            .on_instance_stored(&ct_instance("1.2.3.2.1", "1.2.3.2", 32.0), &source)
+            .await
+            .unwrap();
+
+        let study_doses = registry.get_study_doses("1.2.3.1").await.unwrap();
+        assert_eq!(study_doses.len(), 2);
+        assert!(study_doses.iter().all(|r| r.source == DoseSource::Header));
+
+        let history = alert_manager.get_alert_history(None).await;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].rule_name, "dose_drl:Head Routine:CTDIvol");
+
+        let cumulative = registry
+            .get_patient_cumulative_dose("PAT001")
+            .await
+            .unwrap();
+        assert_eq!(cumulative.study_count, 2);
+        assert_eq!(cumulative.record_count, 3);
+        assert_eq!(cumulative.max_ctdi_vol, Some(75.0));
+
+        let csv = registry.export_csv(Some("PAT001")).await.unwrap();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.contains(",32,"));
+        assert!(!csv.contains(",30,"));
+    }
+
+    #[tokio::test]
+    async fn test_objects_without_dose_are_ignored() {
+        let (registry, _) = registry();
+        let mut instance = ct_instance("1.2.3.3.1", "1.2.3.3", 0.0);
+        instance.metadata.ctdi_vol = None;
+
+        let alerts = registry.record_instance(&instance).await.unwrap();
+        assert!(alerts.is_empty());
+        assert_eq!(registry.export_csv(None).await.unwrap().lines().count(), 1);
+    }
+}
@@ -8,6 +8,8 @@ pub mod alerting;
 pub mod logging;
 pub mod performance;
 pub mod backup;
+pub mod dose;
+pub mod lifecycle;
 
 use std::sync::Arc;
 use anyhow::Result;
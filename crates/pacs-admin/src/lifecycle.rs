@@ -0,0 +1,79 @@
+//! 检查级生命周期元数据
+//!
+//! 从数据库汇总每个检查的检查日期、患者出生日期、模态、检查部位、自定义标签和实例文件，
+//! 供存储层按检查评估生命周期策略（保留期限、合法保留和删除预告）。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_trait::async_trait;
+use tracing::debug;
+
+use pacs_database::{DatabasePool, DatabaseQueries};
+use pacs_storage::{StorageConfig, StorageType, StudyMetadata, StudyMetadataSource};
+
+/// 基于数据库的检查元数据来源
+pub struct DatabaseStudyMetadataSource {
+    /// 数据库连接池
+    database: Arc<DatabasePool>,
+    /// 实例文件所在的本地存储（路径去掉其根目录后即为存储键）
+    storage: StorageConfig,
+}
+
+impl DatabaseStudyMetadataSource {
+    /// 创建检查元数据来源
+    pub fn new(database: Arc<DatabasePool>) -> Self {
+        Self {
+            database,
+            storage: StorageConfig {
+                storage_type: StorageType::Local,
+                local_path: None,
+                object_store_config: None,
+            },
+        }
+    }
+
+    /// 设置存储根目录
+    pub fn with_storage_root(mut self, storage_root: &str) -> Self {
+        self.storage.local_path = Some(storage_root.to_string());
+        self
+    }
+
+    /// 将实例记录中的文件路径转换为存储键
+    fn storage_key(&self, file_path: &str) -> String {
+        self.storage.object_key(file_path)
+    }
+}
+
+#[async_trait]
+impl StudyMetadataSource for DatabaseStudyMetadataSource {
+    async fn studies(&self) -> pacs_core::Result<Vec<StudyMetadata>> {
+        let queries = DatabaseQueries::new(&self.database);
+
+        let mut tags: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for tag in queries.list_study_tags(None).await? {
+            tags.entry(tag.study_uid).or_default().insert(tag.name, tag.value);
+        }
+
+        let studies: Vec<StudyMetadata> = queries
+            .list_study_lifecycle_info()
+            .await?
+            .into_iter()
+            .map(|info| StudyMetadata {
+                tags: tags.remove(&info.study_uid).unwrap_or_default(),
+                file_paths: info
+                    .file_paths
+                    .iter()
+                    .map(|path| self.storage_key(path))
+                    .collect(),
+                study_uid: info.study_uid,
+                study_date: Some(info.study_date),
+                patient_birth_date: info.patient_birth_date,
+                modalities: info.modalities,
+                body_parts: info.body_parts,
+            })
+            .collect();
+
+        debug!("Loaded lifecycle metadata for {} studies", studies.len());
+        Ok(studies)
+    }
+}
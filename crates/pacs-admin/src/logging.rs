@@ -14,7 +14,7 @@ use regex::Regex;
 
 /// 日志级别映射
 fn map_tracing_level(level: &Level) -> LogLevel {
-    match level {
+    match *level {
         Level::ERROR => LogLevel::Error,
         Level::WARN => LogLevel::Warning,
         Level::INFO => LogLevel::Info,
@@ -24,7 +24,7 @@ fn map_tracing_level(level: &Level) -> LogLevel {
 }
 
 /// 日志级别
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum LogLevel {
     Trace,
     Debug,
@@ -138,7 +138,7 @@ pub struct LogAggregator {
     /// 日志索引（按模块）
     index_by_module: Arc<RwLock<HashMap<String, Vec<usize>>>>,
     /// 日志索引（按时间）
-    index_by_time: Arc<RwLock<Vec<(DateTime<Utc>, usize)>>>>,
+    index_by_time: Arc<RwLock<Vec<(DateTime<Utc>, usize)>>>,
     /// 配置
     config: LogConfig,
     /// 正则表达式缓存
@@ -346,10 +346,13 @@ impl LogAggregator {
         }
 
         // 如果没有任何过滤条件，返回所有索引
-        candidates.unwrap_or_else(|| {
-            let cache = self.log_cache.read().await;
-            (0..cache.len()).collect()
-        })
+        match candidates {
+            Some(candidates) => candidates,
+            None => {
+                let cache = self.log_cache.read().await;
+                (0..cache.len()).collect()
+            }
+        }
     }
 
     /// 检查条目是否匹配过滤器
@@ -185,7 +185,7 @@ pub struct AlertConfig {
 }
 
 /// 通知配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct NotificationConfig {
     /// 邮件通知
     pub email: Option<EmailNotificationConfig>,
@@ -53,3 +53,44 @@ pub enum PacsError {
 
 /// PACS系统统一结果类型
 pub type Result<T> = std::result::Result<T, PacsError>;
+
+#[cfg(feature = "web")]
+impl PacsError {
+    /// 对应的HTTP状态码
+    pub fn status_code(&self) -> axum::http::StatusCode {
+        use axum::http::StatusCode;
+
+        match self {
+            PacsError::NotFound(_) => StatusCode::NOT_FOUND,
+            PacsError::Validation(_)
+            | PacsError::Dicom(_)
+            | PacsError::DicomParseError(_)
+            | PacsError::Serialization(_) => StatusCode::BAD_REQUEST,
+            PacsError::Permission(_) => StatusCode::FORBIDDEN,
+            PacsError::InvalidStateTransition { .. } => StatusCode::CONFLICT,
+            PacsError::Config(_)
+            | PacsError::Database(_)
+            | PacsError::Storage(_)
+            | PacsError::Network(_)
+            | PacsError::Internal(_)
+            | PacsError::Io(_)
+            | PacsError::Workflow(_)
+            | PacsError::RoutingError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Web接口错误响应
+#[cfg(feature = "web")]
+impl axum::response::IntoResponse for PacsError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status_code();
+        let body = axum::Json(serde_json::json!({
+            "error": true,
+            "message": self.to_string(),
+            "status": status.as_u16()
+        }));
+
+        (status, body).into_response()
+    }
+}
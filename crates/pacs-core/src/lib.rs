@@ -4,7 +4,10 @@
 
 pub mod error;
 pub mod models;
+pub mod recall;
+pub mod uid;
 pub mod utils;
 
 pub use error::{PacsError, Result};
 pub use models::*;
+pub use recall::{ObjectAvailability, ObjectRecall};
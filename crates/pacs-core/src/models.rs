@@ -60,6 +60,8 @@ pub struct Series {
     pub modality: String,
     pub series_number: i32,
     pub description: Option<String>,
+    #[serde(default)]
+    pub body_part_examined: Option<String>, // DICOM Body Part Examined
     pub images_count: i32,
     pub created_at: DateTime<Utc>,
 }
@@ -0,0 +1,37 @@
+//! 对象召回接口
+//!
+//! 影像文件可能已被生命周期管理迁移到近线或归档层。读取存储文件的调用方
+//! （WADO检索、DICOM转发等）在打开文件前通过 [`ObjectRecall`] 确认对象可读：
+//! 快速层会被同步取回，慢速层则在后台召回，调用方向客户端报告“召回中”而不是报错。
+
+use crate::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 对象可用性
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ObjectAvailability {
+    /// 对象在在线存储中，可以直接读取
+    Online,
+    /// 对象正在从慢速层召回
+    RecallInProgress {
+        /// 召回开始时间
+        started_at: DateTime<Utc>,
+        /// 建议调用方重试的间隔（秒）
+        retry_after_secs: u64,
+    },
+    /// 任何存储层中都没有该对象
+    NotFound,
+}
+
+/// 对象召回
+#[async_trait]
+pub trait ObjectRecall: Send + Sync {
+    /// 确保本地路径上的对象可读
+    ///
+    /// 对象在线时立即返回；位于快速层时取回到在线存储后返回；
+    /// 位于慢速层时启动（或复用已有的）后台召回并返回 [`ObjectAvailability::RecallInProgress`]
+    async fn ensure_available(&self, path: &Path) -> Result<ObjectAvailability>;
+}
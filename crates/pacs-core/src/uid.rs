@@ -0,0 +1,203 @@
+//! DICOM UID生成与校验
+//!
+//! 按PS3.5第9章规则校验UID（仅数字和点、组件无前导零、不超过64字符），
+//! 在可配置的机构根下生成UID，支持以UUID表示的 `2.25.<十进制UUID>` 方案，
+//! 并为去标识化、检查拆分/合并等操作提供确定性的UID派生。
+
+use crate::error::{PacsError, Result};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// UID最大长度
+pub const MAX_UID_LENGTH: usize = 64;
+
+/// 默认机构根标识符
+pub const DEFAULT_ORG_ROOT: &str = "1.2.826.0.1.3680043.9.7382";
+
+/// UUID派生UID的根（ISO/IEC 9834-8）
+pub const UUID_ROOT: &str = "2.25";
+
+/// 机构根后至少保留的随机数字位数
+const MIN_SUFFIX_DIGITS: usize = 20;
+
+/// 确定性派生使用的UUID v5命名空间
+const DERIVATION_NAMESPACE: Uuid = Uuid::from_u128(0x6f1c_2d4e_8a3b_5c7d_9e0f_1a2b_3c4d_5e6f);
+
+/// 进程级默认UID根，未配置时使用 [`DEFAULT_ORG_ROOT`]
+static DEFAULT_ROOT: RwLock<Option<String>> = RwLock::new(None);
+
+/// 按PS3.5规则校验UID，返回具体的错误原因
+pub fn validate_uid(uid: &str) -> Result<()> {
+    if uid.is_empty() {
+        return Err(PacsError::Validation("UID is empty".to_string()));
+    }
+    if uid.len() > MAX_UID_LENGTH {
+        return Err(PacsError::Validation(format!(
+            "UID exceeds {} characters: {}",
+            MAX_UID_LENGTH, uid
+        )));
+    }
+
+    for component in uid.split('.') {
+        if component.is_empty() {
+            return Err(PacsError::Validation(format!(
+                "UID has an empty component: {}",
+                uid
+            )));
+        }
+        if !component.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(PacsError::Validation(format!(
+                "UID contains non-digit characters: {}",
+                uid
+            )));
+        }
+        if component.len() > 1 && component.starts_with('0') {
+            return Err(PacsError::Validation(format!(
+                "UID component has a leading zero: {}",
+                uid
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// 判断UID是否符合PS3.5规则
+pub fn is_valid_uid(uid: &str) -> bool {
+    validate_uid(uid).is_ok()
+}
+
+/// 将UUID转换为 `2.25.<十进制>` 形式的UID
+pub fn uuid_to_uid(uuid: &Uuid) -> String {
+    format!("{}.{}", UUID_ROOT, uuid.as_u128())
+}
+
+/// 设置进程级默认UID根
+pub fn set_default_root(root: &str) -> Result<()> {
+    let generator = UidGenerator::new(root)?;
+    *DEFAULT_ROOT.write().unwrap_or_else(|e| e.into_inner()) = Some(generator.root);
+    Ok(())
+}
+
+/// 获取进程级默认UID生成器
+pub fn default_generator() -> UidGenerator {
+    let root = DEFAULT_ROOT
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| DEFAULT_ORG_ROOT.to_string());
+    UidGenerator { root }
+}
+
+/// UID生成器
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UidGenerator {
+    /// UID根
+    root: String,
+}
+
+impl UidGenerator {
+    /// 在指定机构根下创建生成器
+    pub fn new(root: &str) -> Result<Self> {
+        validate_uid(root)?;
+        if root.len() + 1 + MIN_SUFFIX_DIGITS > MAX_UID_LENGTH {
+            return Err(PacsError::Validation(format!(
+                "UID root is too long to leave {} digits of uniqueness: {}",
+                MIN_SUFFIX_DIGITS, root
+            )));
+        }
+        Ok(Self {
+            root: root.to_string(),
+        })
+    }
+
+    /// 使用 `2.25` UUID方案的生成器
+    pub fn uuid_based() -> Self {
+        Self {
+            root: UUID_ROOT.to_string(),
+        }
+    }
+
+    /// 获取UID根
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// 生成新的随机UID
+    pub fn generate(&self) -> String {
+        self.with_suffix(Uuid::new_v4().as_u128())
+    }
+
+    /// 由源UID确定性派生新UID
+    ///
+    /// 相同的命名空间与源UID总是得到相同结果，命名空间用于区分不同的
+    /// 去标识化项目或拆分/合并操作，避免不同用途的派生结果相互碰撞
+    pub fn derive(&self, namespace: &str, source_uid: &str) -> String {
+        let name = format!("{}\u{0}{}\u{0}{}", self.root, namespace, source_uid);
+        let uuid = Uuid::new_v5(&DERIVATION_NAMESPACE, name.as_bytes());
+        self.with_suffix(uuid.as_u128())
+    }
+
+    /// 将数值后缀拼接到根上，超出长度时截取低位数字
+    fn with_suffix(&self, value: u128) -> String {
+        let budget = MAX_UID_LENGTH - self.root.len() - 1;
+        let value = match 10u128.checked_pow(budget as u32) {
+            Some(modulus) => value % modulus,
+            None => value,
+        };
+        format!("{}.{}", self.root, value)
+    }
+}
+
+impl Default for UidGenerator {
+    fn default() -> Self {
+        Self {
+            root: DEFAULT_ORG_ROOT.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_validate_uid_rules() {
+        assert!(is_valid_uid("1.2.840.10008.5.1.4.1.1.4"));
+        assert!(is_valid_uid("2.25.0"));
+        assert!(!is_valid_uid("1.2.03"));
+        assert!(!is_valid_uid("1..2"));
+        assert!(!is_valid_uid(".1.2"));
+        assert!(!is_valid_uid("1.2."));
+        assert!(!is_valid_uid("1.2.a"));
+        assert!(!is_valid_uid(&format!("1.{}", "2".repeat(63))));
+        assert!(UidGenerator::new(&format!("1.{}", "2".repeat(50))).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_generated_uids_are_valid(root in "[1-9][0-9]{0,4}(\\.(0|[1-9][0-9]{0,4})){0,6}") {
+            let generator = UidGenerator::new(&root).unwrap();
+            let uid = generator.generate();
+            prop_assert!(is_valid_uid(&uid), "{}", uid);
+            let prefix = format!("{}.", root);
+            prop_assert!(uid.starts_with(&prefix));
+        }
+
+        #[test]
+        fn prop_uuid_uids_are_valid(value in any::<u128>()) {
+            let uid = uuid_to_uid(&Uuid::from_u128(value));
+            prop_assert!(is_valid_uid(&uid), "{}", uid);
+        }
+
+        #[test]
+        fn prop_derivation_is_deterministic(namespace in "[a-z]{1,12}", source in "1\\.2(\\.[1-9][0-9]{0,8}){1,8}") {
+            let generator = UidGenerator::default();
+            let derived = generator.derive(&namespace, &source);
+            prop_assert!(is_valid_uid(&derived), "{}", derived);
+            prop_assert_eq!(&derived, &generator.derive(&namespace, &source));
+            prop_assert_ne!(&derived, &generator.derive(&format!("{}x", namespace), &source));
+        }
+    }
+}
@@ -1,23 +1,15 @@
 //! 通用工具函数
 
-use chrono::{DateTime, Utc};
-use uuid::Uuid;
+use crate::uid;
 
-/// 生成唯一的DICOM标识符
+/// 在默认机构根下生成唯一的DICOM标识符
 pub fn generate_dicom_uid() -> String {
-    format!(
-        "{}.{}.{}.{}",
-        "1.2.826.0.1.3680043.9.7382", // 企业根标识符
-        Uuid::new_v4().simple(),
-        Utc::now().timestamp(),
-        std::process::id()
-    )
+    uid::default_generator().generate()
 }
 
-/// 验证DICOM UID格式
+/// 验证DICOM UID格式（PS3.5第9章）
 pub fn is_valid_dicom_uid(uid: &str) -> bool {
-    // 简单的DICOM UID验证逻辑
-    !uid.is_empty() && uid.len() <= 64 && uid.chars().all(|c| c.is_numeric() || c == '.')
+    uid::is_valid_uid(uid)
 }
 
 #[cfg(test)]
@@ -5,8 +5,10 @@
 pub mod connection;
 pub mod models;
 pub mod queries;
+pub mod snapshot;
 
 // 重新导出主要类型
 pub use connection::DatabasePool;
 pub use models::*;
 pub use queries::DatabaseQueries;
+pub use snapshot::{DatabaseSnapshot, StagedRestore, TableDump, SNAPSHOT_TABLES};
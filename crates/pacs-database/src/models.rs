@@ -89,6 +89,7 @@ pub struct DbSeries {
     pub modality: String,
     pub series_number: i32,
     pub description: Option<String>,
+    pub body_part_examined: Option<String>,
     pub images_count: i32,
     pub created_at: DateTime<Utc>,
 }
@@ -102,6 +103,7 @@ impl From<DbSeries> for Series {
             modality: db_series.modality,
             series_number: db_series.series_number,
             description: db_series.description,
+            body_part_examined: db_series.body_part_examined,
             images_count: db_series.images_count,
             created_at: db_series.created_at,
         }
@@ -136,6 +138,179 @@ impl From<DbInstance> for Instance {
     }
 }
 
+/// 数据库辐射剂量表
+#[derive(Debug, Clone, FromRow)]
+pub struct DbRadiationDose {
+    pub id: Uuid,
+    pub study_uid: String,
+    pub sop_instance_uid: String,
+    pub patient_id: Option<String>,
+    pub modality: Option<String>,
+    pub source: String, // RDSR 或 HEADER
+    pub protocol: Option<String>,
+    pub irradiation_events: i32,
+    pub ctdi_vol: Option<f64>,          // mGy
+    pub dlp: Option<f64>,               // mGy·cm
+    pub dose_area_product: Option<f64>, // Gy·m²
+    pub entrance_dose: Option<f64>,     // Gy
+    pub created_at: DateTime<Utc>,
+}
+
+/// 数据库实例关系表（KOS/显示状态对源图像的引用）
+#[derive(Debug, Clone, FromRow)]
+pub struct DbInstanceRelationship {
+    pub id: Uuid,
+    pub relationship_type: String, // KOS 或 PR
+    pub source_sop_instance_uid: String,
+    pub study_uid: String,
+    pub referenced_series_uid: String,
+    pub referenced_sop_class_uid: Option<String>,
+    pub referenced_sop_instance_uid: String,
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 数据库转发队列表
+#[derive(Debug, Clone, FromRow)]
+pub struct DbForwardTask {
+    pub id: Uuid,
+    pub destination: String,
+    pub rule_name: String,
+    pub study_uid: String,
+    pub sop_instance_uid: String,
+    pub file_path: String,
+    pub status: String, // PENDING, IN_PROGRESS, COMPLETED, FAILED
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 转发队列按目的地统计
+#[derive(Debug, Clone, FromRow)]
+pub struct DbForwardQueueStats {
+    pub destination: String,
+    pub pending: i64,
+    pub in_progress: i64,
+    pub completed: i64,
+    pub failed: i64,
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+}
+
+/// 数据库检查稳定性跟踪表
+#[derive(Debug, Clone, FromRow)]
+pub struct DbStudyStability {
+    pub study_uid: String,
+    pub patient_id: Option<String>,
+    pub accession_number: Option<String>,
+    pub modalities: Vec<String>,
+    pub instance_count: i32,
+    pub first_received_at: Option<DateTime<Utc>>,
+    pub last_received_at: Option<DateTime<Utc>>,
+    pub mpps_completed_at: Option<DateTime<Utc>>,
+    pub stable_at: Option<DateTime<Utc>>,
+    pub completion_trigger: Option<String>, // QUIET_PERIOD, MPPS
+    pub notified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 数据库检查操作审计表
+#[derive(Debug, Clone, FromRow)]
+pub struct DbStudyOperation {
+    pub id: Uuid,
+    pub operation_type: String, // MOVE_SERIES, MOVE_STUDY, SPLIT_STUDY, MERGE_STUDIES
+    pub parameters: String,     // JSON
+    pub performed_by: String,
+    pub reason: Option<String>,
+    pub status: String, // IN_PROGRESS, COMPLETED, FAILED, REVERTED
+    pub rejection_note_uid: Option<String>,
+    pub rejection_note_path: Option<String>,
+    pub error: Option<String>,
+    pub reverted_by: Option<String>,
+    pub reverted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 数据库检查操作实例明细表
+#[derive(Debug, Clone, FromRow)]
+pub struct DbStudyOperationItem {
+    pub id: Uuid,
+    pub operation_id: Uuid,
+    pub sop_class_uid: String,
+    pub old_study_uid: String,
+    pub old_series_uid: String,
+    pub old_sop_instance_uid: String,
+    pub new_study_uid: String,
+    pub new_series_uid: String,
+    pub new_sop_instance_uid: String,
+    pub old_patient_id: Option<String>,
+    pub new_patient_id: Option<String>,
+    pub original_path: String,
+    pub held_path: String,
+    pub new_path: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 数据库被拒绝实例表（IOCM）
+#[derive(Debug, Clone, FromRow)]
+pub struct DbRejectedInstance {
+    pub sop_instance_uid: String,
+    pub study_uid: String,
+    pub series_uid: String,
+    pub sop_class_uid: Option<String>,
+    pub reason_code: String, // 113001, 113037, 113038, 113039
+    pub rejection_note_uid: String,
+    pub rejected_by: Option<String>,
+    pub file_path: Option<String>,
+    pub rejected_at: DateTime<Utc>,
+    pub purge_after: DateTime<Utc>,
+    pub purged_at: Option<DateTime<Utc>>,
+}
+
+/// 检查级生命周期评估所需的元数据（聚合自检查、患者、系列与实例表）
+#[derive(Debug, Clone, FromRow)]
+pub struct DbStudyLifecycleInfo {
+    pub study_uid: String,
+    pub study_date: NaiveDate,
+    pub patient_birth_date: Option<NaiveDate>,
+    pub modalities: Vec<String>,
+    pub body_parts: Vec<String>,
+    pub file_paths: Vec<String>,
+}
+
+/// 数据库检查自定义标签表
+#[derive(Debug, Clone, FromRow)]
+pub struct DbStudyTag {
+    pub study_uid: String,
+    pub name: String,
+    pub value: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 实例检索结果（聚合自实例、系列、检查与患者表）
+#[derive(Debug, Clone, FromRow)]
+pub struct DbInstanceMatch {
+    pub patient_id: String,
+    pub patient_name: String,
+    pub patient_birth_date: Option<NaiveDate>,
+    pub patient_sex: Option<String>,
+    pub study_uid: String,
+    pub study_date: NaiveDate,
+    pub study_time: Option<NaiveTime>,
+    pub accession_number: String,
+    pub study_description: Option<String>,
+    pub series_uid: String,
+    pub series_number: i32,
+    pub modality: String,
+    pub series_description: Option<String>,
+    pub sop_instance_uid: String,
+    pub instance_number: i32,
+    pub transfer_syntax_uid: String,
+}
+
 // 插入模型 - 用于创建新记录
 
 /// 新患者插入模型
@@ -199,6 +374,7 @@ pub struct NewSeries {
     pub modality: String,
     pub series_number: i32,
     pub description: Option<String>,
+    pub body_part_examined: Option<String>,
     pub images_count: i32,
 }
 
@@ -211,6 +387,7 @@ impl NewSeries {
             modality: series.modality.clone(),
             series_number: series.series_number,
             description: series.description.clone(),
+            body_part_examined: series.body_part_examined.clone(),
             images_count: series.images_count,
         }
     }
@@ -241,3 +418,123 @@ impl NewInstance {
         }
     }
 }
+
+/// 新辐射剂量记录插入模型
+#[derive(Debug, Clone)]
+pub struct NewRadiationDose {
+    pub id: Uuid,
+    pub study_uid: String,
+    pub sop_instance_uid: String,
+    pub patient_id: Option<String>,
+    pub modality: Option<String>,
+    pub source: String,
+    pub protocol: Option<String>,
+    pub irradiation_events: i32,
+    pub ctdi_vol: Option<f64>,
+    pub dlp: Option<f64>,
+    pub dose_area_product: Option<f64>,
+    pub entrance_dose: Option<f64>,
+}
+
+/// 新实例关系插入模型
+#[derive(Debug, Clone)]
+pub struct NewInstanceRelationship {
+    pub id: Uuid,
+    pub relationship_type: String,
+    pub source_sop_instance_uid: String,
+    pub study_uid: String,
+    pub referenced_series_uid: String,
+    pub referenced_sop_class_uid: Option<String>,
+    pub referenced_sop_instance_uid: String,
+    pub label: Option<String>,
+}
+
+/// 新转发任务插入模型
+#[derive(Debug, Clone)]
+pub struct NewForwardTask {
+    pub id: Uuid,
+    pub destination: String,
+    pub rule_name: String,
+    pub study_uid: String,
+    pub sop_instance_uid: String,
+    pub file_path: String,
+    pub next_attempt_at: DateTime<Utc>,
+}
+
+/// 检查实例到达记录
+#[derive(Debug)]
+pub struct NewStudyArrival {
+    pub study_uid: String,
+    pub patient_id: Option<String>,
+    pub accession_number: Option<String>,
+    pub modality: Option<String>,
+}
+
+/// 新检查操作插入模型
+#[derive(Debug, Clone)]
+pub struct NewStudyOperation {
+    pub id: Uuid,
+    pub operation_type: String,
+    pub parameters: String,
+    pub performed_by: String,
+    pub reason: Option<String>,
+}
+
+/// 新检查操作实例明细插入模型
+#[derive(Debug, Clone)]
+pub struct NewStudyOperationItem {
+    pub id: Uuid,
+    pub operation_id: Uuid,
+    pub sop_class_uid: String,
+    pub old_study_uid: String,
+    pub old_series_uid: String,
+    pub old_sop_instance_uid: String,
+    pub new_study_uid: String,
+    pub new_series_uid: String,
+    pub new_sop_instance_uid: String,
+    pub old_patient_id: Option<String>,
+    pub new_patient_id: Option<String>,
+    pub original_path: String,
+    pub held_path: String,
+    pub new_path: String,
+}
+
+/// 实例归属变更（检查拆分/合并/移动）
+#[derive(Debug, Clone)]
+pub struct InstanceRelocation {
+    pub old_sop_instance_uid: String,
+    pub new_sop_instance_uid: String,
+    pub new_series_uid: String,
+    pub new_study_uid: String,
+    pub patient_id: String,
+    pub patient_name: String,
+    pub accession_number: Option<String>,
+    pub file_path: String,
+}
+
+/// 新被拒绝实例插入模型
+#[derive(Debug, Clone)]
+pub struct NewRejectedInstance {
+    pub sop_instance_uid: String,
+    pub study_uid: String,
+    pub series_uid: String,
+    pub sop_class_uid: Option<String>,
+    pub reason_code: String,
+    pub rejection_note_uid: String,
+    pub rejected_by: Option<String>,
+    pub file_path: Option<String>,
+    pub purge_after: DateTime<Utc>,
+}
+
+/// 实例检索条件（QIDO-RS与C-FIND共用），未设置的条件不参与过滤
+#[derive(Debug, Clone, Default)]
+pub struct InstanceSearch {
+    pub patient_id: Option<String>,
+    pub study_uid: Option<String>,
+    pub accession_number: Option<String>,
+    pub series_uid: Option<String>,
+    pub modality: Option<String>,
+    pub sop_instance_uid: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
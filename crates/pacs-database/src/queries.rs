@@ -2,6 +2,7 @@
 
 use crate::connection::DatabasePool;
 use crate::models::*;
+use chrono::{DateTime, Utc};
 use pacs_core::{Instance, PacsError, Patient, Result, Series, Sex, Study, StudyStatus};
 use sqlx::Row;
 use uuid::Uuid;
@@ -70,6 +71,7 @@ impl<'a> DatabaseQueries<'a> {
                 modality VARCHAR(16) NOT NULL,
                 series_number INTEGER NOT NULL,
                 description TEXT,
+                body_part_examined VARCHAR(64),
                 images_count INTEGER DEFAULT 0,
                 created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
             )
@@ -98,6 +100,202 @@ impl<'a> DatabaseQueries<'a> {
         .await
         .map_err(|e| PacsError::Database(e.to_string()))?;
 
+        // 创建辐射剂量表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS radiation_doses (
+                id UUID PRIMARY KEY,
+                study_uid VARCHAR(64) NOT NULL,
+                sop_instance_uid VARCHAR(64) UNIQUE NOT NULL,
+                patient_id VARCHAR(64),
+                modality VARCHAR(16),
+                source VARCHAR(16) NOT NULL,
+                protocol VARCHAR(256),
+                irradiation_events INTEGER NOT NULL DEFAULT 0,
+                ctdi_vol DOUBLE PRECISION,
+                dlp DOUBLE PRECISION,
+                dose_area_product DOUBLE PRECISION,
+                entrance_dose DOUBLE PRECISION,
+                created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 创建实例关系表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS instance_relationships (
+                id UUID PRIMARY KEY,
+                relationship_type VARCHAR(16) NOT NULL,
+                source_sop_instance_uid VARCHAR(64) NOT NULL,
+                study_uid VARCHAR(64) NOT NULL,
+                referenced_series_uid VARCHAR(64) NOT NULL,
+                referenced_sop_class_uid VARCHAR(64),
+                referenced_sop_instance_uid VARCHAR(64) NOT NULL,
+                label VARCHAR(256),
+                created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                UNIQUE (source_sop_instance_uid, referenced_sop_instance_uid)
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 创建转发队列表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS forward_queue (
+                id UUID PRIMARY KEY,
+                destination VARCHAR(64) NOT NULL,
+                rule_name VARCHAR(128) NOT NULL,
+                study_uid VARCHAR(64) NOT NULL,
+                sop_instance_uid VARCHAR(64) NOT NULL,
+                file_path VARCHAR(512) NOT NULL,
+                status VARCHAR(16) NOT NULL DEFAULT 'PENDING',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                last_error TEXT,
+                created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                UNIQUE (destination, sop_instance_uid)
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 创建检查稳定性跟踪表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS study_stability (
+                study_uid VARCHAR(64) PRIMARY KEY,
+                patient_id VARCHAR(64),
+                accession_number VARCHAR(64),
+                modalities TEXT[] NOT NULL DEFAULT '{}',
+                instance_count INTEGER NOT NULL DEFAULT 0,
+                first_received_at TIMESTAMP WITH TIME ZONE,
+                last_received_at TIMESTAMP WITH TIME ZONE,
+                mpps_completed_at TIMESTAMP WITH TIME ZONE,
+                stable_at TIMESTAMP WITH TIME ZONE,
+                completion_trigger VARCHAR(16),
+                notified_at TIMESTAMP WITH TIME ZONE,
+                created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 创建检查完成事件逐通道投递记录表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS study_completion_deliveries (
+                study_uid VARCHAR(64) NOT NULL REFERENCES study_stability(study_uid) ON DELETE CASCADE,
+                channel VARCHAR(255) NOT NULL,
+                delivered_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                PRIMARY KEY (study_uid, channel)
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 创建检查操作审计表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS study_operations (
+                id UUID PRIMARY KEY,
+                operation_type VARCHAR(32) NOT NULL,
+                parameters TEXT NOT NULL,
+                performed_by VARCHAR(128) NOT NULL,
+                reason TEXT,
+                status VARCHAR(16) NOT NULL DEFAULT 'IN_PROGRESS',
+                rejection_note_uid VARCHAR(64),
+                rejection_note_path VARCHAR(512),
+                error TEXT,
+                reverted_by VARCHAR(128),
+                reverted_at TIMESTAMP WITH TIME ZONE,
+                created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 创建检查操作实例明细表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS study_operation_items (
+                id UUID PRIMARY KEY,
+                operation_id UUID NOT NULL REFERENCES study_operations(id),
+                sop_class_uid VARCHAR(64) NOT NULL,
+                old_study_uid VARCHAR(64) NOT NULL,
+                old_series_uid VARCHAR(64) NOT NULL,
+                old_sop_instance_uid VARCHAR(64) NOT NULL,
+                new_study_uid VARCHAR(64) NOT NULL,
+                new_series_uid VARCHAR(64) NOT NULL,
+                new_sop_instance_uid VARCHAR(64) NOT NULL,
+                old_patient_id VARCHAR(64),
+                new_patient_id VARCHAR(64),
+                original_path VARCHAR(512) NOT NULL,
+                held_path VARCHAR(512) NOT NULL,
+                new_path VARCHAR(512) NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 创建被拒绝实例表（IOCM）
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS rejected_instances (
+                sop_instance_uid VARCHAR(64) PRIMARY KEY,
+                study_uid VARCHAR(64) NOT NULL,
+                series_uid VARCHAR(64) NOT NULL,
+                sop_class_uid VARCHAR(64),
+                reason_code VARCHAR(16) NOT NULL,
+                rejection_note_uid VARCHAR(64) NOT NULL,
+                rejected_by VARCHAR(128),
+                file_path VARCHAR(512),
+                rejected_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                purge_after TIMESTAMP WITH TIME ZONE NOT NULL,
+                purged_at TIMESTAMP WITH TIME ZONE
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 创建检查自定义标签表（生命周期策略使用）
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS study_tags (
+                study_uid VARCHAR(64) NOT NULL,
+                name VARCHAR(64) NOT NULL,
+                value TEXT NOT NULL,
+                updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                PRIMARY KEY (study_uid, name)
+            )
+        "#,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
         // 创建索引以优化查询性能
         self.create_indexes().await?;
 
@@ -121,6 +319,17 @@ impl<'a> DatabaseQueries<'a> {
             "CREATE INDEX IF NOT EXISTS idx_series_study_id ON series(study_id)",
             "CREATE INDEX IF NOT EXISTS idx_instances_sop_instance_uid ON instances(sop_instance_uid)",
             "CREATE INDEX IF NOT EXISTS idx_instances_series_id ON instances(series_id)",
+            "CREATE INDEX IF NOT EXISTS idx_radiation_doses_study_uid ON radiation_doses(study_uid)",
+            "CREATE INDEX IF NOT EXISTS idx_radiation_doses_patient_id ON radiation_doses(patient_id)",
+            "CREATE INDEX IF NOT EXISTS idx_relationships_study_uid ON instance_relationships(study_uid)",
+            "CREATE INDEX IF NOT EXISTS idx_relationships_referenced_series ON instance_relationships(referenced_series_uid)",
+            "CREATE INDEX IF NOT EXISTS idx_relationships_referenced_sop ON instance_relationships(referenced_sop_instance_uid)",
+            "CREATE INDEX IF NOT EXISTS idx_forward_queue_due ON forward_queue(destination, status, next_attempt_at)",
+            "CREATE INDEX IF NOT EXISTS idx_forward_queue_study_uid ON forward_queue(study_uid)",
+            "CREATE INDEX IF NOT EXISTS idx_study_stability_pending ON study_stability(stable_at, notified_at)",
+            "CREATE INDEX IF NOT EXISTS idx_study_operation_items_operation ON study_operation_items(operation_id)",
+            "CREATE INDEX IF NOT EXISTS idx_rejected_instances_purge ON rejected_instances(purged_at, purge_after)",
+            "CREATE INDEX IF NOT EXISTS idx_rejected_instances_note ON rejected_instances(rejection_note_uid)",
         ];
 
         for index_sql in indexes {
@@ -212,14 +421,7 @@ impl<'a> DatabaseQueries<'a> {
     pub async fn create_study(&self, study: &NewStudy) -> Result<Uuid> {
         let pool = self.pool.pool();
 
-        let status_str = match study.status {
-            StudyStatus::Scheduled => "SCHEDULED",
-            StudyStatus::InProgress => "IN_PROGRESS",
-            StudyStatus::Completed => "COMPLETED",
-            StudyStatus::Preliminary => "PRELIMINARY",
-            StudyStatus::Final => "FINAL",
-            StudyStatus::Canceled => "CANCELED",
-        };
+        let status_str = study_status_str(&study.status);
 
         sqlx::query(r#"
             INSERT INTO studies (id, study_uid, patient_id, accession_number, study_date, study_time, modality, description, status)
@@ -293,8 +495,8 @@ impl<'a> DatabaseQueries<'a> {
         let pool = self.pool.pool();
 
         sqlx::query(r#"
-            INSERT INTO series (id, series_uid, study_id, modality, series_number, description, images_count)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            INSERT INTO series (id, series_uid, study_id, modality, series_number, description, body_part_examined, images_count)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             RETURNING id
         "#)
         .bind(series.id)
@@ -303,6 +505,7 @@ impl<'a> DatabaseQueries<'a> {
         .bind(&series.modality)
         .bind(series.series_number)
         .bind(&series.description)
+        .bind(&series.body_part_examined)
         .bind(series.images_count)
         .fetch_one(pool)
         .await
@@ -362,12 +565,14 @@ impl<'a> DatabaseQueries<'a> {
         .map_err(|e| PacsError::Database(e.to_string()))
     }
 
-    /// 根据SOP实例UID查找实例
+    /// 根据SOP实例UID查找实例（已拒绝的实例不可见）
     pub async fn get_instance_by_uid(&self, sop_instance_uid: &str) -> Result<Option<Instance>> {
         let pool = self.pool.pool();
 
         let result =
-            sqlx::query_as::<_, DbInstance>("SELECT * FROM instances WHERE sop_instance_uid = $1")
+            sqlx::query_as::<_, DbInstance>(
+                "SELECT * FROM instances WHERE sop_instance_uid = $1 AND sop_instance_uid NOT IN (SELECT sop_instance_uid FROM rejected_instances)",
+            )
                 .bind(sop_instance_uid)
                 .fetch_optional(pool)
                 .await
@@ -376,12 +581,16 @@ impl<'a> DatabaseQueries<'a> {
         Ok(result.map(|db_instance| Instance::from(db_instance)))
     }
 
-    /// 根据系列ID获取所有实例
+    /// 根据系列ID获取所有实例（已拒绝的实例不可见）
     pub async fn get_instances_by_series_id(&self, series_id: &Uuid) -> Result<Vec<Instance>> {
         let pool = self.pool.pool();
 
         let results = sqlx::query_as::<_, DbInstance>(
-            "SELECT * FROM instances WHERE series_id = $1 ORDER BY instance_number",
+            r#"
+            SELECT * FROM instances WHERE series_id = $1
+                AND sop_instance_uid NOT IN (SELECT sop_instance_uid FROM rejected_instances)
+            ORDER BY instance_number
+        "#,
         )
         .bind(series_id)
         .fetch_all(pool)
@@ -391,6 +600,70 @@ impl<'a> DatabaseQueries<'a> {
         Ok(results.into_iter().map(Instance::from).collect())
     }
 
+    /// 按条件检索实例，已拒绝（IOCM）的实例不出现在结果中
+    pub async fn search_instances(&self, search: &InstanceSearch) -> Result<Vec<DbInstanceMatch>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbInstanceMatch>(
+            r#"
+            SELECT p.patient_id, p.name AS patient_name, p.birth_date AS patient_birth_date,
+                p.sex AS patient_sex, st.study_uid, st.study_date, st.study_time, st.accession_number,
+                st.description AS study_description, se.series_uid, se.series_number, se.modality,
+                se.description AS series_description, i.sop_instance_uid, i.instance_number,
+                i.transfer_syntax_uid
+            FROM instances i
+            JOIN series se ON se.id = i.series_id
+            JOIN studies st ON st.id = se.study_id
+            JOIN patients p ON p.id = st.patient_id
+            WHERE ($1::VARCHAR IS NULL OR p.patient_id = $1)
+                AND ($2::VARCHAR IS NULL OR st.study_uid = $2)
+                AND ($3::VARCHAR IS NULL OR st.accession_number = $3)
+                AND ($4::VARCHAR IS NULL OR se.series_uid = $4)
+                AND ($5::VARCHAR IS NULL OR se.modality = $5)
+                AND ($6::VARCHAR IS NULL OR i.sop_instance_uid = $6)
+                AND i.sop_instance_uid NOT IN (SELECT sop_instance_uid FROM rejected_instances)
+            ORDER BY st.study_uid, se.series_number, se.series_uid, i.instance_number, i.sop_instance_uid
+            LIMIT $7 OFFSET $8
+        "#,
+        )
+        .bind(&search.patient_id)
+        .bind(&search.study_uid)
+        .bind(&search.accession_number)
+        .bind(&search.series_uid)
+        .bind(&search.modality)
+        .bind(&search.sop_instance_uid)
+        .bind(search.limit)
+        .bind(search.offset.unwrap_or(0))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 实例是否已登记（包括已拒绝的实例）
+    pub async fn instance_exists(&self, sop_instance_uid: &str) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query("SELECT EXISTS (SELECT 1 FROM instances WHERE sop_instance_uid = $1) AS found")
+            .bind(sop_instance_uid)
+            .fetch_one(pool)
+            .await
+            .map(|row| row.get("found"))
+            .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 系列图像计数加一
+    pub async fn increment_series_images_count(&self, series_id: &Uuid) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query("UPDATE series SET images_count = images_count + 1 WHERE id = $1")
+            .bind(series_id)
+            .execute(pool)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
     /// 更新系列的图像计数
     pub async fn update_series_images_count(&self, series_id: &Uuid, count: i32) -> Result<()> {
         let pool = self.pool.pool();
@@ -404,4 +677,1193 @@ impl<'a> DatabaseQueries<'a> {
 
         Ok(())
     }
+
+    // ========== 辐射剂量相关操作 ==========
+
+    /// 写入辐射剂量记录，同一SOP实例重复写入时覆盖剂量值
+    pub async fn upsert_radiation_dose(&self, dose: &NewRadiationDose) -> Result<Uuid> {
+        let pool = self.pool.pool();
+
+        sqlx::query(r#"
+            INSERT INTO radiation_doses (id, study_uid, sop_instance_uid, patient_id, modality, source, protocol,
+                irradiation_events, ctdi_vol, dlp, dose_area_product, entrance_dose)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            ON CONFLICT (sop_instance_uid) DO UPDATE SET
+                protocol = EXCLUDED.protocol,
+                irradiation_events = EXCLUDED.irradiation_events,
+                ctdi_vol = EXCLUDED.ctdi_vol,
+                dlp = EXCLUDED.dlp,
+                dose_area_product = EXCLUDED.dose_area_product,
+                entrance_dose = EXCLUDED.entrance_dose
+            RETURNING id
+        "#)
+        .bind(dose.id)
+        .bind(&dose.study_uid)
+        .bind(&dose.sop_instance_uid)
+        .bind(&dose.patient_id)
+        .bind(&dose.modality)
+        .bind(&dose.source)
+        .bind(&dose.protocol)
+        .bind(dose.irradiation_events)
+        .bind(dose.ctdi_vol)
+        .bind(dose.dlp)
+        .bind(dose.dose_area_product)
+        .bind(dose.entrance_dose)
+        .fetch_one(pool)
+        .await
+        .map(|row| row.get("id"))
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取检查的所有辐射剂量记录
+    pub async fn get_radiation_doses_by_study_uid(
+        &self,
+        study_uid: &str,
+    ) -> Result<Vec<DbRadiationDose>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbRadiationDose>(
+            "SELECT * FROM radiation_doses WHERE study_uid = $1 ORDER BY created_at",
+        )
+        .bind(study_uid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取辐射剂量记录，可按患者过滤
+    pub async fn list_radiation_doses(
+        &self,
+        patient_id: Option<&str>,
+    ) -> Result<Vec<DbRadiationDose>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbRadiationDose>(
+            "SELECT * FROM radiation_doses WHERE ($1::VARCHAR IS NULL OR patient_id = $1) ORDER BY created_at",
+        )
+        .bind(patient_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    // ========== 实例关系相关操作 ==========
+
+    /// 写入实例关系，重复的引用会被忽略
+    pub async fn create_instance_relationships(
+        &self,
+        relationships: &[NewInstanceRelationship],
+    ) -> Result<()> {
+        let pool = self.pool.pool();
+
+        for relationship in relationships {
+            sqlx::query(r#"
+                INSERT INTO instance_relationships (id, relationship_type, source_sop_instance_uid, study_uid,
+                    referenced_series_uid, referenced_sop_class_uid, referenced_sop_instance_uid, label)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (source_sop_instance_uid, referenced_sop_instance_uid) DO NOTHING
+            "#)
+            .bind(relationship.id)
+            .bind(&relationship.relationship_type)
+            .bind(&relationship.source_sop_instance_uid)
+            .bind(&relationship.study_uid)
+            .bind(&relationship.referenced_series_uid)
+            .bind(&relationship.referenced_sop_class_uid)
+            .bind(&relationship.referenced_sop_instance_uid)
+            .bind(&relationship.label)
+            .execute(pool)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// 获取检查的关键图像引用，拒绝说明及已拒绝的图像不计入
+    pub async fn get_key_images_by_study_uid(
+        &self,
+        study_uid: &str,
+    ) -> Result<Vec<DbInstanceRelationship>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbInstanceRelationship>(
+            r#"
+            SELECT * FROM instance_relationships WHERE study_uid = $1 AND relationship_type = 'KOS'
+                AND source_sop_instance_uid NOT IN (SELECT rejection_note_uid FROM rejected_instances)
+                AND referenced_sop_instance_uid NOT IN (SELECT sop_instance_uid FROM rejected_instances)
+            ORDER BY created_at
+        "#,
+        )
+        .bind(study_uid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取引用指定序列图像的显示状态
+    pub async fn get_presentation_states_by_series_uid(
+        &self,
+        series_uid: &str,
+    ) -> Result<Vec<DbInstanceRelationship>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbInstanceRelationship>(
+            "SELECT * FROM instance_relationships WHERE referenced_series_uid = $1 AND relationship_type = 'PR' ORDER BY created_at",
+        )
+        .bind(series_uid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取引用指定实例的所有关系
+    pub async fn get_relationships_by_referenced_uid(
+        &self,
+        sop_instance_uid: &str,
+    ) -> Result<Vec<DbInstanceRelationship>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbInstanceRelationship>(
+            "SELECT * FROM instance_relationships WHERE referenced_sop_instance_uid = $1",
+        )
+        .bind(sop_instance_uid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 删除引用方的所有关系
+    pub async fn delete_relationships_by_source_uid(
+        &self,
+        source_sop_instance_uid: &str,
+    ) -> Result<u64> {
+        let pool = self.pool.pool();
+
+        sqlx::query("DELETE FROM instance_relationships WHERE source_sop_instance_uid = $1")
+            .bind(source_sop_instance_uid)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    // ========== 转发队列相关操作 ==========
+
+    /// 写入转发任务，同一目的地的同一实例只入队一次，返回是否新入队
+    pub async fn enqueue_forward_task(&self, task: &NewForwardTask) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query(r#"
+            INSERT INTO forward_queue (id, destination, rule_name, study_uid, sop_instance_uid, file_path, next_attempt_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (destination, sop_instance_uid) DO NOTHING
+        "#)
+        .bind(task.id)
+        .bind(&task.destination)
+        .bind(&task.rule_name)
+        .bind(&task.study_uid)
+        .bind(&task.sop_instance_uid)
+        .bind(&task.file_path)
+        .bind(task.next_attempt_at)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 推迟检查尚未首次发送的转发任务（检查仍在接收新实例）
+    pub async fn postpone_study_forward_tasks(
+        &self,
+        destination: &str,
+        study_uid: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<u64> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            UPDATE forward_queue SET next_attempt_at = $3, updated_at = NOW()
+            WHERE destination = $1 AND study_uid = $2 AND status = 'PENDING'
+                AND attempts = 0 AND next_attempt_at < $3
+        "#,
+        )
+        .bind(destination)
+        .bind(study_uid)
+        .bind(next_attempt_at)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 领取目的地最早到期检查的一批待发送任务，并标记为发送中
+    pub async fn claim_forward_batch(
+        &self,
+        destination: &str,
+        limit: i64,
+    ) -> Result<Vec<DbForwardTask>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbForwardTask>(
+            r#"
+            UPDATE forward_queue SET status = 'IN_PROGRESS', updated_at = NOW()
+            WHERE id IN (
+                SELECT id FROM forward_queue
+                WHERE destination = $1 AND status = 'PENDING' AND next_attempt_at <= NOW()
+                    AND study_uid = (
+                        SELECT study_uid FROM forward_queue
+                        WHERE destination = $1 AND status = 'PENDING' AND next_attempt_at <= NOW()
+                        ORDER BY next_attempt_at, created_at LIMIT 1
+                    )
+                ORDER BY created_at
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+        "#,
+        )
+        .bind(destination)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 标记转发任务已完成
+    pub async fn complete_forward_task(&self, id: &Uuid) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            UPDATE forward_queue SET status = 'COMPLETED', attempts = attempts + 1,
+                last_error = NULL, updated_at = NOW()
+            WHERE id = $1
+        "#,
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// 记录转发失败，给定重试时间时重新排队，否则标记为最终失败
+    pub async fn fail_forward_task(
+        &self,
+        id: &Uuid,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            UPDATE forward_queue SET
+                status = CASE WHEN $3::TIMESTAMPTZ IS NULL THEN 'FAILED' ELSE 'PENDING' END,
+                next_attempt_at = COALESCE($3, next_attempt_at),
+                attempts = attempts + 1,
+                last_error = $2,
+                updated_at = NOW()
+            WHERE id = $1
+        "#,
+        )
+        .bind(id)
+        .bind(error)
+        .bind(retry_at)
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// 推迟发送中的任务（如源文件正在召回），不计入重试次数
+    pub async fn defer_forward_task(
+        &self,
+        id: &Uuid,
+        reason: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            UPDATE forward_queue SET
+                status = 'PENDING',
+                next_attempt_at = $3,
+                last_error = $2,
+                updated_at = NOW()
+            WHERE id = $1
+        "#,
+        )
+        .bind(id)
+        .bind(reason)
+        .bind(next_attempt_at)
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// 将发送中的任务恢复为待发送（服务重启后调用）
+    pub async fn reset_in_progress_forward_tasks(&self) -> Result<u64> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            "UPDATE forward_queue SET status = 'PENDING', updated_at = NOW() WHERE status = 'IN_PROGRESS'",
+        )
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 按目的地统计转发队列
+    pub async fn get_forward_queue_stats(&self) -> Result<Vec<DbForwardQueueStats>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbForwardQueueStats>(
+            r#"
+            SELECT destination,
+                COUNT(*) FILTER (WHERE status = 'PENDING') AS pending,
+                COUNT(*) FILTER (WHERE status = 'IN_PROGRESS') AS in_progress,
+                COUNT(*) FILTER (WHERE status = 'COMPLETED') AS completed,
+                COUNT(*) FILTER (WHERE status = 'FAILED') AS failed,
+                MIN(created_at) FILTER (WHERE status = 'PENDING') AS oldest_pending_at
+            FROM forward_queue
+            GROUP BY destination
+            ORDER BY destination
+        "#,
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取最终失败的转发任务
+    pub async fn get_failed_forward_tasks(
+        &self,
+        destination: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<DbForwardTask>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbForwardTask>(
+            r#"
+            SELECT * FROM forward_queue
+            WHERE status = 'FAILED' AND ($1::VARCHAR IS NULL OR destination = $1)
+            ORDER BY updated_at DESC
+            LIMIT $2
+        "#,
+        )
+        .bind(destination)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 将最终失败的转发任务重新排队
+    pub async fn retry_failed_forward_tasks(&self, destination: Option<&str>) -> Result<u64> {
+        let pool = self.pool.pool();
+
+        sqlx::query(r#"
+            UPDATE forward_queue SET status = 'PENDING', attempts = 0, next_attempt_at = NOW(), updated_at = NOW()
+            WHERE status = 'FAILED' AND ($1::VARCHAR IS NULL OR destination = $1)
+        "#)
+        .bind(destination)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 更新检查状态
+    pub async fn update_study_status(&self, study_uid: &str, status: &StudyStatus) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query("UPDATE studies SET status = $2, updated_at = NOW() WHERE study_uid = $1")
+            .bind(study_uid)
+            .bind(study_status_str(status))
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+            .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    // ========== 检查稳定性相关操作 ==========
+
+    /// 记录检查新实例到达，刷新最后接收时间
+    pub async fn record_study_arrival(
+        &self,
+        arrival: &NewStudyArrival,
+    ) -> Result<DbStudyStability> {
+        let pool = self.pool.pool();
+        let modalities: Vec<String> = arrival.modality.iter().cloned().collect();
+
+        sqlx::query_as::<_, DbStudyStability>(
+            r#"
+            INSERT INTO study_stability (study_uid, patient_id, accession_number, modalities,
+                instance_count, first_received_at, last_received_at)
+            VALUES ($1, $2, $3, $4, 1, NOW(), NOW())
+            ON CONFLICT (study_uid) DO UPDATE SET
+                patient_id = COALESCE(study_stability.patient_id, EXCLUDED.patient_id),
+                accession_number = COALESCE(study_stability.accession_number, EXCLUDED.accession_number),
+                modalities = CASE
+                    WHEN EXCLUDED.modalities <@ study_stability.modalities THEN study_stability.modalities
+                    ELSE study_stability.modalities || EXCLUDED.modalities
+                END,
+                instance_count = study_stability.instance_count + 1,
+                first_received_at = COALESCE(study_stability.first_received_at, NOW()),
+                last_received_at = NOW(),
+                updated_at = NOW()
+            RETURNING *
+        "#,
+        )
+        .bind(&arrival.study_uid)
+        .bind(&arrival.patient_id)
+        .bind(&arrival.accession_number)
+        .bind(&modalities)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 记录检查的MPPS完成（可能早于影像到达），已稳定的检查不受影响
+    pub async fn record_study_mpps_completed(&self, study_uid: &str) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            INSERT INTO study_stability (study_uid, mpps_completed_at) VALUES ($1, NOW())
+            ON CONFLICT (study_uid) DO UPDATE SET mpps_completed_at = NOW(), updated_at = NOW()
+            WHERE study_stability.stable_at IS NULL
+        "#,
+        )
+        .bind(study_uid)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取已收到实例但尚未稳定的检查
+    pub async fn get_unstable_studies(&self) -> Result<Vec<DbStudyStability>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbStudyStability>(
+            "SELECT * FROM study_stability WHERE stable_at IS NULL AND instance_count > 0 ORDER BY last_received_at",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 将检查标记为稳定
+    ///
+    /// 仅当检查尚未稳定且最后接收时间不晚于`received_before`时生效，
+    /// 避免判定期间到达的新实例被忽略；返回更新后的记录
+    pub async fn mark_study_stable(
+        &self,
+        study_uid: &str,
+        trigger: &str,
+        received_before: DateTime<Utc>,
+    ) -> Result<Option<DbStudyStability>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbStudyStability>(
+            r#"
+            UPDATE study_stability SET stable_at = NOW(), completion_trigger = $2, updated_at = NOW()
+            WHERE study_uid = $1 AND stable_at IS NULL AND last_received_at <= $3
+            RETURNING *
+        "#,
+        )
+        .bind(study_uid)
+        .bind(trigger)
+        .bind(received_before)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取已稳定但完成事件尚未成功发出的检查
+    pub async fn get_unnotified_stable_studies(&self) -> Result<Vec<DbStudyStability>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbStudyStability>(
+            "SELECT * FROM study_stability WHERE stable_at IS NOT NULL AND notified_at IS NULL ORDER BY stable_at",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 标记检查完成事件已发出，返回是否为首次标记
+    pub async fn mark_study_completion_notified(&self, study_uid: &str) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            UPDATE study_stability SET notified_at = NOW(), updated_at = NOW()
+            WHERE study_uid = $1 AND notified_at IS NULL
+        "#,
+        )
+        .bind(study_uid)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取检查完成事件已成功投递的通道
+    pub async fn get_study_completion_deliveries(&self, study_uid: &str) -> Result<Vec<String>> {
+        let pool = self.pool.pool();
+
+        let rows =
+            sqlx::query("SELECT channel FROM study_completion_deliveries WHERE study_uid = $1")
+                .bind(study_uid)
+                .fetch_all(pool)
+                .await
+                .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(rows.iter().map(|row| row.get("channel")).collect())
+    }
+
+    /// 记录检查完成事件已投递到指定通道
+    pub async fn record_study_completion_delivery(
+        &self,
+        study_uid: &str,
+        channel: &str,
+    ) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            INSERT INTO study_completion_deliveries (study_uid, channel) VALUES ($1, $2)
+            ON CONFLICT (study_uid, channel) DO NOTHING
+        "#,
+        )
+        .bind(study_uid)
+        .bind(channel)
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    // ========== 检查操作相关操作 ==========
+
+    /// 变更实例归属（检查、序列、患者），必要时复制原检查/序列记录
+    ///
+    /// 实例没有数据库记录时返回false
+    pub async fn relocate_instance(&self, relocation: &InstanceRelocation) -> Result<bool> {
+        let mut tx = self
+            .pool
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT i.series_id, s.study_id FROM instances i
+            JOIN series s ON s.id = i.series_id
+            WHERE i.sop_instance_uid = $1
+        "#,
+        )
+        .bind(&relocation.old_sop_instance_uid)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+        let Some(row) = row else {
+            return Ok(false);
+        };
+        let old_series_id: Uuid = row.get("series_id");
+        let old_study_id: Uuid = row.get("study_id");
+
+        sqlx::query(
+            "INSERT INTO patients (id, patient_id, name) VALUES ($1, $2, $3) ON CONFLICT (patient_id) DO NOTHING",
+        )
+        .bind(Uuid::new_v4())
+        .bind(&relocation.patient_id)
+        .bind(&relocation.patient_name)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        sqlx::query(r#"
+            INSERT INTO studies (id, study_uid, patient_id, accession_number, study_date, study_time, modality, description, status)
+            SELECT $1, $2, (SELECT id FROM patients WHERE patient_id = $3), COALESCE($4, accession_number),
+                study_date, study_time, modality, description, status
+            FROM studies WHERE id = $5
+            ON CONFLICT (study_uid) DO NOTHING
+        "#)
+        .bind(Uuid::new_v4())
+        .bind(&relocation.new_study_uid)
+        .bind(&relocation.patient_id)
+        .bind(&relocation.accession_number)
+        .bind(old_study_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        sqlx::query(r#"
+            INSERT INTO series (id, series_uid, study_id, modality, series_number, description, body_part_examined, images_count)
+            SELECT $1, $2, (SELECT id FROM studies WHERE study_uid = $3), modality, series_number, description, body_part_examined, 0
+            FROM series WHERE id = $4
+            ON CONFLICT (series_uid) DO NOTHING
+        "#)
+        .bind(Uuid::new_v4())
+        .bind(&relocation.new_series_uid)
+        .bind(&relocation.new_study_uid)
+        .bind(old_series_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        // 配置了数据库的入库管道已为改写后的实例建立索引，以迁移的原记录为准
+        sqlx::query("DELETE FROM instances WHERE sop_instance_uid = $1")
+            .bind(&relocation.new_sop_instance_uid)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE instances SET sop_instance_uid = $2, file_path = $3,
+                series_id = (SELECT id FROM series WHERE series_uid = $4)
+            WHERE sop_instance_uid = $1
+        "#,
+        )
+        .bind(&relocation.old_sop_instance_uid)
+        .bind(&relocation.new_sop_instance_uid)
+        .bind(&relocation.file_path)
+        .bind(&relocation.new_series_uid)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE series SET images_count = (SELECT COUNT(*) FROM instances WHERE series_id = series.id)
+            WHERE id = $1 OR series_uid = $2
+        "#,
+        )
+        .bind(old_series_id)
+        .bind(&relocation.new_series_uid)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+        Ok(true)
+    }
+
+    /// 实例文件移动后更新文件路径
+    ///
+    /// 实例索引、尚未完成的转发任务和未清除的拒绝记录在同一事务中更新
+    pub async fn update_instance_file_path(
+        &self,
+        sop_instance_uid: &str,
+        file_path: &str,
+    ) -> Result<()> {
+        let mut tx = self
+            .pool
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        for statement in [
+            "UPDATE instances SET file_path = $2 WHERE sop_instance_uid = $1",
+            r#"
+            UPDATE forward_queue SET file_path = $2, updated_at = NOW()
+            WHERE sop_instance_uid = $1 AND status <> 'COMPLETED'
+        "#,
+            r#"
+            UPDATE rejected_instances SET file_path = $2
+            WHERE sop_instance_uid = $1 AND purged_at IS NULL
+        "#,
+        ] {
+            sqlx::query(statement)
+                .bind(sop_instance_uid)
+                .bind(file_path)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| PacsError::Database(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// 创建检查操作审计记录
+    pub async fn create_study_operation(&self, operation: &NewStudyOperation) -> Result<Uuid> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            INSERT INTO study_operations (id, operation_type, parameters, performed_by, reason)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+        "#,
+        )
+        .bind(operation.id)
+        .bind(&operation.operation_type)
+        .bind(&operation.parameters)
+        .bind(&operation.performed_by)
+        .bind(&operation.reason)
+        .fetch_one(pool)
+        .await
+        .map(|row| row.get("id"))
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 记录检查操作处理的实例
+    pub async fn add_study_operation_item(&self, item: &NewStudyOperationItem) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query(r#"
+            INSERT INTO study_operation_items (id, operation_id, sop_class_uid, old_study_uid, old_series_uid,
+                old_sop_instance_uid, new_study_uid, new_series_uid, new_sop_instance_uid,
+                old_patient_id, new_patient_id, original_path, held_path, new_path)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        "#)
+        .bind(item.id)
+        .bind(item.operation_id)
+        .bind(&item.sop_class_uid)
+        .bind(&item.old_study_uid)
+        .bind(&item.old_series_uid)
+        .bind(&item.old_sop_instance_uid)
+        .bind(&item.new_study_uid)
+        .bind(&item.new_series_uid)
+        .bind(&item.new_sop_instance_uid)
+        .bind(&item.old_patient_id)
+        .bind(&item.new_patient_id)
+        .bind(&item.original_path)
+        .bind(&item.held_path)
+        .bind(&item.new_path)
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// 更新检查操作状态及其拒绝说明
+    pub async fn update_study_operation_status(
+        &self,
+        id: &Uuid,
+        status: &str,
+        rejection_note_uid: Option<&str>,
+        rejection_note_path: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            UPDATE study_operations SET status = $2,
+                rejection_note_uid = COALESCE($3, rejection_note_uid),
+                rejection_note_path = COALESCE($4, rejection_note_path),
+                error = $5, updated_at = NOW()
+            WHERE id = $1
+        "#,
+        )
+        .bind(id)
+        .bind(status)
+        .bind(rejection_note_uid)
+        .bind(rejection_note_path)
+        .bind(error)
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// 标记检查操作已撤销，操作已撤销时返回false
+    pub async fn mark_study_operation_reverted(
+        &self,
+        id: &Uuid,
+        reverted_by: &str,
+    ) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            UPDATE study_operations SET status = 'REVERTED', reverted_by = $2, reverted_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND status <> 'REVERTED'
+        "#,
+        )
+        .bind(id)
+        .bind(reverted_by)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 根据ID获取检查操作
+    pub async fn get_study_operation(&self, id: &Uuid) -> Result<Option<DbStudyOperation>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbStudyOperation>("SELECT * FROM study_operations WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取最近的检查操作
+    pub async fn list_study_operations(&self, limit: i64) -> Result<Vec<DbStudyOperation>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbStudyOperation>(
+            "SELECT * FROM study_operations ORDER BY created_at DESC LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取检查操作处理的实例
+    pub async fn get_study_operation_items(
+        &self,
+        operation_id: &Uuid,
+    ) -> Result<Vec<DbStudyOperationItem>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbStudyOperationItem>(
+            "SELECT * FROM study_operation_items WHERE operation_id = $1 ORDER BY created_at",
+        )
+        .bind(operation_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    // ========== 实例拒绝（IOCM）相关操作 ==========
+
+    /// 记录实例拒绝，实例已被拒绝时返回false
+    pub async fn record_instance_rejection(&self, rejection: &NewRejectedInstance) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query(r#"
+            INSERT INTO rejected_instances (sop_instance_uid, study_uid, series_uid, sop_class_uid, reason_code,
+                rejection_note_uid, rejected_by, file_path, purge_after)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (sop_instance_uid) DO NOTHING
+        "#)
+        .bind(&rejection.sop_instance_uid)
+        .bind(&rejection.study_uid)
+        .bind(&rejection.series_uid)
+        .bind(&rejection.sop_class_uid)
+        .bind(&rejection.reason_code)
+        .bind(&rejection.rejection_note_uid)
+        .bind(&rejection.rejected_by)
+        .bind(&rejection.file_path)
+        .bind(rejection.purge_after)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取实例的拒绝记录
+    pub async fn get_instance_rejection(
+        &self,
+        sop_instance_uid: &str,
+    ) -> Result<Option<DbRejectedInstance>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbRejectedInstance>(
+            "SELECT * FROM rejected_instances WHERE sop_instance_uid = $1",
+        )
+        .bind(sop_instance_uid)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取到期待清除的被拒绝实例
+    pub async fn get_due_rejections(&self, limit: i64) -> Result<Vec<DbRejectedInstance>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbRejectedInstance>(
+            r#"
+            SELECT * FROM rejected_instances WHERE purged_at IS NULL AND purge_after <= NOW()
+            ORDER BY purge_after LIMIT $1
+        "#,
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 标记被拒绝实例已清除
+    pub async fn mark_rejection_purged(&self, sop_instance_uid: &str) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query("UPDATE rejected_instances SET purged_at = NOW() WHERE sop_instance_uid = $1")
+            .bind(sop_instance_uid)
+            .execute(pool)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// 被拒绝实例再次入库后重新安排清除
+    pub async fn reschedule_rejection_purge(
+        &self,
+        sop_instance_uid: &str,
+        file_path: &str,
+        purge_after: DateTime<Utc>,
+    ) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            UPDATE rejected_instances SET file_path = $2, purge_after = $3, purged_at = NULL
+            WHERE sop_instance_uid = $1
+        "#,
+        )
+        .bind(sop_instance_uid)
+        .bind(file_path)
+        .bind(purge_after)
+        .execute(pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 删除拒绝说明产生的拒绝记录，返回受影响的实例数
+    pub async fn delete_rejections_by_note_uid(&self, rejection_note_uid: &str) -> Result<u64> {
+        let pool = self.pool.pool();
+
+        sqlx::query("DELETE FROM rejected_instances WHERE rejection_note_uid = $1")
+            .bind(rejection_note_uid)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取检查中已拒绝的实例UID
+    pub async fn get_rejected_instance_uids(&self, study_uid: &str) -> Result<Vec<String>> {
+        let pool = self.pool.pool();
+
+        let rows =
+            sqlx::query("SELECT sop_instance_uid FROM rejected_instances WHERE study_uid = $1")
+                .bind(study_uid)
+                .fetch_all(pool)
+                .await
+                .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        Ok(rows.iter().map(|row| row.get("sop_instance_uid")).collect())
+    }
+
+    /// 获取最近的拒绝记录，可按检查过滤
+    pub async fn list_rejections(
+        &self,
+        study_uid: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<DbRejectedInstance>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbRejectedInstance>(
+            r#"
+            SELECT * FROM rejected_instances WHERE ($1::VARCHAR IS NULL OR study_uid = $1)
+            ORDER BY rejected_at DESC LIMIT $2
+        "#,
+        )
+        .bind(study_uid)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取全部实例的SOP实例UID与文件路径
+    pub async fn list_instance_files(&self) -> Result<Vec<(String, String)>> {
+        let pool = self.pool.pool();
+
+        let rows = sqlx::query("SELECT sop_instance_uid, file_path FROM instances")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("sop_instance_uid"), row.get("file_path")))
+            .collect())
+    }
+
+    /// 获取检查（可限定序列）中实例的序列实例UID、SOP实例UID与文件路径
+    pub async fn list_study_instance_files(
+        &self,
+        study_uid: &str,
+        series_uid: Option<&str>,
+    ) -> Result<Vec<(String, String, String)>> {
+        let pool = self.pool.pool();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT se.series_uid, i.sop_instance_uid, i.file_path
+            FROM instances i
+            JOIN series se ON se.id = i.series_id
+            JOIN studies st ON st.id = se.study_id
+            WHERE st.study_uid = $1 AND ($2::VARCHAR IS NULL OR se.series_uid = $2)
+        "#,
+        )
+        .bind(study_uid)
+        .bind(series_uid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.get("series_uid"),
+                    row.get("sop_instance_uid"),
+                    row.get("file_path"),
+                )
+            })
+            .collect())
+    }
+
+    /// 物理删除实例记录及其引用关系并更新序列图像计数，实例不存在时返回false
+    pub async fn delete_instance_by_uid(&self, sop_instance_uid: &str) -> Result<bool> {
+        let mut tx = self
+            .pool
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        let series_id: Option<Uuid> =
+            sqlx::query("DELETE FROM instances WHERE sop_instance_uid = $1 RETURNING series_id")
+                .bind(sop_instance_uid)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| PacsError::Database(e.to_string()))?
+                .map(|row| row.get("series_id"));
+
+        sqlx::query("DELETE FROM instance_relationships WHERE source_sop_instance_uid = $1")
+            .bind(sop_instance_uid)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        if let Some(series_id) = series_id {
+            sqlx::query(
+                "UPDATE series SET images_count = (SELECT COUNT(*) FROM instances WHERE series_id = $1) WHERE id = $1",
+            )
+            .bind(series_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+        Ok(series_id.is_some())
+    }
+
+    // ========== 检查生命周期相关操作 ==========
+
+    /// 获取全部检查的生命周期元数据：检查日期、患者出生日期、模态、检查部位及实例文件
+    pub async fn list_study_lifecycle_info(&self) -> Result<Vec<DbStudyLifecycleInfo>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbStudyLifecycleInfo>(
+            r#"
+            SELECT st.study_uid, st.study_date, p.birth_date AS patient_birth_date,
+                COALESCE(ARRAY_AGG(DISTINCT se.modality) FILTER (WHERE se.modality IS NOT NULL), '{}') AS modalities,
+                COALESCE(ARRAY_AGG(DISTINCT se.body_part_examined) FILTER (WHERE se.body_part_examined IS NOT NULL), '{}') AS body_parts,
+                COALESCE(ARRAY_AGG(i.file_path) FILTER (WHERE i.file_path IS NOT NULL), '{}') AS file_paths
+            FROM studies st
+            JOIN patients p ON p.id = st.patient_id
+            LEFT JOIN series se ON se.study_id = st.id
+            LEFT JOIN instances i ON i.series_id = se.id
+            GROUP BY st.id, st.study_uid, st.study_date, p.birth_date
+            ORDER BY st.study_date
+        "#,
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 获取检查自定义标签，可按检查过滤
+    pub async fn list_study_tags(&self, study_uid: Option<&str>) -> Result<Vec<DbStudyTag>> {
+        let pool = self.pool.pool();
+
+        sqlx::query_as::<_, DbStudyTag>(
+            r#"
+            SELECT * FROM study_tags WHERE ($1::VARCHAR IS NULL OR study_uid = $1)
+            ORDER BY study_uid, name
+        "#,
+        )
+        .bind(study_uid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))
+    }
+
+    /// 设置检查自定义标签（已存在时覆盖）
+    pub async fn set_study_tag(&self, study_uid: &str, name: &str, value: &str) -> Result<()> {
+        let pool = self.pool.pool();
+
+        sqlx::query(
+            r#"
+            INSERT INTO study_tags (study_uid, name, value, updated_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (study_uid, name) DO UPDATE SET value = EXCLUDED.value, updated_at = NOW()
+        "#,
+        )
+        .bind(study_uid)
+        .bind(name)
+        .bind(value)
+        .execute(pool)
+        .await
+        .map_err(|e| PacsError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// 删除检查自定义标签，标签不存在时返回false
+    pub async fn delete_study_tag(&self, study_uid: &str, name: &str) -> Result<bool> {
+        let pool = self.pool.pool();
+
+        sqlx::query("DELETE FROM study_tags WHERE study_uid = $1 AND name = $2")
+            .bind(study_uid)
+            .bind(name)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+            .map_err(|e| PacsError::Database(e.to_string()))
+    }
+}
+
+/// 检查状态的数据库存储值
+fn study_status_str(status: &StudyStatus) -> &'static str {
+    match status {
+        StudyStatus::Scheduled => "SCHEDULED",
+        StudyStatus::InProgress => "IN_PROGRESS",
+        StudyStatus::Completed => "COMPLETED",
+        StudyStatus::Preliminary => "PRELIMINARY",
+        StudyStatus::Final => "FINAL",
+        StudyStatus::Canceled => "CANCELED",
+    }
 }
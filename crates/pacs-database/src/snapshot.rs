@@ -0,0 +1,202 @@
+//! 数据库逻辑快照
+//!
+//! 以JSON行的形式导出索引相关的全部表，用于与存储备份配套的时间点恢复。
+//! 导出在可重复读的只读事务中进行，保证各表数据来自同一时刻；
+//! 导入在单个事务中清空并按外键依赖顺序重新写入，失败时整体回滚；
+//! 也可以先写入未提交的事务，待对象恢复成功后再提交，使索引与对象一起恢复。
+
+use crate::connection::DatabasePool;
+use chrono::{DateTime, Utc};
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Row, Transaction};
+
+/// 快照包含的表（按外键依赖顺序排列）
+pub const SNAPSHOT_TABLES: &[&str] = &[
+    "patients",
+    "studies",
+    "series",
+    "instances",
+    "radiation_doses",
+    "instance_relationships",
+    "forward_queue",
+    "study_stability",
+    "study_operations",
+    "study_operation_items",
+    "rejected_instances",
+    "study_tags",
+];
+
+/// 单表导出数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableDump {
+    /// 表名
+    pub name: String,
+    /// 行数据（列名到值的JSON对象）
+    pub rows: Vec<serde_json::Value>,
+}
+
+/// 数据库逻辑快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseSnapshot {
+    /// 快照时间
+    pub taken_at: DateTime<Utc>,
+    /// 各表数据
+    pub tables: Vec<TableDump>,
+}
+
+impl DatabaseSnapshot {
+    /// 导出当前数据库
+    pub async fn capture(pool: &DatabasePool) -> Result<Self> {
+        let mut tx = pool
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        let taken_at = Utc::now();
+        let mut tables = Vec::with_capacity(SNAPSHOT_TABLES.len());
+        for table in SNAPSHOT_TABLES {
+            let rows = sqlx::query(&format!(
+                "SELECT row_to_json(t)::TEXT AS row FROM {} t",
+                table
+            ))
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?
+            .into_iter()
+            .map(|row| serde_json::from_str(row.get::<&str, _>("row")))
+            .collect::<std::result::Result<Vec<serde_json::Value>, _>>()?;
+            tables.push(TableDump {
+                name: table.to_string(),
+                rows,
+            });
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        tracing::info!(
+            "Captured database snapshot with {} rows",
+            tables.iter().map(|t| t.rows.len()).sum::<usize>()
+        );
+        Ok(Self { taken_at, tables })
+    }
+
+    /// 用快照内容替换数据库中的对应表
+    pub async fn restore(&self, pool: &DatabasePool) -> Result<()> {
+        self.stage_restore(pool).await?.commit().await
+    }
+
+    /// 在未提交的事务中用快照内容替换对应表
+    ///
+    /// 提交前其他连接看到的仍是原有数据，写入这些表会被阻塞；
+    /// 丢弃返回值即回滚
+    pub async fn stage_restore(&self, pool: &DatabasePool) -> Result<StagedRestore> {
+        for table in &self.tables {
+            if !SNAPSHOT_TABLES.contains(&table.name.as_str()) {
+                return Err(PacsError::Validation(format!(
+                    "Snapshot contains unknown table {}",
+                    table.name
+                )));
+            }
+        }
+
+        let mut tx = pool
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        sqlx::query(&format!("TRUNCATE {} CASCADE", SNAPSHOT_TABLES.join(", ")))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        for table in SNAPSHOT_TABLES {
+            let Some(dump) = self.tables.iter().find(|t| t.name == *table) else {
+                continue;
+            };
+            if dump.rows.is_empty() {
+                continue;
+            }
+
+            sqlx::query(&format!(
+                "INSERT INTO {0} SELECT * FROM json_populate_recordset(NULL::{0}, $1::JSON)",
+                table
+            ))
+            .bind(serde_json::to_string(&dump.rows)?)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| PacsError::Database(format!("Failed to restore {}: {}", table, e)))?;
+        }
+
+        Ok(StagedRestore {
+            tx,
+            taken_at: self.taken_at,
+        })
+    }
+
+    /// 指定表的行数
+    pub fn row_count(&self, table: &str) -> usize {
+        self.tables
+            .iter()
+            .find(|t| t.name == table)
+            .map_or(0, |t| t.rows.len())
+    }
+
+    /// 快照中实例的SOP实例UID与文件路径
+    pub fn instance_files(&self) -> Vec<(String, String)> {
+        self.tables
+            .iter()
+            .filter(|t| t.name == "instances")
+            .flat_map(|t| t.rows.iter())
+            .filter_map(|row| {
+                Some((
+                    row.get("sop_instance_uid")?.as_str()?.to_string(),
+                    row.get("file_path")?.as_str()?.to_string(),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// 已写入但尚未提交的快照恢复
+pub struct StagedRestore {
+    /// 恢复所在的事务
+    tx: Transaction<'static, Postgres>,
+    /// 快照时间
+    taken_at: DateTime<Utc>,
+}
+
+impl StagedRestore {
+    /// 提交恢复
+    pub async fn commit(self) -> Result<()> {
+        self.tx
+            .commit()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        tracing::info!("Restored database snapshot taken at {}", self.taken_at);
+        Ok(())
+    }
+
+    /// 放弃恢复，数据库保持原样
+    pub async fn rollback(self) -> Result<()> {
+        self.tx
+            .rollback()
+            .await
+            .map_err(|e| PacsError::Database(e.to_string()))?;
+
+        tracing::warn!(
+            "Rolled back restore of database snapshot taken at {}",
+            self.taken_at
+        );
+        Ok(())
+    }
+}
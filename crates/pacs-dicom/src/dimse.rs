@@ -58,6 +58,8 @@ impl CommandSet {
             0x0021 => CommandType::CMove,
             0x0010 => CommandType::CGet,
             0x0FFF => CommandType::CCancel,
+            0x0140 => CommandType::NCreate,
+            0x0120 => CommandType::NSet,
             _ => CommandType::Unknown,
         }
     }
@@ -72,5 +74,7 @@ pub enum CommandType {
     CMove,
     CGet,
     CCancel,
+    NCreate,
+    NSet,
     Unknown,
 }
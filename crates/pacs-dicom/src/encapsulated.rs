@@ -0,0 +1,297 @@
+//! 封装文档（Encapsulated PDF/CDA）处理
+//!
+//! 从封装文档实例的Encapsulated Document (0042,0011)中提取原始文档，
+//! 以及将上传的PDF封装为附属于已有检查的新Encapsulated PDF实例。
+
+use chrono::Utc;
+use dicom::core::{DataElement, PrimitiveValue, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::{DefaultDicomObject, FileMetaTableBuilder, InMemDicomObject};
+use pacs_core::utils::generate_dicom_uid;
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// PDF MIME类型
+pub const PDF_MIME_TYPE: &str = "application/pdf";
+/// CDA MIME类型
+pub const CDA_MIME_TYPE: &str = "text/xml";
+
+/// 封装文档
+#[derive(Debug, Clone)]
+pub struct EncapsulatedDocument {
+    /// SOP实例UID
+    pub sop_instance_uid: String,
+    /// SOP类UID
+    pub sop_class_uid: String,
+    /// 文档MIME类型
+    pub mime_type: String,
+    /// 文档标题
+    pub document_title: Option<String>,
+    /// 文档内容
+    pub data: Vec<u8>,
+}
+
+impl EncapsulatedDocument {
+    /// 文档摘要信息
+    pub fn info(&self) -> EncapsulatedDocumentInfo {
+        EncapsulatedDocumentInfo {
+            mime_type: self.mime_type.clone(),
+            document_title: self.document_title.clone(),
+            length: self.data.len() as u64,
+        }
+    }
+
+    /// 按MIME类型推荐的文件扩展名
+    pub fn file_extension(&self) -> &'static str {
+        match self.mime_type.as_str() {
+            PDF_MIME_TYPE => "pdf",
+            CDA_MIME_TYPE | "application/xml" => "xml",
+            _ => "bin",
+        }
+    }
+}
+
+/// 封装文档摘要信息（入库时记录）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncapsulatedDocumentInfo {
+    /// 文档MIME类型
+    pub mime_type: String,
+    /// 文档标题
+    pub document_title: Option<String>,
+    /// 文档长度（字节）
+    pub length: u64,
+}
+
+/// 新建Encapsulated PDF实例所需的检查信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncapsulatedPdfRequest {
+    /// 所属检查实例UID
+    pub study_instance_uid: String,
+    /// 患者ID
+    pub patient_id: String,
+    /// 患者姓名
+    pub patient_name: String,
+    /// 患者出生日期（YYYYMMDD）
+    pub patient_birth_date: Option<String>,
+    /// 患者性别
+    pub patient_sex: Option<String>,
+    /// 检查号
+    pub accession_number: Option<String>,
+    /// 文档标题
+    pub document_title: String,
+}
+
+/// 封装文档处理器
+pub struct EncapsulatedDocumentHandler;
+
+impl EncapsulatedDocumentHandler {
+    /// 判断SOP Class是否为封装文档
+    pub fn is_encapsulated_document(sop_class_uid: &str) -> bool {
+        matches!(
+            sop_class_uid.trim_end_matches('\0'),
+            uids::ENCAPSULATED_PDF_STORAGE | uids::ENCAPSULATED_CDA_STORAGE
+        )
+    }
+
+    /// 从DICOM字节流提取封装文档
+    pub fn extract_bytes(data: &[u8]) -> Result<EncapsulatedDocument> {
+        let obj = crate::parser::DicomParser::read_object(data)?;
+        Self::extract(&obj)
+            .ok_or_else(|| PacsError::Validation("DICOM对象不是封装PDF/CDA文档".to_string()))
+    }
+
+    /// 提取封装文档，非封装文档对象返回None
+    pub fn extract(obj: &InMemDicomObject) -> Option<EncapsulatedDocument> {
+        let sop_class_uid = get_string(obj, tags::SOP_CLASS_UID)?;
+        if !Self::is_encapsulated_document(&sop_class_uid) {
+            return None;
+        }
+
+        let mut data = obj
+            .element(tags::ENCAPSULATED_DOCUMENT)
+            .ok()?
+            .to_bytes()
+            .ok()?
+            .into_owned();
+
+        // 去除为满足偶数长度而添加的填充字节
+        let declared_length = obj
+            .element(tags::ENCAPSULATED_DOCUMENT_LENGTH)
+            .ok()
+            .and_then(|e| e.to_int::<u32>().ok());
+        match declared_length {
+            Some(length) if (length as usize) <= data.len() => data.truncate(length as usize),
+            _ => {
+                if data.last() == Some(&0) {
+                    data.pop();
+                }
+            }
+        }
+
+        let mime_type =
+            get_string(obj, tags::MIME_TYPE_OF_ENCAPSULATED_DOCUMENT).unwrap_or_else(|| {
+                if sop_class_uid.trim_end_matches('\0') == uids::ENCAPSULATED_CDA_STORAGE {
+                    CDA_MIME_TYPE.to_string()
+                } else {
+                    PDF_MIME_TYPE.to_string()
+                }
+            });
+
+        let document = EncapsulatedDocument {
+            sop_instance_uid: get_string(obj, tags::SOP_INSTANCE_UID).unwrap_or_default(),
+            sop_class_uid,
+            mime_type,
+            document_title: get_string(obj, tags::DOCUMENT_TITLE),
+            data,
+        };
+        debug!(
+            "提取封装文档: {} ({}, {} 字节)",
+            document.sop_instance_uid,
+            document.mime_type,
+            document.data.len()
+        );
+        Some(document)
+    }
+
+    /// 将PDF封装为附属于已有检查的新Encapsulated PDF实例
+    pub fn wrap_pdf(pdf: &[u8], request: &EncapsulatedPdfRequest) -> Result<DefaultDicomObject> {
+        if !pdf.starts_with(b"%PDF") {
+            return Err(PacsError::Validation("上传内容不是PDF文档".to_string()));
+        }
+
+        let sop_instance_uid = generate_dicom_uid();
+        let series_instance_uid = generate_dicom_uid();
+        let now = Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let time = now.format("%H%M%S").to_string();
+
+        let mut document = pdf.to_vec();
+        if !document.len().is_multiple_of(2) {
+            document.push(0);
+        }
+
+        let mut obj = InMemDicomObject::new_empty();
+        let strings = [
+            (tags::SPECIFIC_CHARACTER_SET, VR::CS, "ISO_IR 192"),
+            (tags::SOP_CLASS_UID, VR::UI, uids::ENCAPSULATED_PDF_STORAGE),
+            (tags::SOP_INSTANCE_UID, VR::UI, sop_instance_uid.as_str()),
+            (
+                tags::STUDY_INSTANCE_UID,
+                VR::UI,
+                request.study_instance_uid.as_str(),
+            ),
+            (
+                tags::SERIES_INSTANCE_UID,
+                VR::UI,
+                series_instance_uid.as_str(),
+            ),
+            (tags::PATIENT_ID, VR::LO, request.patient_id.as_str()),
+            (tags::PATIENT_NAME, VR::PN, request.patient_name.as_str()),
+            (tags::MODALITY, VR::CS, "DOC"),
+            (tags::CONVERSION_TYPE, VR::CS, "SD"),
+            (tags::SERIES_NUMBER, VR::IS, "999"),
+            (tags::INSTANCE_NUMBER, VR::IS, "1"),
+            (tags::CONTENT_DATE, VR::DA, date.as_str()),
+            (tags::CONTENT_TIME, VR::TM, time.as_str()),
+            (tags::BURNED_IN_ANNOTATION, VR::CS, "YES"),
+            (
+                tags::DOCUMENT_TITLE,
+                VR::ST,
+                request.document_title.as_str(),
+            ),
+            (
+                tags::MIME_TYPE_OF_ENCAPSULATED_DOCUMENT,
+                VR::LO,
+                PDF_MIME_TYPE,
+            ),
+        ];
+        for (tag, vr, value) in strings {
+            obj.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+        }
+
+        let optional_strings = [
+            (
+                tags::PATIENT_BIRTH_DATE,
+                VR::DA,
+                &request.patient_birth_date,
+            ),
+            (tags::PATIENT_SEX, VR::CS, &request.patient_sex),
+            (tags::ACCESSION_NUMBER, VR::SH, &request.accession_number),
+        ];
+        for (tag, vr, value) in optional_strings {
+            obj.put(DataElement::new(
+                tag,
+                vr,
+                PrimitiveValue::from(value.as_deref().unwrap_or("")),
+            ));
+        }
+
+        obj.put(DataElement::new(
+            tags::ENCAPSULATED_DOCUMENT_LENGTH,
+            VR::UL,
+            PrimitiveValue::from(pdf.len() as u32),
+        ));
+        obj.put(DataElement::new(
+            tags::ENCAPSULATED_DOCUMENT,
+            VR::OB,
+            PrimitiveValue::from(document),
+        ));
+
+        info!(
+            "封装PDF文档: {} -> 检查 {}",
+            sop_instance_uid, request.study_instance_uid
+        );
+
+        obj.with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+            .map_err(|e| PacsError::Dicom(format!("无法生成文件元信息: {:?}", e)))
+    }
+
+    /// 将PDF封装为新实例并编码为DICOM文件字节
+    pub fn wrap_pdf_bytes(pdf: &[u8], request: &EncapsulatedPdfRequest) -> Result<Vec<u8>> {
+        let obj = Self::wrap_pdf(pdf, request)?;
+        crate::ingest::IngestPipeline::encode_object(&obj)
+    }
+}
+
+/// 获取去除填充字符后的字符串值
+fn get_string(obj: &InMemDicomObject, tag: dicom::core::Tag) -> Option<String> {
+    obj.element(tag)
+        .ok()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_and_extract_pdf() {
+        // 奇数长度，验证填充字节被正确去除
+        let pdf = b"%PDF-1.4\n%%EOF".to_vec();
+        let request = EncapsulatedPdfRequest {
+            study_instance_uid: "1.2.3".to_string(),
+            patient_id: "PAT001".to_string(),
+            patient_name: "Doe^John".to_string(),
+            patient_birth_date: None,
+            patient_sex: Some("M".to_string()),
+            accession_number: Some("ACC001".to_string()),
+            document_title: "申请单".to_string(),
+        };
+
+        let bytes = EncapsulatedDocumentHandler::wrap_pdf_bytes(&pdf, &request).unwrap();
+        let document = EncapsulatedDocumentHandler::extract_bytes(&bytes).unwrap();
+
+        assert_eq!(document.data, pdf);
+        assert_eq!(document.mime_type, PDF_MIME_TYPE);
+        assert_eq!(document.document_title.as_deref(), Some("申请单"));
+        assert_eq!(document.file_extension(), "pdf");
+        assert!(EncapsulatedDocumentHandler::is_encapsulated_document(
+            &document.sop_class_uid
+        ));
+
+        assert!(EncapsulatedDocumentHandler::wrap_pdf(b"not a pdf", &request).is_err());
+    }
+}
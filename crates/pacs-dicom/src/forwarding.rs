@@ -0,0 +1,762 @@
+//! 自动转发（DICOM路由）
+//!
+//! 对每个入库实例评估转发规则，命中的实例写入数据库持久化的按目的地队列。
+//! 后台投递循环在检查稳定（一段时间内没有新实例到达）后以C-STORE整检查发送，
+//! 失败按指数退避重试，并限制每个目的地的并发关联数。
+
+use crate::ingest::{IngestListener, IngestSource, IngestedInstance};
+use crate::parser::{DicomParser, ParsedDicomObject};
+use crate::scu::{DicomScu, RemoteAe};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dicom::core::dictionary::DataDictionary;
+use dicom::dictionary_std::StandardDataDictionary;
+use dicom::object::InMemDicomObject;
+use pacs_core::{ObjectAvailability, ObjectRecall, Result};
+use pacs_database::{DatabasePool, DatabaseQueries, DbForwardTask, NewForwardTask};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+/// 转发目的地
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardingDestination {
+    /// 目的地名称（规则中引用）
+    pub name: String,
+    /// 远端AE
+    pub remote: RemoteAe,
+    /// 最大并发关联数
+    pub max_concurrency: usize,
+    /// 是否启用
+    pub enabled: bool,
+}
+
+impl ForwardingDestination {
+    /// 创建目的地，默认单关联发送
+    pub fn new(name: impl Into<String>, remote: RemoteAe) -> Self {
+        Self {
+            name: name.into(),
+            remote,
+            max_concurrency: 1,
+            enabled: true,
+        }
+    }
+}
+
+/// 转发规则条件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// 模态（忽略大小写）
+    Modality { values: Vec<String> },
+    /// 发送方AE标题
+    CallingAeTitle { values: Vec<String> },
+    /// SOP类UID
+    SopClass { values: Vec<String> },
+    /// 任意标签，`tag` 为关键字或 `(gggg,eeee)`，`pattern` 支持 `*` 与 `?` 通配符
+    Tag { tag: String, pattern: String },
+}
+
+impl RuleCondition {
+    /// 是否需要读取完整数据集才能判断
+    pub fn requires_dataset(&self) -> bool {
+        matches!(self, RuleCondition::Tag { .. })
+    }
+
+    /// 判断条件是否满足
+    pub fn matches(&self, context: &ForwardingContext<'_>) -> bool {
+        match self {
+            RuleCondition::Modality { values } => context
+                .metadata
+                .modality
+                .as_deref()
+                .is_some_and(|modality| values.iter().any(|v| v.eq_ignore_ascii_case(modality))),
+            RuleCondition::CallingAeTitle { values } => context
+                .calling_ae_title
+                .is_some_and(|ae| values.iter().any(|v| v.trim() == ae.trim())),
+            RuleCondition::SopClass { values } => context
+                .metadata
+                .sop_class_uid
+                .as_deref()
+                .is_some_and(|uid| values.iter().any(|v| v == uid)),
+            RuleCondition::Tag { tag, pattern } => {
+                let Some(tag) = StandardDataDictionary.parse_tag(tag) else {
+                    warn!("转发规则中的标签无法识别: {}", tag);
+                    return false;
+                };
+                context
+                    .dataset
+                    .and_then(|obj| obj.element(tag).ok())
+                    .and_then(|e| e.to_str().ok())
+                    .is_some_and(|value| {
+                        wildcard_match(pattern, value.trim_end_matches(['\0', ' ']))
+                    })
+            }
+        }
+    }
+}
+
+/// 转发规则，所有条件同时满足时转发到全部目的地
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardingRule {
+    /// 规则名称
+    pub name: String,
+    /// 是否启用
+    pub enabled: bool,
+    /// 匹配条件（为空时匹配所有实例）
+    pub conditions: Vec<RuleCondition>,
+    /// 目的地名称
+    pub destinations: Vec<String>,
+}
+
+impl ForwardingRule {
+    /// 判断实例是否命中规则
+    pub fn matches(&self, context: &ForwardingContext<'_>) -> bool {
+        self.enabled && self.conditions.iter().all(|c| c.matches(context))
+    }
+
+    /// 是否需要读取完整数据集才能判断
+    pub fn requires_dataset(&self) -> bool {
+        self.enabled && self.conditions.iter().any(RuleCondition::requires_dataset)
+    }
+}
+
+/// 规则评估上下文
+pub struct ForwardingContext<'a> {
+    /// 实例元数据
+    pub metadata: &'a ParsedDicomObject,
+    /// 发送方AE标题
+    pub calling_ae_title: Option<&'a str>,
+    /// 完整数据集（仅标签条件需要）
+    pub dataset: Option<&'a InMemDicomObject>,
+}
+
+/// 重试策略
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// 最大尝试次数（达到后标记为最终失败）
+    pub max_attempts: u32,
+    /// 首次重试等待时间
+    pub initial_backoff: Duration,
+    /// 最长重试等待时间
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// 第 `attempts` 次失败后的等待时间（指数退避）
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            initial_backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// 转发配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardingConfig {
+    /// 检查稳定时间：最后一个实例到达后等待多久再发送
+    pub stability_period: Duration,
+    /// 队列轮询间隔
+    pub poll_interval: Duration,
+    /// 单个关联最多发送的实例数
+    pub batch_size: i64,
+    /// 重试策略
+    pub retry: RetryPolicy,
+}
+
+impl Default for ForwardingConfig {
+    fn default() -> Self {
+        Self {
+            stability_period: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(5),
+            batch_size: 500,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// 目的地队列状态（管理界面展示）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardQueueStatus {
+    /// 目的地名称
+    pub destination: String,
+    /// 远端AE
+    pub remote: Option<RemoteAe>,
+    /// 是否启用
+    pub enabled: bool,
+    /// 待发送数
+    pub pending: i64,
+    /// 发送中数
+    pub in_progress: i64,
+    /// 已完成数
+    pub completed: i64,
+    /// 最终失败数
+    pub failed: i64,
+    /// 最早待发送任务的入队时间
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+    /// 当前活动关联数
+    pub active_associations: usize,
+}
+
+/// 转发路由器
+pub struct ForwardingRouter {
+    /// 数据库连接池
+    db: Arc<DatabasePool>,
+    /// C-STORE客户端
+    scu: DicomScu,
+    /// 转发配置
+    config: ForwardingConfig,
+    /// 转发规则
+    rules: RwLock<Vec<ForwardingRule>>,
+    /// 目的地
+    destinations: RwLock<HashMap<String, ForwardingDestination>>,
+    /// 每个目的地的并发关联许可
+    permits: RwLock<HashMap<String, Arc<Semaphore>>>,
+    /// 对象召回服务（源文件可能已迁移到归档层级时配置）
+    recall: Option<Arc<dyn ObjectRecall>>,
+}
+
+impl ForwardingRouter {
+    /// 创建转发路由器
+    pub fn new(db: Arc<DatabasePool>, scu: DicomScu, config: ForwardingConfig) -> Self {
+        Self {
+            db,
+            scu,
+            config,
+            rules: RwLock::new(Vec::new()),
+            destinations: RwLock::new(HashMap::new()),
+            permits: RwLock::new(HashMap::new()),
+            recall: None,
+        }
+    }
+
+    /// 设置对象召回服务，发送前确保源文件在线
+    pub fn with_object_recall(mut self, recall: Arc<dyn ObjectRecall>) -> Self {
+        self.recall = Some(recall);
+        self
+    }
+
+    /// 添加或替换目的地
+    pub async fn add_destination(&self, destination: ForwardingDestination) {
+        info!(
+            "添加转发目的地: {} -> {}",
+            destination.name, destination.remote
+        );
+        self.permits.write().await.insert(
+            destination.name.clone(),
+            Arc::new(Semaphore::new(destination.max_concurrency.max(1))),
+        );
+        self.destinations
+            .write()
+            .await
+            .insert(destination.name.clone(), destination);
+    }
+
+    /// 删除目的地（已入队任务保留在数据库中）
+    pub async fn remove_destination(&self, name: &str) -> Option<ForwardingDestination> {
+        self.permits.write().await.remove(name);
+        self.destinations.write().await.remove(name)
+    }
+
+    /// 获取所有目的地
+    pub async fn get_destinations(&self) -> Vec<ForwardingDestination> {
+        self.destinations.read().await.values().cloned().collect()
+    }
+
+    /// 添加或替换规则
+    pub async fn add_rule(&self, rule: ForwardingRule) {
+        info!("添加转发规则: {} -> {:?}", rule.name, rule.destinations);
+        let mut rules = self.rules.write().await;
+        rules.retain(|r| r.name != rule.name);
+        rules.push(rule);
+    }
+
+    /// 删除规则
+    pub async fn remove_rule(&self, name: &str) -> bool {
+        let mut rules = self.rules.write().await;
+        let before = rules.len();
+        rules.retain(|r| r.name != name);
+        rules.len() != before
+    }
+
+    /// 获取所有规则
+    pub async fn get_rules(&self) -> Vec<ForwardingRule> {
+        self.rules.read().await.clone()
+    }
+
+    /// 评估规则，返回 (规则名称, 目的地名称)，同一目的地只保留第一个命中的规则
+    pub async fn evaluate(&self, context: &ForwardingContext<'_>) -> Vec<(String, String)> {
+        let rules = self.rules.read().await;
+        let mut targets: Vec<(String, String)> = Vec::new();
+        for rule in rules.iter().filter(|r| r.matches(context)) {
+            for destination in &rule.destinations {
+                if !targets.iter().any(|(_, d)| d == destination) {
+                    targets.push((rule.name.clone(), destination.clone()));
+                }
+            }
+        }
+        targets
+    }
+
+    /// 为入库实例评估规则并写入转发队列，返回入队的目的地数
+    pub async fn route(&self, instance: &IngestedInstance, source: &IngestSource) -> Result<usize> {
+        let requires_dataset = self
+            .rules
+            .read()
+            .await
+            .iter()
+            .any(ForwardingRule::requires_dataset);
+        let dataset = if requires_dataset {
+            let data = tokio::fs::read(&instance.file_path).await?;
+            Some(DicomParser::read_object(&data)?)
+        } else {
+            None
+        };
+
+        let calling_ae_title = match source {
+            IngestSource::CStore { calling_ae_title } => calling_ae_title.as_deref(),
+            _ => None,
+        };
+        let context = ForwardingContext {
+            metadata: &instance.metadata,
+            calling_ae_title,
+            dataset: dataset.as_deref(),
+        };
+        let targets = self.evaluate(&context).await;
+        if targets.is_empty() {
+            return Ok(0);
+        }
+
+        let study_uid = instance
+            .metadata
+            .study_instance_uid
+            .clone()
+            .unwrap_or_default();
+        let ready_at = Utc::now() + stability_delay(self.config.stability_period);
+        let destinations = self.destinations.read().await;
+        let queries = DatabaseQueries::new(&self.db);
+        let mut queued = 0;
+
+        for (rule_name, destination) in targets {
+            if !destinations.contains_key(&destination) {
+                warn!(
+                    "转发规则 {} 引用了不存在的目的地: {}",
+                    rule_name, destination
+                );
+                continue;
+            }
+
+            let task = NewForwardTask {
+                id: Uuid::new_v4(),
+                destination: destination.clone(),
+                rule_name,
+                study_uid: study_uid.clone(),
+                sop_instance_uid: instance.sop_instance_uid.clone(),
+                file_path: instance.file_path.to_string_lossy().to_string(),
+                next_attempt_at: ready_at,
+            };
+            if queries.enqueue_forward_task(&task).await? {
+                queued += 1;
+            }
+            // 检查仍在接收新实例，推迟整检查的发送时间
+            queries
+                .postpone_study_forward_tasks(&destination, &study_uid, ready_at)
+                .await?;
+        }
+
+        debug!(
+            "实例 {} 已加入 {} 个转发队列",
+            instance.sop_instance_uid, queued
+        );
+        Ok(queued)
+    }
+
+    /// 恢复上次运行中断时处于发送中的任务
+    pub async fn recover(&self) -> Result<u64> {
+        let count = DatabaseQueries::new(&self.db)
+            .reset_in_progress_forward_tasks()
+            .await?;
+        if count > 0 {
+            info!("恢复 {} 个中断的转发任务", count);
+        }
+        Ok(count)
+    }
+
+    /// 启动后台投递循环
+    pub fn start(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let router = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = router.recover().await {
+                error!("恢复转发队列失败: {}", e);
+            }
+
+            let mut interval = tokio::time::interval(router.config.poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = router.dispatch_once().await {
+                    error!("转发队列投递失败: {}", e);
+                }
+            }
+        })
+    }
+
+    /// 为所有目的地领取到期任务并启动发送，返回启动的关联数
+    pub async fn dispatch_once(self: &Arc<Self>) -> Result<usize> {
+        let destinations: Vec<ForwardingDestination> = self
+            .destinations
+            .read()
+            .await
+            .values()
+            .filter(|d| d.enabled)
+            .cloned()
+            .collect();
+        let queries = DatabaseQueries::new(&self.db);
+        let mut started = 0;
+
+        for destination in destinations {
+            let Some(semaphore) = self.permits.read().await.get(&destination.name).cloned() else {
+                continue;
+            };
+
+            // 每个空闲许可领取一个检查的一批任务
+            while let Ok(permit) = semaphore.clone().try_acquire_owned() {
+                let tasks = queries
+                    .claim_forward_batch(&destination.name, self.config.batch_size)
+                    .await?;
+                if tasks.is_empty() {
+                    break;
+                }
+
+                let router = self.clone();
+                let destination = destination.clone();
+                tokio::spawn(async move {
+                    router.deliver(&destination, tasks, permit).await;
+                });
+                started += 1;
+            }
+        }
+
+        Ok(started)
+    }
+
+    /// 通过一个关联发送一批任务并更新队列状态
+    async fn deliver(
+        &self,
+        destination: &ForwardingDestination,
+        tasks: Vec<DbForwardTask>,
+        _permit: OwnedSemaphorePermit,
+    ) {
+        info!(
+            "转发 {} 个实例到 {} (检查 {})",
+            tasks.len(),
+            destination.name,
+            tasks.first().map(|t| t.study_uid.as_str()).unwrap_or("")
+        );
+
+        let files: Vec<PathBuf> = tasks.iter().map(|t| PathBuf::from(&t.file_path)).collect();
+        let queries = DatabaseQueries::new(&self.db);
+
+        if let Some(retry_after) = self.pending_recall(&files).await {
+            info!(
+                "转发到 {} 的源文件召回中，{}秒后重试",
+                destination.name,
+                retry_after.as_secs()
+            );
+            let next_attempt_at = Utc::now() + stability_delay(retry_after);
+            for task in &tasks {
+                if let Err(e) = queries
+                    .defer_forward_task(&task.id, "源文件召回中", next_attempt_at)
+                    .await
+                {
+                    error!("更新转发任务状态失败: {} ({})", task.id, e);
+                }
+            }
+            return;
+        }
+
+        let results = match self.scu.store_files(&destination.remote, files).await {
+            Ok(results) => results,
+            Err(e) => {
+                warn!("转发到 {} 失败: {}", destination.name, e);
+                for task in &tasks {
+                    self.record_failure(&queries, task, &e.to_string()).await;
+                }
+                return;
+            }
+        };
+
+        let results: HashMap<PathBuf, _> = results
+            .into_iter()
+            .map(|r| (r.file_path.clone(), r))
+            .collect();
+        for task in &tasks {
+            let outcome = match results.get(&PathBuf::from(&task.file_path)) {
+                Some(result) if result.is_success() => {
+                    queries.complete_forward_task(&task.id).await
+                }
+                Some(result) => {
+                    let message = result
+                        .error
+                        .clone()
+                        .unwrap_or_else(|| format!("状态 0x{:04X}", result.status));
+                    self.record_failure(&queries, task, &message).await;
+                    Ok(())
+                }
+                None => {
+                    self.record_failure(&queries, task, "未返回发送结果").await;
+                    Ok(())
+                }
+            };
+            if let Err(e) = outcome {
+                error!("更新转发任务状态失败: {} ({})", task.id, e);
+            }
+        }
+    }
+
+    /// 确保所有源文件在线，任一文件仍在召回中时返回建议的重试间隔
+    ///
+    /// 对每个文件都发起检查，使整检查的召回同时启动
+    async fn pending_recall(&self, files: &[PathBuf]) -> Option<Duration> {
+        let recall = self.recall.as_ref()?;
+        let mut retry_after: Option<u64> = None;
+        for file in files {
+            match recall.ensure_available(file).await {
+                Ok(ObjectAvailability::RecallInProgress {
+                    retry_after_secs, ..
+                }) => {
+                    retry_after = Some(retry_after.unwrap_or(0).max(retry_after_secs));
+                }
+                Ok(_) => {}
+                // 召回失败时照常发送，由发送结果记录失败
+                Err(e) => warn!("召回源文件失败: {} ({})", file.display(), e),
+            }
+        }
+        retry_after.map(Duration::from_secs)
+    }
+
+    /// 记录发送失败，按重试策略重新排队或标记为最终失败
+    async fn record_failure(
+        &self,
+        queries: &DatabaseQueries<'_>,
+        task: &DbForwardTask,
+        message: &str,
+    ) {
+        let attempts = task.attempts.max(0) as u32 + 1;
+        let retry_at = (attempts < self.config.retry.max_attempts)
+            .then(|| Utc::now() + stability_delay(self.config.retry.backoff(attempts)));
+        if retry_at.is_none() {
+            warn!(
+                "转发任务已达最大重试次数: {} -> {} ({})",
+                task.sop_instance_uid, task.destination, message
+            );
+        }
+
+        if let Err(e) = queries.fail_forward_task(&task.id, message, retry_at).await {
+            error!("更新转发任务状态失败: {} ({})", task.id, e);
+        }
+    }
+
+    /// 获取各目的地的队列状态
+    pub async fn queue_status(&self) -> Result<Vec<ForwardQueueStatus>> {
+        let stats = DatabaseQueries::new(&self.db)
+            .get_forward_queue_stats()
+            .await?;
+        let destinations = self.destinations.read().await;
+        let permits = self.permits.read().await;
+
+        let active = |name: &str| match (destinations.get(name), permits.get(name)) {
+            (Some(d), Some(s)) => d.max_concurrency.max(1) - s.available_permits(),
+            _ => 0,
+        };
+
+        let mut status: Vec<ForwardQueueStatus> = stats
+            .into_iter()
+            .map(|s| ForwardQueueStatus {
+                remote: destinations.get(&s.destination).map(|d| d.remote.clone()),
+                enabled: destinations.get(&s.destination).is_some_and(|d| d.enabled),
+                active_associations: active(&s.destination),
+                destination: s.destination,
+                pending: s.pending,
+                in_progress: s.in_progress,
+                completed: s.completed,
+                failed: s.failed,
+                oldest_pending_at: s.oldest_pending_at,
+            })
+            .collect();
+
+        // 尚无任务的目的地也需要展示
+        for destination in destinations.values() {
+            if !status.iter().any(|s| s.destination == destination.name) {
+                status.push(ForwardQueueStatus {
+                    destination: destination.name.clone(),
+                    remote: Some(destination.remote.clone()),
+                    enabled: destination.enabled,
+                    pending: 0,
+                    in_progress: 0,
+                    completed: 0,
+                    failed: 0,
+                    oldest_pending_at: None,
+                    active_associations: active(&destination.name),
+                });
+            }
+        }
+        status.sort_by(|a, b| a.destination.cmp(&b.destination));
+        Ok(status)
+    }
+
+    /// 获取最终失败的转发任务
+    pub async fn get_failed_tasks(
+        &self,
+        destination: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<DbForwardTask>> {
+        DatabaseQueries::new(&self.db)
+            .get_failed_forward_tasks(destination, limit)
+            .await
+    }
+
+    /// 将最终失败的任务重新排队
+    pub async fn retry_failed(&self, destination: Option<&str>) -> Result<u64> {
+        let count = DatabaseQueries::new(&self.db)
+            .retry_failed_forward_tasks(destination)
+            .await?;
+        info!("重新排队 {} 个失败的转发任务", count);
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl IngestListener for ForwardingRouter {
+    async fn on_instance_stored(
+        &self,
+        instance: &IngestedInstance,
+        source: &IngestSource,
+    ) -> Result<()> {
+        self.route(instance, source).await.map(|_| ())
+    }
+}
+
+/// 将标准库时长转换为chrono时长
+fn stability_delay(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX)
+}
+
+/// DICOM风格通配符匹配：`*` 匹配任意串，`?` 匹配单个字符
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::{DataElement, PrimitiveValue, VR};
+    use dicom::dictionary_std::tags;
+
+    #[test]
+    fn test_rule_matching() {
+        let metadata = ParsedDicomObject {
+            modality: Some("CT".to_string()),
+            sop_class_uid: Some("1.2.840.10008.5.1.4.1.1.2".to_string()),
+            ..Default::default()
+        };
+        let dataset = InMemDicomObject::from_element_iter([DataElement::new(
+            tags::INSTITUTION_NAME,
+            VR::LO,
+            PrimitiveValue::from("City Hospital East"),
+        )]);
+        let context = ForwardingContext {
+            metadata: &metadata,
+            calling_ae_title: Some("CT_SCANNER1"),
+            dataset: Some(&dataset),
+        };
+
+        let rule = ForwardingRule {
+            name: "ct-to-3d".to_string(),
+            enabled: true,
+            conditions: vec![
+                RuleCondition::Modality {
+                    values: vec!["ct".to_string()],
+                },
+                RuleCondition::CallingAeTitle {
+                    values: vec!["CT_SCANNER1".to_string()],
+                },
+                RuleCondition::Tag {
+                    tag: "InstitutionName".to_string(),
+                    pattern: "City*East".to_string(),
+                },
+            ],
+            destinations: vec!["3DLAB".to_string()],
+        };
+        assert!(rule.matches(&context));
+        assert!(rule.requires_dataset());
+
+        let mg_rule = ForwardingRule {
+            conditions: vec![RuleCondition::Modality {
+                values: vec!["MG".to_string()],
+            }],
+            ..rule.clone()
+        };
+        assert!(!mg_rule.matches(&context));
+
+        let tag_rule = ForwardingRule {
+            conditions: vec![RuleCondition::Tag {
+                tag: "(0008,0080)".to_string(),
+                pattern: "?ity Hospital West".to_string(),
+            }],
+            ..rule
+        };
+        assert!(!tag_rule.matches(&context));
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(10));
+        assert_eq!(policy.backoff(2), Duration::from_secs(20));
+        assert_eq!(policy.backoff(3), Duration::from_secs(40));
+        assert_eq!(policy.backoff(4), Duration::from_secs(60));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("A*B?D", "AxxBcD"));
+        assert!(!wildcard_match("A*B", "AxxC"));
+    }
+}
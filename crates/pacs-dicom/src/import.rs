@@ -0,0 +1,617 @@
+//! DICOM介质与目录批量导入
+//!
+//! 遍历DICOMDIR或任意目录树，逐个文件通过入库管道导入，
+//! 导入任务记录持久化为JSON文件，支持中断后续传，并生成逐文件错误报告。
+
+use crate::ingest::{IngestOutcome, IngestPipeline, IngestSource, ReconciliationPolicy};
+use chrono::{DateTime, Utc};
+use dicom::dictionary_std::tags;
+use dicom::object::open_file;
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+/// DICOMDIR文件名
+const DICOMDIR_NAME: &str = "DICOMDIR";
+
+/// 导入来源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ImportSource {
+    /// DICOMDIR索引的介质（路径指向DICOMDIR文件）
+    Dicomdir(PathBuf),
+    /// 任意目录树
+    Directory(PathBuf),
+}
+
+/// 导入任务状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ImportJobStatus {
+    /// 等待中
+    Pending,
+    /// 进行中
+    Running,
+    /// 已完成
+    Completed,
+    /// 已完成但部分文件失败
+    CompletedWithErrors,
+    /// 已失败
+    Failed,
+}
+
+/// 单个文件的导入错误
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportFileError {
+    /// 文件路径
+    pub file_path: String,
+    /// 错误信息
+    pub error: String,
+    /// 发生时间
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// 导入任务记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportJob {
+    /// 任务ID
+    pub id: String,
+    /// 导入来源
+    pub source: ImportSource,
+    /// 患者信息调和策略
+    pub reconciliation: ReconciliationPolicy,
+    /// 任务状态
+    pub status: ImportJobStatus,
+    /// 待处理文件总数
+    pub total_files: u64,
+    /// 已处理的文件（用于续传）
+    pub processed_files: HashSet<String>,
+    /// 新入库实例数
+    pub stored_count: u64,
+    /// 重复实例数
+    pub duplicate_count: u64,
+    /// 非DICOM文件（已跳过）
+    pub skipped_files: Vec<String>,
+    /// 失败文件报告
+    pub failed_files: Vec<ImportFileError>,
+    /// 创建时间
+    pub created_at: DateTime<Utc>,
+    /// 更新时间
+    pub updated_at: DateTime<Utc>,
+    /// 结束时间
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl ImportJob {
+    /// 创建新的导入任务
+    pub fn new(source: ImportSource, reconciliation: ReconciliationPolicy) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            source,
+            reconciliation,
+            status: ImportJobStatus::Pending,
+            total_files: 0,
+            processed_files: HashSet::new(),
+            stored_count: 0,
+            duplicate_count: 0,
+            skipped_files: Vec::new(),
+            failed_files: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            finished_at: None,
+        }
+    }
+
+    /// 任务是否已结束
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            ImportJobStatus::Completed | ImportJobStatus::CompletedWithErrors
+        )
+    }
+
+    /// 获取错误报告
+    pub fn get_error_report(&self) -> String {
+        let mut report = format!(
+            "导入任务 {}: 共 {} 个文件, 入库 {}, 重复 {}, 跳过 {}, 失败 {}\n",
+            self.id,
+            self.total_files,
+            self.stored_count,
+            self.duplicate_count,
+            self.skipped_files.len(),
+            self.failed_files.len()
+        );
+
+        for failure in &self.failed_files {
+            report.push_str(&format!("  - {}: {}\n", failure.file_path, failure.error));
+        }
+
+        report
+    }
+}
+
+/// DICOM批量导入器
+///
+/// 重复判定由入库管道完成：管道配置了实例索引时，数据库中已登记的实例同样按重复计
+pub struct DicomImporter {
+    /// 入库管道
+    pipeline: Arc<IngestPipeline>,
+    /// 任务记录目录
+    job_dir: PathBuf,
+    /// 每处理多少个文件保存一次任务记录
+    checkpoint_interval: usize,
+}
+
+impl DicomImporter {
+    /// 创建新的导入器
+    pub fn new<P: Into<PathBuf>>(pipeline: Arc<IngestPipeline>, job_dir: P) -> Self {
+        Self {
+            pipeline,
+            job_dir: job_dir.into(),
+            checkpoint_interval: 50,
+        }
+    }
+
+    /// 设置任务记录保存间隔
+    pub fn with_checkpoint_interval(mut self, interval: usize) -> Self {
+        self.checkpoint_interval = interval.max(1);
+        self
+    }
+
+    /// 创建并执行导入任务
+    pub async fn import(
+        &self,
+        source: ImportSource,
+        reconciliation: ReconciliationPolicy,
+    ) -> Result<ImportJob> {
+        let mut job = self.create_job(source, reconciliation).await?;
+        self.run_job(&mut job).await?;
+        Ok(job)
+    }
+
+    /// 创建导入任务并保存记录，不执行（由调用方通过 [`Self::run_job`] 执行）
+    pub async fn create_job(
+        &self,
+        source: ImportSource,
+        reconciliation: ReconciliationPolicy,
+    ) -> Result<ImportJob> {
+        let job = ImportJob::new(source, reconciliation);
+        self.save_job(&job).await?;
+        info!("创建导入任务: {} ({:?})", job.id, job.source);
+        Ok(job)
+    }
+
+    /// 续传未完成的导入任务
+    pub async fn resume(&self, job_id: &str) -> Result<ImportJob> {
+        let mut job = self.load_job(job_id).await?;
+
+        if job.is_finished() {
+            info!("导入任务已完成，无需续传: {}", job_id);
+            return Ok(job);
+        }
+
+        info!(
+            "续传导入任务: {} (已处理 {} 个文件)",
+            job_id,
+            job.processed_files.len()
+        );
+        self.run_job(&mut job).await?;
+        Ok(job)
+    }
+
+    /// 执行导入任务
+    pub async fn run_job(&self, job: &mut ImportJob) -> Result<()> {
+        job.status = ImportJobStatus::Running;
+
+        let files = match Self::collect_files(&job.source).await {
+            Ok(files) => files,
+            Err(e) => {
+                error!("枚举导入文件失败: {} - {}", job.id, e);
+                job.status = ImportJobStatus::Failed;
+                job.updated_at = Utc::now();
+                self.save_job(job).await?;
+                return Err(e);
+            }
+        };
+        job.total_files = files.len() as u64;
+        self.save_job(job).await?;
+
+        let source = IngestSource::Import {
+            job_id: job.id.clone(),
+        };
+        let policy = job.reconciliation.clone();
+        let mut since_checkpoint = 0usize;
+
+        for file in files {
+            let key = file.to_string_lossy().to_string();
+            if job.processed_files.contains(&key) {
+                continue;
+            }
+
+            match self.import_file(&file, &source, &policy).await {
+                Ok(Some(IngestOutcome::Stored(_))) => job.stored_count += 1,
+                Ok(Some(IngestOutcome::Duplicate { .. })) => job.duplicate_count += 1,
+                Ok(None) => {
+                    debug!("跳过非DICOM文件: {}", key);
+                    job.skipped_files.push(key.clone());
+                }
+                Err(e) => {
+                    warn!("导入文件失败: {} - {}", key, e);
+                    job.failed_files.push(ImportFileError {
+                        file_path: key.clone(),
+                        error: e.to_string(),
+                        occurred_at: Utc::now(),
+                    });
+                }
+            }
+
+            job.processed_files.insert(key);
+            since_checkpoint += 1;
+            if since_checkpoint >= self.checkpoint_interval {
+                job.updated_at = Utc::now();
+                self.save_job(job).await?;
+                since_checkpoint = 0;
+            }
+        }
+
+        job.status = if job.failed_files.is_empty() {
+            ImportJobStatus::Completed
+        } else {
+            ImportJobStatus::CompletedWithErrors
+        };
+        job.updated_at = Utc::now();
+        job.finished_at = Some(job.updated_at);
+        self.save_job(job).await?;
+
+        info!(
+            "导入任务完成: {} (入库 {}, 重复 {}, 失败 {})",
+            job.id,
+            job.stored_count,
+            job.duplicate_count,
+            job.failed_files.len()
+        );
+
+        Ok(())
+    }
+
+    /// 以流方式导入单个文件，非DICOM文件返回None
+    ///
+    /// 只读取文件头判断是否为DICOM文件，之后交给入库管道流式入库
+    async fn import_file(
+        &self,
+        file: &Path,
+        source: &IngestSource,
+        policy: &ReconciliationPolicy,
+    ) -> Result<Option<IngestOutcome>> {
+        if !IngestPipeline::file_has_meta(file).await? {
+            return Ok(None);
+        }
+
+        self.pipeline
+            .ingest_file(file, source, Some(policy))
+            .await
+            .map(Some)
+    }
+
+    /// 枚举导入来源中的所有文件
+    pub async fn collect_files(source: &ImportSource) -> Result<Vec<PathBuf>> {
+        match source {
+            ImportSource::Dicomdir(dicomdir) => Self::read_dicomdir(dicomdir),
+            ImportSource::Directory(root) => Self::walk_directory(root).await,
+        }
+    }
+
+    /// 读取DICOMDIR中引用的文件
+    fn read_dicomdir(dicomdir: &Path) -> Result<Vec<PathBuf>> {
+        let obj = open_file(dicomdir)
+            .map_err(|e| PacsError::DicomParseError(format!("无法解析DICOMDIR: {:?}", e)))?;
+        let base_dir = dicomdir.parent().unwrap_or_else(|| Path::new("."));
+
+        let records = obj
+            .element(tags::DIRECTORY_RECORD_SEQUENCE)
+            .map_err(|_| PacsError::DicomParseError("DICOMDIR缺少目录记录序列".to_string()))?;
+
+        let mut files = Vec::new();
+        for record in records.items().unwrap_or(&[]) {
+            let Some(file_id) = record
+                .element(tags::REFERENCED_FILE_ID)
+                .ok()
+                .and_then(|e| e.to_multi_str().ok())
+            else {
+                continue;
+            };
+
+            let Some(relative) = Self::referenced_file_path(&file_id) else {
+                warn!("忽略越出介质目录的文件引用: {:?} ({:?})", file_id, dicomdir);
+                continue;
+            };
+            let path = base_dir.join(&relative);
+
+            // 介质上的文件名可能与DICOMDIR记录的大小写不一致
+            if path.exists() {
+                files.push(path);
+            } else {
+                let lower = base_dir.join(relative.to_string_lossy().to_lowercase());
+                files.push(if lower.exists() { lower } else { path });
+            }
+        }
+
+        debug!("DICOMDIR引用了 {} 个文件: {:?}", files.len(), dicomdir);
+        Ok(files)
+    }
+
+    /// 将ReferencedFileID的各级组件拼成相对路径
+    ///
+    /// 每个组件必须是单级普通文件名，空组件、`.`、`..`或绝对路径返回None，
+    /// 防止DICOMDIR引用介质目录以外的文件
+    fn referenced_file_path(file_id: &[String]) -> Option<PathBuf> {
+        let mut relative = PathBuf::new();
+        for component in file_id.iter().map(|c| c.trim()) {
+            let mut components = Path::new(component).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(name)), None) => relative.push(name),
+                _ => return None,
+            }
+        }
+        (!relative.as_os_str().is_empty()).then_some(relative)
+    }
+
+    /// 递归遍历目录
+    async fn walk_directory(root: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let file_type = entry.file_type().await?;
+
+                if file_type.is_dir() {
+                    pending.push(path);
+                } else if file_type.is_file()
+                    && path.file_name().and_then(|n| n.to_str()) != Some(DICOMDIR_NAME)
+                {
+                    files.push(path);
+                }
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    /// 加载导入任务记录
+    pub async fn load_job(&self, job_id: &str) -> Result<ImportJob> {
+        let path = self.job_path(job_id);
+        let data = tokio::fs::read(&path)
+            .await
+            .map_err(|_| PacsError::NotFound(format!("导入任务不存在: {}", job_id)))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// 列出所有导入任务
+    pub async fn list_jobs(&self) -> Result<Vec<ImportJob>> {
+        let mut jobs = Vec::new();
+        if tokio::fs::metadata(&self.job_dir).await.is_err() {
+            return Ok(jobs);
+        }
+
+        let mut entries = tokio::fs::read_dir(&self.job_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match serde_json::from_slice::<ImportJob>(&tokio::fs::read(&path).await?) {
+                Ok(job) => jobs.push(job),
+                Err(e) => warn!("无法读取导入任务记录 {:?}: {}", path, e),
+            }
+        }
+
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.created_at));
+        Ok(jobs)
+    }
+
+    /// 保存导入任务记录
+    async fn save_job(&self, job: &ImportJob) -> Result<()> {
+        tokio::fs::create_dir_all(&self.job_dir).await?;
+
+        let path = self.job_path(&job.id);
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(job)?).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+
+    fn job_path(&self, job_id: &str) -> PathBuf {
+        self.job_dir.join(format!("{}.json", job_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::InstanceIndex;
+    use crate::ingest::IngestedInstance;
+    use async_trait::async_trait;
+    use dicom::core::{DataElement, VR};
+    use dicom::dictionary_std::uids;
+    use dicom::object::{FileMetaTableBuilder, InMemDicomObject};
+    use std::collections::HashMap;
+    use tokio::sync::Mutex;
+
+    const STUDY_UID: &str = "1.2.826.0.1.3680043.9.7382.2";
+    const SERIES_UID: &str = "1.2.826.0.1.3680043.9.7382.2.1";
+
+    /// 内存实例索引，记录每个检查登记的实例
+    #[derive(Default)]
+    struct MemoryIndex {
+        studies: Mutex<HashMap<String, HashSet<String>>>,
+    }
+
+    #[async_trait]
+    impl InstanceIndex for MemoryIndex {
+        async fn contains_instance(&self, sop_instance_uid: &str) -> Result<bool> {
+            Ok(self
+                .studies
+                .lock()
+                .await
+                .values()
+                .any(|instances| instances.contains(sop_instance_uid)))
+        }
+
+        async fn index_instance(&self, instance: &IngestedInstance) -> Result<()> {
+            let study_uid = instance.metadata.study_instance_uid.clone().unwrap();
+            self.studies
+                .lock()
+                .await
+                .entry(study_uid)
+                .or_default()
+                .insert(instance.sop_instance_uid.clone());
+            Ok(())
+        }
+    }
+
+    fn write_instance(dir: &Path, sop_instance_uid: &str) {
+        let obj = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, sop_instance_uid),
+            DataElement::new(tags::PATIENT_ID, VR::LO, "PAT001"),
+            DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, STUDY_UID),
+            DataElement::new(tags::SERIES_INSTANCE_UID, VR::UI, SERIES_UID),
+            DataElement::new(tags::MODALITY, VR::CS, "CT"),
+        ]);
+        let obj = obj
+            .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+            .unwrap();
+        let data = IngestPipeline::encode_object(&obj).unwrap();
+        std::fs::write(dir.join(format!("{}.dcm", sop_instance_uid)), data).unwrap();
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pacs-import-{}-{}", name, Uuid::new_v4()))
+    }
+
+    fn sample_media() -> PathBuf {
+        let media = temp_dir("media");
+        std::fs::create_dir_all(media.join("IMAGES")).unwrap();
+        write_instance(&media.join("IMAGES"), "1.2.826.0.1.3680043.9.7382.2.1.1");
+        write_instance(&media.join("IMAGES"), "1.2.826.0.1.3680043.9.7382.2.1.2");
+        std::fs::write(media.join("README.TXT"), b"not dicom").unwrap();
+        media
+    }
+
+    #[tokio::test]
+    async fn test_reimport_skips_instances_already_indexed() {
+        let media = sample_media();
+        let index = Arc::new(MemoryIndex::default());
+        let job_dir = temp_dir("jobs");
+
+        let storage_a = temp_dir("storage");
+        let pipeline = Arc::new(IngestPipeline::new(&storage_a).with_index(index.clone()));
+        let importer = DicomImporter::new(pipeline, &job_dir);
+        let job = importer
+            .import(
+                ImportSource::Directory(media.clone()),
+                ReconciliationPolicy::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(job.status, ImportJobStatus::Completed);
+        assert_eq!(job.total_files, 3);
+        assert_eq!(job.stored_count, 2);
+        assert_eq!(job.skipped_files.len(), 1);
+
+        // 存储已迁移（本地没有文件），但数据库中已登记的检查不能再次入库
+        let storage_b = temp_dir("storage");
+        let pipeline = Arc::new(IngestPipeline::new(&storage_b).with_index(index.clone()));
+        let importer = DicomImporter::new(pipeline, &job_dir);
+        let job = importer
+            .import(
+                ImportSource::Directory(media.clone()),
+                ReconciliationPolicy::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(job.stored_count, 0);
+        assert_eq!(job.duplicate_count, 2);
+        assert!(!storage_b.join(STUDY_UID).exists());
+
+        let studies = index.studies.lock().await;
+        assert_eq!(studies.len(), 1);
+        assert_eq!(studies[STUDY_UID].len(), 2);
+        drop(studies);
+
+        assert_eq!(importer.list_jobs().await.unwrap().len(), 2);
+
+        for dir in [media, job_dir, storage_a, storage_b] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn test_referenced_file_id_must_stay_on_media() {
+        let file_id = |components: &[&str]| -> Vec<String> {
+            components.iter().map(|c| c.to_string()).collect()
+        };
+
+        assert_eq!(
+            DicomImporter::referenced_file_path(&file_id(&["IMAGES", "IM0001 "])),
+            Some(PathBuf::from("IMAGES").join("IM0001"))
+        );
+        for escaping in [
+            &["..", "ETC", "PASSWD"][..],
+            &["IMAGES", ".."],
+            &["/ETC"],
+            &["IMAGES", "A/../../B"],
+            &["."],
+            &[""],
+            &[],
+        ] {
+            assert_eq!(
+                DicomImporter::referenced_file_path(&file_id(escaping)),
+                None,
+                "{:?}",
+                escaping
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_skips_processed_files() {
+        let media = sample_media();
+        let storage = temp_dir("storage");
+        let job_dir = temp_dir("jobs");
+        let pipeline = Arc::new(IngestPipeline::new(&storage));
+        let importer = DicomImporter::new(pipeline, &job_dir);
+
+        // 模拟中断：第一个文件已处理，任务仍在进行中
+        let mut job = ImportJob::new(
+            ImportSource::Directory(media.clone()),
+            ReconciliationPolicy::default(),
+        );
+        let files = DicomImporter::collect_files(&job.source).await.unwrap();
+        job.processed_files
+            .insert(files[0].to_string_lossy().to_string());
+        job.stored_count = 1;
+        job.status = ImportJobStatus::Running;
+        importer.save_job(&job).await.unwrap();
+
+        let resumed = importer.resume(&job.id).await.unwrap();
+        assert!(resumed.is_finished());
+        assert_eq!(resumed.processed_files.len(), 3);
+        assert_eq!(resumed.stored_count + resumed.duplicate_count, 2);
+        assert_eq!(resumed.skipped_files.len(), 1);
+
+        let again = importer.resume(&job.id).await.unwrap();
+        assert_eq!(again.updated_at, resumed.updated_at);
+
+        for dir in [media, job_dir, storage] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
@@ -0,0 +1,493 @@
+//! 实例索引
+//!
+//! 入库管道在落盘前查询实例是否已登记，落盘后把新实例登记到患者/检查/序列/实例表，
+//! 并写入KOS/显示状态对源图像的引用关系；检索时由索引给出已拒绝（隐藏）的实例，
+//! 以及已迁出本地存储、需要召回的实例文件。
+//! 患者、检查、序列按业务ID查找已有记录，只在不存在时创建，重复导入不会产生重复记录。
+//! 布局迁移移动对象文件时，由 [`DatabaseRelocationListener`] 同步更新数据库中的文件路径。
+
+use crate::ingest::{IngestedInstance, StoredInstanceFile};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveTime, Utc};
+use pacs_core::{PacsError, Result, Sex, StudyStatus};
+use pacs_database::{DatabasePool, DatabaseQueries, NewInstance, NewPatient, NewSeries, NewStudy};
+use pacs_storage::{ObjectRelocationListener, StoredObject};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::debug;
+use uuid::Uuid;
+
+/// 缺少传输语法时的默认值（隐式VR Little Endian）
+const DEFAULT_TRANSFER_SYNTAX_UID: &str = "1.2.840.10008.1.2";
+
+/// 实例索引
+#[async_trait]
+pub trait InstanceIndex: Send + Sync {
+    /// 实例是否已登记
+    async fn contains_instance(&self, sop_instance_uid: &str) -> Result<bool>;
+
+    /// 登记新入库的实例
+    async fn index_instance(&self, instance: &IngestedInstance) -> Result<()>;
+
+    /// 检查中已隐藏（IOCM拒绝）的实例，检索时不再返回
+    async fn hidden_instances(&self, _study_instance_uid: &str) -> Result<HashSet<String>> {
+        Ok(HashSet::new())
+    }
+
+    /// 检查（可限定序列）中已登记的实例文件，文件可能已迁出本地存储
+    async fn instance_files(
+        &self,
+        _study_instance_uid: &str,
+        _series_instance_uid: Option<&str>,
+    ) -> Result<Vec<StoredInstanceFile>> {
+        Ok(Vec::new())
+    }
+}
+
+/// 基于数据库的实例索引
+pub struct DatabaseInstanceIndex {
+    /// 数据库连接池
+    database: Arc<DatabasePool>,
+}
+
+impl DatabaseInstanceIndex {
+    /// 创建数据库实例索引
+    pub fn new(database: Arc<DatabasePool>) -> Self {
+        Self { database }
+    }
+
+    /// 查找或创建患者，返回患者记录ID
+    async fn ensure_patient(
+        &self,
+        queries: &DatabaseQueries<'_>,
+        instance: &IngestedInstance,
+    ) -> Result<Uuid> {
+        let metadata = &instance.metadata;
+        let patient_id = metadata
+            .patient_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| PacsError::Validation("缺少患者ID".to_string()))?;
+
+        if let Some(patient) = queries.get_patient_by_patient_id(patient_id).await? {
+            return Ok(patient.id);
+        }
+
+        let patient = NewPatient {
+            id: Uuid::new_v4(),
+            patient_id: patient_id.to_string(),
+            name: metadata.patient_name.clone().unwrap_or_default(),
+            sex: metadata.patient_sex.as_deref().and_then(parse_sex),
+            birth_date: metadata.patient_birth_date.as_deref().and_then(parse_date),
+        };
+        match queries.create_patient(&patient).await {
+            Ok(id) => Ok(id),
+            // 并发入库时可能已被其他实例创建
+            Err(e) => match queries.get_patient_by_patient_id(patient_id).await? {
+                Some(patient) => Ok(patient.id),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// 查找或创建检查，返回检查记录ID
+    async fn ensure_study(
+        &self,
+        queries: &DatabaseQueries<'_>,
+        instance: &IngestedInstance,
+        patient_id: Uuid,
+    ) -> Result<Uuid> {
+        let metadata = &instance.metadata;
+        let study_uid = required(&metadata.study_instance_uid, "检查实例UID")?;
+
+        if let Some(study) = queries.get_study_by_uid(study_uid).await? {
+            return Ok(study.id);
+        }
+
+        let study = NewStudy {
+            id: Uuid::new_v4(),
+            study_uid: study_uid.to_string(),
+            patient_id,
+            accession_number: metadata.accession_number.clone().unwrap_or_default(),
+            study_date: metadata
+                .study_date
+                .as_deref()
+                .and_then(parse_date)
+                .unwrap_or_else(|| Utc::now().date_naive()),
+            study_time: metadata.study_time.as_deref().and_then(parse_time),
+            modality: metadata
+                .modality
+                .clone()
+                .unwrap_or_else(|| "OT".to_string()),
+            description: metadata.study_description.clone(),
+            status: StudyStatus::InProgress,
+        };
+        match queries.create_study(&study).await {
+            Ok(id) => {
+                debug!("登记新检查: {}", study_uid);
+                Ok(id)
+            }
+            Err(e) => match queries.get_study_by_uid(study_uid).await? {
+                Some(study) => Ok(study.id),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// 查找或创建序列，返回序列记录ID
+    async fn ensure_series(
+        &self,
+        queries: &DatabaseQueries<'_>,
+        instance: &IngestedInstance,
+        study_id: Uuid,
+    ) -> Result<Uuid> {
+        let metadata = &instance.metadata;
+        let series_uid = required(&metadata.series_instance_uid, "序列实例UID")?;
+
+        if let Some(series) = queries.get_series_by_uid(series_uid).await? {
+            return Ok(series.id);
+        }
+
+        let series = NewSeries {
+            id: Uuid::new_v4(),
+            series_uid: series_uid.to_string(),
+            study_id,
+            modality: metadata
+                .modality
+                .clone()
+                .unwrap_or_else(|| "OT".to_string()),
+            series_number: parse_number(&metadata.series_number),
+            description: metadata.series_description.clone(),
+            body_part_examined: metadata.body_part_examined.clone(),
+            images_count: 0,
+        };
+        match queries.create_series(&series).await {
+            Ok(id) => Ok(id),
+            Err(e) => match queries.get_series_by_uid(series_uid).await? {
+                Some(series) => Ok(series.id),
+                None => Err(e),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl InstanceIndex for DatabaseInstanceIndex {
+    async fn contains_instance(&self, sop_instance_uid: &str) -> Result<bool> {
+        DatabaseQueries::new(&self.database)
+            .instance_exists(sop_instance_uid)
+            .await
+    }
+
+    async fn index_instance(&self, instance: &IngestedInstance) -> Result<()> {
+        let queries = DatabaseQueries::new(&self.database);
+        if queries.instance_exists(&instance.sop_instance_uid).await? {
+            debug!("实例已登记，跳过: {}", instance.sop_instance_uid);
+            return Ok(());
+        }
+
+        let patient_id = self.ensure_patient(&queries, instance).await?;
+        let study_id = self.ensure_study(&queries, instance, patient_id).await?;
+        let series_id = self.ensure_series(&queries, instance, study_id).await?;
+
+        let metadata = &instance.metadata;
+        queries
+            .create_instance(&NewInstance {
+                id: Uuid::new_v4(),
+                sop_instance_uid: instance.sop_instance_uid.clone(),
+                series_id,
+                instance_number: parse_number(&metadata.instance_number),
+                file_path: instance.file_path.to_string_lossy().to_string(),
+                file_size: instance.file_size as i64,
+                transfer_syntax_uid: metadata
+                    .transfer_syntax_uid
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_TRANSFER_SYNTAX_UID.to_string()),
+            })
+            .await?;
+        queries.increment_series_images_count(&series_id).await?;
+
+        // KOS/显示状态对源图像的引用
+        if !instance.references.is_empty() {
+            let relationships: Vec<_> = instance
+                .references
+                .iter()
+                .map(|reference| reference.to_new_relationship())
+                .collect();
+            queries
+                .create_instance_relationships(&relationships)
+                .await?;
+        }
+
+        debug!("实例已登记: {}", instance.sop_instance_uid);
+        Ok(())
+    }
+
+    async fn hidden_instances(&self, study_instance_uid: &str) -> Result<HashSet<String>> {
+        let uids = DatabaseQueries::new(&self.database)
+            .get_rejected_instance_uids(study_instance_uid)
+            .await?;
+        Ok(uids.into_iter().collect())
+    }
+
+    async fn instance_files(
+        &self,
+        study_instance_uid: &str,
+        series_instance_uid: Option<&str>,
+    ) -> Result<Vec<StoredInstanceFile>> {
+        let files = DatabaseQueries::new(&self.database)
+            .list_study_instance_files(study_instance_uid, series_instance_uid)
+            .await?;
+        Ok(files
+            .into_iter()
+            .map(
+                |(series_instance_uid, sop_instance_uid, file_path)| StoredInstanceFile {
+                    series_instance_uid,
+                    sop_instance_uid,
+                    file_path: PathBuf::from(file_path),
+                },
+            )
+            .collect())
+    }
+}
+
+/// 布局迁移的数据库同步：实例索引、转发队列和拒绝记录中的文件路径随对象移动更新
+pub struct DatabaseRelocationListener {
+    /// 数据库连接池
+    database: Arc<DatabasePool>,
+    /// 存储根目录（对象键相对于此目录）
+    storage_dir: PathBuf,
+}
+
+impl DatabaseRelocationListener {
+    /// 创建迁移同步器
+    pub fn new<P: Into<PathBuf>>(database: Arc<DatabasePool>, storage_dir: P) -> Self {
+        Self {
+            database,
+            storage_dir: storage_dir.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl ObjectRelocationListener for DatabaseRelocationListener {
+    async fn object_relocated(&self, object: &StoredObject, previous_key: &str) -> Result<()> {
+        let file_path = self.storage_dir.join(&object.object_key);
+        DatabaseQueries::new(&self.database)
+            .update_instance_file_path(
+                &object.instance.sop_instance_uid,
+                &file_path.to_string_lossy(),
+            )
+            .await?;
+        debug!(
+            "实例文件位置已更新: {} ({} -> {})",
+            object.instance.sop_instance_uid, previous_key, object.object_key
+        );
+        Ok(())
+    }
+}
+
+/// 读取必需的UID
+fn required<'a>(value: &'a Option<String>, name: &str) -> Result<&'a str> {
+    value
+        .as_deref()
+        .filter(|uid| !uid.is_empty())
+        .ok_or_else(|| PacsError::Validation(format!("缺少{}", name)))
+}
+
+/// 解析DICOM日期（DA，YYYYMMDD）
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y%m%d").ok()
+}
+
+/// 解析DICOM时间（TM，HHMMSS.FFFFFF，允许省略分秒）
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    let value = value.trim();
+    let (hms, _) = value.split_once('.').unwrap_or((value, ""));
+    match hms.len() {
+        6 => NaiveTime::parse_from_str(hms, "%H%M%S").ok(),
+        4 => NaiveTime::parse_from_str(&format!("{}00", hms), "%H%M%S").ok(),
+        2 => NaiveTime::parse_from_str(&format!("{}0000", hms), "%H%M%S").ok(),
+        _ => None,
+    }
+}
+
+/// 解析DICOM性别（CS）
+fn parse_sex(value: &str) -> Option<Sex> {
+    match value.trim() {
+        "M" => Some(Sex::Male),
+        "F" => Some(Sex::Female),
+        "O" => Some(Sex::Other),
+        _ => None,
+    }
+}
+
+/// 解析整数字符串（IS），缺失或无效时为0
+fn parse_number(value: &Option<String>) -> i32 {
+    value
+        .as_deref()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dicom_values() {
+        assert_eq!(parse_date("20240131"), NaiveDate::from_ymd_opt(2024, 1, 31));
+        assert_eq!(parse_date("2024-01-31"), None);
+        assert_eq!(parse_time("1430"), NaiveTime::from_hms_opt(14, 30, 0));
+        assert_eq!(parse_time("143005.123"), NaiveTime::from_hms_opt(14, 30, 5));
+        assert!(matches!(parse_sex("F "), Some(Sex::Female)));
+        assert_eq!(parse_number(&Some(" 12".to_string())), 12);
+        assert_eq!(parse_number(&None), 0);
+    }
+
+    /// 布局迁移后实例表、未完成的转发任务和拒绝记录指向新位置（需设置PACS_TEST_DATABASE_URL）
+    #[tokio::test]
+    async fn test_layout_migration_updates_database_file_paths() {
+        use crate::ingest::{IngestOutcome, IngestPipeline, IngestSource};
+        use dicom::core::{DataElement, VR};
+        use dicom::dictionary_std::{tags, uids};
+        use dicom::object::{FileMetaTableBuilder, InMemDicomObject};
+        use pacs_database::{NewForwardTask, NewRejectedInstance};
+        use pacs_storage::{
+            LayoutManager, MigrationState, StorageConfig, StorageLayout, StorageManager,
+            StorageType,
+        };
+
+        let Ok(database_url) = std::env::var("PACS_TEST_DATABASE_URL") else {
+            return;
+        };
+        let db = Arc::new(DatabasePool::new(&database_url, 2).await.unwrap());
+        let queries = DatabaseQueries::new(&db);
+        queries.create_tables().await.unwrap();
+
+        let dir = std::env::temp_dir().join(format!("pacs-index-{}", Uuid::new_v4()));
+        let storage = Arc::new(
+            StorageManager::new(StorageConfig {
+                storage_type: StorageType::Local,
+                local_path: Some(dir.to_string_lossy().to_string()),
+                object_store_config: None,
+            })
+            .await
+            .unwrap(),
+        );
+        let manager = Arc::new(
+            LayoutManager::new(storage, StorageLayout::hierarchical())
+                .unwrap()
+                .with_relocation_listener(Arc::new(DatabaseRelocationListener::new(
+                    db.clone(),
+                    &dir,
+                ))),
+        );
+        let pipeline = IngestPipeline::new(&dir)
+            .with_layout_manager(manager.clone())
+            .with_database(db.clone());
+
+        let study_uid = format!("2.25.{}", Uuid::new_v4().as_u128());
+        let series_uid = format!("{}.1", study_uid);
+        let sop_uid = format!("{}.1.1", study_uid);
+        let obj = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, sop_uid.as_str()),
+            DataElement::new(tags::PATIENT_ID, VR::LO, "PAT001"),
+            DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, study_uid.as_str()),
+            DataElement::new(tags::SERIES_INSTANCE_UID, VR::UI, series_uid.as_str()),
+            DataElement::new(tags::MODALITY, VR::CS, "CT"),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+        .unwrap();
+        let data = IngestPipeline::encode_object(&obj).unwrap();
+        let IngestOutcome::Stored(instance) = pipeline
+            .ingest(&data, &IngestSource::Upload { username: None }, None)
+            .await
+            .unwrap()
+        else {
+            panic!("instance was not stored");
+        };
+        let old_path = instance.file_path.to_string_lossy().to_string();
+
+        let destination = format!("DEST_{}", Uuid::new_v4().simple());
+        let task_id = Uuid::new_v4();
+        queries
+            .enqueue_forward_task(&NewForwardTask {
+                id: task_id,
+                destination: destination.clone(),
+                rule_name: "all".to_string(),
+                study_uid: study_uid.clone(),
+                sop_instance_uid: sop_uid.clone(),
+                file_path: old_path.clone(),
+                next_attempt_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        queries
+            .fail_forward_task(&task_id, "association rejected", None)
+            .await
+            .unwrap();
+        queries
+            .record_instance_rejection(&NewRejectedInstance {
+                sop_instance_uid: sop_uid.clone(),
+                study_uid: study_uid.clone(),
+                series_uid: series_uid.clone(),
+                sop_class_uid: None,
+                reason_code: "113001".to_string(),
+                rejection_note_uid: format!("{}.9", study_uid),
+                rejected_by: None,
+                file_path: Some(old_path.clone()),
+                purge_after: Utc::now() - chrono::Duration::hours(1),
+            })
+            .await
+            .unwrap();
+
+        let target = StorageLayout::hashed(1);
+        let migration_id = manager.start_migration(target.clone()).await.unwrap();
+        for _ in 0..100 {
+            let migration = manager.get_migration(&migration_id).await.unwrap();
+            if migration.state != MigrationState::Running {
+                assert_eq!(migration.migrated_objects, 1);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let new_path = dir
+            .join(manager.locate(&sop_uid).await.unwrap().object_key)
+            .to_string_lossy()
+            .to_string();
+        assert_ne!(new_path, old_path);
+        assert!(std::path::Path::new(&new_path).exists());
+        assert!(!std::path::Path::new(&old_path).exists());
+
+        let indexed = queries
+            .list_study_instance_files(&study_uid, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            indexed,
+            vec![(series_uid, sop_uid.clone(), new_path.clone())]
+        );
+        let tasks = queries
+            .get_failed_forward_tasks(Some(&destination), 10)
+            .await
+            .unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].file_path, new_path);
+        let rejection = queries
+            .get_due_rejections(10_000)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|rejection| rejection.sop_instance_uid == sop_uid)
+            .unwrap();
+        assert_eq!(rejection.file_path.as_deref(), Some(new_path.as_str()));
+
+        queries.mark_rejection_purged(&sop_uid).await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,1161 @@
+//! 影像入库管道
+//!
+//! C-STORE接收与批量导入共用的入库流程：解析DICOM数据、患者信息调和、
+//! 按SOP实例UID去重（存储文件与实例索引），以Study/Series/SOP实例UID组织的路径落盘，
+//! 并登记到实例索引。配置布局管理器时经由其存储，已登记对象的重复发送按内容判定：
+//! 逐字节相同的跳过，内容不同的覆盖原对象。
+
+use crate::encapsulated::{EncapsulatedDocumentHandler, EncapsulatedDocumentInfo};
+use crate::index::{DatabaseInstanceIndex, InstanceIndex};
+use crate::parser::{DicomParser, ParsedDicomObject};
+use crate::references::{InstanceReference, ReferenceExtractor};
+use crate::sr::RadiationDoseRecorder;
+use async_trait::async_trait;
+use dicom::core::value::DataSetSequence;
+use dicom::core::{DataElement, PrimitiveValue, VR};
+use dicom::dictionary_std::tags;
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::object::file::ReadPreamble;
+use dicom::object::{DefaultDicomObject, FileMetaTableBuilder, InMemDicomObject, OpenFileOptions};
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+use pacs_core::{PacsError, Result};
+use pacs_database::DatabasePool;
+use pacs_storage::{InstanceKey, LayoutManager, StorageLayout, StoreOutcome};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// DICOM文件前导长度
+const PREAMBLE_LENGTH: usize = 128;
+
+/// 流式入库的临时目录名（位于存储根目录下）
+const INCOMING_DIR: &str = ".incoming";
+
+/// 入库来源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IngestSource {
+    /// 网络C-STORE接收
+    CStore { calling_ae_title: Option<String> },
+    /// 介质/目录批量导入
+    Import { job_id: String },
+    /// Web接口上传
+    Upload { username: Option<String> },
+    /// 检查拆分/合并/移动等管理操作
+    StudyOperation { operation_id: String },
+}
+
+impl std::fmt::Display for IngestSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IngestSource::CStore { calling_ae_title } => write!(
+                f,
+                "C-STORE({})",
+                calling_ae_title.as_deref().unwrap_or("未知AE")
+            ),
+            IngestSource::Import { job_id } => write!(f, "导入任务({})", job_id),
+            IngestSource::Upload { username } => {
+                write!(f, "Web上传({})", username.as_deref().unwrap_or("匿名"))
+            }
+            IngestSource::StudyOperation { operation_id } => {
+                write!(f, "检查操作({})", operation_id)
+            }
+        }
+    }
+}
+
+/// 患者信息调和策略
+///
+/// 用于外院影像入库时改写患者ID，避免与本院患者ID冲突
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconciliationPolicy {
+    /// 外院患者ID到本院患者ID的映射（优先于前缀规则）
+    pub patient_id_map: HashMap<String, String>,
+    /// 外院患者ID前缀（例如 "EXT_"），为空则不改写
+    pub outside_id_prefix: Option<String>,
+    /// 改写后是否在Other Patient IDs Sequence中保留原始ID
+    pub keep_original_id: bool,
+}
+
+impl ReconciliationPolicy {
+    /// 计算调和后的患者ID，无需改写时返回None
+    pub fn reconcile_patient_id(&self, patient_id: &str) -> Option<String> {
+        if let Some(local_id) = self.patient_id_map.get(patient_id) {
+            return (local_id != patient_id).then(|| local_id.clone());
+        }
+
+        match &self.outside_id_prefix {
+            Some(prefix) if !prefix.is_empty() && !patient_id.starts_with(prefix.as_str()) => {
+                Some(format!("{}{}", prefix, patient_id))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 已入库实例信息
+#[derive(Debug, Clone)]
+pub struct IngestedInstance {
+    /// SOP实例UID
+    pub sop_instance_uid: String,
+    /// 存储文件路径
+    pub file_path: PathBuf,
+    /// 文件大小（字节）
+    pub file_size: u64,
+    /// 调和前的原始患者ID（未改写时为None）
+    pub original_patient_id: Option<String>,
+    /// 入库后的元数据
+    pub metadata: ParsedDicomObject,
+    /// KOS/显示状态对源图像的引用（普通图像为空）
+    pub references: Vec<InstanceReference>,
+    /// 封装文档（Encapsulated PDF/CDA）摘要信息
+    pub document: Option<EncapsulatedDocumentInfo>,
+}
+
+/// 入库结果
+#[derive(Debug, Clone)]
+pub enum IngestOutcome {
+    /// 新实例已存储
+    Stored(Box<IngestedInstance>),
+    /// SOP实例UID已存在，跳过
+    Duplicate { sop_instance_uid: String },
+}
+
+/// 不带文件元信息的数据集的元信息来源（C-STORE的DIMSE命令和表示上下文）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetMeta {
+    /// SOP类UID
+    pub sop_class_uid: String,
+    /// SOP实例UID
+    pub sop_instance_uid: String,
+    /// 协商的传输语法UID
+    pub transfer_syntax_uid: String,
+}
+
+/// 已存储的实例文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredInstanceFile {
+    /// 序列实例UID
+    pub series_instance_uid: String,
+    /// SOP实例UID（取自文件名）
+    pub sop_instance_uid: String,
+    /// 文件路径
+    pub file_path: PathBuf,
+}
+
+/// 入库事件监听器
+///
+/// 新实例落盘后按注册顺序回调，监听器失败只记录日志，不影响入库结果
+#[async_trait]
+pub trait IngestListener: Send + Sync {
+    /// 新实例已存储
+    async fn on_instance_stored(
+        &self,
+        instance: &IngestedInstance,
+        source: &IngestSource,
+    ) -> Result<()>;
+}
+
+/// 待写入的实例内容
+enum InstanceContent<'a> {
+    /// 内存中的完整文件
+    Bytes(&'a [u8]),
+    /// 流式入库的临时文件
+    Spooled(&'a Path),
+}
+
+/// 影像入库管道
+pub struct IngestPipeline {
+    /// 存储根目录
+    storage_dir: PathBuf,
+    /// 正在入库的SOP实例UID，防止并发重复写入
+    in_flight: Mutex<HashSet<String>>,
+    /// 入库事件监听器
+    listeners: Vec<Arc<dyn IngestListener>>,
+    /// 实例索引（数据库），用于去重和登记新实例
+    index: Option<Arc<dyn InstanceIndex>>,
+    /// 实例存储布局（未配置布局管理器时使用）
+    layout: StorageLayout,
+    /// 存储布局管理器，提供当前布局并记录对象目录
+    layout_manager: Option<Arc<LayoutManager>>,
+}
+
+impl IngestPipeline {
+    /// 创建新的入库管道
+    pub fn new<P: Into<PathBuf>>(storage_dir: P) -> Self {
+        Self {
+            storage_dir: storage_dir.into(),
+            in_flight: Mutex::new(HashSet::new()),
+            listeners: Vec::new(),
+            index: None,
+            layout: StorageLayout::hierarchical(),
+            layout_manager: None,
+        }
+    }
+
+    /// 设置实例存储布局
+    pub fn with_layout(mut self, layout: StorageLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// 使用存储布局管理器
+    ///
+    /// 新实例经由管理器按当前布局落盘并登记到其对象目录，布局迁移因此覆盖入库的实例，
+    /// 已登记实例的重复发送按内容判定是否重复；管理器的存储须是以本管道存储根目录为根的本地存储
+    pub fn with_layout_manager(mut self, manager: Arc<LayoutManager>) -> Self {
+        self.layout_manager = Some(manager);
+        self
+    }
+
+    /// 设置实例索引
+    pub fn with_index(mut self, index: Arc<dyn InstanceIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// 使用数据库登记入库实例
+    ///
+    /// 设置数据库实例索引，并注册写入剂量记录等数据库监听器
+    pub fn with_database(self, database: Arc<DatabasePool>) -> Self {
+        self.with_index(Arc::new(DatabaseInstanceIndex::new(database.clone())))
+            .with_listener(Arc::new(RadiationDoseRecorder::new(database)))
+    }
+
+    /// 注册入库事件监听器
+    pub fn with_listener(mut self, listener: Arc<dyn IngestListener>) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// 获取存储根目录
+    pub fn storage_dir(&self) -> &Path {
+        &self.storage_dir
+    }
+
+    /// 入库一份DICOM数据
+    ///
+    /// `data` 可以是完整的DICOM文件，也可以是不带文件元信息的数据集
+    /// （按隐式VR Little Endian解析，与C-STORE默认传输语法一致）
+    pub async fn ingest(
+        &self,
+        data: &[u8],
+        source: &IngestSource,
+        policy: Option<&ReconciliationPolicy>,
+    ) -> Result<IngestOutcome> {
+        let has_file_meta = Self::has_file_meta(data);
+        let mut obj = if has_file_meta {
+            DicomParser::read_object(data)?
+        } else {
+            Self::read_bare_dataset(data)?
+        };
+
+        let (sop_instance_uid, file_path) = self.locate_instance(&obj).await?;
+        if !self.begin(&sop_instance_uid, &file_path).await? {
+            debug!("实例已存在，跳过入库: {} ({})", sop_instance_uid, source);
+            return Ok(IngestOutcome::Duplicate { sop_instance_uid });
+        }
+
+        let result = self
+            .store_object(&mut obj, data, has_file_meta, &file_path, policy)
+            .await;
+        self.in_flight.lock().await.remove(&sop_instance_uid);
+
+        let Some((file_path, file_size, original_patient_id)) = result? else {
+            debug!(
+                "实例内容未变化，跳过入库: {} ({})",
+                sop_instance_uid, source
+            );
+            return Ok(IngestOutcome::Duplicate { sop_instance_uid });
+        };
+        self.finish(
+            &obj,
+            sop_instance_uid,
+            file_path,
+            file_size,
+            original_patient_id,
+            source,
+        )
+        .await
+    }
+
+    /// 以流方式入库DICOM文件，内存占用与对象大小无关
+    ///
+    /// 数据先写入存储目录下的临时文件，只解析像素数据之前的头部后原样移入
+    /// 实例路径。不带文件元信息的数据集或需要改写患者ID时回退为整体读入内存
+    pub async fn ingest_stream<R>(
+        &self,
+        mut reader: R,
+        source: &IngestSource,
+        policy: Option<&ReconciliationPolicy>,
+    ) -> Result<IngestOutcome>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let spool_dir = self.storage_dir.join(INCOMING_DIR);
+        tokio::fs::create_dir_all(&spool_dir).await?;
+        let spool_path = spool_dir.join(format!("{}.partial", Uuid::new_v4()));
+
+        let result = self
+            .ingest_spooled(&mut reader, &spool_path, source, policy)
+            .await;
+        if tokio::fs::metadata(&spool_path).await.is_ok() {
+            let _ = tokio::fs::remove_file(&spool_path).await;
+        }
+        result
+    }
+
+    /// 以流方式入库不带文件元信息的数据集（C-STORE）
+    ///
+    /// 按 `meta` 生成文件元信息写在数据集之前，之后与 [`Self::ingest_stream`]
+    /// 一样只解析头部，数据集不整体载入内存也不重新编码
+    pub async fn ingest_dataset_stream<R>(
+        &self,
+        reader: R,
+        meta: &DatasetMeta,
+        source: &IngestSource,
+        policy: Option<&ReconciliationPolicy>,
+    ) -> Result<IngestOutcome>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let header = Self::file_header(meta)?;
+        self.ingest_stream(Cursor::new(header).chain(reader), source, policy)
+            .await
+    }
+
+    /// 将数据写入临时文件后入库
+    async fn ingest_spooled<R>(
+        &self,
+        reader: &mut R,
+        spool_path: &Path,
+        source: &IngestSource,
+        policy: Option<&ReconciliationPolicy>,
+    ) -> Result<IngestOutcome>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let mut file = tokio::fs::File::create(spool_path).await?;
+        let file_size = tokio::io::copy(reader, &mut file).await?;
+        file.flush().await?;
+        drop(file);
+
+        if !Self::file_has_meta(spool_path).await? {
+            let data = tokio::fs::read(spool_path).await?;
+            return self.ingest(&data, source, policy).await;
+        }
+
+        // 只读取像素数据之前的元素
+        let header_path = spool_path.to_path_buf();
+        let obj = tokio::task::spawn_blocking(move || {
+            OpenFileOptions::new()
+                .read_preamble(ReadPreamble::Auto)
+                .read_until(tags::PIXEL_DATA)
+                .open_file(&header_path)
+        })
+        .await
+        .map_err(|e| PacsError::Internal(format!("DICOM头解析任务失败: {}", e)))?
+        .map_err(|e| PacsError::DicomParseError(format!("无法解析DICOM文件头: {:?}", e)))?;
+
+        let needs_rewrite = policy.is_some_and(|policy| {
+            obj.element(tags::PATIENT_ID)
+                .ok()
+                .and_then(|e| e.to_str().ok())
+                .and_then(|id| policy.reconcile_patient_id(id.trim()))
+                .is_some()
+        });
+        if needs_rewrite {
+            let data = tokio::fs::read(spool_path).await?;
+            return self.ingest(&data, source, policy).await;
+        }
+
+        let (sop_instance_uid, file_path) = self.locate_instance(&obj).await?;
+        if !self.begin(&sop_instance_uid, &file_path).await? {
+            debug!("实例已存在，跳过入库: {} ({})", sop_instance_uid, source);
+            return Ok(IngestOutcome::Duplicate { sop_instance_uid });
+        }
+
+        let result = self
+            .write_instance(&obj, InstanceContent::Spooled(spool_path), &file_path)
+            .await;
+        self.in_flight.lock().await.remove(&sop_instance_uid);
+        let Some(file_path) = result? else {
+            debug!(
+                "实例内容未变化，跳过入库: {} ({})",
+                sop_instance_uid, source
+            );
+            return Ok(IngestOutcome::Duplicate { sop_instance_uid });
+        };
+
+        self.finish(&obj, sop_instance_uid, file_path, file_size, None, source)
+            .await
+    }
+
+    /// 入库本地DICOM文件（流式读取，不整体载入内存）
+    pub async fn ingest_file<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        source: &IngestSource,
+        policy: Option<&ReconciliationPolicy>,
+    ) -> Result<IngestOutcome> {
+        let file = tokio::fs::File::open(file_path.as_ref()).await?;
+        self.ingest_stream(file, source, policy).await
+    }
+
+    /// 读取实例UID并计算存储路径
+    async fn locate_instance(&self, obj: &DefaultDicomObject) -> Result<(String, PathBuf)> {
+        let sop_instance_uid = Self::required_uid(obj, tags::SOP_INSTANCE_UID, "SOP Instance UID")?;
+        let study_instance_uid =
+            Self::required_uid(obj, tags::STUDY_INSTANCE_UID, "Study Instance UID")?;
+        let series_instance_uid =
+            Self::required_uid(obj, tags::SERIES_INSTANCE_UID, "Series Instance UID")?;
+
+        let file_path = self
+            .instance_path(&study_instance_uid, &series_instance_uid, &sop_instance_uid)
+            .await?;
+        Ok((sop_instance_uid, file_path))
+    }
+
+    /// 登记正在入库的实例，实例已存在（文件或索引中）或正在入库时返回false
+    ///
+    /// 布局管理器对象目录中已有的实例不在此判定，写入时由管理器按内容判定
+    async fn begin(&self, sop_instance_uid: &str, file_path: &Path) -> Result<bool> {
+        let catalogued = match &self.layout_manager {
+            Some(manager) => manager.locate(sop_instance_uid).await.is_some(),
+            None => false,
+        };
+        if !catalogued {
+            if let Some(index) = &self.index {
+                if index.contains_instance(sop_instance_uid).await? {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let mut in_flight = self.in_flight.lock().await;
+        if in_flight.contains(sop_instance_uid)
+            || (!catalogued && tokio::fs::metadata(file_path).await.is_ok())
+        {
+            return Ok(false);
+        }
+        in_flight.insert(sop_instance_uid.to_string());
+        Ok(true)
+    }
+
+    /// 登记实例索引、生成入库结果并通知监听器
+    ///
+    /// 索引登记失败时删除已落盘的文件并返回错误，避免出现未登记的孤立文件
+    async fn finish(
+        &self,
+        obj: &DefaultDicomObject,
+        sop_instance_uid: String,
+        file_path: PathBuf,
+        file_size: u64,
+        original_patient_id: Option<String>,
+        source: &IngestSource,
+    ) -> Result<IngestOutcome> {
+        let metadata = DicomParser::extract_metadata(obj)?;
+        let references = ReferenceExtractor::extract(obj).unwrap_or_default();
+        let document = EncapsulatedDocumentHandler::extract(obj).map(|doc| doc.info());
+
+        info!(
+            "实例入库完成: {} -> {:?} ({})",
+            sop_instance_uid, file_path, source
+        );
+
+        let instance = IngestedInstance {
+            sop_instance_uid,
+            file_path,
+            file_size,
+            original_patient_id,
+            metadata,
+            references,
+            document,
+        };
+        if let Some(index) = &self.index {
+            if let Err(e) = index.index_instance(&instance).await {
+                warn!(
+                    "实例索引登记失败，撤销入库: {} ({})",
+                    instance.sop_instance_uid, e
+                );
+                let _ = tokio::fs::remove_file(&instance.file_path).await;
+                self.prune_empty_dirs(&instance.file_path).await;
+                return Err(e);
+            }
+        }
+        for listener in &self.listeners {
+            if let Err(e) = listener.on_instance_stored(&instance, source).await {
+                warn!("入库监听器处理失败: {} ({})", instance.sop_instance_uid, e);
+            }
+        }
+
+        Ok(IngestOutcome::Stored(Box::new(instance)))
+    }
+
+    /// 计算实例的存储路径
+    ///
+    /// 对象目录中已有记录时返回记录的位置（布局迁移期间可能仍在原布局下），
+    /// 否则按当前布局推导；UID不合法时返回校验错误
+    pub async fn instance_path(
+        &self,
+        study_instance_uid: &str,
+        series_instance_uid: &str,
+        sop_instance_uid: &str,
+    ) -> Result<PathBuf> {
+        let instance = InstanceKey::new(study_instance_uid, series_instance_uid, sop_instance_uid);
+        if let Some(manager) = &self.layout_manager {
+            if let Some(object) = manager.locate(sop_instance_uid).await {
+                if object.instance == instance {
+                    return Ok(self.storage_dir.join(object.object_key));
+                }
+            }
+        }
+        let object_key = self.current_layout().await.object_key(&instance)?;
+        Ok(self.storage_dir.join(object_key))
+    }
+
+    /// 当前存储布局
+    async fn current_layout(&self) -> StorageLayout {
+        match &self.layout_manager {
+            Some(manager) => manager.layout().await,
+            None => self.layout.clone(),
+        }
+    }
+
+    /// 写入实例内容，返回实际存储路径；与已存储对象逐字节相同时返回None
+    ///
+    /// 配置了布局管理器时经由其存储并登记对象目录（内容不同时覆盖原对象），
+    /// 否则先写临时文件（或直接移动流式入库的临时文件）到 `file_path`
+    async fn write_instance(
+        &self,
+        obj: &DefaultDicomObject,
+        content: InstanceContent<'_>,
+        file_path: &Path,
+    ) -> Result<Option<PathBuf>> {
+        if let Some(manager) = &self.layout_manager {
+            let instance = InstanceKey::new(
+                &Self::required_uid(obj, tags::STUDY_INSTANCE_UID, "Study Instance UID")?,
+                &Self::required_uid(obj, tags::SERIES_INSTANCE_UID, "Series Instance UID")?,
+                &Self::required_uid(obj, tags::SOP_INSTANCE_UID, "SOP Instance UID")?,
+            );
+            let outcome = match content {
+                InstanceContent::Bytes(bytes) => manager.store(&instance, bytes).await?,
+                InstanceContent::Spooled(path) => manager.store_file(&instance, path).await?,
+            };
+            return Ok(match outcome {
+                StoreOutcome::Identical(_) => None,
+                outcome => Some(self.storage_dir.join(&outcome.object().object_key)),
+            });
+        }
+
+        if let Some(parent) = file_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        match content {
+            InstanceContent::Bytes(bytes) => {
+                // 先写临时文件再重命名，避免留下不完整的实例文件
+                let tmp_path = file_path.with_extension("dcm.partial");
+                tokio::fs::write(&tmp_path, bytes).await?;
+                tokio::fs::rename(&tmp_path, file_path).await?;
+            }
+            InstanceContent::Spooled(path) => tokio::fs::rename(path, file_path).await?,
+        }
+        Ok(Some(file_path.to_path_buf()))
+    }
+
+    /// 列出检查（或其中一个序列）已存储的实例文件，按路径排序
+    pub async fn list_instance_files(
+        &self,
+        study_instance_uid: &str,
+        series_instance_uid: Option<&str>,
+    ) -> Result<Vec<StoredInstanceFile>> {
+        // 对象目录中的实例（含布局迁移期间仍在原位置的对象），文件已不存在的跳过
+        let mut files = Vec::new();
+        if let Some(manager) = &self.layout_manager {
+            for object in manager
+                .study_objects(study_instance_uid, series_instance_uid)
+                .await
+            {
+                let file_path = self.storage_dir.join(&object.object_key);
+                if tokio::fs::metadata(&file_path).await.is_ok() {
+                    files.push(StoredInstanceFile {
+                        series_instance_uid: object.instance.series_instance_uid,
+                        sop_instance_uid: object.instance.sop_instance_uid,
+                        file_path,
+                    });
+                }
+            }
+        }
+
+        let study_dir = self
+            .storage_dir
+            .join(self.current_layout().await.study_key(study_instance_uid)?);
+        if tokio::fs::metadata(&study_dir).await.is_err() {
+            if files.is_empty() {
+                return Err(PacsError::NotFound(format!("检查 {}", study_instance_uid)));
+            }
+            files.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+            return Ok(files);
+        }
+
+        let mut series_dirs = Vec::new();
+        match series_instance_uid {
+            Some(series_uid) => {
+                series_dirs.push((series_uid.to_string(), study_dir.join(series_uid)))
+            }
+            None => {
+                let mut entries = tokio::fs::read_dir(&study_dir).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.file_type().await?.is_dir() {
+                        series_dirs.push((
+                            entry.file_name().to_string_lossy().to_string(),
+                            entry.path(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        let cataloged: HashSet<String> = files
+            .iter()
+            .map(|file| file.sop_instance_uid.clone())
+            .collect();
+        for (series_uid, series_dir) in series_dirs {
+            let Ok(mut entries) = tokio::fs::read_dir(&series_dir).await else {
+                continue;
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("dcm") {
+                    continue;
+                }
+                if let Some(sop_instance_uid) = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .filter(|uid| !cataloged.contains(*uid))
+                {
+                    files.push(StoredInstanceFile {
+                        series_instance_uid: series_uid.clone(),
+                        sop_instance_uid: sop_instance_uid.to_string(),
+                        file_path: path.clone(),
+                    });
+                }
+            }
+        }
+        files.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        Ok(files)
+    }
+
+    /// 列出可检索的实例文件，实例索引中已隐藏（IOCM拒绝）的实例不在其中
+    ///
+    /// 对象目录和实例索引中登记的实例不要求文件仍在本地，
+    /// 已迁移到近线/归档层的实例由调用方召回后再读取
+    pub async fn list_retrievable_instance_files(
+        &self,
+        study_instance_uid: &str,
+        series_instance_uid: Option<&str>,
+    ) -> Result<Vec<StoredInstanceFile>> {
+        let mut files: HashMap<String, StoredInstanceFile> = HashMap::new();
+        if let Some(manager) = &self.layout_manager {
+            for object in manager
+                .study_objects(study_instance_uid, series_instance_uid)
+                .await
+            {
+                files.insert(
+                    object.instance.sop_instance_uid.clone(),
+                    StoredInstanceFile {
+                        series_instance_uid: object.instance.series_instance_uid,
+                        sop_instance_uid: object.instance.sop_instance_uid,
+                        file_path: self.storage_dir.join(&object.object_key),
+                    },
+                );
+            }
+        }
+        if let Some(index) = &self.index {
+            for file in index
+                .instance_files(study_instance_uid, series_instance_uid)
+                .await?
+            {
+                if !file.file_path.starts_with(&self.storage_dir) {
+                    warn!(
+                        "实例 {} 的文件不在存储目录下，跳过: {}",
+                        file.sop_instance_uid,
+                        file.file_path.display()
+                    );
+                    continue;
+                }
+                files.entry(file.sop_instance_uid.clone()).or_insert(file);
+            }
+        }
+        match self
+            .list_instance_files(study_instance_uid, series_instance_uid)
+            .await
+        {
+            Ok(local) => {
+                for file in local {
+                    files.entry(file.sop_instance_uid.clone()).or_insert(file);
+                }
+            }
+            Err(PacsError::NotFound(_)) if !files.is_empty() => {}
+            Err(e) => return Err(e),
+        }
+
+        let hidden = self.hidden_instances(study_instance_uid).await?;
+        let mut files: Vec<StoredInstanceFile> = files
+            .into_values()
+            .filter(|file| !hidden.contains(&file.sop_instance_uid))
+            .collect();
+        files.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        Ok(files)
+    }
+
+    /// 实例是否可检索（未被实例索引隐藏）
+    pub async fn is_instance_retrievable(
+        &self,
+        study_instance_uid: &str,
+        sop_instance_uid: &str,
+    ) -> Result<bool> {
+        Ok(!self
+            .hidden_instances(study_instance_uid)
+            .await?
+            .contains(sop_instance_uid))
+    }
+
+    /// 实例索引中已隐藏的实例，未配置索引时为空
+    async fn hidden_instances(&self, study_instance_uid: &str) -> Result<HashSet<String>> {
+        match &self.index {
+            Some(index) => index.hidden_instances(study_instance_uid).await,
+            None => Ok(HashSet::new()),
+        }
+    }
+
+    /// 删除实例文件所在的空目录（序列、检查），不越过存储根目录
+    pub async fn prune_empty_dirs(&self, file_path: &Path) {
+        prune_empty_dirs(file_path, &self.storage_dir).await
+    }
+
+    /// 检查本地文件是否包含DICOM文件头，只读取文件开头
+    pub async fn file_has_meta(path: &Path) -> Result<bool> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut head = [0u8; PREAMBLE_LENGTH + 4];
+        let mut read = 0;
+        while read < head.len() {
+            let n = file.read(&mut head[read..]).await?;
+            if n == 0 {
+                break;
+            }
+            read += n;
+        }
+        Ok(Self::has_file_meta(&head[..read]))
+    }
+
+    /// 检查数据是否包含DICOM文件头（"DICM"魔数，前导可选）
+    pub fn has_file_meta(data: &[u8]) -> bool {
+        data.starts_with(b"DICM")
+            || (data.len() >= PREAMBLE_LENGTH + 4
+                && &data[PREAMBLE_LENGTH..PREAMBLE_LENGTH + 4] == b"DICM")
+    }
+
+    /// 应用调和策略并写入存储，返回存储路径、文件大小和原始患者ID
+    ///
+    /// 与已存储对象逐字节相同时返回None
+    async fn store_object(
+        &self,
+        obj: &mut DefaultDicomObject,
+        original_data: &[u8],
+        has_file_meta: bool,
+        file_path: &Path,
+        policy: Option<&ReconciliationPolicy>,
+    ) -> Result<Option<(PathBuf, u64, Option<String>)>> {
+        let original_patient_id = match policy {
+            Some(policy) => Self::apply_reconciliation(obj, policy),
+            None => None,
+        };
+
+        // 未改写的完整文件按原样保存，避免重新编码
+        let encoded;
+        let bytes = if has_file_meta && original_patient_id.is_none() {
+            original_data
+        } else {
+            encoded = Self::encode_object(obj)?;
+            &encoded[..]
+        };
+
+        let written = self
+            .write_instance(obj, InstanceContent::Bytes(bytes), file_path)
+            .await?;
+        Ok(written.map(|file_path| (file_path, bytes.len() as u64, original_patient_id)))
+    }
+
+    /// 按策略改写患者ID，返回被替换的原始ID
+    fn apply_reconciliation(
+        obj: &mut DefaultDicomObject,
+        policy: &ReconciliationPolicy,
+    ) -> Option<String> {
+        let patient_id = obj
+            .element(tags::PATIENT_ID)
+            .ok()
+            .and_then(|e| e.to_str().ok())
+            .map(|s| s.trim().to_string())?;
+
+        let new_id = policy.reconcile_patient_id(&patient_id)?;
+
+        obj.put(DataElement::new(
+            tags::PATIENT_ID,
+            VR::LO,
+            PrimitiveValue::from(new_id.as_str()),
+        ));
+        if policy.keep_original_id {
+            let item = InMemDicomObject::from_element_iter([DataElement::new(
+                tags::PATIENT_ID,
+                VR::LO,
+                PrimitiveValue::from(patient_id.as_str()),
+            )]);
+            obj.put(DataElement::new(
+                tags::OTHER_PATIENT_I_DS_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![item]),
+            ));
+        }
+
+        debug!("患者ID已调和: {} -> {}", patient_id, new_id);
+        Some(patient_id)
+    }
+
+    /// 读取不带文件元信息的数据集，并补全文件元信息
+    fn read_bare_dataset(data: &[u8]) -> Result<DefaultDicomObject> {
+        let ts = TransferSyntaxRegistry
+            .get(dicom::transfer_syntax::entries::IMPLICIT_VR_LITTLE_ENDIAN.uid())
+            .ok_or_else(|| PacsError::DicomParseError("传输语法注册表不可用".to_string()))?;
+
+        let dataset = InMemDicomObject::read_dataset_with_ts(Cursor::new(data), ts)
+            .map_err(|e| PacsError::DicomParseError(format!("无法解析DICOM数据集: {:?}", e)))?;
+
+        dataset
+            .with_meta(FileMetaTableBuilder::new().transfer_syntax(ts.uid()))
+            .map_err(|e| PacsError::DicomParseError(format!("无法生成文件元信息: {:?}", e)))
+    }
+
+    /// 生成前导、"DICM"魔数和文件元信息组
+    fn file_header(meta: &DatasetMeta) -> Result<Vec<u8>> {
+        let table = FileMetaTableBuilder::new()
+            .media_storage_sop_class_uid(meta.sop_class_uid.as_str())
+            .media_storage_sop_instance_uid(meta.sop_instance_uid.as_str())
+            .transfer_syntax(meta.transfer_syntax_uid.as_str())
+            .build()
+            .map_err(|e| PacsError::DicomParseError(format!("无法生成文件元信息: {:?}", e)))?;
+
+        let mut header = vec![0u8; PREAMBLE_LENGTH];
+        header.extend_from_slice(b"DICM");
+        table
+            .write(&mut header)
+            .map_err(|e| PacsError::Dicom(format!("文件元信息编码失败: {:?}", e)))?;
+        Ok(header)
+    }
+
+    /// 将DICOM对象编码为文件格式字节
+    pub(crate) fn encode_object(obj: &DefaultDicomObject) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        obj.write_all(&mut buffer)
+            .map_err(|e| PacsError::Dicom(format!("DICOM对象编码失败: {:?}", e)))?;
+        Ok(buffer)
+    }
+
+    /// 读取必需的UID元素
+    fn required_uid(obj: &DefaultDicomObject, tag: dicom::core::Tag, name: &str) -> Result<String> {
+        let uid = obj
+            .element(tag)
+            .ok()
+            .and_then(|e| e.to_str().ok())
+            .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+            .unwrap_or_default();
+
+        if uid.is_empty() || !pacs_core::utils::is_valid_dicom_uid(&uid) {
+            warn!("入库数据缺少有效的{}: {:?}", name, uid);
+            return Err(PacsError::Validation(format!("缺少有效的{}", name)));
+        }
+        Ok(uid)
+    }
+}
+
+/// 删除文件所在的空目录，直到存储根目录为止
+pub(crate) async fn prune_empty_dirs(file_path: &Path, storage_dir: &Path) {
+    let mut dir = file_path.parent();
+    while let Some(current) = dir {
+        if current == storage_dir || !current.starts_with(storage_dir) {
+            break;
+        }
+        if tokio::fs::remove_dir(current).await.is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::dictionary_std::uids;
+
+    fn sample_instance(patient_id: &str, sop_instance_uid: &str) -> Vec<u8> {
+        let obj = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, sop_instance_uid),
+            DataElement::new(tags::PATIENT_ID, VR::LO, patient_id),
+            DataElement::new(
+                tags::STUDY_INSTANCE_UID,
+                VR::UI,
+                "1.2.826.0.1.3680043.9.7382.1",
+            ),
+            DataElement::new(
+                tags::SERIES_INSTANCE_UID,
+                VR::UI,
+                "1.2.826.0.1.3680043.9.7382.1.1",
+            ),
+            DataElement::new(tags::MODALITY, VR::CS, "CT"),
+        ]);
+        let obj = obj
+            .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+            .unwrap();
+        IngestPipeline::encode_object(&obj).unwrap()
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("pacs-ingest-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_reconcile_patient_id() {
+        let mut policy = ReconciliationPolicy {
+            outside_id_prefix: Some("EXT_".to_string()),
+            ..Default::default()
+        };
+        policy
+            .patient_id_map
+            .insert("OUT123".to_string(), "PAT001".to_string());
+
+        assert_eq!(
+            policy.reconcile_patient_id("OUT123"),
+            Some("PAT001".to_string())
+        );
+        assert_eq!(
+            policy.reconcile_patient_id("999"),
+            Some("EXT_999".to_string())
+        );
+        assert_eq!(policy.reconcile_patient_id("EXT_999"), None);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_deduplicates_and_reconciles() {
+        let dir = temp_dir();
+        let pipeline = IngestPipeline::new(&dir);
+        let source = IngestSource::Import {
+            job_id: "test".to_string(),
+        };
+        let policy = ReconciliationPolicy {
+            outside_id_prefix: Some("EXT_".to_string()),
+            keep_original_id: true,
+            ..Default::default()
+        };
+        let data = sample_instance("12345", "1.2.826.0.1.3680043.9.7382.1.1.1");
+
+        let outcome = pipeline
+            .ingest(&data, &source, Some(&policy))
+            .await
+            .unwrap();
+        match outcome {
+            IngestOutcome::Stored(instance) => {
+                assert_eq!(instance.original_patient_id.as_deref(), Some("12345"));
+                assert_eq!(instance.metadata.patient_id.as_deref(), Some("EXT_12345"));
+                assert!(instance.file_path.exists());
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+
+        let outcome = pipeline
+            .ingest(&data, &source, Some(&policy))
+            .await
+            .unwrap();
+        assert!(matches!(outcome, IngestOutcome::Duplicate { .. }));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_stream_keeps_original_bytes() {
+        let dir = temp_dir();
+        let pipeline = IngestPipeline::new(&dir);
+        let source = IngestSource::Upload { username: None };
+        let data = sample_instance("PAT001", "1.2.826.0.1.3680043.9.7382.1.1.2");
+
+        let outcome = pipeline
+            .ingest_stream(Cursor::new(data.clone()), &source, None)
+            .await
+            .unwrap();
+        match outcome {
+            IngestOutcome::Stored(instance) => {
+                assert_eq!(instance.file_size, data.len() as u64);
+                assert_eq!(instance.metadata.patient_id.as_deref(), Some("PAT001"));
+                assert_eq!(std::fs::read(&instance.file_path).unwrap(), data);
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        let spooled = std::fs::read_dir(dir.join(INCOMING_DIR)).unwrap().count();
+        assert_eq!(spooled, 0);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_follows_layout_manager() {
+        use pacs_storage::{MigrationState, StorageConfig, StorageManager, StorageType};
+
+        let dir = temp_dir();
+        let storage = Arc::new(
+            StorageManager::new(StorageConfig {
+                storage_type: StorageType::Local,
+                local_path: Some(dir.to_string_lossy().to_string()),
+                object_store_config: None,
+            })
+            .await
+            .unwrap(),
+        );
+        let manager = Arc::new(LayoutManager::new(storage, StorageLayout::hierarchical()).unwrap());
+        let pipeline = IngestPipeline::new(&dir).with_layout_manager(manager.clone());
+        let source = IngestSource::Upload { username: None };
+        let study_uid = "1.2.826.0.1.3680043.9.7382.1";
+        let series_uid = "1.2.826.0.1.3680043.9.7382.1.1";
+
+        let first = "1.2.826.0.1.3680043.9.7382.1.1.1";
+        pipeline
+            .ingest(&sample_instance("PAT001", first), &source, None)
+            .await
+            .unwrap();
+        let object = manager.locate(first).await.unwrap();
+        assert_eq!(
+            object.object_key,
+            format!("{}/{}/{}.dcm", study_uid, series_uid, first)
+        );
+
+        // 迁移到哈希布局后，新实例按新布局落盘，两个实例都能定位和列出
+        let target = StorageLayout::hashed(1);
+        let migration_id = manager.start_migration(target.clone()).await.unwrap();
+        for _ in 0..100 {
+            let migration = manager.get_migration(&migration_id).await.unwrap();
+            if migration.state != MigrationState::Running {
+                assert_eq!(migration.migrated_objects, 1);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let second = "1.2.826.0.1.3680043.9.7382.1.1.2";
+        let outcome = pipeline
+            .ingest_stream(
+                Cursor::new(sample_instance("PAT001", second)),
+                &source,
+                None,
+            )
+            .await
+            .unwrap();
+        let IngestOutcome::Stored(instance) = outcome else {
+            panic!("instance was not stored");
+        };
+        let expected = target
+            .object_key(&InstanceKey::new(study_uid, series_uid, second))
+            .unwrap();
+        assert_eq!(instance.file_path, dir.join(&expected));
+        assert_eq!(manager.locate(second).await.unwrap().object_key, expected);
+
+        let files = pipeline.list_instance_files(study_uid, None).await.unwrap();
+        let uids: Vec<_> = files.iter().map(|f| f.sop_instance_uid.as_str()).collect();
+        assert_eq!(uids.len(), 2);
+        assert!(uids.contains(&first) && uids.contains(&second));
+        for file in &files {
+            assert!(file.file_path.exists());
+        }
+        assert_eq!(
+            pipeline
+                .instance_path(study_uid, series_uid, first)
+                .await
+                .unwrap(),
+            dir.join(manager.locate(first).await.unwrap().object_key)
+        );
+        assert!(pipeline
+            .instance_path(study_uid, "..", first)
+            .await
+            .is_err());
+
+        // 迁出本地存储的实例仍可检索，由调用方召回
+        let migrated = dir.join(manager.locate(first).await.unwrap().object_key);
+        std::fs::remove_file(&migrated).unwrap();
+        let local = pipeline.list_instance_files(study_uid, None).await.unwrap();
+        assert_eq!(local.len(), 1);
+        let retrievable = pipeline
+            .list_retrievable_instance_files(study_uid, Some(series_uid))
+            .await
+            .unwrap();
+        assert_eq!(retrievable.len(), 2);
+        assert!(retrievable.iter().any(|f| f.file_path == migrated));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_layout_manager_decides_resends_by_content() {
+        use pacs_storage::{StorageConfig, StorageManager, StorageType};
+
+        let dir = temp_dir();
+        let storage = Arc::new(
+            StorageManager::new(StorageConfig {
+                storage_type: StorageType::Local,
+                local_path: Some(dir.to_string_lossy().to_string()),
+                object_store_config: None,
+            })
+            .await
+            .unwrap(),
+        );
+        let manager = Arc::new(LayoutManager::new(storage, StorageLayout::hierarchical()).unwrap());
+        let pipeline = IngestPipeline::new(&dir).with_layout_manager(manager.clone());
+        let source = IngestSource::Upload { username: None };
+        let sop_uid = "1.2.826.0.1.3680043.9.7382.1.1.1";
+        let original = sample_instance("PAT001", sop_uid);
+
+        let outcome = pipeline
+            .ingest_stream(Cursor::new(original.clone()), &source, None)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, IngestOutcome::Stored(_)));
+
+        // 逐字节相同的重复发送不重复入库
+        let outcome = pipeline.ingest(&original, &source, None).await.unwrap();
+        assert!(matches!(outcome, IngestOutcome::Duplicate { .. }));
+        let outcome = pipeline
+            .ingest_stream(Cursor::new(original.clone()), &source, None)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, IngestOutcome::Duplicate { .. }));
+
+        // 内容不同的重复发送覆盖原对象
+        let corrected = sample_instance("PAT002", sop_uid);
+        let outcome = pipeline
+            .ingest_stream(Cursor::new(corrected.clone()), &source, None)
+            .await
+            .unwrap();
+        let IngestOutcome::Stored(instance) = outcome else {
+            panic!("corrected instance was not stored");
+        };
+        assert_eq!(std::fs::read(&instance.file_path).unwrap(), corrected);
+        assert_eq!(
+            manager.locate(sop_uid).await.unwrap().size,
+            corrected.len() as u64
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
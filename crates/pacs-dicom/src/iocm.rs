@@ -0,0 +1,591 @@
+//! IHE IOCM实例拒绝与延迟清除
+//!
+//! 收到（或本地生成）拒绝说明后，被引用的实例立即从查询结果中隐藏；
+//! 经过宽限期后再物理删除文件与数据库记录。已拒绝的实例再次被发送时
+//! 保持隐藏，并重新安排清除。
+
+use crate::ingest::{
+    prune_empty_dirs, IngestListener, IngestOutcome, IngestPipeline, IngestSource, IngestedInstance,
+};
+use crate::parser::DicomParser;
+use crate::references::ReferenceExtractor;
+use crate::rejection::{
+    RejectedInstance, RejectionNoteBuilder, RejectionNoteRequest, RejectionReason,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dicom::core::Tag;
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::InMemDicomObject;
+use pacs_core::{uid::validate_uid, PacsError, Result};
+use pacs_database::{DatabasePool, DatabaseQueries, DbRejectedInstance, NewRejectedInstance};
+use pacs_storage::LayoutManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// 实例拒绝配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IocmConfig {
+    /// 拒绝后到物理清除的宽限期
+    pub purge_grace_period: Duration,
+    /// 后台清除间隔
+    pub purge_interval: Duration,
+    /// 每轮最多清除的实例数
+    pub purge_batch_size: i64,
+}
+
+impl Default for IocmConfig {
+    fn default() -> Self {
+        Self {
+            purge_grace_period: Duration::from_secs(24 * 3600),
+            purge_interval: Duration::from_secs(600),
+            purge_batch_size: 100,
+        }
+    }
+}
+
+/// 实例拒绝请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceRejectionRequest {
+    /// 被拒绝实例所属检查UID
+    pub study_instance_uid: String,
+    /// 被拒绝的SOP实例UID
+    pub sop_instance_uids: Vec<String>,
+    /// 拒绝原因
+    pub reason: RejectionReason,
+}
+
+/// 拒绝处理结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectionReport {
+    /// 拒绝说明SOP实例UID
+    pub rejection_note_uid: String,
+    /// 拒绝原因
+    pub reason: RejectionReason,
+    /// 拒绝说明所属检查UID
+    pub study_instance_uid: String,
+    /// 本次新拒绝的实例数
+    pub rejected: usize,
+    /// 此前已被拒绝的实例数
+    pub already_rejected: usize,
+    /// 计划物理清除的时间
+    pub purge_after: DateTime<Utc>,
+}
+
+/// 实例拒绝服务
+///
+/// 作为入库监听器注册到入库管道后，经C-STORE等途径收到的拒绝说明会被自动处理
+pub struct InstanceRejectionService {
+    /// 数据库连接池
+    db: Arc<DatabasePool>,
+    /// 存储根目录
+    storage_dir: PathBuf,
+    /// 拒绝配置
+    config: IocmConfig,
+    /// 存储布局管理器，清除实例时同时移除其对象目录记录
+    layout_manager: Option<Arc<LayoutManager>>,
+}
+
+impl InstanceRejectionService {
+    /// 创建新的实例拒绝服务
+    pub fn new<P: Into<PathBuf>>(
+        db: Arc<DatabasePool>,
+        storage_dir: P,
+        config: IocmConfig,
+    ) -> Self {
+        Self {
+            db,
+            storage_dir: storage_dir.into(),
+            config,
+            layout_manager: None,
+        }
+    }
+
+    /// 设置存储布局管理器
+    pub fn with_layout_manager(mut self, manager: Arc<LayoutManager>) -> Self {
+        self.layout_manager = Some(manager);
+        self
+    }
+
+    /// 获取拒绝配置
+    pub fn config(&self) -> &IocmConfig {
+        &self.config
+    }
+
+    /// 拒绝检查中的实例：生成拒绝说明，隐藏实例并将拒绝说明入库
+    pub async fn reject(
+        &self,
+        pipeline: &IngestPipeline,
+        request: &InstanceRejectionRequest,
+        rejected_by: &str,
+    ) -> Result<RejectionReport> {
+        if request.sop_instance_uids.is_empty() {
+            return Err(PacsError::Validation(
+                "至少需要指定一个被拒绝的实例".to_string(),
+            ));
+        }
+
+        let files: HashMap<String, _> = pipeline
+            .list_instance_files(&request.study_instance_uid, None)
+            .await?
+            .into_iter()
+            .map(|file| (file.sop_instance_uid.clone(), file))
+            .collect();
+
+        let mut instances = Vec::new();
+        let mut template = None;
+        for sop_instance_uid in &request.sop_instance_uids {
+            let file = files.get(sop_instance_uid).ok_or_else(|| {
+                PacsError::NotFound(format!(
+                    "检查 {} 中的实例 {}",
+                    request.study_instance_uid, sop_instance_uid
+                ))
+            })?;
+            let obj = DicomParser::read_object(&tokio::fs::read(&file.file_path).await?)?;
+            instances.push(RejectedInstance {
+                series_instance_uid: file.series_instance_uid.clone(),
+                sop_class_uid: get_string(&obj, tags::SOP_CLASS_UID).unwrap_or_default(),
+                sop_instance_uid: sop_instance_uid.clone(),
+            });
+            template.get_or_insert(obj);
+        }
+        let template = template.ok_or_else(|| PacsError::Internal("没有可用的实例".to_string()))?;
+
+        let note = RejectionNoteBuilder::build(&RejectionNoteRequest {
+            study_instance_uid: request.study_instance_uid.clone(),
+            patient_id: get_string(&template, tags::PATIENT_ID).unwrap_or_default(),
+            patient_name: get_string(&template, tags::PATIENT_NAME).unwrap_or_default(),
+            patient_birth_date: get_string(&template, tags::PATIENT_BIRTH_DATE),
+            patient_sex: get_string(&template, tags::PATIENT_SEX),
+            accession_number: get_string(&template, tags::ACCESSION_NUMBER),
+            reason: request.reason,
+            instances,
+        })?;
+
+        // 先隐藏实例再入库拒绝说明，入库失败时撤回
+        let report = self
+            .process_rejection_note(&note, Some(rejected_by))
+            .await?
+            .ok_or_else(|| PacsError::Internal("生成的拒绝说明无法识别".to_string()))?;
+
+        let source = IngestSource::Upload {
+            username: Some(rejected_by.to_string()),
+        };
+        let stored = match IngestPipeline::encode_object(&note) {
+            Ok(bytes) => pipeline.ingest(&bytes, &source, None).await,
+            Err(e) => Err(e),
+        };
+        match stored {
+            Ok(IngestOutcome::Stored(_)) => Ok(report),
+            Ok(IngestOutcome::Duplicate { sop_instance_uid }) => {
+                self.withdraw(&report.rejection_note_uid).await?;
+                Err(PacsError::Internal(format!(
+                    "Duplicate SOP Instance UID: {}",
+                    sop_instance_uid
+                )))
+            }
+            Err(e) => {
+                self.withdraw(&report.rejection_note_uid).await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// 处理拒绝说明，隐藏其引用的实例；对象不是拒绝说明时返回None
+    ///
+    /// 引用无法解析或任一引用的UID不合法时拒绝整个拒绝说明，不隐藏任何实例。
+    /// 实例文件路径取自实例索引，尚未入库的实例在再次接收时补记
+    pub async fn process_rejection_note(
+        &self,
+        note: &InMemDicomObject,
+        rejected_by: Option<&str>,
+    ) -> Result<Option<RejectionReport>> {
+        let Some(reason) = RejectionReason::from_rejection_note(note) else {
+            return Ok(None);
+        };
+        let rejection_note_uid = get_string(note, tags::SOP_INSTANCE_UID)
+            .ok_or_else(|| PacsError::Validation("拒绝说明缺少SOP Instance UID".to_string()))?;
+        let study_instance_uid = get_string(note, tags::STUDY_INSTANCE_UID).unwrap_or_default();
+        let purge_after = self.purge_deadline();
+
+        let references = ReferenceExtractor::extract(note)
+            .filter(|references| !references.is_empty())
+            .ok_or_else(|| {
+                warn!("拒绝说明 {} 没有可解析的实例引用", rejection_note_uid);
+                PacsError::Validation(format!(
+                    "Rejection note {} has no readable instance references",
+                    rejection_note_uid
+                ))
+            })?;
+        for reference in &references {
+            for uid in [
+                &reference.study_instance_uid,
+                &reference.referenced_series_uid,
+                &reference.referenced_sop_instance_uid,
+            ] {
+                if let Err(e) = validate_uid(uid) {
+                    warn!("拒绝说明 {} 包含无效的UID: {}", rejection_note_uid, e);
+                    return Err(e);
+                }
+            }
+        }
+
+        let queries = DatabaseQueries::new(&self.db);
+        let mut rejected = 0;
+        let mut already_rejected = 0;
+        for reference in references {
+            let file_path = queries
+                .get_instance_by_uid(&reference.referenced_sop_instance_uid)
+                .await?
+                .map(|instance| instance.file_path);
+
+            let inserted = queries
+                .record_instance_rejection(&NewRejectedInstance {
+                    sop_instance_uid: reference.referenced_sop_instance_uid,
+                    study_uid: reference.study_instance_uid,
+                    series_uid: reference.referenced_series_uid,
+                    sop_class_uid: reference.referenced_sop_class_uid,
+                    reason_code: reason.code_value().to_string(),
+                    rejection_note_uid: rejection_note_uid.clone(),
+                    rejected_by: rejected_by.map(str::to_string),
+                    file_path,
+                    purge_after,
+                })
+                .await?;
+            if inserted {
+                rejected += 1;
+            } else {
+                already_rejected += 1;
+            }
+        }
+
+        info!(
+            "处理拒绝说明 {}: 检查 {}（{}），新拒绝 {} 个实例，{} 个此前已拒绝",
+            rejection_note_uid,
+            study_instance_uid,
+            reason.code_meaning(),
+            rejected,
+            already_rejected
+        );
+        Ok(Some(RejectionReport {
+            rejection_note_uid,
+            reason,
+            study_instance_uid,
+            rejected,
+            already_rejected,
+            purge_after,
+        }))
+    }
+
+    /// 清除宽限期已过的被拒绝实例，返回本轮清除的实例数
+    pub async fn purge_once(&self) -> Result<usize> {
+        let queries = DatabaseQueries::new(&self.db);
+        let due = queries
+            .get_due_rejections(self.config.purge_batch_size)
+            .await?;
+
+        let mut purged = 0;
+        for rejection in due {
+            // 只删除存储目录下的文件，其他路径只清除记录
+            let path = rejection
+                .file_path
+                .as_ref()
+                .map(PathBuf::from)
+                .filter(|path| {
+                    let inside = path.starts_with(&self.storage_dir)
+                        && path
+                            .components()
+                            .all(|c| !matches!(c, std::path::Component::ParentDir));
+                    if !inside {
+                        warn!("被拒绝实例文件不在存储目录下，不予删除: {:?}", path);
+                    }
+                    inside
+                });
+            if let Some(path) = path {
+                match tokio::fs::remove_file(&path).await {
+                    Ok(()) => prune_empty_dirs(&path, &self.storage_dir).await,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        warn!("被拒绝实例文件删除失败: {:?} ({})", path, e);
+                        continue;
+                    }
+                }
+            }
+            if let Some(manager) = &self.layout_manager {
+                manager.forget(&rejection.sop_instance_uid).await?;
+            }
+            queries
+                .delete_instance_by_uid(&rejection.sop_instance_uid)
+                .await?;
+            queries
+                .mark_rejection_purged(&rejection.sop_instance_uid)
+                .await?;
+            debug!(
+                "已清除被拒绝实例: {} (拒绝说明 {})",
+                rejection.sop_instance_uid, rejection.rejection_note_uid
+            );
+            purged += 1;
+        }
+
+        if purged > 0 {
+            info!("本轮清除 {} 个被拒绝实例", purged);
+        }
+        Ok(purged)
+    }
+
+    /// 启动后台清除循环
+    pub fn start(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(service.config.purge_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = service.purge_once().await {
+                    error!("被拒绝实例清除失败: {}", e);
+                }
+            }
+        })
+    }
+
+    /// 获取最近的拒绝记录
+    pub async fn list_rejections(
+        &self,
+        study_uid: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<DbRejectedInstance>> {
+        DatabaseQueries::new(&self.db)
+            .list_rejections(study_uid, limit)
+            .await
+    }
+
+    /// 撤回拒绝说明产生的拒绝记录，被引用的实例重新可见
+    async fn withdraw(&self, rejection_note_uid: &str) -> Result<()> {
+        let restored = DatabaseQueries::new(&self.db)
+            .delete_rejections_by_note_uid(rejection_note_uid)
+            .await?;
+        warn!(
+            "撤回拒绝说明 {}，{} 个实例恢复可见",
+            rejection_note_uid, restored
+        );
+        Ok(())
+    }
+
+    /// 计算物理清除时间
+    fn purge_deadline(&self) -> DateTime<Utc> {
+        let now = Utc::now();
+        chrono::Duration::from_std(self.config.purge_grace_period)
+            .ok()
+            .and_then(|period| now.checked_add_signed(period))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+#[async_trait]
+impl IngestListener for InstanceRejectionService {
+    async fn on_instance_stored(
+        &self,
+        instance: &IngestedInstance,
+        source: &IngestSource,
+    ) -> Result<()> {
+        let is_kos = instance
+            .metadata
+            .sop_class_uid
+            .as_deref()
+            .map(|uid| uid.trim_end_matches('\0') == uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE)
+            .unwrap_or(false);
+        if is_kos {
+            let obj = DicomParser::read_object(&tokio::fs::read(&instance.file_path).await?)?;
+            let rejected_by = source.to_string();
+            if self
+                .process_rejection_note(&obj, Some(&rejected_by))
+                .await?
+                .is_some()
+            {
+                return Ok(());
+            }
+        }
+
+        let queries = DatabaseQueries::new(&self.db);
+        if let Some(rejection) = queries
+            .get_instance_rejection(&instance.sop_instance_uid)
+            .await?
+        {
+            warn!(
+                "已拒绝的实例再次被接收，保持隐藏: {} (拒绝说明 {}，{})",
+                instance.sop_instance_uid, rejection.rejection_note_uid, source
+            );
+            queries
+                .reschedule_rejection_purge(
+                    &instance.sop_instance_uid,
+                    &instance.file_path.to_string_lossy(),
+                    self.purge_deadline(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// 获取去除填充字符后的字符串值
+fn get_string(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    obj.element(tag)
+        .ok()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejection_request_deserialize() {
+        let request: InstanceRejectionRequest = serde_json::from_str(
+            r#"{"study_instance_uid":"1.2.3","sop_instance_uids":["1.2.3.1.1"],"reason":"PatientSafety"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.reason, RejectionReason::PatientSafety);
+        assert_eq!(request.reason.code_value(), "113037");
+
+        let config = IocmConfig::default();
+        assert!(config.purge_grace_period > config.purge_interval);
+    }
+
+    /// 构造CT实例
+    fn ct_instance(study_uid: &str, series_uid: &str, sop_instance_uid: &str) -> Vec<u8> {
+        use dicom::core::{DataElement, VR};
+        use dicom::object::FileMetaTableBuilder;
+
+        let obj = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, sop_instance_uid),
+            DataElement::new(tags::PATIENT_ID, VR::LO, "IOCM001"),
+            DataElement::new(tags::PATIENT_NAME, VR::PN, "Test^Iocm"),
+            DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, study_uid),
+            DataElement::new(tags::SERIES_INSTANCE_UID, VR::UI, series_uid),
+            DataElement::new(tags::MODALITY, VR::CS, "CT"),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+        .unwrap();
+        IngestPipeline::encode_object(&obj).unwrap()
+    }
+
+    /// 已拒绝的实例不再出现在C-FIND结果和WADO检索中（需设置PACS_TEST_DATABASE_URL）
+    #[tokio::test]
+    async fn test_rejected_instance_hidden_from_query_and_retrieve() {
+        use crate::query;
+        use crate::services::{CFindService, CommandField, DicomService, DimseRequest};
+        use dicom::core::{DataElement, VR};
+
+        let Ok(database_url) = std::env::var("PACS_TEST_DATABASE_URL") else {
+            return;
+        };
+        let db = Arc::new(DatabasePool::new(&database_url, 2).await.unwrap());
+        DatabaseQueries::new(&db).create_tables().await.unwrap();
+
+        let dir = std::env::temp_dir().join(format!("pacs-iocm-{}", uuid::Uuid::new_v4()));
+        let study_uid = format!("2.25.{}", uuid::Uuid::new_v4().as_u128());
+        let series_uid = format!("{}.1", study_uid);
+        let kept_uid = format!("{}.1.1", study_uid);
+        let rejected_uid = format!("{}.1.2", study_uid);
+
+        let service = Arc::new(InstanceRejectionService::new(
+            db.clone(),
+            &dir,
+            IocmConfig::default(),
+        ));
+        let pipeline = IngestPipeline::new(&dir)
+            .with_database(db.clone())
+            .with_listener(service.clone());
+        let source = IngestSource::Upload { username: None };
+        for sop_instance_uid in [&kept_uid, &rejected_uid] {
+            let bytes = ct_instance(&study_uid, &series_uid, sop_instance_uid);
+            pipeline.ingest(&bytes, &source, None).await.unwrap();
+        }
+
+        service
+            .reject(
+                &pipeline,
+                &InstanceRejectionRequest {
+                    study_instance_uid: study_uid.clone(),
+                    sop_instance_uids: vec![rejected_uid.clone()],
+                    reason: RejectionReason::QualityReasons,
+                },
+                "admin",
+            )
+            .await
+            .unwrap();
+
+        // C-FIND实例级查询
+        let identifier = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::QUERY_RETRIEVE_LEVEL, VR::CS, "IMAGE"),
+            DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, study_uid.as_str()),
+            DataElement::new(tags::SERIES_INSTANCE_UID, VR::UI, series_uid.as_str()),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, ""),
+        ]);
+        let responses = CFindService::with_database(db.clone())
+            .handle_request_all(DimseRequest {
+                command_field: CommandField::CFind,
+                message_id: 1,
+                affected_sop_class_uid: uids::STUDY_ROOT_QUERY_RETRIEVE_INFORMATION_MODEL_FIND
+                    .to_string(),
+                dataset: Some(query::encode_identifier(&identifier).unwrap()),
+                dataset_file: None,
+                affected_sop_instance_uid: None,
+                transfer_syntax_uid: None,
+                calling_ae_title: None,
+            })
+            .await
+            .unwrap();
+        let matches: Vec<String> = responses
+            .iter()
+            .filter_map(|response| response.dataset.as_deref())
+            .map(|dataset| {
+                let identifier = query::decode_identifier(dataset).unwrap();
+                get_string(&identifier, tags::SOP_INSTANCE_UID).unwrap()
+            })
+            .collect();
+        assert_eq!(matches, vec![kept_uid.clone()]);
+
+        // WADO检索
+        let files = pipeline
+            .list_retrievable_instance_files(&study_uid, Some(&series_uid))
+            .await
+            .unwrap();
+        let retrievable: Vec<_> = files.iter().map(|f| f.sop_instance_uid.as_str()).collect();
+        assert_eq!(retrievable, vec![kept_uid.as_str()]);
+        assert!(!pipeline
+            .is_instance_retrievable(&study_uid, &rejected_uid)
+            .await
+            .unwrap());
+
+        // 引用了非法UID的拒绝说明被整体拒绝
+        let crafted = RejectionNoteBuilder::build(&RejectionNoteRequest {
+            study_instance_uid: study_uid.clone(),
+            patient_id: "PAT".to_string(),
+            patient_name: String::new(),
+            patient_birth_date: None,
+            patient_sex: None,
+            accession_number: None,
+            reason: RejectionReason::QualityReasons,
+            instances: vec![RejectedInstance {
+                series_instance_uid: "..".to_string(),
+                sop_class_uid: uids::CT_IMAGE_STORAGE.to_string(),
+                sop_instance_uid: kept_uid.clone(),
+            }],
+        })
+        .unwrap();
+        assert!(service.process_rejection_note(&crafted, None).await.is_err());
+        assert!(pipeline
+            .is_instance_retrievable(&study_uid, &kept_uid)
+            .await
+            .unwrap());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
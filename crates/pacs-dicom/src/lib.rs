@@ -4,14 +4,62 @@
 
 pub mod association;
 pub mod dimse;
+pub mod encapsulated;
+pub mod forwarding;
+pub mod import;
+pub mod index;
+pub mod ingest;
+pub mod iocm;
 pub mod parser;
+pub mod prefetch;
+pub mod query;
+pub mod references;
+pub mod rejection;
+pub mod scu;
 pub mod server;
 pub mod services;
+pub mod sr;
+pub mod stability;
+pub mod study_operations;
 pub mod transfer_syntax;
 pub mod validator;
 
+pub use encapsulated::{EncapsulatedDocument, EncapsulatedDocumentHandler, EncapsulatedPdfRequest};
+pub use forwarding::{
+    ForwardQueueStatus, ForwardingConfig, ForwardingDestination, ForwardingRouter, ForwardingRule,
+    RetryPolicy, RuleCondition,
+};
+pub use import::{DicomImporter, ImportJob, ImportJobStatus, ImportSource};
+pub use index::{DatabaseInstanceIndex, DatabaseRelocationListener, InstanceIndex};
+pub use ingest::{
+    DatasetMeta, IngestListener, IngestOutcome, IngestPipeline, IngestSource, IngestedInstance,
+    ReconciliationPolicy, StoredInstanceFile,
+};
+pub use iocm::{InstanceRejectionRequest, InstanceRejectionService, IocmConfig, RejectionReport};
 pub use parser::{DicomParser, ParsedDicomObject};
+pub use prefetch::{
+    PrefetchConfig, PrefetchReport, PrefetchRequest, PrefetchService, PrefetchTrigger,
+    PriorSelector,
+};
+pub use query::{InstanceQueryService, QueryLevel};
+pub use references::{InstanceReference, ReferenceExtractor, ReferenceKind};
+pub use rejection::{
+    RejectedInstance, RejectionNoteBuilder, RejectionNoteRequest, RejectionReason,
+};
+pub use scu::{
+    DicomScu, MoveResult, RemoteAe, ScuConfig, StoreResult, StudyQuery, StudyQueryResult,
+};
 pub use server::{DicomServer, DicomServerConfig};
 pub use services::*;
+pub use sr::{
+    RadiationDoseRecorder, RadiationDoseSummary, SrContentItem, SrParser, SrValue, StructuredReport,
+};
+pub use stability::{
+    CompletionTrigger, EventDeliveries, StabilityConfig, StudyCompletedEvent,
+    StudyCompletedListener, StudyStabilityTracker,
+};
+pub use study_operations::{
+    PatientDemographics, StudyOperation, StudyOperationReport, StudyOperationService,
+};
 pub use transfer_syntax::{TransferSyntaxInfo, TransferSyntaxManager};
 pub use validator::{DicomValidator, ValidationResult};
@@ -5,7 +5,8 @@
 use dicom::core::value::{PrimitiveValue, Value};
 use dicom::dictionary_std::tags;
 use dicom::encoding::TransferSyntax;
-use dicom::object::{open_file, DefaultDicomObject, InMemDicomObject};
+use dicom::object::file::ReadPreamble;
+use dicom::object::{open_file, DefaultDicomObject, OpenFileOptions};
 use pacs_core::{PacsError, Result};
 use std::io::Cursor;
 use std::path::Path;
@@ -40,18 +41,28 @@ impl DicomParser {
         })?;
 
         debug!("成功解析DICOM文件，开始提取元数据");
-        Self::extract_metadata(obj)
+        Self::extract_metadata(&obj)
     }
 
     /// 解析DICOM字节数据
     pub async fn parse_bytes(data: &[u8]) -> Result<ParsedDicomObject> {
         info!("开始解析DICOM字节数据，大小: {} bytes", data.len());
 
-        // 简化实现：暂时不支持字节数据直接解析
-        // 可以先写入临时文件再解析，或者使用其他方法
-        Err(PacsError::DicomParseError(
-            "字节数据解析暂未实现，请使用parse_file方法".to_string(),
-        ))
+        let obj = Self::read_object(data)?;
+        Self::extract_metadata(&obj)
+    }
+
+    /// 从字节数据读取DICOM文件对象
+    ///
+    /// 数据需为DICOM文件格式（文件元信息头之前的128字节前导可有可无）
+    pub fn read_object(data: &[u8]) -> Result<DefaultDicomObject> {
+        OpenFileOptions::new()
+            .read_preamble(ReadPreamble::Auto)
+            .from_reader(Cursor::new(data))
+            .map_err(|e| {
+                error!("DICOM字节数据解析失败: {:?}", e);
+                PacsError::DicomParseError(format!("无法解析DICOM数据: {:?}", e))
+            })
     }
 
     /// 验证DICOM文件完整性
@@ -87,55 +98,62 @@ impl DicomParser {
     }
 
     /// 从DICOM对象中提取元数据
-    fn extract_metadata(obj: impl Into<DefaultDicomObject>) -> Result<ParsedDicomObject> {
-        let obj = obj.into();
+    pub fn extract_metadata(obj: &DefaultDicomObject) -> Result<ParsedDicomObject> {
         let mut parsed = ParsedDicomObject::new();
 
         // 提取患者信息
-        parsed.patient_id = Self::get_string_element(&obj, tags::PATIENT_ID);
-        parsed.patient_name = Self::get_string_element(&obj, tags::PATIENT_NAME);
-        parsed.patient_birth_date = Self::get_string_element(&obj, tags::PATIENT_BIRTH_DATE);
-        parsed.patient_sex = Self::get_string_element(&obj, tags::PATIENT_SEX);
+        parsed.patient_id = Self::get_string_element(obj, tags::PATIENT_ID);
+        parsed.patient_name = Self::get_string_element(obj, tags::PATIENT_NAME);
+        parsed.patient_birth_date = Self::get_string_element(obj, tags::PATIENT_BIRTH_DATE);
+        parsed.patient_sex = Self::get_string_element(obj, tags::PATIENT_SEX);
 
         // 提取检查信息
-        parsed.study_instance_uid = Self::get_string_element(&obj, tags::STUDY_INSTANCE_UID);
-        parsed.study_date = Self::get_string_element(&obj, tags::STUDY_DATE);
-        parsed.study_time = Self::get_string_element(&obj, tags::STUDY_TIME);
-        parsed.study_description = Self::get_string_element(&obj, tags::STUDY_DESCRIPTION);
-        parsed.accession_number = Self::get_string_element(&obj, tags::ACCESSION_NUMBER);
+        parsed.study_instance_uid = Self::get_string_element(obj, tags::STUDY_INSTANCE_UID);
+        parsed.study_date = Self::get_string_element(obj, tags::STUDY_DATE);
+        parsed.study_time = Self::get_string_element(obj, tags::STUDY_TIME);
+        parsed.study_description = Self::get_string_element(obj, tags::STUDY_DESCRIPTION);
+        parsed.accession_number = Self::get_string_element(obj, tags::ACCESSION_NUMBER);
 
         // 提取序列信息
-        parsed.series_instance_uid = Self::get_string_element(&obj, tags::SERIES_INSTANCE_UID);
-        parsed.series_number = Self::get_string_element(&obj, tags::SERIES_NUMBER);
-        parsed.series_description = Self::get_string_element(&obj, tags::SERIES_DESCRIPTION);
-        parsed.modality = Self::get_string_element(&obj, tags::MODALITY);
+        parsed.series_instance_uid = Self::get_string_element(obj, tags::SERIES_INSTANCE_UID);
+        parsed.series_number = Self::get_string_element(obj, tags::SERIES_NUMBER);
+        parsed.series_description = Self::get_string_element(obj, tags::SERIES_DESCRIPTION);
+        parsed.modality = Self::get_string_element(obj, tags::MODALITY);
 
         // 提取实例信息
-        parsed.sop_instance_uid = Self::get_string_element(&obj, tags::SOP_INSTANCE_UID);
-        parsed.sop_class_uid = Self::get_string_element(&obj, tags::SOP_CLASS_UID);
-        parsed.instance_number = Self::get_string_element(&obj, tags::INSTANCE_NUMBER);
+        parsed.sop_instance_uid = Self::get_string_element(obj, tags::SOP_INSTANCE_UID);
+        parsed.sop_class_uid = Self::get_string_element(obj, tags::SOP_CLASS_UID);
+        parsed.instance_number = Self::get_string_element(obj, tags::INSTANCE_NUMBER);
 
         // 提取设备信息
-        parsed.institution_name = Self::get_string_element(&obj, tags::INSTITUTION_NAME);
-        parsed.manufacturer = Self::get_string_element(&obj, tags::MANUFACTURER);
+        parsed.institution_name = Self::get_string_element(obj, tags::INSTITUTION_NAME);
+        parsed.manufacturer = Self::get_string_element(obj, tags::MANUFACTURER);
         parsed.manufacturer_model_name =
-            Self::get_string_element(&obj, tags::MANUFACTURER_MODEL_NAME);
+            Self::get_string_element(obj, tags::MANUFACTURER_MODEL_NAME);
 
         // 提取图像信息
-        parsed.rows = Self::get_integer_element(&obj, tags::ROWS);
-        parsed.columns = Self::get_integer_element(&obj, tags::COLUMNS);
-        parsed.bits_allocated = Self::get_integer_element(&obj, tags::BITS_ALLOCATED);
-        parsed.bits_stored = Self::get_integer_element(&obj, tags::BITS_STORED);
-        parsed.high_bit = Self::get_integer_element(&obj, tags::HIGH_BIT);
-        parsed.pixel_representation = Self::get_integer_element(&obj, tags::PIXEL_REPRESENTATION);
+        parsed.rows = Self::get_integer_element(obj, tags::ROWS);
+        parsed.columns = Self::get_integer_element(obj, tags::COLUMNS);
+        parsed.bits_allocated = Self::get_integer_element(obj, tags::BITS_ALLOCATED);
+        parsed.bits_stored = Self::get_integer_element(obj, tags::BITS_STORED);
+        parsed.high_bit = Self::get_integer_element(obj, tags::HIGH_BIT);
+        parsed.pixel_representation = Self::get_integer_element(obj, tags::PIXEL_REPRESENTATION);
 
         // 提取传输语法信息
-        parsed.transfer_syntax_uid = Self::get_string_element(&obj, tags::TRANSFER_SYNTAX_UID);
+        parsed.transfer_syntax_uid = Self::get_string_element(obj, tags::TRANSFER_SYNTAX_UID)
+            .or_else(|| Some(obj.meta().transfer_syntax().to_string()));
 
         // 提取其他重要信息
-        parsed.patient_age = Self::get_string_element(&obj, tags::PATIENT_AGE);
-        parsed.patient_weight = Self::get_string_element(&obj, tags::PATIENT_WEIGHT);
-        parsed.body_part_examined = Self::get_string_element(&obj, tags::BODY_PART_EXAMINED);
+        parsed.patient_age = Self::get_string_element(obj, tags::PATIENT_AGE);
+        parsed.patient_weight = Self::get_string_element(obj, tags::PATIENT_WEIGHT);
+        parsed.body_part_examined = Self::get_string_element(obj, tags::BODY_PART_EXAMINED);
+        parsed.protocol_name = Self::get_string_element(obj, tags::PROTOCOL_NAME);
+
+        // 提取剂量信息
+        parsed.ctdi_vol = Self::get_float_element(obj, tags::CTD_IVOL);
+        parsed.entrance_dose_mgy = Self::get_float_element(obj, tags::ENTRANCE_DOSE_INM_GY);
+        parsed.area_dose_product =
+            Self::get_float_element(obj, tags::IMAGE_AND_FLUOROSCOPY_AREA_DOSE_PRODUCT);
 
         info!(
             "成功提取DICOM元数据，患者ID: {:?}, 检查UID: {:?}",
@@ -145,14 +163,16 @@ impl DicomParser {
         Ok(parsed)
     }
 
-    /// 获取字符串类型元素的值
+    /// 获取字符串类型元素的值（去掉补齐偶数长度的结尾空字符和空格）
     fn get_string_element(obj: &DefaultDicomObject, tag: dicom::core::Tag) -> Option<String> {
         match obj.element(tag) {
             Ok(element) => match element.value() {
-                Value::Primitive(PrimitiveValue::Str(s)) => Some(s.to_string()),
-                Value::Primitive(PrimitiveValue::Strs(strings)) => {
-                    strings.first().map(|s| s.to_string())
+                Value::Primitive(PrimitiveValue::Str(s)) => {
+                    Some(s.trim_end_matches(['\0', ' ']).to_string())
                 }
+                Value::Primitive(PrimitiveValue::Strs(strings)) => strings
+                    .first()
+                    .map(|s| s.trim_end_matches(['\0', ' ']).to_string()),
                 _ => {
                     debug!("标签 {:?} 不是字符串类型", tag);
                     None
@@ -185,6 +205,23 @@ impl DicomParser {
         }
     }
 
+    /// 获取浮点类型元素的值（DS/FD/FL）
+    fn get_float_element(obj: &DefaultDicomObject, tag: dicom::core::Tag) -> Option<f64> {
+        match obj.element(tag) {
+            Ok(element) => match element.to_float64() {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    debug!("标签 {:?} 不是数值类型", tag);
+                    None
+                }
+            },
+            Err(_) => {
+                debug!("未找到标签: {:?}", tag);
+                None
+            }
+        }
+    }
+
     /// 获取DICOM传输语法
     pub fn get_transfer_syntax(transfer_syntax_uid: &str) -> Result<TransferSyntax> {
         // 简化实现，暂时不支持具体的传输语法对象
@@ -280,6 +317,16 @@ pub struct ParsedDicomObject {
     // === 其他信息 ===
     /// 检查部位
     pub body_part_examined: Option<String>,
+    /// 协议名称
+    pub protocol_name: Option<String>,
+
+    // === 剂量信息 ===
+    /// CTDIvol（mGy）
+    pub ctdi_vol: Option<f64>,
+    /// 入射剂量（mGy）
+    pub entrance_dose_mgy: Option<f64>,
+    /// 图像及透视剂量面积乘积KAP（dGy·cm²）
+    pub area_dose_product: Option<f64>,
 }
 
 impl Default for ParsedDicomObject {
@@ -321,6 +368,10 @@ impl ParsedDicomObject {
             pixel_representation: None,
             transfer_syntax_uid: None,
             body_part_examined: None,
+            protocol_name: None,
+            ctdi_vol: None,
+            entrance_dose_mgy: None,
+            area_dose_product: None,
         }
     }
 
@@ -444,6 +495,14 @@ impl ParsedDicomObject {
         self.transfer_syntax_uid.clone()
     }
 
+    // === 剂量信息访问器 ===
+    /// 是否包含任何剂量头字段
+    pub fn has_dose_fields(&self) -> bool {
+        self.ctdi_vol.is_some()
+            || self.entrance_dose_mgy.is_some()
+            || self.area_dose_product.is_some()
+    }
+
     // === 数据验证 ===
     /// 验证DICOM对象的完整性
     pub fn validate(&self) -> bool {
@@ -0,0 +1,439 @@
+//! 历史检查预取
+//!
+//! 新申请或新检查到达时，按患者ID向配置的远端PACS执行C-FIND，
+//! 挑选相关的历史检查（相同部位/模态、N年以内、最多M个），
+//! 再以C-MOVE取回到本机；本地已完整存在的检查不会重复获取。
+
+use crate::ingest::{IngestListener, IngestSource, IngestedInstance};
+use crate::scu::{DicomScu, RemoteAe, StudyQuery, StudyQueryResult};
+use async_trait::async_trait;
+use chrono::{Months, NaiveDate, Utc};
+use pacs_core::Result;
+use pacs_database::{DatabasePool, DatabaseQueries};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, error, info, warn};
+
+/// 预取配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrefetchConfig {
+    /// 查询的远端PACS（按顺序，同一检查以先查到的为准）
+    pub archives: Vec<RemoteAe>,
+    /// C-MOVE目标AE标题（本机）
+    pub local_ae_title: String,
+    /// 只取最近N年内的检查
+    pub lookback_years: u32,
+    /// 每次最多取回的历史检查数
+    pub max_priors: usize,
+    /// 要求模态相同
+    pub match_modality: bool,
+    /// 要求检查部位相同
+    pub match_body_part: bool,
+    /// 新检查到达时触发预取
+    pub trigger_on_new_study: bool,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self {
+            archives: Vec::new(),
+            local_ae_title: "PACS_SERVER".to_string(),
+            lookback_years: 5,
+            max_priors: 3,
+            match_modality: true,
+            match_body_part: true,
+            trigger_on_new_study: true,
+        }
+    }
+}
+
+/// 预取触发来源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PrefetchTrigger {
+    /// HL7 ORM检查申请
+    Order { placer_order_number: String },
+    /// 新检查到达
+    NewStudy { study_instance_uid: String },
+    /// 手动触发
+    Manual,
+}
+
+impl std::fmt::Display for PrefetchTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrefetchTrigger::Order {
+                placer_order_number,
+            } => write!(f, "申请单({})", placer_order_number),
+            PrefetchTrigger::NewStudy { study_instance_uid } => {
+                write!(f, "新检查({})", study_instance_uid)
+            }
+            PrefetchTrigger::Manual => write!(f, "手动"),
+        }
+    }
+}
+
+/// 预取请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefetchRequest {
+    /// 患者ID
+    pub patient_id: String,
+    /// 当前检查模态
+    pub modality: Option<String>,
+    /// 当前检查部位
+    pub body_part: Option<String>,
+    /// 当前检查UID（不作为历史检查取回）
+    pub current_study_uid: Option<String>,
+    /// 触发来源
+    pub trigger: PrefetchTrigger,
+}
+
+/// 远端查到的候选历史检查
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorCandidate {
+    /// 所在远端PACS
+    pub archive: RemoteAe,
+    /// 检查信息
+    pub study: StudyQueryResult,
+}
+
+/// 预取结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PrefetchReport {
+    /// 患者ID
+    pub patient_id: String,
+    /// 远端查到的检查数
+    pub candidates: usize,
+    /// 入选的历史检查UID
+    pub selected: Vec<String>,
+    /// 本地已存在而跳过的检查UID
+    pub already_local: Vec<String>,
+    /// 已取回的检查UID
+    pub retrieved: Vec<String>,
+    /// 取回失败的检查UID及原因
+    pub failed: Vec<(String, String)>,
+}
+
+/// 历史检查挑选
+pub struct PriorSelector;
+
+impl PriorSelector {
+    /// 按相关性挑选历史检查：过滤时间窗口、模态与部位，按匹配程度和日期排序后截断
+    pub fn select(
+        config: &PrefetchConfig,
+        request: &PrefetchRequest,
+        candidates: Vec<PriorCandidate>,
+        today: NaiveDate,
+    ) -> Vec<PriorCandidate> {
+        let earliest = today
+            .checked_sub_months(Months::new(config.lookback_years.saturating_mul(12)))
+            .unwrap_or(NaiveDate::MIN);
+
+        let mut seen = HashSet::new();
+        let mut selected: Vec<(u8, PriorCandidate)> = candidates
+            .into_iter()
+            .filter(|c| request.current_study_uid.as_deref() != Some(&c.study.study_instance_uid))
+            .filter(|c| seen.insert(c.study.study_instance_uid.clone()))
+            .filter(|c| c.study.study_date.is_some_and(|d| d >= earliest))
+            .filter_map(|c| {
+                let modality = Self::matches_modality(request, &c.study);
+                let body_part = Self::matches_body_part(request, &c.study);
+                if (config.match_modality && modality == Some(false))
+                    || (config.match_body_part && body_part == Some(false))
+                {
+                    return None;
+                }
+                // 明确匹配的检查优先于无法判断的检查
+                let score = u8::from(modality == Some(true)) + u8::from(body_part == Some(true));
+                Some((score, c))
+            })
+            .collect();
+
+        selected.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .cmp(a_score)
+                .then_with(|| b.study.study_date.cmp(&a.study.study_date))
+        });
+        selected
+            .into_iter()
+            .take(config.max_priors)
+            .map(|(_, c)| c)
+            .collect()
+    }
+
+    /// 模态是否匹配，无法判断时返回None
+    fn matches_modality(request: &PrefetchRequest, study: &StudyQueryResult) -> Option<bool> {
+        let modality = request.modality.as_deref()?;
+        if study.modalities.is_empty() {
+            return None;
+        }
+        Some(
+            study
+                .modalities
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case(modality)),
+        )
+    }
+
+    /// 检查部位是否匹配，无法判断时返回None
+    fn matches_body_part(request: &PrefetchRequest, study: &StudyQueryResult) -> Option<bool> {
+        let body_part = request.body_part.as_deref()?;
+        let study_body_part = study.body_part_examined.as_deref()?;
+        Some(study_body_part.eq_ignore_ascii_case(body_part))
+    }
+}
+
+/// 历史检查预取服务
+pub struct PrefetchService {
+    /// 数据库连接池
+    db: Arc<DatabasePool>,
+    /// C-FIND/C-MOVE客户端
+    scu: DicomScu,
+    /// 预取配置
+    config: PrefetchConfig,
+    /// 预取请求队列
+    sender: mpsc::UnboundedSender<PrefetchRequest>,
+    /// 队列接收端，启动后台任务时取出
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<PrefetchRequest>>>,
+    /// 正在取回的检查UID，避免重复C-MOVE，也用于识别取回的历史检查
+    in_flight: Mutex<HashSet<String>>,
+    /// 已触发过预取的检查UID
+    triggered_studies: Mutex<HashSet<String>>,
+}
+
+impl PrefetchService {
+    /// 创建预取服务
+    pub fn new(db: Arc<DatabasePool>, scu: DicomScu, config: PrefetchConfig) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            db,
+            scu,
+            config,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            in_flight: Mutex::new(HashSet::new()),
+            triggered_studies: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 获取配置
+    pub fn config(&self) -> &PrefetchConfig {
+        &self.config
+    }
+
+    /// 提交预取请求，由后台任务依次处理
+    pub fn submit(&self, request: PrefetchRequest) -> Result<()> {
+        debug!(
+            "提交预取请求: 患者 {} ({})",
+            request.patient_id, request.trigger
+        );
+        self.sender
+            .send(request)
+            .map_err(|e| pacs_core::PacsError::Internal(format!("预取队列已关闭: {}", e)))
+    }
+
+    /// 启动后台预取任务
+    pub fn start(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let Some(mut receiver) = service.receiver.lock().await.take() else {
+                warn!("预取服务已在运行");
+                return;
+            };
+            while let Some(request) = receiver.recv().await {
+                if let Err(e) = service.prefetch(&request).await {
+                    error!(
+                        "预取失败: 患者 {} ({}): {}",
+                        request.patient_id, request.trigger, e
+                    );
+                }
+            }
+        })
+    }
+
+    /// 执行一次预取
+    pub async fn prefetch(&self, request: &PrefetchRequest) -> Result<PrefetchReport> {
+        let today = Utc::now().date_naive();
+        let query = StudyQuery {
+            patient_id: request.patient_id.clone(),
+            study_date_from: today
+                .checked_sub_months(Months::new(self.config.lookback_years.saturating_mul(12))),
+            study_date_to: None,
+        };
+
+        let mut candidates = Vec::new();
+        for archive in &self.config.archives {
+            match self.scu.find_studies(archive, &query).await {
+                Ok(studies) => candidates.extend(studies.into_iter().map(|study| PriorCandidate {
+                    archive: archive.clone(),
+                    study,
+                })),
+                Err(e) => warn!("查询远端PACS {} 失败: {}", archive, e),
+            }
+        }
+
+        let mut report = PrefetchReport {
+            patient_id: request.patient_id.clone(),
+            candidates: candidates.len(),
+            ..Default::default()
+        };
+        let selected = PriorSelector::select(&self.config, request, candidates, today);
+
+        for candidate in selected {
+            let study_uid = candidate.study.study_instance_uid.clone();
+            report.selected.push(study_uid.clone());
+
+            if self.is_stored_locally(&candidate.study).await? {
+                debug!("历史检查已在本地: {}", study_uid);
+                report.already_local.push(study_uid);
+                continue;
+            }
+            if !self.in_flight.lock().await.insert(study_uid.clone()) {
+                debug!("历史检查正在取回: {}", study_uid);
+                continue;
+            }
+
+            let result = self
+                .scu
+                .move_study(&candidate.archive, &study_uid, &self.config.local_ae_title)
+                .await;
+            self.in_flight.lock().await.remove(&study_uid);
+
+            match result {
+                Ok(result) if result.is_success() => report.retrieved.push(study_uid),
+                Ok(result) => report.failed.push((
+                    study_uid,
+                    format!("状态 0x{:04X}，失败子操作 {}", result.status, result.failed),
+                )),
+                Err(e) => report.failed.push((study_uid, e.to_string())),
+            }
+        }
+
+        info!(
+            "预取完成: 患者 {} ({})，候选 {}，入选 {}，本地已有 {}，取回 {}，失败 {}",
+            report.patient_id,
+            request.trigger,
+            report.candidates,
+            report.selected.len(),
+            report.already_local.len(),
+            report.retrieved.len(),
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    /// 检查是否已完整存储在本地（远端未返回实例数时以检查存在为准）
+    async fn is_stored_locally(&self, study: &StudyQueryResult) -> Result<bool> {
+        let queries = DatabaseQueries::new(&self.db);
+        let Some(local) = queries.get_study_by_uid(&study.study_instance_uid).await? else {
+            return Ok(false);
+        };
+        let Some(remote_count) = study.number_of_instances else {
+            return Ok(true);
+        };
+
+        let local_count: i64 = queries
+            .get_series_by_study_id(&local.id)
+            .await?
+            .iter()
+            .map(|s| s.images_count as i64)
+            .sum();
+        Ok(local_count >= remote_count as i64)
+    }
+}
+
+#[async_trait]
+impl IngestListener for PrefetchService {
+    async fn on_instance_stored(
+        &self,
+        instance: &IngestedInstance,
+        _source: &IngestSource,
+    ) -> Result<()> {
+        if !self.config.trigger_on_new_study {
+            return Ok(());
+        }
+        let metadata = &instance.metadata;
+        let (Some(study_uid), Some(patient_id)) =
+            (&metadata.study_instance_uid, &metadata.patient_id)
+        else {
+            return Ok(());
+        };
+
+        // 取回的历史检查本身不再触发预取
+        if self.in_flight.lock().await.contains(study_uid)
+            || !self
+                .triggered_studies
+                .lock()
+                .await
+                .insert(study_uid.clone())
+        {
+            return Ok(());
+        }
+
+        self.submit(PrefetchRequest {
+            patient_id: patient_id.clone(),
+            modality: metadata.modality.clone(),
+            body_part: metadata.body_part_examined.clone(),
+            current_study_uid: Some(study_uid.clone()),
+            trigger: PrefetchTrigger::NewStudy {
+                study_instance_uid: study_uid.clone(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(uid: &str, date: &str, modality: &str, body_part: Option<&str>) -> PriorCandidate {
+        PriorCandidate {
+            archive: RemoteAe::new("ARCHIVE", "127.0.0.1", 104),
+            study: StudyQueryResult {
+                study_instance_uid: uid.to_string(),
+                patient_id: Some("PAT001".to_string()),
+                study_date: NaiveDate::parse_from_str(date, "%Y%m%d").ok(),
+                study_description: None,
+                accession_number: None,
+                modalities: vec![modality.to_string()],
+                body_part_examined: body_part.map(str::to_string),
+                number_of_instances: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_select_relevant_priors() {
+        let config = PrefetchConfig {
+            lookback_years: 3,
+            max_priors: 2,
+            ..Default::default()
+        };
+        let request = PrefetchRequest {
+            patient_id: "PAT001".to_string(),
+            modality: Some("CT".to_string()),
+            body_part: Some("CHEST".to_string()),
+            current_study_uid: Some("1.1".to_string()),
+            trigger: PrefetchTrigger::Manual,
+        };
+        let candidates = vec![
+            candidate("1.1", "20240601", "CT", Some("CHEST")), // 当前检查
+            candidate("1.2", "20230101", "CT", None),          // 部位未知
+            candidate("1.3", "20220101", "CT", Some("CHEST")),
+            candidate("1.4", "20240101", "MR", Some("CHEST")), // 模态不同
+            candidate("1.5", "20240201", "CT", Some("HEAD")),  // 部位不同
+            candidate("1.6", "20180101", "CT", Some("CHEST")), // 超出时间窗口
+            candidate("1.3", "20220101", "CT", Some("CHEST")), // 另一远端的重复结果
+        ];
+
+        let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let selected = PriorSelector::select(&config, &request, candidates, today);
+        let uids: Vec<_> = selected
+            .iter()
+            .map(|c| c.study.study_instance_uid.as_str())
+            .collect();
+        assert_eq!(uids, vec!["1.3", "1.2"]);
+    }
+}
@@ -0,0 +1,455 @@
+//! C-FIND查询
+//!
+//! 按查询/检索级别（PATIENT/STUDY/SERIES/IMAGE）从实例表检索匹配项，再按级别聚合为响应标识。
+//! 检索基于实例表，已拒绝（IOCM）的实例不会返回，只含已拒绝实例的序列、检查和患者也随之隐藏。
+//! 匹配键只支持单值精确匹配和通用匹配（空值或`*`），其他通配符和UID列表匹配按不支持处理。
+
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::encoding::{TransferSyntax, TransferSyntaxIndex};
+use dicom::object::InMemDicomObject;
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+use pacs_core::{PacsError, Result};
+use pacs_database::{DatabasePool, DatabaseQueries, DbInstanceMatch, InstanceSearch};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// 查询/检索级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryLevel {
+    /// 患者级
+    Patient,
+    /// 检查级
+    Study,
+    /// 序列级
+    Series,
+    /// 实例级
+    Image,
+}
+
+impl QueryLevel {
+    /// 解析查询/检索级别（0008,0052）
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_uppercase().as_str() {
+            "PATIENT" => Some(Self::Patient),
+            "STUDY" => Some(Self::Study),
+            "SERIES" => Some(Self::Series),
+            "IMAGE" => Some(Self::Image),
+            _ => None,
+        }
+    }
+
+    /// 级别的DICOM代码串
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Patient => "PATIENT",
+            Self::Study => "STUDY",
+            Self::Series => "SERIES",
+            Self::Image => "IMAGE",
+        }
+    }
+
+    /// 匹配项在该级别的聚合键
+    fn group_key<'a>(&self, entry: &'a DbInstanceMatch) -> &'a str {
+        match self {
+            Self::Patient => &entry.patient_id,
+            Self::Study => &entry.study_uid,
+            Self::Series => &entry.series_uid,
+            Self::Image => &entry.sop_instance_uid,
+        }
+    }
+}
+
+/// 实例查询服务
+pub struct InstanceQueryService {
+    /// 数据库连接池
+    database: Arc<DatabasePool>,
+}
+
+impl InstanceQueryService {
+    /// 创建实例查询服务
+    pub fn new(database: Arc<DatabasePool>) -> Self {
+        Self { database }
+    }
+
+    /// 按C-FIND查询标识检索，返回各匹配项的响应标识
+    pub async fn find(&self, identifier: &InMemDicomObject) -> Result<Vec<InMemDicomObject>> {
+        let level = get_string(identifier, tags::QUERY_RETRIEVE_LEVEL)
+            .as_deref()
+            .and_then(QueryLevel::parse)
+            .ok_or_else(|| PacsError::Validation("缺少或无效的查询/检索级别".to_string()))?;
+        let search = search_from_identifier(identifier)?;
+        let matches = DatabaseQueries::new(&self.database)
+            .search_instances(&search)
+            .await?;
+        Ok(group_matches(level, &matches))
+    }
+}
+
+/// 由查询标识生成检索条件
+pub fn search_from_identifier(identifier: &InMemDicomObject) -> Result<InstanceSearch> {
+    Ok(InstanceSearch {
+        patient_id: match_key(identifier, tags::PATIENT_ID)?,
+        study_uid: match_key(identifier, tags::STUDY_INSTANCE_UID)?,
+        accession_number: match_key(identifier, tags::ACCESSION_NUMBER)?,
+        series_uid: match_key(identifier, tags::SERIES_INSTANCE_UID)?,
+        modality: match_key(identifier, tags::MODALITY)?,
+        sop_instance_uid: match_key(identifier, tags::SOP_INSTANCE_UID)?,
+        ..Default::default()
+    })
+}
+
+/// 按级别聚合匹配实例，生成响应标识（保持检索顺序）
+pub fn group_matches(level: QueryLevel, matches: &[DbInstanceMatch]) -> Vec<InMemDicomObject> {
+    let mut order: Vec<&str> = Vec::new();
+    let mut groups: HashMap<&str, Vec<&DbInstanceMatch>> = HashMap::new();
+    for entry in matches {
+        let key = level.group_key(entry);
+        groups
+            .entry(key)
+            .or_insert_with(|| {
+                order.push(key);
+                Vec::new()
+            })
+            .push(entry);
+    }
+
+    order
+        .into_iter()
+        .map(|key| response_identifier(level, &groups[key]))
+        .collect()
+}
+
+/// 解码C-FIND查询标识（隐式VR Little Endian）
+pub fn decode_identifier(data: &[u8]) -> Result<InMemDicomObject> {
+    InMemDicomObject::read_dataset_with_ts(Cursor::new(data), implicit_vr_little_endian()?)
+        .map_err(|e| PacsError::DicomParseError(format!("无法解析C-FIND查询标识: {:?}", e)))
+}
+
+/// 编码C-FIND响应标识（隐式VR Little Endian）
+pub fn encode_identifier(identifier: &InMemDicomObject) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    identifier
+        .write_dataset_with_ts(&mut data, implicit_vr_little_endian()?)
+        .map_err(|e| PacsError::Dicom(format!("无法编码C-FIND响应标识: {:?}", e)))?;
+    Ok(data)
+}
+
+/// 把查询结果标识转换为DICOM JSON（QIDO-RS响应）
+pub fn identifier_to_json(identifier: &InMemDicomObject) -> Value {
+    let mut attributes = serde_json::Map::new();
+    for element in identifier {
+        let vr = element.vr();
+        let values: Vec<String> = element
+            .to_multi_str()
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|v| v.trim_end_matches(['\0', ' ']).to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut attribute = serde_json::Map::new();
+        attribute.insert("vr".to_string(), json!(vr.to_string()));
+        if !values.is_empty() {
+            let values: Vec<Value> = match vr {
+                VR::PN => values
+                    .into_iter()
+                    .map(|v| json!({"Alphabetic": v}))
+                    .collect(),
+                VR::IS => values
+                    .into_iter()
+                    .map(|v| v.trim().parse::<i64>().map_or(json!(v), |n| json!(n)))
+                    .collect(),
+                _ => values.into_iter().map(|v| json!(v)).collect(),
+            };
+            attribute.insert("Value".to_string(), json!(values));
+        }
+
+        let tag = element.header().tag;
+        attributes.insert(
+            format!("{:04X}{:04X}", tag.group(), tag.element()),
+            Value::Object(attribute),
+        );
+    }
+    Value::Object(attributes)
+}
+
+/// 生成一组匹配实例的响应标识
+fn response_identifier(level: QueryLevel, entries: &[&DbInstanceMatch]) -> InMemDicomObject {
+    let first = entries[0];
+    let mut identifier = InMemDicomObject::new_empty();
+    put(
+        &mut identifier,
+        tags::QUERY_RETRIEVE_LEVEL,
+        VR::CS,
+        level.as_str(),
+    );
+
+    match level {
+        QueryLevel::Patient => {
+            put_patient(&mut identifier, first);
+            let studies: BTreeSet<&str> = entries.iter().map(|e| e.study_uid.as_str()).collect();
+            put(
+                &mut identifier,
+                tags::NUMBER_OF_PATIENT_RELATED_STUDIES,
+                VR::IS,
+                &studies.len().to_string(),
+            );
+        }
+        QueryLevel::Study => {
+            put_patient(&mut identifier, first);
+            put_study(&mut identifier, first);
+            let series: BTreeSet<&str> = entries.iter().map(|e| e.series_uid.as_str()).collect();
+            let modalities: Vec<String> = entries
+                .iter()
+                .map(|e| e.modality.clone())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            identifier.put(DataElement::new(
+                tags::MODALITIES_IN_STUDY,
+                VR::CS,
+                PrimitiveValue::Strs(modalities.into()),
+            ));
+            put(
+                &mut identifier,
+                tags::NUMBER_OF_STUDY_RELATED_SERIES,
+                VR::IS,
+                &series.len().to_string(),
+            );
+            put(
+                &mut identifier,
+                tags::NUMBER_OF_STUDY_RELATED_INSTANCES,
+                VR::IS,
+                &entries.len().to_string(),
+            );
+        }
+        QueryLevel::Series => {
+            put(
+                &mut identifier,
+                tags::STUDY_INSTANCE_UID,
+                VR::UI,
+                &first.study_uid,
+            );
+            put_series(&mut identifier, first);
+            put(
+                &mut identifier,
+                tags::NUMBER_OF_SERIES_RELATED_INSTANCES,
+                VR::IS,
+                &entries.len().to_string(),
+            );
+        }
+        QueryLevel::Image => {
+            put(
+                &mut identifier,
+                tags::STUDY_INSTANCE_UID,
+                VR::UI,
+                &first.study_uid,
+            );
+            put(
+                &mut identifier,
+                tags::SERIES_INSTANCE_UID,
+                VR::UI,
+                &first.series_uid,
+            );
+            put(
+                &mut identifier,
+                tags::SOP_INSTANCE_UID,
+                VR::UI,
+                &first.sop_instance_uid,
+            );
+            put(
+                &mut identifier,
+                tags::INSTANCE_NUMBER,
+                VR::IS,
+                &first.instance_number.to_string(),
+            );
+        }
+    }
+    identifier
+}
+
+/// 写入患者级属性
+fn put_patient(identifier: &mut InMemDicomObject, entry: &DbInstanceMatch) {
+    put(identifier, tags::PATIENT_ID, VR::LO, &entry.patient_id);
+    put(identifier, tags::PATIENT_NAME, VR::PN, &entry.patient_name);
+    let birth_date = entry
+        .patient_birth_date
+        .map(|date| date.format("%Y%m%d").to_string())
+        .unwrap_or_default();
+    put(identifier, tags::PATIENT_BIRTH_DATE, VR::DA, &birth_date);
+    put(
+        identifier,
+        tags::PATIENT_SEX,
+        VR::CS,
+        entry.patient_sex.as_deref().unwrap_or_default(),
+    );
+}
+
+/// 写入检查级属性
+fn put_study(identifier: &mut InMemDicomObject, entry: &DbInstanceMatch) {
+    put(
+        identifier,
+        tags::STUDY_INSTANCE_UID,
+        VR::UI,
+        &entry.study_uid,
+    );
+    put(
+        identifier,
+        tags::STUDY_DATE,
+        VR::DA,
+        &entry.study_date.format("%Y%m%d").to_string(),
+    );
+    let study_time = entry
+        .study_time
+        .map(|time| time.format("%H%M%S").to_string())
+        .unwrap_or_default();
+    put(identifier, tags::STUDY_TIME, VR::TM, &study_time);
+    put(
+        identifier,
+        tags::ACCESSION_NUMBER,
+        VR::SH,
+        &entry.accession_number,
+    );
+    put(
+        identifier,
+        tags::STUDY_DESCRIPTION,
+        VR::LO,
+        entry.study_description.as_deref().unwrap_or_default(),
+    );
+}
+
+/// 写入序列级属性
+fn put_series(identifier: &mut InMemDicomObject, entry: &DbInstanceMatch) {
+    put(
+        identifier,
+        tags::SERIES_INSTANCE_UID,
+        VR::UI,
+        &entry.series_uid,
+    );
+    put(identifier, tags::MODALITY, VR::CS, &entry.modality);
+    put(
+        identifier,
+        tags::SERIES_NUMBER,
+        VR::IS,
+        &entry.series_number.to_string(),
+    );
+    put(
+        identifier,
+        tags::SERIES_DESCRIPTION,
+        VR::LO,
+        entry.series_description.as_deref().unwrap_or_default(),
+    );
+}
+
+/// 写入字符串属性
+fn put(identifier: &mut InMemDicomObject, tag: Tag, vr: VR, value: &str) {
+    identifier.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+}
+
+/// 读取匹配键：空值和`*`为通用匹配，其他通配符和UID列表不支持
+fn match_key(identifier: &InMemDicomObject, tag: Tag) -> Result<Option<String>> {
+    match get_string(identifier, tag) {
+        None => Ok(None),
+        Some(value) if value == "*" => Ok(None),
+        Some(value) if value.contains(['*', '?', '\\']) => Err(PacsError::Validation(format!(
+            "不支持的匹配键 {}: {}",
+            tag, value
+        ))),
+        Some(value) => Ok(Some(value)),
+    }
+}
+
+/// 获取去除填充字符后的字符串值
+fn get_string(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    obj.element(tag)
+        .ok()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// 隐式VR Little Endian传输语法
+fn implicit_vr_little_endian() -> Result<&'static TransferSyntax> {
+    TransferSyntaxRegistry
+        .get(uids::IMPLICIT_VR_LITTLE_ENDIAN)
+        .ok_or_else(|| PacsError::Dicom("传输语法注册表不可用".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn entry(study: &str, series: &str, sop: &str, modality: &str) -> DbInstanceMatch {
+        DbInstanceMatch {
+            patient_id: "P1".to_string(),
+            patient_name: "Doe^John".to_string(),
+            patient_birth_date: None,
+            patient_sex: Some("M".to_string()),
+            study_uid: study.to_string(),
+            study_date: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+            study_time: None,
+            accession_number: "ACC1".to_string(),
+            study_description: None,
+            series_uid: series.to_string(),
+            series_number: 1,
+            modality: modality.to_string(),
+            series_description: None,
+            sop_instance_uid: sop.to_string(),
+            instance_number: 1,
+            transfer_syntax_uid: uids::IMPLICIT_VR_LITTLE_ENDIAN.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_group_matches_by_level() {
+        let matches = vec![
+            entry("1.1", "1.1.1", "1.1.1.1", "CT"),
+            entry("1.1", "1.1.2", "1.1.2.1", "SR"),
+            entry("1.1", "1.1.1", "1.1.1.2", "CT"),
+        ];
+
+        let studies = group_matches(QueryLevel::Study, &matches);
+        assert_eq!(studies.len(), 1);
+        assert_eq!(
+            get_string(&studies[0], tags::NUMBER_OF_STUDY_RELATED_SERIES).as_deref(),
+            Some("2")
+        );
+        assert_eq!(
+            get_string(&studies[0], tags::MODALITIES_IN_STUDY).as_deref(),
+            Some("CT\\SR")
+        );
+
+        let series = group_matches(QueryLevel::Series, &matches);
+        assert_eq!(series.len(), 2);
+        assert_eq!(
+            get_string(&series[0], tags::NUMBER_OF_SERIES_RELATED_INSTANCES).as_deref(),
+            Some("2")
+        );
+
+        let decoded = decode_identifier(&encode_identifier(&series[1]).unwrap()).unwrap();
+        assert_eq!(
+            get_string(&decoded, tags::SERIES_INSTANCE_UID).as_deref(),
+            Some("1.1.2")
+        );
+    }
+
+    #[test]
+    fn test_match_keys() {
+        let mut identifier = InMemDicomObject::new_empty();
+        put(&mut identifier, tags::PATIENT_ID, VR::LO, "*");
+        put(&mut identifier, tags::STUDY_INSTANCE_UID, VR::UI, "1.2.3");
+        let search = search_from_identifier(&identifier).unwrap();
+        assert_eq!(search.patient_id, None);
+        assert_eq!(search.study_uid.as_deref(), Some("1.2.3"));
+
+        put(&mut identifier, tags::ACCESSION_NUMBER, VR::SH, "AC*");
+        assert!(search_from_identifier(&identifier).is_err());
+    }
+}
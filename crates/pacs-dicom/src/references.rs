@@ -0,0 +1,265 @@
+//! 关键图像选择（KOS）与显示状态（GSPS）引用解析
+//!
+//! 识别KOS/显示状态对象，解析其引用的源图像，生成可持久化的实例关系。
+
+use crate::sr::SrParser;
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::InMemDicomObject;
+use pacs_database::NewInstanceRelationship;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::debug;
+use uuid::Uuid;
+
+/// 显示状态存储类SOP Class UID公共前缀
+const PRESENTATION_STATE_PREFIX: &str = "1.2.840.10008.5.1.4.1.1.11.";
+
+/// 引用关系类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ReferenceKind {
+    /// 关键图像选择
+    KeyObjectSelection,
+    /// 显示状态（窗宽窗位、标注等）
+    PresentationState,
+}
+
+impl ReferenceKind {
+    /// 根据SOP Class UID判断引用关系类型
+    pub fn from_sop_class_uid(sop_class_uid: &str) -> Option<Self> {
+        let sop_class_uid = sop_class_uid.trim_end_matches('\0');
+        if sop_class_uid == uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE {
+            Some(Self::KeyObjectSelection)
+        } else if sop_class_uid.starts_with(PRESENTATION_STATE_PREFIX) {
+            Some(Self::PresentationState)
+        } else {
+            None
+        }
+    }
+
+    /// 数据库中的关系类型标识
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::KeyObjectSelection => "KOS",
+            Self::PresentationState => "PR",
+        }
+    }
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// 实例引用关系
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceReference {
+    /// 关系类型
+    pub kind: ReferenceKind,
+    /// 引用方（KOS/显示状态）SOP实例UID
+    pub source_sop_instance_uid: String,
+    /// 引用方所属检查UID
+    pub study_instance_uid: String,
+    /// 被引用图像所属序列UID
+    pub referenced_series_uid: String,
+    /// 被引用图像SOP Class UID
+    pub referenced_sop_class_uid: Option<String>,
+    /// 被引用图像SOP实例UID
+    pub referenced_sop_instance_uid: String,
+    /// 标签（KOS文档标题或显示状态内容标签）
+    pub label: Option<String>,
+}
+
+impl InstanceReference {
+    /// 转换为数据库插入模型
+    pub fn to_new_relationship(&self) -> NewInstanceRelationship {
+        NewInstanceRelationship {
+            id: Uuid::new_v4(),
+            relationship_type: self.kind.as_str().to_string(),
+            source_sop_instance_uid: self.source_sop_instance_uid.clone(),
+            study_uid: self.study_instance_uid.clone(),
+            referenced_series_uid: self.referenced_series_uid.clone(),
+            referenced_sop_class_uid: self.referenced_sop_class_uid.clone(),
+            referenced_sop_instance_uid: self.referenced_sop_instance_uid.clone(),
+            label: self.label.clone(),
+        }
+    }
+}
+
+/// 引用解析器
+pub struct ReferenceExtractor;
+
+impl ReferenceExtractor {
+    /// 解析对象中的引用关系，非KOS/显示状态对象返回None
+    pub fn extract(obj: &InMemDicomObject) -> Option<Vec<InstanceReference>> {
+        let sop_class_uid = get_string(obj, tags::SOP_CLASS_UID)?;
+        let kind = ReferenceKind::from_sop_class_uid(&sop_class_uid)?;
+        let source_sop_instance_uid = get_string(obj, tags::SOP_INSTANCE_UID)?;
+        let study_instance_uid = get_string(obj, tags::STUDY_INSTANCE_UID).unwrap_or_default();
+
+        let (label, series_items) = match kind {
+            ReferenceKind::KeyObjectSelection => {
+                // KOS的文档标题即根节点概念名称（如"Of Interest"）
+                let label = SrParser::parse(obj).ok().map(|report| report.title());
+                let mut series_items = Vec::new();
+                for study in items(obj, tags::CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE) {
+                    series_items.extend(items(study, tags::REFERENCED_SERIES_SEQUENCE));
+                }
+                (label, series_items)
+            }
+            ReferenceKind::PresentationState => (
+                get_string(obj, tags::CONTENT_LABEL),
+                items(obj, tags::REFERENCED_SERIES_SEQUENCE).collect(),
+            ),
+        };
+
+        let instance_sequence = match kind {
+            ReferenceKind::KeyObjectSelection => tags::REFERENCED_SOP_SEQUENCE,
+            ReferenceKind::PresentationState => tags::REFERENCED_IMAGE_SEQUENCE,
+        };
+
+        let mut references = Vec::new();
+        for series in series_items {
+            let referenced_series_uid =
+                get_string(series, tags::SERIES_INSTANCE_UID).unwrap_or_default();
+            for instance in items(series, instance_sequence) {
+                let Some(referenced_sop_instance_uid) =
+                    get_string(instance, tags::REFERENCED_SOP_INSTANCE_UID)
+                else {
+                    continue;
+                };
+                references.push(InstanceReference {
+                    kind,
+                    source_sop_instance_uid: source_sop_instance_uid.clone(),
+                    study_instance_uid: study_instance_uid.clone(),
+                    referenced_series_uid: referenced_series_uid.clone(),
+                    referenced_sop_class_uid: get_string(instance, tags::REFERENCED_SOP_CLASS_UID),
+                    referenced_sop_instance_uid,
+                    label: label.clone(),
+                });
+            }
+        }
+
+        debug!(
+            "解析{}引用: {} -> {} 个实例",
+            kind,
+            source_sop_instance_uid,
+            references.len()
+        );
+        Some(references)
+    }
+}
+
+/// 遍历序列的所有项
+fn items(obj: &InMemDicomObject, tag: dicom::core::Tag) -> impl Iterator<Item = &InMemDicomObject> {
+    obj.element(tag)
+        .ok()
+        .and_then(|e| e.items())
+        .unwrap_or_default()
+        .iter()
+}
+
+/// 获取去除填充字符后的字符串值
+fn get_string(obj: &InMemDicomObject, tag: dicom::core::Tag) -> Option<String> {
+    obj.element(tag)
+        .ok()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::value::DataSetSequence;
+    use dicom::core::{DataElement, PrimitiveValue, VR};
+
+    fn referenced_image(sop_uid: &str) -> InMemDicomObject {
+        InMemDicomObject::from_element_iter([
+            DataElement::new(
+                tags::REFERENCED_SOP_CLASS_UID,
+                VR::UI,
+                PrimitiveValue::from(uids::CT_IMAGE_STORAGE),
+            ),
+            DataElement::new(
+                tags::REFERENCED_SOP_INSTANCE_UID,
+                VR::UI,
+                PrimitiveValue::from(sop_uid),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_extract_presentation_state_references() {
+        let series = InMemDicomObject::from_element_iter([
+            DataElement::new(
+                tags::SERIES_INSTANCE_UID,
+                VR::UI,
+                PrimitiveValue::from("1.2.3.1"),
+            ),
+            DataElement::new(
+                tags::REFERENCED_IMAGE_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![
+                    referenced_image("1.2.3.1.1"),
+                    referenced_image("1.2.3.1.2"),
+                ]),
+            ),
+        ]);
+        let gsps = InMemDicomObject::from_element_iter([
+            DataElement::new(
+                tags::SOP_CLASS_UID,
+                VR::UI,
+                PrimitiveValue::from(uids::GRAYSCALE_SOFTCOPY_PRESENTATION_STATE_STORAGE),
+            ),
+            DataElement::new(
+                tags::SOP_INSTANCE_UID,
+                VR::UI,
+                PrimitiveValue::from("1.2.3.9"),
+            ),
+            DataElement::new(
+                tags::STUDY_INSTANCE_UID,
+                VR::UI,
+                PrimitiveValue::from("1.2.3"),
+            ),
+            DataElement::new(tags::CONTENT_LABEL, VR::CS, PrimitiveValue::from("LUNG")),
+            DataElement::new(
+                tags::REFERENCED_SERIES_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![series]),
+            ),
+        ]);
+
+        let references = ReferenceExtractor::extract(&gsps).unwrap();
+        assert_eq!(references.len(), 2);
+        assert_eq!(references[0].kind, ReferenceKind::PresentationState);
+        assert_eq!(references[1].referenced_sop_instance_uid, "1.2.3.1.2");
+        assert_eq!(references[0].referenced_series_uid, "1.2.3.1");
+        assert_eq!(references[0].label.as_deref(), Some("LUNG"));
+
+        let relationship = references[0].to_new_relationship();
+        assert_eq!(relationship.relationship_type, "PR");
+        assert_eq!(relationship.study_uid, "1.2.3");
+    }
+
+    #[test]
+    fn test_ignore_ordinary_images() {
+        let image = InMemDicomObject::from_element_iter([
+            DataElement::new(
+                tags::SOP_CLASS_UID,
+                VR::UI,
+                PrimitiveValue::from(uids::CT_IMAGE_STORAGE),
+            ),
+            DataElement::new(
+                tags::SOP_INSTANCE_UID,
+                VR::UI,
+                PrimitiveValue::from("1.2.3.1.1"),
+            ),
+        ]);
+        assert!(ReferenceExtractor::extract(&image).is_none());
+        assert_eq!(
+            ReferenceKind::from_sop_class_uid(uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE),
+            Some(ReferenceKind::KeyObjectSelection)
+        );
+    }
+}
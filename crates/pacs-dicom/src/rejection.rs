@@ -0,0 +1,362 @@
+//! IOCM拒绝说明（Rejection Note KOS）
+//!
+//! 按IHE IOCM生成引用被拒绝实例的关键对象选择文档，文档标题（概念名称）
+//! 标识拒绝原因。下游系统收到后隐藏或删除被引用的实例。
+
+use crate::ingest::IngestPipeline;
+use chrono::Utc;
+use dicom::core::value::DataSetSequence;
+use dicom::core::{DataElement, PrimitiveValue, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::{DefaultDicomObject, FileMetaTableBuilder, InMemDicomObject};
+use pacs_core::utils::generate_dicom_uid;
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// 拒绝原因（CID 7011）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RejectionReason {
+    /// 质量原因
+    QualityReasons,
+    /// 患者安全原因
+    PatientSafety,
+    /// 错误的工作列表条目（患者/检查归属错误）
+    IncorrectWorklistEntry,
+    /// 超出保存期限
+    RetentionExpired,
+}
+
+impl RejectionReason {
+    /// DCM编码值
+    pub fn code_value(&self) -> &'static str {
+        match self {
+            Self::QualityReasons => "113001",
+            Self::PatientSafety => "113037",
+            Self::IncorrectWorklistEntry => "113038",
+            Self::RetentionExpired => "113039",
+        }
+    }
+
+    /// 编码含义
+    pub fn code_meaning(&self) -> &'static str {
+        match self {
+            Self::QualityReasons => "Rejected for Quality Reasons",
+            Self::PatientSafety => "Rejected for Patient Safety Reasons",
+            Self::IncorrectWorklistEntry => "Incorrect Modality Worklist Entry",
+            Self::RetentionExpired => "Data Retention Policy Expired",
+        }
+    }
+
+    /// 根据DCM编码值识别拒绝原因
+    pub fn from_code_value(code_value: &str) -> Option<Self> {
+        match code_value.trim() {
+            "113001" => Some(Self::QualityReasons),
+            "113037" => Some(Self::PatientSafety),
+            "113038" => Some(Self::IncorrectWorklistEntry),
+            "113039" => Some(Self::RetentionExpired),
+            _ => None,
+        }
+    }
+
+    /// 识别拒绝说明的拒绝原因，对象不是拒绝说明时返回None
+    pub fn from_rejection_note(obj: &InMemDicomObject) -> Option<Self> {
+        let sop_class_uid = obj.element(tags::SOP_CLASS_UID).ok()?.to_str().ok()?;
+        if sop_class_uid.trim_end_matches(['\0', ' '])
+            != uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE
+        {
+            return None;
+        }
+
+        let concept = obj
+            .element(tags::CONCEPT_NAME_CODE_SEQUENCE)
+            .ok()?
+            .items()?
+            .first()?;
+        let scheme = concept
+            .element(tags::CODING_SCHEME_DESIGNATOR)
+            .ok()?
+            .to_str()
+            .ok()?;
+        if scheme.trim_end_matches(['\0', ' ']) != "DCM" {
+            return None;
+        }
+        let code_value = concept.element(tags::CODE_VALUE).ok()?.to_str().ok()?;
+        Self::from_code_value(code_value.trim_end_matches('\0'))
+    }
+}
+
+/// 被拒绝的实例
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedInstance {
+    /// 序列实例UID
+    pub series_instance_uid: String,
+    /// SOP Class UID
+    pub sop_class_uid: String,
+    /// SOP实例UID
+    pub sop_instance_uid: String,
+}
+
+/// 拒绝说明生成请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectionNoteRequest {
+    /// 被拒绝实例所属检查UID
+    pub study_instance_uid: String,
+    /// 患者ID
+    pub patient_id: String,
+    /// 患者姓名
+    pub patient_name: String,
+    /// 患者出生日期
+    pub patient_birth_date: Option<String>,
+    /// 患者性别
+    pub patient_sex: Option<String>,
+    /// 检查号
+    pub accession_number: Option<String>,
+    /// 拒绝原因
+    pub reason: RejectionReason,
+    /// 被拒绝的实例
+    pub instances: Vec<RejectedInstance>,
+}
+
+/// 拒绝说明生成器
+pub struct RejectionNoteBuilder;
+
+impl RejectionNoteBuilder {
+    /// 生成拒绝说明KOS对象
+    pub fn build(request: &RejectionNoteRequest) -> Result<DefaultDicomObject> {
+        if request.instances.is_empty() {
+            return Err(PacsError::Validation(
+                "拒绝说明至少需要引用一个实例".to_string(),
+            ));
+        }
+
+        let sop_instance_uid = generate_dicom_uid();
+        let series_instance_uid = generate_dicom_uid();
+        let now = Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let time = now.format("%H%M%S").to_string();
+
+        let mut obj = InMemDicomObject::new_empty();
+        let strings = [
+            (tags::SPECIFIC_CHARACTER_SET, VR::CS, "ISO_IR 192"),
+            (
+                tags::SOP_CLASS_UID,
+                VR::UI,
+                uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE,
+            ),
+            (tags::SOP_INSTANCE_UID, VR::UI, sop_instance_uid.as_str()),
+            (
+                tags::STUDY_INSTANCE_UID,
+                VR::UI,
+                request.study_instance_uid.as_str(),
+            ),
+            (
+                tags::SERIES_INSTANCE_UID,
+                VR::UI,
+                series_instance_uid.as_str(),
+            ),
+            (tags::PATIENT_ID, VR::LO, request.patient_id.as_str()),
+            (tags::PATIENT_NAME, VR::PN, request.patient_name.as_str()),
+            (tags::MODALITY, VR::CS, "KO"),
+            (tags::SERIES_NUMBER, VR::IS, "1"),
+            (tags::INSTANCE_NUMBER, VR::IS, "1"),
+            (tags::CONTENT_DATE, VR::DA, date.as_str()),
+            (tags::CONTENT_TIME, VR::TM, time.as_str()),
+            (tags::VALUE_TYPE, VR::CS, "CONTAINER"),
+            (tags::CONTINUITY_OF_CONTENT, VR::CS, "SEPARATE"),
+        ];
+        for (tag, vr, value) in strings {
+            obj.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+        }
+
+        let optional_strings = [
+            (
+                tags::PATIENT_BIRTH_DATE,
+                VR::DA,
+                &request.patient_birth_date,
+            ),
+            (tags::PATIENT_SEX, VR::CS, &request.patient_sex),
+            (tags::ACCESSION_NUMBER, VR::SH, &request.accession_number),
+        ];
+        for (tag, vr, value) in optional_strings {
+            obj.put(DataElement::new(
+                tag,
+                vr,
+                PrimitiveValue::from(value.as_deref().unwrap_or("")),
+            ));
+        }
+
+        obj.put(sequence(
+            tags::CONCEPT_NAME_CODE_SEQUENCE,
+            vec![code_item(
+                request.reason.code_value(),
+                "DCM",
+                request.reason.code_meaning(),
+            )],
+        ));
+        obj.put(sequence(
+            tags::CONTENT_TEMPLATE_SEQUENCE,
+            vec![InMemDicomObject::from_element_iter([
+                DataElement::new(tags::MAPPING_RESOURCE, VR::CS, PrimitiveValue::from("DCMR")),
+                DataElement::new(
+                    tags::TEMPLATE_IDENTIFIER,
+                    VR::CS,
+                    PrimitiveValue::from("2010"),
+                ),
+            ])],
+        ));
+
+        // 按序列分组被引用实例
+        let mut by_series: BTreeMap<&str, Vec<&RejectedInstance>> = BTreeMap::new();
+        for instance in &request.instances {
+            by_series
+                .entry(instance.series_instance_uid.as_str())
+                .or_default()
+                .push(instance);
+        }
+        let series_items = by_series
+            .into_iter()
+            .map(|(series_uid, instances)| {
+                InMemDicomObject::from_element_iter([
+                    DataElement::new(
+                        tags::SERIES_INSTANCE_UID,
+                        VR::UI,
+                        PrimitiveValue::from(series_uid),
+                    ),
+                    sequence(
+                        tags::REFERENCED_SOP_SEQUENCE,
+                        instances.into_iter().map(referenced_sop).collect(),
+                    ),
+                ])
+            })
+            .collect();
+        obj.put(sequence(
+            tags::CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE,
+            vec![InMemDicomObject::from_element_iter([
+                DataElement::new(
+                    tags::STUDY_INSTANCE_UID,
+                    VR::UI,
+                    PrimitiveValue::from(request.study_instance_uid.as_str()),
+                ),
+                sequence(tags::REFERENCED_SERIES_SEQUENCE, series_items),
+            ])],
+        ));
+
+        let content_items = request
+            .instances
+            .iter()
+            .map(|instance| {
+                InMemDicomObject::from_element_iter([
+                    DataElement::new(
+                        tags::RELATIONSHIP_TYPE,
+                        VR::CS,
+                        PrimitiveValue::from("CONTAINS"),
+                    ),
+                    DataElement::new(tags::VALUE_TYPE, VR::CS, PrimitiveValue::from("IMAGE")),
+                    sequence(
+                        tags::REFERENCED_SOP_SEQUENCE,
+                        vec![referenced_sop(instance)],
+                    ),
+                ])
+            })
+            .collect();
+        obj.put(sequence(tags::CONTENT_SEQUENCE, content_items));
+
+        info!(
+            "生成拒绝说明: {} -> 检查 {}（{}，{} 个实例）",
+            sop_instance_uid,
+            request.study_instance_uid,
+            request.reason.code_meaning(),
+            request.instances.len()
+        );
+
+        obj.with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+            .map_err(|e| PacsError::Dicom(format!("无法生成文件元信息: {:?}", e)))
+    }
+
+    /// 生成拒绝说明并编码为DICOM文件字节
+    pub fn build_bytes(request: &RejectionNoteRequest) -> Result<Vec<u8>> {
+        let obj = Self::build(request)?;
+        IngestPipeline::encode_object(&obj)
+    }
+}
+
+/// 构造序列元素
+fn sequence(tag: dicom::core::Tag, items: Vec<InMemDicomObject>) -> DataElement<InMemDicomObject> {
+    DataElement::new(tag, VR::SQ, DataSetSequence::from(items))
+}
+
+/// 构造编码项
+fn code_item(value: &str, scheme: &str, meaning: &str) -> InMemDicomObject {
+    InMemDicomObject::from_element_iter([
+        DataElement::new(tags::CODE_VALUE, VR::SH, PrimitiveValue::from(value)),
+        DataElement::new(
+            tags::CODING_SCHEME_DESIGNATOR,
+            VR::SH,
+            PrimitiveValue::from(scheme),
+        ),
+        DataElement::new(tags::CODE_MEANING, VR::LO, PrimitiveValue::from(meaning)),
+    ])
+}
+
+/// 构造被引用SOP项
+fn referenced_sop(instance: &RejectedInstance) -> InMemDicomObject {
+    InMemDicomObject::from_element_iter([
+        DataElement::new(
+            tags::REFERENCED_SOP_CLASS_UID,
+            VR::UI,
+            PrimitiveValue::from(instance.sop_class_uid.as_str()),
+        ),
+        DataElement::new(
+            tags::REFERENCED_SOP_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from(instance.sop_instance_uid.as_str()),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::{ReferenceExtractor, ReferenceKind};
+
+    #[test]
+    fn test_rejection_note_references() {
+        let instance = |series: &str, sop: &str| RejectedInstance {
+            series_instance_uid: series.to_string(),
+            sop_class_uid: uids::CT_IMAGE_STORAGE.to_string(),
+            sop_instance_uid: sop.to_string(),
+        };
+        let request = RejectionNoteRequest {
+            study_instance_uid: "1.2.3".to_string(),
+            patient_id: "PAT001".to_string(),
+            patient_name: "TEST^PATIENT".to_string(),
+            patient_birth_date: None,
+            patient_sex: None,
+            accession_number: Some("ACC001".to_string()),
+            reason: RejectionReason::IncorrectWorklistEntry,
+            instances: vec![
+                instance("1.2.3.1", "1.2.3.1.1"),
+                instance("1.2.3.1", "1.2.3.1.2"),
+                instance("1.2.3.2", "1.2.3.2.1"),
+            ],
+        };
+
+        let note = RejectionNoteBuilder::build(&request).unwrap();
+        let references = ReferenceExtractor::extract(&note).unwrap();
+        assert_eq!(references.len(), 3);
+        assert!(references
+            .iter()
+            .all(|r| r.kind == ReferenceKind::KeyObjectSelection));
+        assert_eq!(
+            references[0].label.as_deref(),
+            Some("Incorrect Modality Worklist Entry")
+        );
+        assert_eq!(references[2].referenced_series_uid, "1.2.3.2");
+        assert_eq!(
+            RejectionReason::from_rejection_note(&note),
+            Some(RejectionReason::IncorrectWorklistEntry)
+        );
+    }
+}
@@ -0,0 +1,1015 @@
+//! DICOM SCU（服务类用户）
+//!
+//! 作为关联请求方连接远端AE，支持C-ECHO验证、C-STORE发送，
+//! 以及检查级（Study Root）的C-FIND查询与C-MOVE获取。
+//! 关联与DIMSE交互基于同步的dicom-ul实现，在阻塞线程池中执行。
+
+use crate::parser::DicomParser;
+use chrono::NaiveDate;
+use dicom::core::{DataElement, PrimitiveValue, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::encoding::{TransferSyntax, TransferSyntaxIndex};
+use dicom::object::{DefaultDicomObject, InMemDicomObject};
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+use dicom::ul::pdu::{PDataValue, PDataValueType};
+use dicom::ul::{ClientAssociation, ClientAssociationOptions, Pdu};
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// C-STORE-RQ命令字段
+const C_STORE_RQ: u16 = 0x0001;
+/// C-FIND-RQ命令字段
+const C_FIND_RQ: u16 = 0x0020;
+/// C-MOVE-RQ命令字段
+const C_MOVE_RQ: u16 = 0x0021;
+/// C-ECHO-RQ命令字段
+const C_ECHO_RQ: u16 = 0x0030;
+/// 命令后跟随数据集
+const DATA_SET_PRESENT: u16 = 0x0000;
+/// 命令后无数据集
+const NO_DATA_SET: u16 = 0x0101;
+/// 本地处理失败时记录的状态码（Refused: SOP Class not supported）
+const STATUS_NOT_SUPPORTED: u16 = 0x0122;
+/// 本地处理失败时记录的状态码（Error: Cannot understand）
+const STATUS_CANNOT_UNDERSTAND: u16 = 0xC000;
+
+/// 远端应用实体
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteAe {
+    /// AE标题
+    pub ae_title: String,
+    /// 主机地址
+    pub host: String,
+    /// 端口
+    pub port: u16,
+}
+
+impl RemoteAe {
+    /// 创建远端AE
+    pub fn new(ae_title: impl Into<String>, host: impl Into<String>, port: u16) -> Self {
+        Self {
+            ae_title: ae_title.into(),
+            host: host.into(),
+            port,
+        }
+    }
+
+    /// 套接字地址（host:port）
+    pub fn socket_address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+impl std::fmt::Display for RemoteAe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}:{}", self.ae_title, self.host, self.port)
+    }
+}
+
+/// SCU配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScuConfig {
+    /// 本地AE标题（Calling AE）
+    pub calling_ae_title: String,
+    /// 建立连接超时
+    pub connect_timeout: Duration,
+    /// DIMSE读写超时
+    pub dimse_timeout: Duration,
+    /// 本地可接收的最大PDU长度
+    pub max_pdu_length: u32,
+}
+
+impl Default for ScuConfig {
+    fn default() -> Self {
+        Self {
+            calling_ae_title: "PACS_SERVER".to_string(),
+            connect_timeout: Duration::from_secs(10),
+            dimse_timeout: Duration::from_secs(60),
+            max_pdu_length: 16384,
+        }
+    }
+}
+
+/// 单个实例的C-STORE结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreResult {
+    /// 本地文件路径
+    pub file_path: PathBuf,
+    /// SOP实例UID（文件无法解析时为空）
+    pub sop_instance_uid: String,
+    /// DIMSE状态码
+    pub status: u16,
+    /// 失败原因
+    pub error: Option<String>,
+}
+
+impl StoreResult {
+    /// 是否发送成功（Success或Warning）
+    pub fn is_success(&self) -> bool {
+        is_success_status(self.status)
+    }
+}
+
+/// 判断DIMSE状态码是否表示成功（含警告）
+pub fn is_success_status(status: u16) -> bool {
+    matches!(status, 0x0000 | 0x0001 | 0xB000 | 0xB006 | 0xB007)
+}
+
+/// 判断DIMSE状态码是否为Pending（后续还有响应）
+fn is_pending_status(status: u16) -> bool {
+    matches!(status, 0xFF00 | 0xFF01)
+}
+
+/// 检查级C-FIND查询条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StudyQuery {
+    /// 患者ID
+    pub patient_id: String,
+    /// 检查日期下限（含）
+    pub study_date_from: Option<NaiveDate>,
+    /// 检查日期上限（含）
+    pub study_date_to: Option<NaiveDate>,
+}
+
+/// C-FIND返回的检查
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StudyQueryResult {
+    /// 检查实例UID
+    pub study_instance_uid: String,
+    /// 患者ID
+    pub patient_id: Option<String>,
+    /// 检查日期
+    pub study_date: Option<NaiveDate>,
+    /// 检查描述
+    pub study_description: Option<String>,
+    /// 检查号
+    pub accession_number: Option<String>,
+    /// 检查包含的模态
+    pub modalities: Vec<String>,
+    /// 检查部位（远端支持时返回）
+    pub body_part_examined: Option<String>,
+    /// 检查实例数
+    pub number_of_instances: Option<u32>,
+}
+
+impl StudyQueryResult {
+    /// 从C-FIND响应标识符解析
+    fn from_identifier(identifier: &InMemDicomObject) -> Option<Self> {
+        let get = |tag| {
+            identifier
+                .element(tag)
+                .ok()
+                .and_then(|e| e.to_str().ok())
+                .map(|s| s.trim_end_matches(['\0', ' ']).trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+
+        Some(Self {
+            study_instance_uid: get(tags::STUDY_INSTANCE_UID)?,
+            patient_id: get(tags::PATIENT_ID),
+            study_date: get(tags::STUDY_DATE)
+                .and_then(|d| NaiveDate::parse_from_str(&d, "%Y%m%d").ok()),
+            study_description: get(tags::STUDY_DESCRIPTION),
+            accession_number: get(tags::ACCESSION_NUMBER),
+            modalities: get(tags::MODALITIES_IN_STUDY)
+                .map(|m| m.split('\\').map(|v| v.trim().to_string()).collect())
+                .unwrap_or_default(),
+            body_part_examined: get(tags::BODY_PART_EXAMINED),
+            number_of_instances: get(tags::NUMBER_OF_STUDY_RELATED_INSTANCES)
+                .and_then(|n| n.parse().ok()),
+        })
+    }
+}
+
+/// C-MOVE结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MoveResult {
+    /// 最终状态码
+    pub status: u16,
+    /// 完成的子操作数
+    pub completed: u32,
+    /// 失败的子操作数
+    pub failed: u32,
+    /// 警告的子操作数
+    pub warning: u32,
+}
+
+impl MoveResult {
+    /// 是否全部成功（含警告）
+    pub fn is_success(&self) -> bool {
+        is_success_status(self.status) && self.failed == 0
+    }
+}
+
+/// DIMSE响应消息
+struct DimseMessage {
+    /// 命令集
+    command: InMemDicomObject,
+    /// 数据集（按协商的传输语法编码）
+    dataset: Option<Vec<u8>>,
+}
+
+impl DimseMessage {
+    /// 响应状态码
+    fn status(&self) -> Result<u16> {
+        self.command_u16(tags::STATUS)
+            .ok_or_else(|| PacsError::DicomParseError("响应缺少Status元素".to_string()))
+    }
+
+    /// 读取命令集中的US元素
+    fn command_u16(&self, tag: dicom::core::Tag) -> Option<u16> {
+        self.command
+            .element(tag)
+            .ok()
+            .and_then(|e| e.to_int::<u16>().ok())
+    }
+}
+
+/// 待发送的实例
+struct PreparedInstance {
+    file_path: PathBuf,
+    object: DefaultDicomObject,
+    sop_class_uid: String,
+    sop_instance_uid: String,
+    transfer_syntax_uid: String,
+}
+
+/// DICOM SCU客户端
+#[derive(Debug, Clone, Default)]
+pub struct DicomScu {
+    config: ScuConfig,
+}
+
+impl DicomScu {
+    /// 创建SCU客户端
+    pub fn new(config: ScuConfig) -> Self {
+        Self { config }
+    }
+
+    /// 获取配置
+    pub fn config(&self) -> &ScuConfig {
+        &self.config
+    }
+
+    /// 对远端AE执行C-ECHO验证
+    pub async fn echo(&self, remote: &RemoteAe) -> Result<()> {
+        let config = self.config.clone();
+        let remote = remote.clone();
+        run_blocking(move || {
+            let mut association = establish(
+                &config,
+                &remote,
+                &[(
+                    uids::VERIFICATION,
+                    vec![uids::IMPLICIT_VR_LITTLE_ENDIAN.to_string()],
+                )],
+            )?;
+            let pc_id = association
+                .presentation_contexts()
+                .first()
+                .map(|pc| pc.id)
+                .ok_or_else(|| PacsError::Dicom(format!("{} 拒绝了验证服务", remote)))?;
+
+            let command = command_set(C_ECHO_RQ, 1, uids::VERIFICATION, None, NO_DATA_SET)?;
+            send_command(&mut association, pc_id, command)?;
+            let status = receive_status(&mut association)?;
+            release(association);
+
+            if is_success_status(status) {
+                Ok(())
+            } else {
+                Err(PacsError::Dicom(format!(
+                    "C-ECHO失败: {} 状态 0x{:04X}",
+                    remote, status
+                )))
+            }
+        })
+        .await
+    }
+
+    /// 在同一个关联中向远端AE发送一组DICOM文件
+    ///
+    /// 关联建立失败时返回错误；单个实例的失败记录在对应的结果中
+    pub async fn store_files(
+        &self,
+        remote: &RemoteAe,
+        files: Vec<PathBuf>,
+    ) -> Result<Vec<StoreResult>> {
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut results = Vec::new();
+        let mut instances = Vec::new();
+        for file_path in files {
+            match prepare_instance(&file_path).await {
+                Ok(instance) => instances.push(instance),
+                Err(e) => {
+                    warn!("无法读取待发送文件 {:?}: {}", file_path, e);
+                    results.push(StoreResult {
+                        file_path,
+                        sop_instance_uid: String::new(),
+                        status: STATUS_CANNOT_UNDERSTAND,
+                        error: Some(e.to_string()),
+                    });
+                }
+            }
+        }
+        if instances.is_empty() {
+            return Ok(results);
+        }
+
+        let config = self.config.clone();
+        let remote = remote.clone();
+        let sent = run_blocking(move || store_blocking(&config, &remote, instances)).await?;
+        results.extend(sent);
+        Ok(results)
+    }
+
+    /// 以检查级C-FIND按患者查询远端检查
+    pub async fn find_studies(
+        &self,
+        remote: &RemoteAe,
+        query: &StudyQuery,
+    ) -> Result<Vec<StudyQueryResult>> {
+        let config = self.config.clone();
+        let remote = remote.clone();
+        let query = query.clone();
+        run_blocking(move || find_blocking(&config, &remote, &query)).await
+    }
+
+    /// 以检查级C-MOVE请求远端将检查发送到目标AE
+    pub async fn move_study(
+        &self,
+        remote: &RemoteAe,
+        study_instance_uid: &str,
+        destination_ae_title: &str,
+    ) -> Result<MoveResult> {
+        let config = self.config.clone();
+        let remote = remote.clone();
+        let study_instance_uid = study_instance_uid.to_string();
+        let destination_ae_title = destination_ae_title.to_string();
+        run_blocking(move || {
+            move_blocking(&config, &remote, &study_instance_uid, &destination_ae_title)
+        })
+        .await
+    }
+}
+
+/// 在阻塞线程池中执行同步网络操作
+async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| PacsError::Internal(format!("SCU任务异常终止: {}", e)))?
+}
+
+/// 读取文件并提取发送所需的元信息
+async fn prepare_instance(file_path: &PathBuf) -> Result<PreparedInstance> {
+    let data = tokio::fs::read(file_path).await?;
+    let object = DicomParser::read_object(&data)?;
+    let meta = object.meta();
+    Ok(PreparedInstance {
+        file_path: file_path.clone(),
+        sop_class_uid: trim_uid(meta.media_storage_sop_class_uid()),
+        sop_instance_uid: trim_uid(meta.media_storage_sop_instance_uid()),
+        transfer_syntax_uid: trim_uid(meta.transfer_syntax()),
+        object,
+    })
+}
+
+/// 同步执行C-STORE
+fn store_blocking(
+    config: &ScuConfig,
+    remote: &RemoteAe,
+    instances: Vec<PreparedInstance>,
+) -> Result<Vec<StoreResult>> {
+    // 每个(SOP类, 原始传输语法)单独提议一个表示上下文，
+    // 另为每个SOP类提议一个显式/隐式VR Little Endian上下文，供非压缩数据转码发送
+    let mut proposals: Vec<(&str, Vec<String>)> = Vec::new();
+    for instance in &instances {
+        let original = vec![instance.transfer_syntax_uid.clone()];
+        if !proposals
+            .iter()
+            .any(|(class, ts)| *class == instance.sop_class_uid && *ts == original)
+        {
+            proposals.push((&instance.sop_class_uid, original));
+        }
+        let native = native_transfer_syntaxes();
+        if !proposals
+            .iter()
+            .any(|(class, ts)| *class == instance.sop_class_uid && *ts == native)
+        {
+            proposals.push((&instance.sop_class_uid, native));
+        }
+    }
+
+    let mut association = establish(config, remote, &proposals)?;
+    let mut results = Vec::with_capacity(instances.len());
+
+    for (index, instance) in instances.iter().enumerate() {
+        let message_id = (index % 0xFFFF) as u16 + 1;
+        let result = match store_instance(&mut association, instance, message_id) {
+            Ok(status) => StoreResult {
+                file_path: instance.file_path.clone(),
+                sop_instance_uid: instance.sop_instance_uid.clone(),
+                status,
+                error: (!is_success_status(status))
+                    .then(|| format!("远端返回状态 0x{:04X}", status)),
+            },
+            Err(StoreError::Instance(status, message)) => StoreResult {
+                file_path: instance.file_path.clone(),
+                sop_instance_uid: instance.sop_instance_uid.clone(),
+                status,
+                error: Some(message),
+            },
+            Err(StoreError::Association(e)) => {
+                // 关联已不可用，中止剩余实例的发送
+                let _ = association.abort();
+                return Err(e);
+            }
+        };
+        debug!(
+            "C-STORE {} -> {}: 0x{:04X}",
+            result.sop_instance_uid, remote, result.status
+        );
+        results.push(result);
+    }
+
+    release(association);
+    info!(
+        "C-STORE完成: {} 个实例发送到 {}，成功 {}",
+        results.len(),
+        remote,
+        results.iter().filter(|r| r.is_success()).count()
+    );
+    Ok(results)
+}
+
+/// 同步执行C-FIND
+fn find_blocking(
+    config: &ScuConfig,
+    remote: &RemoteAe,
+    query: &StudyQuery,
+) -> Result<Vec<StudyQueryResult>> {
+    let sop_class = uids::STUDY_ROOT_QUERY_RETRIEVE_INFORMATION_MODEL_FIND;
+    let mut association = establish(config, remote, &[(sop_class, native_transfer_syntaxes())])?;
+    let (pc_id, ts) = accepted_context(&association, sop_class, remote)?;
+
+    let date_range = match (query.study_date_from, query.study_date_to) {
+        (None, None) => String::new(),
+        (from, to) => format!(
+            "{}-{}",
+            from.map(|d| d.format("%Y%m%d").to_string())
+                .unwrap_or_default(),
+            to.map(|d| d.format("%Y%m%d").to_string())
+                .unwrap_or_default()
+        ),
+    };
+    let identifier = InMemDicomObject::from_element_iter([
+        DataElement::new(
+            tags::QUERY_RETRIEVE_LEVEL,
+            VR::CS,
+            PrimitiveValue::from("STUDY"),
+        ),
+        DataElement::new(
+            tags::PATIENT_ID,
+            VR::LO,
+            PrimitiveValue::from(query.patient_id.as_str()),
+        ),
+        DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, PrimitiveValue::Empty),
+        DataElement::new(
+            tags::STUDY_DATE,
+            VR::DA,
+            PrimitiveValue::from(date_range.as_str()),
+        ),
+        DataElement::new(tags::STUDY_DESCRIPTION, VR::LO, PrimitiveValue::Empty),
+        DataElement::new(tags::ACCESSION_NUMBER, VR::SH, PrimitiveValue::Empty),
+        DataElement::new(tags::MODALITIES_IN_STUDY, VR::CS, PrimitiveValue::Empty),
+        DataElement::new(tags::BODY_PART_EXAMINED, VR::CS, PrimitiveValue::Empty),
+        DataElement::new(
+            tags::NUMBER_OF_STUDY_RELATED_INSTANCES,
+            VR::IS,
+            PrimitiveValue::Empty,
+        ),
+    ]);
+
+    let command = encode_command(request_command(C_FIND_RQ, 1, sop_class, DATA_SET_PRESENT))?;
+    let result = send_command(&mut association, pc_id, command)
+        .and_then(|_| send_dataset(&mut association, pc_id, &identifier, ts))
+        .and_then(|_| {
+            let mut studies = Vec::new();
+            loop {
+                let response = receive_message(&mut association)?;
+                let status = response.status()?;
+                if !is_pending_status(status) {
+                    if !is_success_status(status) {
+                        return Err(PacsError::Dicom(format!(
+                            "C-FIND失败: {} 状态 0x{:04X}",
+                            remote, status
+                        )));
+                    }
+                    return Ok(studies);
+                }
+                if let Some(data) = response.dataset {
+                    let identifier = InMemDicomObject::read_dataset_with_ts(Cursor::new(data), ts)
+                        .map_err(|e| {
+                            PacsError::DicomParseError(format!("无法解析C-FIND响应: {:?}", e))
+                        })?;
+                    studies.extend(StudyQueryResult::from_identifier(&identifier));
+                }
+            }
+        });
+
+    match result {
+        Ok(studies) => {
+            release(association);
+            info!(
+                "C-FIND {} 患者 {}: {} 个检查",
+                remote,
+                query.patient_id,
+                studies.len()
+            );
+            Ok(studies)
+        }
+        Err(e) => {
+            let _ = association.abort();
+            Err(e)
+        }
+    }
+}
+
+/// 同步执行C-MOVE
+fn move_blocking(
+    config: &ScuConfig,
+    remote: &RemoteAe,
+    study_instance_uid: &str,
+    destination_ae_title: &str,
+) -> Result<MoveResult> {
+    let sop_class = uids::STUDY_ROOT_QUERY_RETRIEVE_INFORMATION_MODEL_MOVE;
+    let mut association = establish(config, remote, &[(sop_class, native_transfer_syntaxes())])?;
+    let (pc_id, ts) = accepted_context(&association, sop_class, remote)?;
+
+    let identifier = InMemDicomObject::from_element_iter([
+        DataElement::new(
+            tags::QUERY_RETRIEVE_LEVEL,
+            VR::CS,
+            PrimitiveValue::from("STUDY"),
+        ),
+        DataElement::new(
+            tags::STUDY_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from(study_instance_uid),
+        ),
+    ]);
+    let mut command = request_command(C_MOVE_RQ, 1, sop_class, DATA_SET_PRESENT);
+    command.put(DataElement::new(
+        tags::MOVE_DESTINATION,
+        VR::AE,
+        PrimitiveValue::from(destination_ae_title),
+    ));
+    let command = encode_command(command)?;
+
+    let result = send_command(&mut association, pc_id, command)
+        .and_then(|_| send_dataset(&mut association, pc_id, &identifier, ts))
+        .and_then(|_| loop {
+            let response = receive_message(&mut association)?;
+            let status = response.status()?;
+            let result = MoveResult {
+                status,
+                completed: response
+                    .command_u16(tags::NUMBER_OF_COMPLETED_SUBOPERATIONS)
+                    .unwrap_or(0) as u32,
+                failed: response
+                    .command_u16(tags::NUMBER_OF_FAILED_SUBOPERATIONS)
+                    .unwrap_or(0) as u32,
+                warning: response
+                    .command_u16(tags::NUMBER_OF_WARNING_SUBOPERATIONS)
+                    .unwrap_or(0) as u32,
+            };
+            if !is_pending_status(status) {
+                break Ok(result);
+            }
+        });
+
+    match result {
+        Ok(result) => {
+            release(association);
+            info!(
+                "C-MOVE {} 检查 {} -> {}: 状态 0x{:04X}，完成 {}，失败 {}",
+                remote,
+                study_instance_uid,
+                destination_ae_title,
+                result.status,
+                result.completed,
+                result.failed
+            );
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = association.abort();
+            Err(e)
+        }
+    }
+}
+
+/// 获取SOP类已接受的表示上下文
+fn accepted_context(
+    association: &ClientAssociation<TcpStream>,
+    sop_class_uid: &str,
+    remote: &RemoteAe,
+) -> Result<(u8, &'static TransferSyntax)> {
+    association
+        .presentation_contexts()
+        .iter()
+        .filter(|pc| trim_uid(&pc.abstract_syntax) == sop_class_uid)
+        .find_map(|pc| {
+            TransferSyntaxRegistry
+                .get(trim_uid(&pc.transfer_syntax).as_str())
+                .map(|ts| (pc.id, ts))
+        })
+        .ok_or_else(|| PacsError::Dicom(format!("{} 不支持 {}", remote, sop_class_uid)))
+}
+
+/// 单个实例发送错误
+enum StoreError {
+    /// 实例无法发送，关联仍可继续使用
+    Instance(u16, String),
+    /// 关联层错误
+    Association(PacsError),
+}
+
+/// 发送单个实例，返回远端状态码
+fn store_instance(
+    association: &mut ClientAssociation<TcpStream>,
+    instance: &PreparedInstance,
+    message_id: u16,
+) -> std::result::Result<u16, StoreError> {
+    let (pc_id, ts) = select_presentation_context(association, instance).ok_or_else(|| {
+        StoreError::Instance(
+            STATUS_NOT_SUPPORTED,
+            format!(
+                "远端未接受SOP类 {} / 传输语法 {}",
+                instance.sop_class_uid, instance.transfer_syntax_uid
+            ),
+        )
+    })?;
+
+    let mut dataset = Vec::new();
+    instance
+        .object
+        .write_dataset_with_ts(&mut dataset, ts)
+        .map_err(|e| {
+            StoreError::Instance(STATUS_CANNOT_UNDERSTAND, format!("数据集编码失败: {:?}", e))
+        })?;
+
+    let command = command_set(
+        C_STORE_RQ,
+        message_id,
+        &instance.sop_class_uid,
+        Some(&instance.sop_instance_uid),
+        DATA_SET_PRESENT,
+    )
+    .map_err(StoreError::Association)?;
+    send_command(association, pc_id, command).map_err(StoreError::Association)?;
+
+    let mut writer = association.send_pdata(pc_id);
+    writer
+        .write_all(&dataset)
+        .and_then(|_| writer.finish())
+        .map_err(|e| StoreError::Association(PacsError::Dicom(format!("发送数据集失败: {}", e))))?;
+
+    receive_status(association).map_err(StoreError::Association)
+}
+
+/// 为实例选择已接受的表示上下文
+fn select_presentation_context(
+    association: &ClientAssociation<TcpStream>,
+    instance: &PreparedInstance,
+) -> Option<(u8, &'static TransferSyntax)> {
+    let contexts = association.presentation_contexts();
+    let matching = contexts
+        .iter()
+        .filter(|pc| trim_uid(&pc.abstract_syntax) == instance.sop_class_uid);
+
+    // 优先使用原始传输语法，避免转码
+    for pc in matching.clone() {
+        if trim_uid(&pc.transfer_syntax) == instance.transfer_syntax_uid {
+            return TransferSyntaxRegistry
+                .get(&instance.transfer_syntax_uid)
+                .map(|ts| (pc.id, ts));
+        }
+    }
+
+    // 非压缩数据可在Little Endian传输语法之间转换
+    let original = TransferSyntaxRegistry.get(&instance.transfer_syntax_uid)?;
+    if original.is_encapsulated_pixel_data() {
+        return None;
+    }
+    matching
+        .filter_map(|pc| {
+            TransferSyntaxRegistry
+                .get(trim_uid(&pc.transfer_syntax).as_str())
+                .map(|ts| (pc.id, ts))
+        })
+        .find(|(_, ts)| !ts.is_encapsulated_pixel_data())
+}
+
+/// 建立关联
+fn establish(
+    config: &ScuConfig,
+    remote: &RemoteAe,
+    proposals: &[(&str, Vec<String>)],
+) -> Result<ClientAssociation<TcpStream>> {
+    let mut options = ClientAssociationOptions::new()
+        .calling_ae_title(config.calling_ae_title.as_str())
+        .called_ae_title(remote.ae_title.as_str())
+        .max_pdu_length(config.max_pdu_length)
+        .connection_timeout(config.connect_timeout)
+        .read_timeout(config.dimse_timeout)
+        .write_timeout(config.dimse_timeout);
+    for (abstract_syntax, transfer_syntaxes) in proposals {
+        options = options
+            .with_presentation_context(abstract_syntax.to_string(), transfer_syntaxes.clone());
+    }
+
+    let association = options
+        .establish(remote.socket_address())
+        .map_err(|e| PacsError::Dicom(format!("无法与 {} 建立关联: {}", remote, e)))?;
+    debug!(
+        "已与 {} 建立关联，接受 {} 个表示上下文",
+        remote,
+        association.presentation_contexts().len()
+    );
+    Ok(association)
+}
+
+/// 释放关联，失败时仅记录日志
+fn release(association: ClientAssociation<TcpStream>) {
+    if let Err(e) = association.release() {
+        warn!("释放关联失败: {}", e);
+    }
+}
+
+/// 可在原生（非压缩）格式间互相转换的传输语法
+fn native_transfer_syntaxes() -> Vec<String> {
+    vec![
+        uids::EXPLICIT_VR_LITTLE_ENDIAN.to_string(),
+        uids::IMPLICIT_VR_LITTLE_ENDIAN.to_string(),
+    ]
+}
+
+/// 构建DIMSE请求命令集（隐式VR Little Endian编码）
+fn command_set(
+    command_field: u16,
+    message_id: u16,
+    sop_class_uid: &str,
+    sop_instance_uid: Option<&str>,
+    data_set_type: u16,
+) -> Result<Vec<u8>> {
+    let mut command = request_command(command_field, message_id, sop_class_uid, data_set_type);
+    if let Some(sop_instance_uid) = sop_instance_uid {
+        command.put(DataElement::new(
+            tags::AFFECTED_SOP_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from(sop_instance_uid),
+        ));
+    }
+    encode_command(command)
+}
+
+/// 构建请求命令的公共元素
+fn request_command(
+    command_field: u16,
+    message_id: u16,
+    sop_class_uid: &str,
+    data_set_type: u16,
+) -> InMemDicomObject {
+    let mut command = InMemDicomObject::from_element_iter([
+        DataElement::new(
+            tags::AFFECTED_SOP_CLASS_UID,
+            VR::UI,
+            PrimitiveValue::from(sop_class_uid),
+        ),
+        DataElement::new(
+            tags::COMMAND_FIELD,
+            VR::US,
+            PrimitiveValue::from(command_field),
+        ),
+        DataElement::new(tags::MESSAGE_ID, VR::US, PrimitiveValue::from(message_id)),
+        DataElement::new(
+            tags::COMMAND_DATA_SET_TYPE,
+            VR::US,
+            PrimitiveValue::from(data_set_type),
+        ),
+    ]);
+    if command_field != C_ECHO_RQ {
+        command.put(DataElement::new(
+            tags::PRIORITY,
+            VR::US,
+            PrimitiveValue::from(0u16),
+        ));
+    }
+    command
+}
+
+/// 编码命令集并补充命令组长度
+fn encode_command(mut command: InMemDicomObject) -> Result<Vec<u8>> {
+    // 命令组长度为其后所有命令元素的编码长度
+    let ts = implicit_vr_little_endian()?;
+    let mut body = Vec::new();
+    command
+        .write_dataset_with_ts(&mut body, ts)
+        .map_err(|e| PacsError::Dicom(format!("命令集编码失败: {:?}", e)))?;
+    command.put(DataElement::new(
+        tags::COMMAND_GROUP_LENGTH,
+        VR::UL,
+        PrimitiveValue::from(body.len() as u32),
+    ));
+
+    let mut encoded = Vec::new();
+    command
+        .write_dataset_with_ts(&mut encoded, ts)
+        .map_err(|e| PacsError::Dicom(format!("命令集编码失败: {:?}", e)))?;
+    Ok(encoded)
+}
+
+/// 发送命令集
+fn send_command(
+    association: &mut ClientAssociation<TcpStream>,
+    pc_id: u8,
+    command: Vec<u8>,
+) -> Result<()> {
+    association
+        .send(&Pdu::PData {
+            data: vec![PDataValue {
+                presentation_context_id: pc_id,
+                value_type: PDataValueType::Command,
+                is_last: true,
+                data: command,
+            }],
+        })
+        .map_err(|e| PacsError::Dicom(format!("发送命令失败: {}", e)))
+}
+
+/// 以P-DATA发送数据集
+fn send_dataset(
+    association: &mut ClientAssociation<TcpStream>,
+    pc_id: u8,
+    dataset: &InMemDicomObject,
+    ts: &TransferSyntax,
+) -> Result<()> {
+    let mut data = Vec::new();
+    dataset
+        .write_dataset_with_ts(&mut data, ts)
+        .map_err(|e| PacsError::Dicom(format!("数据集编码失败: {:?}", e)))?;
+
+    let mut writer = association.send_pdata(pc_id);
+    writer
+        .write_all(&data)
+        .and_then(|_| writer.finish())
+        .map_err(|e| PacsError::Dicom(format!("发送数据集失败: {}", e)))
+}
+
+/// 接收一个完整的DIMSE响应（命令集及其后的数据集）
+fn receive_message(association: &mut ClientAssociation<TcpStream>) -> Result<DimseMessage> {
+    let mut command_bytes = Vec::new();
+    let mut command: Option<InMemDicomObject> = None;
+    let mut dataset = Vec::new();
+    let mut dataset_complete = false;
+
+    loop {
+        let pdu = association
+            .receive()
+            .map_err(|e| PacsError::Dicom(format!("接收响应失败: {}", e)))?;
+        match pdu {
+            Pdu::PData { data } => {
+                for value in data {
+                    match value.value_type {
+                        PDataValueType::Command => {
+                            command_bytes.extend_from_slice(&value.data);
+                            if value.is_last {
+                                command = Some(
+                                    InMemDicomObject::read_dataset_with_ts(
+                                        Cursor::new(std::mem::take(&mut command_bytes)),
+                                        implicit_vr_little_endian()?,
+                                    )
+                                    .map_err(|e| {
+                                        PacsError::DicomParseError(format!(
+                                            "无法解析响应命令集: {:?}",
+                                            e
+                                        ))
+                                    })?,
+                                );
+                            }
+                        }
+                        PDataValueType::Data => {
+                            dataset.extend_from_slice(&value.data);
+                            dataset_complete |= value.is_last;
+                        }
+                    }
+                }
+            }
+            Pdu::AbortRQ { .. } => {
+                return Err(PacsError::Dicom("远端中止了关联".to_string()));
+            }
+            other => {
+                return Err(PacsError::Dicom(format!("收到意外的PDU: {:?}", other)));
+            }
+        }
+
+        if let Some(parsed) = command.take() {
+            let message = DimseMessage {
+                command: parsed,
+                dataset: None,
+            };
+            let has_dataset = message
+                .command_u16(tags::COMMAND_DATA_SET_TYPE)
+                .is_some_and(|t| t != NO_DATA_SET);
+            if !has_dataset {
+                return Ok(message);
+            }
+            if dataset_complete {
+                return Ok(DimseMessage {
+                    dataset: Some(dataset),
+                    ..message
+                });
+            }
+            command = Some(message.command);
+        }
+    }
+}
+
+/// 接收响应命令集并返回状态码
+fn receive_status(association: &mut ClientAssociation<TcpStream>) -> Result<u16> {
+    receive_message(association)?.status()
+}
+
+/// 隐式VR Little Endian传输语法
+fn implicit_vr_little_endian() -> Result<&'static TransferSyntax> {
+    TransferSyntaxRegistry
+        .get(uids::IMPLICIT_VR_LITTLE_ENDIAN)
+        .ok_or_else(|| PacsError::Dicom("传输语法注册表不可用".to_string()))
+}
+
+/// 去除UID末尾的填充字符
+fn trim_uid(uid: &str) -> String {
+    uid.trim_end_matches(['\0', ' ']).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_command_set() {
+        let encoded = command_set(
+            C_STORE_RQ,
+            7,
+            uids::CT_IMAGE_STORAGE,
+            Some("1.2.3.4"),
+            DATA_SET_PRESENT,
+        )
+        .unwrap();
+        let command = InMemDicomObject::read_dataset_with_ts(
+            Cursor::new(&encoded),
+            implicit_vr_little_endian().unwrap(),
+        )
+        .unwrap();
+
+        let group_length = command
+            .element(tags::COMMAND_GROUP_LENGTH)
+            .unwrap()
+            .to_int::<u32>()
+            .unwrap();
+        // 组长度不含组长度元素自身（隐式VR下为12字节）
+        assert_eq!(group_length as usize, encoded.len() - 12);
+        assert_eq!(
+            command
+                .element(tags::COMMAND_FIELD)
+                .unwrap()
+                .to_int::<u16>()
+                .unwrap(),
+            C_STORE_RQ
+        );
+        assert_eq!(
+            command
+                .element(tags::AFFECTED_SOP_INSTANCE_UID)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .trim_end_matches('\0'),
+            "1.2.3.4"
+        );
+        assert!(is_success_status(0xB000));
+        assert!(!is_success_status(0xA700));
+    }
+}
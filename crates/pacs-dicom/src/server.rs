@@ -2,10 +2,15 @@
 
 use crate::{
     association::{AssociationManager, PresentationContext, PresentationContextResult},
-    services::{DicomService, ServiceManager},
+    ingest::IngestPipeline,
+    services::{CFindService, CStoreService, DicomService, MppsService, ServiceManager},
+    stability::StudyStabilityTracker,
 };
+use dicom::dictionary_std::uids;
 use pacs_core::{PacsError, Result};
+use pacs_database::DatabasePool;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio_util::codec::Decoder;
@@ -44,13 +49,47 @@ impl DicomServer {
         // 确保存储目录存在
         tokio::fs::create_dir_all(&config.storage_dir).await?;
 
+        let mut service_manager = ServiceManager::new();
+        service_manager
+            .register_storage_service(Arc::new(CStoreService::new(config.storage_dir.clone())));
+
         Ok(Self {
             config,
             association_manager: AssociationManager::new(),
-            service_manager: ServiceManager::new(),
+            service_manager,
         })
     }
 
+    /// 使用共享的入库管道处理C-STORE（与Web上传、批量导入共用索引和入库监听器）
+    pub fn with_ingest_pipeline(mut self, pipeline: Arc<IngestPipeline>) -> Self {
+        self.service_manager
+            .register_storage_service(Arc::new(CStoreService::with_pipeline(pipeline)));
+        self
+    }
+
+    /// 从数据库响应患者根和检查根C-FIND查询
+    pub fn with_database(mut self, database: Arc<DatabasePool>) -> Self {
+        for sop_class_uid in [
+            uids::PATIENT_ROOT_QUERY_RETRIEVE_INFORMATION_MODEL_FIND,
+            uids::STUDY_ROOT_QUERY_RETRIEVE_INFORMATION_MODEL_FIND,
+        ] {
+            self.service_manager.register_service(
+                sop_class_uid.to_string(),
+                Box::new(CFindService::with_database(database.clone())),
+            );
+        }
+        self
+    }
+
+    /// 接收MPPS，执行步骤完成时通知稳定性跟踪器
+    pub fn with_stability_tracker(mut self, tracker: Arc<StudyStabilityTracker>) -> Self {
+        self.service_manager.register_service(
+            uids::MODALITY_PERFORMED_PROCEDURE_STEP.to_string(),
+            Box::new(MppsService::new(tracker)),
+        );
+        self
+    }
+
     /// 启动DICOM服务器
     pub async fn start(&self) -> Result<()> {
         let addr = SocketAddr::from(([0, 0, 0, 0], self.config.port));
@@ -124,7 +163,7 @@ impl Clone for DicomServer {
         Self {
             config: self.config.clone(),
             association_manager: AssociationManager::new(),
-            service_manager: ServiceManager::new(),
+            service_manager: self.service_manager.clone(),
         }
     }
 }
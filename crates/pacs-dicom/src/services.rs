@@ -1,14 +1,27 @@
 //! DICOM服务实现
 
+use crate::ingest::{DatasetMeta, IngestOutcome, IngestPipeline, IngestSource};
+use crate::query::{self, InstanceQueryService};
+use crate::stability::StudyStabilityTracker;
 use async_trait::async_trait;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
 use pacs_core::{PacsError, Result};
+use pacs_database::DatabasePool;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tracing::{debug, info, warn};
 
 /// DICOM服务特征
 #[async_trait]
 pub trait DicomService: Send + Sync {
     async fn handle_request(&self, request: DimseRequest) -> Result<DimseResponse>;
+
+    /// 处理请求并返回全部响应（C-FIND等服务先返回若干Pending响应，最后是终态响应）
+    async fn handle_request_all(&self, request: DimseRequest) -> Result<Vec<DimseResponse>> {
+        Ok(vec![self.handle_request(request).await?])
+    }
 }
 
 /// DICOM消息服务元素请求
@@ -18,6 +31,14 @@ pub struct DimseRequest {
     pub message_id: u16,
     pub affected_sop_class_uid: String,
     pub dataset: Option<Vec<u8>>,
+    /// 关联层边接收边写入的数据集临时文件，设置时优先于 `dataset`（由关联层清理）
+    pub dataset_file: Option<PathBuf>,
+    /// 受影响的SOP实例UID（N-SET为请求的SOP实例UID）
+    pub affected_sop_instance_uid: Option<String>,
+    /// 数据集所在表示上下文协商的传输语法UID
+    pub transfer_syntax_uid: Option<String>,
+    /// 发起关联的调用方AE标题
+    pub calling_ae_title: Option<String>,
 }
 
 /// DICOM消息服务元素响应
@@ -39,6 +60,8 @@ pub enum CommandField {
     CGet,
     CEcho,
     CCancel,
+    NCreate,
+    NSet,
 }
 
 /// DIMSE状态码
@@ -71,12 +94,35 @@ impl DicomService for CEchoService {
 
 /// C-STORE服务
 pub struct CStoreService {
-    storage_dir: String,
+    pipeline: Arc<IngestPipeline>,
 }
 
 impl CStoreService {
     pub fn new(storage_dir: String) -> Self {
-        Self { storage_dir }
+        Self::with_pipeline(Arc::new(IngestPipeline::new(storage_dir)))
+    }
+
+    /// 使用共享的入库管道创建C-STORE服务
+    pub fn with_pipeline(pipeline: Arc<IngestPipeline>) -> Self {
+        Self { pipeline }
+    }
+
+    /// 以流方式入库关联层写入的数据集文件
+    async fn ingest_file(
+        &self,
+        dataset_file: &Path,
+        meta: Option<&DatasetMeta>,
+        source: &IngestSource,
+    ) -> Result<IngestOutcome> {
+        match meta {
+            Some(meta) => {
+                let file = tokio::fs::File::open(dataset_file).await?;
+                self.pipeline
+                    .ingest_dataset_stream(file, meta, source, None)
+                    .await
+            }
+            None => self.pipeline.ingest_file(dataset_file, source, None).await,
+        }
     }
 }
 
@@ -85,64 +131,278 @@ impl DicomService for CStoreService {
     async fn handle_request(&self, request: DimseRequest) -> Result<DimseResponse> {
         info!("处理C-STORE请求");
 
-        match request.dataset {
-            Some(dataset) => {
-                // 这里应该解析DICOM数据集并存储
-                debug!("接收到DICOM数据集，大小: {} bytes", dataset.len());
+        let source = IngestSource::CStore {
+            calling_ae_title: request.calling_ae_title.clone(),
+        };
+        // 已知实例UID和传输语法时按裸数据集流式入库，不在内存中解析和重新编码
+        let meta = match (
+            &request.affected_sop_instance_uid,
+            &request.transfer_syntax_uid,
+        ) {
+            (Some(sop_instance_uid), Some(transfer_syntax_uid)) => Some(DatasetMeta {
+                sop_class_uid: request.affected_sop_class_uid.clone(),
+                sop_instance_uid: sop_instance_uid.clone(),
+                transfer_syntax_uid: transfer_syntax_uid.clone(),
+            }),
+            _ => None,
+        };
 
-                // 简化实现：直接写入文件
-                let filename = format!(
-                    "{}/{}.dcm",
-                    self.storage_dir,
-                    chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
-                );
-
-                tokio::fs::write(&filename, dataset).await?;
-                info!("DICOM文件已存储: {}", filename);
-
-                Ok(DimseResponse {
-                    command_field: CommandField::CStore,
-                    message_id_being_responded_to: request.message_id,
-                    status: DimseStatus::Success,
-                    affected_sop_class_uid: request.affected_sop_class_uid,
-                    dataset: None,
+        let result = match (&request.dataset_file, &request.dataset) {
+            (Some(dataset_file), _) => {
+                debug!("接收到DICOM数据集文件: {:?}", dataset_file);
+                Some(self.ingest_file(dataset_file, meta.as_ref(), &source).await)
+            }
+            (None, Some(dataset)) => {
+                debug!("接收到DICOM数据集，大小: {} bytes", dataset.len());
+                Some(match meta {
+                    Some(meta) if !IngestPipeline::has_file_meta(dataset) => {
+                        self.pipeline
+                            .ingest_dataset_stream(dataset.as_slice(), &meta, &source, None)
+                            .await
+                    }
+                    _ => self.pipeline.ingest(dataset, &source, None).await,
                 })
             }
+            (None, None) => None,
+        };
+
+        let status = match result {
+            Some(Ok(IngestOutcome::Stored(instance))) => {
+                info!("DICOM文件已存储: {:?}", instance.file_path);
+                DimseStatus::Success
+            }
+            Some(Ok(IngestOutcome::Duplicate { sop_instance_uid })) => {
+                info!("重复实例，已忽略: {}", sop_instance_uid);
+                DimseStatus::Success
+            }
+            Some(Err(PacsError::DicomParseError(e))) | Some(Err(PacsError::Validation(e))) => {
+                warn!("C-STORE数据集无法识别: {}", e);
+                DimseStatus::Failure(0xC000) // 无法理解
+            }
+            Some(Err(e)) => {
+                warn!("C-STORE入库失败: {}", e);
+                DimseStatus::Failure(0xA700) // 资源不足
+            }
             None => {
                 warn!("C-STORE请求缺少数据集");
-                Ok(DimseResponse {
-                    command_field: CommandField::CStore,
-                    message_id_being_responded_to: request.message_id,
-                    status: DimseStatus::Failure(0xC000), // 失败
-                    affected_sop_class_uid: request.affected_sop_class_uid,
-                    dataset: None,
-                })
+                DimseStatus::Failure(0xC000) // 失败
             }
-        }
+        };
+
+        Ok(DimseResponse {
+            command_field: CommandField::CStore,
+            message_id_being_responded_to: request.message_id,
+            status,
+            affected_sop_class_uid: request.affected_sop_class_uid,
+            dataset: None,
+        })
     }
 }
 
 /// C-FIND服务
 pub struct CFindService {
-    // 这里应该包含数据库连接
+    /// 实例查询服务，未配置数据库时查询结果为空
+    query: Option<InstanceQueryService>,
 }
 
 impl CFindService {
     pub fn new() -> Self {
-        Self {}
+        Self { query: None }
+    }
+
+    /// 从数据库检索查询结果（不返回已拒绝的实例）
+    pub fn with_database(database: Arc<DatabasePool>) -> Self {
+        Self {
+            query: Some(InstanceQueryService::new(database)),
+        }
+    }
+
+    /// 按查询标识检索匹配项
+    async fn find(&self, dataset: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
+        let Some(query) = &self.query else {
+            return Ok(Vec::new());
+        };
+        let dataset =
+            dataset.ok_or_else(|| PacsError::Validation("C-FIND请求缺少查询标识".to_string()))?;
+        let identifier = query::decode_identifier(dataset)?;
+        query
+            .find(&identifier)
+            .await?
+            .iter()
+            .map(query::encode_identifier)
+            .collect()
+    }
+}
+
+impl Default for CFindService {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[async_trait]
 impl DicomService for CFindService {
     async fn handle_request(&self, request: DimseRequest) -> Result<DimseResponse> {
+        let mut responses = self.handle_request_all(request).await?;
+        Ok(responses.pop().expect("C-FIND至少返回终态响应"))
+    }
+
+    async fn handle_request_all(&self, request: DimseRequest) -> Result<Vec<DimseResponse>> {
         debug!("处理C-FIND请求");
 
-        // 简化实现：返回空结果
-        Ok(DimseResponse {
+        let response = |status, dataset| DimseResponse {
             command_field: CommandField::CFind,
             message_id_being_responded_to: request.message_id,
-            status: DimseStatus::Success,
+            status,
+            affected_sop_class_uid: request.affected_sop_class_uid.clone(),
+            dataset,
+        };
+
+        match self.find(request.dataset.as_deref()).await {
+            Ok(matches) => {
+                info!("C-FIND返回 {} 个匹配项", matches.len());
+                let mut responses: Vec<_> = matches
+                    .into_iter()
+                    .map(|identifier| response(DimseStatus::Pending, Some(identifier)))
+                    .collect();
+                responses.push(response(DimseStatus::Success, None));
+                Ok(responses)
+            }
+            Err(PacsError::DicomParseError(e)) | Err(PacsError::Validation(e)) => {
+                warn!("C-FIND查询标识无法处理: {}", e);
+                Ok(vec![response(DimseStatus::Failure(0xC000), None)]) // 无法处理
+            }
+            Err(e) => {
+                warn!("C-FIND检索失败: {}", e);
+                Ok(vec![response(DimseStatus::Failure(0xA700), None)]) // 资源不足
+            }
+        }
+    }
+}
+
+/// MPPS（Modality Performed Procedure Step）服务
+///
+/// N-CREATE时记录执行步骤关联的检查，N-SET将状态置为COMPLETED时
+/// 通知稳定性跟踪器，检查在宽限期后判定接收完成
+pub struct MppsService {
+    /// 检查稳定性跟踪器
+    tracker: Arc<StudyStabilityTracker>,
+    /// 执行步骤实例UID到关联检查UID的映射
+    step_studies: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl MppsService {
+    pub fn new(tracker: Arc<StudyStabilityTracker>) -> Self {
+        Self {
+            tracker,
+            step_studies: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 执行步骤状态（0040,0252）
+    fn step_status(dataset: &InMemDicomObject) -> Option<String> {
+        let status = dataset
+            .element(tags::PERFORMED_PROCEDURE_STEP_STATUS)
+            .ok()?
+            .to_str()
+            .ok()?;
+        Some(status.trim_end_matches(['\0', ' ']).to_uppercase())
+    }
+
+    /// 计划步骤属性序列（0040,0270）中的检查UID
+    fn study_uids(dataset: &InMemDicomObject) -> Vec<String> {
+        let Some(items) = dataset
+            .element(tags::SCHEDULED_STEP_ATTRIBUTES_SEQUENCE)
+            .ok()
+            .and_then(|e| e.items())
+        else {
+            return Vec::new();
+        };
+        let mut study_uids: Vec<String> = items
+            .iter()
+            .filter_map(|item| item.element(tags::STUDY_INSTANCE_UID).ok()?.to_str().ok())
+            .map(|uid| uid.trim_end_matches(['\0', ' ']).to_string())
+            .filter(|uid| !uid.is_empty())
+            .collect();
+        study_uids.dedup();
+        study_uids
+    }
+
+    /// 处理N-CREATE/N-SET，返回响应状态
+    async fn process(&self, request: &DimseRequest) -> Result<DimseStatus> {
+        let step_uid = request
+            .affected_sop_instance_uid
+            .clone()
+            .ok_or_else(|| PacsError::Validation("MPPS请求缺少执行步骤实例UID".to_string()))?;
+        let dataset = request
+            .dataset
+            .as_deref()
+            .ok_or_else(|| PacsError::Validation("MPPS请求缺少数据集".to_string()))?;
+        let dataset = query::decode_identifier(dataset)?;
+
+        let mut study_uids = Self::study_uids(&dataset);
+        {
+            let mut step_studies = self.step_studies.lock().unwrap_or_else(|e| e.into_inner());
+            if request.command_field == CommandField::NCreate {
+                step_studies.insert(step_uid.clone(), study_uids.clone());
+            } else if study_uids.is_empty() {
+                // N-SET通常不再携带计划步骤属性，使用N-CREATE时记录的检查
+                study_uids = step_studies.get(&step_uid).cloned().unwrap_or_default();
+            }
+        }
+
+        match Self::step_status(&dataset).as_deref() {
+            Some("COMPLETED") => {
+                if study_uids.is_empty() {
+                    warn!("MPPS {} 已完成，但未找到关联的检查", step_uid);
+                }
+                for study_uid in &study_uids {
+                    self.tracker.mpps_completed(study_uid).await?;
+                }
+                self.forget(&step_uid);
+            }
+            Some("DISCONTINUED") => {
+                info!("MPPS {} 已中止", step_uid);
+                self.forget(&step_uid);
+            }
+            _ => debug!("MPPS {} 进行中，关联检查: {:?}", step_uid, study_uids),
+        }
+        Ok(DimseStatus::Success)
+    }
+
+    /// 执行步骤结束后不再跟踪
+    fn forget(&self, step_uid: &str) {
+        self.step_studies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(step_uid);
+    }
+}
+
+#[async_trait]
+impl DicomService for MppsService {
+    async fn handle_request(&self, request: DimseRequest) -> Result<DimseResponse> {
+        debug!("处理MPPS {:?}请求", request.command_field);
+
+        let status = match request.command_field {
+            CommandField::NCreate | CommandField::NSet => match self.process(&request).await {
+                Ok(status) => status,
+                Err(PacsError::DicomParseError(e)) | Err(PacsError::Validation(e)) => {
+                    warn!("MPPS请求无法处理: {}", e);
+                    DimseStatus::Failure(0x0106) // 属性值无效
+                }
+                Err(e) => {
+                    warn!("MPPS处理失败: {}", e);
+                    DimseStatus::Failure(0x0110) // 处理失败
+                }
+            },
+            _ => DimseStatus::Failure(0x0211), // 不支持的操作
+        };
+
+        Ok(DimseResponse {
+            command_field: request.command_field,
+            message_id_being_responded_to: request.message_id,
+            status,
             affected_sop_class_uid: request.affected_sop_class_uid,
             dataset: None,
         })
@@ -150,8 +410,11 @@ impl DicomService for CFindService {
 }
 
 /// DICOM服务管理器
+#[derive(Clone)]
 pub struct ServiceManager {
-    services: HashMap<String, Box<dyn DicomService>>,
+    services: HashMap<String, Arc<dyn DicomService>>,
+    /// 存储服务，处理未单独注册的存储类SOP的C-STORE请求
+    storage_service: Option<Arc<dyn DicomService>>,
 }
 
 impl ServiceManager {
@@ -161,31 +424,59 @@ impl ServiceManager {
         // 注册标准服务
         services.insert(
             "1.2.840.10008.1.1".to_string(), // Verification SOP Class
-            Box::new(CEchoService) as Box<dyn DicomService>,
+            Arc::new(CEchoService) as Arc<dyn DicomService>,
         );
 
-        Self { services }
+        Self {
+            services,
+            storage_service: None,
+        }
     }
 
     pub fn register_service(&mut self, sop_class_uid: String, service: Box<dyn DicomService>) {
-        self.services.insert(sop_class_uid, service);
+        self.services.insert(sop_class_uid, Arc::from(service));
+    }
+
+    /// 注册存储服务
+    pub fn register_storage_service(&mut self, service: Arc<dyn DicomService>) {
+        self.storage_service = Some(service);
     }
 
     pub async fn handle_request(&self, request: DimseRequest) -> Result<DimseResponse> {
-        match self.services.get(&request.affected_sop_class_uid) {
+        match self.service_for(&request) {
             Some(service) => service.handle_request(request).await,
-            None => {
-                warn!("不支持的SOP类: {}", request.affected_sop_class_uid);
-                Ok(DimseResponse {
-                    command_field: request.command_field,
-                    message_id_being_responded_to: request.message_id,
-                    status: DimseStatus::Failure(0x0122), // SOP类不支持
-                    affected_sop_class_uid: request.affected_sop_class_uid,
-                    dataset: None,
-                })
-            }
+            None => Ok(unsupported_sop_class(request)),
+        }
+    }
+
+    /// 处理请求并返回全部响应（含C-FIND的Pending响应）
+    pub async fn handle_request_all(&self, request: DimseRequest) -> Result<Vec<DimseResponse>> {
+        match self.service_for(&request) {
+            Some(service) => service.handle_request_all(request).await,
+            None => Ok(vec![unsupported_sop_class(request)]),
         }
     }
+
+    /// 查找处理请求的服务
+    fn service_for(&self, request: &DimseRequest) -> Option<&Arc<dyn DicomService>> {
+        match self.services.get(&request.affected_sop_class_uid) {
+            Some(service) => Some(service),
+            None if request.command_field == CommandField::CStore => self.storage_service.as_ref(),
+            None => None,
+        }
+    }
+}
+
+/// 不支持的SOP类响应
+fn unsupported_sop_class(request: DimseRequest) -> DimseResponse {
+    warn!("不支持的SOP类: {}", request.affected_sop_class_uid);
+    DimseResponse {
+        command_field: request.command_field,
+        message_id_being_responded_to: request.message_id,
+        status: DimseStatus::Failure(0x0122), // SOP类不支持
+        affected_sop_class_uid: request.affected_sop_class_uid,
+        dataset: None,
+    }
 }
 
 impl Default for ServiceManager {
@@ -193,3 +484,225 @@ impl Default for ServiceManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::{IngestListener, IngestedInstance};
+    use dicom::core::{DataElement, VR};
+    use dicom::dictionary_std::{tags, uids};
+    use dicom::object::{FileMetaTableBuilder, InMemDicomObject};
+    use std::sync::Mutex;
+
+    /// 记录入库来源的监听器
+    #[derive(Default)]
+    struct SourceRecorder {
+        sources: Mutex<Vec<IngestSource>>,
+    }
+
+    #[async_trait]
+    impl IngestListener for SourceRecorder {
+        async fn on_instance_stored(
+            &self,
+            _instance: &IngestedInstance,
+            source: &IngestSource,
+        ) -> Result<()> {
+            self.sources.lock().unwrap().push(source.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cstore_passes_calling_ae_title() {
+        let dir = std::env::temp_dir().join(format!("pacs-cstore-{}", uuid::Uuid::new_v4()));
+        let recorder = Arc::new(SourceRecorder::default());
+        let pipeline = Arc::new(IngestPipeline::new(&dir).with_listener(recorder.clone()));
+        let service = CStoreService::with_pipeline(pipeline);
+
+        let obj = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE),
+            DataElement::new(
+                tags::SOP_INSTANCE_UID,
+                VR::UI,
+                "1.2.826.0.1.3680043.9.7382.5.1.1",
+            ),
+            DataElement::new(tags::PATIENT_ID, VR::LO, "PAT001"),
+            DataElement::new(
+                tags::STUDY_INSTANCE_UID,
+                VR::UI,
+                "1.2.826.0.1.3680043.9.7382.5",
+            ),
+            DataElement::new(
+                tags::SERIES_INSTANCE_UID,
+                VR::UI,
+                "1.2.826.0.1.3680043.9.7382.5.1",
+            ),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+        .unwrap();
+        let mut dataset = Vec::new();
+        obj.write_all(&mut dataset).unwrap();
+
+        let response = service
+            .handle_request(DimseRequest {
+                command_field: CommandField::CStore,
+                message_id: 1,
+                affected_sop_class_uid: uids::CT_IMAGE_STORAGE.to_string(),
+                dataset: Some(dataset),
+                dataset_file: None,
+                affected_sop_instance_uid: None,
+                transfer_syntax_uid: None,
+                calling_ae_title: Some("CT_SCANNER1".to_string()),
+            })
+            .await
+            .unwrap();
+        assert!(matches!(response.status, DimseStatus::Success));
+
+        let sources = recorder.sources.lock().unwrap();
+        assert!(matches!(
+            sources.as_slice(),
+            [IngestSource::CStore { calling_ae_title: Some(ae) }] if ae == "CT_SCANNER1"
+        ));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_cstore_streams_bare_dataset_file() {
+        let dir = std::env::temp_dir().join(format!("pacs-cstore-{}", uuid::Uuid::new_v4()));
+        let pipeline = Arc::new(IngestPipeline::new(&dir));
+        let service = CStoreService::with_pipeline(pipeline.clone());
+
+        let study_uid = "1.2.826.0.1.3680043.9.7382.6";
+        let series_uid = "1.2.826.0.1.3680043.9.7382.6.1";
+        let instance_uid = "1.2.826.0.1.3680043.9.7382.6.1.1";
+        let obj = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, instance_uid),
+            DataElement::new(tags::PATIENT_ID, VR::LO, "PAT002"),
+            DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, study_uid),
+            DataElement::new(tags::SERIES_INSTANCE_UID, VR::UI, series_uid),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::IMPLICIT_VR_LITTLE_ENDIAN))
+        .unwrap();
+        // 关联层落盘的是不带文件元信息的裸数据集
+        std::fs::create_dir_all(&dir).unwrap();
+        let dataset_file = dir.join("received.dataset");
+        obj.write_dataset(std::fs::File::create(&dataset_file).unwrap())
+            .unwrap();
+
+        let response = service
+            .handle_request(DimseRequest {
+                command_field: CommandField::CStore,
+                message_id: 1,
+                affected_sop_class_uid: uids::CT_IMAGE_STORAGE.to_string(),
+                dataset: None,
+                dataset_file: Some(dataset_file),
+                affected_sop_instance_uid: Some(instance_uid.to_string()),
+                transfer_syntax_uid: Some(uids::IMPLICIT_VR_LITTLE_ENDIAN.to_string()),
+                calling_ae_title: None,
+            })
+            .await
+            .unwrap();
+        assert!(matches!(response.status, DimseStatus::Success));
+
+        let stored = pipeline
+            .instance_path(study_uid, series_uid, instance_uid)
+            .await
+            .unwrap();
+        let stored = dicom::object::open_file(stored).unwrap();
+        assert_eq!(stored.meta().media_storage_sop_instance_uid(), instance_uid);
+        assert_eq!(
+            stored.element(tags::PATIENT_ID).unwrap().to_str().unwrap(),
+            "PAT002"
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// N-SET将执行步骤置为COMPLETED时记录检查的MPPS完成时间（需设置PACS_TEST_DATABASE_URL）
+    #[tokio::test]
+    async fn test_mpps_completion_reaches_stability_tracker() {
+        use crate::stability::{StabilityConfig, StudyStabilityTracker};
+        use dicom::core::value::DataSetSequence;
+        use pacs_database::{DatabaseQueries, NewStudyArrival};
+
+        let Ok(database_url) = std::env::var("PACS_TEST_DATABASE_URL") else {
+            return;
+        };
+        let db = Arc::new(DatabasePool::new(&database_url, 2).await.unwrap());
+        let queries = DatabaseQueries::new(&db);
+        queries.create_tables().await.unwrap();
+
+        let study_uid = format!("2.25.{}", uuid::Uuid::new_v4().as_u128());
+        let step_uid = format!("2.25.{}", uuid::Uuid::new_v4().as_u128());
+        queries
+            .record_study_arrival(&NewStudyArrival {
+                study_uid: study_uid.clone(),
+                patient_id: Some("PAT003".to_string()),
+                accession_number: None,
+                modality: Some("CT".to_string()),
+            })
+            .await
+            .unwrap();
+
+        let tracker = Arc::new(StudyStabilityTracker::new(
+            db.clone(),
+            StabilityConfig::default(),
+        ));
+        let service = MppsService::new(tracker);
+        let request = |command_field, dataset: InMemDicomObject| DimseRequest {
+            command_field,
+            message_id: 1,
+            affected_sop_class_uid: uids::MODALITY_PERFORMED_PROCEDURE_STEP.to_string(),
+            dataset: Some(query::encode_identifier(&dataset).unwrap()),
+            dataset_file: None,
+            affected_sop_instance_uid: Some(step_uid.clone()),
+            transfer_syntax_uid: None,
+            calling_ae_title: Some("CT_SCANNER1".to_string()),
+        };
+        let mpps_completed_at = || async {
+            queries
+                .get_unstable_studies()
+                .await
+                .unwrap()
+                .into_iter()
+                .find(|s| s.study_uid == study_uid)
+                .unwrap()
+                .mpps_completed_at
+        };
+
+        let scheduled_step = InMemDicomObject::from_element_iter([DataElement::new(
+            tags::STUDY_INSTANCE_UID,
+            VR::UI,
+            study_uid.as_str(),
+        )]);
+        let create = InMemDicomObject::from_element_iter([
+            DataElement::new(
+                tags::SCHEDULED_STEP_ATTRIBUTES_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![scheduled_step]),
+            ),
+            DataElement::new(tags::PERFORMED_PROCEDURE_STEP_STATUS, VR::CS, "IN PROGRESS"),
+        ]);
+        let response = service
+            .handle_request(request(CommandField::NCreate, create))
+            .await
+            .unwrap();
+        assert!(matches!(response.status, DimseStatus::Success));
+        assert!(mpps_completed_at().await.is_none());
+
+        // N-SET只携带状态，检查UID取自N-CREATE
+        let set = InMemDicomObject::from_element_iter([DataElement::new(
+            tags::PERFORMED_PROCEDURE_STEP_STATUS,
+            VR::CS,
+            "COMPLETED",
+        )]);
+        let response = service
+            .handle_request(request(CommandField::NSet, set))
+            .await
+            .unwrap();
+        assert!(matches!(response.status, DimseStatus::Success));
+        assert!(mpps_completed_at().await.is_some());
+    }
+}
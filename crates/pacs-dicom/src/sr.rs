@@ -0,0 +1,961 @@
+//! DICOM结构化报告（SR）解析与渲染
+//!
+//! 将SR对象的内容树解析为类型化的Rust结构，支持渲染为HTML/纯文本，
+//! 并从X射线辐射剂量SR（TID 10011 / TID 10001）中提取CTDIvol、DLP等剂量值，
+//! 入库时写入剂量表。
+
+use crate::ingest::{IngestListener, IngestSource, IngestedInstance};
+use async_trait::async_trait;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use pacs_core::{PacsError, Result};
+use pacs_database::{DatabasePool, DatabaseQueries, NewRadiationDose};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// SR存储类SOP Class UID公共前缀
+const SR_SOP_CLASS_PREFIX: &str = "1.2.840.10008.5.1.4.1.1.88.";
+
+/// DCM编码方案中的剂量相关概念代码
+pub mod dose_codes {
+    /// X-Ray Radiation Dose Report（报告根节点）
+    pub const XRAY_RADIATION_DOSE_REPORT: &str = "113701";
+    /// CT Acquisition（CT照射事件）
+    pub const CT_ACQUISITION: &str = "113819";
+    /// Irradiation Event X-Ray Data（投照照射事件）
+    pub const IRRADIATION_EVENT_XRAY_DATA: &str = "113706";
+    /// Irradiation Event UID
+    pub const IRRADIATION_EVENT_UID: &str = "113769";
+    /// Acquisition Protocol
+    pub const ACQUISITION_PROTOCOL: &str = "125203";
+    /// Mean CTDIvol
+    pub const MEAN_CTDI_VOL: &str = "113830";
+    /// DLP
+    pub const DLP: &str = "113838";
+    /// CT Dose Length Product Total
+    pub const CT_DLP_TOTAL: &str = "113813";
+    /// Dose Area Product
+    pub const DOSE_AREA_PRODUCT: &str = "122130";
+    /// Dose Area Product Total
+    pub const DOSE_AREA_PRODUCT_TOTAL: &str = "113722";
+    /// Dose (RP) Total
+    pub const DOSE_RP_TOTAL: &str = "113725";
+}
+
+/// 编码概念（Code Value / Coding Scheme Designator / Code Meaning）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodedConcept {
+    /// 代码值
+    pub code_value: String,
+    /// 编码方案
+    pub coding_scheme_designator: String,
+    /// 代码含义
+    pub code_meaning: String,
+}
+
+impl CodedConcept {
+    /// 是否为指定编码方案中的指定代码
+    pub fn is(&self, scheme: &str, code_value: &str) -> bool {
+        self.coding_scheme_designator == scheme && self.code_value == code_value
+    }
+}
+
+/// 内容项之间的关系类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SrRelationship {
+    /// CONTAINS
+    Contains,
+    /// HAS PROPERTIES
+    HasProperties,
+    /// HAS OBS CONTEXT
+    HasObsContext,
+    /// HAS ACQ CONTEXT
+    HasAcqContext,
+    /// HAS CONCEPT MOD
+    HasConceptMod,
+    /// INFERRED FROM
+    InferredFrom,
+    /// SELECTED FROM
+    SelectedFrom,
+    /// 其他关系类型
+    Other(String),
+}
+
+impl From<&str> for SrRelationship {
+    fn from(value: &str) -> Self {
+        match value.trim() {
+            "CONTAINS" => Self::Contains,
+            "HAS PROPERTIES" => Self::HasProperties,
+            "HAS OBS CONTEXT" => Self::HasObsContext,
+            "HAS ACQ CONTEXT" => Self::HasAcqContext,
+            "HAS CONCEPT MOD" => Self::HasConceptMod,
+            "INFERRED FROM" => Self::InferredFrom,
+            "SELECTED FROM" => Self::SelectedFrom,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SrRelationship {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::Contains => "CONTAINS",
+            Self::HasProperties => "HAS PROPERTIES",
+            Self::HasObsContext => "HAS OBS CONTEXT",
+            Self::HasAcqContext => "HAS ACQ CONTEXT",
+            Self::HasConceptMod => "HAS CONCEPT MOD",
+            Self::InferredFrom => "INFERRED FROM",
+            Self::SelectedFrom => "SELECTED FROM",
+            Self::Other(other) => other,
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// 内容项的值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SrValue {
+    /// 容器
+    Container {
+        /// 内容连续性（SEPARATE/CONTINUOUS）
+        continuity: Option<String>,
+    },
+    /// 文本
+    Text(String),
+    /// 数值
+    Num {
+        /// 数值
+        value: f64,
+        /// 计量单位
+        unit: Option<CodedConcept>,
+    },
+    /// 编码值
+    Code(CodedConcept),
+    /// 图像引用
+    Image {
+        /// 引用的SOP Class UID
+        referenced_sop_class_uid: String,
+        /// 引用的SOP Instance UID
+        referenced_sop_instance_uid: String,
+        /// 引用的帧号
+        frame_numbers: Vec<i32>,
+    },
+    /// UID引用
+    UidRef(String),
+    /// 其他值类型（DATE、TIME、PNAME、SCOORD等），保留原始文本
+    Other {
+        /// 值类型
+        value_type: String,
+        /// 文本形式的值
+        value: Option<String>,
+    },
+}
+
+impl SrValue {
+    /// 值类型名称
+    pub fn value_type(&self) -> &str {
+        match self {
+            Self::Container { .. } => "CONTAINER",
+            Self::Text(_) => "TEXT",
+            Self::Num { .. } => "NUM",
+            Self::Code(_) => "CODE",
+            Self::Image { .. } => "IMAGE",
+            Self::UidRef(_) => "UIDREF",
+            Self::Other { value_type, .. } => value_type,
+        }
+    }
+
+    /// 值的显示文本，容器返回None
+    pub fn display_text(&self) -> Option<String> {
+        match self {
+            Self::Container { .. } => None,
+            Self::Text(text) | Self::UidRef(text) => Some(text.clone()),
+            Self::Num { value, unit } => Some(match unit {
+                Some(unit) => format!("{} {}", value, unit.code_value),
+                None => value.to_string(),
+            }),
+            Self::Code(code) => Some(code.code_meaning.clone()),
+            Self::Image {
+                referenced_sop_instance_uid,
+                ..
+            } => Some(format!("图像 {}", referenced_sop_instance_uid)),
+            Self::Other { value, .. } => value.clone(),
+        }
+    }
+}
+
+/// SR内容项（内容树节点）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SrContentItem {
+    /// 与父节点的关系，根节点为None
+    pub relationship: Option<SrRelationship>,
+    /// 概念名称
+    pub concept_name: Option<CodedConcept>,
+    /// 值
+    pub value: SrValue,
+    /// 子节点
+    pub children: Vec<SrContentItem>,
+}
+
+impl SrContentItem {
+    /// 概念名称是否为DCM编码方案中的指定代码
+    pub fn has_concept(&self, code_value: &str) -> bool {
+        self.concept_name
+            .as_ref()
+            .is_some_and(|c| c.is("DCM", code_value))
+    }
+
+    /// 在子树中（不含自身）按深度优先顺序查找第一个匹配概念的节点
+    pub fn find_descendant(&self, code_value: &str) -> Option<&SrContentItem> {
+        for child in &self.children {
+            if child.has_concept(code_value) {
+                return Some(child);
+            }
+            if let Some(found) = child.find_descendant(code_value) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// 收集子树中（不含自身）所有匹配概念的节点
+    pub fn find_all_descendants<'a>(&'a self, code_value: &str, out: &mut Vec<&'a SrContentItem>) {
+        for child in &self.children {
+            if child.has_concept(code_value) {
+                out.push(child);
+            }
+            child.find_all_descendants(code_value, out);
+        }
+    }
+
+    /// 获取数值型节点的值
+    pub fn numeric_value(&self) -> Option<f64> {
+        match &self.value {
+            SrValue::Num { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// 获取文本/UID/编码型节点的文本值
+    pub fn text_value(&self) -> Option<String> {
+        match &self.value {
+            SrValue::Container { .. } | SrValue::Num { .. } | SrValue::Image { .. } => None,
+            other => other.display_text(),
+        }
+    }
+}
+
+/// 解析后的结构化报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructuredReport {
+    /// SOP实例UID
+    pub sop_instance_uid: String,
+    /// SOP类UID
+    pub sop_class_uid: String,
+    /// 检查实例UID
+    pub study_instance_uid: Option<String>,
+    /// 患者ID
+    pub patient_id: Option<String>,
+    /// 完成标志（PARTIAL/COMPLETE）
+    pub completion_flag: Option<String>,
+    /// 验证标志（UNVERIFIED/VERIFIED）
+    pub verification_flag: Option<String>,
+    /// 内容树根节点
+    pub root: SrContentItem,
+}
+
+impl StructuredReport {
+    /// 报告标题（根节点概念名称）
+    pub fn title(&self) -> String {
+        self.root
+            .concept_name
+            .as_ref()
+            .map(|c| c.code_meaning.clone())
+            .unwrap_or_else(|| "结构化报告".to_string())
+    }
+
+    /// 渲染为纯文本
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&self.title());
+        out.push('\n');
+        for child in &self.root.children {
+            Self::render_text_item(child, 1, &mut out);
+        }
+        out
+    }
+
+    /// 渲染为HTML片段
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<div class=\"sr-report\">");
+        out.push_str(&format!("<h1>{}</h1>", escape_html(&self.title())));
+        if let Some(flag) = &self.verification_flag {
+            out.push_str(&format!(
+                "<p class=\"sr-verification\">{}</p>",
+                escape_html(flag)
+            ));
+        }
+        Self::render_html_children(&self.root.children, &mut out);
+        out.push_str("</div>");
+        out
+    }
+
+    fn render_text_item(item: &SrContentItem, depth: usize, out: &mut String) {
+        let name = item
+            .concept_name
+            .as_ref()
+            .map(|c| c.code_meaning.as_str())
+            .unwrap_or("");
+        out.push_str(&"  ".repeat(depth));
+        match item.value.display_text() {
+            Some(value) if name.is_empty() => out.push_str(&value),
+            Some(value) => out.push_str(&format!("{}: {}", name, value)),
+            None => out.push_str(name),
+        }
+        out.push('\n');
+        for child in &item.children {
+            Self::render_text_item(child, depth + 1, out);
+        }
+    }
+
+    fn render_html_children(children: &[SrContentItem], out: &mut String) {
+        if children.is_empty() {
+            return;
+        }
+        out.push_str("<ul>");
+        for child in children {
+            let name = child
+                .concept_name
+                .as_ref()
+                .map(|c| escape_html(&c.code_meaning))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "<li class=\"sr-{}\">",
+                escape_html(&child.value.value_type().to_lowercase())
+            ));
+            match child.value.display_text() {
+                Some(value) => out.push_str(&format!(
+                    "<span class=\"sr-name\">{}</span> <span class=\"sr-value\">{}</span>",
+                    name,
+                    escape_html(&value)
+                )),
+                None => out.push_str(&format!("<strong>{}</strong>", name)),
+            }
+            Self::render_html_children(&child.children, out);
+            out.push_str("</li>");
+        }
+        out.push_str("</ul>");
+    }
+}
+
+/// 单个照射事件的剂量值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IrradiationEventDose {
+    /// 照射事件UID
+    pub event_uid: Option<String>,
+    /// 采集协议
+    pub protocol: Option<String>,
+    /// 平均CTDIvol（mGy）
+    pub ctdi_vol: Option<f64>,
+    /// 剂量长度乘积DLP（mGy·cm）
+    pub dlp: Option<f64>,
+    /// 剂量面积乘积DAP/KAP（Gy·m²）
+    pub dose_area_product: Option<f64>,
+}
+
+/// 从辐射剂量SR中提取的剂量汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RadiationDoseSummary {
+    /// 剂量报告的SOP实例UID
+    pub sop_instance_uid: String,
+    /// 检查实例UID
+    pub study_instance_uid: Option<String>,
+    /// 患者ID
+    pub patient_id: Option<String>,
+    /// 照射事件列表
+    pub events: Vec<IrradiationEventDose>,
+    /// CT总DLP（mGy·cm）
+    pub dlp_total: Option<f64>,
+    /// 总剂量面积乘积（Gy·m²）
+    pub dose_area_product_total: Option<f64>,
+    /// 参考点总剂量（Gy），即入射剂量
+    pub dose_rp_total: Option<f64>,
+}
+
+impl RadiationDoseSummary {
+    /// 从结构化报告中提取剂量信息，非辐射剂量报告返回None
+    pub fn from_report(report: &StructuredReport) -> Option<Self> {
+        let root = &report.root;
+        if !root.has_concept(dose_codes::XRAY_RADIATION_DOSE_REPORT) {
+            debug!("非辐射剂量报告: {}", report.sop_instance_uid);
+            return None;
+        }
+
+        let mut event_nodes = Vec::new();
+        root.find_all_descendants(dose_codes::CT_ACQUISITION, &mut event_nodes);
+        root.find_all_descendants(dose_codes::IRRADIATION_EVENT_XRAY_DATA, &mut event_nodes);
+
+        let events: Vec<IrradiationEventDose> = event_nodes
+            .into_iter()
+            .map(|node| IrradiationEventDose {
+                event_uid: node
+                    .find_descendant(dose_codes::IRRADIATION_EVENT_UID)
+                    .and_then(|n| n.text_value()),
+                protocol: node
+                    .find_descendant(dose_codes::ACQUISITION_PROTOCOL)
+                    .and_then(|n| n.text_value()),
+                ctdi_vol: node
+                    .find_descendant(dose_codes::MEAN_CTDI_VOL)
+                    .and_then(|n| n.numeric_value()),
+                dlp: node
+                    .find_descendant(dose_codes::DLP)
+                    .and_then(|n| n.numeric_value()),
+                dose_area_product: node
+                    .find_descendant(dose_codes::DOSE_AREA_PRODUCT)
+                    .and_then(|n| n.numeric_value()),
+            })
+            .collect();
+
+        // 累计值缺失时由各照射事件求和
+        let dlp_total = root
+            .find_descendant(dose_codes::CT_DLP_TOTAL)
+            .and_then(|n| n.numeric_value())
+            .or_else(|| sum_present(events.iter().map(|e| e.dlp)));
+        let dose_area_product_total = root
+            .find_descendant(dose_codes::DOSE_AREA_PRODUCT_TOTAL)
+            .and_then(|n| n.numeric_value())
+            .or_else(|| sum_present(events.iter().map(|e| e.dose_area_product)));
+        let dose_rp_total = root
+            .find_descendant(dose_codes::DOSE_RP_TOTAL)
+            .and_then(|n| n.numeric_value());
+
+        info!(
+            "提取辐射剂量: {} ({} 个照射事件, DLP {:?})",
+            report.sop_instance_uid,
+            events.len(),
+            dlp_total
+        );
+
+        Some(Self {
+            sop_instance_uid: report.sop_instance_uid.clone(),
+            study_instance_uid: report.study_instance_uid.clone(),
+            patient_id: report.patient_id.clone(),
+            events,
+            dlp_total,
+            dose_area_product_total,
+            dose_rp_total,
+        })
+    }
+
+    /// 各照射事件中最大的CTDIvol
+    pub fn max_ctdi_vol(&self) -> Option<f64> {
+        self.events
+            .iter()
+            .filter_map(|e| e.ctdi_vol)
+            .fold(None, |max, v| Some(max.map_or(v, |m: f64| m.max(v))))
+    }
+
+    /// 转换为数据库剂量记录
+    pub fn to_new_radiation_dose(&self, modality: Option<String>) -> NewRadiationDose {
+        NewRadiationDose {
+            id: Uuid::new_v4(),
+            study_uid: self.study_instance_uid.clone().unwrap_or_default(),
+            sop_instance_uid: self.sop_instance_uid.clone(),
+            patient_id: self.patient_id.clone(),
+            modality,
+            source: "RDSR".to_string(),
+            protocol: self.events.iter().find_map(|e| e.protocol.clone()),
+            irradiation_events: self.events.len() as i32,
+            ctdi_vol: self.max_ctdi_vol(),
+            dlp: self.dlp_total,
+            dose_area_product: self.dose_area_product_total,
+            entrance_dose: self.dose_rp_total,
+        }
+    }
+}
+
+/// SR解析器
+pub struct SrParser;
+
+impl SrParser {
+    /// 判断SOP Class是否为结构化报告
+    pub fn is_structured_report(sop_class_uid: &str) -> bool {
+        sop_class_uid
+            .trim_end_matches('\0')
+            .starts_with(SR_SOP_CLASS_PREFIX)
+    }
+
+    /// 从DICOM字节流解析结构化报告
+    pub fn parse_bytes(data: &[u8]) -> Result<StructuredReport> {
+        let obj = crate::parser::DicomParser::read_object(data)?;
+        Self::parse(&obj)
+    }
+
+    /// 解析结构化报告
+    pub fn parse(obj: &InMemDicomObject) -> Result<StructuredReport> {
+        let value_type = get_string(obj, tags::VALUE_TYPE);
+        if value_type.as_deref() != Some("CONTAINER") {
+            return Err(PacsError::DicomParseError(format!(
+                "SR根节点必须为CONTAINER，实际为: {:?}",
+                value_type
+            )));
+        }
+
+        let sop_instance_uid = get_string(obj, tags::SOP_INSTANCE_UID)
+            .ok_or_else(|| PacsError::DicomParseError("缺少SOP Instance UID".to_string()))?;
+        let sop_class_uid = get_string(obj, tags::SOP_CLASS_UID).unwrap_or_default();
+
+        let root = Self::parse_item(obj, None)?;
+        debug!("解析SR内容树完成: {}", sop_instance_uid);
+
+        Ok(StructuredReport {
+            sop_instance_uid,
+            sop_class_uid,
+            study_instance_uid: get_string(obj, tags::STUDY_INSTANCE_UID),
+            patient_id: get_string(obj, tags::PATIENT_ID),
+            completion_flag: get_string(obj, tags::COMPLETION_FLAG),
+            verification_flag: get_string(obj, tags::VERIFICATION_FLAG),
+            root,
+        })
+    }
+
+    /// 递归解析内容项
+    fn parse_item(
+        item: &InMemDicomObject,
+        relationship: Option<SrRelationship>,
+    ) -> Result<SrContentItem> {
+        let value_type = get_string(item, tags::VALUE_TYPE)
+            .ok_or_else(|| PacsError::DicomParseError("SR内容项缺少Value Type".to_string()))?;
+        let concept_name = first_item(item, tags::CONCEPT_NAME_CODE_SEQUENCE).map(parse_code);
+
+        let value = match value_type.as_str() {
+            "CONTAINER" => SrValue::Container {
+                continuity: get_string(item, tags::CONTINUITY_OF_CONTENT),
+            },
+            "TEXT" => SrValue::Text(get_string(item, tags::TEXT_VALUE).unwrap_or_default()),
+            "NUM" => {
+                let measured = first_item(item, tags::MEASURED_VALUE_SEQUENCE);
+                let value = measured
+                    .and_then(|m| m.element(tags::NUMERIC_VALUE).ok())
+                    .and_then(|e| e.to_float64().ok());
+                match value {
+                    Some(value) => SrValue::Num {
+                        value,
+                        unit: measured
+                            .and_then(|m| first_item(m, tags::MEASUREMENT_UNITS_CODE_SEQUENCE))
+                            .map(parse_code),
+                    },
+                    // 无测量值（如带Numeric Value Qualifier的空值）
+                    None => SrValue::Other {
+                        value_type,
+                        value: None,
+                    },
+                }
+            }
+            "CODE" => match first_item(item, tags::CONCEPT_CODE_SEQUENCE) {
+                Some(code) => SrValue::Code(parse_code(code)),
+                None => SrValue::Other {
+                    value_type,
+                    value: None,
+                },
+            },
+            "IMAGE" | "COMPOSITE" => {
+                let reference = first_item(item, tags::REFERENCED_SOP_SEQUENCE);
+                SrValue::Image {
+                    referenced_sop_class_uid: reference
+                        .and_then(|r| get_string(r, tags::REFERENCED_SOP_CLASS_UID))
+                        .unwrap_or_default(),
+                    referenced_sop_instance_uid: reference
+                        .and_then(|r| get_string(r, tags::REFERENCED_SOP_INSTANCE_UID))
+                        .unwrap_or_default(),
+                    frame_numbers: reference
+                        .and_then(|r| r.element(tags::REFERENCED_FRAME_NUMBER).ok())
+                        .and_then(|e| e.to_multi_int::<i32>().ok())
+                        .unwrap_or_default(),
+                }
+            }
+            "UIDREF" => SrValue::UidRef(get_string(item, tags::UID).unwrap_or_default()),
+            "DATE" => SrValue::Other {
+                value: get_string(item, tags::DATE),
+                value_type,
+            },
+            "TIME" => SrValue::Other {
+                value: get_string(item, tags::TIME),
+                value_type,
+            },
+            "DATETIME" => SrValue::Other {
+                value: get_string(item, tags::DATE_TIME),
+                value_type,
+            },
+            "PNAME" => SrValue::Other {
+                value: get_string(item, tags::PERSON_NAME),
+                value_type,
+            },
+            _ => SrValue::Other {
+                value_type,
+                value: None,
+            },
+        };
+
+        let mut children = Vec::new();
+        if let Some(items) = item
+            .element(tags::CONTENT_SEQUENCE)
+            .ok()
+            .and_then(|e| e.items())
+        {
+            for child in items {
+                let relationship = get_string(child, tags::RELATIONSHIP_TYPE)
+                    .map(|r| SrRelationship::from(r.as_str()));
+                children.push(Self::parse_item(child, relationship)?);
+            }
+        }
+
+        Ok(SrContentItem {
+            relationship,
+            concept_name,
+            value,
+            children,
+        })
+    }
+}
+
+/// 解析编码序列项
+fn parse_code(item: &InMemDicomObject) -> CodedConcept {
+    CodedConcept {
+        code_value: get_string(item, tags::CODE_VALUE).unwrap_or_default(),
+        coding_scheme_designator: get_string(item, tags::CODING_SCHEME_DESIGNATOR)
+            .unwrap_or_default(),
+        code_meaning: get_string(item, tags::CODE_MEANING).unwrap_or_default(),
+    }
+}
+
+/// 获取序列的第一个项
+fn first_item(obj: &InMemDicomObject, tag: dicom::core::Tag) -> Option<&InMemDicomObject> {
+    obj.element(tag)
+        .ok()
+        .and_then(|e| e.items())
+        .and_then(|items| items.first())
+}
+
+/// 辐射剂量记录器
+///
+/// 作为入库监听器注册到入库管道，新入库的辐射剂量SR解析后写入剂量表
+pub struct RadiationDoseRecorder {
+    /// 数据库连接池
+    database: Arc<DatabasePool>,
+}
+
+impl RadiationDoseRecorder {
+    /// 创建剂量记录器
+    pub fn new(database: Arc<DatabasePool>) -> Self {
+        Self { database }
+    }
+
+    /// 从入库实例中提取剂量记录，非辐射剂量SR返回None
+    pub async fn extract(instance: &IngestedInstance) -> Result<Option<NewRadiationDose>> {
+        let is_sr = instance
+            .metadata
+            .sop_class_uid
+            .as_deref()
+            .is_some_and(SrParser::is_structured_report);
+        if !is_sr {
+            return Ok(None);
+        }
+
+        let data = tokio::fs::read(&instance.file_path).await?;
+        let report = SrParser::parse_bytes(&data)?;
+        Ok(RadiationDoseSummary::from_report(&report)
+            .map(|summary| summary.to_new_radiation_dose(instance.metadata.modality.clone())))
+    }
+}
+
+#[async_trait]
+impl IngestListener for RadiationDoseRecorder {
+    async fn on_instance_stored(
+        &self,
+        instance: &IngestedInstance,
+        _source: &IngestSource,
+    ) -> Result<()> {
+        let Some(dose) = Self::extract(instance).await? else {
+            return Ok(());
+        };
+
+        DatabaseQueries::new(&self.database)
+            .upsert_radiation_dose(&dose)
+            .await?;
+        info!(
+            "剂量记录已保存: {} (检查 {})",
+            dose.sop_instance_uid, dose.study_uid
+        );
+        Ok(())
+    }
+}
+
+/// 获取去除填充字符后的字符串值
+fn get_string(obj: &InMemDicomObject, tag: dicom::core::Tag) -> Option<String> {
+    obj.element(tag)
+        .ok()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// 对存在的值求和，全部缺失时返回None
+fn sum_present(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    values
+        .flatten()
+        .fold(None, |sum, v| Some(sum.unwrap_or(0.0) + v))
+}
+
+/// HTML转义
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::core::value::DataSetSequence;
+    use dicom::core::{DataElement, PrimitiveValue, VR};
+
+    fn code(value: &str, meaning: &str) -> InMemDicomObject {
+        InMemDicomObject::from_element_iter([
+            DataElement::new(tags::CODE_VALUE, VR::SH, PrimitiveValue::from(value)),
+            DataElement::new(
+                tags::CODING_SCHEME_DESIGNATOR,
+                VR::SH,
+                PrimitiveValue::from("DCM"),
+            ),
+            DataElement::new(tags::CODE_MEANING, VR::LO, PrimitiveValue::from(meaning)),
+        ])
+    }
+
+    fn item(
+        value_type: &str,
+        concept: (&str, &str),
+        mut elements: Vec<DataElement<InMemDicomObject>>,
+        children: Vec<InMemDicomObject>,
+    ) -> InMemDicomObject {
+        elements.push(DataElement::new(
+            tags::RELATIONSHIP_TYPE,
+            VR::CS,
+            PrimitiveValue::from("CONTAINS"),
+        ));
+        elements.push(DataElement::new(
+            tags::VALUE_TYPE,
+            VR::CS,
+            PrimitiveValue::from(value_type),
+        ));
+        elements.push(DataElement::new(
+            tags::CONCEPT_NAME_CODE_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(vec![code(concept.0, concept.1)]),
+        ));
+        if !children.is_empty() {
+            elements.push(DataElement::new(
+                tags::CONTENT_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(children),
+            ));
+        }
+        InMemDicomObject::from_element_iter(elements)
+    }
+
+    fn num(concept: (&str, &str), value: &str, unit: &str) -> InMemDicomObject {
+        let measured = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::NUMERIC_VALUE, VR::DS, PrimitiveValue::from(value)),
+            DataElement::new(
+                tags::MEASUREMENT_UNITS_CODE_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![code(unit, unit)]),
+            ),
+        ]);
+        item(
+            "NUM",
+            concept,
+            vec![DataElement::new(
+                tags::MEASURED_VALUE_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![measured]),
+            )],
+            vec![],
+        )
+    }
+
+    fn ct_dose_report() -> InMemDicomObject {
+        let acquisition = |uid: &str, ctdi: &str, dlp: &str| {
+            item(
+                "CONTAINER",
+                ("113819", "CT Acquisition"),
+                vec![],
+                vec![
+                    item(
+                        "TEXT",
+                        ("125203", "Acquisition Protocol"),
+                        vec![DataElement::new(
+                            tags::TEXT_VALUE,
+                            VR::UT,
+                            PrimitiveValue::from("Head <Routine>"),
+                        )],
+                        vec![],
+                    ),
+                    item(
+                        "UIDREF",
+                        ("113769", "Irradiation Event UID"),
+                        vec![DataElement::new(
+                            tags::UID,
+                            VR::UI,
+                            PrimitiveValue::from(uid),
+                        )],
+                        vec![],
+                    ),
+                    item(
+                        "CONTAINER",
+                        ("113829", "CT Dose"),
+                        vec![],
+                        vec![
+                            num(("113830", "Mean CTDIvol"), ctdi, "mGy"),
+                            num(("113838", "DLP"), dlp, "mGy.cm"),
+                        ],
+                    ),
+                ],
+            )
+        };
+
+        let mut root = item(
+            "CONTAINER",
+            ("113701", "X-Ray Radiation Dose Report"),
+            vec![
+                DataElement::new(
+                    tags::SOP_INSTANCE_UID,
+                    VR::UI,
+                    PrimitiveValue::from("1.2.3.9"),
+                ),
+                DataElement::new(
+                    tags::SOP_CLASS_UID,
+                    VR::UI,
+                    PrimitiveValue::from("1.2.840.10008.5.1.4.1.1.88.67"),
+                ),
+                DataElement::new(
+                    tags::STUDY_INSTANCE_UID,
+                    VR::UI,
+                    PrimitiveValue::from("1.2.3"),
+                ),
+            ],
+            vec![
+                acquisition("1.2.3.9.1", "42.5", "520.0"),
+                acquisition("1.2.3.9.2", "12.0", "300.0"),
+            ],
+        );
+        root.remove_element(tags::RELATIONSHIP_TYPE);
+        root
+    }
+
+    #[test]
+    fn test_parse_and_render_sr() {
+        let report = SrParser::parse(&ct_dose_report()).unwrap();
+        assert!(SrParser::is_structured_report(&report.sop_class_uid));
+        assert_eq!(report.root.relationship, None);
+        assert_eq!(report.root.children.len(), 2);
+
+        let acquisition = &report.root.children[0];
+        assert_eq!(acquisition.relationship, Some(SrRelationship::Contains));
+        let ctdi = acquisition
+            .find_descendant(dose_codes::MEAN_CTDI_VOL)
+            .unwrap();
+        assert_eq!(ctdi.numeric_value(), Some(42.5));
+
+        let text = report.to_text();
+        assert!(text.starts_with("X-Ray Radiation Dose Report\n"));
+        assert!(text.contains("      Mean CTDIvol: 42.5 mGy"));
+
+        let html = report.to_html();
+        assert!(html.contains("<h1>X-Ray Radiation Dose Report</h1>"));
+        assert!(html.contains("Head &lt;Routine&gt;"));
+    }
+
+    #[test]
+    fn test_extract_ct_dose() {
+        let report = SrParser::parse(&ct_dose_report()).unwrap();
+        let dose = RadiationDoseSummary::from_report(&report).unwrap();
+
+        assert_eq!(dose.events.len(), 2);
+        assert_eq!(dose.events[1].event_uid.as_deref(), Some("1.2.3.9.2"));
+        assert_eq!(dose.max_ctdi_vol(), Some(42.5));
+        // 缺少DLP总量时由照射事件求和
+        assert_eq!(dose.dlp_total, Some(820.0));
+
+        let record = dose.to_new_radiation_dose(Some("CT".to_string()));
+        assert_eq!(record.study_uid, "1.2.3");
+        assert_eq!(record.irradiation_events, 2);
+        assert_eq!(record.protocol.as_deref(), Some("Head <Routine>"));
+    }
+
+    #[test]
+    fn test_html_escapes_value_type_class() {
+        let mut report = SrParser::parse(&ct_dose_report()).unwrap();
+        report.root.children.push(SrContentItem {
+            relationship: Some(SrRelationship::Contains),
+            concept_name: None,
+            value: SrValue::Other {
+                value_type: "X\" onmouseover=\"alert(1)".to_string(),
+                value: Some("v".to_string()),
+            },
+            children: vec![],
+        });
+
+        let html = report.to_html();
+        assert!(!html.contains("\" onmouseover"));
+        assert!(html.contains("<li class=\"sr-x&quot; onmouseover=&quot;alert(1)\">"));
+    }
+
+    #[tokio::test]
+    async fn test_dose_recorder_extracts_rdsr() {
+        use crate::ingest::IngestPipeline;
+        use crate::parser::ParsedDicomObject;
+        use dicom::object::FileMetaTableBuilder;
+
+        let path = std::env::temp_dir().join(format!("pacs-rdsr-{}.dcm", Uuid::new_v4()));
+        let obj = ct_dose_report()
+            .with_meta(FileMetaTableBuilder::new().transfer_syntax("1.2.840.10008.1.2.1"))
+            .unwrap();
+        std::fs::write(&path, IngestPipeline::encode_object(&obj).unwrap()).unwrap();
+
+        let mut instance = IngestedInstance {
+            sop_instance_uid: "1.2.3.9".to_string(),
+            file_path: path.clone(),
+            file_size: 0,
+            original_patient_id: None,
+            metadata: ParsedDicomObject {
+                sop_class_uid: Some("1.2.840.10008.5.1.4.1.1.88.67".to_string()),
+                modality: Some("SR".to_string()),
+                ..ParsedDicomObject::new()
+            },
+            references: vec![],
+            document: None,
+        };
+        let dose = RadiationDoseRecorder::extract(&instance)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(dose.study_uid, "1.2.3");
+        assert_eq!(dose.dlp, Some(820.0));
+        assert_eq!(dose.modality.as_deref(), Some("SR"));
+
+        // 普通图像不读取文件
+        instance.metadata.sop_class_uid = Some("1.2.840.10008.5.1.4.1.1.2".to_string());
+        assert!(RadiationDoseRecorder::extract(&instance)
+            .await
+            .unwrap()
+            .is_none());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,462 @@
+//! 检查稳定性检测
+//!
+//! 跟踪每个检查最后一次收到实例的时间，按模态配置的静默期内没有新实例到达
+//! （或收到MPPS完成后经过短暂宽限期）即判定检查接收完毕。稳定状态与事件发送
+//! 状态持久化在数据库中，检查完成事件在服务重启后也只会成功发出一次。
+//! 监听器按通道登记投递结果，部分通道失败重发时只投递尚未成功的通道。
+
+use crate::ingest::{IngestListener, IngestSource, IngestedInstance};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use pacs_core::{Result, StudyStatus};
+use pacs_database::{DatabasePool, DatabaseQueries, DbStudyStability, NewStudyArrival};
+use pacs_workflow::{StudyEvent, StudyStateMachine};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// 稳定性检测配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StabilityConfig {
+    /// 默认静默期
+    pub default_quiet_period: Duration,
+    /// 按模态覆盖的静默期（多模态检查取最长值）
+    pub modality_quiet_periods: HashMap<String, Duration>,
+    /// 收到MPPS完成后的宽限期，用于等待传输中的最后几幅影像
+    pub mpps_grace_period: Duration,
+    /// 后台检测间隔
+    pub poll_interval: Duration,
+}
+
+impl Default for StabilityConfig {
+    fn default() -> Self {
+        Self {
+            default_quiet_period: Duration::from_secs(120),
+            modality_quiet_periods: HashMap::new(),
+            mpps_grace_period: Duration::from_secs(10),
+            poll_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl StabilityConfig {
+    /// 获取检查适用的静默期，未覆盖的模态按默认静默期参与取最长值
+    pub fn quiet_period_for(&self, modalities: &[String]) -> Duration {
+        modalities
+            .iter()
+            .map(|m| {
+                self.modality_quiet_periods
+                    .get(&m.to_uppercase())
+                    .copied()
+                    .unwrap_or(self.default_quiet_period)
+            })
+            .max()
+            .unwrap_or(self.default_quiet_period)
+    }
+}
+
+/// 检查完成的触发方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompletionTrigger {
+    /// 静默期内没有新实例到达
+    QuietPeriod,
+    /// 收到MPPS完成
+    Mpps,
+}
+
+impl CompletionTrigger {
+    /// 数据库存储值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompletionTrigger::QuietPeriod => "QUIET_PERIOD",
+            CompletionTrigger::Mpps => "MPPS",
+        }
+    }
+
+    /// 从数据库存储值解析
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "QUIET_PERIOD" => Some(CompletionTrigger::QuietPeriod),
+            "MPPS" => Some(CompletionTrigger::Mpps),
+            _ => None,
+        }
+    }
+}
+
+/// 检查完成事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StudyCompletedEvent {
+    /// 检查实例UID
+    pub study_instance_uid: String,
+    /// 患者ID
+    pub patient_id: Option<String>,
+    /// 检查号
+    pub accession_number: Option<String>,
+    /// 检查包含的模态
+    pub modalities: Vec<String>,
+    /// 已接收实例数
+    pub instance_count: i32,
+    /// 首个实例到达时间
+    pub first_received_at: Option<DateTime<Utc>>,
+    /// 最后实例到达时间
+    pub last_received_at: Option<DateTime<Utc>>,
+    /// 判定稳定的时间
+    pub completed_at: DateTime<Utc>,
+    /// 触发方式
+    pub trigger: CompletionTrigger,
+}
+
+impl StudyCompletedEvent {
+    /// 由稳定性记录构造事件，记录尚未稳定时返回None
+    fn from_record(record: DbStudyStability) -> Option<Self> {
+        let completed_at = record.stable_at?;
+        let trigger = record
+            .completion_trigger
+            .as_deref()
+            .and_then(CompletionTrigger::parse)
+            .unwrap_or(CompletionTrigger::QuietPeriod);
+
+        Some(Self {
+            study_instance_uid: record.study_uid,
+            patient_id: record.patient_id,
+            accession_number: record.accession_number,
+            modalities: record.modalities,
+            instance_count: record.instance_count,
+            first_received_at: record.first_received_at,
+            last_received_at: record.last_received_at,
+            completed_at,
+            trigger,
+        })
+    }
+
+    /// 事件唯一标识，重发时保持不变，供下游去重
+    pub fn event_id(&self) -> String {
+        format!("study.completed:{}", self.study_instance_uid)
+    }
+}
+
+/// 检查完成事件的逐通道投递记录
+///
+/// 通道由监听器定义（如某个Webhook订阅或消息交换器），已投递的通道在重发时跳过
+#[derive(Debug, Default)]
+pub struct EventDeliveries {
+    /// 已投递的通道
+    delivered: HashSet<String>,
+    /// 本轮新投递、尚未持久化的通道
+    pending: Vec<String>,
+}
+
+impl EventDeliveries {
+    /// 由已投递的通道创建
+    pub fn new(delivered: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            delivered: delivered.into_iter().collect(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// 通道是否已投递
+    pub fn is_delivered(&self, channel: &str) -> bool {
+        self.delivered.contains(channel)
+    }
+
+    /// 登记通道投递成功
+    pub fn mark_delivered(&mut self, channel: impl Into<String>) {
+        let channel = channel.into();
+        if self.delivered.insert(channel.clone()) {
+            self.pending.push(channel);
+        }
+    }
+
+    /// 取出本轮新投递的通道
+    fn take_pending(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// 检查完成事件监听器
+///
+/// 所有监听器都成功后事件才标记为已发出，否则在下一轮检测中重发。
+/// 监听器应通过 `deliveries` 登记已成功的通道并跳过已投递的通道，避免重复投递
+#[async_trait]
+pub trait StudyCompletedListener: Send + Sync {
+    /// 检查已接收完毕
+    async fn on_study_completed(
+        &self,
+        event: &StudyCompletedEvent,
+        deliveries: &mut EventDeliveries,
+    ) -> Result<()>;
+}
+
+/// 检查稳定性跟踪器
+pub struct StudyStabilityTracker {
+    /// 数据库连接池
+    db: Arc<DatabasePool>,
+    /// 检测配置
+    config: StabilityConfig,
+    /// 检查状态机
+    state_machine: StudyStateMachine,
+    /// 检查完成事件监听器
+    listeners: Vec<Arc<dyn StudyCompletedListener>>,
+}
+
+impl StudyStabilityTracker {
+    /// 创建新的稳定性跟踪器
+    pub fn new(db: Arc<DatabasePool>, config: StabilityConfig) -> Self {
+        Self {
+            db,
+            config,
+            state_machine: StudyStateMachine::new(),
+            listeners: Vec::new(),
+        }
+    }
+
+    /// 注册检查完成事件监听器
+    pub fn with_listener(mut self, listener: Arc<dyn StudyCompletedListener>) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// 获取检测配置
+    pub fn config(&self) -> &StabilityConfig {
+        &self.config
+    }
+
+    /// 记录MPPS完成，检查在宽限期后判定稳定
+    pub async fn mpps_completed(&self, study_uid: &str) -> Result<()> {
+        let updated = DatabaseQueries::new(&self.db)
+            .record_study_mpps_completed(study_uid)
+            .await?;
+        if updated {
+            info!("检查 {} 收到MPPS完成", study_uid);
+        } else {
+            debug!("检查 {} 已完成，忽略MPPS完成通知", study_uid);
+        }
+        Ok(())
+    }
+
+    /// 启动后台检测循环
+    pub fn start(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let tracker = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tracker.config.poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = tracker.check_once().await {
+                    error!("检查稳定性检测失败: {}", e);
+                }
+            }
+        })
+    }
+
+    /// 判定已稳定的检查并发出完成事件，返回本轮发出的事件数
+    ///
+    /// 上次未成功发出（包括服务重启前）的事件也在此重发
+    pub async fn check_once(&self) -> Result<usize> {
+        let queries = DatabaseQueries::new(&self.db);
+        let now = Utc::now();
+
+        for record in queries.get_unstable_studies().await? {
+            let Some(last_received_at) = record.last_received_at else {
+                continue;
+            };
+            let (trigger, quiet_period) = if record.mpps_completed_at.is_some() {
+                (CompletionTrigger::Mpps, self.config.mpps_grace_period)
+            } else {
+                (
+                    CompletionTrigger::QuietPeriod,
+                    self.config.quiet_period_for(&record.modalities),
+                )
+            };
+
+            let Some(cutoff) = chrono::Duration::from_std(quiet_period)
+                .ok()
+                .and_then(|period| now.checked_sub_signed(period))
+            else {
+                continue;
+            };
+            if last_received_at > cutoff {
+                continue;
+            }
+            if queries
+                .mark_study_stable(&record.study_uid, trigger.as_str(), cutoff)
+                .await?
+                .is_some()
+            {
+                info!(
+                    "检查 {} 已稳定（{}，{} 个实例）",
+                    record.study_uid,
+                    trigger.as_str(),
+                    record.instance_count
+                );
+            }
+        }
+
+        let mut notified = 0;
+        for record in queries.get_unnotified_stable_studies().await? {
+            let Some(event) = StudyCompletedEvent::from_record(record) else {
+                continue;
+            };
+            match self.complete_study(&event).await {
+                Ok(()) => {
+                    if queries
+                        .mark_study_completion_notified(&event.study_instance_uid)
+                        .await?
+                    {
+                        notified += 1;
+                    }
+                }
+                Err(e) => warn!(
+                    "检查 {} 完成事件发送失败，稍后重试: {}",
+                    event.study_instance_uid, e
+                ),
+            }
+        }
+
+        Ok(notified)
+    }
+
+    /// 推进检查状态并通知所有监听器
+    async fn complete_study(&self, event: &StudyCompletedEvent) -> Result<()> {
+        self.transition_study(&event.study_instance_uid).await?;
+
+        let queries = DatabaseQueries::new(&self.db);
+        let study_uid = &event.study_instance_uid;
+        let mut deliveries =
+            EventDeliveries::new(queries.get_study_completion_deliveries(study_uid).await?);
+        for listener in &self.listeners {
+            let result = listener.on_study_completed(event, &mut deliveries).await;
+            // 失败前已成功的通道同样登记，重发时不再投递
+            for channel in deliveries.take_pending() {
+                queries
+                    .record_study_completion_delivery(study_uid, &channel)
+                    .await?;
+            }
+            result?;
+        }
+        Ok(())
+    }
+
+    /// 通过状态机将检查推进到已完成状态
+    async fn transition_study(&self, study_uid: &str) -> Result<()> {
+        let queries = DatabaseQueries::new(&self.db);
+        let Some(study) = queries.get_study_by_uid(study_uid).await? else {
+            debug!("检查 {} 没有预约记录，跳过状态转换", study_uid);
+            return Ok(());
+        };
+
+        let mut status = study.status.clone();
+        if status == StudyStatus::Scheduled {
+            status = self
+                .state_machine
+                .transition(&status, &StudyEvent::Started)?;
+        }
+        if !self
+            .state_machine
+            .can_transition(&status, &StudyEvent::Completed)
+        {
+            debug!("检查 {} 当前状态为 {:?}，无需转换", study_uid, study.status);
+            return Ok(());
+        }
+
+        let status = self
+            .state_machine
+            .transition(&status, &StudyEvent::Completed)?;
+        queries.update_study_status(study_uid, &status).await?;
+        info!(
+            "检查 {} 状态: {:?} -> {:?}",
+            study_uid, study.status, status
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IngestListener for StudyStabilityTracker {
+    async fn on_instance_stored(
+        &self,
+        instance: &IngestedInstance,
+        _source: &IngestSource,
+    ) -> Result<()> {
+        let metadata = &instance.metadata;
+        let Some(study_uid) = metadata.study_instance_uid.clone() else {
+            return Ok(());
+        };
+
+        let record = DatabaseQueries::new(&self.db)
+            .record_study_arrival(&NewStudyArrival {
+                study_uid,
+                patient_id: metadata.patient_id.clone(),
+                accession_number: metadata.accession_number.clone(),
+                modality: metadata.modality.clone(),
+            })
+            .await?;
+
+        if record.stable_at.is_some() {
+            warn!(
+                "检查 {} 已判定完成后又收到实例 {}",
+                record.study_uid, instance.sop_instance_uid
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_deliveries_track_new_channels() {
+        let mut deliveries = EventDeliveries::new(["webhook:a".to_string()]);
+        assert!(deliveries.is_delivered("webhook:a"));
+        assert!(!deliveries.is_delivered("amqp:pacs.events"));
+
+        deliveries.mark_delivered("webhook:a");
+        deliveries.mark_delivered("amqp:pacs.events");
+        assert!(deliveries.is_delivered("amqp:pacs.events"));
+        assert_eq!(deliveries.take_pending(), vec!["amqp:pacs.events"]);
+        assert!(deliveries.take_pending().is_empty());
+    }
+
+    #[test]
+    fn test_quiet_period_for_modalities() {
+        let mut config = StabilityConfig::default();
+        config
+            .modality_quiet_periods
+            .insert("MR".to_string(), Duration::from_secs(300));
+        config
+            .modality_quiet_periods
+            .insert("CR".to_string(), Duration::from_secs(30));
+
+        assert_eq!(config.quiet_period_for(&[]), Duration::from_secs(120));
+        assert_eq!(
+            config.quiet_period_for(&["CR".to_string()]),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            config.quiet_period_for(&["cr".to_string(), "MR".to_string()]),
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            config.quiet_period_for(&["US".to_string()]),
+            Duration::from_secs(120)
+        );
+    }
+
+    #[test]
+    fn test_quiet_period_for_mixed_modalities() {
+        let mut config = StabilityConfig::default();
+        config
+            .modality_quiet_periods
+            .insert("CT".to_string(), Duration::from_secs(30));
+
+        // XA未覆盖，按默认静默期参与比较
+        assert_eq!(
+            config.quiet_period_for(&["CT".to_string(), "XA".to_string()]),
+            Duration::from_secs(120)
+        );
+    }
+}
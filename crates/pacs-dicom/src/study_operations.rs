@@ -0,0 +1,775 @@
+//! 检查拆分、合并与移动
+//!
+//! 纠正技师将影像发送到错误患者/检查的情况：在序列或检查级别改写患者与检查
+//! 属性，按IOCM要求以操作ID为命名空间确定性派生新的UID，重新入库改写后的实例，
+//! 并为原实例生成拒绝说明（Rejection Note KOS）。原文件移入操作暂存目录，
+//! 所有实例变更记录在审计表中，操作可以撤销。
+
+use crate::ingest::{IngestOutcome, IngestPipeline, IngestSource, StoredInstanceFile};
+use crate::parser::DicomParser;
+use crate::rejection::{
+    RejectedInstance, RejectionNoteBuilder, RejectionNoteRequest, RejectionReason,
+};
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom::object::{DefaultDicomObject, FileMetaTableBuilder, InMemDicomObject};
+use pacs_core::uid::UidGenerator;
+use pacs_core::{PacsError, Result};
+use pacs_database::{
+    DatabasePool, DatabaseQueries, DbStudyOperation, InstanceRelocation, NewStudyOperation,
+    NewStudyOperationItem,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// 暂存原实例文件的目录名（位于存储根目录下）
+const HOLD_DIR: &str = ".operations";
+
+/// 患者级属性
+const PATIENT_ATTRIBUTES: [(Tag, VR); 4] = [
+    (tags::PATIENT_ID, VR::LO),
+    (tags::PATIENT_NAME, VR::PN),
+    (tags::PATIENT_BIRTH_DATE, VR::DA),
+    (tags::PATIENT_SEX, VR::CS),
+];
+
+/// 检查级属性
+const STUDY_ATTRIBUTES: [(Tag, VR); 6] = [
+    (tags::ACCESSION_NUMBER, VR::SH),
+    (tags::STUDY_DATE, VR::DA),
+    (tags::STUDY_TIME, VR::TM),
+    (tags::STUDY_DESCRIPTION, VR::LO),
+    (tags::STUDY_ID, VR::SH),
+    (tags::REFERRING_PHYSICIAN_NAME, VR::PN),
+];
+
+/// 患者身份信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatientDemographics {
+    /// 患者ID
+    pub patient_id: String,
+    /// 患者姓名
+    pub patient_name: String,
+    /// 出生日期（YYYYMMDD）
+    pub birth_date: Option<String>,
+    /// 性别（M/F/O）
+    pub sex: Option<String>,
+}
+
+/// 检查管理操作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StudyOperation {
+    /// 将序列移动到另一个检查
+    MoveSeries {
+        series_instance_uid: String,
+        target_study_instance_uid: String,
+    },
+    /// 将检查移动到另一个患者
+    MoveStudy {
+        study_instance_uid: String,
+        target_patient: PatientDemographics,
+    },
+    /// 将检查中的部分序列拆分为新检查
+    SplitStudy {
+        study_instance_uid: String,
+        series_instance_uids: Vec<String>,
+        accession_number: Option<String>,
+    },
+    /// 将源检查的全部序列合并到目标检查
+    MergeStudies {
+        source_study_instance_uid: String,
+        target_study_instance_uid: String,
+    },
+}
+
+impl StudyOperation {
+    /// 审计记录中的操作类型
+    pub fn operation_type(&self) -> &'static str {
+        match self {
+            Self::MoveSeries { .. } => "MOVE_SERIES",
+            Self::MoveStudy { .. } => "MOVE_STUDY",
+            Self::SplitStudy { .. } => "SPLIT_STUDY",
+            Self::MergeStudies { .. } => "MERGE_STUDIES",
+        }
+    }
+}
+
+/// 检查操作结果
+#[derive(Debug, Clone, Serialize)]
+pub struct StudyOperationReport {
+    /// 操作ID
+    pub operation_id: Uuid,
+    /// 操作类型
+    pub operation_type: String,
+    /// 处理的实例数
+    pub instances: usize,
+    /// 实例现在所属的检查UID
+    pub study_instance_uids: Vec<String>,
+    /// 本次生成的拒绝说明SOP实例UID
+    pub rejection_note_uid: Option<String>,
+}
+
+/// 单个实例的改写计划
+#[derive(Debug, Clone)]
+struct InstanceRewrite {
+    /// 原文件路径
+    source_path: PathBuf,
+    /// 原检查UID
+    study_uid: String,
+    /// 原序列UID
+    series_uid: String,
+    /// 原SOP实例UID（从文件名得到）
+    sop_instance_uid: String,
+    /// 新检查UID
+    new_study_uid: String,
+    /// 新序列UID
+    new_series_uid: String,
+    /// 新SOP实例UID
+    new_sop_instance_uid: String,
+    /// 需要覆盖的患者/检查属性
+    overrides: Vec<(Tag, VR, String)>,
+}
+
+/// 检查操作服务
+pub struct StudyOperationService {
+    /// 数据库连接池
+    db: Arc<DatabasePool>,
+    /// 入库管道（改写后的实例与拒绝说明经由管道入库并通知监听器）
+    pipeline: Arc<IngestPipeline>,
+    /// UID生成器
+    uid_generator: UidGenerator,
+}
+
+impl StudyOperationService {
+    /// 创建新的检查操作服务
+    pub fn new(db: Arc<DatabasePool>, pipeline: Arc<IngestPipeline>) -> Self {
+        Self {
+            db,
+            pipeline,
+            uid_generator: pacs_core::uid::default_generator(),
+        }
+    }
+
+    /// 使用指定的UID生成器
+    pub fn with_uid_generator(mut self, uid_generator: UidGenerator) -> Self {
+        self.uid_generator = uid_generator;
+        self
+    }
+
+    /// 执行检查操作
+    pub async fn execute(
+        &self,
+        operation: &StudyOperation,
+        performed_by: &str,
+        reason: Option<&str>,
+    ) -> Result<StudyOperationReport> {
+        let operation_id = Uuid::new_v4();
+        let rewrites = self.plan(operation_id, operation).await?;
+        if rewrites.is_empty() {
+            return Err(PacsError::NotFound("操作未匹配到任何实例".to_string()));
+        }
+
+        let queries = DatabaseQueries::new(&self.db);
+        queries
+            .create_study_operation(&NewStudyOperation {
+                id: operation_id,
+                operation_type: operation.operation_type().to_string(),
+                parameters: serde_json::to_string(operation)
+                    .map_err(|e| PacsError::Internal(e.to_string()))?,
+                performed_by: performed_by.to_string(),
+                reason: reason.map(str::to_string),
+            })
+            .await?;
+        info!(
+            "{} 执行检查操作 {} ({}，{} 个实例)",
+            performed_by,
+            operation_id,
+            operation.operation_type(),
+            rewrites.len()
+        );
+
+        match self.apply(operation_id, &rewrites).await {
+            Ok(rejection_note) => {
+                let (note_uid, note_path) = rejection_note.unzip();
+                let note_path = note_path.map(|p: PathBuf| p.to_string_lossy().to_string());
+                queries
+                    .update_study_operation_status(
+                        &operation_id,
+                        "COMPLETED",
+                        note_uid.as_deref(),
+                        note_path.as_deref(),
+                        None,
+                    )
+                    .await?;
+
+                let mut study_instance_uids: Vec<String> =
+                    rewrites.iter().map(|r| r.new_study_uid.clone()).collect();
+                study_instance_uids.dedup();
+                Ok(StudyOperationReport {
+                    operation_id,
+                    operation_type: operation.operation_type().to_string(),
+                    instances: rewrites.len(),
+                    study_instance_uids,
+                    rejection_note_uid: note_uid,
+                })
+            }
+            Err(e) => {
+                warn!("检查操作 {} 失败，可撤销已处理的实例: {}", operation_id, e);
+                queries
+                    .update_study_operation_status(
+                        &operation_id,
+                        "FAILED",
+                        None,
+                        None,
+                        Some(&e.to_string()),
+                    )
+                    .await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// 撤销检查操作：恢复原实例，删除操作生成的实例并为其生成拒绝说明
+    pub async fn revert(
+        &self,
+        operation_id: &Uuid,
+        performed_by: &str,
+    ) -> Result<StudyOperationReport> {
+        let queries = DatabaseQueries::new(&self.db);
+        let operation = queries
+            .get_study_operation(operation_id)
+            .await?
+            .ok_or_else(|| PacsError::NotFound(format!("检查操作 {}", operation_id)))?;
+        if operation.status == "REVERTED" || operation.status == "IN_PROGRESS" {
+            return Err(PacsError::Workflow(format!(
+                "检查操作 {} 当前状态为 {}，无法撤销",
+                operation_id, operation.status
+            )));
+        }
+
+        let items = queries.get_study_operation_items(operation_id).await?;
+        let source = IngestSource::StudyOperation {
+            operation_id: operation_id.to_string(),
+        };
+        let mut rejected = Vec::new();
+        let mut note_template = None;
+
+        // 本地撤回原拒绝说明，被恢复的实例重新可见
+        if let (Some(note_uid), Some(note_path)) = (
+            &operation.rejection_note_uid,
+            &operation.rejection_note_path,
+        ) {
+            let note_path = PathBuf::from(note_path);
+            if tokio::fs::remove_file(&note_path).await.is_ok() {
+                self.pipeline.prune_empty_dirs(&note_path).await;
+            }
+            queries.delete_relationships_by_source_uid(note_uid).await?;
+            queries.delete_rejections_by_note_uid(note_uid).await?;
+        }
+
+        for item in &items {
+            let new_path = PathBuf::from(&item.new_path);
+            if let Ok(data) = tokio::fs::read(&new_path).await {
+                if note_template.is_none() {
+                    note_template = DicomParser::read_object(&data).ok();
+                }
+                tokio::fs::remove_file(&new_path).await?;
+                self.pipeline.prune_empty_dirs(&new_path).await;
+            }
+            rejected.push(RejectedInstance {
+                series_instance_uid: item.new_series_uid.clone(),
+                sop_class_uid: item.sop_class_uid.clone(),
+                sop_instance_uid: item.new_sop_instance_uid.clone(),
+            });
+
+            let held_path = PathBuf::from(&item.held_path);
+            if tokio::fs::metadata(&held_path).await.is_ok() {
+                self.pipeline.ingest_file(&held_path, &source, None).await?;
+                tokio::fs::remove_file(&held_path).await?;
+            } else {
+                warn!("原实例暂存文件不存在，无法恢复: {:?}", held_path);
+            }
+
+            let restored = self
+                .pipeline
+                .instance_path(
+                    &item.old_study_uid,
+                    &item.old_series_uid,
+                    &item.old_sop_instance_uid,
+                )
+                .await?;
+            if let Ok(data) = tokio::fs::read(&restored).await {
+                let obj = DicomParser::read_object(&data)?;
+                queries
+                    .relocate_instance(&InstanceRelocation {
+                        old_sop_instance_uid: item.new_sop_instance_uid.clone(),
+                        new_sop_instance_uid: item.old_sop_instance_uid.clone(),
+                        new_series_uid: item.old_series_uid.clone(),
+                        new_study_uid: item.old_study_uid.clone(),
+                        patient_id: get_string(&obj, tags::PATIENT_ID).unwrap_or_default(),
+                        patient_name: get_string(&obj, tags::PATIENT_NAME).unwrap_or_default(),
+                        accession_number: get_string(&obj, tags::ACCESSION_NUMBER),
+                        file_path: restored.to_string_lossy().to_string(),
+                    })
+                    .await?;
+            }
+        }
+
+        let rejection_note = match note_template {
+            // 每个操作的实例都归入同一个新检查
+            Some(template) if !rejected.is_empty() => Some(
+                self.store_rejection_note(&template, &items[0].new_study_uid, rejected, &source)
+                    .await?,
+            ),
+            _ => None,
+        };
+
+        queries
+            .mark_study_operation_reverted(operation_id, performed_by)
+            .await?;
+        info!("{} 撤销检查操作 {}", performed_by, operation_id);
+
+        let mut study_instance_uids: Vec<String> =
+            items.iter().map(|i| i.old_study_uid.clone()).collect();
+        study_instance_uids.dedup();
+        Ok(StudyOperationReport {
+            operation_id: *operation_id,
+            operation_type: operation.operation_type,
+            instances: items.len(),
+            study_instance_uids,
+            rejection_note_uid: rejection_note.map(|(uid, _)| uid),
+        })
+    }
+
+    /// 获取最近的检查操作审计记录
+    pub async fn list_operations(&self, limit: i64) -> Result<Vec<DbStudyOperation>> {
+        DatabaseQueries::new(&self.db)
+            .list_study_operations(limit)
+            .await
+    }
+
+    /// 生成实例改写计划
+    async fn plan(
+        &self,
+        operation_id: Uuid,
+        operation: &StudyOperation,
+    ) -> Result<Vec<InstanceRewrite>> {
+        let namespace = operation_id.to_string();
+
+        match operation {
+            StudyOperation::MoveSeries {
+                series_instance_uid,
+                target_study_instance_uid,
+            } => {
+                let study_uid = self.locate_series(series_instance_uid).await?;
+                if &study_uid == target_study_instance_uid {
+                    return Err(PacsError::Validation("序列已属于目标检查".to_string()));
+                }
+                let overrides = self
+                    .target_study_attributes(target_study_instance_uid)
+                    .await?;
+                let files = self
+                    .pipeline
+                    .list_instance_files(&study_uid, Some(series_instance_uid.as_str()))
+                    .await?;
+                Ok(self.rewrites(
+                    &namespace,
+                    &study_uid,
+                    files,
+                    target_study_instance_uid,
+                    &overrides,
+                ))
+            }
+            StudyOperation::MergeStudies {
+                source_study_instance_uid,
+                target_study_instance_uid,
+            } => {
+                if source_study_instance_uid == target_study_instance_uid {
+                    return Err(PacsError::Validation("源检查与目标检查相同".to_string()));
+                }
+                let overrides = self
+                    .target_study_attributes(target_study_instance_uid)
+                    .await?;
+                let files = self
+                    .pipeline
+                    .list_instance_files(source_study_instance_uid, None)
+                    .await?;
+                Ok(self.rewrites(
+                    &namespace,
+                    source_study_instance_uid,
+                    files,
+                    target_study_instance_uid,
+                    &overrides,
+                ))
+            }
+            StudyOperation::SplitStudy {
+                study_instance_uid,
+                series_instance_uids,
+                accession_number,
+            } => {
+                if series_instance_uids.is_empty() {
+                    return Err(PacsError::Validation(
+                        "拆分检查至少需要指定一个序列".to_string(),
+                    ));
+                }
+                let mut files = Vec::new();
+                for series_uid in series_instance_uids {
+                    let series_files = self
+                        .pipeline
+                        .list_instance_files(study_instance_uid, Some(series_uid.as_str()))
+                        .await?;
+                    if series_files.is_empty() {
+                        return Err(PacsError::NotFound(format!(
+                            "检查 {} 中的序列 {}",
+                            study_instance_uid, series_uid
+                        )));
+                    }
+                    files.extend(series_files);
+                }
+                let overrides = accession_number
+                    .iter()
+                    .map(|accession| (tags::ACCESSION_NUMBER, VR::SH, accession.clone()))
+                    .collect::<Vec<_>>();
+                let new_study_uid = self.uid_generator.derive(&namespace, study_instance_uid);
+                Ok(self.rewrites(
+                    &namespace,
+                    study_instance_uid,
+                    files,
+                    &new_study_uid,
+                    &overrides,
+                ))
+            }
+            StudyOperation::MoveStudy {
+                study_instance_uid,
+                target_patient,
+            } => {
+                let overrides = vec![
+                    (tags::PATIENT_ID, VR::LO, target_patient.patient_id.clone()),
+                    (
+                        tags::PATIENT_NAME,
+                        VR::PN,
+                        target_patient.patient_name.clone(),
+                    ),
+                    (
+                        tags::PATIENT_BIRTH_DATE,
+                        VR::DA,
+                        target_patient.birth_date.clone().unwrap_or_default(),
+                    ),
+                    (
+                        tags::PATIENT_SEX,
+                        VR::CS,
+                        target_patient.sex.clone().unwrap_or_default(),
+                    ),
+                ];
+                let files = self
+                    .pipeline
+                    .list_instance_files(study_instance_uid, None)
+                    .await?;
+                let new_study_uid = self.uid_generator.derive(&namespace, study_instance_uid);
+                Ok(self.rewrites(
+                    &namespace,
+                    study_instance_uid,
+                    files,
+                    &new_study_uid,
+                    &overrides,
+                ))
+            }
+        }
+    }
+
+    /// 为一组实例文件生成改写计划
+    fn rewrites(
+        &self,
+        namespace: &str,
+        study_uid: &str,
+        files: Vec<StoredInstanceFile>,
+        new_study_uid: &str,
+        overrides: &[(Tag, VR, String)],
+    ) -> Vec<InstanceRewrite> {
+        files
+            .into_iter()
+            .map(|file| InstanceRewrite {
+                study_uid: study_uid.to_string(),
+                new_study_uid: new_study_uid.to_string(),
+                new_series_uid: self
+                    .uid_generator
+                    .derive(namespace, &file.series_instance_uid),
+                new_sop_instance_uid: self.uid_generator.derive(namespace, &file.sop_instance_uid),
+                series_uid: file.series_instance_uid,
+                sop_instance_uid: file.sop_instance_uid,
+                source_path: file.file_path,
+                overrides: overrides.to_vec(),
+            })
+            .collect()
+    }
+
+    /// 执行改写计划，返回原实例拒绝说明的UID与路径
+    async fn apply(
+        &self,
+        operation_id: Uuid,
+        rewrites: &[InstanceRewrite],
+    ) -> Result<Option<(String, PathBuf)>> {
+        let queries = DatabaseQueries::new(&self.db);
+        let source = IngestSource::StudyOperation {
+            operation_id: operation_id.to_string(),
+        };
+        let hold_dir = self
+            .pipeline
+            .storage_dir()
+            .join(HOLD_DIR)
+            .join(operation_id.to_string());
+        tokio::fs::create_dir_all(&hold_dir).await?;
+
+        let mut rejected = Vec::new();
+        let mut note_template = None;
+
+        for rewrite in rewrites {
+            let data = tokio::fs::read(&rewrite.source_path).await?;
+            let original = DicomParser::read_object(&data)?;
+            let sop_class_uid = get_string(&original, tags::SOP_CLASS_UID).unwrap_or_default();
+            let old_patient_id = get_string(&original, tags::PATIENT_ID);
+
+            let rewritten = rewrite_object(original.clone(), rewrite)?;
+            let patient_id = get_string(&rewritten, tags::PATIENT_ID).unwrap_or_default();
+            let patient_name = get_string(&rewritten, tags::PATIENT_NAME).unwrap_or_default();
+            let accession_number = get_string(&rewritten, tags::ACCESSION_NUMBER);
+            let bytes = IngestPipeline::encode_object(&rewritten)?;
+
+            let new_path = match self.pipeline.ingest(&bytes, &source, None).await? {
+                IngestOutcome::Stored(instance) => instance.file_path,
+                IngestOutcome::Duplicate { .. } => {
+                    self.pipeline
+                        .instance_path(
+                            &rewrite.new_study_uid,
+                            &rewrite.new_series_uid,
+                            &rewrite.new_sop_instance_uid,
+                        )
+                        .await?
+                }
+            };
+
+            let held_path = hold_dir.join(format!("{}.dcm", rewrite.sop_instance_uid));
+            tokio::fs::rename(&rewrite.source_path, &held_path).await?;
+            self.pipeline.prune_empty_dirs(&rewrite.source_path).await;
+
+            queries
+                .add_study_operation_item(&NewStudyOperationItem {
+                    id: Uuid::new_v4(),
+                    operation_id,
+                    sop_class_uid: sop_class_uid.clone(),
+                    old_study_uid: rewrite.study_uid.clone(),
+                    old_series_uid: rewrite.series_uid.clone(),
+                    old_sop_instance_uid: rewrite.sop_instance_uid.clone(),
+                    new_study_uid: rewrite.new_study_uid.clone(),
+                    new_series_uid: rewrite.new_series_uid.clone(),
+                    new_sop_instance_uid: rewrite.new_sop_instance_uid.clone(),
+                    new_patient_id: Some(patient_id.clone()).filter(|id| !id.is_empty()),
+                    old_patient_id,
+                    original_path: rewrite.source_path.to_string_lossy().to_string(),
+                    held_path: held_path.to_string_lossy().to_string(),
+                    new_path: new_path.to_string_lossy().to_string(),
+                })
+                .await?;
+
+            let relocated = queries
+                .relocate_instance(&InstanceRelocation {
+                    old_sop_instance_uid: rewrite.sop_instance_uid.clone(),
+                    new_sop_instance_uid: rewrite.new_sop_instance_uid.clone(),
+                    new_series_uid: rewrite.new_series_uid.clone(),
+                    new_study_uid: rewrite.new_study_uid.clone(),
+                    patient_id,
+                    patient_name,
+                    accession_number,
+                    file_path: new_path.to_string_lossy().to_string(),
+                })
+                .await?;
+            if !relocated {
+                debug!("实例 {} 没有数据库记录", rewrite.sop_instance_uid);
+            }
+
+            rejected.push(RejectedInstance {
+                series_instance_uid: rewrite.series_uid.clone(),
+                sop_class_uid,
+                sop_instance_uid: rewrite.sop_instance_uid.clone(),
+            });
+            if note_template.is_none() {
+                note_template = Some(original);
+            }
+        }
+
+        match note_template {
+            Some(template) => {
+                let study_uid = rewrites[0].study_uid.clone();
+                self.store_rejection_note(&template, &study_uid, rejected, &source)
+                    .await
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 生成并入库拒绝说明，患者与检查属性取自被拒绝实例
+    async fn store_rejection_note(
+        &self,
+        template: &DefaultDicomObject,
+        study_uid: &str,
+        instances: Vec<RejectedInstance>,
+        source: &IngestSource,
+    ) -> Result<(String, PathBuf)> {
+        let request = RejectionNoteRequest {
+            study_instance_uid: study_uid.to_string(),
+            patient_id: get_string(template, tags::PATIENT_ID).unwrap_or_default(),
+            patient_name: get_string(template, tags::PATIENT_NAME).unwrap_or_default(),
+            patient_birth_date: get_string(template, tags::PATIENT_BIRTH_DATE),
+            patient_sex: get_string(template, tags::PATIENT_SEX),
+            accession_number: get_string(template, tags::ACCESSION_NUMBER),
+            reason: RejectionReason::IncorrectWorklistEntry,
+            instances,
+        };
+        let bytes = RejectionNoteBuilder::build_bytes(&request)?;
+
+        match self.pipeline.ingest(&bytes, source, None).await? {
+            IngestOutcome::Stored(instance) => Ok((instance.sop_instance_uid, instance.file_path)),
+            IngestOutcome::Duplicate { sop_instance_uid } => Err(PacsError::Internal(format!(
+                "Duplicate SOP Instance UID: {}",
+                sop_instance_uid
+            ))),
+        }
+    }
+
+    /// 查找序列所属的检查UID
+    async fn locate_series(&self, series_uid: &str) -> Result<String> {
+        let mut studies = tokio::fs::read_dir(self.pipeline.storage_dir()).await?;
+        while let Some(entry) = studies.next_entry().await? {
+            let study_uid = entry.file_name().to_string_lossy().to_string();
+            if study_uid.starts_with('.') {
+                continue;
+            }
+            if tokio::fs::metadata(entry.path().join(series_uid))
+                .await
+                .map(|m| m.is_dir())
+                .unwrap_or(false)
+            {
+                return Ok(study_uid);
+            }
+        }
+        Err(PacsError::NotFound(format!("序列 {}", series_uid)))
+    }
+
+    /// 读取目标检查的患者级与检查级属性
+    async fn target_study_attributes(&self, study_uid: &str) -> Result<Vec<(Tag, VR, String)>> {
+        let files = self.pipeline.list_instance_files(study_uid, None).await?;
+        let file = files
+            .first()
+            .ok_or_else(|| PacsError::NotFound(format!("检查 {} 中的实例", study_uid)))?;
+        let obj = DicomParser::read_object(&tokio::fs::read(&file.file_path).await?)?;
+
+        Ok(PATIENT_ATTRIBUTES
+            .iter()
+            .chain(STUDY_ATTRIBUTES.iter())
+            .map(|(tag, vr)| (*tag, *vr, get_string(&obj, *tag).unwrap_or_default()))
+            .collect())
+    }
+}
+
+/// 应用改写计划：覆盖属性、替换UID并重建文件元信息
+fn rewrite_object(
+    obj: DefaultDicomObject,
+    rewrite: &InstanceRewrite,
+) -> Result<DefaultDicomObject> {
+    let transfer_syntax = obj
+        .meta()
+        .transfer_syntax()
+        .trim_end_matches('\0')
+        .to_string();
+    let mut dataset: InMemDicomObject = obj.into_inner();
+
+    for (tag, vr, value) in &rewrite.overrides {
+        dataset.put(DataElement::new(
+            *tag,
+            *vr,
+            PrimitiveValue::from(value.as_str()),
+        ));
+    }
+    let uids = [
+        (tags::STUDY_INSTANCE_UID, &rewrite.new_study_uid),
+        (tags::SERIES_INSTANCE_UID, &rewrite.new_series_uid),
+        (tags::SOP_INSTANCE_UID, &rewrite.new_sop_instance_uid),
+    ];
+    for (tag, uid) in uids {
+        dataset.put(DataElement::new(
+            tag,
+            VR::UI,
+            PrimitiveValue::from(uid.as_str()),
+        ));
+    }
+
+    dataset
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(transfer_syntax))
+        .map_err(|e| PacsError::Dicom(format!("无法生成文件元信息: {:?}", e)))
+}
+
+/// 获取去除填充字符后的字符串值
+fn get_string(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    obj.element(tag)
+        .ok()
+        .and_then(|e| e.to_str().ok())
+        .map(|s| s.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicom::dictionary_std::uids;
+
+    #[test]
+    fn test_rewrite_object_replaces_uids_and_attributes() {
+        let obj = InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, "1.2.3.1.1"),
+            DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, "1.2.3"),
+            DataElement::new(tags::SERIES_INSTANCE_UID, VR::UI, "1.2.3.1"),
+            DataElement::new(tags::PATIENT_ID, VR::LO, "WRONG"),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+        .unwrap();
+
+        let generator = UidGenerator::default();
+        let rewrite = InstanceRewrite {
+            source_path: PathBuf::from("1.2.3.1.1.dcm"),
+            study_uid: "1.2.3".to_string(),
+            series_uid: "1.2.3.1".to_string(),
+            sop_instance_uid: "1.2.3.1.1".to_string(),
+            new_study_uid: "1.2.4".to_string(),
+            new_series_uid: generator.derive("op", "1.2.3.1"),
+            new_sop_instance_uid: generator.derive("op", "1.2.3.1.1"),
+            overrides: vec![(tags::PATIENT_ID, VR::LO, "PAT001".to_string())],
+        };
+
+        let rewritten = rewrite_object(obj, &rewrite).unwrap();
+        assert_eq!(
+            get_string(&rewritten, tags::PATIENT_ID).as_deref(),
+            Some("PAT001")
+        );
+        assert_eq!(
+            get_string(&rewritten, tags::STUDY_INSTANCE_UID).as_deref(),
+            Some("1.2.4")
+        );
+        assert_eq!(
+            get_string(&rewritten, tags::SOP_INSTANCE_UID),
+            Some(rewrite.new_sop_instance_uid.clone())
+        );
+        assert_eq!(
+            rewritten
+                .meta()
+                .media_storage_sop_instance_uid()
+                .trim_end_matches('\0'),
+            rewrite.new_sop_instance_uid
+        );
+    }
+}
@@ -97,7 +97,7 @@ impl ApiHandler {
 }
 
 /// 创建API路由
-pub fn create_api_routes() -> Router<ApiState> {
+pub fn create_api_routes() -> Router {
     let api_state = ApiState::new();
 
     Router::new()
@@ -105,12 +105,14 @@ pub fn create_api_routes() -> Router<ApiState> {
         .route("/health", get(ApiHandler::health_check))
         .route("/webhooks", post(ApiHandler::create_webhook))
         .with_state(api_state)
-        .layer(axum::middleware::from_fn(|req, next| async move {
-            info!("API request: {} {}", req.method(), req.uri());
-            let response = next.run(req).await;
-            info!("API response: {}", response.status());
-            response
-        }))
+        .layer(axum::middleware::from_fn(
+            |req: axum::extract::Request, next: axum::middleware::Next| async move {
+                info!("API request: {} {}", req.method(), req.uri());
+                let response = next.run(req).await;
+                info!("API response: {}", response.status());
+                response
+            },
+        ))
 }
 
 /// API服务器
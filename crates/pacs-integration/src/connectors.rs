@@ -72,7 +72,7 @@ pub enum ConnectorStatus {
 
 /// 连接器接口
 #[async_trait]
-pub trait Connector: Send + Sync {
+pub trait Connector: AsAny + Send + Sync {
     /// 获取连接器名称
     fn name(&self) -> &str;
 
@@ -448,7 +448,7 @@ pub trait AsAny {
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
-impl<T: Connector + 'static> AsAny for T {
+impl<T: std::any::Any> AsAny for T {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -7,11 +7,20 @@
 //! - SIU消息（预约信息）
 
 use anyhow::Result;
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
 use tracing::{debug, error, info, warn};
 
+/// MLLP帧起始字节
+const MLLP_START_BLOCK: u8 = 0x0b;
+/// MLLP帧结束字节，其后跟一个回车
+const MLLP_END_BLOCK: u8 = 0x1c;
+
 #[derive(Error, Debug, Serialize, Deserialize)]
 pub enum Hl7Error {
     #[error("Invalid HL7 message format: {0}")]
@@ -63,6 +72,7 @@ pub struct Hl7Message {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hl7Segment {
     pub segment_type: String,
+    /// 各字段的组件，下标0为段名；MSH-1为字段分隔符本身，MSH段第n个字段位于下标n-1
     pub fields: Vec<Vec<String>>,
 }
 
@@ -87,6 +97,18 @@ pub struct OrderInfo {
     pub ordering_physician: Option<String>,
     pub priority: String,
     pub scheduled_time: Option<DateTime<Utc>>,
+    pub patient_id: Option<String>, // PID-3
+    pub modality: Option<String>,   // OBR-24 诊断服务科室
+    pub body_part: Option<String>,  // OBR-15 检查部位
+}
+
+/// 检查申请监听器
+///
+/// ORM消息解析成功后回调，用于历史检查预取等后续处理
+#[async_trait]
+pub trait OrderListener: Send + Sync {
+    /// 收到新的检查申请
+    async fn on_order(&self, order: &OrderInfo) -> Result<()>;
 }
 
 /// HL7解析器
@@ -147,25 +169,25 @@ impl Hl7Parser {
             message_type,
             trigger_event: msh_segment
                 .fields
-                .get(9)
-                .and_then(|f| f.first())
+                .get(8)
+                .and_then(|f| f.get(1))
                 .cloned()
                 .unwrap_or_default(),
             message_control_id: msh_segment
                 .fields
-                .get(10)
+                .get(9)
                 .and_then(|f| f.first())
                 .cloned()
                 .unwrap_or_default(),
             processing_id: msh_segment
                 .fields
-                .get(11)
+                .get(10)
                 .and_then(|f| f.first())
                 .cloned()
                 .unwrap_or_default(),
             version_id: msh_segment
                 .fields
-                .get(12)
+                .get(11)
                 .and_then(|f| f.first())
                 .cloned()
                 .unwrap_or_default(),
@@ -184,16 +206,19 @@ impl Hl7Parser {
         let segment_type = parts[0].to_string();
         let mut fields = Vec::new();
 
-        for part in parts.iter().skip(1) {
-            let field_parts: Vec<String> = part
-                .split(self.repetition_separator)
-                .map(|r| {
-                    r.split(self.component_separator)
-                        .map(|c| c.to_string())
-                        .collect()
-                })
-                .collect();
-            fields.push(field_parts);
+        // 段名占下标0，字段下标与字段序号一致；每个字段取首次重复的各组件
+        for part in parts.iter() {
+            let repetition = part.split(self.repetition_separator).next().unwrap_or("");
+            let components: Vec<String> = if segment_type == "MSH" && fields.len() == 1 {
+                // MSH-2为编码字符本身，不再拆分
+                vec![part.to_string()]
+            } else {
+                repetition
+                    .split(self.component_separator)
+                    .map(|c| c.to_string())
+                    .collect()
+            };
+            fields.push(components);
         }
 
         Ok(Hl7Segment {
@@ -386,6 +411,27 @@ impl Hl7Parser {
             .and_then(|f| f.first())
             .and_then(|time_str| self.parse_hl7_datetime(time_str).ok());
 
+        let patient_id = message
+            .segments
+            .iter()
+            .find(|s| s.segment_type == "PID")
+            .and_then(|pid| pid.fields.get(3))
+            .and_then(|f| f.first())
+            .filter(|id| !id.is_empty())
+            .cloned();
+
+        let modality = obr_segment
+            .and_then(|obr| obr.fields.get(24))
+            .and_then(|f| f.first())
+            .filter(|m| !m.is_empty())
+            .cloned();
+
+        let body_part = obr_segment
+            .and_then(|obr| obr.fields.get(15))
+            .and_then(|f| f.first())
+            .filter(|b| !b.is_empty())
+            .cloned();
+
         Ok(Some(OrderInfo {
             placer_order_number,
             filler_order_number,
@@ -394,6 +440,9 @@ impl Hl7Parser {
             ordering_physician,
             priority,
             scheduled_time,
+            patient_id,
+            modality,
+            body_part,
         }))
     }
 
@@ -444,6 +493,7 @@ impl Hl7Parser {
 /// HL7接口处理器
 pub struct Hl7Interface {
     parser: Hl7Parser,
+    order_listeners: Vec<Arc<dyn OrderListener>>,
 }
 
 impl Hl7Interface {
@@ -451,6 +501,71 @@ impl Hl7Interface {
     pub fn new() -> Self {
         Self {
             parser: Hl7Parser::new(),
+            order_listeners: Vec::new(),
+        }
+    }
+
+    /// 注册检查申请监听器
+    pub fn with_order_listener(mut self, listener: Arc<dyn OrderListener>) -> Self {
+        self.order_listeners.push(listener);
+        self
+    }
+
+    /// 在指定地址接收MLLP连接，逐条处理HL7消息并回复ACK
+    pub async fn serve(self: Arc<Self>, addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        info!("HL7 MLLP listener started on {}", addr);
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let interface = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = interface.handle_connection(stream).await {
+                    warn!("HL7 connection from {} closed with error: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    /// 处理单个MLLP连接，连接关闭时返回
+    async fn handle_connection<S>(&self, stream: S) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+        let mut frame = Vec::new();
+        loop {
+            // 丢弃帧起始字节之前的内容
+            frame.clear();
+            if reader.read_until(MLLP_START_BLOCK, &mut frame).await? == 0 {
+                return Ok(());
+            }
+            frame.clear();
+            reader.read_until(MLLP_END_BLOCK, &mut frame).await?;
+            if frame.pop() != Some(MLLP_END_BLOCK) {
+                return Ok(());
+            }
+            reader.read_u8().await?;
+
+            // MLLP中的段以回车分隔，解析器按行处理
+            let message = String::from_utf8_lossy(&frame).replace('\r', "\n");
+            let ack = match self.process_message(&message).await {
+                Ok(parsed) => self.generate_ack(&parsed, true, None),
+                Err(e) => match self.parser.parse(&message) {
+                    Ok(parsed) => self.generate_ack(&parsed, false, Some(&e.to_string())),
+                    Err(_) => {
+                        warn!("Discarding unparseable HL7 message: {}", e);
+                        continue;
+                    }
+                },
+            };
+
+            let mut response = Vec::with_capacity(ack.len() + 3);
+            response.push(MLLP_START_BLOCK);
+            response.extend_from_slice(ack.as_bytes());
+            response.extend_from_slice(&[MLLP_END_BLOCK, b'\r']);
+            writer.write_all(&response).await?;
+            writer.flush().await?;
         }
     }
 
@@ -508,6 +623,14 @@ impl Hl7Interface {
         );
         // TODO: 集成到工作流引擎和数据库模块
         // 这里应该创建新的检查记录并触发工作流
+        for listener in &self.order_listeners {
+            if let Err(e) = listener.on_order(order_info).await {
+                warn!(
+                    "Order listener failed for {}: {}",
+                    order_info.placer_order_number, e
+                );
+            }
+        }
         Ok(())
     }
 
@@ -537,3 +660,60 @@ impl Default for Hl7Interface {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Mutex;
+
+    /// 记录收到的检查申请
+    #[derive(Default)]
+    struct RecordingListener {
+        orders: Mutex<Vec<OrderInfo>>,
+    }
+
+    #[async_trait]
+    impl OrderListener for RecordingListener {
+        async fn on_order(&self, order: &OrderInfo) -> Result<()> {
+            self.orders.lock().await.push(order.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mllp_order_reaches_listener_and_is_acknowledged() {
+        let listener = Arc::new(RecordingListener::default());
+        let interface = Hl7Interface::new().with_order_listener(listener.clone());
+        let (mut client, server) = tokio::io::duplex(4096);
+        let connection = tokio::spawn(async move { interface.handle_connection(server).await });
+
+        let message = [
+            "MSH|^~\\&|RIS|HOSPITAL|PACS|HOSPITAL|20241030120000||ORM^O01|123457|P|2.5",
+            "PID|1||PAT12345^HOSPITAL||DOE^JOHN||19800101|M",
+            "ORC|NW|ORD12345||ORD12345^HOSPITAL",
+            "OBR|1|ORD12345||CT-ABDOMEN^Abdomen CT",
+        ]
+        .join("\r");
+        let mut frame = vec![MLLP_START_BLOCK];
+        frame.extend_from_slice(message.as_bytes());
+        frame.extend_from_slice(&[MLLP_END_BLOCK, b'\r']);
+        client.write_all(&frame).await.unwrap();
+
+        let mut reader = BufReader::new(&mut client);
+        let mut ack = Vec::new();
+        reader.read_until(MLLP_END_BLOCK, &mut ack).await.unwrap();
+        let ack = String::from_utf8_lossy(&ack);
+        assert!(ack.starts_with('\u{0b}'));
+        assert!(ack.contains("MSA|AA|"), "unexpected ACK: {}", ack);
+
+        let orders = listener.orders.lock().await;
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].placer_order_number, "ORD12345");
+        assert_eq!(orders[0].patient_id.as_deref(), Some("PAT12345"));
+        drop(orders);
+
+        drop(reader);
+        drop(client);
+        connection.await.unwrap().unwrap();
+    }
+}
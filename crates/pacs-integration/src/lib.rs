@@ -6,13 +6,18 @@
 //! - Webhook事件通知系统，实现实时事件推送
 //! - 外部系统连接器，支持多种第三方系统集成
 //! - 消息队列集成，提供可靠的消息传递机制
+//! - 检查申请触发的历史检查预取
+//! - 检查接收完成事件推送
 
 pub mod api;
 pub mod connectors;
 pub mod hl7;
 pub mod message_queue;
+pub mod prefetch;
+pub mod study_events;
 pub mod webhook;
 
 pub use api::{ApiServer, ApiState, SystemStatsResponse};
-pub use hl7::{Hl7Interface, Hl7Message, Hl7Parser, OrderInfo, PatientInfo};
+pub use hl7::{Hl7Interface, Hl7Message, Hl7Parser, OrderInfo, OrderListener, PatientInfo};
+pub use study_events::StudyCompletionNotifier;
 pub use webhook::{WebhookEvent, WebhookEventType, WebhookManager, WebhookSubscription};
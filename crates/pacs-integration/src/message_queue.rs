@@ -13,6 +13,7 @@ use lapin::{
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
@@ -26,6 +27,20 @@ pub struct MessageQueueConfig {
     pub prefetch_count: u16,
 }
 
+impl MessageQueueConfig {
+    /// 带心跳和连接超时参数的连接URI
+    pub fn connection_uri(&self) -> String {
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        format!(
+            "{}{}heartbeat={}&connection_timeout={}",
+            self.url,
+            separator,
+            self.heartbeat,
+            u64::from(self.connection_timeout) * 1000
+        )
+    }
+}
+
 impl Default for MessageQueueConfig {
     fn default() -> Self {
         Self {
@@ -167,12 +182,12 @@ impl QueueConfig {
         use lapin::types::AMQPValue;
         self.arguments.insert(
             "x-dead-letter-exchange".into(),
-            AMQPValue::LongString(exchange.to_string()),
+            AMQPValue::LongString(exchange.into()),
         );
         if let Some(key) = routing_key {
             self.arguments.insert(
                 "x-dead-letter-routing-key".into(),
-                AMQPValue::LongString(key.to_string()),
+                AMQPValue::LongString(key.into()),
             );
         }
         self
@@ -207,11 +222,15 @@ impl MessagePublisher {
     /// 连接到消息队列
     pub async fn connect(&self) -> Result<()> {
         let conn = Connection::connect(
-            &self.config.url,
-            ConnectionProperties::default().with_heartbeat(self.config.heartbeat),
+            &self.config.connection_uri(),
+            ConnectionProperties::default(),
         )
         .await?;
         let channel = conn.create_channel().await?;
+        // 开启发布确认，Broker确认后才视为发布成功
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
 
         // 设置QoS
         channel
@@ -253,7 +272,7 @@ impl MessagePublisher {
                 .await?;
 
             match confirm {
-                Confirmation::Ack(_) => {
+                Confirmation::Ack(_) | Confirmation::NotRequested => {
                     debug!("Message published successfully: {}", message.id);
                     Ok(())
                 }
@@ -303,7 +322,7 @@ impl MessagePublisher {
 pub struct MessageSubscriber {
     channel: RwLock<Option<Channel>>,
     config: MessageQueueConfig,
-    handlers: RwLock<HashMap<String, Box<dyn MessageHandler>>>,
+    handlers: Arc<RwLock<HashMap<String, Box<dyn MessageHandler>>>>,
 }
 
 impl MessageSubscriber {
@@ -312,15 +331,15 @@ impl MessageSubscriber {
         Self {
             channel: RwLock::new(None),
             config,
-            handlers: RwLock::new(HashMap::new()),
+            handlers: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
     /// 连接到消息队列
     pub async fn connect(&self) -> Result<()> {
         let conn = Connection::connect(
-            &self.config.url,
-            ConnectionProperties::default().with_heartbeat(self.config.heartbeat),
+            &self.config.connection_uri(),
+            ConnectionProperties::default(),
         )
         .await?;
         let channel = conn.create_channel().await?;
@@ -411,57 +430,54 @@ impl MessageSubscriber {
             info!("Started consuming messages from queue: {}", queue_name);
 
             let handlers = self.handlers.clone();
-            consumer.set_delegate(move |delivery| {
+            consumer.set_delegate(move |delivery: lapin::message::DeliveryResult| {
                 let handlers = handlers.clone();
                 Box::pin(async move {
-                    if let Some(delivery) = delivery {
-                        match Self::process_delivery(&handlers, delivery).await {
-                            Ok(_) => {
-                                // 消息处理成功，发送ACK
-                                delivery.ack(BasicAckOptions::default()).await?;
-                            }
-                            Err(e) => {
-                                error!("Failed to process message: {}", e);
-                                // 检查是否可以重试
-                                if let Ok(message_str) = std::str::from_utf8(&delivery.data) {
-                                    if let Ok(mut message) =
-                                        serde_json::from_str::<Message>(message_str)
-                                    {
-                                        if message.increment_retry() {
-                                            // 可以重试，重新入队
-                                            warn!(
-                                                "Message retry {}/{}: {}",
-                                                message.retry_count,
-                                                message.max_retries,
-                                                message.id
-                                            );
-                                            delivery
-                                                .nack(BasicNackOptions::default().requeue(true))
-                                                .await?;
-                                        } else {
-                                            // 超过最大重试次数，拒绝并丢弃
-                                            error!(
-                                                "Message max retries exceeded, dropping: {}",
-                                                message.id
-                                            );
-                                            delivery
-                                                .nack(BasicNackOptions::default().requeue(false))
-                                                .await?;
-                                        }
+                    let delivery = match delivery {
+                        Ok(Some(delivery)) => delivery,
+                        Ok(None) => return,
+                        Err(e) => {
+                            error!("Failed to receive message: {}", e);
+                            return;
+                        }
+                    };
+
+                    let result = match Self::process_delivery(&handlers, &delivery).await {
+                        // 消息处理成功，发送ACK
+                        Ok(_) => delivery.ack(BasicAckOptions::default()).await,
+                        Err(e) => {
+                            error!("Failed to process message: {}", e);
+                            // 检查是否可以重试
+                            let requeue = match serde_json::from_slice::<Message>(&delivery.data) {
+                                Ok(mut message) => {
+                                    let retry = message.increment_retry();
+                                    if retry {
+                                        warn!(
+                                            "Message retry {}/{}: {}",
+                                            message.retry_count, message.max_retries, message.id
+                                        );
                                     } else {
-                                        delivery
-                                            .nack(BasicNackOptions::default().requeue(false))
-                                            .await?;
+                                        // 超过最大重试次数，拒绝并丢弃
+                                        error!(
+                                            "Message max retries exceeded, dropping: {}",
+                                            message.id
+                                        );
                                     }
-                                } else {
-                                    delivery
-                                        .nack(BasicNackOptions::default().requeue(false))
-                                        .await?;
+                                    retry
                                 }
-                            }
+                                Err(_) => false,
+                            };
+                            delivery
+                                .nack(BasicNackOptions {
+                                    requeue,
+                                    ..BasicNackOptions::default()
+                                })
+                                .await
                         }
+                    };
+                    if let Err(e) = result {
+                        error!("Failed to acknowledge message: {}", e);
                     }
-                    Ok(())
                 })
             });
 
@@ -474,7 +490,7 @@ impl MessageSubscriber {
     /// 处理接收到的消息
     async fn process_delivery(
         handlers: &RwLock<HashMap<String, Box<dyn MessageHandler>>>,
-        delivery: lapin::message::Delivery,
+        delivery: &lapin::message::Delivery,
     ) -> Result<()> {
         let message_str = std::str::from_utf8(&delivery.data)?;
         let message: Message = serde_json::from_str(message_str)?;
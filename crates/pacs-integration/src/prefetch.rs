@@ -0,0 +1,34 @@
+//! 检查申请触发的历史检查预取
+//!
+//! 将HL7 ORM检查申请转换为预取请求，交给DICOM模块的预取服务处理。
+
+use anyhow::Result;
+use async_trait::async_trait;
+use pacs_dicom::{PrefetchRequest, PrefetchService, PrefetchTrigger};
+use tracing::debug;
+
+use crate::hl7::{OrderInfo, OrderListener};
+
+#[async_trait]
+impl OrderListener for PrefetchService {
+    async fn on_order(&self, order: &OrderInfo) -> Result<()> {
+        let Some(patient_id) = order.patient_id.clone() else {
+            debug!(
+                "Order {} has no patient ID, skipping prefetch",
+                order.placer_order_number
+            );
+            return Ok(());
+        };
+
+        self.submit(PrefetchRequest {
+            patient_id,
+            modality: order.modality.clone(),
+            body_part: order.body_part.clone(),
+            current_study_uid: None,
+            trigger: PrefetchTrigger::Order {
+                placer_order_number: order.placer_order_number.clone(),
+            },
+        })?;
+        Ok(())
+    }
+}
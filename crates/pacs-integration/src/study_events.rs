@@ -0,0 +1,179 @@
+//! 检查完成事件推送
+//!
+//! 将DICOM模块稳定性跟踪器判定的检查完成事件转发为Webhook和消息队列事件。
+//! 每个Webhook订阅和消息交换器是一个投递通道，重发时跳过已成功的通道；
+//! 事件ID由检查UID确定，重发时保持不变，下游可据此对投递记录之外的重复去重。
+
+use async_trait::async_trait;
+use pacs_core::{PacsError, Result};
+use pacs_dicom::{EventDeliveries, StudyCompletedEvent, StudyCompletedListener};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::message_queue::{Message, MessagePublisher, MessageType};
+use crate::webhook::WebhookManager;
+
+/// 检查完成事件通知器
+#[derive(Default)]
+pub struct StudyCompletionNotifier {
+    /// Webhook管理器
+    webhooks: Option<Arc<RwLock<WebhookManager>>>,
+    /// 消息发布器及目标交换器
+    publisher: Option<(Arc<MessagePublisher>, String)>,
+}
+
+impl StudyCompletionNotifier {
+    /// 创建新的通知器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 通过Webhook推送事件
+    pub fn with_webhooks(mut self, webhooks: Arc<RwLock<WebhookManager>>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// 通过消息队列发布事件
+    pub fn with_message_publisher(
+        mut self,
+        publisher: Arc<MessagePublisher>,
+        exchange: &str,
+    ) -> Self {
+        self.publisher = Some((publisher, exchange.to_string()));
+        self
+    }
+}
+
+#[async_trait]
+impl StudyCompletedListener for StudyCompletionNotifier {
+    async fn on_study_completed(
+        &self,
+        event: &StudyCompletedEvent,
+        deliveries: &mut EventDeliveries,
+    ) -> Result<()> {
+        let data = serde_json::to_value(event)
+            .map_err(|e| PacsError::Internal(format!("Failed to serialize event: {}", e)))?;
+        let mut failures = 0;
+
+        if let Some(webhooks) = &self.webhooks {
+            let mut webhook_event = WebhookManager::create_study_completed_event(data.clone());
+            webhook_event.id = event.event_id();
+
+            let webhooks = webhooks.read().await;
+            for subscription in webhooks.subscriptions_for(&webhook_event.event_type).await {
+                let channel = format!("webhook:{}", subscription.id);
+                if deliveries.is_delivered(&channel) {
+                    continue;
+                }
+                match webhooks.deliver_event(&subscription, &webhook_event).await {
+                    Ok(()) => deliveries.mark_delivered(channel),
+                    Err(e) => {
+                        warn!(
+                            "Failed to deliver study completed webhook to {}: {}",
+                            subscription.url, e
+                        );
+                        failures += 1;
+                    }
+                }
+            }
+        }
+
+        if let Some((publisher, exchange)) = &self.publisher {
+            let channel = format!("amqp:{}", exchange);
+            if !deliveries.is_delivered(&channel) {
+                let message_type = MessageType::StudyCompleted;
+                let routing_key = message_type.as_str().to_string();
+                let mut message = Message::new(message_type, data, "pacs".to_string());
+                message.id = event.event_id();
+                match publisher.publish(exchange, &routing_key, &message).await {
+                    Ok(()) => deliveries.mark_delivered(channel),
+                    Err(e) => {
+                        warn!("Failed to publish study completed message: {}", e);
+                        failures += 1;
+                    }
+                }
+            }
+        }
+
+        if failures > 0 {
+            return Err(PacsError::Internal(format!(
+                "Study completed event {} failed on {} channel(s)",
+                event.event_id(),
+                failures
+            )));
+        }
+
+        debug!("Study completed event sent: {}", event.study_instance_uid);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_queue::MessageQueueConfig;
+    use crate::webhook::WebhookSubscriptionRequest;
+    use axum::{http::HeaderMap, routing::post, Router};
+    use pacs_dicom::CompletionTrigger;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_retry_skips_delivered_webhook() {
+        // 记录收到的事件ID
+        let received = Arc::new(Mutex::new(Vec::<String>::new()));
+        let recorder = received.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap| async move {
+                let event_id = headers["X-PACS-Event-Id"].to_str().unwrap().to_string();
+                recorder.lock().unwrap().push(event_id);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut webhooks = WebhookManager::new();
+        webhooks
+            .subscribe(WebhookSubscriptionRequest {
+                url: format!("http://{}/hook", addr),
+                events: vec!["study.completed".to_string()],
+                secret: None,
+                active: Some(true),
+            })
+            .await
+            .unwrap();
+        // 未连接的发布器每次发布都失败，触发重发
+        let notifier = StudyCompletionNotifier::new()
+            .with_webhooks(Arc::new(RwLock::new(webhooks)))
+            .with_message_publisher(
+                Arc::new(MessagePublisher::new(MessageQueueConfig::default())),
+                "pacs.events",
+            );
+
+        let event = StudyCompletedEvent {
+            study_instance_uid: "1.2.826.0.1.3680043.9.7382.7".to_string(),
+            patient_id: Some("PAT001".to_string()),
+            accession_number: None,
+            modalities: vec!["CT".to_string()],
+            instance_count: 3,
+            first_received_at: None,
+            last_received_at: None,
+            completed_at: chrono::Utc::now(),
+            trigger: CompletionTrigger::QuietPeriod,
+        };
+        let mut deliveries = EventDeliveries::default();
+        assert!(notifier
+            .on_study_completed(&event, &mut deliveries)
+            .await
+            .is_err());
+        assert!(notifier
+            .on_study_completed(&event, &mut deliveries)
+            .await
+            .is_err());
+
+        assert_eq!(*received.lock().unwrap(), vec![event.event_id()]);
+    }
+}
@@ -236,13 +236,13 @@ impl WebhookManager {
         let mut handles = Vec::new();
         for subscription in interested_subscriptions {
             let subscription = subscription.clone();
+            let event = event.clone();
             let payload = payload.clone();
             let client = self.client.clone();
 
-            let handle =
-                tokio::spawn(
-                    async move { Self::send_webhook(&client, &subscription, &payload).await },
-                );
+            let handle = tokio::spawn(async move {
+                Self::send_webhook(&client, &subscription, &event, &payload).await
+            });
             handles.push(handle);
         }
 
@@ -256,17 +256,45 @@ impl WebhookManager {
         Ok(())
     }
 
+    /// 获取对指定事件感兴趣的订阅
+    pub async fn subscriptions_for(
+        &self,
+        event_type: &WebhookEventType,
+    ) -> Vec<WebhookSubscription> {
+        let subscriptions = self.subscriptions.read().await;
+        subscriptions
+            .values()
+            .filter(|sub| sub.is_interested_in(event_type))
+            .cloned()
+            .collect()
+    }
+
+    /// 向单个订阅者发送事件，失败时返回错误供调用方重试
+    pub async fn deliver_event(
+        &self,
+        subscription: &WebhookSubscription,
+        event: &WebhookEvent,
+    ) -> Result<()> {
+        let payload = serde_json::to_string(event)?;
+        Self::send_webhook(&self.client, subscription, event, &payload).await
+    }
+
     /// 发送单个Webhook
+    ///
+    /// 请求头携带事件ID，重发同一事件时保持不变，订阅者可据此去重
     async fn send_webhook(
         client: &reqwest::Client,
         subscription: &WebhookSubscription,
+        event: &WebhookEvent,
         payload: &str,
     ) -> Result<()> {
         let mut request = client
             .post(&subscription.url)
             .header("Content-Type", "application/json")
             .header("User-Agent", "PACS-Webhook/1.0")
-            .header("X-PACS-Event", payload);
+            .header("X-PACS-Event", event.event_type.as_str())
+            .header("X-PACS-Event-Id", event.id.as_str())
+            .body(payload.to_string());
 
         // 添加签名头
         if let Some(signature) = subscription.generate_signature(payload) {
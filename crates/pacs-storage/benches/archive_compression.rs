@@ -0,0 +1,347 @@
+//! 归档压缩基准测试
+//!
+//! 对各模态的代表性DICOM对象比较Gzip、Zstd、LZ4在不同级别下的压缩速度、解压速度与压缩率，
+//! 用于确定 `default_modality_compression` 中的默认设置。
+//!
+//! 默认使用合成数据（与真实设备输出的位深、尺寸和噪声水平接近）；
+//! 设置 `PACS_BENCH_DICOM_DIR` 指向包含 `.dcm` 文件的目录时改用真实数据，按模态分组。
+//!
+//! 运行：`cargo bench -p pacs-storage --bench archive_compression`
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dicom::core::{DataElement, PrimitiveValue, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::{FileMetaTableBuilder, InMemDicomObject};
+use pacs_storage::{
+    compress, decompress, dicom_modality, CompressionAlgorithm, CompressionSettings,
+};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// 指定真实DICOM样本目录的环境变量
+const SAMPLE_DIR_ENV: &str = "PACS_BENCH_DICOM_DIR";
+
+/// 每个模态最多使用的真实样本数
+const MAX_SAMPLES_PER_MODALITY: usize = 8;
+
+/// 参与比较的压缩设置
+fn candidates() -> Vec<CompressionSettings> {
+    vec![
+        CompressionSettings::new(CompressionAlgorithm::Gzip, 6),
+        CompressionSettings::new(CompressionAlgorithm::Zstd, 3),
+        CompressionSettings::new(CompressionAlgorithm::Zstd, 9),
+        CompressionSettings::new(CompressionAlgorithm::Zstd, 19),
+        CompressionSettings::new(CompressionAlgorithm::Lz4, 0),
+        CompressionSettings::new(CompressionAlgorithm::Lz4, 9),
+    ]
+}
+
+/// 确定性的伪随机数发生器，保证每次运行数据一致
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as u32
+    }
+
+    /// 近似正态分布的噪声（12个均匀分布之和）
+    fn noise(&mut self, sigma: f64) -> f64 {
+        let sum: f64 = (0..12)
+            .map(|_| f64::from(self.next()) / f64::from(u32::MAX))
+            .sum();
+        (sum - 6.0) * sigma
+    }
+}
+
+/// 生成灰度断层/投影图像的像素：椭圆体模加高斯噪声
+fn grayscale_pixels(
+    width: usize,
+    height: usize,
+    background: f64,
+    body: f64,
+    sigma: f64,
+    max: f64,
+    rng: &mut Lcg,
+) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(width * height * 2);
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    for y in 0..height {
+        for x in 0..width {
+            let dx = (x as f64 - cx) / (cx * 0.8);
+            let dy = (y as f64 - cy) / (cy * 0.65);
+            let r = dx * dx + dy * dy;
+            let base = if r > 1.0 {
+                background
+            } else if r > 0.85 {
+                // 骨皮质环
+                body + (max - body) * 0.6
+            } else {
+                body + (1.0 - r) * (max - body) * 0.1
+            };
+            let value = (base + rng.noise(sigma)).clamp(0.0, max) as u16;
+            pixels.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    pixels
+}
+
+/// 构造DICOM对象字节
+fn dicom_object(
+    modality: &str,
+    sop_class: &str,
+    transfer_syntax: &str,
+    extra: Vec<DataElement<InMemDicomObject>>,
+) -> Vec<u8> {
+    let mut obj = InMemDicomObject::from_element_iter([
+        DataElement::new(tags::SOP_CLASS_UID, VR::UI, PrimitiveValue::from(sop_class)),
+        DataElement::new(
+            tags::SOP_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from("1.2.826.0.1.3680043.2.1125.1.1"),
+        ),
+        DataElement::new(
+            tags::STUDY_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from("1.2.826.0.1.3680043.2.1125.1"),
+        ),
+        DataElement::new(
+            tags::SERIES_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from("1.2.826.0.1.3680043.2.1125.1.2"),
+        ),
+        DataElement::new(tags::MODALITY, VR::CS, PrimitiveValue::from(modality)),
+        DataElement::new(tags::PATIENT_ID, VR::LO, PrimitiveValue::from("BENCH0001")),
+        DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("BENCH^PATIENT"),
+        ),
+        DataElement::new(tags::STUDY_DATE, VR::DA, PrimitiveValue::from("20240101")),
+    ]);
+    for element in extra {
+        obj.put(element);
+    }
+
+    let mut bytes = Vec::new();
+    obj.with_meta(FileMetaTableBuilder::new().transfer_syntax(transfer_syntax))
+        .expect("valid file meta")
+        .write_all(&mut bytes)
+        .expect("serializable object");
+    bytes
+}
+
+/// 构造16位灰度图像对象
+fn grayscale_object(
+    modality: &str,
+    sop_class: &str,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    bits_stored: u16,
+) -> Vec<u8> {
+    dicom_object(
+        modality,
+        sop_class,
+        uids::EXPLICIT_VR_LITTLE_ENDIAN,
+        vec![
+            DataElement::new(tags::ROWS, VR::US, PrimitiveValue::from(height as u16)),
+            DataElement::new(tags::COLUMNS, VR::US, PrimitiveValue::from(width as u16)),
+            DataElement::new(tags::SAMPLES_PER_PIXEL, VR::US, PrimitiveValue::from(1_u16)),
+            DataElement::new(
+                tags::PHOTOMETRIC_INTERPRETATION,
+                VR::CS,
+                PrimitiveValue::from("MONOCHROME2"),
+            ),
+            DataElement::new(tags::BITS_ALLOCATED, VR::US, PrimitiveValue::from(16_u16)),
+            DataElement::new(tags::BITS_STORED, VR::US, PrimitiveValue::from(bits_stored)),
+            DataElement::new(
+                tags::HIGH_BIT,
+                VR::US,
+                PrimitiveValue::from(bits_stored - 1),
+            ),
+            DataElement::new(
+                tags::PIXEL_REPRESENTATION,
+                VR::US,
+                PrimitiveValue::from(0_u16),
+            ),
+            DataElement::new(tags::PIXEL_DATA, VR::OW, PrimitiveValue::from(pixels)),
+        ],
+    )
+}
+
+/// 合成的各模态代表性样本
+fn synthetic_samples() -> BTreeMap<String, Vec<Vec<u8>>> {
+    let mut rng = Lcg(0x5eed);
+    let mut samples = BTreeMap::new();
+
+    // CT：512x512，12位，HU偏移1024，噪声约15HU
+    let pixels = grayscale_pixels(512, 512, 24.0, 1064.0, 15.0, 4095.0, &mut rng);
+    samples.insert(
+        "CT".to_string(),
+        vec![grayscale_object(
+            "CT",
+            uids::CT_IMAGE_STORAGE,
+            512,
+            512,
+            pixels,
+            12,
+        )],
+    );
+
+    // MR：256x256，12位，信噪比较低
+    let pixels = grayscale_pixels(256, 256, 8.0, 900.0, 40.0, 4095.0, &mut rng);
+    samples.insert(
+        "MR".to_string(),
+        vec![grayscale_object(
+            "MR",
+            uids::MR_IMAGE_STORAGE,
+            256,
+            256,
+            pixels,
+            12,
+        )],
+    );
+
+    // DX：1536x1536，14位，平滑背景
+    let pixels = grayscale_pixels(1536, 1536, 15000.0, 6000.0, 30.0, 16383.0, &mut rng);
+    samples.insert(
+        "DX".to_string(),
+        vec![grayscale_object(
+            "DX",
+            uids::DIGITAL_X_RAY_IMAGE_STORAGE_FOR_PRESENTATION,
+            1536,
+            1536,
+            pixels,
+            14,
+        )],
+    );
+
+    // US：像素数据来自有损压缩的多帧图像，熵接近随机数据
+    let encapsulated: Vec<u8> = (0..640 * 480 * 3 / 8 * 10)
+        .map(|_| rng.next() as u8)
+        .collect();
+    samples.insert(
+        "US".to_string(),
+        vec![dicom_object(
+            "US",
+            uids::ULTRASOUND_MULTI_FRAME_IMAGE_STORAGE,
+            uids::EXPLICIT_VR_LITTLE_ENDIAN,
+            vec![
+                DataElement::new(tags::NUMBER_OF_FRAMES, VR::IS, PrimitiveValue::from("10")),
+                DataElement::new(tags::PIXEL_DATA, VR::OB, PrimitiveValue::from(encapsulated)),
+            ],
+        )],
+    );
+
+    // SR：无像素的结构化报告，文本冗余高
+    let text =
+        "Findings: No acute intracranial abnormality. Ventricles and sulci are normal in size. "
+            .repeat(40);
+    samples.insert(
+        "SR".to_string(),
+        vec![dicom_object(
+            "SR",
+            uids::COMPREHENSIVE_SR_STORAGE,
+            uids::EXPLICIT_VR_LITTLE_ENDIAN,
+            vec![DataElement::new(
+                tags::TEXT_VALUE,
+                VR::UT,
+                PrimitiveValue::from(text),
+            )],
+        )],
+    );
+
+    samples
+}
+
+/// 从目录加载真实样本并按模态分组
+fn directory_samples(dir: &Path) -> BTreeMap<String, Vec<Vec<u8>>> {
+    let mut samples: BTreeMap<String, Vec<Vec<u8>>> = BTreeMap::new();
+    let entries = std::fs::read_dir(dir).expect("readable sample directory");
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("dcm") {
+            continue;
+        }
+        let Ok(data) = std::fs::read(&path) else {
+            continue;
+        };
+        let Some(modality) = dicom_modality(&data) else {
+            continue;
+        };
+        let group = samples.entry(modality).or_default();
+        if group.len() < MAX_SAMPLES_PER_MODALITY {
+            group.push(data);
+        }
+    }
+    samples
+}
+
+fn archive_compression(c: &mut Criterion) {
+    let samples = match std::env::var(SAMPLE_DIR_ENV) {
+        Ok(dir) => directory_samples(Path::new(&dir)),
+        Err(_) => synthetic_samples(),
+    };
+
+    println!(
+        "{:<6} {:<10} {:>12} {:>12} {:>8}",
+        "modal", "codec", "original", "archived", "ratio"
+    );
+    for (modality, objects) in &samples {
+        let original: usize = objects.iter().map(Vec::len).sum();
+        let mut group = c.benchmark_group(format!("archive/{}", modality));
+        group
+            .sample_size(10)
+            .measurement_time(Duration::from_secs(3))
+            .throughput(Throughput::Bytes(original as u64));
+
+        for settings in candidates() {
+            let label = format!("{:?}-{}", settings.algorithm, settings.level);
+            let compressed: Vec<Vec<u8>> = objects
+                .iter()
+                .map(|data| compress(data, &settings).expect("compressible"))
+                .collect();
+            let archived: usize = compressed.iter().map(Vec::len).sum();
+            println!(
+                "{:<6} {:<10} {:>12} {:>12} {:>7.1}%",
+                modality,
+                label,
+                original,
+                archived,
+                (1.0 - archived as f64 / original as f64) * 100.0
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new("compress", &label),
+                objects,
+                |b, objects| {
+                    b.iter(|| {
+                        for data in objects {
+                            compress(data, &settings).expect("compressible");
+                        }
+                    })
+                },
+            );
+            group.bench_with_input(
+                BenchmarkId::new("decompress", &label),
+                &compressed,
+                |b, compressed| {
+                    b.iter(|| {
+                        for data in compressed {
+                            decompress(data, settings.algorithm, 0).expect("decompressible");
+                        }
+                    })
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, archive_compression);
+criterion_main!(benches);
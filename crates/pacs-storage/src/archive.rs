@@ -1,13 +1,42 @@
 //! 归档管理
 
+use crate::backend::ObjectMetadata;
+use crate::compression::{
+    decode_archive_object, decompress, default_modality_compression, dicom_modality,
+    encode_archive_object, ArchiveObjectHeader, CompressionAlgorithm, CompressionSettings,
+};
 use crate::lifecycle::{LifecycleManager, LifecycleStage};
-use crate::storage::{StorageConfig, StorageManager, StorageType};
-use chrono::{DateTime, Utc};
+use crate::scheduler::{JobDefinition, JobScheduler, MaintenanceWindow, ScheduledJob};
+use crate::state::{StateRecovery, StateStore, StateTransaction};
+use crate::storage::{StorageConfig, StorageManager};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveTime, Utc};
 use pacs_core::{PacsError, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 
+/// 扫描存储时每页列举的对象数
+const SCAN_PAGE_SIZE: usize = 1000;
+
+/// 默认每批归档的文件数
+const DEFAULT_ARCHIVE_BATCH_SIZE: usize = 100;
+
+/// 归档对象的路径前缀，扫描时跳过
+const ARCHIVE_PREFIX: &str = "archive/";
+
+/// 自动归档任务的默认计划：每30分钟
+pub const DEFAULT_ARCHIVE_SCHEDULE: &str = "*/30 * * * *";
+
+/// 状态存储中归档任务的集合名（键为任务ID）
+pub const ARCHIVE_TASKS: &str = "archive.tasks";
+
+fn default_batch_size() -> usize {
+    DEFAULT_ARCHIVE_BATCH_SIZE
+}
+
 /// 归档策略
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArchivePolicy {
@@ -19,11 +48,17 @@ pub struct ArchivePolicy {
     pub target_storage: StorageConfig,
     /// 压缩设置
     pub compression_settings: Option<CompressionSettings>,
+    /// 按模态覆盖的压缩设置（键为大写模态，如 `CT`），优先于 `compression_settings`
+    #[serde(default)]
+    pub modality_compression: HashMap<String, CompressionSettings>,
     /// 是否启用
     pub enabled: bool,
+    /// 每批归档的文件数
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
 }
 
-/// 归档条件
+/// 归档条件（策略中的条件须全部满足）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ArchiveCondition {
     /// 基于时间（天数）
@@ -36,24 +71,27 @@ pub enum ArchiveCondition {
     PathPrefix(String),
 }
 
-/// 压缩设置
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CompressionSettings {
-    /// 压缩算法
-    pub algorithm: CompressionAlgorithm,
-    /// 压缩级别
-    pub level: u8,
-}
-
-/// 压缩算法
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum CompressionAlgorithm {
-    /// Gzip
-    Gzip,
-    /// Zstd
-    Zstd,
-    /// LZ4
-    Lz4,
+impl ArchiveCondition {
+    /// 判断对象是否满足条件
+    ///
+    /// `created_at` 为生命周期登记的创建时间（未登记时取对象修改时间），
+    /// `recent_accesses` 为统计窗口内的访问次数
+    pub fn matches(
+        &self,
+        object: &ObjectMetadata,
+        created_at: DateTime<Utc>,
+        recent_accesses: u64,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match self {
+            ArchiveCondition::TimeBasedDays(days) => {
+                now - created_at >= Duration::days(i64::from(*days))
+            }
+            ArchiveCondition::FileSizeGreaterThan(size) => object.size > *size,
+            ArchiveCondition::AccessFrequencyLessThan(count) => recent_accesses < u64::from(*count),
+            ArchiveCondition::PathPrefix(prefix) => object.key.starts_with(prefix.as_str()),
+        }
+    }
 }
 
 /// 归档任务状态
@@ -98,6 +136,23 @@ pub struct ArchiveTask {
     pub compression_ratio: Option<f64>,
     /// 错误信息
     pub error_message: Option<String>,
+    /// 自动归档批次ID（手动归档为None）
+    #[serde(default)]
+    pub batch_id: Option<String>,
+    /// 实际使用的压缩设置（未压缩为None）
+    #[serde(default)]
+    pub compression: Option<CompressionSettings>,
+}
+
+/// 已归档对象的位置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedObject {
+    /// 归档存储配置
+    pub storage: StorageConfig,
+    /// 归档对象路径
+    pub archive_path: String,
+    /// 归档时间
+    pub archived_at: DateTime<Utc>,
 }
 
 /// 归档管理器
@@ -112,6 +167,8 @@ pub struct ArchiveManager {
     task_history: Vec<ArchiveTask>,
     /// 活跃任务
     active_tasks: HashMap<String, ArchiveTask>,
+    /// 持久化状态存储（为None时任务历史只保存在内存中）
+    state_store: Option<Arc<StateStore>>,
 }
 
 impl ArchiveManager {
@@ -123,6 +180,130 @@ impl ArchiveManager {
             lifecycle_manager: LifecycleManager::new(),
             task_history: Vec::new(),
             active_tasks: HashMap::new(),
+            state_store: None,
+        }
+    }
+
+    /// 设置持久化状态存储，归档任务和内置生命周期管理器的文件状态随之写入
+    pub fn with_state_store(mut self, store: Arc<StateStore>) -> Self {
+        self.lifecycle_manager.set_state_store(store.clone());
+        self.state_store = Some(store);
+        self
+    }
+
+    /// 从状态存储加载任务历史和生命周期状态，并恢复上次运行中断的归档任务
+    ///
+    /// 应在添加存储管理器和归档策略之后调用。归档对象完整（头部的大小和哈希校验通过）
+    /// 的任务视为已完成并删除残留的源文件，否则删除不完整的归档对象并将任务标记为失败，
+    /// 下次自动归档时重新执行
+    pub async fn load_state(&mut self) -> Result<StateRecovery> {
+        let Some(store) = self.state_store.clone() else {
+            return Ok(StateRecovery::default());
+        };
+        self.lifecycle_manager.load_state().await?;
+
+        let mut recovery = StateRecovery::default();
+        let mut history = Vec::new();
+        for (_, mut task) in store.load::<ArchiveTask>(ARCHIVE_TASKS).await? {
+            recovery.loaded += 1;
+            if !matches!(
+                task.status,
+                ArchiveTaskStatus::Pending | ArchiveTaskStatus::InProgress
+            ) {
+                history.push(task);
+                continue;
+            }
+
+            match self.recover_archive_task(&mut task).await {
+                Ok(true) => {
+                    info!("Completed interrupted archive task: {}", task.id);
+                    task.status = ArchiveTaskStatus::Completed;
+                    self.lifecycle_manager
+                        .mark_stage(&task.file_path, LifecycleStage::Archive);
+                    recovery.rolled_forward += 1;
+                }
+                Ok(false) => {
+                    info!("Rolled back interrupted archive task: {}", task.id);
+                    task.status = ArchiveTaskStatus::Failed;
+                    task.error_message = Some("Interrupted before completion".to_string());
+                    recovery.rolled_back += 1;
+                }
+                Err(e) => {
+                    error!("Failed to recover archive task {}: {}", task.id, e);
+                    task.status = ArchiveTaskStatus::Failed;
+                    task.error_message = Some(format!("Interrupted before completion: {}", e));
+                    recovery.unresolved += 1;
+                }
+            }
+            task.end_time = Some(Utc::now());
+
+            let mut txn = StateTransaction::new();
+            txn.put(ARCHIVE_TASKS, &task.id, &task)?;
+            self.lifecycle_manager
+                .stage_status(&mut txn, &task.file_path)?;
+            store.commit(txn).await?;
+            history.push(task);
+        }
+
+        history.retain(|task| !self.task_history.iter().any(|t| t.id == task.id));
+        self.task_history.extend(history);
+        self.task_history.sort_by_key(|t| t.start_time);
+
+        info!(
+            "Loaded archive state: {} tasks, {} interrupted tasks completed, {} rolled back, {} unresolved",
+            recovery.loaded, recovery.rolled_forward, recovery.rolled_back, recovery.unresolved
+        );
+        Ok(recovery)
+    }
+
+    /// 判断中断的归档任务应完成（true）还是回滚（false），并清理另一侧的残留
+    async fn recover_archive_task(&self, task: &mut ArchiveTask) -> Result<bool> {
+        // 写入归档对象前的中断不会留下任何副本
+        if task.archive_path.is_empty() {
+            return Ok(false);
+        }
+
+        let policy = self
+            .policies
+            .get(&task.policy_name)
+            .ok_or_else(|| PacsError::Config("Archive policy not found".to_string()))?;
+        let source_storage = self
+            .storage_managers
+            .values()
+            .next()
+            .ok_or_else(|| PacsError::Config("No storage manager available".to_string()))?;
+        let target_storage = StorageManager::new(policy.target_storage.clone()).await?;
+
+        let source_exists = source_storage.file_exists(&task.file_path).await?;
+        let archived_size = match target_storage.get_file(&task.archive_path).await {
+            Ok(object) => decode_archive_object(&object)
+                .ok()
+                .map(|_| object.len() as u64),
+            Err(_) => None,
+        };
+
+        match archived_size {
+            Some(size) => {
+                if source_exists {
+                    source_storage.delete_file(&task.file_path).await?;
+                }
+                task.archive_size = Some(size);
+                Ok(true)
+            }
+            None if source_exists => {
+                if target_storage
+                    .file_exists(&task.archive_path)
+                    .await
+                    .unwrap_or(false)
+                {
+                    target_storage.delete_file(&task.archive_path).await?;
+                }
+                Ok(false)
+            }
+            None => Err(PacsError::Storage(format!(
+                "Neither {} nor a valid archive copy at {} exists",
+                task.file_path, task.archive_path
+            ))),
         }
     }
 
@@ -136,18 +317,37 @@ impl ArchiveManager {
         self.policies.insert(policy.name.clone(), policy);
     }
 
+    /// 获取生命周期管理器，用于登记文件和记录访问
+    pub fn lifecycle_manager_mut(&mut self) -> &mut LifecycleManager {
+        &mut self.lifecycle_manager
+    }
+
     /// 手动归档文件
     pub async fn archive_file(&mut self, file_path: &str, policy_name: &str) -> Result<String> {
+        self.start_archive_task(file_path, policy_name, None).await
+    }
+
+    /// 创建并执行归档任务
+    async fn start_archive_task(
+        &mut self,
+        file_path: &str,
+        policy_name: &str,
+        batch_id: Option<String>,
+    ) -> Result<String> {
         let policy = self
             .policies
             .get(policy_name)
-            .ok_or_else(|| PacsError::configuration("Archive policy not found"))?;
+            .ok_or_else(|| PacsError::Config("Archive policy not found".to_string()))?;
 
         if !policy.enabled {
-            return Err(PacsError::configuration("Archive policy is disabled"));
+            return Err(PacsError::Config("Archive policy is disabled".to_string()));
         }
 
-        let task_id = format!("archive_{}_{}", policy_name, Utc::now().timestamp());
+        let task_id = format!(
+            "archive_{}_{}",
+            policy_name,
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        );
 
         let task = ArchiveTask {
             id: task_id.clone(),
@@ -162,6 +362,8 @@ impl ArchiveManager {
             archive_size: None,
             compression_ratio: None,
             error_message: None,
+            batch_id,
+            compression: None,
         };
 
         self.active_tasks.insert(task_id.clone(), task);
@@ -176,26 +378,63 @@ impl ArchiveManager {
 
     /// 执行归档任务
     async fn execute_archive_task(&mut self, task_id: &str) -> Result<()> {
-        let task = self
+        let mut task = self
             .active_tasks
-            .get_mut(task_id)
-            .ok_or_else(|| PacsError::configuration("Archive task not found"))?;
+            .remove(task_id)
+            .ok_or_else(|| PacsError::Config("Archive task not found".to_string()))?;
+
+        let result = self.run_archive_task(&mut task).await;
+        match &result {
+            Ok(()) => {
+                task.status = ArchiveTaskStatus::Completed;
+                info!(
+                    "Archive task completed: {} (compressed to {} bytes, ratio: {:.2}%)",
+                    task_id,
+                    task.archive_size.unwrap_or(0),
+                    task.compression_ratio.unwrap_or(0.0) * 100.0
+                );
+            }
+            Err(e) => {
+                error!("Archive task {} failed: {}", task_id, e);
+                task.status = ArchiveTaskStatus::Failed;
+                task.error_message = Some(e.to_string());
+            }
+        }
+        task.end_time = Some(Utc::now());
+
+        // 更新生命周期管理，任务结果与文件阶段在同一事务中持久化
+        if result.is_ok() {
+            self.lifecycle_manager
+                .mark_stage(&task.file_path, LifecycleStage::Archive);
+        }
+        let mut txn = StateTransaction::new();
+        txn.put(ARCHIVE_TASKS, &task.id, &task)?;
+        self.lifecycle_manager
+            .stage_status(&mut txn, &task.file_path)?;
+        self.task_history.push(task);
+        if let Some(store) = &self.state_store {
+            store.commit(txn).await?;
+        }
+
+        result
+    }
 
+    /// 读取、压缩并写入归档对象，成功后删除源文件
+    async fn run_archive_task(&self, task: &mut ArchiveTask) -> Result<()> {
         let policy = self
             .policies
             .get(&task.policy_name)
-            .ok_or_else(|| PacsError::configuration("Archive policy not found"))?;
+            .ok_or_else(|| PacsError::Config("Archive policy not found".to_string()))?;
 
         task.status = ArchiveTaskStatus::InProgress;
-
-        info!("Executing archive task: {}", task_id);
+        info!("Executing archive task: {}", task.id);
 
         // 获取源存储管理器（默认使用第一个存储管理器）
         let source_storage = self
             .storage_managers
             .values()
             .next()
-            .ok_or_else(|| PacsError::configuration("No storage manager available"))?;
+            .ok_or_else(|| PacsError::Config("No storage manager available".to_string()))?;
 
         // 获取文件信息
         let file_data = source_storage.get_file(&task.file_path).await?;
@@ -205,56 +444,50 @@ impl ArchiveManager {
         let target_storage = StorageManager::new(policy.target_storage.clone()).await?;
 
         // 生成归档路径
-        let archive_path = self.generate_archive_path(&task.file_path);
-        task.archive_path = archive_path.clone();
-
-        // 执行压缩（如果启用）
-        let processed_data = if let Some(compression_settings) = &policy.compression_settings {
-            self.compress_data(&file_data, compression_settings).await?
+        task.archive_path = self.generate_archive_path(&task.file_path);
+
+        // 按模态选择压缩设置，写入自描述的归档对象
+        let settings = Self::compression_for(policy, &file_data);
+        let archive_object = encode_archive_object(&file_data, settings)?;
+        task.compression = settings.cloned();
+        task.archive_size = Some(archive_object.len() as u64);
+        task.compression_ratio = Some(if file_data.is_empty() {
+            0.0
         } else {
-            file_data
-        };
-
-        task.archive_size = Some(processed_data.len() as u64);
-        task.compression_ratio = Some(1.0 - (processed_data.len() as f64 / file_data.len() as f64));
+            1.0 - (archive_object.len() as f64 / file_data.len() as f64)
+        });
+
+        // 写入归档对象前持久化任务，中断后据此回滚或完成
+        if let Some(store) = &self.state_store {
+            let mut txn = StateTransaction::new();
+            txn.put(ARCHIVE_TASKS, &task.id, &*task)?;
+            store.commit(txn).await?;
+        }
 
         // 存储到归档位置
         target_storage
-            .store_file(&processed_data, &archive_path)
+            .store_file(&archive_object, &task.archive_path)
             .await?;
 
         // 从源存储删除原文件
         source_storage.delete_file(&task.file_path).await?;
 
-        // 更新任务状态
-        task.status = ArchiveTaskStatus::Completed;
-        task.end_time = Some(Utc::now());
-
-        info!(
-            "Archive task completed: {} (compressed to {} bytes, ratio: {:.2}%)",
-            task_id,
-            processed_data.len(),
-            task.compression_ratio.unwrap_or(0.0) * 100.0
-        );
-
-        // 移动到历史记录
-        if let Some(completed_task) = self.active_tasks.remove(task_id) {
-            self.task_history.push(completed_task);
-        }
+        Ok(())
+    }
 
-        // 更新生命周期管理
-        if let Err(e) = self
-            .lifecycle_manager
-            .transition_file(&task.file_path, LifecycleStage::Archive)
-            .await
-        {
-            warn!(
-                "Failed to update lifecycle status for {}: {}",
-                task.file_path, e
-            );
+    /// 选择压缩设置：模态覆盖优先，其次为策略默认设置
+    fn compression_for<'a>(
+        policy: &'a ArchivePolicy,
+        data: &[u8],
+    ) -> Option<&'a CompressionSettings> {
+        if !policy.modality_compression.is_empty() {
+            if let Some(settings) =
+                dicom_modality(data).and_then(|m| policy.modality_compression.get(&m))
+            {
+                return Some(settings);
+            }
         }
-
-        Ok(())
+        policy.compression_settings.as_ref()
     }
 
     /// 生成归档路径
@@ -268,61 +501,45 @@ impl ArchiveManager {
         format!("archive/{}/{}/{}", date, Utc::now().timestamp(), filename)
     }
 
-    /// 压缩数据
-    async fn compress_data(&self, data: &[u8], settings: &CompressionSettings) -> Result<Vec<u8>> {
-        match settings.algorithm {
-            CompressionAlgorithm::Gzip => {
-                use flate2::write::GzEncoder;
-                use flate2::Compression;
-                use std::io::Write;
-
-                let mut encoder =
-                    GzEncoder::new(Vec::new(), Compression::new(settings.level.into()));
-                encoder.write_all(data)?;
-                Ok(encoder.finish()?)
-            }
-            CompressionAlgorithm::Zstd => {
-                // TODO: 实现zstd压缩
-                warn!("Zstd compression not yet implemented, using original data");
-                Ok(data.to_vec())
-            }
-            CompressionAlgorithm::Lz4 => {
-                // TODO: 实现lz4压缩
-                warn!("LZ4 compression not yet implemented, using original data");
-                Ok(data.to_vec())
-            }
-        }
-    }
-
     /// 自动归档处理
+    ///
+    /// 扫描源存储找出满足策略条件的文件，按策略的批次大小分批创建归档任务
     pub async fn process_auto_archive(&mut self) -> Result<Vec<String>> {
         let mut created_tasks = Vec::new();
-
-        for (policy_name, policy) in &self.policies {
-            if !policy.enabled {
-                continue;
-            }
-
-            info!("Processing auto archive for policy: {}", policy_name);
-
-            // 获取符合条件的文件
-            let eligible_files = self.find_eligible_files(policy).await?;
-
-            for file_path in eligible_files {
-                // 检查是否已有归档任务
-                let has_active_task = self
-                    .active_tasks
-                    .values()
-                    .any(|t| t.file_path == file_path && t.policy_name == *policy_name);
-
-                let has_completed_task = self
-                    .task_history
-                    .iter()
-                    .any(|t| t.file_path == file_path && t.policy_name == *policy_name);
-
-                if !has_active_task && !has_completed_task {
-                    if let Ok(task_id) = self.archive_file(&file_path, policy_name).await {
-                        created_tasks.push(task_id);
+        let policies: Vec<ArchivePolicy> = self
+            .policies
+            .values()
+            .filter(|policy| policy.enabled)
+            .cloned()
+            .collect();
+
+        for policy in policies {
+            info!("Processing auto archive for policy: {}", policy.name);
+
+            // 获取符合条件且尚无归档任务的文件
+            let pending: Vec<ObjectMetadata> = self
+                .find_eligible_files(&policy)
+                .await?
+                .into_iter()
+                .filter(|object| !self.has_archive_task(&object.key, &policy.name))
+                .collect();
+
+            for (index, batch) in pending.chunks(policy.batch_size.max(1)).enumerate() {
+                let batch_id =
+                    format!("batch_{}_{}_{}", policy.name, Utc::now().timestamp(), index);
+                info!(
+                    "Starting archive batch {} with {} files",
+                    batch_id,
+                    batch.len()
+                );
+
+                for object in batch {
+                    match self
+                        .start_archive_task(&object.key, &policy.name, Some(batch_id.clone()))
+                        .await
+                    {
+                        Ok(task_id) => created_tasks.push(task_id),
+                        Err(e) => warn!("Failed to archive {}: {}", object.key, e),
                     }
                 }
             }
@@ -335,24 +552,101 @@ impl ArchiveManager {
         Ok(created_tasks)
     }
 
+    /// 默认的自动归档任务定义：每30分钟检查一次，只在01:00-05:00之间启动
+    pub fn default_job_definition() -> JobDefinition {
+        let window = MaintenanceWindow::new(
+            NaiveTime::from_hms_opt(1, 0, 0).unwrap_or_default(),
+            NaiveTime::from_hms_opt(5, 0, 0).unwrap_or_default(),
+        );
+        JobDefinition::new("archive", DEFAULT_ARCHIVE_SCHEDULE).with_maintenance_window(window)
+    }
+
+    /// 注册自动归档定时任务
+    pub async fn schedule_jobs(
+        manager: Arc<Mutex<Self>>,
+        scheduler: &JobScheduler,
+        definition: JobDefinition,
+    ) -> Result<()> {
+        scheduler
+            .register(definition, Arc::new(ArchiveJob { manager }))
+            .await
+    }
+
+    /// 文件是否已有该策略的活跃或历史归档任务
+    fn has_archive_task(&self, file_path: &str, policy_name: &str) -> bool {
+        self.active_tasks
+            .values()
+            .chain(self.task_history.iter())
+            .filter(|t| t.status != ArchiveTaskStatus::Failed)
+            .any(|t| t.file_path == file_path && t.policy_name == policy_name)
+    }
+
     /// 查找符合条件的文件
-    async fn find_eligible_files(&self, policy: &ArchivePolicy) -> Result<Vec<String>> {
+    ///
+    /// 分页遍历源存储，跳过归档目录和已处于归档/待删除阶段的文件；
+    /// 没有任何条件的策略不会匹配文件
+    async fn find_eligible_files(&self, policy: &ArchivePolicy) -> Result<Vec<ObjectMetadata>> {
         let mut eligible_files = Vec::new();
+        if policy.conditions.is_empty() {
+            warn!("Archive policy {} has no conditions, skipping", policy.name);
+            return Ok(eligible_files);
+        }
 
-        // 简化实现，实际应用中需要遍历存储并检查每个文件
-        // 这里提供一个基本的框架
-
-        // 获取存储管理器
         let storage_manager = self
             .storage_managers
             .values()
             .next()
-            .ok_or_else(|| PacsError::configuration("No storage manager available"))?;
+            .ok_or_else(|| PacsError::Config("No storage manager available".to_string()))?;
+
+        let now = Utc::now();
+        let mut scanned = 0usize;
+        let mut start_after: Option<String> = None;
+        loop {
+            let page = storage_manager
+                .list_files_page(None, start_after.as_deref(), SCAN_PAGE_SIZE)
+                .await?;
+            scanned += page.objects.len();
+
+            for object in page.objects {
+                if object.key.starts_with(ARCHIVE_PREFIX) {
+                    continue;
+                }
 
-        // TODO: 实现文件遍历和条件检查逻辑
-        // 这里需要根据具体的存储类型实现文件列表获取
+                let status = self.lifecycle_manager.get_file_status(&object.key);
+                if status.is_some_and(|status| {
+                    matches!(
+                        status.current_stage,
+                        LifecycleStage::Archive | LifecycleStage::PendingDeletion
+                    )
+                }) {
+                    continue;
+                }
+
+                let created_at = status
+                    .map(|status| status.created_at)
+                    .unwrap_or(object.last_modified);
+                let recent_accesses = self.lifecycle_manager.recent_access_count(&object.key);
+                if policy
+                    .conditions
+                    .iter()
+                    .all(|condition| condition.matches(&object, created_at, recent_accesses, now))
+                {
+                    eligible_files.push(object);
+                }
+            }
 
-        debug!("Searching for eligible files for policy: {}", policy.name);
+            match page.next_start_after {
+                Some(next) => start_after = Some(next),
+                None => break,
+            }
+        }
+
+        debug!(
+            "Policy {}: {} of {} scanned files eligible for archive",
+            policy.name,
+            eligible_files.len(),
+            scanned
+        );
 
         Ok(eligible_files)
     }
@@ -364,7 +658,9 @@ impl ArchiveManager {
             .task_history
             .iter()
             .find(|t| t.id == task_id && t.status == ArchiveTaskStatus::Completed)
-            .ok_or_else(|| PacsError::configuration("Archive task not found or not completed"))?;
+            .ok_or_else(|| {
+                PacsError::Config("Archive task not found or not completed".to_string())
+            })?;
 
         info!(
             "Restoring file from archive: {} to {}",
@@ -375,7 +671,7 @@ impl ArchiveManager {
         let policy = self
             .policies
             .get(&archive_task.policy_name)
-            .ok_or_else(|| PacsError::configuration("Archive policy not found"))?;
+            .ok_or_else(|| PacsError::Config("Archive policy not found".to_string()))?;
 
         // 创建归档存储管理器
         let archive_storage = StorageManager::new(policy.target_storage.clone()).await?;
@@ -383,54 +679,72 @@ impl ArchiveManager {
         // 读取归档文件
         let archived_data = archive_storage.get_file(&archive_task.archive_path).await?;
 
-        // 解压缩（如果需要）
-        let restored_data = if policy.compression_settings.is_some() {
-            self.decompress_data(
-                &archived_data,
-                &policy.compression_settings.as_ref().unwrap(),
-            )
-            .await?
+        // 解码归档对象；没有头部的旧对象只有Gzip真正压缩过
+        let restored_data = if ArchiveObjectHeader::parse(&archived_data)?.is_some() {
+            decode_archive_object(&archived_data)?.1
         } else {
-            archived_data
+            match &policy.compression_settings {
+                Some(settings) if settings.algorithm == CompressionAlgorithm::Gzip => decompress(
+                    &archived_data,
+                    CompressionAlgorithm::Gzip,
+                    archive_task.original_size as usize,
+                )?,
+                _ => archived_data,
+            }
         };
 
-        // 存储到目标位置
+        self.store_restored(&restored_data, target_path).await
+    }
+
+    /// 直接从归档对象恢复文件，不依赖归档任务历史
+    ///
+    /// 归档对象须带有自描述头部，解压后校验原始大小与哈希
+    pub async fn restore_from_archive(
+        &self,
+        archive_storage: &StorageConfig,
+        archive_path: &str,
+        target_path: &str,
+    ) -> Result<ArchiveObjectHeader> {
+        let archive_storage = StorageManager::new(archive_storage.clone()).await?;
+        let archived_data = archive_storage.get_file(archive_path).await?;
+        let (header, restored_data) = decode_archive_object(&archived_data)?;
+
+        info!(
+            "Restoring file from archive object: {} to {}",
+            archive_path, target_path
+        );
+        self.store_restored(&restored_data, target_path).await?;
+        Ok(header)
+    }
+
+    /// 将恢复的数据写入源存储
+    async fn store_restored(&self, data: &[u8], target_path: &str) -> Result<()> {
         let target_storage = self
             .storage_managers
             .values()
             .next()
-            .ok_or_else(|| PacsError::configuration("No storage manager available"))?;
+            .ok_or_else(|| PacsError::Config("No storage manager available".to_string()))?;
 
-        target_storage
-            .store_file(&restored_data, target_path)
-            .await?;
+        target_storage.store_file(data, target_path).await?;
 
         info!("File restored successfully: {}", target_path);
 
         Ok(())
     }
 
-    /// 解压缩数据
-    async fn decompress_data(
-        &self,
-        data: &[u8],
-        settings: &CompressionSettings,
-    ) -> Result<Vec<u8>> {
-        match settings.algorithm {
-            CompressionAlgorithm::Gzip => {
-                use flate2::read::GzDecoder;
-                use std::io::Read;
-
-                let mut decoder = GzDecoder::new(data);
-                let mut decompressed = Vec::new();
-                decoder.read_to_end(&mut decompressed)?;
-                Ok(decompressed)
-            }
-            _ => {
-                warn!("Decompression not implemented for algorithm, using original data");
-                Ok(data.to_vec())
-            }
-        }
+    /// 查找文件最近一次完成归档的对象位置
+    pub fn archived_location(&self, file_path: &str) -> Option<ArchivedObject> {
+        let task = self
+            .task_history
+            .iter()
+            .rev()
+            .find(|t| t.file_path == file_path && t.status == ArchiveTaskStatus::Completed)?;
+        let policy = self.policies.get(&task.policy_name)?;
+        Some(ArchivedObject {
+            storage: policy.target_storage.clone(),
+            archive_path: task.archive_path.clone(),
+            archived_at: task.end_time.unwrap_or(task.start_time),
+        })
     }
 
     /// 获取归档任务列表
@@ -452,11 +766,10 @@ impl ArchiveManager {
                 ArchiveCondition::AccessFrequencyLessThan(10), // 30天内访问少于10次
             ],
             target_storage,
-            compression_settings: Some(CompressionSettings {
-                algorithm: CompressionAlgorithm::Gzip,
-                level: 6,
-            }),
+            compression_settings: Some(CompressionSettings::new(CompressionAlgorithm::Zstd, 3)),
+            modality_compression: default_modality_compression(),
             enabled: true,
+            batch_size: DEFAULT_ARCHIVE_BATCH_SIZE,
         }
     }
 }
@@ -466,3 +779,153 @@ impl Default for ArchiveManager {
         Self::new()
     }
 }
+
+/// 自动归档定时任务
+pub struct ArchiveJob {
+    /// 归档管理器
+    manager: Arc<Mutex<ArchiveManager>>,
+}
+
+#[async_trait]
+impl ScheduledJob for ArchiveJob {
+    async fn run(&self) -> Result<String> {
+        let created = self.manager.lock().await.process_auto_archive().await?;
+        Ok(format!("{} archive tasks created", created.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageType;
+
+    #[test]
+    fn test_archive_conditions() {
+        let now = Utc::now();
+        let object = ObjectMetadata {
+            key: "1.2.3/1.2.3.4/1.2.3.4.5.dcm".to_string(),
+            size: 4096,
+            last_modified: now,
+            e_tag: None,
+        };
+        let created_at = now - Duration::days(400);
+
+        assert!(ArchiveCondition::TimeBasedDays(365).matches(&object, created_at, 0, now));
+        assert!(!ArchiveCondition::TimeBasedDays(365).matches(&object, now, 0, now));
+        assert!(ArchiveCondition::FileSizeGreaterThan(1024).matches(&object, now, 0, now));
+        assert!(ArchiveCondition::AccessFrequencyLessThan(10).matches(&object, now, 9, now));
+        assert!(!ArchiveCondition::AccessFrequencyLessThan(10).matches(&object, now, 10, now));
+        assert!(ArchiveCondition::PathPrefix("1.2.3/".to_string()).matches(&object, now, 0, now));
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_archive_recovery() {
+        let root = std::env::temp_dir().join(format!(
+            "pacs-archive-state-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let local = |dir: &str| StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(root.join(dir).to_string_lossy().to_string()),
+            object_store_config: None,
+        };
+        let online = StorageManager::new(local("online")).await.unwrap();
+        let archive_storage = StorageManager::new(local("archive")).await.unwrap();
+        let policy = ArchiveManager::create_default_policy(local("archive"));
+        let policy_name = policy.name.clone();
+
+        let store = Arc::new(StateStore::open(root.join("state")).await.unwrap());
+        let mut manager = ArchiveManager::new().with_state_store(store.clone());
+        manager.add_storage_manager("online".to_string(), online.clone());
+        manager.add_policy(policy.clone());
+        for name in ["done.dcm", "copied.dcm", "partial.dcm"] {
+            online.store_file(name.as_bytes(), name).await.unwrap();
+            manager
+                .lifecycle_manager_mut()
+                .register_file(name, None)
+                .await
+                .unwrap();
+        }
+        manager
+            .archive_file("done.dcm", &policy_name)
+            .await
+            .unwrap();
+
+        // 模拟写入归档对象后（copied）和写入过程中（partial）崩溃的任务
+        let mut txn = StateTransaction::new();
+        for name in ["copied.dcm", "partial.dcm"] {
+            let task = ArchiveTask {
+                id: format!("archive_{}", name),
+                policy_name: policy_name.clone(),
+                file_path: name.to_string(),
+                original_path: name.to_string(),
+                archive_path: format!("archive/{}", name),
+                status: ArchiveTaskStatus::InProgress,
+                start_time: Utc::now(),
+                end_time: None,
+                original_size: name.len() as u64,
+                archive_size: None,
+                compression_ratio: None,
+                error_message: None,
+                batch_id: None,
+                compression: None,
+            };
+            txn.put(ARCHIVE_TASKS, &task.id, &task).unwrap();
+        }
+        store.commit(txn).await.unwrap();
+        let object = encode_archive_object(b"copied.dcm", None).unwrap();
+        archive_storage
+            .store_file(&object, "archive/copied.dcm")
+            .await
+            .unwrap();
+        archive_storage
+            .store_file(&object[..10], "archive/partial.dcm")
+            .await
+            .unwrap();
+        drop(manager);
+        drop(store);
+
+        let store = Arc::new(StateStore::open(root.join("state")).await.unwrap());
+        let mut manager = ArchiveManager::new().with_state_store(store);
+        manager.add_storage_manager("online".to_string(), online.clone());
+        manager.add_policy(policy);
+        manager
+            .lifecycle_manager_mut()
+            .add_storage_manager(LifecycleStage::Online, online.clone());
+        let recovery = manager.load_state().await.unwrap();
+        assert_eq!(
+            recovery,
+            StateRecovery {
+                loaded: 3,
+                rolled_forward: 1,
+                rolled_back: 1,
+                unresolved: 0,
+            }
+        );
+
+        // 完整的归档副本：完成任务并删除源文件
+        assert!(!online.file_exists("copied.dcm").await.unwrap());
+        assert!(manager.archived_location("copied.dcm").is_some());
+        // 不完整的归档副本：删除副本，源文件保留，任务失败后可重新归档
+        assert!(online.file_exists("partial.dcm").await.unwrap());
+        assert!(!archive_storage
+            .file_exists("archive/partial.dcm")
+            .await
+            .unwrap());
+        assert!(!manager.has_archive_task("partial.dcm", &policy_name));
+
+        let lifecycle = manager.lifecycle_manager_mut();
+        for (name, stage) in [
+            ("done.dcm", LifecycleStage::Archive),
+            ("copied.dcm", LifecycleStage::Archive),
+            ("partial.dcm", LifecycleStage::Online),
+        ] {
+            assert_eq!(
+                lifecycle.get_file_status(name).unwrap().current_stage,
+                stage
+            );
+        }
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+}
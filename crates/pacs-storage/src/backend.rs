@@ -0,0 +1,795 @@
+//! 存储后端抽象
+//!
+//! [`StorageBackend`] 统一了本地文件系统、S3兼容存储（含MinIO）、GCS、Azure
+//! 与内存存储的对象操作，归档、备份和生命周期代码只需面向该trait编写，
+//! 测试时可直接使用内存后端。
+
+use crate::storage::{
+    AwsS3Config, AzureConfig, ByteRange, ByteStream, GcsConfig, RangedStream, StorageConfig,
+    StorageType,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::{StreamExt, TryStreamExt};
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::memory::InMemory;
+use object_store::{path::Path as ObjectPath, GetOptions, GetRange, ObjectMeta, ObjectStore};
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+
+/// 写入过程中的临时文件后缀
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// 对象元数据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectMetadata {
+    /// 对象键
+    pub key: String,
+    /// 对象大小（字节）
+    pub size: u64,
+    /// 最后修改时间
+    pub last_modified: DateTime<Utc>,
+    /// 实体标签（本地存储为None）
+    pub e_tag: Option<String>,
+}
+
+/// 分页列举结果
+#[derive(Debug, Clone, Default)]
+pub struct ObjectPage {
+    /// 本页对象
+    pub objects: Vec<ObjectMetadata>,
+    /// 下一页的起始键，没有更多对象时为None
+    pub next_start_after: Option<String>,
+}
+
+/// 存储后端
+///
+/// 对象键使用 `/` 分隔的相对路径；`list` 的前缀按路径段匹配，
+/// 即前缀 `a/b` 匹配 `a/b/c` 而不匹配 `a/bc`
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// 后端名称，用于日志和监控
+    fn name(&self) -> &str;
+
+    /// 写入对象，已存在时覆盖
+    async fn put(&self, key: &str, data: Bytes) -> Result<ObjectMetadata>;
+
+    /// 以流方式写入对象，失败时不留下不完整的对象
+    async fn put_stream(
+        &self,
+        key: &str,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> Result<ObjectMetadata>;
+
+    /// 读取整个对象
+    async fn get(&self, key: &str) -> Result<Bytes>;
+
+    /// 以流方式读取整个对象
+    async fn get_stream(&self, key: &str) -> Result<ByteStream>;
+
+    /// 以流方式读取对象的指定字节范围
+    async fn get_range_stream(&self, key: &str, range: ByteRange) -> Result<RangedStream>;
+
+    /// 获取对象元数据，对象不存在时返回None
+    async fn head(&self, key: &str) -> Result<Option<ObjectMetadata>>;
+
+    /// 删除对象，对象不存在时视为成功
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// 列出前缀下的所有对象，按键排序
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ObjectMetadata>>;
+
+    /// 分页列出前缀下键大于 `start_after` 的对象，按键排序，每页最多 `limit` 个
+    async fn list_page(
+        &self,
+        prefix: Option<&str>,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Result<ObjectPage>;
+
+    /// 在同一后端内复制对象
+    async fn copy(&self, from: &str, to: &str) -> Result<ObjectMetadata>;
+}
+
+/// 按存储配置创建后端
+pub fn create_backend(config: &StorageConfig) -> Result<Arc<dyn StorageBackend>> {
+    match &config.storage_type {
+        StorageType::Local => {
+            let local_path = config.local_path.as_ref().ok_or_else(|| {
+                PacsError::Config("Local storage path not configured".to_string())
+            })?;
+            Ok(Arc::new(LocalBackend::new(local_path)))
+        }
+        StorageType::ObjectStorage => {
+            let os_config = config.object_store_config.as_ref().ok_or_else(|| {
+                PacsError::Config("Missing object store configuration".to_string())
+            })?;
+
+            let backend = if let Some(aws_config) = &os_config.aws {
+                ObjectStoreBackend::s3(aws_config)?
+            } else if let Some(gcs_config) = &os_config.gcs {
+                ObjectStoreBackend::gcs(gcs_config)?
+            } else if let Some(azure_config) = &os_config.azure {
+                ObjectStoreBackend::azure(azure_config)?
+            } else {
+                return Err(PacsError::Config(
+                    "No valid object store configuration found".to_string(),
+                ));
+            };
+            Ok(Arc::new(backend))
+        }
+        StorageType::Memory => Ok(Arc::new(ObjectStoreBackend::in_memory())),
+    }
+}
+
+/// 本地文件系统后端
+pub struct LocalBackend {
+    /// 存储根目录
+    root: PathBuf,
+}
+
+impl LocalBackend {
+    /// 创建以指定目录为根的后端
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// 存储根目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 将对象键解析为根目录下的路径，拒绝逃逸根目录的键
+    fn resolve(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        if key.is_empty()
+            || !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(PacsError::Validation(format!(
+                "Invalid object key: {}",
+                key
+            )));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    /// 临时文件路径
+    fn partial_path(path: &Path) -> PathBuf {
+        let mut partial = path.as_os_str().to_os_string();
+        partial.push(PARTIAL_SUFFIX);
+        PathBuf::from(partial)
+    }
+
+    /// 打开对象文件，文件不存在时返回NotFound
+    async fn open(&self, key: &str) -> Result<tokio::fs::File> {
+        match tokio::fs::File::open(self.resolve(key)?).await {
+            Ok(file) => Ok(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(PacsError::NotFound(format!("Object {}", key)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 读取文件元数据
+    fn metadata(key: String, metadata: &std::fs::Metadata) -> ObjectMetadata {
+        ObjectMetadata {
+            key,
+            size: metadata.len(),
+            last_modified: metadata
+                .modified()
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_else(|_| Utc::now()),
+            e_tag: None,
+        }
+    }
+
+    /// 将临时文件移入最终位置，失败时清理临时文件
+    async fn commit(
+        &self,
+        key: &str,
+        path: &Path,
+        result: std::io::Result<()>,
+    ) -> Result<ObjectMetadata> {
+        let partial_path = Self::partial_path(path);
+        let result = match result {
+            Ok(()) => tokio::fs::rename(&partial_path, path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&partial_path).await;
+            return Err(e.into());
+        }
+
+        let metadata = tokio::fs::metadata(path).await?;
+        Ok(Self::metadata(key.to_string(), &metadata))
+    }
+
+    /// 读取目录项，按对象键顺序排列（目录按 `名称/` 参与比较）
+    async fn read_entries(&self, dir: &Path, dir_key: &str) -> Result<Vec<LocalEntry>> {
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut result = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let metadata = entry.metadata().await?;
+            let key = if dir_key.is_empty() {
+                name
+            } else {
+                format!("{}/{}", dir_key, name)
+            };
+            if metadata.is_dir() {
+                result.push(LocalEntry {
+                    sort_key: format!("{}/", key),
+                    key,
+                    metadata: None,
+                });
+            } else if !key.ends_with(PARTIAL_SUFFIX) {
+                result.push(LocalEntry {
+                    sort_key: key.clone(),
+                    key,
+                    metadata: Some(metadata),
+                });
+            }
+        }
+
+        result.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
+        Ok(result)
+    }
+}
+
+/// 目录遍历中的条目
+struct LocalEntry {
+    /// 对象键（目录为其相对路径）
+    key: String,
+    /// 排序键，目录附加 `/` 以保证遍历顺序与对象键的字典序一致
+    sort_key: String,
+    /// 文件元数据（目录为None）
+    metadata: Option<std::fs::Metadata>,
+}
+
+#[async_trait]
+impl StorageBackend for LocalBackend {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn put(&self, key: &str, data: Bytes) -> Result<ObjectMetadata> {
+        let path = self.resolve(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let result = tokio::fs::write(Self::partial_path(&path), &data).await;
+        self.commit(key, &path, result).await
+    }
+
+    async fn put_stream(
+        &self,
+        key: &str,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> Result<ObjectMetadata> {
+        let path = self.resolve(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let partial_path = Self::partial_path(&path);
+        let result = async {
+            let mut file = tokio::fs::File::create(&partial_path).await?;
+            tokio::io::copy(reader, &mut file).await?;
+            file.sync_all().await
+        }
+        .await;
+        self.commit(key, &path, result).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes> {
+        let mut file = self.open(key).await?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await?;
+        Ok(Bytes::from(data))
+    }
+
+    async fn get_stream(&self, key: &str) -> Result<ByteStream> {
+        let file = self.open(key).await?;
+        Ok(ReaderStream::new(file).map_err(PacsError::from).boxed())
+    }
+
+    async fn get_range_stream(&self, key: &str, range: ByteRange) -> Result<RangedStream> {
+        let mut file = self.open(key).await?;
+        let total_size = file.metadata().await?.len();
+        let resolved = range
+            .resolve(total_size)
+            .ok_or_else(|| unsatisfiable(key, &range, total_size))?;
+
+        file.seek(std::io::SeekFrom::Start(resolved.start)).await?;
+        let reader = file.take(resolved.end - resolved.start);
+        Ok(RangedStream {
+            stream: ReaderStream::new(reader).map_err(PacsError::from).boxed(),
+            range: resolved,
+            total_size,
+        })
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectMetadata>> {
+        match tokio::fs::metadata(self.resolve(key)?).await {
+            Ok(metadata) if metadata.is_file() => {
+                Ok(Some(Self::metadata(key.to_string(), &metadata)))
+            }
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.resolve(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ObjectMetadata>> {
+        Ok(self.list_page(prefix, None, usize::MAX).await?.objects)
+    }
+
+    async fn list_page(
+        &self,
+        prefix: Option<&str>,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Result<ObjectPage> {
+        let prefix = prefix
+            .map(|p| p.trim_matches('/'))
+            .filter(|p| !p.is_empty());
+        let (start, start_key) = match prefix {
+            Some(prefix) => (self.resolve(prefix)?, prefix.to_string()),
+            None => (self.root.clone(), String::new()),
+        };
+
+        let mut objects = Vec::new();
+        let mut pending = match tokio::fs::metadata(&start).await {
+            Ok(metadata) if metadata.is_file() => vec![LocalEntry {
+                sort_key: start_key.clone(),
+                key: start_key,
+                metadata: Some(metadata),
+            }],
+            Ok(_) => self.read_entries(&start, &start_key).await?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        pending.reverse();
+
+        // 栈顶始终是字典序最小的条目，按键顺序深度优先遍历
+        while let Some(entry) = pending.pop() {
+            if objects.len() >= limit {
+                break;
+            }
+
+            match entry.metadata {
+                Some(metadata) => {
+                    if start_after.is_some_and(|after| entry.key.as_str() <= after) {
+                        continue;
+                    }
+                    objects.push(Self::metadata(entry.key, &metadata));
+                }
+                None => {
+                    // 目录下的键全部不大于起始键时跳过整个目录
+                    if start_after.is_some_and(|after| {
+                        entry.sort_key.as_str() < after && !after.starts_with(&entry.sort_key)
+                    }) {
+                        continue;
+                    }
+                    let mut children = self
+                        .read_entries(&self.root.join(&entry.key), &entry.key)
+                        .await?;
+                    children.reverse();
+                    pending.extend(children);
+                }
+            }
+        }
+
+        let next_start_after = (objects.len() >= limit)
+            .then(|| objects.last().map(|object| object.key.clone()))
+            .flatten();
+        Ok(ObjectPage {
+            objects,
+            next_start_after,
+        })
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<ObjectMetadata> {
+        let source = self.resolve(from)?;
+        if tokio::fs::metadata(&source).await.is_err() {
+            return Err(PacsError::NotFound(format!("Object {}", from)));
+        }
+
+        let path = self.resolve(to)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let result = tokio::fs::copy(&source, Self::partial_path(&path))
+            .await
+            .map(|_| ());
+        self.commit(to, &path, result).await
+    }
+}
+
+/// 基于 `object_store` 的后端，覆盖S3兼容存储、GCS、Azure和内存存储
+pub struct ObjectStoreBackend {
+    /// 后端名称
+    name: String,
+    /// 对象存储客户端
+    store: Arc<dyn ObjectStore>,
+}
+
+impl ObjectStoreBackend {
+    /// 包装已有的对象存储客户端
+    pub fn new(name: &str, store: Arc<dyn ObjectStore>) -> Self {
+        Self {
+            name: name.to_string(),
+            store,
+        }
+    }
+
+    /// S3兼容存储（AWS S3、MinIO等）
+    ///
+    /// 配置了 `http://` 端点时允许非TLS连接，便于对接本地MinIO
+    pub fn s3(config: &AwsS3Config) -> Result<Self> {
+        let mut builder = AmazonS3Builder::new()
+            .with_bucket_name(&config.bucket)
+            .with_region(&config.region)
+            .with_access_key_id(&config.access_key_id)
+            .with_secret_access_key(&config.secret_access_key);
+
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+
+        let store = builder
+            .build()
+            .map_err(|e| PacsError::Config(format!("Invalid S3 configuration: {}", e)))?;
+        Ok(Self::new("s3", Arc::new(store)))
+    }
+
+    /// Google Cloud Storage
+    pub fn gcs(config: &GcsConfig) -> Result<Self> {
+        let store = GoogleCloudStorageBuilder::new()
+            .with_bucket_name(&config.bucket)
+            .with_service_account_key(&config.service_account_key)
+            .build()
+            .map_err(|e| PacsError::Config(format!("Invalid GCS configuration: {}", e)))?;
+        Ok(Self::new("gcs", Arc::new(store)))
+    }
+
+    /// Azure Blob Storage
+    pub fn azure(config: &AzureConfig) -> Result<Self> {
+        let store = MicrosoftAzureBuilder::new()
+            .with_account(&config.account)
+            .with_access_key(&config.access_key)
+            .with_container_name(&config.container)
+            .build()
+            .map_err(|e| PacsError::Config(format!("Invalid Azure configuration: {}", e)))?;
+        Ok(Self::new("azure", Arc::new(store)))
+    }
+
+    /// 内存存储，进程退出后数据丢失，主要用于测试
+    pub fn in_memory() -> Self {
+        Self::new("memory", Arc::new(InMemory::new()))
+    }
+
+    /// 将对象元数据转换为统一格式
+    fn metadata(meta: ObjectMeta) -> ObjectMetadata {
+        ObjectMetadata {
+            key: meta.location.to_string(),
+            size: meta.size as u64,
+            last_modified: meta.last_modified,
+            e_tag: meta.e_tag,
+        }
+    }
+
+    /// 读取写入后的对象元数据
+    async fn stored(&self, key: &str) -> Result<ObjectMetadata> {
+        self.head(key)
+            .await?
+            .ok_or_else(|| PacsError::Storage(format!("Object {} missing after write", key)))
+    }
+}
+
+/// 转换对象存储错误，对象不存在时映射为NotFound
+fn map_store_error(key: &str, error: object_store::Error) -> PacsError {
+    match error {
+        object_store::Error::NotFound { .. } => PacsError::NotFound(format!("Object {}", key)),
+        e => PacsError::Storage(format!("Object store operation on {} failed: {}", key, e)),
+    }
+}
+
+/// 字节范围不可满足的错误
+fn unsatisfiable(key: &str, range: &ByteRange, size: u64) -> PacsError {
+    PacsError::Validation(format!(
+        "Range {:?} not satisfiable for {} ({} bytes)",
+        range, key, size
+    ))
+}
+
+#[async_trait]
+impl StorageBackend for ObjectStoreBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn put(&self, key: &str, data: Bytes) -> Result<ObjectMetadata> {
+        let size = data.len() as u64;
+        let result = self
+            .store
+            .put(&ObjectPath::from(key), data)
+            .await
+            .map_err(|e| map_store_error(key, e))?;
+        Ok(ObjectMetadata {
+            key: key.to_string(),
+            size,
+            last_modified: Utc::now(),
+            e_tag: result.e_tag,
+        })
+    }
+
+    async fn put_stream(
+        &self,
+        key: &str,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> Result<ObjectMetadata> {
+        let object_path = ObjectPath::from(key);
+        let (multipart_id, mut writer) = self
+            .store
+            .put_multipart(&object_path)
+            .await
+            .map_err(|e| map_store_error(key, e))?;
+
+        let result = async {
+            tokio::io::copy(reader, &mut writer).await?;
+            writer.shutdown().await
+        }
+        .await;
+        if let Err(e) = result {
+            if let Err(abort_err) = self
+                .store
+                .abort_multipart(&object_path, &multipart_id)
+                .await
+            {
+                warn!(
+                    "Failed to abort multipart upload for {}: {}",
+                    key, abort_err
+                );
+            }
+            return Err(PacsError::Storage(format!(
+                "Multipart upload of {} failed: {}",
+                key, e
+            )));
+        }
+
+        self.stored(key).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes> {
+        let result = self
+            .store
+            .get(&ObjectPath::from(key))
+            .await
+            .map_err(|e| map_store_error(key, e))?;
+        result.bytes().await.map_err(|e| map_store_error(key, e))
+    }
+
+    async fn get_stream(&self, key: &str) -> Result<ByteStream> {
+        let result = self
+            .store
+            .get(&ObjectPath::from(key))
+            .await
+            .map_err(|e| map_store_error(key, e))?;
+        let key = key.to_string();
+        Ok(result
+            .into_stream()
+            .map_err(move |e| map_store_error(&key, e))
+            .boxed())
+    }
+
+    async fn get_range_stream(&self, key: &str, range: ByteRange) -> Result<RangedStream> {
+        let total_size = self
+            .head(key)
+            .await?
+            .ok_or_else(|| PacsError::NotFound(format!("Object {}", key)))?
+            .size;
+        let resolved = range
+            .resolve(total_size)
+            .ok_or_else(|| unsatisfiable(key, &range, total_size))?;
+
+        let options = GetOptions {
+            range: Some(GetRange::Bounded(
+                resolved.start as usize..resolved.end as usize,
+            )),
+            ..Default::default()
+        };
+        let result = self
+            .store
+            .get_opts(&ObjectPath::from(key), options)
+            .await
+            .map_err(|e| map_store_error(key, e))?;
+        let owned_key = key.to_string();
+        Ok(RangedStream {
+            stream: result
+                .into_stream()
+                .map_err(move |e| map_store_error(&owned_key, e))
+                .boxed(),
+            range: resolved,
+            total_size,
+        })
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectMetadata>> {
+        match self.store.head(&ObjectPath::from(key)).await {
+            Ok(meta) => Ok(Some(Self::metadata(meta))),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(map_store_error(key, e)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match self.store.delete(&ObjectPath::from(key)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(map_store_error(key, e)),
+        }
+    }
+
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ObjectMetadata>> {
+        let prefix = prefix.map(ObjectPath::from);
+        let mut objects: Vec<ObjectMetadata> = self
+            .store
+            .list(prefix.as_ref())
+            .map_ok(Self::metadata)
+            .try_collect()
+            .await
+            .map_err(|e| PacsError::Storage(format!("Failed to list objects: {}", e)))?;
+
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn list_page(
+        &self,
+        prefix: Option<&str>,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Result<ObjectPage> {
+        // S3/GCS/Azure和内存存储均按字典序返回，起始键由服务端过滤
+        let prefix = prefix.map(ObjectPath::from);
+        let listing = match start_after {
+            Some(start_after) => self
+                .store
+                .list_with_offset(prefix.as_ref(), &ObjectPath::from(start_after)),
+            None => self.store.list(prefix.as_ref()),
+        };
+        let objects: Vec<ObjectMetadata> = listing
+            .take(limit)
+            .map_ok(Self::metadata)
+            .try_collect()
+            .await
+            .map_err(|e| PacsError::Storage(format!("Failed to list objects: {}", e)))?;
+
+        let next_start_after = (objects.len() >= limit)
+            .then(|| objects.last().map(|object| object.key.clone()))
+            .flatten();
+        Ok(ObjectPage {
+            objects,
+            next_start_after,
+        })
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<ObjectMetadata> {
+        self.store
+            .copy(&ObjectPath::from(from), &ObjectPath::from(to))
+            .await
+            .map_err(|e| map_store_error(from, e))?;
+        self.stored(to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 对后端执行一组通用的读写断言
+    async fn exercise_backend(backend: &dyn StorageBackend) {
+        let stored = backend
+            .put("1.2.3/1.2.3.4/a.dcm", Bytes::from_static(b"hello world"))
+            .await
+            .unwrap();
+        assert_eq!(stored.size, 11);
+        backend
+            .put("1.2.30/b.dcm", Bytes::from_static(b"other"))
+            .await
+            .unwrap();
+
+        let copied = backend
+            .copy("1.2.3/1.2.3.4/a.dcm", "1.2.3/1.2.3.5/a.dcm")
+            .await
+            .unwrap();
+        assert_eq!(copied.size, 11);
+        assert_eq!(
+            backend.get("1.2.3/1.2.3.5/a.dcm").await.unwrap(),
+            Bytes::from_static(b"hello world")
+        );
+
+        let keys: Vec<String> = backend
+            .list(Some("1.2.3"))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|object| object.key)
+            .collect();
+        assert_eq!(keys, vec!["1.2.3/1.2.3.4/a.dcm", "1.2.3/1.2.3.5/a.dcm"]);
+
+        let first = backend.list_page(None, None, 2).await.unwrap();
+        let second = backend
+            .list_page(None, first.next_start_after.as_deref(), 2)
+            .await
+            .unwrap();
+        let keys: Vec<String> = first
+            .objects
+            .into_iter()
+            .chain(second.objects)
+            .map(|object| object.key)
+            .collect();
+        assert_eq!(
+            keys,
+            vec!["1.2.3/1.2.3.4/a.dcm", "1.2.3/1.2.3.5/a.dcm", "1.2.30/b.dcm"]
+        );
+
+        let ranged = backend
+            .get_range_stream("1.2.3/1.2.3.4/a.dcm", ByteRange::Suffix(5))
+            .await
+            .unwrap();
+        let chunks: Vec<Bytes> = ranged.stream.try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), b"world");
+
+        backend.delete("1.2.3/1.2.3.4/a.dcm").await.unwrap();
+        backend.delete("1.2.3/1.2.3.4/a.dcm").await.unwrap();
+        assert!(backend.head("1.2.3/1.2.3.4/a.dcm").await.unwrap().is_none());
+        assert!(matches!(
+            backend.get("1.2.3/1.2.3.4/a.dcm").await,
+            Err(PacsError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_memory_and_local_backends() {
+        exercise_backend(&ObjectStoreBackend::in_memory()).await;
+
+        let dir = std::env::temp_dir().join(format!(
+            "pacs-backend-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let local = LocalBackend::new(&dir);
+        exercise_backend(&local).await;
+        assert!(local.resolve("../escape.dcm").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
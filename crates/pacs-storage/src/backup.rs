@@ -1,16 +1,49 @@
 //! 备份和恢复机制
 
-use crate::storage::{StorageConfig, StorageManager};
+use crate::backend::ObjectMetadata;
+use crate::encryption::{KeyRing, ReportSignature, BACKUP_KEYS_ENV, BACKUP_KEY_FILE_ENV};
+use crate::scheduler::{JobDefinition, JobScheduler, ScheduledJob};
+use crate::state::{StateRecovery, StateStore, StateTransaction};
+use crate::storage::{StorageConfig, StorageManager, StorageType};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use async_trait::async_trait;
 use chrono::{DateTime, Duration, Utc};
+use dicom::dictionary_std::tags;
+use dicom::object::OpenFileOptions;
 use pacs_core::{PacsError, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
-use tokio::time::{interval, sleep};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 
+/// 扫描源存储时每页列举的对象数
+const BACKUP_LIST_PAGE_SIZE: usize = 1000;
+
+/// 备份清单目录名
+const MANIFEST_DIR: &str = "manifests";
+
+/// 备份对象的zstd压缩级别
+const BACKUP_COMPRESSION_LEVEL: i32 = 3;
+
+/// DICOM文件前导区长度
+const DICOM_PREAMBLE_LENGTH: usize = 128;
+
+/// 状态存储中备份历史的集合名（键为备份ID）
+pub const BACKUP_HISTORY: &str = "backup.history";
+
+/// 状态存储中进行中备份的集合名（键为备份ID）
+pub const BACKUP_ACTIVE: &str = "backup.active";
+
+/// 恢复演练任务名称
+pub const RESTORE_DRILL_JOB: &str = "backup:restore-drills";
+
+/// 恢复演练任务的检查计划：每小时整点，实际是否演练由各配置的演练间隔决定
+const RESTORE_DRILL_SCHEDULE: &str = "0 * * * *";
+
 /// 备份类型
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BackupType {
@@ -31,12 +64,21 @@ pub enum BackupStatus {
     InProgress,
     /// 已完成
     Completed,
+    /// 部分完成（有文件未能备份或恢复）
+    Partial,
     /// 已失败
     Failed,
     /// 已取消
     Cancelled,
 }
 
+impl BackupStatus {
+    /// 是否为可用于恢复的备份状态（已完成或部分完成）
+    pub fn is_restorable(&self) -> bool {
+        matches!(self, BackupStatus::Completed | BackupStatus::Partial)
+    }
+}
+
 /// 备份配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupConfig {
@@ -50,14 +92,47 @@ pub struct BackupConfig {
     pub target_storage: StorageConfig,
     /// 备份路径前缀
     pub backup_prefix: String,
-    /// 备份计划（cron表达式，简化版）
+    /// 备份计划（标准cron表达式：分 时 日 月 周）
     pub schedule: Option<String>,
+    /// 备份计划的时区（IANA名称，默认UTC）
+    #[serde(default)]
+    pub schedule_timezone: Option<String>,
     /// 保留备份数量
     pub retention_count: u32,
     /// 是否启用压缩
     pub compression_enabled: bool,
     /// 是否启用加密
     pub encryption_enabled: bool,
+    /// 定期恢复演练配置（None表示不演练）
+    #[serde(default)]
+    pub restore_drill: Option<RestoreDrillConfig>,
+}
+
+/// 恢复演练配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreDrillConfig {
+    /// 演练间隔（小时）
+    pub interval_hours: u32,
+    /// 随机抽样的对象数量
+    pub sample_size: usize,
+    /// 临时恢复目录，每次演练在其下创建子目录并在结束后清理
+    pub scratch_path: String,
+    /// 是否解析DICOM文件头
+    pub parse_dicom_headers: bool,
+}
+
+impl Default for RestoreDrillConfig {
+    fn default() -> Self {
+        Self {
+            interval_hours: 24 * 7,
+            sample_size: 20,
+            scratch_path: std::env::temp_dir()
+                .join("pacs_restore_drill")
+                .to_string_lossy()
+                .into_owned(),
+            parse_dicom_headers: true,
+        }
+    }
 }
 
 /// 备份信息
@@ -85,6 +160,24 @@ pub struct BackupInfo {
     pub base_backup_id: Option<String>,
     /// 文件清单
     pub file_manifest: Vec<BackupFileEntry>,
+    /// 相对基础备份已删除的文件
+    #[serde(default)]
+    pub deleted_paths: Vec<String>,
+    /// 恢复演练报告
+    #[serde(default)]
+    pub restore_drills: Vec<RestoreDrillReport>,
+    /// 清单签名（配置了备份密钥时写入清单，加载清单时校验）
+    #[serde(default)]
+    pub manifest_signature: Option<ReportSignature>,
+}
+
+impl BackupInfo {
+    /// 参与清单签名的内容
+    fn signing_payload(&self) -> Result<Vec<u8>> {
+        let mut unsigned = self.clone();
+        unsigned.manifest_signature = None;
+        Ok(serde_json::to_vec(&unsigned)?)
+    }
 }
 
 /// 备份文件条目
@@ -102,6 +195,12 @@ pub struct BackupFileEntry {
     pub modified_time: DateTime<Utc>,
     /// 备份状态
     pub backup_status: BackupStatus,
+    /// 备份对象是否经过zstd压缩
+    #[serde(default)]
+    pub compressed: bool,
+    /// 加密密钥ID（未加密时为None）
+    #[serde(default)]
+    pub key_id: Option<String>,
 }
 
 /// 恢复信息
@@ -125,18 +224,150 @@ pub struct RestoreInfo {
     pub target_path: String,
     /// 错误信息
     pub error_message: Option<String>,
+    /// 备份时复制失败的文件，恢复结果中缺失或只有更早的版本
+    #[serde(default)]
+    pub missing_paths: Vec<String>,
+}
+
+/// 校验失败的备份对象
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationFailure {
+    /// 原始文件路径
+    pub path: String,
+    /// 失败原因
+    pub reason: String,
+}
+
+/// 备份校验报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupVerificationReport {
+    /// 备份ID
+    pub backup_id: String,
+    /// 校验时间
+    pub verified_at: DateTime<Utc>,
+    /// 校验的对象数量
+    pub objects_checked: u64,
+    /// 校验的数据大小
+    pub bytes_checked: u64,
+    /// 通过DICOM文件头解析的对象数量
+    pub dicom_headers_checked: u64,
+    /// 校验失败的对象
+    pub failures: Vec<VerificationFailure>,
+}
+
+impl BackupVerificationReport {
+    /// 是否全部通过
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// 恢复演练中单个对象的比对结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DrillOutcome {
+    /// 恢复内容与在线存储一致
+    Matched,
+    /// 恢复成功，但在线存储中的文件在备份后已被修改
+    LiveModified,
+    /// 恢复成功，但在线存储中的文件已不存在
+    LiveMissing,
+    /// 恢复失败
+    Failed(String),
+}
+
+/// 恢复演练抽样结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrillSample {
+    /// 原始文件路径
+    pub path: String,
+    /// 文件大小
+    pub size: u64,
+    /// 比对结果
+    pub outcome: DrillOutcome,
+}
+
+/// 恢复演练报告
+///
+/// 签名覆盖除签名字段外的全部内容，可通过 [`BackupManager::verify_drill_report`] 校验
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreDrillReport {
+    /// 演练ID
+    pub id: String,
+    /// 备份ID
+    pub backup_id: String,
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    /// 结束时间
+    pub finished_at: DateTime<Utc>,
+    /// 备份中可恢复的对象总数
+    pub population: u64,
+    /// 抽样结果
+    pub samples: Vec<DrillSample>,
+    /// 是否全部恢复成功
+    pub passed: bool,
+    /// 报告签名
+    pub signature: Option<ReportSignature>,
+}
+
+impl RestoreDrillReport {
+    /// 参与签名的报告内容
+    fn signing_payload(&self) -> Result<Vec<u8>> {
+        let mut unsigned = self.clone();
+        unsigned.signature = None;
+        Ok(serde_json::to_vec(&unsigned)?)
+    }
+}
+
+/// 一次备份运行的结果
+#[derive(Debug, Default)]
+struct BackupRun {
+    /// 成功备份的文件数
+    file_count: u64,
+    /// 成功备份的数据大小
+    total_size: u64,
+    /// 文件清单
+    file_manifest: Vec<BackupFileEntry>,
+    /// 相对基础备份已删除的文件
+    deleted_paths: Vec<String>,
+}
+
+/// 已登记、等待复制文件的备份
+struct PreparedBackup {
+    /// 备份配置
+    config: BackupConfig,
+    /// 进行中的备份信息
+    info: BackupInfo,
+    /// 基础备份时刻的文件集合
+    reference: BTreeMap<String, BackupFileEntry>,
+    /// 基础备份中复制失败的文件
+    gaps: Vec<String>,
+    /// 源存储
+    source_storage: Arc<StorageManager>,
+    /// 备份目标存储
+    target_storage: Arc<StorageManager>,
+    /// 加密密钥环（未启用加密时为None）
+    key_ring: Option<KeyRing>,
 }
 
 /// 备份管理器
+///
+/// 备份对象写入目标存储的 `<backup_prefix>/<备份ID>/data/<原路径>`，
+/// 清单写入 `<backup_prefix>/manifests/<备份ID>.json`，
+/// 进程重启后可通过 [`BackupManager::load_backup_history`] 重建备份历史；
+/// 配置了状态存储时由 [`BackupManager::load_state`] 加载，并清理中断的备份
 pub struct BackupManager {
     /// 备份配置
     configs: HashMap<String, BackupConfig>,
-    /// 存储管理器
-    storage_managers: HashMap<String, StorageManager>,
+    /// 存储管理器（按存储配置缓存）
+    storage_managers: HashMap<String, Arc<StorageManager>>,
     /// 备份历史
     backup_history: Vec<BackupInfo>,
     /// 当前正在进行的备份
     active_backups: HashMap<String, BackupInfo>,
+    /// 备份加密密钥环
+    key_ring: Option<KeyRing>,
+    /// 持久化状态存储（为None时备份历史只保存在内存和清单中）
+    state_store: Option<Arc<StateStore>>,
 }
 
 impl BackupManager {
@@ -147,9 +378,28 @@ impl BackupManager {
             storage_managers: HashMap::new(),
             backup_history: Vec::new(),
             active_backups: HashMap::new(),
+            key_ring: None,
+            state_store: None,
         }
     }
 
+    /// 设置持久化状态存储，备份历史和进行中的备份随之写入
+    pub fn with_state_store(mut self, store: Arc<StateStore>) -> Self {
+        self.state_store = Some(store);
+        self
+    }
+
+    /// 设置备份加密密钥环
+    pub fn with_key_ring(mut self, key_ring: KeyRing) -> Self {
+        self.key_ring = Some(key_ring);
+        self
+    }
+
+    /// 替换备份加密密钥环（密钥轮换后调用）
+    pub fn set_key_ring(&mut self, key_ring: KeyRing) {
+        self.key_ring = Some(key_ring);
+    }
+
     /// 添加备份配置
     pub fn add_config(&mut self, config: BackupConfig) -> Result<()> {
         self.configs.insert(config.name.clone(), config);
@@ -157,17 +407,83 @@ impl BackupManager {
     }
 
     /// 执行备份
+    ///
+    /// 增量备份以最近一次成功的任意类型备份为基础，差异备份以最近一次完整备份为基础，
+    /// 只复制大小/修改时间变化且哈希不同的文件，并记录已删除的文件
     pub async fn execute_backup(
         &mut self,
         config_name: &str,
         backup_type: BackupType,
     ) -> Result<String> {
-        let config = self
-            .configs
-            .get(config_name)
-            .ok_or_else(|| PacsError::configuration("Backup configuration not found"))?;
+        let prepared = self.begin_backup(config_name, backup_type).await?;
+        let run = copy_changed_files(&prepared).await;
+        self.complete_backup(prepared, run).await
+    }
+
+    /// 在共享的备份管理器上执行备份
+    ///
+    /// 只在登记和结束备份时持有锁，复制文件期间查询、恢复和其他配置的备份不被阻塞
+    pub async fn execute_shared_backup(
+        manager: &Mutex<BackupManager>,
+        config_name: &str,
+        backup_type: BackupType,
+    ) -> Result<String> {
+        let prepared = manager
+            .lock()
+            .await
+            .begin_backup(config_name, backup_type)
+            .await?;
+        let run = copy_changed_files(&prepared).await;
+        manager.lock().await.complete_backup(prepared, run).await
+    }
 
-        let backup_id = format!("backup_{}_{}", config_name, Utc::now().timestamp());
+    /// 登记新备份并准备复制所需的基础状态、存储和密钥
+    async fn begin_backup(
+        &mut self,
+        config_name: &str,
+        backup_type: BackupType,
+    ) -> Result<PreparedBackup> {
+        let config = self.configs.get(config_name).cloned().ok_or_else(|| {
+            PacsError::Config(format!("Backup configuration not found: {}", config_name))
+        })?;
+        if self
+            .active_backups
+            .values()
+            .any(|b| b.config_name == config_name)
+        {
+            return Err(PacsError::Storage(format!(
+                "A backup of {} is already in progress",
+                config_name
+            )));
+        }
+
+        let base_backup_id = match backup_type {
+            BackupType::Full => None,
+            BackupType::Incremental => Some(
+                self.find_latest_completed_backup(config_name)
+                    .ok_or_else(|| {
+                        PacsError::Config("No base backup found for incremental backup".to_string())
+                    })?
+                    .id
+                    .clone(),
+            ),
+            BackupType::Differential => Some(
+                self.find_latest_backup(config_name, BackupType::Full)
+                    .ok_or_else(|| {
+                        PacsError::Config(
+                            "No full backup found for differential backup".to_string(),
+                        )
+                    })?
+                    .id
+                    .clone(),
+            ),
+        };
+
+        let backup_id = format!(
+            "backup_{}_{}",
+            config_name,
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        );
 
         let backup_info = BackupInfo {
             id: backup_id.clone(),
@@ -179,208 +495,842 @@ impl BackupManager {
             file_count: 0,
             total_size: 0,
             error_message: None,
-            base_backup_id: None,
+            base_backup_id: base_backup_id.clone(),
             file_manifest: Vec::new(),
+            deleted_paths: Vec::new(),
+            restore_drills: Vec::new(),
+            manifest_signature: None,
         };
 
         self.active_backups
             .insert(backup_id.clone(), backup_info.clone());
+        let mut txn = StateTransaction::new();
+        txn.put(BACKUP_ACTIVE, &backup_id, &backup_info)?;
+        self.commit(txn).await?;
 
-        info!("Starting backup: {} ({})", backup_id, backup_type);
-
-        let result = match backup_type {
-            BackupType::Full => self.execute_full_backup(&backup_id, config).await,
-            BackupType::Incremental => self.execute_incremental_backup(&backup_id, config).await,
-            BackupType::Differential => self.execute_differential_backup(&backup_id, config).await,
-        };
+        info!(
+            "Starting backup: {} ({:?}, base: {:?})",
+            backup_id, backup_type, base_backup_id
+        );
 
-        let (file_count, total_size, file_manifest) = match result {
-            Ok((count, size, manifest)) => {
-                info!(
-                    "Backup completed successfully: {} (files: {}, size: {} bytes)",
-                    backup_id, count, size
-                );
-                (count, size, manifest)
+        let resources = async {
+            let (reference, gaps) = match &base_backup_id {
+                Some(base_id) => (self.backup_state(base_id)?, self.backup_gaps(base_id)?),
+                None => (BTreeMap::new(), Vec::new()),
+            };
+            let source_storage = self.get_storage_manager(&config.source_storage).await?;
+            let target_storage = self.get_storage_manager(&config.target_storage).await?;
+            let key_ring = if config.encryption_enabled {
+                Some(self.key_ring()?.clone())
+            } else {
+                None
+            };
+            Ok::<_, PacsError>((reference, gaps, source_storage, target_storage, key_ring))
+        }
+        .await;
+
+        match resources {
+            Ok((reference, gaps, source_storage, target_storage, key_ring)) => Ok(PreparedBackup {
+                config,
+                info: backup_info,
+                reference,
+                gaps,
+                source_storage,
+                target_storage,
+                key_ring,
+            }),
+            Err(e) => {
+                self.fail_backup(backup_info, &e).await;
+                Err(e)
             }
+        }
+    }
+
+    /// 结束备份：写入清单、移入历史并清理过期备份
+    async fn complete_backup(
+        &mut self,
+        prepared: PreparedBackup,
+        run: Result<BackupRun>,
+    ) -> Result<String> {
+        let PreparedBackup { config, info, .. } = prepared;
+        let backup_id = info.id.clone();
+
+        let result = async {
+            let run = run?;
+            let failed = run
+                .file_manifest
+                .iter()
+                .filter(|entry| entry.backup_status == BackupStatus::Failed)
+                .count();
+            let mut completed_backup = info.clone();
+            completed_backup.end_time = Some(Utc::now());
+            completed_backup.status = if failed == 0 {
+                BackupStatus::Completed
+            } else {
+                completed_backup.error_message = Some(format!(
+                    "{} of {} changed files failed to back up",
+                    failed,
+                    run.file_manifest.len()
+                ));
+                BackupStatus::Partial
+            };
+            completed_backup.file_count = run.file_count;
+            completed_backup.total_size = run.total_size;
+            completed_backup.file_manifest = run.file_manifest;
+            completed_backup.deleted_paths = run.deleted_paths;
+
+            self.write_manifest(&config, &completed_backup).await?;
+            Ok::<_, PacsError>(completed_backup)
+        }
+        .await;
+
+        let completed_backup = match result {
+            Ok(completed_backup) => completed_backup,
             Err(e) => {
-                error!("Backup failed: {} - {}", backup_id, e);
-                if let Some(active_backup) = self.active_backups.get_mut(&backup_id) {
-                    active_backup.status = BackupStatus::Failed;
-                    active_backup.error_message = Some(e.to_string());
-                }
+                self.fail_backup(info, &e).await;
                 return Err(e);
             }
         };
 
-        // 更新备份信息
-        if let Some(active_backup) = self.active_backups.remove(&backup_id) {
-            let mut completed_backup = active_backup;
-            completed_backup.end_time = Some(Utc::now());
-            completed_backup.status = BackupStatus::Completed;
-            completed_backup.file_count = file_count;
-            completed_backup.total_size = total_size;
-            completed_backup.file_manifest = file_manifest;
+        self.active_backups.remove(&backup_id);
+        match &completed_backup.error_message {
+            Some(message) => warn!(
+                "Backup completed partially: {} - {} (files: {}, size: {} bytes)",
+                backup_id, message, completed_backup.file_count, completed_backup.total_size
+            ),
+            None => info!(
+                "Backup completed successfully: {} (files: {}, size: {} bytes, deleted: {})",
+                backup_id,
+                completed_backup.file_count,
+                completed_backup.total_size,
+                completed_backup.deleted_paths.len()
+            ),
+        }
+        self.finish_backup(completed_backup).await?;
 
-            self.backup_history.push(completed_backup);
+        // 清理过期备份
+        self.cleanup_expired_backups(&config.name).await?;
+        Ok(backup_id)
+    }
 
-            // 清理过期备份
-            self.cleanup_expired_backups(config_name).await?;
+    /// 将备份记为失败并移入历史
+    async fn fail_backup(&mut self, mut backup: BackupInfo, error: &PacsError) {
+        error!("Backup failed: {} - {}", backup.id, error);
+        self.active_backups.remove(&backup.id);
+        backup.status = BackupStatus::Failed;
+        backup.end_time = Some(Utc::now());
+        backup.error_message = Some(error.to_string());
+        let backup_id = backup.id.clone();
+        if let Err(persist_error) = self.finish_backup(backup).await {
+            error!(
+                "Failed to persist failed backup {}: {}",
+                backup_id, persist_error
+            );
         }
+    }
 
-        Ok(backup_id)
+    /// 恢复备份到本地目录
+    pub async fn restore_backup(&mut self, backup_id: &str, target_path: &str) -> Result<String> {
+        let target_storage = StorageManager::new(StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(target_path.to_string()),
+            object_store_config: None,
+        })
+        .await?;
+
+        let restore_info = self.restore_backup_to(backup_id, &target_storage).await?;
+        Ok(restore_info.id)
     }
 
-    /// 执行完整备份
-    async fn execute_full_backup(
+    /// 恢复备份到指定存储
+    ///
+    /// 沿基础备份链从完整备份开始依次应用增量/差异备份，得到备份时刻的文件集合，
+    /// 校验每个文件的哈希后写入目标存储，任一文件失败时返回错误。
+    /// 备份时复制失败的文件记录在 `missing_paths` 中，此时恢复状态为部分完成
+    pub async fn restore_backup_to(
         &mut self,
         backup_id: &str,
-        config: &BackupConfig,
-    ) -> Result<(u64, u64, Vec<BackupFileEntry>)> {
-        let source_storage = self.get_storage_manager(&config.source_storage).await?;
-        let target_storage = self.get_storage_manager(&config.target_storage).await?;
+        target_storage: &StorageManager,
+    ) -> Result<RestoreInfo> {
+        let config = self.config_for_backup(backup_id)?;
+        let state = self.backup_state(backup_id)?;
+        let backup_storage = self.get_storage_manager(&config.target_storage).await?;
+        let key_ring = self.decryption_keys(&state)?;
+        let missing_paths = self.backup_gaps(backup_id)?;
+
+        let mut restore_info = RestoreInfo {
+            id: format!(
+                "restore_{}_{}",
+                backup_id,
+                Utc::now().timestamp_nanos_opt().unwrap_or_default()
+            ),
+            backup_id: backup_id.to_string(),
+            start_time: Utc::now(),
+            end_time: None,
+            status: BackupStatus::InProgress,
+            file_count: 0,
+            total_size: 0,
+            target_path: target_storage.backend().name().to_string(),
+            error_message: None,
+            missing_paths,
+        };
+
+        info!(
+            "Starting restore: {} from backup {} ({} files)",
+            restore_info.id,
+            backup_id,
+            state.len()
+        );
+
+        let mut failures = Vec::new();
+        for file_entry in state.values() {
+            let result = async {
+                let file_data =
+                    read_backup_object(&backup_storage, file_entry, key_ring.as_ref()).await?;
+                target_storage
+                    .store_file(&file_data, &file_entry.original_path)
+                    .await
+            }
+            .await;
+
+            match result {
+                Ok(_) => {
+                    restore_info.file_count += 1;
+                    restore_info.total_size += file_entry.size;
+                }
+                Err(e) => {
+                    error!("Failed to restore {}: {}", file_entry.original_path, e);
+                    failures.push(file_entry.original_path.clone());
+                }
+            }
+        }
 
-        let mut file_count = 0u64;
-        let mut total_size = 0u64;
-        let mut file_manifest = Vec::new();
+        restore_info.end_time = Some(Utc::now());
+        if !failures.is_empty() {
+            restore_info.status = BackupStatus::Failed;
+            let message = format!(
+                "Restore {} failed for {} of {} files",
+                restore_info.id,
+                failures.len(),
+                state.len()
+            );
+            restore_info.error_message = Some(message.clone());
+            return Err(PacsError::Storage(message));
+        }
 
-        // 这里简化处理，实际应用中需要遍历源存储的所有文件
-        // 可以通过存储管理器的统计信息获取文件列表
+        if restore_info.missing_paths.is_empty() {
+            restore_info.status = BackupStatus::Completed;
+            info!(
+                "Restore completed: {} (files: {}, size: {} bytes)",
+                restore_info.id, restore_info.file_count, restore_info.total_size
+            );
+        } else {
+            restore_info.status = BackupStatus::Partial;
+            let message = format!(
+                "Restore {} is missing {} files that failed to back up",
+                restore_info.id,
+                restore_info.missing_paths.len()
+            );
+            warn!(
+                "{} (files: {}, size: {} bytes)",
+                message, restore_info.file_count, restore_info.total_size
+            );
+            restore_info.error_message = Some(message);
+        }
 
-        info!("Executing full backup: {}", backup_id);
+        Ok(restore_info)
+    }
 
-        // 示例：备份所有DICOM文件
-        // 实际实现需要根据具体存储类型进行文件遍历
+    /// 获取从完整备份到指定备份的恢复链
+    pub fn restore_chain(&self, backup_id: &str) -> Result<Vec<BackupInfo>> {
+        let mut chain: Vec<BackupInfo> = Vec::new();
+        let mut current = Some(backup_id.to_string());
+
+        while let Some(id) = current {
+            if chain.iter().any(|b| b.id == id) {
+                return Err(PacsError::Storage(format!(
+                    "Backup chain of {} contains a cycle at {}",
+                    backup_id, id
+                )));
+            }
 
-        Ok((file_count, total_size, file_manifest))
+            let backup = self
+                .backup_history
+                .iter()
+                .find(|b| b.id == id && b.status.is_restorable())
+                .ok_or_else(|| {
+                    PacsError::NotFound(format!("Backup {} in restore chain of {}", id, backup_id))
+                })?;
+            current = backup.base_backup_id.clone();
+            chain.push(backup.clone());
+        }
+
+        chain.reverse();
+        if chain
+            .first()
+            .is_some_and(|b| b.backup_type != BackupType::Full)
+        {
+            return Err(PacsError::Storage(format!(
+                "Restore chain of {} does not start with a full backup",
+                backup_id
+            )));
+        }
+
+        Ok(chain)
     }
 
-    /// 执行增量备份
-    async fn execute_incremental_backup(
-        &mut self,
-        backup_id: &str,
-        config: &BackupConfig,
-    ) -> Result<(u64, u64, Vec<BackupFileEntry>)> {
-        // 找到最近的基础备份
-        let base_backup = self
-            .find_latest_backup(&config.config_name, BackupType::Full)
-            .or_else(|| self.find_latest_backup(&config.config_name, BackupType::Differential));
+    /// 计算指定备份时刻的完整文件集合
+    pub fn backup_state(&self, backup_id: &str) -> Result<BTreeMap<String, BackupFileEntry>> {
+        let mut state = BTreeMap::new();
+
+        for backup in self.restore_chain(backup_id)? {
+            for path in &backup.deleted_paths {
+                state.remove(path);
+            }
+            for entry in backup.file_manifest {
+                if entry.backup_status == BackupStatus::Completed {
+                    state.insert(entry.original_path.clone(), entry);
+                }
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// 计算指定备份时刻缺失的文件
+    ///
+    /// 即恢复链中最近一次备份时复制失败、之后既未成功复制也未删除的文件
+    pub fn backup_gaps(&self, backup_id: &str) -> Result<Vec<String>> {
+        let mut gaps = BTreeSet::new();
+
+        for backup in self.restore_chain(backup_id)? {
+            for path in &backup.deleted_paths {
+                gaps.remove(path);
+            }
+            for entry in &backup.file_manifest {
+                if entry.backup_status == BackupStatus::Completed {
+                    gaps.remove(&entry.original_path);
+                } else {
+                    gaps.insert(entry.original_path.clone());
+                }
+            }
+        }
+
+        Ok(gaps.into_iter().collect())
+    }
 
-        if base_backup.is_none() {
-            return Err(PacsError::configuration(
-                "No base backup found for incremental backup",
-            ));
+    /// 将结束的备份移入历史，并在同一事务中持久化
+    async fn finish_backup(&mut self, backup: BackupInfo) -> Result<()> {
+        let mut txn = StateTransaction::new();
+        txn.delete(BACKUP_ACTIVE, &backup.id);
+        txn.put(BACKUP_HISTORY, &backup.id, &backup)?;
+        self.backup_history.push(backup);
+        self.commit(txn).await
+    }
+
+    /// 持久化历史中的备份记录
+    async fn persist_backup(&self, backup: &BackupInfo) -> Result<()> {
+        let mut txn = StateTransaction::new();
+        txn.put(BACKUP_HISTORY, &backup.id, backup)?;
+        self.commit(txn).await
+    }
+
+    /// 提交状态事务（未配置状态存储时忽略）
+    async fn commit(&self, txn: StateTransaction) -> Result<()> {
+        match &self.state_store {
+            Some(store) => store.commit(txn).await,
+            None => Ok(()),
         }
+    }
+
+    /// 从状态存储加载备份历史，并清理上次运行中断的备份
+    ///
+    /// 应在添加备份配置之后调用。中断的备份没有写入清单，无法用于恢复，
+    /// 其已复制的备份对象被删除，记录标记为失败后移入历史
+    pub async fn load_state(&mut self) -> Result<StateRecovery> {
+        let Some(store) = self.state_store.clone() else {
+            return Ok(StateRecovery::default());
+        };
 
+        let mut recovery = StateRecovery::default();
+        for (_, backup) in store.load::<BackupInfo>(BACKUP_HISTORY).await? {
+            recovery.loaded += 1;
+            if !self.backup_history.iter().any(|b| b.id == backup.id) {
+                self.backup_history.push(backup);
+            }
+        }
+
+        for (backup_id, mut backup) in store.load::<BackupInfo>(BACKUP_ACTIVE).await? {
+            recovery.loaded += 1;
+            match self.discard_interrupted_backup(&backup).await {
+                Ok(removed) => {
+                    info!(
+                        "Discarded interrupted backup {} ({} objects removed)",
+                        backup_id, removed
+                    );
+                    recovery.rolled_back += 1;
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to remove objects of interrupted backup {}: {}",
+                        backup_id, e
+                    );
+                    recovery.unresolved += 1;
+                }
+            }
+
+            backup.status = BackupStatus::Failed;
+            backup.end_time = Some(Utc::now());
+            backup.error_message = Some("Interrupted before completion".to_string());
+            let mut txn = StateTransaction::new();
+            txn.delete(BACKUP_ACTIVE, &backup_id);
+            txn.put(BACKUP_HISTORY, &backup_id, &backup)?;
+            store.commit(txn).await?;
+            self.backup_history.push(backup);
+        }
+
+        self.backup_history.sort_by_key(|b| b.start_time);
         info!(
-            "Executing incremental backup: {} (base: {:?})",
-            backup_id,
-            base_backup.as_ref().map(|b| &b.id)
+            "Loaded backup state: {} records, {} interrupted backups discarded",
+            recovery.loaded, recovery.rolled_back
         );
+        Ok(recovery)
+    }
+
+    /// 删除中断备份已写入的备份对象，返回删除的对象数
+    async fn discard_interrupted_backup(&mut self, backup: &BackupInfo) -> Result<usize> {
+        let config = self
+            .configs
+            .get(&backup.config_name)
+            .cloned()
+            .ok_or_else(|| {
+                PacsError::Config(format!(
+                    "Backup configuration not found: {}",
+                    backup.config_name
+                ))
+            })?;
+        let target_storage = self.get_storage_manager(&config.target_storage).await?;
+        let prefix = format!("{}/{}/", backup_root(&config), backup.id);
 
-        // TODO: 实现增量备份逻辑
-        // 比较文件修改时间和哈希值，只备份变更的文件
+        let mut removed = 0;
+        for object in target_storage.list_files(Some(&prefix)).await? {
+            target_storage.delete_file(&object.key).await?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
 
-        Ok((0, 0, Vec::new()))
+    /// 从目标存储加载已持久化的备份清单，返回新加载的备份数
+    pub async fn load_backup_history(&mut self) -> Result<usize> {
+        let configs: Vec<BackupConfig> = self.configs.values().cloned().collect();
+        let mut loaded = 0;
+
+        for config in configs {
+            let target_storage = self.get_storage_manager(&config.target_storage).await?;
+            let prefix = format!("{}/{}", backup_root(&config), MANIFEST_DIR);
+
+            for object in target_storage.list_files(Some(&prefix)).await? {
+                let data = target_storage.get_file(&object.key).await?;
+                let backup: BackupInfo = match serde_json::from_slice(&data) {
+                    Ok(backup) => backup,
+                    Err(e) => {
+                        warn!("Skipping unreadable backup manifest {}: {}", object.key, e);
+                        continue;
+                    }
+                };
+
+                if backup.config_name != config.name
+                    || self.backup_history.iter().any(|b| b.id == backup.id)
+                {
+                    continue;
+                }
+                if let Err(e) = self.verify_manifest(&backup) {
+                    warn!(
+                        "Skipping unauthenticated backup manifest {}: {}",
+                        object.key, e
+                    );
+                    continue;
+                }
+                self.backup_history.push(backup);
+                loaded += 1;
+            }
+        }
+
+        self.backup_history.sort_by_key(|b| b.start_time);
+        info!("Loaded {} backup manifests", loaded);
+        Ok(loaded)
     }
 
-    /// 执行差异备份
-    async fn execute_differential_backup(
+    /// 将备份清单写入目标存储，配置了备份密钥时附带清单签名
+    async fn write_manifest(&mut self, config: &BackupConfig, backup: &BackupInfo) -> Result<()> {
+        let mut manifest = backup.clone();
+        manifest.manifest_signature = match self.available_key_ring()? {
+            Some(key_ring) => Some(key_ring.sign(&backup.signing_payload()?)?),
+            None => None,
+        };
+        let target_storage = self.get_storage_manager(&config.target_storage).await?;
+        let data = serde_json::to_vec_pretty(&manifest)?;
+        target_storage
+            .store_file(&data, &manifest_path(config, &backup.id))
+            .await?;
+        Ok(())
+    }
+
+    /// 校验清单签名
+    ///
+    /// 配置了备份密钥时清单必须带有有效签名，否则无法发现对清单中哈希、
+    /// 密钥ID、压缩标记或文件列表的篡改；未配置密钥时无法校验，直接接受
+    fn verify_manifest(&mut self, backup: &BackupInfo) -> Result<()> {
+        let Some(key_ring) = self.available_key_ring()? else {
+            return Ok(());
+        };
+        let signature = backup.manifest_signature.as_ref().ok_or_else(|| {
+            PacsError::Validation(format!("Backup manifest {} is not signed", backup.id))
+        })?;
+        key_ring.verify_signature(&backup.signing_payload()?, signature)
+    }
+
+    /// 校验备份
+    ///
+    /// 重新读取恢复该备份所需的全部对象，解密解压后核对清单中的哈希，
+    /// 可选地解析DICOM文件头；单个对象失败不会中断校验，结果汇总在报告中
+    pub async fn verify_backup(
         &mut self,
         backup_id: &str,
-        config: &BackupConfig,
-    ) -> Result<(u64, u64, Vec<BackupFileEntry>)> {
-        // 找到最近的基础备份
-        let base_backup = self.find_latest_backup(&config.config_name, BackupType::Full);
+        parse_dicom_headers: bool,
+    ) -> Result<BackupVerificationReport> {
+        let config = self.config_for_backup(backup_id)?;
+        let state = self.backup_state(backup_id)?;
+        let backup_storage = self.get_storage_manager(&config.target_storage).await?;
+        let key_ring = self.decryption_keys(&state)?;
+
+        let mut report = BackupVerificationReport {
+            backup_id: backup_id.to_string(),
+            verified_at: Utc::now(),
+            objects_checked: 0,
+            bytes_checked: 0,
+            dicom_headers_checked: 0,
+            failures: Vec::new(),
+        };
 
-        if base_backup.is_none() {
-            return Err(PacsError::configuration(
-                "No full backup found for differential backup",
-            ));
+        for entry in state.values() {
+            report.objects_checked += 1;
+            let result = async {
+                let file_data =
+                    read_backup_object(&backup_storage, entry, key_ring.as_ref()).await?;
+                let dicom_checked =
+                    parse_dicom_headers && looks_like_dicom(&entry.original_path, &file_data);
+                if dicom_checked {
+                    check_dicom_header(&file_data)?;
+                }
+                Ok::<_, PacsError>((file_data.len() as u64, dicom_checked))
+            }
+            .await;
+
+            match result {
+                Ok((size, dicom_checked)) => {
+                    report.bytes_checked += size;
+                    if dicom_checked {
+                        report.dicom_headers_checked += 1;
+                    }
+                }
+                Err(e) => {
+                    warn!("Verification failed for {}: {}", entry.original_path, e);
+                    report.failures.push(VerificationFailure {
+                        path: entry.original_path.clone(),
+                        reason: e.to_string(),
+                    });
+                }
+            }
         }
 
-        info!(
-            "Executing differential backup: {} (base: {:?})",
+        if report.passed() {
+            info!(
+                "Backup {} verified: {} objects, {} bytes, {} DICOM headers",
+                backup_id,
+                report.objects_checked,
+                report.bytes_checked,
+                report.dicom_headers_checked
+            );
+        } else {
+            error!(
+                "Backup {} verification failed for {} of {} objects",
+                backup_id,
+                report.failures.len(),
+                report.objects_checked
+            );
+        }
+        Ok(report)
+    }
+
+    /// 执行恢复演练
+    ///
+    /// 从备份中随机抽样恢复到临时目录，校验恢复结果并与在线存储比对，
+    /// 生成报告并随备份清单一起保存；配置了备份密钥时报告带签名，
+    /// 未配置时仍可演练未加密的备份，报告不签名
+    pub async fn run_restore_drill(
+        &mut self,
+        backup_id: &str,
+        drill: &RestoreDrillConfig,
+    ) -> Result<RestoreDrillReport> {
+        let config = self.config_for_backup(backup_id)?;
+        let key_ring = self.available_key_ring()?.cloned();
+        let state = self.backup_state(backup_id)?;
+        let backup_storage = self.get_storage_manager(&config.target_storage).await?;
+        let live_storage = self.get_storage_manager(&config.source_storage).await?;
+
+        let started_at = Utc::now();
+        let drill_id = format!(
+            "drill_{}_{}",
             backup_id,
-            base_backup.as_ref().map(|b| &b.id)
+            started_at.timestamp_nanos_opt().unwrap_or_default()
+        );
+        let scratch_dir = Path::new(&drill.scratch_path).join(&drill_id);
+        let scratch_storage = StorageManager::new(StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(scratch_dir.to_string_lossy().into_owned()),
+            object_store_config: None,
+        })
+        .await?;
+
+        let population = state.len() as u64;
+        let sample = random_sample(state.into_values().collect(), drill.sample_size);
+        info!(
+            "Starting restore drill {} with {} of {} objects",
+            drill_id,
+            sample.len(),
+            population
         );
 
-        // TODO: 实现差异备份逻辑
-        // 备份自上次完整备份以来的所有变更文件
+        let mut samples = Vec::with_capacity(sample.len());
+        for entry in &sample {
+            let outcome = restore_drill_sample(
+                &backup_storage,
+                &scratch_storage,
+                &live_storage,
+                entry,
+                key_ring.as_ref(),
+                drill.parse_dicom_headers,
+            )
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Restore drill failed for {}: {}", entry.original_path, e);
+                DrillOutcome::Failed(e.to_string())
+            });
+            samples.push(DrillSample {
+                path: entry.original_path.clone(),
+                size: entry.size,
+                outcome,
+            });
+        }
 
-        Ok((0, 0, Vec::new()))
-    }
+        if let Err(e) = tokio::fs::remove_dir_all(&scratch_dir).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(
+                    "Failed to clean up drill scratch directory {}: {}",
+                    scratch_dir.display(),
+                    e
+                );
+            }
+        }
 
-    /// 恢复备份
-    pub async fn restore_backup(&mut self, backup_id: &str, target_path: &str) -> Result<String> {
-        let backup_info = self
+        let mut report = RestoreDrillReport {
+            id: drill_id,
+            backup_id: backup_id.to_string(),
+            started_at,
+            finished_at: Utc::now(),
+            population,
+            passed: samples
+                .iter()
+                .all(|s| !matches!(s.outcome, DrillOutcome::Failed(_))),
+            samples,
+            signature: None,
+        };
+        report.signature = match &key_ring {
+            Some(key_ring) => Some(key_ring.sign(&report.signing_payload()?)?),
+            None => None,
+        };
+
+        let mut backup = self
             .backup_history
             .iter()
             .find(|b| b.id == backup_id)
-            .or_else(|| self.active_backups.get(backup_id))
-            .ok_or_else(|| PacsError::configuration("Backup not found"))?;
-
-        let restore_id = format!("restore_{}_{}", backup_id, Utc::now().timestamp());
+            .cloned()
+            .ok_or_else(|| PacsError::NotFound(format!("Backup {}", backup_id)))?;
+        backup.restore_drills.push(report.clone());
+        self.write_manifest(&config, &backup).await?;
+        self.persist_backup(&backup).await?;
+        if let Some(existing) = self.backup_history.iter_mut().find(|b| b.id == backup_id) {
+            *existing = backup;
+        }
 
-        info!(
-            "Starting restore: {} from backup {} to {}",
-            restore_id, backup_id, target_path
-        );
+        if report.passed {
+            info!("Restore drill {} passed", report.id);
+        } else {
+            error!("Restore drill {} failed", report.id);
+        }
+        Ok(report)
+    }
 
-        let mut file_count = 0u64;
-        let mut total_size = 0u64;
+    /// 对到期的备份配置执行恢复演练
+    ///
+    /// 每个配置在最近一次成功备份上演练，距上次演练不足间隔时跳过
+    pub async fn run_due_restore_drills(&mut self) -> Vec<RestoreDrillReport> {
+        let now = Utc::now();
+        let due: Vec<(String, RestoreDrillConfig)> = self
+            .configs
+            .values()
+            .filter_map(|config| {
+                let drill = config.restore_drill.clone()?;
+                let last_drill = self
+                    .backup_history
+                    .iter()
+                    .filter(|b| b.config_name == config.name)
+                    .flat_map(|b| b.restore_drills.iter())
+                    .map(|r| r.started_at)
+                    .max();
+                let is_due = last_drill.is_none_or(|last| {
+                    now - last >= Duration::hours(i64::from(drill.interval_hours))
+                });
+                let latest = self.find_latest_completed_backup(&config.name)?;
+                is_due.then(|| (latest.id.clone(), drill))
+            })
+            .collect();
 
-        // 恢复文件清单中的所有文件
-        for file_entry in &backup_info.file_manifest {
-            if file_entry.backup_status != BackupStatus::Completed {
-                warn!(
-                    "Skipping file with failed backup status: {}",
-                    file_entry.original_path
-                );
-                continue;
+        let mut reports = Vec::new();
+        for (backup_id, drill) in due {
+            match self.run_restore_drill(&backup_id, &drill).await {
+                Ok(report) => reports.push(report),
+                Err(e) => error!("Restore drill for backup {} failed: {}", backup_id, e),
             }
+        }
+        reports
+    }
 
-            // 从备份存储读取文件
-            let backup_storage = self
-                .get_storage_manager(&self.configs[&backup_info.config_name].target_storage)
-                .await?;
-            let file_data = backup_storage.get_file(&file_entry.backup_path).await?;
-
-            // 计算文件哈希以验证完整性
-            let hash = calculate_file_hash(&file_data);
-            if hash != file_entry.hash {
-                error!(
-                    "File hash mismatch for {} (expected: {}, actual: {})",
-                    file_entry.original_path, file_entry.hash, hash
-                );
-                continue;
+    /// 校验恢复演练报告的签名
+    pub fn verify_drill_report(&mut self, report: &RestoreDrillReport) -> Result<()> {
+        let signature = report.signature.as_ref().ok_or_else(|| {
+            PacsError::Validation(format!("Restore drill report {} is not signed", report.id))
+        })?;
+        let payload = report.signing_payload()?;
+        self.key_ring()?.verify_signature(&payload, signature)
+    }
+
+    /// 使用当前密钥重新加密备份中以旧密钥加密的对象，返回重新加密的对象数
+    ///
+    /// 对象先以原密钥解密并校验哈希，再以当前密钥加密写回原路径，最后重写清单
+    pub async fn rotate_backup_keys(&mut self, backup_id: &str) -> Result<u64> {
+        let mut backup = self
+            .backup_history
+            .iter()
+            .find(|b| b.id == backup_id && b.status.is_restorable())
+            .cloned()
+            .ok_or_else(|| PacsError::NotFound(format!("Backup {}", backup_id)))?;
+        let config = self.config_for_backup(backup_id)?;
+        let key_ring = self.key_ring()?.clone();
+        let backup_storage = self.get_storage_manager(&config.target_storage).await?;
+
+        let mut rotated = 0;
+        for entry in backup.file_manifest.iter_mut() {
+            let key_id = match &entry.key_id {
+                Some(key_id) if key_id != key_ring.active_key_id() => key_id.clone(),
+                _ => continue,
+            };
+
+            let stored = backup_storage.get_file(&entry.backup_path).await?;
+            let plaintext = key_ring.open(&key_id, &stored, &object_aad(entry))?;
+            let content = if entry.compressed {
+                zstd::stream::decode_all(plaintext.as_slice())?
+            } else {
+                plaintext.clone()
+            };
+            if calculate_file_hash(&content) != entry.hash {
+                return Err(PacsError::Storage(format!(
+                    "File hash mismatch for {} during key rotation",
+                    entry.original_path
+                )));
             }
 
-            // 写入到目标路径
-            // 这里需要根据目标路径类型创建相应的存储管理器
-            // 简化处理，假设是本地文件系统
+            let sealed = key_ring.seal(&plaintext, &object_aad(entry))?;
+            backup_storage
+                .store_file(&sealed.data, &entry.backup_path)
+                .await?;
+            entry.key_id = Some(sealed.key_id);
+            rotated += 1;
+        }
 
-            file_count += 1;
-            total_size += file_entry.size;
+        if rotated > 0 {
+            self.write_manifest(&config, &backup).await?;
+            self.persist_backup(&backup).await?;
+            if let Some(existing) = self.backup_history.iter_mut().find(|b| b.id == backup_id) {
+                *existing = backup;
+            }
         }
 
         info!(
-            "Restore completed: {} (files: {}, size: {} bytes)",
-            restore_id, file_count, total_size
+            "Re-encrypted {} objects of backup {} with key {}",
+            rotated,
+            backup_id,
+            key_ring.active_key_id()
         );
+        Ok(rotated)
+    }
+
+    /// 获取备份所属的配置
+    fn config_for_backup(&self, backup_id: &str) -> Result<BackupConfig> {
+        let config_name = self
+            .backup_history
+            .iter()
+            .find(|b| b.id == backup_id)
+            .map(|b| b.config_name.clone())
+            .ok_or_else(|| PacsError::NotFound(format!("Backup {}", backup_id)))?;
+        self.configs.get(&config_name).cloned().ok_or_else(|| {
+            PacsError::Config(format!("Backup configuration not found: {}", config_name))
+        })
+    }
 
-        Ok(restore_id)
+    /// 获取解密备份状态所需的密钥环，状态中没有加密对象时返回None
+    fn decryption_keys(
+        &mut self,
+        state: &BTreeMap<String, BackupFileEntry>,
+    ) -> Result<Option<KeyRing>> {
+        if state.values().any(|entry| entry.key_id.is_some()) {
+            Ok(Some(self.key_ring()?.clone()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 获取已配置的备份密钥环，未设置时从环境变量加载，均未配置时返回None
+    fn available_key_ring(&mut self) -> Result<Option<&KeyRing>> {
+        if self.key_ring.is_none() {
+            self.key_ring = KeyRing::from_environment()?;
+        }
+        Ok(self.key_ring.as_ref())
+    }
+
+    /// 获取备份加密密钥环，未设置时从环境变量加载
+    fn key_ring(&mut self) -> Result<&KeyRing> {
+        self.available_key_ring()?.ok_or_else(|| {
+            PacsError::Config(format!(
+                "Backup encryption requires keys (set {} or {})",
+                BACKUP_KEY_FILE_ENV, BACKUP_KEYS_ENV
+            ))
+        })
     }
 
     /// 获取存储管理器
-    async fn get_storage_manager(&mut self, config: &StorageConfig) -> Result<&StorageManager> {
-        let config_key = format!("{:?}", config.storage_type);
+    async fn get_storage_manager(&mut self, config: &StorageConfig) -> Result<Arc<StorageManager>> {
+        let config_key = storage_key(config);
 
-        if !self.storage_managers.contains_key(&config_key) {
-            let storage_manager = StorageManager::new(config.clone()).await?;
-            self.storage_managers.insert(config_key, storage_manager);
+        if let Some(storage_manager) = self.storage_managers.get(&config_key) {
+            return Ok(storage_manager.clone());
         }
 
-        Ok(self.storage_managers.get(&config_key).unwrap())
+        let storage_manager = Arc::new(StorageManager::new(config.clone()).await?);
+        self.storage_managers
+            .insert(config_key, storage_manager.clone());
+        Ok(storage_manager)
     }
 
     /// 查找最新的备份
@@ -394,65 +1344,87 @@ impl BackupManager {
             .filter(|b| {
                 b.config_name == config_name
                     && b.backup_type == backup_type
-                    && b.status == BackupStatus::Completed
+                    && b.status.is_restorable()
             })
             .max_by(|a, b| a.start_time.cmp(&b.start_time))
     }
 
+    /// 查找最新的任意类型可恢复备份
+    fn find_latest_completed_backup(&self, config_name: &str) -> Option<&BackupInfo> {
+        self.backup_history
+            .iter()
+            .filter(|b| b.config_name == config_name && b.status.is_restorable())
+            .max_by(|a, b| a.start_time.cmp(&b.start_time))
+    }
+
     /// 清理过期备份
+    ///
+    /// 保留最近N个成功备份及其恢复链上的基础备份
     async fn cleanup_expired_backups(&mut self, config_name: &str) -> Result<()> {
-        let config = self
-            .configs
-            .get(config_name)
-            .ok_or_else(|| PacsError::configuration("Backup configuration not found"))?;
+        let config = self.configs.get(config_name).cloned().ok_or_else(|| {
+            PacsError::Config(format!("Backup configuration not found: {}", config_name))
+        })?;
 
-        let mut backups_to_remove = Vec::new();
-        let mut completed_backups: Vec<_> = self
+        let mut completed_backups: Vec<&BackupInfo> = self
             .backup_history
             .iter()
-            .enumerate()
-            .filter(|(_, b)| b.config_name == config_name && b.status == BackupStatus::Completed)
+            .filter(|b| b.config_name == config_name && b.status.is_restorable())
             .collect();
 
         // 按开始时间降序排序
-        completed_backups.sort_by(|(_, a), (_, b)| b.start_time.cmp(&a.start_time));
+        completed_backups.sort_by_key(|b| std::cmp::Reverse(b.start_time));
 
-        // 保留最近的N个备份
-        if completed_backups.len() > config.retention_count as usize {
-            for (index, backup) in completed_backups
-                .iter()
-                .skip(config.retention_count as usize)
-            {
-                backups_to_remove.push(*index);
+        let mut retained = HashSet::new();
+        for backup in completed_backups
+            .iter()
+            .take(config.retention_count as usize)
+        {
+            for chained in self.restore_chain(&backup.id)? {
+                retained.insert(chained.id);
             }
         }
 
-        // 删除过期备份文件
-        for &index in &backups_to_remove {
-            let backup = &self.backup_history[index];
+        let expired: Vec<BackupInfo> = completed_backups
+            .into_iter()
+            .filter(|b| !retained.contains(&b.id))
+            .cloned()
+            .collect();
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let target_storage = self.get_storage_manager(&config.target_storage).await?;
+        for backup in &expired {
             info!("Removing expired backup: {}", backup.id);
 
             // 从目标存储删除备份文件
-            if let Ok(target_storage) = self
-                .get_storage_manager(&self.configs[config_name].target_storage)
+            for file_entry in &backup.file_manifest {
+                if file_entry.backup_path.is_empty() {
+                    continue;
+                }
+                if let Err(e) = target_storage.delete_file(&file_entry.backup_path).await {
+                    warn!(
+                        "Failed to delete backup file {}: {}",
+                        file_entry.backup_path, e
+                    );
+                }
+            }
+            if let Err(e) = target_storage
+                .delete_file(&manifest_path(&config, &backup.id))
                 .await
             {
-                for file_entry in &backup.file_manifest {
-                    if let Err(e) = target_storage.delete_file(&file_entry.backup_path).await {
-                        warn!(
-                            "Failed to delete backup file {}: {}",
-                            file_entry.backup_path, e
-                        );
-                    }
-                }
+                warn!("Failed to delete manifest of backup {}: {}", backup.id, e);
             }
         }
 
         // 从历史记录中移除
-        backups_to_remove.sort_by(|a, b| b.cmp(a)); // 降序删除，避免索引变化
-        for index in backups_to_remove {
-            self.backup_history.remove(index);
+        self.backup_history
+            .retain(|b| !expired.iter().any(|e| e.id == b.id));
+        let mut txn = StateTransaction::new();
+        for backup in &expired {
+            txn.delete(BACKUP_HISTORY, &backup.id);
         }
+        self.commit(txn).await?;
 
         Ok(())
     }
@@ -462,6 +1434,30 @@ impl BackupManager {
         &self.backup_history
     }
 
+    /// 获取备份配置
+    pub fn get_config(&self, config_name: &str) -> Option<&BackupConfig> {
+        self.configs.get(config_name)
+    }
+
+    /// 获取指定时间点之前完成的成功备份，按完成时间从新到旧排列
+    pub fn backups_completed_before(
+        &self,
+        config_name: &str,
+        at: DateTime<Utc>,
+    ) -> Vec<&BackupInfo> {
+        let mut backups: Vec<&BackupInfo> = self
+            .backup_history
+            .iter()
+            .filter(|b| {
+                b.config_name == config_name
+                    && b.status.is_restorable()
+                    && b.end_time.is_some_and(|end| end <= at)
+            })
+            .collect();
+        backups.sort_by_key(|b| std::cmp::Reverse(b.end_time));
+        backups
+    }
+
     /// 获取正在进行的备份
     pub fn get_active_backups(&self) -> &HashMap<String, BackupInfo> {
         &self.active_backups
@@ -475,41 +1471,59 @@ impl BackupManager {
             info!("Backup cancelled: {}", backup_id);
             Ok(())
         } else {
-            Err(PacsError::configuration("Backup not found or not active"))
+            Err(PacsError::NotFound(format!(
+                "Backup not found or not active: {}",
+                backup_id
+            )))
         }
     }
 
-    /// 启动自动备份调度
-    pub async fn start_auto_backup(&mut self) -> Result<()> {
-        info!("Starting automatic backup scheduling");
-
-        let mut interval = interval(tokio::time::Duration::from_secs(3600)); // 每小时检查一次
-
-        loop {
-            interval.tick().await;
-
-            for (config_name, config) in &self.configs {
-                // 检查是否有计划备份
-                if let Some(_schedule) = &config.schedule {
-                    // TODO: 解析cron表达式并检查是否到了备份时间
-                    // 这里简化处理，实际应用中可以使用cron库
-
-                    debug!("Checking backup schedule for: {}", config_name);
-
-                    // 检查是否已有正在进行的备份
-                    let has_active_backup = self
-                        .active_backups
-                        .values()
-                        .any(|b| b.config_name == *config_name);
-
-                    if !has_active_backup {
-                        if let Err(e) = self.execute_backup(config_name, BackupType::Full).await {
-                            error!("Auto backup failed for {}: {}", config_name, e);
-                        }
-                    }
+    /// 注册定时任务
+    ///
+    /// 每个配置了计划的备份配置注册一个 `backup:<配置名>` 任务，
+    /// 另注册一个每小时检查的恢复演练任务，返回注册的任务数
+    pub async fn schedule_jobs(
+        manager: Arc<Mutex<Self>>,
+        scheduler: &JobScheduler,
+    ) -> Result<usize> {
+        let definitions: Vec<(JobDefinition, String, BackupType)> = manager
+            .lock()
+            .await
+            .configs
+            .values()
+            .filter_map(|config| {
+                let schedule = config.schedule.as_ref()?;
+                let mut definition =
+                    JobDefinition::new(&format!("backup:{}", config.name), schedule);
+                if let Some(timezone) = &config.schedule_timezone {
+                    definition = definition.with_timezone(timezone);
                 }
-            }
+                Some((definition, config.name.clone(), config.backup_type.clone()))
+            })
+            .collect();
+
+        let mut registered = 0;
+        for (definition, config_name, backup_type) in definitions {
+            scheduler
+                .register(
+                    definition,
+                    Arc::new(BackupJob {
+                        manager: manager.clone(),
+                        config_name,
+                        backup_type,
+                    }),
+                )
+                .await?;
+            registered += 1;
         }
+
+        scheduler
+            .register(
+                JobDefinition::new(RESTORE_DRILL_JOB, RESTORE_DRILL_SCHEDULE),
+                Arc::new(RestoreDrillJob { manager }),
+            )
+            .await?;
+        Ok(registered + 1)
     }
 
     /// 创建默认备份配置
@@ -524,9 +1538,11 @@ impl BackupManager {
             target_storage,
             backup_prefix: "pacs_backup".to_string(),
             schedule: Some("0 2 * * *".to_string()), // 每天凌晨2点
-            retention_count: 7,                      // 保留7个备份
+            schedule_timezone: None,
+            retention_count: 7, // 保留7个备份
             compression_enabled: true,
             encryption_enabled: true,
+            restore_drill: Some(RestoreDrillConfig::default()),
         }
     }
 }
@@ -537,9 +1553,624 @@ impl Default for BackupManager {
     }
 }
 
+/// 定时备份任务
+pub struct BackupJob {
+    /// 备份管理器
+    manager: Arc<Mutex<BackupManager>>,
+    /// 备份配置名称
+    config_name: String,
+    /// 备份类型
+    backup_type: BackupType,
+}
+
+#[async_trait]
+impl ScheduledJob for BackupJob {
+    async fn run(&self) -> Result<String> {
+        let backup_id = BackupManager::execute_shared_backup(
+            &self.manager,
+            &self.config_name,
+            self.backup_type.clone(),
+        )
+        .await?;
+        Ok(format!("backup {} completed", backup_id))
+    }
+}
+
+/// 定期恢复演练任务
+pub struct RestoreDrillJob {
+    /// 备份管理器
+    manager: Arc<Mutex<BackupManager>>,
+}
+
+#[async_trait]
+impl ScheduledJob for RestoreDrillJob {
+    async fn run(&self) -> Result<String> {
+        let reports = self.manager.lock().await.run_due_restore_drills().await;
+        let failed = reports.iter().filter(|r| !r.passed).count();
+        if failed > 0 {
+            return Err(PacsError::Storage(format!(
+                "{} of {} restore drills failed",
+                failed,
+                reports.len()
+            )));
+        }
+        Ok(format!("{} restore drills passed", reports.len()))
+    }
+}
+
+/// 备份对象所在的根前缀
+fn backup_root(config: &BackupConfig) -> String {
+    config.backup_prefix.trim_matches('/').to_string()
+}
+
+/// 备份清单的存储路径
+fn manifest_path(config: &BackupConfig, backup_id: &str) -> String {
+    format!(
+        "{}/{}/{}.json",
+        backup_root(config),
+        MANIFEST_DIR,
+        backup_id
+    )
+}
+
+/// 存储配置的缓存键
+fn storage_key(config: &StorageConfig) -> String {
+    format!(
+        "{:?}:{:?}:{:?}",
+        config.storage_type, config.local_path, config.object_store_config
+    )
+}
+
+/// 遍历源存储，复制相对参考状态发生变化的文件
+///
+/// `gaps` 是基础备份中复制失败的文件，不在参考状态中，本次会重新复制；
+/// 已从源存储删除的记为删除，使其不再被视为缺失
+async fn copy_changed_files(backup: &PreparedBackup) -> Result<BackupRun> {
+    let PreparedBackup {
+        config,
+        info,
+        reference,
+        gaps,
+        source_storage,
+        target_storage,
+        key_ring,
+    } = backup;
+    let backup_id = &info.id;
+
+    // 源与目标为同一存储时跳过备份目录本身
+    let skip_prefix = (storage_key(&config.source_storage) == storage_key(&config.target_storage))
+        .then(|| format!("{}/", backup_root(config)));
+
+    let mut run = BackupRun::default();
+    let mut seen = HashSet::new();
+    let mut start_after: Option<String> = None;
+    loop {
+        let page = source_storage
+            .list_files_page(None, start_after.as_deref(), BACKUP_LIST_PAGE_SIZE)
+            .await?;
+
+        for object in page.objects {
+            if skip_prefix
+                .as_deref()
+                .is_some_and(|prefix| object.key.starts_with(prefix))
+            {
+                continue;
+            }
+            seen.insert(object.key.clone());
+
+            let previous = reference.get(&object.key);
+            if previous.is_some_and(|entry| {
+                entry.size == object.size && entry.modified_time == object.last_modified
+            }) {
+                continue;
+            }
+
+            let entry = match backup_object(
+                source_storage,
+                target_storage,
+                config,
+                backup_id,
+                &object,
+                previous,
+                key_ring.as_ref(),
+            )
+            .await
+            {
+                Ok(Some(entry)) => entry,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Failed to back up {}: {}", object.key, e);
+                    BackupFileEntry {
+                        original_path: object.key.clone(),
+                        backup_path: String::new(),
+                        size: object.size,
+                        hash: String::new(),
+                        modified_time: object.last_modified,
+                        backup_status: BackupStatus::Failed,
+                        compressed: false,
+                        key_id: None,
+                    }
+                }
+            };
+
+            if entry.backup_status == BackupStatus::Completed {
+                run.file_count += 1;
+                run.total_size += entry.size;
+            }
+            run.file_manifest.push(entry);
+        }
+
+        match page.next_start_after {
+            Some(next) => start_after = Some(next),
+            None => break,
+        }
+    }
+
+    run.deleted_paths = reference
+        .keys()
+        .chain(gaps)
+        .filter(|path| !seen.contains(*path))
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    debug!(
+        "Backup {} scanned {} files, copied {}",
+        backup_id,
+        seen.len(),
+        run.file_count
+    );
+    Ok(run)
+}
+
+/// 备份单个对象，内容与参考条目相同时返回None
+async fn backup_object(
+    source_storage: &StorageManager,
+    target_storage: &StorageManager,
+    config: &BackupConfig,
+    backup_id: &str,
+    object: &ObjectMetadata,
+    previous: Option<&BackupFileEntry>,
+    key_ring: Option<&KeyRing>,
+) -> Result<Option<BackupFileEntry>> {
+    let file_data = source_storage.get_file(&object.key).await?;
+    let hash = calculate_file_hash(&file_data);
+    if previous.is_some_and(|entry| entry.hash == hash) {
+        return Ok(None);
+    }
+
+    let mut entry = BackupFileEntry {
+        original_path: object.key.clone(),
+        backup_path: format!("{}/{}/data/{}", backup_root(config), backup_id, object.key),
+        size: file_data.len() as u64,
+        hash,
+        modified_time: object.last_modified,
+        backup_status: BackupStatus::Completed,
+        compressed: config.compression_enabled,
+        key_id: None,
+    };
+    let mut stored = file_data;
+    if entry.compressed {
+        stored = zstd::bulk::compress(&stored, BACKUP_COMPRESSION_LEVEL)
+            .map_err(|e| PacsError::Storage(format!("Failed to compress {}: {}", object.key, e)))?;
+    }
+    if let Some(key_ring) = key_ring {
+        let sealed = key_ring.seal(&stored, &object_aad(&entry))?;
+        stored = sealed.data;
+        entry.key_id = Some(sealed.key_id);
+    }
+    target_storage
+        .store_file(&stored, &entry.backup_path)
+        .await?;
+
+    Ok(Some(entry))
+}
+
+/// 备份对象的附加认证数据
+///
+/// 绑定原始路径以及清单中的哈希、大小和压缩标记，篡改清单中的这些字段
+/// 或在对象之间调换密文都会使解密失败；密钥ID决定解密所用的密钥，同样无法替换
+fn object_aad(entry: &BackupFileEntry) -> Vec<u8> {
+    format!(
+        "{}\0{}\0{}\0{}",
+        entry.original_path, entry.hash, entry.size, entry.compressed
+    )
+    .into_bytes()
+}
+
+/// 还原备份对象的原始内容（先解密再解压）
+///
+/// 解密以原始路径和清单字段作为附加认证数据，密文或清单被篡改、
+/// 密文在对象之间调换时返回错误
+fn decode_backup_object(
+    entry: &BackupFileEntry,
+    stored: Vec<u8>,
+    key_ring: Option<&KeyRing>,
+) -> Result<Vec<u8>> {
+    let mut data = stored;
+    if let Some(key_id) = &entry.key_id {
+        let key_ring = key_ring.ok_or_else(|| {
+            PacsError::Config(format!(
+                "Backup object {} is encrypted but no backup keys are configured",
+                entry.original_path
+            ))
+        })?;
+        data = key_ring.open(key_id, &data, &object_aad(entry))?;
+    }
+    if entry.compressed {
+        data = zstd::stream::decode_all(data.as_slice()).map_err(|e| {
+            PacsError::Storage(format!(
+                "Failed to decompress backup object {}: {}",
+                entry.original_path, e
+            ))
+        })?;
+    }
+    Ok(data)
+}
+
+/// 读取并还原备份对象，校验哈希与清单一致
+async fn read_backup_object(
+    backup_storage: &StorageManager,
+    entry: &BackupFileEntry,
+    key_ring: Option<&KeyRing>,
+) -> Result<Vec<u8>> {
+    let stored = backup_storage.get_file(&entry.backup_path).await?;
+    let file_data = decode_backup_object(entry, stored, key_ring)?;
+
+    // 计算文件哈希以验证完整性
+    let hash = calculate_file_hash(&file_data);
+    if hash != entry.hash {
+        return Err(PacsError::Storage(format!(
+            "File hash mismatch for {} (expected: {}, actual: {})",
+            entry.original_path, entry.hash, hash
+        )));
+    }
+    Ok(file_data)
+}
+
+/// 恢复单个演练样本到临时存储，并与在线存储比对
+async fn restore_drill_sample(
+    backup_storage: &StorageManager,
+    scratch_storage: &StorageManager,
+    live_storage: &StorageManager,
+    entry: &BackupFileEntry,
+    key_ring: Option<&KeyRing>,
+    parse_dicom_headers: bool,
+) -> Result<DrillOutcome> {
+    let file_data = read_backup_object(backup_storage, entry, key_ring).await?;
+    if parse_dicom_headers && looks_like_dicom(&entry.original_path, &file_data) {
+        check_dicom_header(&file_data)?;
+    }
+
+    scratch_storage
+        .store_file(&file_data, &entry.original_path)
+        .await?;
+    let restored = scratch_storage.get_file(&entry.original_path).await?;
+    if calculate_file_hash(&restored) != entry.hash {
+        return Err(PacsError::Storage(format!(
+            "Restored copy of {} differs from the backup manifest",
+            entry.original_path
+        )));
+    }
+
+    match live_storage.get_file(&entry.original_path).await {
+        Ok(live) if calculate_file_hash(&live) == entry.hash => Ok(DrillOutcome::Matched),
+        Ok(_) => Ok(DrillOutcome::LiveModified),
+        Err(PacsError::NotFound(_)) => Ok(DrillOutcome::LiveMissing),
+        Err(e) => Err(e),
+    }
+}
+
+/// 判断对象是否应按DICOM文件解析
+fn looks_like_dicom(path: &str, data: &[u8]) -> bool {
+    path.to_ascii_lowercase().ends_with(".dcm")
+        || data.get(DICOM_PREAMBLE_LENGTH..DICOM_PREAMBLE_LENGTH + 4) == Some(b"DICM".as_slice())
+}
+
+/// 解析DICOM文件头（读到像素数据之前），并检查SOP实例UID
+fn check_dicom_header(data: &[u8]) -> Result<()> {
+    let body = match data.get(DICOM_PREAMBLE_LENGTH..DICOM_PREAMBLE_LENGTH + 4) {
+        Some(magic) if magic == b"DICM" => &data[DICOM_PREAMBLE_LENGTH..],
+        _ => data,
+    };
+
+    let object = OpenFileOptions::new()
+        .read_until(tags::PIXEL_DATA)
+        .from_reader(body)
+        .map_err(|e| PacsError::DicomParseError(format!("Invalid DICOM header: {}", e)))?;
+    if object
+        .meta()
+        .media_storage_sop_instance_uid()
+        .trim_end_matches('\0')
+        .is_empty()
+    {
+        return Err(PacsError::DicomParseError(
+            "DICOM header has no SOP Instance UID".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 从候选对象中随机抽取指定数量
+fn random_sample<T>(mut items: Vec<T>, count: usize) -> Vec<T> {
+    let count = count.min(items.len());
+    for i in 0..count {
+        let j = i + (OsRng.next_u64() % (items.len() - i) as u64) as usize;
+        items.swap(i, j);
+    }
+    items.truncate(count);
+    items
+}
+
 /// 计算文件哈希值
 fn calculate_file_hash(data: &[u8]) -> String {
     let mut hasher = Sha256::new();
     hasher.update(data);
     format!("{:x}", hasher.finalize())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 创建本地存储配置
+    fn local_storage(dir: &Path) -> StorageConfig {
+        StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(dir.to_string_lossy().into_owned()),
+            object_store_config: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup_verify_and_drill() {
+        let root = std::env::temp_dir().join(format!(
+            "pacs-backup-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let source = StorageManager::new(local_storage(&root.join("live")))
+            .await
+            .unwrap();
+        source
+            .store_file(b"study one", "1.2.3/a.bin")
+            .await
+            .unwrap();
+        source
+            .store_file(b"study two", "1.2.4/b.bin")
+            .await
+            .unwrap();
+
+        let mut config = BackupManager::create_default_config(
+            local_storage(&root.join("live")),
+            local_storage(&root.join("backup")),
+        );
+        config.restore_drill = Some(RestoreDrillConfig {
+            scratch_path: root.join("scratch").to_string_lossy().into_owned(),
+            ..RestoreDrillConfig::default()
+        });
+        let mut manager = BackupManager::new().with_key_ring(KeyRing::new("k1", [7; 32]).unwrap());
+        manager.add_config(config.clone()).unwrap();
+
+        let backup_id = manager
+            .execute_backup(&config.name, BackupType::Full)
+            .await
+            .unwrap();
+        assert!(manager
+            .verify_backup(&backup_id, true)
+            .await
+            .unwrap()
+            .passed());
+
+        source.store_file(b"changed", "1.2.4/b.bin").await.unwrap();
+        let reports = manager.run_due_restore_drills().await;
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].passed);
+        assert!(reports[0]
+            .samples
+            .iter()
+            .any(|s| s.outcome == DrillOutcome::LiveModified));
+        manager.verify_drill_report(&reports[0]).unwrap();
+        assert!(manager.run_due_restore_drills().await.is_empty());
+
+        let mut forged = reports[0].clone();
+        forged.passed = !forged.passed;
+        assert!(manager.verify_drill_report(&forged).is_err());
+
+        let entry = manager.backup_state(&backup_id).unwrap()["1.2.3/a.bin"].clone();
+        let backup_storage = StorageManager::new(local_storage(&root.join("backup")))
+            .await
+            .unwrap();
+        let mut stored = backup_storage.get_file(&entry.backup_path).await.unwrap();
+        *stored.last_mut().unwrap() ^= 1;
+        backup_storage
+            .store_file(&stored, &entry.backup_path)
+            .await
+            .unwrap();
+        let report = manager.verify_backup(&backup_id, false).await.unwrap();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].path, "1.2.3/a.bin");
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_restore_drill_without_keys_is_unsigned() {
+        let root = std::env::temp_dir().join(format!(
+            "pacs-backup-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let source = StorageManager::new(local_storage(&root.join("live")))
+            .await
+            .unwrap();
+        source
+            .store_file(b"study one", "1.2.3/a.bin")
+            .await
+            .unwrap();
+
+        let mut config = BackupManager::create_default_config(
+            local_storage(&root.join("live")),
+            local_storage(&root.join("backup")),
+        );
+        config.encryption_enabled = false;
+        let drill = RestoreDrillConfig {
+            scratch_path: root.join("scratch").to_string_lossy().into_owned(),
+            ..RestoreDrillConfig::default()
+        };
+        let mut manager = BackupManager::new();
+        manager.add_config(config.clone()).unwrap();
+        let backup_id = manager
+            .execute_backup(&config.name, BackupType::Full)
+            .await
+            .unwrap();
+
+        let report = manager.run_restore_drill(&backup_id, &drill).await.unwrap();
+        assert!(report.passed);
+        assert!(report.signature.is_none());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_fields_are_authenticated() {
+        let root = std::env::temp_dir().join(format!(
+            "pacs-backup-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let source = StorageManager::new(local_storage(&root.join("live")))
+            .await
+            .unwrap();
+        source
+            .store_file(b"study one", "1.2.3/a.bin")
+            .await
+            .unwrap();
+
+        let config = BackupManager::create_default_config(
+            local_storage(&root.join("live")),
+            local_storage(&root.join("backup")),
+        );
+        let key_ring = KeyRing::new("k1", [7; 32]).unwrap();
+        let mut manager = BackupManager::new().with_key_ring(key_ring.clone());
+        manager.add_config(config.clone()).unwrap();
+        let backup_id = manager
+            .execute_backup(&config.name, BackupType::Full)
+            .await
+            .unwrap();
+
+        // 篡改清单字段后密文无法通过认证
+        let backup_storage = StorageManager::new(local_storage(&root.join("backup")))
+            .await
+            .unwrap();
+        let entry = manager.backup_state(&backup_id).unwrap()["1.2.3/a.bin"].clone();
+        assert!(read_backup_object(&backup_storage, &entry, Some(&key_ring))
+            .await
+            .is_ok());
+        for tampered in [
+            BackupFileEntry {
+                compressed: !entry.compressed,
+                ..entry.clone()
+            },
+            BackupFileEntry {
+                hash: calculate_file_hash(b"forged"),
+                ..entry.clone()
+            },
+            BackupFileEntry {
+                size: entry.size + 1,
+                ..entry.clone()
+            },
+        ] {
+            assert!(
+                read_backup_object(&backup_storage, &tampered, Some(&key_ring))
+                    .await
+                    .is_err()
+            );
+        }
+
+        // 签名的清单可以加载，被改动的清单被跳过
+        let path = manifest_path(&config, &backup_id);
+        let mut reloaded = BackupManager::new().with_key_ring(key_ring.clone());
+        reloaded.add_config(config.clone()).unwrap();
+        assert_eq!(reloaded.load_backup_history().await.unwrap(), 1);
+
+        let manifest = backup_storage.get_file(&path).await.unwrap();
+        let mut forged: BackupInfo = serde_json::from_slice(&manifest).unwrap();
+        forged.deleted_paths.push("1.2.3/a.bin".to_string());
+        backup_storage
+            .store_file(&serde_json::to_vec(&forged).unwrap(), &path)
+            .await
+            .unwrap();
+        let mut reloaded = BackupManager::new().with_key_ring(key_ring);
+        reloaded.add_config(config.clone()).unwrap();
+        assert_eq!(reloaded.load_backup_history().await.unwrap(), 0);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failed_entries_mark_backup_partial_and_restore_reports_gap() {
+        let root = std::env::temp_dir().join(format!(
+            "pacs-backup-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let source = StorageManager::new(local_storage(&root.join("live")))
+            .await
+            .unwrap();
+        source
+            .store_file(b"study one", "1.2.3/a.bin")
+            .await
+            .unwrap();
+        // 悬空的符号链接会被列出但无法读取
+        std::os::unix::fs::symlink(root.join("missing"), root.join("live/1.2.3/b.bin")).unwrap();
+
+        let config = BackupManager::create_default_config(
+            local_storage(&root.join("live")),
+            local_storage(&root.join("backup")),
+        );
+        let mut manager = BackupManager::new().with_key_ring(KeyRing::new("k1", [7; 32]).unwrap());
+        manager.add_config(config.clone()).unwrap();
+
+        let full_id = manager
+            .execute_backup(&config.name, BackupType::Full)
+            .await
+            .unwrap();
+        let full = manager.get_backup_list().last().unwrap().clone();
+        assert_eq!(full.status, BackupStatus::Partial);
+        assert!(full.error_message.is_some());
+        assert_eq!(manager.backup_gaps(&full_id).unwrap(), vec!["1.2.3/b.bin"]);
+
+        let restored = StorageManager::new(local_storage(&root.join("restore")))
+            .await
+            .unwrap();
+        let restore = manager
+            .restore_backup_to(&full_id, &restored)
+            .await
+            .unwrap();
+        assert_eq!(restore.status, BackupStatus::Partial);
+        assert_eq!(restore.missing_paths, vec!["1.2.3/b.bin"]);
+        assert_eq!(restore.file_count, 1);
+
+        // 下一次增量备份补齐缺失的文件
+        std::fs::remove_file(root.join("live/1.2.3/b.bin")).unwrap();
+        source
+            .store_file(b"study two", "1.2.3/b.bin")
+            .await
+            .unwrap();
+        let incremental_id = manager
+            .execute_backup(&config.name, BackupType::Incremental)
+            .await
+            .unwrap();
+        assert!(manager.backup_gaps(&incremental_id).unwrap().is_empty());
+        let restore = manager
+            .restore_backup_to(&incremental_id, &restored)
+            .await
+            .unwrap();
+        assert_eq!(restore.status, BackupStatus::Completed);
+        assert_eq!(
+            restored.get_file("1.2.3/b.bin").await.unwrap(),
+            b"study two".to_vec()
+        );
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
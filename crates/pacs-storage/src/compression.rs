@@ -0,0 +1,351 @@
+//! 归档对象压缩
+//!
+//! 归档对象以自描述头部开头，记录压缩算法、级别、原始大小和原始数据的SHA-256哈希，
+//! 恢复时只依赖对象本身，不依赖归档任务历史或当前策略配置。
+//!
+//! 头部格式（50字节，整数均为小端序）：
+//!
+//! | 偏移 | 长度 | 内容                                   |
+//! |------|------|----------------------------------------|
+//! | 0    | 7    | 魔数 `PACSARC`                         |
+//! | 7    | 1    | 格式版本                               |
+//! | 8    | 1    | 算法（0 不压缩，1 Gzip，2 Zstd，3 LZ4） |
+//! | 9    | 1    | 压缩级别                               |
+//! | 10   | 8    | 原始大小                               |
+//! | 18   | 32   | 原始数据SHA-256                        |
+
+use dicom::dictionary_std::tags;
+use dicom::object::OpenFileOptions;
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+
+/// 归档对象头部魔数
+pub const ARCHIVE_MAGIC: &[u8; 7] = b"PACSARC";
+
+/// 归档对象头部格式版本
+const ARCHIVE_FORMAT_VERSION: u8 = 1;
+
+/// 归档对象头部长度
+pub const ARCHIVE_HEADER_LENGTH: usize = 50;
+
+/// DICOM文件前导长度
+const DICOM_PREAMBLE_LENGTH: usize = 128;
+
+/// 压缩设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompressionSettings {
+    /// 压缩算法
+    pub algorithm: CompressionAlgorithm,
+    /// 压缩级别（超出算法支持范围时取最近的有效值）
+    pub level: u8,
+}
+
+impl CompressionSettings {
+    /// 创建压缩设置
+    pub fn new(algorithm: CompressionAlgorithm, level: u8) -> Self {
+        Self { algorithm, level }
+    }
+
+    /// 实际使用的压缩级别
+    pub fn effective_level(&self) -> u8 {
+        let range = self.algorithm.level_range();
+        self.level.clamp(*range.start(), *range.end())
+    }
+}
+
+/// 压缩算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CompressionAlgorithm {
+    /// Gzip
+    Gzip,
+    /// Zstd
+    Zstd,
+    /// LZ4
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    /// 支持的压缩级别范围（LZ4 3级以上为高压缩模式）
+    pub fn level_range(&self) -> RangeInclusive<u8> {
+        match self {
+            CompressionAlgorithm::Gzip => 0..=9,
+            CompressionAlgorithm::Zstd => 1..=22,
+            CompressionAlgorithm::Lz4 => 0..=16,
+        }
+    }
+
+    /// 头部中的算法编号
+    fn code(&self) -> u8 {
+        match self {
+            CompressionAlgorithm::Gzip => 1,
+            CompressionAlgorithm::Zstd => 2,
+            CompressionAlgorithm::Lz4 => 3,
+        }
+    }
+
+    /// 由头部中的算法编号解析，0表示不压缩
+    fn from_code(code: u8) -> Result<Option<Self>> {
+        match code {
+            0 => Ok(None),
+            1 => Ok(Some(CompressionAlgorithm::Gzip)),
+            2 => Ok(Some(CompressionAlgorithm::Zstd)),
+            3 => Ok(Some(CompressionAlgorithm::Lz4)),
+            _ => Err(PacsError::Storage(format!(
+                "Unknown archive compression algorithm: {}",
+                code
+            ))),
+        }
+    }
+}
+
+/// 归档对象头部
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveObjectHeader {
+    /// 压缩算法（None表示未压缩）
+    pub algorithm: Option<CompressionAlgorithm>,
+    /// 压缩级别
+    pub level: u8,
+    /// 原始大小
+    pub original_size: u64,
+    /// 原始数据SHA-256（十六进制）
+    pub original_hash: String,
+}
+
+impl ArchiveObjectHeader {
+    /// 解析归档对象头部，返回头部与压缩后的数据
+    ///
+    /// 对象不以魔数开头时返回None（旧格式的归档对象）
+    pub fn parse(data: &[u8]) -> Result<Option<(Self, &[u8])>> {
+        if !data.starts_with(ARCHIVE_MAGIC) {
+            return Ok(None);
+        }
+        if data.len() < ARCHIVE_HEADER_LENGTH {
+            return Err(PacsError::Storage(
+                "Archive object header is truncated".to_string(),
+            ));
+        }
+        if data[7] != ARCHIVE_FORMAT_VERSION {
+            return Err(PacsError::Storage(format!(
+                "Unsupported archive object format version: {}",
+                data[7]
+            )));
+        }
+
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&data[10..18]);
+        let header = Self {
+            algorithm: CompressionAlgorithm::from_code(data[8])?,
+            level: data[9],
+            original_size: u64::from_le_bytes(size),
+            original_hash: hex(&data[18..ARCHIVE_HEADER_LENGTH]),
+        };
+        Ok(Some((header, &data[ARCHIVE_HEADER_LENGTH..])))
+    }
+
+    /// 序列化头部
+    fn to_bytes(&self, hash: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(ARCHIVE_HEADER_LENGTH);
+        bytes.extend_from_slice(ARCHIVE_MAGIC);
+        bytes.push(ARCHIVE_FORMAT_VERSION);
+        bytes.push(self.algorithm.map(|a| a.code()).unwrap_or(0));
+        bytes.push(self.level);
+        bytes.extend_from_slice(&self.original_size.to_le_bytes());
+        bytes.extend_from_slice(hash);
+        bytes
+    }
+}
+
+/// 压缩数据
+pub fn compress(data: &[u8], settings: &CompressionSettings) -> Result<Vec<u8>> {
+    let level = settings.effective_level();
+    match settings.algorithm {
+        CompressionAlgorithm::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level.into()));
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        CompressionAlgorithm::Zstd => Ok(zstd::bulk::compress(data, level.into())?),
+        CompressionAlgorithm::Lz4 => {
+            let mut encoder = lz4::EncoderBuilder::new()
+                .level(level.into())
+                .content_size(data.len() as u64)
+                .build(Vec::with_capacity(data.len() / 2))?;
+            encoder.write_all(data)?;
+            let (compressed, result) = encoder.finish();
+            result?;
+            Ok(compressed)
+        }
+    }
+}
+
+/// 解压数据，`size_hint` 用于预分配输出缓冲
+pub fn decompress(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+    size_hint: usize,
+) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::with_capacity(size_hint);
+    match algorithm {
+        CompressionAlgorithm::Gzip => {
+            flate2::read::GzDecoder::new(data).read_to_end(&mut decompressed)?;
+        }
+        CompressionAlgorithm::Zstd => {
+            zstd::stream::read::Decoder::new(data)?.read_to_end(&mut decompressed)?;
+        }
+        CompressionAlgorithm::Lz4 => {
+            lz4::Decoder::new(data)?.read_to_end(&mut decompressed)?;
+        }
+    }
+    Ok(decompressed)
+}
+
+/// 编码归档对象：写入自描述头部并按设置压缩
+pub fn encode_archive_object(
+    data: &[u8],
+    settings: Option<&CompressionSettings>,
+) -> Result<Vec<u8>> {
+    let hash = Sha256::digest(data);
+    let header = ArchiveObjectHeader {
+        algorithm: settings.map(|s| s.algorithm),
+        level: settings.map(|s| s.effective_level()).unwrap_or(0),
+        original_size: data.len() as u64,
+        original_hash: String::new(),
+    };
+
+    let mut object = header.to_bytes(&hash);
+    match settings {
+        Some(settings) => object.extend_from_slice(&compress(data, settings)?),
+        None => object.extend_from_slice(data),
+    }
+    Ok(object)
+}
+
+/// 解码归档对象，校验原始大小与哈希
+pub fn decode_archive_object(object: &[u8]) -> Result<(ArchiveObjectHeader, Vec<u8>)> {
+    let (header, payload) = ArchiveObjectHeader::parse(object)?
+        .ok_or_else(|| PacsError::Storage("Archive object has no header".to_string()))?;
+
+    let size_hint = usize::try_from(header.original_size).unwrap_or(0);
+    let data = match header.algorithm {
+        Some(algorithm) => decompress(payload, algorithm, size_hint)?,
+        None => payload.to_vec(),
+    };
+
+    if data.len() as u64 != header.original_size {
+        return Err(PacsError::Storage(format!(
+            "Archive object size mismatch: expected {} bytes, got {}",
+            header.original_size,
+            data.len()
+        )));
+    }
+    if hex(&Sha256::digest(&data)) != header.original_hash {
+        return Err(PacsError::Storage(
+            "Archive object hash mismatch".to_string(),
+        ));
+    }
+    Ok((header, data))
+}
+
+/// 按模态的默认压缩设置
+///
+/// 依据 `benches/archive_compression.rs` 的测量结果选择：
+/// - 未压缩的灰度图像（CT/MR/PT/NM/CR/DX/MG）及结构化报告等无像素对象（SR/KO/PR）：
+///   Zstd 9 比 Zstd 3 多压缩 0.5~3 个百分点（DX 最明显），压缩速度仍有数十MB/s；
+///   Zstd 19 再多 1~3 个百分点但压缩慢一个数量级，不适合批量归档
+/// - 像素数据通常已有损压缩的模态（US/ES/XC）：任何算法都几乎无收益，
+///   Zstd 3 对不可压缩数据直通最快
+/// - LZ4 解压最快，但压缩率明显落后，不作为归档默认值
+pub fn default_modality_compression() -> HashMap<String, CompressionSettings> {
+    let zstd = |level| CompressionSettings::new(CompressionAlgorithm::Zstd, level);
+    let mut defaults = HashMap::new();
+    for modality in ["CT", "MR", "PT", "NM", "CR", "DX", "MG", "SR", "KO", "PR"] {
+        defaults.insert(modality.to_string(), zstd(9));
+    }
+    for modality in ["US", "ES", "XC"] {
+        defaults.insert(modality.to_string(), zstd(3));
+    }
+    defaults
+}
+
+/// 读取DICOM对象的模态，非DICOM数据或缺少模态时返回None
+pub fn dicom_modality(data: &[u8]) -> Option<String> {
+    let body = match data.get(DICOM_PREAMBLE_LENGTH..DICOM_PREAMBLE_LENGTH + 4) {
+        Some(magic) if magic == b"DICM" => &data[DICOM_PREAMBLE_LENGTH..],
+        _ => data,
+    };
+
+    let object = OpenFileOptions::new()
+        .read_until(tags::PIXEL_DATA)
+        .from_reader(body)
+        .ok()?;
+    let modality = object.element(tags::MODALITY).ok()?.to_str().ok()?;
+    let modality = modality.trim_end_matches(['\0', ' ']).to_uppercase();
+    (!modality.is_empty()).then_some(modality)
+}
+
+/// 十六进制编码
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_object_round_trip() {
+        let data: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+
+        for algorithm in [
+            CompressionAlgorithm::Gzip,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Lz4,
+        ] {
+            let settings = CompressionSettings::new(algorithm, 30);
+            let object = encode_archive_object(&data, Some(&settings)).unwrap();
+            assert!(object.len() < data.len());
+
+            let (header, decoded) = decode_archive_object(&object).unwrap();
+            assert_eq!(header.algorithm, Some(algorithm));
+            assert_eq!(header.level, *algorithm.level_range().end());
+            assert_eq!(header.original_size, data.len() as u64);
+            assert_eq!(decoded, data);
+        }
+
+        let mut object = encode_archive_object(&data, None).unwrap();
+        assert_eq!(decode_archive_object(&object).unwrap().1, data);
+        *object.last_mut().unwrap() ^= 1;
+        assert!(decode_archive_object(&object).is_err());
+        assert!(ArchiveObjectHeader::parse(&data).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_dicom_modality() {
+        use dicom::core::{DataElement, VR};
+        use dicom::dictionary_std::uids;
+        use dicom::object::{FileMetaTableBuilder, InMemDicomObject};
+
+        let mut bytes = Vec::new();
+        InMemDicomObject::from_element_iter([
+            DataElement::new(tags::SOP_CLASS_UID, VR::UI, uids::CT_IMAGE_STORAGE),
+            DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, "1.2.3.4"),
+            DataElement::new(tags::MODALITY, VR::CS, "ct"),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+        .unwrap()
+        .write_all(&mut bytes)
+        .unwrap();
+
+        assert_eq!(dicom_modality(&bytes).as_deref(), Some("CT"));
+        assert_eq!(dicom_modality(b"not dicom"), None);
+        assert_eq!(
+            default_modality_compression()["CT"],
+            CompressionSettings::new(CompressionAlgorithm::Zstd, 9)
+        );
+    }
+}
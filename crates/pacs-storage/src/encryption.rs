@@ -0,0 +1,356 @@
+//! 备份加密
+//!
+//! 使用AES-256-GCM对备份对象做认证加密，每个对象使用随机nonce，
+//! 调用方提供的附加认证数据（备份对象的路径和清单字段）防止密文被篡改或在对象之间调换。
+//! 密钥环按密钥ID保存多把密钥，新备份使用当前密钥，旧密钥保留用于解密，
+//! 从而支持密钥轮换。备份清单和恢复演练报告使用由同一密钥派生的HMAC-SHA256密钥签名。
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use hmac::{Hmac, Mac};
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// 指定密钥文件路径的环境变量
+pub const BACKUP_KEY_FILE_ENV: &str = "PACS_BACKUP_KEY_FILE";
+
+/// 直接提供密钥的环境变量（`<密钥ID>:<64位十六进制>`，多把密钥以逗号分隔）
+pub const BACKUP_KEYS_ENV: &str = "PACS_BACKUP_KEYS";
+
+/// nonce长度（字节）
+const NONCE_LENGTH: usize = 12;
+
+/// 密钥长度（字节）
+const KEY_LENGTH: usize = 32;
+
+/// 报告签名算法名称
+pub const REPORT_SIGNATURE_ALGORITHM: &str = "HMAC-SHA256";
+
+/// 派生报告签名密钥时使用的上下文，避免加密密钥被直接用于签名
+const SIGNING_CONTEXT: &[u8] = b"pacs-backup-report-signing";
+
+/// 报告签名
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportSignature {
+    /// 签名所用的密钥ID
+    pub key_id: String,
+    /// 签名算法
+    pub algorithm: String,
+    /// 签名值（十六进制）
+    pub value: String,
+}
+
+/// 加密后的对象
+#[derive(Debug, Clone)]
+pub struct SealedObject {
+    /// 加密所用的密钥ID
+    pub key_id: String,
+    /// nonce与密文（含认证标签）
+    pub data: Vec<u8>,
+}
+
+/// 备份密钥环
+///
+/// 密钥文件每行一把密钥，格式为 `<密钥ID>:<64位十六进制>`，`#` 开头为注释；
+/// 最后一把密钥为当前加密密钥，轮换时追加新行即可
+#[derive(Clone)]
+pub struct KeyRing {
+    /// 密钥ID到密钥的映射
+    keys: HashMap<String, [u8; KEY_LENGTH]>,
+    /// 当前加密密钥ID
+    active_key_id: String,
+}
+
+impl KeyRing {
+    /// 以单把密钥创建密钥环
+    pub fn new(key_id: &str, key: [u8; KEY_LENGTH]) -> Result<Self> {
+        validate_key_id(key_id)?;
+        Ok(Self {
+            keys: HashMap::from([(key_id.to_string(), key)]),
+            active_key_id: key_id.to_string(),
+        })
+    }
+
+    /// 解析密钥定义文本（每行或逗号分隔一把密钥）
+    pub fn parse(definitions: &str) -> Result<Self> {
+        let mut ring: Option<Self> = None;
+
+        for definition in definitions
+            .split(['\n', ','])
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            let (key_id, hex) = definition.split_once(':').ok_or_else(|| {
+                PacsError::Config("Backup key must be formatted as <id>:<hex>".to_string())
+            })?;
+            let key = decode_key(hex.trim())?;
+            match ring.as_mut() {
+                Some(ring) => ring.rotate(key_id.trim(), key)?,
+                None => ring = Some(Self::new(key_id.trim(), key)?),
+            }
+        }
+
+        ring.ok_or_else(|| PacsError::Config("No backup keys defined".to_string()))
+    }
+
+    /// 从密钥文件加载
+    pub fn from_key_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            PacsError::Config(format!(
+                "Failed to read backup key file {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        Self::parse(&content)
+    }
+
+    /// 从环境变量加载，优先使用密钥文件，均未设置时返回None
+    pub fn from_environment() -> Result<Option<Self>> {
+        if let Ok(path) = std::env::var(BACKUP_KEY_FILE_ENV) {
+            return Self::from_key_file(path).map(Some);
+        }
+        match std::env::var(BACKUP_KEYS_ENV) {
+            Ok(definitions) => Self::parse(&definitions).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// 添加新密钥并设为当前加密密钥，旧密钥保留用于解密
+    pub fn rotate(&mut self, key_id: &str, key: [u8; KEY_LENGTH]) -> Result<()> {
+        validate_key_id(key_id)?;
+        if self
+            .keys
+            .get(key_id)
+            .is_some_and(|existing| *existing != key)
+        {
+            return Err(PacsError::Config(format!(
+                "Backup key ID {} is already used by a different key",
+                key_id
+            )));
+        }
+
+        self.keys.insert(key_id.to_string(), key);
+        self.active_key_id = key_id.to_string();
+        Ok(())
+    }
+
+    /// 当前加密密钥ID
+    pub fn active_key_id(&self) -> &str {
+        &self.active_key_id
+    }
+
+    /// 是否持有指定密钥
+    pub fn contains(&self, key_id: &str) -> bool {
+        self.keys.contains_key(key_id)
+    }
+
+    /// 使用当前密钥加密
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<SealedObject> {
+        let cipher = self.cipher(&self.active_key_id)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| PacsError::Internal("Backup encryption failed".to_string()))?;
+
+        let mut data = Vec::with_capacity(NONCE_LENGTH + ciphertext.len());
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(SealedObject {
+            key_id: self.active_key_id.clone(),
+            data,
+        })
+    }
+
+    /// 解密并校验认证标签，密文或附加数据被篡改时返回错误
+    pub fn open(&self, key_id: &str, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < NONCE_LENGTH {
+            return Err(PacsError::Storage(
+                "Encrypted backup object is truncated".to_string(),
+            ));
+        }
+
+        let cipher = self.cipher(key_id)?;
+        let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| {
+                PacsError::Storage(format!(
+                    "Backup object failed authentication with key {} (tampered or wrong key)",
+                    key_id
+                ))
+            })
+    }
+
+    /// 使用当前密钥派生的签名密钥对数据签名
+    pub fn sign(&self, data: &[u8]) -> Result<ReportSignature> {
+        let mut mac = self.signing_mac(&self.active_key_id)?;
+        mac.update(data);
+        let value = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Ok(ReportSignature {
+            key_id: self.active_key_id.clone(),
+            algorithm: REPORT_SIGNATURE_ALGORITHM.to_string(),
+            value,
+        })
+    }
+
+    /// 校验数据签名，签名不匹配时返回错误
+    pub fn verify_signature(&self, data: &[u8], signature: &ReportSignature) -> Result<()> {
+        if signature.algorithm != REPORT_SIGNATURE_ALGORITHM {
+            return Err(PacsError::Validation(format!(
+                "Unsupported signature algorithm: {}",
+                signature.algorithm
+            )));
+        }
+        let expected = decode_hex(&signature.value)
+            .ok_or_else(|| PacsError::Validation("Signature is not valid hex".to_string()))?;
+
+        let mut mac = self.signing_mac(&signature.key_id)?;
+        mac.update(data);
+        mac.verify_slice(&expected).map_err(|_| {
+            PacsError::Validation(format!(
+                "Signature verification failed with key {}",
+                signature.key_id
+            ))
+        })
+    }
+
+    /// 创建指定密钥派生的签名器
+    fn signing_mac(&self, key_id: &str) -> Result<Hmac<Sha256>> {
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| PacsError::Config(format!("Backup key {} not available", key_id)))?;
+        let mut derive = <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .map_err(|e| PacsError::Internal(e.to_string()))?;
+        derive.update(SIGNING_CONTEXT);
+        let signing_key = derive.finalize().into_bytes();
+        <Hmac<Sha256> as Mac>::new_from_slice(&signing_key)
+            .map_err(|e| PacsError::Internal(e.to_string()))
+    }
+
+    /// 创建指定密钥的加密器
+    fn cipher(&self, key_id: &str) -> Result<Aes256Gcm> {
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| PacsError::Config(format!("Backup key {} not available", key_id)))?;
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)))
+    }
+}
+
+impl fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut key_ids: Vec<&String> = self.keys.keys().collect();
+        key_ids.sort();
+        f.debug_struct("KeyRing")
+            .field("key_ids", &key_ids)
+            .field("active_key_id", &self.active_key_id)
+            .finish()
+    }
+}
+
+/// 校验密钥ID
+fn validate_key_id(key_id: &str) -> Result<()> {
+    if key_id.is_empty()
+        || !key_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(PacsError::Config(format!(
+            "Invalid backup key ID: {:?}",
+            key_id
+        )));
+    }
+    Ok(())
+}
+
+/// 解析64位十六进制密钥
+fn decode_key(hex: &str) -> Result<[u8; KEY_LENGTH]> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(PacsError::Config(
+            "Backup key contains non-hex characters".to_string(),
+        ));
+    }
+    if hex.len() != KEY_LENGTH * 2 {
+        return Err(PacsError::Config(format!(
+            "Backup key must be {} hex characters",
+            KEY_LENGTH * 2
+        )));
+    }
+
+    decode_hex(hex)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| PacsError::Config("Backup key contains non-hex characters".to_string()))
+}
+
+/// 解析十六进制字符串
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_and_rotation() {
+        let mut ring = KeyRing::parse(&format!("# keys\nk1:{}", "11".repeat(32))).unwrap();
+        let sealed = ring.seal(b"dicom bytes", b"a.dcm").unwrap();
+        assert_eq!(sealed.key_id, "k1");
+        assert_eq!(
+            ring.open("k1", &sealed.data, b"a.dcm").unwrap(),
+            b"dicom bytes"
+        );
+        assert!(ring.open("k1", &sealed.data, b"b.dcm").is_err());
+
+        let mut tampered = sealed.data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(ring.open("k1", &tampered, b"a.dcm").is_err());
+
+        ring.rotate("k2", [0x22; 32]).unwrap();
+        assert_eq!(ring.active_key_id(), "k2");
+        assert_eq!(
+            ring.open("k1", &sealed.data, b"a.dcm").unwrap(),
+            b"dicom bytes"
+        );
+        assert!(ring.rotate("k1", [0x33; 32]).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_non_hex_keys() {
+        // 64字节但包含多字节字符，按字节切分会落在字符中间
+        let key = format!("{}é", "1".repeat(62));
+        assert_eq!(key.len(), 64);
+        assert!(KeyRing::parse(&format!("k1:{}", key)).is_err());
+        assert!(KeyRing::parse(&format!("k1:{}", "zz".repeat(32))).is_err());
+        assert!(KeyRing::parse(&format!("k1:{}", "11".repeat(31))).is_err());
+    }
+}
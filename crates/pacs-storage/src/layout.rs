@@ -0,0 +1,1128 @@
+//! 实例存储布局
+//!
+//! 由Study/Series/SOP Instance UID推导对象键（可按检查UID哈希做目录扇出），
+//! 为每个对象记录SHA-256与大小，识别逐字节相同的重复发送，并支持通过后台
+//! 迁移任务将所有对象搬迁到新的布局。
+//!
+//! 配置状态存储后，对象目录、当前布局和迁移任务随每次变更持久化，
+//! 重启时由 [`LayoutManager::load_state`] 恢复，中断的迁移继续执行。
+
+use crate::state::{StateRecovery, StateStore, StateTransaction};
+use crate::storage::StorageManager;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use pacs_core::uid::validate_uid;
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio::sync::RwLock;
+use tokio_util::io::StreamReader;
+use tracing::{debug, error, info, warn};
+
+/// 哈希扇出的最大目录层数
+const MAX_FAN_OUT_LEVELS: u8 = 8;
+
+/// 状态存储中的对象目录集合
+const CATALOG_COLLECTION: &str = "layout_catalog";
+
+/// 状态存储中的布局配置集合
+const LAYOUT_COLLECTION: &str = "layout_config";
+
+/// 布局配置中当前布局的键
+const CURRENT_LAYOUT_KEY: &str = "current";
+
+/// 状态存储中的迁移任务集合
+const MIGRATION_COLLECTION: &str = "layout_migrations";
+
+/// 存储布局
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageLayout {
+    /// 对象键前缀
+    pub prefix: Option<String>,
+    /// 哈希扇出目录层数，每层取检查UID哈希的两位十六进制（0表示不扇出）
+    pub fan_out_levels: u8,
+}
+
+impl StorageLayout {
+    /// 按 `<study>/<series>/<sop>.dcm` 组织的层级布局
+    pub fn hierarchical() -> Self {
+        Self {
+            prefix: None,
+            fan_out_levels: 0,
+        }
+    }
+
+    /// 在层级布局前增加哈希扇出目录，如 `3f/a2/<study>/<series>/<sop>.dcm`
+    pub fn hashed(fan_out_levels: u8) -> Self {
+        Self {
+            prefix: None,
+            fan_out_levels,
+        }
+    }
+
+    /// 设置对象键前缀
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_matches('/');
+        self.prefix = (!prefix.is_empty()).then(|| prefix.to_string());
+        self
+    }
+
+    /// 校验布局参数
+    pub fn validate(&self) -> Result<()> {
+        if self.fan_out_levels > MAX_FAN_OUT_LEVELS {
+            return Err(PacsError::Config(format!(
+                "Fan-out levels must not exceed {}",
+                MAX_FAN_OUT_LEVELS
+            )));
+        }
+        if let Some(prefix) = &self.prefix {
+            if prefix
+                .split('/')
+                .any(|part| part.is_empty() || part == "..")
+            {
+                return Err(PacsError::Config(format!(
+                    "Invalid layout prefix: {}",
+                    prefix
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// 推导检查目录的对象键前缀
+    pub fn study_key(&self, study_instance_uid: &str) -> Result<String> {
+        validate_uid(study_instance_uid)?;
+
+        let mut parts = Vec::new();
+        if let Some(prefix) = &self.prefix {
+            parts.push(prefix.clone());
+        }
+        if self.fan_out_levels > 0 {
+            let digest = format!("{:x}", Sha256::digest(study_instance_uid.as_bytes()));
+            for level in 0..self.fan_out_levels as usize {
+                parts.push(digest[level * 2..level * 2 + 2].to_string());
+            }
+        }
+        parts.push(study_instance_uid.to_string());
+        Ok(parts.join("/"))
+    }
+
+    /// 推导实例的对象键
+    pub fn object_key(&self, instance: &InstanceKey) -> Result<String> {
+        instance.validate()?;
+        Ok(format!(
+            "{}/{}/{}.dcm",
+            self.study_key(&instance.study_instance_uid)?,
+            instance.series_instance_uid,
+            instance.sop_instance_uid
+        ))
+    }
+}
+
+impl Default for StorageLayout {
+    fn default() -> Self {
+        Self::hierarchical()
+    }
+}
+
+/// 实例标识
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InstanceKey {
+    /// 检查实例UID
+    pub study_instance_uid: String,
+    /// 序列实例UID
+    pub series_instance_uid: String,
+    /// SOP实例UID
+    pub sop_instance_uid: String,
+}
+
+impl InstanceKey {
+    /// 创建实例标识
+    pub fn new(
+        study_instance_uid: &str,
+        series_instance_uid: &str,
+        sop_instance_uid: &str,
+    ) -> Self {
+        Self {
+            study_instance_uid: study_instance_uid.to_string(),
+            series_instance_uid: series_instance_uid.to_string(),
+            sop_instance_uid: sop_instance_uid.to_string(),
+        }
+    }
+
+    /// 校验UID，合法UID只含数字和点，保证对象键不会越出存储根目录
+    pub fn validate(&self) -> Result<()> {
+        validate_uid(&self.study_instance_uid)?;
+        validate_uid(&self.series_instance_uid)?;
+        validate_uid(&self.sop_instance_uid)
+    }
+}
+
+/// 已存储对象
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredObject {
+    /// 实例标识
+    pub instance: InstanceKey,
+    /// 对象键
+    pub object_key: String,
+    /// 内容SHA-256（十六进制）
+    pub sha256: String,
+    /// 对象大小（字节）
+    pub size: u64,
+    /// 存储时间
+    pub stored_at: DateTime<Utc>,
+}
+
+/// 存储结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StoreOutcome {
+    /// 新对象已存储
+    Stored(StoredObject),
+    /// 与已存储对象逐字节相同，未重复写入
+    Identical(StoredObject),
+    /// 同一SOP实例的内容不同，已覆盖
+    Replaced {
+        /// 新对象
+        object: StoredObject,
+        /// 被覆盖内容的SHA-256
+        previous_sha256: String,
+    },
+}
+
+impl StoreOutcome {
+    /// 获取存储后的对象
+    pub fn object(&self) -> &StoredObject {
+        match self {
+            StoreOutcome::Stored(object) | StoreOutcome::Identical(object) => object,
+            StoreOutcome::Replaced { object, .. } => object,
+        }
+    }
+}
+
+/// 布局迁移状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MigrationState {
+    /// 进行中
+    Running,
+    /// 已完成
+    Completed,
+    /// 部分对象迁移失败
+    CompletedWithErrors,
+}
+
+/// 布局迁移任务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutMigration {
+    /// 任务ID
+    pub id: String,
+    /// 原布局
+    pub source_layout: StorageLayout,
+    /// 目标布局
+    pub target_layout: StorageLayout,
+    /// 任务状态
+    pub state: MigrationState,
+    /// 需要迁移的对象数
+    pub total_objects: usize,
+    /// 已迁移的对象数
+    pub migrated_objects: usize,
+    /// 已位于目标位置的对象数
+    pub skipped_objects: usize,
+    /// 迁移失败的对象（SOP实例UID及原因）
+    pub failures: Vec<(String, String)>,
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    /// 结束时间
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// 对象位置变更的接收者
+///
+/// 布局迁移在新位置的副本校验通过后、删除原对象之前通知，供记录了文件位置的
+/// 外部索引（实例表、转发队列等）同步更新；返回错误时本次迁移撤销
+#[async_trait]
+pub trait ObjectRelocationListener: Send + Sync {
+    /// 对象已复制到 `object.object_key`，原对象键为 `previous_key`
+    async fn object_relocated(&self, object: &StoredObject, previous_key: &str) -> Result<()>;
+}
+
+/// 存储布局管理器
+pub struct LayoutManager {
+    /// 存储管理器
+    storage: Arc<StorageManager>,
+    /// 新对象使用的布局
+    layout: RwLock<StorageLayout>,
+    /// 对象目录，按SOP实例UID索引
+    catalog: RwLock<HashMap<String, StoredObject>>,
+    /// 布局迁移任务
+    migrations: RwLock<HashMap<String, LayoutMigration>>,
+    /// 持久化状态存储
+    state_store: Option<Arc<StateStore>>,
+    /// 对象位置变更的接收者
+    relocation_listener: Option<Arc<dyn ObjectRelocationListener>>,
+}
+
+impl LayoutManager {
+    /// 创建新的布局管理器
+    pub fn new(storage: Arc<StorageManager>, layout: StorageLayout) -> Result<Self> {
+        layout.validate()?;
+        Ok(Self {
+            storage,
+            layout: RwLock::new(layout),
+            catalog: RwLock::new(HashMap::new()),
+            migrations: RwLock::new(HashMap::new()),
+            state_store: None,
+            relocation_listener: None,
+        })
+    }
+
+    /// 设置持久化状态存储
+    pub fn with_state_store(mut self, store: Arc<StateStore>) -> Self {
+        self.state_store = Some(store);
+        self
+    }
+
+    /// 设置对象位置变更的接收者
+    pub fn with_relocation_listener(mut self, listener: Arc<dyn ObjectRelocationListener>) -> Self {
+        self.relocation_listener = Some(listener);
+        self
+    }
+
+    /// 从状态存储恢复对象目录、当前布局和迁移任务，继续执行中断的迁移
+    pub async fn load_state(self: &Arc<Self>) -> Result<StateRecovery> {
+        let Some(store) = self.state_store.clone() else {
+            return Ok(StateRecovery::default());
+        };
+        let mut recovery = StateRecovery::default();
+
+        let objects: Vec<(String, StoredObject)> = store.load(CATALOG_COLLECTION).await?;
+        recovery.loaded += objects.len();
+        self.catalog.write().await.extend(objects);
+
+        if let Some(layout) = store
+            .get::<StorageLayout>(LAYOUT_COLLECTION, CURRENT_LAYOUT_KEY)
+            .await?
+        {
+            layout.validate()?;
+            *self.layout.write().await = layout;
+        }
+
+        let migrations: Vec<(String, LayoutMigration)> = store.load(MIGRATION_COLLECTION).await?;
+        recovery.loaded += migrations.len();
+        let interrupted: Vec<LayoutMigration> = migrations
+            .iter()
+            .map(|(_, migration)| migration)
+            .filter(|migration| migration.state == MigrationState::Running)
+            .cloned()
+            .collect();
+        self.migrations.write().await.extend(migrations);
+
+        for migration in interrupted {
+            let pending = self.pending_objects(&migration.target_layout).await;
+            info!(
+                "Resuming layout migration {} ({} objects remaining)",
+                migration.id,
+                pending.len()
+            );
+            recovery.rolled_forward += 1;
+            let manager = self.clone();
+            tokio::spawn(async move {
+                manager
+                    .run_migration(&migration.id, &migration.target_layout, pending)
+                    .await;
+            });
+        }
+
+        info!(
+            "Loaded layout state: {} objects, {} migrations resumed",
+            self.catalog.read().await.len(),
+            recovery.rolled_forward
+        );
+        Ok(recovery)
+    }
+
+    /// 获取当前布局
+    pub async fn layout(&self) -> StorageLayout {
+        self.layout.read().await.clone()
+    }
+
+    /// 按当前布局推导实例的对象键
+    pub async fn object_key(&self, instance: &InstanceKey) -> Result<String> {
+        self.layout.read().await.object_key(instance)
+    }
+
+    /// 存储实例，逐字节相同的重复发送不会重复写入
+    pub async fn store(&self, instance: &InstanceKey, data: &[u8]) -> Result<StoreOutcome> {
+        let sha256 = calculate_sha256(data);
+        if let Some(existing) = self.identical_object(instance, &sha256).await {
+            return Ok(StoreOutcome::Identical(existing));
+        }
+
+        let object_key = self.layout.read().await.object_key(instance)?;
+        self.storage.store_file(data, &object_key).await?;
+        self.commit_store(instance, object_key, sha256, data.len() as u64)
+            .await
+    }
+
+    /// 以流方式存储本地文件中的实例（如入库管道的临时文件），判定规则同 [`Self::store`]
+    pub async fn store_file(&self, instance: &InstanceKey, path: &Path) -> Result<StoreOutcome> {
+        let (sha256, size) = file_sha256(path).await?;
+        if let Some(existing) = self.identical_object(instance, &sha256).await {
+            return Ok(StoreOutcome::Identical(existing));
+        }
+
+        let object_key = self.layout.read().await.object_key(instance)?;
+        let file = tokio::fs::File::open(path).await?;
+        self.storage.store_stream(file, &object_key).await?;
+        self.commit_store(instance, object_key, sha256, size).await
+    }
+
+    /// 查找与待存储内容逐字节相同的已存储对象
+    async fn identical_object(&self, instance: &InstanceKey, sha256: &str) -> Option<StoredObject> {
+        let existing = self
+            .locate(&instance.sop_instance_uid)
+            .await
+            .filter(|existing| existing.sha256 == sha256 && existing.instance == *instance)?;
+        debug!("Identical re-send of {} ignored", instance.sop_instance_uid);
+        Some(existing)
+    }
+
+    /// 登记已写入的对象，内容不同的旧对象位于其他位置时删除
+    async fn commit_store(
+        &self,
+        instance: &InstanceKey,
+        object_key: String,
+        sha256: String,
+        size: u64,
+    ) -> Result<StoreOutcome> {
+        let previous = self.locate(&instance.sop_instance_uid).await;
+        let object = StoredObject {
+            instance: instance.clone(),
+            object_key,
+            sha256,
+            size,
+            stored_at: Utc::now(),
+        };
+        self.record(object.clone()).await?;
+
+        match previous {
+            Some(previous) => {
+                warn!(
+                    "Instance {} re-sent with different content, replacing {}",
+                    instance.sop_instance_uid, previous.sha256
+                );
+                if previous.object_key != object.object_key {
+                    self.delete_object_file(&previous.object_key).await;
+                }
+                Ok(StoreOutcome::Replaced {
+                    object,
+                    previous_sha256: previous.sha256,
+                })
+            }
+            None => Ok(StoreOutcome::Stored(object)),
+        }
+    }
+
+    /// 读取实例内容
+    pub async fn get(&self, sop_instance_uid: &str) -> Result<Vec<u8>> {
+        let object = self
+            .locate(sop_instance_uid)
+            .await
+            .ok_or_else(|| PacsError::NotFound(format!("Instance {}", sop_instance_uid)))?;
+        self.storage.get_file(&object.object_key).await
+    }
+
+    /// 查找实例的存储记录
+    pub async fn locate(&self, sop_instance_uid: &str) -> Option<StoredObject> {
+        self.catalog.read().await.get(sop_instance_uid).cloned()
+    }
+
+    /// 删除实例，实例不存在时返回false
+    pub async fn delete(&self, sop_instance_uid: &str) -> Result<bool> {
+        let Some(object) = self.locate(sop_instance_uid).await else {
+            return Ok(false);
+        };
+        self.forget(sop_instance_uid).await?;
+        self.storage.delete_file(&object.object_key).await?;
+        Ok(true)
+    }
+
+    /// 登记已写入存储的对象（入库管道落盘后，或启动时从数据库索引重建目录）
+    pub async fn register(&self, object: StoredObject) -> Result<()> {
+        object.instance.validate()?;
+        self.record(object).await
+    }
+
+    /// 移除对象记录，不删除对象文件（对象已被其他流程删除或移走时使用）
+    pub async fn forget(&self, sop_instance_uid: &str) -> Result<()> {
+        if let Some(store) = &self.state_store {
+            let mut txn = StateTransaction::new();
+            txn.delete(CATALOG_COLLECTION, sop_instance_uid);
+            store.commit(txn).await?;
+        }
+        self.catalog.write().await.remove(sop_instance_uid);
+        Ok(())
+    }
+
+    /// 获取所有对象记录
+    pub async fn objects(&self) -> Vec<StoredObject> {
+        self.catalog.read().await.values().cloned().collect()
+    }
+
+    /// 获取检查（或其中一个序列）的对象记录
+    pub async fn study_objects(
+        &self,
+        study_instance_uid: &str,
+        series_instance_uid: Option<&str>,
+    ) -> Vec<StoredObject> {
+        self.catalog
+            .read()
+            .await
+            .values()
+            .filter(|object| object.instance.study_instance_uid == study_instance_uid)
+            .filter(|object| {
+                series_instance_uid
+                    .is_none_or(|series_uid| object.instance.series_instance_uid == series_uid)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 启动后台布局迁移，新对象立即使用目标布局，返回任务ID
+    pub async fn start_migration(self: &Arc<Self>, target_layout: StorageLayout) -> Result<String> {
+        target_layout.validate()?;
+
+        {
+            let migrations = self.migrations.read().await;
+            if let Some(running) = migrations
+                .values()
+                .find(|m| m.state == MigrationState::Running)
+            {
+                return Err(PacsError::Validation(format!(
+                    "Layout migration {} is already running",
+                    running.id
+                )));
+            }
+        }
+
+        let source_layout = self.layout.read().await.clone();
+        let pending = self.pending_objects(&target_layout).await;
+
+        let migration_id = format!("layout_migration_{}", Utc::now().timestamp_millis());
+        let migration = LayoutMigration {
+            id: migration_id.clone(),
+            source_layout,
+            target_layout: target_layout.clone(),
+            state: MigrationState::Running,
+            total_objects: pending.len(),
+            migrated_objects: 0,
+            skipped_objects: 0,
+            failures: Vec::new(),
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        // 新布局和迁移任务一并提交，重启后据此继续迁移
+        if let Some(store) = &self.state_store {
+            let mut txn = StateTransaction::new();
+            txn.put(LAYOUT_COLLECTION, CURRENT_LAYOUT_KEY, &target_layout)?;
+            txn.put(MIGRATION_COLLECTION, &migration_id, &migration)?;
+            store.commit(txn).await?;
+        }
+        *self.layout.write().await = target_layout.clone();
+        self.migrations
+            .write()
+            .await
+            .insert(migration_id.clone(), migration);
+        info!(
+            "Started layout migration {} for {} objects",
+            migration_id,
+            pending.len()
+        );
+
+        let manager = self.clone();
+        let id = migration_id.clone();
+        tokio::spawn(async move {
+            manager.run_migration(&id, &target_layout, pending).await;
+        });
+
+        Ok(migration_id)
+    }
+
+    /// 获取迁移任务状态
+    pub async fn get_migration(&self, migration_id: &str) -> Option<LayoutMigration> {
+        self.migrations.read().await.get(migration_id).cloned()
+    }
+
+    /// 获取所有迁移任务
+    pub async fn list_migrations(&self) -> Vec<LayoutMigration> {
+        self.migrations.read().await.values().cloned().collect()
+    }
+
+    /// 尚未位于目标布局的对象
+    async fn pending_objects(&self, target: &StorageLayout) -> Vec<String> {
+        self.catalog
+            .read()
+            .await
+            .values()
+            .filter(|object| {
+                target
+                    .object_key(&object.instance)
+                    .map_or(true, |key| key != object.object_key)
+            })
+            .map(|object| object.instance.sop_instance_uid.clone())
+            .collect()
+    }
+
+    /// 逐个迁移对象并更新任务进度
+    async fn run_migration(
+        &self,
+        migration_id: &str,
+        target: &StorageLayout,
+        pending: Vec<String>,
+    ) {
+        for sop_instance_uid in pending {
+            let result = self.migrate_object(&sop_instance_uid, target).await;
+
+            let snapshot = {
+                let mut migrations = self.migrations.write().await;
+                let Some(migration) = migrations.get_mut(migration_id) else {
+                    return;
+                };
+                match result {
+                    Ok(true) => migration.migrated_objects += 1,
+                    Ok(false) => migration.skipped_objects += 1,
+                    Err(e) => {
+                        error!("Failed to migrate {}: {}", sop_instance_uid, e);
+                        migration.failures.push((sop_instance_uid, e.to_string()));
+                    }
+                }
+                migration.clone()
+            };
+            self.persist_migration(&snapshot).await;
+        }
+
+        let snapshot = {
+            let mut migrations = self.migrations.write().await;
+            let Some(migration) = migrations.get_mut(migration_id) else {
+                return;
+            };
+            migration.state = if migration.failures.is_empty() {
+                MigrationState::Completed
+            } else {
+                MigrationState::CompletedWithErrors
+            };
+            migration.finished_at = Some(Utc::now());
+            info!(
+                "Layout migration {} finished: {} migrated, {} skipped, {} failed",
+                migration_id,
+                migration.migrated_objects,
+                migration.skipped_objects,
+                migration.failures.len()
+            );
+            migration.clone()
+        };
+        self.persist_migration(&snapshot).await;
+    }
+
+    /// 持久化迁移进度，失败只记录日志（重启后未记录的对象会重新检查）
+    async fn persist_migration(&self, migration: &LayoutMigration) {
+        let Some(store) = &self.state_store else {
+            return;
+        };
+        let mut txn = StateTransaction::new();
+        let committed = match txn.put(MIGRATION_COLLECTION, &migration.id, migration) {
+            Ok(()) => store.commit(txn).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = committed {
+            warn!("Failed to persist layout migration {}: {}", migration.id, e);
+        }
+    }
+
+    /// 持久化并记录对象
+    async fn record(&self, object: StoredObject) -> Result<()> {
+        if let Some(store) = &self.state_store {
+            let mut txn = StateTransaction::new();
+            txn.put(
+                CATALOG_COLLECTION,
+                &object.instance.sop_instance_uid,
+                &object,
+            )?;
+            store.commit(txn).await?;
+        }
+        self.catalog
+            .write()
+            .await
+            .insert(object.instance.sop_instance_uid.clone(), object);
+        Ok(())
+    }
+
+    /// 将单个对象以流方式复制到目标布局并校验哈希，成功后删除原对象
+    ///
+    /// 对象已位于目标位置（或迁移期间已被删除）时返回false
+    async fn migrate_object(&self, sop_instance_uid: &str, target: &StorageLayout) -> Result<bool> {
+        let Some(object) = self.locate(sop_instance_uid).await else {
+            return Ok(false);
+        };
+        let new_key = target.object_key(&object.instance)?;
+        if new_key == object.object_key {
+            return Ok(false);
+        }
+
+        let source = match self.storage.get_stream(&object.object_key).await {
+            Ok(stream) => stream,
+            // 对象已被其他流程删除，不再保留记录
+            Err(PacsError::NotFound(_)) => {
+                self.forget_if_unchanged(&object).await?;
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        };
+        // 复制时计算原对象的哈希，写入后再读取新对象校验
+        let mut hasher = Sha256::new();
+        let reader = StreamReader::new(
+            source
+                .map_ok(|chunk| {
+                    hasher.update(&chunk);
+                    chunk
+                })
+                .map_err(std::io::Error::other),
+        );
+        self.storage.store_stream(reader, &new_key).await?;
+        if format!("{:x}", hasher.finalize()) != object.sha256 {
+            self.delete_object_file(&new_key).await;
+            return Err(PacsError::Storage(format!(
+                "Checksum mismatch for {} before migration",
+                object.object_key
+            )));
+        }
+        if self.object_sha256(&new_key).await? != object.sha256 {
+            self.delete_object_file(&new_key).await;
+            return Err(PacsError::Storage(format!(
+                "Checksum mismatch for {} after migration",
+                new_key
+            )));
+        }
+
+        // 迁移期间对象未被覆盖或删除时才更新位置：先通知外部索引，
+        // 再持久化目录，最后删除原对象；任一步失败时撤销已完成的步骤
+        let relocated = {
+            let mut catalog = self.catalog.write().await;
+            match catalog.get_mut(sop_instance_uid) {
+                Some(current) if current.object_key == object.object_key => {
+                    let mut moved = current.clone();
+                    moved.object_key = new_key.clone();
+                    if let Err(e) = self.relocate(&moved, &object).await {
+                        drop(catalog);
+                        self.delete_object_file(&new_key).await;
+                        return Err(e);
+                    }
+                    *current = moved;
+                    true
+                }
+                _ => false,
+            }
+        };
+        if !relocated {
+            self.delete_object_file(&new_key).await;
+            return Ok(false);
+        }
+
+        self.delete_object_file(&object.object_key).await;
+        debug!("Migrated {} -> {}", object.object_key, new_key);
+        Ok(true)
+    }
+
+    /// 通知外部索引并持久化对象的新位置，持久化失败时将外部索引恢复到原位置
+    async fn relocate(&self, moved: &StoredObject, previous: &StoredObject) -> Result<()> {
+        if let Some(listener) = &self.relocation_listener {
+            listener
+                .object_relocated(moved, &previous.object_key)
+                .await?;
+        }
+        let persisted = match &self.state_store {
+            Some(store) => {
+                let mut txn = StateTransaction::new();
+                match txn.put(CATALOG_COLLECTION, &moved.instance.sop_instance_uid, moved) {
+                    Ok(()) => store.commit(txn).await,
+                    Err(e) => Err(e),
+                }
+            }
+            None => Ok(()),
+        };
+        if let Err(e) = persisted {
+            if let Some(listener) = &self.relocation_listener {
+                if let Err(revert) = listener.object_relocated(previous, &moved.object_key).await {
+                    error!(
+                        "Failed to restore location of {} after catalog error: {}",
+                        previous.instance.sop_instance_uid, revert
+                    );
+                }
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// 以流方式计算已存储对象的SHA-256（十六进制）
+    async fn object_sha256(&self, object_key: &str) -> Result<String> {
+        let mut stream = self.storage.get_stream(object_key).await?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = stream.try_next().await? {
+            hasher.update(&chunk);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// 对象记录未变化时移除记录
+    async fn forget_if_unchanged(&self, object: &StoredObject) -> Result<()> {
+        let unchanged = self
+            .locate(&object.instance.sop_instance_uid)
+            .await
+            .is_some_and(|current| current.object_key == object.object_key);
+        if unchanged {
+            warn!(
+                "Object {} no longer exists, removing from catalog",
+                object.object_key
+            );
+            self.forget(&object.instance.sop_instance_uid).await?;
+        }
+        Ok(())
+    }
+
+    /// 删除对象文件，失败只记录日志
+    async fn delete_object_file(&self, object_key: &str) {
+        if let Err(e) = self.storage.delete_file(object_key).await {
+            warn!("Failed to delete object {}: {}", object_key, e);
+        }
+    }
+}
+
+/// 计算内容的SHA-256（十六进制）
+fn calculate_sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// 流式计算本地文件的SHA-256（十六进制）及大小
+pub async fn file_sha256(path: &Path) -> Result<(String, u64)> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        size += n as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_keys() {
+        let instance = InstanceKey::new("1.2.3", "1.2.3.4", "1.2.3.4.5");
+
+        assert_eq!(
+            StorageLayout::hierarchical().object_key(&instance).unwrap(),
+            "1.2.3/1.2.3.4/1.2.3.4.5.dcm"
+        );
+
+        let hashed = StorageLayout::hashed(2).with_prefix("/dicom/");
+        let key = hashed.object_key(&instance).unwrap();
+        let digest = calculate_sha256(b"1.2.3");
+        assert_eq!(
+            key,
+            format!(
+                "dicom/{}/{}/1.2.3/1.2.3.4/1.2.3.4.5.dcm",
+                &digest[..2],
+                &digest[2..4]
+            )
+        );
+
+        let traversal = InstanceKey::new("1.2.3", "..", "1.2.3.4.5");
+        assert!(hashed.object_key(&traversal).is_err());
+        assert!(StorageLayout::hashed(9).validate().is_err());
+    }
+
+    async fn memory_storage() -> Arc<StorageManager> {
+        Arc::new(
+            StorageManager::new(crate::storage::StorageConfig {
+                storage_type: crate::storage::StorageType::Memory,
+                local_path: None,
+                object_store_config: None,
+            })
+            .await
+            .unwrap(),
+        )
+    }
+
+    /// 等待迁移任务结束
+    async fn wait_for_migration(manager: &LayoutManager, migration_id: &str) -> LayoutMigration {
+        for _ in 0..100 {
+            if let Some(migration) = manager.get_migration(migration_id).await {
+                if migration.state != MigrationState::Running {
+                    return migration;
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("migration {} did not finish", migration_id);
+    }
+
+    #[tokio::test]
+    async fn test_store_deduplicates_and_persists_catalog() {
+        let dir = std::env::temp_dir().join(format!(
+            "pacs-layout-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let store = Arc::new(StateStore::open(&dir).await.unwrap());
+        let storage = memory_storage().await;
+        let manager = LayoutManager::new(storage.clone(), StorageLayout::hierarchical())
+            .unwrap()
+            .with_state_store(store.clone());
+
+        let instance = InstanceKey::new("1.2.3", "1.2.3.4", "1.2.3.4.5");
+        let stored = manager.store(&instance, b"first").await.unwrap();
+        assert!(matches!(stored, StoreOutcome::Stored(_)));
+        let identical = manager.store(&instance, b"first").await.unwrap();
+        assert!(matches!(identical, StoreOutcome::Identical(_)));
+        let replaced = manager.store(&instance, b"second").await.unwrap();
+        assert!(matches!(
+            replaced,
+            StoreOutcome::Replaced { ref previous_sha256, .. }
+                if *previous_sha256 == calculate_sha256(b"first")
+        ));
+
+        let other = InstanceKey::new("1.2.3", "1.2.3.4", "1.2.3.4.6");
+        manager.store(&other, b"other").await.unwrap();
+        assert!(manager.delete("1.2.3.4.6").await.unwrap());
+
+        // 重启后从状态存储恢复目录
+        let reopened = Arc::new(
+            LayoutManager::new(storage, StorageLayout::hierarchical())
+                .unwrap()
+                .with_state_store(Arc::new(StateStore::open(&dir).await.unwrap())),
+        );
+        let recovery = reopened.load_state().await.unwrap();
+        assert_eq!(recovery.loaded, 1);
+        let object = reopened.locate("1.2.3.4.5").await.unwrap();
+        assert_eq!(object.sha256, calculate_sha256(b"second"));
+        assert!(reopened.locate("1.2.3.4.6").await.is_none());
+        assert_eq!(reopened.get("1.2.3.4.5").await.unwrap(), b"second");
+        assert_eq!(
+            reopened.study_objects("1.2.3", Some("1.2.3.4")).await.len(),
+            1
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_migration_relocates_objects_and_survives_restart() {
+        let dir = std::env::temp_dir().join(format!(
+            "pacs-layout-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let storage = memory_storage().await;
+        let manager = Arc::new(
+            LayoutManager::new(storage.clone(), StorageLayout::hierarchical())
+                .unwrap()
+                .with_state_store(Arc::new(StateStore::open(&dir).await.unwrap())),
+        );
+
+        let instances = [
+            InstanceKey::new("1.2.3", "1.2.3.4", "1.2.3.4.5"),
+            InstanceKey::new("1.2.9", "1.2.9.4", "1.2.9.4.5"),
+        ];
+        for instance in &instances {
+            manager
+                .store(instance, instance.sop_instance_uid.as_bytes())
+                .await
+                .unwrap();
+        }
+        // 目录中存在但文件已被删除的对象
+        manager
+            .register(StoredObject {
+                instance: InstanceKey::new("1.2.7", "1.2.7.4", "1.2.7.4.5"),
+                object_key: "1.2.7/1.2.7.4/1.2.7.4.5.dcm".to_string(),
+                sha256: calculate_sha256(b"gone"),
+                size: 4,
+                stored_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+
+        let target = StorageLayout::hashed(1).with_prefix("v2");
+        let migration_id = manager.start_migration(target.clone()).await.unwrap();
+        let migration = wait_for_migration(&manager, &migration_id).await;
+        assert_eq!(migration.state, MigrationState::Completed);
+        assert_eq!(migration.migrated_objects, 2);
+        assert_eq!(migration.skipped_objects, 1);
+
+        let reopened = Arc::new(
+            LayoutManager::new(storage.clone(), StorageLayout::hierarchical())
+                .unwrap()
+                .with_state_store(Arc::new(StateStore::open(&dir).await.unwrap())),
+        );
+        reopened.load_state().await.unwrap();
+        assert_eq!(reopened.layout().await, target);
+        assert!(reopened.locate("1.2.7.4.5").await.is_none());
+        for instance in &instances {
+            let object = reopened.locate(&instance.sop_instance_uid).await.unwrap();
+            assert_eq!(object.object_key, target.object_key(instance).unwrap());
+            assert_eq!(
+                reopened.get(&instance.sop_instance_uid).await.unwrap(),
+                instance.sop_instance_uid.as_bytes()
+            );
+            let old_key = StorageLayout::hierarchical().object_key(instance).unwrap();
+            assert!(!storage.file_exists(&old_key).await.unwrap());
+        }
+        assert_eq!(
+            reopened.get_migration(&migration_id).await.unwrap().state,
+            MigrationState::Completed
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// 记录位置变更的接收者，拒绝指定实例的变更
+    #[derive(Default)]
+    struct RecordingListener {
+        relocations: tokio::sync::Mutex<Vec<(String, String, String)>>,
+        refuse: Option<String>,
+    }
+
+    #[async_trait]
+    impl ObjectRelocationListener for RecordingListener {
+        async fn object_relocated(&self, object: &StoredObject, previous_key: &str) -> Result<()> {
+            if self.refuse.as_deref() == Some(object.instance.sop_instance_uid.as_str()) {
+                return Err(PacsError::Database("index unavailable".to_string()));
+            }
+            self.relocations.lock().await.push((
+                object.instance.sop_instance_uid.clone(),
+                previous_key.to_string(),
+                object.object_key.clone(),
+            ));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migration_updates_external_index_before_removing_source() {
+        let storage = memory_storage().await;
+        let listener = Arc::new(RecordingListener {
+            refuse: Some("1.2.9.4.5".to_string()),
+            ..Default::default()
+        });
+        let manager = Arc::new(
+            LayoutManager::new(storage.clone(), StorageLayout::hierarchical())
+                .unwrap()
+                .with_relocation_listener(listener.clone()),
+        );
+        let moved = InstanceKey::new("1.2.3", "1.2.3.4", "1.2.3.4.5");
+        let refused = InstanceKey::new("1.2.9", "1.2.9.4", "1.2.9.4.5");
+        manager.store(&moved, b"moved").await.unwrap();
+        manager.store(&refused, b"refused").await.unwrap();
+
+        let target = StorageLayout::hashed(1);
+        let migration_id = manager.start_migration(target.clone()).await.unwrap();
+        let migration = wait_for_migration(&manager, &migration_id).await;
+        assert_eq!(migration.state, MigrationState::CompletedWithErrors);
+        assert_eq!(migration.migrated_objects, 1);
+        assert_eq!(migration.failures.len(), 1);
+
+        let old_key = StorageLayout::hierarchical().object_key(&moved).unwrap();
+        let new_key = target.object_key(&moved).unwrap();
+        assert_eq!(
+            *listener.relocations.lock().await,
+            vec![("1.2.3.4.5".to_string(), old_key.clone(), new_key.clone())]
+        );
+        assert!(!storage.file_exists(&old_key).await.unwrap());
+        assert_eq!(storage.get_file(&new_key).await.unwrap(), b"moved");
+
+        // 外部索引拒绝更新的对象保留在原位置，不留下新副本
+        let old_key = StorageLayout::hierarchical().object_key(&refused).unwrap();
+        assert_eq!(
+            manager.locate("1.2.9.4.5").await.unwrap().object_key,
+            old_key
+        );
+        assert!(storage.file_exists(&old_key).await.unwrap());
+        assert!(!storage
+            .file_exists(&target.object_key(&refused).unwrap())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_migration_resumes_on_load() {
+        let dir = std::env::temp_dir().join(format!(
+            "pacs-layout-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let store = Arc::new(StateStore::open(&dir).await.unwrap());
+        let storage = memory_storage().await;
+        let instance = InstanceKey::new("1.2.3", "1.2.3.4", "1.2.3.4.5");
+        let old_key = StorageLayout::hierarchical().object_key(&instance).unwrap();
+        storage.store_file(b"data", &old_key).await.unwrap();
+
+        // 模拟迁移开始后进程退出：布局和任务已持久化，对象仍在原位置
+        let target = StorageLayout::hashed(2);
+        let migration = LayoutMigration {
+            id: "layout_migration_1".to_string(),
+            source_layout: StorageLayout::hierarchical(),
+            target_layout: target.clone(),
+            state: MigrationState::Running,
+            total_objects: 1,
+            migrated_objects: 0,
+            skipped_objects: 0,
+            failures: Vec::new(),
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        let mut txn = StateTransaction::new();
+        txn.put(
+            CATALOG_COLLECTION,
+            &instance.sop_instance_uid,
+            &StoredObject {
+                instance: instance.clone(),
+                object_key: old_key.clone(),
+                sha256: calculate_sha256(b"data"),
+                size: 4,
+                stored_at: Utc::now(),
+            },
+        )
+        .unwrap();
+        txn.put(LAYOUT_COLLECTION, CURRENT_LAYOUT_KEY, &target)
+            .unwrap();
+        txn.put(MIGRATION_COLLECTION, &migration.id, &migration)
+            .unwrap();
+        store.commit(txn).await.unwrap();
+
+        let manager = Arc::new(
+            LayoutManager::new(storage.clone(), StorageLayout::hierarchical())
+                .unwrap()
+                .with_state_store(store),
+        );
+        let recovery = manager.load_state().await.unwrap();
+        assert_eq!(recovery.rolled_forward, 1);
+
+        let finished = wait_for_migration(&manager, &migration.id).await;
+        assert_eq!(finished.state, MigrationState::Completed);
+        assert_eq!(finished.migrated_objects, 1);
+        let object = manager.locate(&instance.sop_instance_uid).await.unwrap();
+        assert_eq!(object.object_key, target.object_key(&instance).unwrap());
+        assert!(!storage.file_exists(&old_key).await.unwrap());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
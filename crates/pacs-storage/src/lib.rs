@@ -3,13 +3,29 @@
 //! 负责影像文件的存储和归档管理。
 
 pub mod archive;
+pub mod backend;
 pub mod backup;
+pub mod compression;
+pub mod encryption;
+pub mod layout;
 pub mod lifecycle;
 pub mod monitoring;
+pub mod recall;
+pub mod retention;
+pub mod scheduler;
+pub mod state;
 pub mod storage;
 
 pub use archive::*;
+pub use backend::*;
 pub use backup::*;
+pub use compression::*;
+pub use encryption::*;
+pub use layout::*;
 pub use lifecycle::*;
 pub use monitoring::*;
+pub use recall::*;
+pub use retention::*;
+pub use scheduler::*;
+pub use state::*;
 pub use storage::*;
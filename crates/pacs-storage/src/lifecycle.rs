@@ -1,14 +1,50 @@
 //! 数据生命周期管理
 
-use crate::storage::{StorageConfig, StorageManager, StorageType};
+use crate::monitoring::{Alert, AlertLevel};
+use crate::retention::{
+    stage_rank, study_schedule, LegalHold, StudyLifecycleRule, StudyLifecycleRun, StudyMetadata,
+    StudyMetadataSource, UpcomingDeletion,
+};
+use crate::scheduler::{JobDefinition, JobScheduler, ScheduledJob};
+use crate::state::{StateRecovery, StateStore, StateTransaction};
+use crate::storage::{StorageConfig, StorageManager};
+use async_trait::async_trait;
 use chrono::{DateTime, Duration, Utc};
 use pacs_core::{PacsError, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
-use tokio::time::{interval, sleep};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 
+/// 访问频率统计窗口（天）
+pub const ACCESS_WINDOW_DAYS: i64 = 30;
+
+/// 生命周期任务的默认计划：每小时整点
+pub const DEFAULT_LIFECYCLE_SCHEDULE: &str = "0 * * * *";
+
+/// 状态存储中文件生命周期状态的集合名（键为文件路径）
+pub const LIFECYCLE_FILES: &str = "lifecycle.files";
+
+/// 状态存储中进行中转换的集合名（键为文件路径）
+pub const LIFECYCLE_TRANSITIONS: &str = "lifecycle.transitions";
+
+/// 状态存储中法律保留的集合名（键为检查UID）
+pub const LIFECYCLE_LEGAL_HOLDS: &str = "lifecycle.legal_holds";
+
+/// 状态存储中删除公示时间的集合名（键为检查UID）
+pub const LIFECYCLE_DELETION_NOTICES: &str = "lifecycle.deletion_notices";
+
+/// 检查进入即将删除报告后到允许删除的默认公示期（天）
+pub const DEFAULT_DELETION_NOTICE_DAYS: u32 = 30;
+
+/// 状态存储中等待删除源文件的转换的集合名（键为文件路径）
+pub const LIFECYCLE_SOURCE_DELETIONS: &str = "lifecycle.source_deletions";
+
+/// 转换校验通过后到删除源文件的默认延迟（小时）
+pub const DEFAULT_SOURCE_DELETION_DELAY_HOURS: i64 = 24;
+
 /// 生命周期阶段
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum LifecycleStage {
@@ -91,6 +127,56 @@ pub struct LifecycleStatus {
     pub next_transition_at: Option<DateTime<Utc>>,
     /// 访问次数
     pub access_count: u64,
+    /// 所属检查UID（由检查级策略管理时设置）
+    #[serde(default)]
+    pub study_uid: Option<String>,
+}
+
+/// 进行中的阶段转换
+///
+/// 复制文件前写入状态存储，完成后与文件的新状态在同一事务中清除；
+/// 启动时仍存在的记录说明转换被中断，由 [`LifecycleManager::load_state`] 回滚或完成
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingTransition {
+    /// 文件路径
+    pub file_path: String,
+    /// 源阶段
+    pub from_stage: LifecycleStage,
+    /// 目标阶段
+    pub to_stage: LifecycleStage,
+    /// 目标存储配置（为None时使用目标阶段的存储管理器）
+    pub target_storage: Option<StorageConfig>,
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+}
+
+/// 已完成校验、等待删除源文件的转换
+///
+/// 目标副本校验通过后与文件的新位置在同一事务中写入，延迟到期时再次校验目标副本，
+/// 通过后删除源文件，不一致时回滚到源文件并告警
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSourceDeletion {
+    /// 文件路径
+    pub file_path: String,
+    /// 源阶段（待删除的副本所在阶段）
+    pub source_stage: LifecycleStage,
+    /// 目标阶段
+    pub target_stage: LifecycleStage,
+    /// 目标存储配置（为None时使用目标阶段的存储管理器）
+    pub target_storage: Option<StorageConfig>,
+    /// 文件内容的SHA-256
+    pub sha256: String,
+    /// 校验通过时间
+    pub verified_at: DateTime<Utc>,
+    /// 允许删除源文件的时间
+    pub delete_after: DateTime<Utc>,
+}
+
+/// 生命周期告警接收者
+#[async_trait]
+pub trait LifecycleAlertSink: Send + Sync {
+    /// 发出告警
+    async fn raise_alert(&self, alert: Alert);
 }
 
 /// 生命周期管理器
@@ -103,6 +189,30 @@ pub struct LifecycleManager {
     file_status_cache: HashMap<String, LifecycleStatus>,
     /// 是否启用自动管理
     auto_management_enabled: bool,
+    /// 依附文件（KOS/显示状态）到其引用的图像文件
+    referenced_files: HashMap<String, HashSet<String>>,
+    /// 图像文件到引用它的依附文件
+    dependent_files: HashMap<String, HashSet<String>>,
+    /// 统计窗口内的文件访问时间
+    recent_accesses: HashMap<String, VecDeque<DateTime<Utc>>>,
+    /// 持久化状态存储（为None时状态只保存在内存中）
+    state_store: Option<Arc<StateStore>>,
+    /// 检查级生命周期规则
+    study_rules: Vec<StudyLifecycleRule>,
+    /// 检查元数据来源
+    study_source: Option<Arc<dyn StudyMetadataSource>>,
+    /// 检查UID到法律保留
+    legal_holds: HashMap<String, LegalHold>,
+    /// 检查UID到首次列入即将删除报告的时间
+    deletion_notices: HashMap<String, DateTime<Utc>>,
+    /// 删除公示期（天）
+    deletion_notice_days: u32,
+    /// 文件路径到等待删除源文件的转换
+    source_deletions: HashMap<String, PendingSourceDeletion>,
+    /// 校验通过后到删除源文件的延迟
+    source_deletion_delay: Duration,
+    /// 告警接收者
+    alert_sink: Option<Arc<dyn LifecycleAlertSink>>,
 }
 
 impl LifecycleManager {
@@ -113,7 +223,184 @@ impl LifecycleManager {
             policies: Vec::new(),
             file_status_cache: HashMap::new(),
             auto_management_enabled: true,
+            referenced_files: HashMap::new(),
+            dependent_files: HashMap::new(),
+            recent_accesses: HashMap::new(),
+            state_store: None,
+            study_rules: Vec::new(),
+            study_source: None,
+            legal_holds: HashMap::new(),
+            deletion_notices: HashMap::new(),
+            deletion_notice_days: DEFAULT_DELETION_NOTICE_DAYS,
+            source_deletions: HashMap::new(),
+            source_deletion_delay: Duration::hours(DEFAULT_SOURCE_DELETION_DELAY_HOURS),
+            alert_sink: None,
+        }
+    }
+
+    /// 设置持久化状态存储，文件状态和阶段转换随之写入
+    pub fn with_state_store(mut self, store: Arc<StateStore>) -> Self {
+        self.state_store = Some(store);
+        self
+    }
+
+    /// 设置持久化状态存储
+    pub fn set_state_store(&mut self, store: Arc<StateStore>) {
+        self.state_store = Some(store);
+    }
+
+    /// 设置校验通过后到删除源文件的延迟
+    pub fn set_source_deletion_delay(&mut self, delay: Duration) {
+        self.source_deletion_delay = delay;
+    }
+
+    /// 设置告警接收者，转换校验失败时发出告警
+    pub fn with_alert_sink(mut self, sink: Arc<dyn LifecycleAlertSink>) -> Self {
+        self.alert_sink = Some(sink);
+        self
+    }
+
+    /// 设置告警接收者
+    pub fn set_alert_sink(&mut self, sink: Arc<dyn LifecycleAlertSink>) {
+        self.alert_sink = Some(sink);
+    }
+
+    /// 从状态存储加载文件状态，并恢复上次运行中断的阶段转换
+    ///
+    /// 应在添加存储管理器之后调用。中断的转换尚未更新文件位置：源文件仍存在时回滚
+    /// （删除可能不完整的目标副本）；源文件已删除而目标副本存在时视为已完成。
+    /// 等待删除源文件的转换原样加载，由下次 [`LifecycleManager::purge_transitioned_sources`] 处理
+    pub async fn load_state(&mut self) -> Result<StateRecovery> {
+        let Some(store) = self.state_store.clone() else {
+            return Ok(StateRecovery::default());
+        };
+
+        let mut recovery = StateRecovery::default();
+        for (file_path, status) in store.load::<LifecycleStatus>(LIFECYCLE_FILES).await? {
+            self.file_status_cache.insert(file_path, status);
+            recovery.loaded += 1;
+        }
+        self.legal_holds
+            .extend(store.load::<LegalHold>(LIFECYCLE_LEGAL_HOLDS).await?);
+        self.deletion_notices.extend(
+            store
+                .load::<DateTime<Utc>>(LIFECYCLE_DELETION_NOTICES)
+                .await?,
+        );
+        self.source_deletions.extend(
+            store
+                .load::<PendingSourceDeletion>(LIFECYCLE_SOURCE_DELETIONS)
+                .await?,
+        );
+
+        for (file_path, pending) in store
+            .load::<PendingTransition>(LIFECYCLE_TRANSITIONS)
+            .await?
+        {
+            let mut txn = StateTransaction::new();
+            match self.recover_transition(&pending).await {
+                Ok(Some(true)) => {
+                    info!(
+                        "Completed interrupted transition of {} to {:?}",
+                        file_path, pending.to_stage
+                    );
+                    if let Some(status) = self.file_status_cache.get_mut(&file_path) {
+                        status.current_stage = pending.to_stage.clone();
+                        status.next_transition_at = None;
+                        txn.put(LIFECYCLE_FILES, &file_path, status)?;
+                    }
+                    recovery.rolled_forward += 1;
+                }
+                Ok(Some(false)) => {
+                    info!(
+                        "Rolled back interrupted transition of {} to {:?}",
+                        file_path, pending.to_stage
+                    );
+                    recovery.rolled_back += 1;
+                }
+                Ok(None) => {
+                    error!(
+                        "Interrupted transition of {} left no copy in {:?} or {:?} storage",
+                        file_path, pending.from_stage, pending.to_stage
+                    );
+                    recovery.unresolved += 1;
+                    continue;
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to recover interrupted transition of {}: {}",
+                        file_path, e
+                    );
+                    recovery.unresolved += 1;
+                    continue;
+                }
+            }
+            txn.delete(LIFECYCLE_TRANSITIONS, &file_path);
+            store.commit(txn).await?;
+        }
+
+        info!(
+            "Loaded lifecycle state: {} files, {} transitions completed, {} rolled back, {} unresolved",
+            recovery.loaded, recovery.rolled_forward, recovery.rolled_back, recovery.unresolved
+        );
+        Ok(recovery)
+    }
+
+    /// 判断中断的转换应完成（Some(true)）还是回滚（Some(false)），两边都没有文件时返回None
+    async fn recover_transition(&self, pending: &PendingTransition) -> Result<Option<bool>> {
+        let source = self
+            .storage_managers
+            .get(&pending.from_stage)
+            .ok_or_else(|| {
+                PacsError::Config(format!(
+                    "Storage for stage {:?} not configured",
+                    pending.from_stage
+                ))
+            })?;
+        let target = self
+            .target_storage_for(&pending.to_stage, pending.target_storage.as_ref())
+            .await?;
+
+        let source_exists = source.file_exists(&pending.file_path).await?;
+        let target_exists = target.file_exists(&pending.file_path).await?;
+
+        // 目标副本未经校验，源文件仍在时一律回滚
+        if source_exists {
+            if target_exists {
+                target.delete_file(&pending.file_path).await?;
+            }
+            return Ok(Some(false));
+        }
+        if target_exists {
+            return Ok(Some(true));
+        }
+        Ok(None)
+    }
+
+    /// 提交状态事务（未配置状态存储时忽略）
+    async fn commit(&self, txn: StateTransaction) -> Result<()> {
+        match &self.state_store {
+            Some(store) => store.commit(txn).await,
+            None => Ok(()),
+        }
+    }
+
+    /// 将文件的当前状态加入事务
+    pub(crate) fn stage_status(&self, txn: &mut StateTransaction, file_path: &str) -> Result<()> {
+        if let Some(status) = self.file_status_cache.get(file_path) {
+            txn.put(LIFECYCLE_FILES, file_path, status)?;
         }
+        Ok(())
+    }
+
+    /// 持久化文件的当前状态
+    async fn persist_status(&self, file_path: &str) -> Result<()> {
+        if self.state_store.is_none() {
+            return Ok(());
+        }
+        let mut txn = StateTransaction::new();
+        self.stage_status(&mut txn, file_path)?;
+        self.commit(txn).await
     }
 
     /// 添加存储管理器
@@ -135,7 +422,7 @@ impl LifecycleManager {
     pub async fn register_file(
         &mut self,
         file_path: &str,
-        tags: Option<HashMap<String, String>>,
+        _tags: Option<HashMap<String, String>>,
     ) -> Result<()> {
         let status = LifecycleStatus {
             file_path: file_path.to_string(),
@@ -144,49 +431,448 @@ impl LifecycleManager {
             last_accessed_at: None,
             next_transition_at: None,
             access_count: 0,
+            study_uid: None,
         };
 
         self.file_status_cache.insert(file_path.to_string(), status);
+        self.persist_status(file_path).await?;
 
         info!("Registered file in lifecycle management: {}", file_path);
         Ok(())
     }
 
+    /// 添加检查级生命周期规则
+    ///
+    /// 存在检查级规则时，已关联检查的文件只按检查级规则转换，不再按路径规则转换
+    pub fn add_study_rule(&mut self, rule: StudyLifecycleRule) {
+        self.study_rules.push(rule);
+    }
+
+    /// 获取检查级生命周期规则
+    pub fn study_rules(&self) -> &[StudyLifecycleRule] {
+        &self.study_rules
+    }
+
+    /// 设置检查元数据来源，定时任务据此评估检查级规则
+    pub fn with_study_metadata_source(mut self, source: Arc<dyn StudyMetadataSource>) -> Self {
+        self.study_source = Some(source);
+        self
+    }
+
+    /// 设置删除公示期：检查首次列入即将删除报告后至少经过该天数才会删除
+    pub fn set_deletion_notice_days(&mut self, days: u32) {
+        self.deletion_notice_days = days;
+    }
+
+    /// 设置法律保留，保留期间检查不会被删除
+    pub async fn place_legal_hold(&mut self, hold: LegalHold) -> Result<()> {
+        let mut txn = StateTransaction::new();
+        txn.put(LIFECYCLE_LEGAL_HOLDS, &hold.study_uid, &hold)?;
+        self.commit(txn).await?;
+
+        info!(
+            "Placed legal hold on study {} by {}: {}",
+            hold.study_uid, hold.placed_by, hold.reason
+        );
+        self.legal_holds.insert(hold.study_uid.clone(), hold);
+        Ok(())
+    }
+
+    /// 解除法律保留，返回被解除的保留
+    pub async fn release_legal_hold(&mut self, study_uid: &str) -> Result<Option<LegalHold>> {
+        if !self.legal_holds.contains_key(study_uid) {
+            return Ok(None);
+        }
+        let mut txn = StateTransaction::new();
+        txn.delete(LIFECYCLE_LEGAL_HOLDS, study_uid);
+        self.commit(txn).await?;
+
+        info!("Released legal hold on study {}", study_uid);
+        Ok(self.legal_holds.remove(study_uid))
+    }
+
+    /// 获取全部法律保留，按设置时间排列
+    pub fn legal_holds(&self) -> Vec<LegalHold> {
+        let mut holds: Vec<LegalHold> = self.legal_holds.values().cloned().collect();
+        holds.sort_by_key(|h| h.placed_at);
+        holds
+    }
+
+    /// 文件所属检查是否处于法律保留
+    fn is_on_legal_hold(&self, file_path: &str) -> bool {
+        self.file_status_cache
+            .get(file_path)
+            .and_then(|status| status.study_uid.as_ref())
+            .is_some_and(|study_uid| self.legal_holds.contains_key(study_uid))
+    }
+
+    /// 从元数据来源读取检查并评估检查级规则，未设置来源时不执行
+    pub async fn evaluate_studies(&mut self) -> Result<StudyLifecycleRun> {
+        let Some(source) = self.study_source.clone() else {
+            return Ok(StudyLifecycleRun::default());
+        };
+        let studies = source.studies().await?;
+        self.apply_study_policies(&studies).await
+    }
+
+    /// 按检查级规则转换检查的全部文件
+    ///
+    /// 未登记的文件随之登记。保留期到期的检查须已列入即将删除报告满公示期且不处于法律保留，
+    /// 才将其文件标记为待删除（由过期清理删除）；否则只执行到期的层级转换
+    pub async fn apply_study_policies(
+        &mut self,
+        studies: &[StudyMetadata],
+    ) -> Result<StudyLifecycleRun> {
+        let now = Utc::now();
+        let notice = Duration::days(i64::from(self.deletion_notice_days));
+        let mut run = StudyLifecycleRun::default();
+
+        for study in studies {
+            run.studies_evaluated += 1;
+            self.attach_study_files(study).await?;
+
+            let schedule = study_schedule(&self.study_rules, study);
+            let deletion = schedule
+                .iter()
+                .find(|s| s.stage == LifecycleStage::PendingDeletion);
+
+            // 进入公示窗口时记录首次公示时间，规则变化不再删除时清除
+            match deletion {
+                Some(deletion) if deletion.due_at <= now + notice => {
+                    if !self.deletion_notices.contains_key(&study.study_uid) {
+                        let mut txn = StateTransaction::new();
+                        txn.put(LIFECYCLE_DELETION_NOTICES, &study.study_uid, &now)?;
+                        self.commit(txn).await?;
+                        self.deletion_notices.insert(study.study_uid.clone(), now);
+                    }
+                }
+                _ => {
+                    if self.deletion_notices.remove(&study.study_uid).is_some() {
+                        let mut txn = StateTransaction::new();
+                        txn.delete(LIFECYCLE_DELETION_NOTICES, &study.study_uid);
+                        self.commit(txn).await?;
+                    }
+                }
+            }
+
+            if let Some(deletion) = deletion.filter(|d| d.due_at <= now) {
+                if self.legal_holds.contains_key(&study.study_uid) {
+                    debug!("Study {} is on legal hold, not deleting", study.study_uid);
+                    run.studies_on_hold += 1;
+                } else if self
+                    .deletion_notices
+                    .get(&study.study_uid)
+                    .is_some_and(|noticed_at| *noticed_at + notice <= now)
+                {
+                    info!(
+                        "Study {} reached end of retention (rule {}), marking {} files for deletion",
+                        study.study_uid,
+                        deletion.rule_id,
+                        study.file_paths.len()
+                    );
+                    run.files_marked_for_deletion +=
+                        self.mark_study_for_deletion(study, now).await?;
+                    continue;
+                } else {
+                    run.studies_awaiting_notice += 1;
+                }
+            }
+
+            // 执行已到期的最后一个层级转换
+            let Some(target) = schedule
+                .iter()
+                .rev()
+                .find(|s| s.due_at <= now && s.stage != LifecycleStage::PendingDeletion)
+            else {
+                continue;
+            };
+            let transition = LifecycleTransition {
+                stage: target.stage.clone(),
+                days_after_creation: 0,
+                target_storage: None,
+            };
+            for file_path in &study.file_paths {
+                let Some(mut status) = self.file_status_cache.get(file_path).cloned() else {
+                    continue;
+                };
+                if stage_rank(&status.current_stage) >= stage_rank(&target.stage)
+                    || self.source_deletions.contains_key(file_path)
+                {
+                    continue;
+                }
+                match self
+                    .transition_file(file_path, &mut status, &transition)
+                    .await
+                {
+                    Ok(()) => {
+                        run.files_transitioned += 1;
+                        self.file_status_cache.insert(file_path.clone(), status);
+                    }
+                    Err(e) => error!(
+                        "Failed to transition file {} of study {}: {}",
+                        file_path, study.study_uid, e
+                    ),
+                }
+            }
+        }
+
+        info!(
+            "Evaluated {} studies: {} files transitioned, {} files marked for deletion, {} studies on hold, {} awaiting notice",
+            run.studies_evaluated,
+            run.files_transitioned,
+            run.files_marked_for_deletion,
+            run.studies_on_hold,
+            run.studies_awaiting_notice
+        );
+        Ok(run)
+    }
+
+    /// 登记检查的文件并关联检查UID
+    async fn attach_study_files(&mut self, study: &StudyMetadata) -> Result<()> {
+        let mut txn = StateTransaction::new();
+        for file_path in &study.file_paths {
+            let status = self
+                .file_status_cache
+                .entry(file_path.clone())
+                .or_insert_with(|| LifecycleStatus {
+                    file_path: file_path.clone(),
+                    current_stage: LifecycleStage::Online,
+                    created_at: Utc::now(),
+                    last_accessed_at: None,
+                    next_transition_at: None,
+                    access_count: 0,
+                    study_uid: None,
+                });
+            if status.study_uid.as_deref() != Some(study.study_uid.as_str()) {
+                status.study_uid = Some(study.study_uid.clone());
+                txn.put(LIFECYCLE_FILES, file_path, status)?;
+            }
+        }
+        self.commit(txn).await
+    }
+
+    /// 将检查的文件标记为待删除，返回标记的文件数
+    async fn mark_study_for_deletion(
+        &mut self,
+        study: &StudyMetadata,
+        now: DateTime<Utc>,
+    ) -> Result<usize> {
+        let mut txn = StateTransaction::new();
+        let mut marked = 0;
+        for file_path in &study.file_paths {
+            if let Some(status) = self.file_status_cache.get_mut(file_path) {
+                if status.current_stage == LifecycleStage::PendingDeletion {
+                    continue;
+                }
+                status.current_stage = LifecycleStage::PendingDeletion;
+                status.next_transition_at = Some(now);
+                txn.put(LIFECYCLE_FILES, file_path, status)?;
+                marked += 1;
+            }
+        }
+        txn.delete(LIFECYCLE_DELETION_NOTICES, &study.study_uid);
+        self.commit(txn).await?;
+        self.deletion_notices.remove(&study.study_uid);
+        Ok(marked)
+    }
+
+    /// 从元数据来源读取检查，生成指定天数内到期的即将删除报告
+    pub async fn upcoming_deletions(&self, within_days: u32) -> Result<Vec<UpcomingDeletion>> {
+        let Some(source) = self.study_source.clone() else {
+            return Ok(Vec::new());
+        };
+        let studies = source.studies().await?;
+        Ok(self.upcoming_deletions_for(&studies, within_days))
+    }
+
+    /// 生成指定天数内保留期到期的检查报告，按预计执行时间排列
+    ///
+    /// 尚未公示的检查按从现在开始公示计算执行时间
+    pub fn upcoming_deletions_for(
+        &self,
+        studies: &[StudyMetadata],
+        within_days: u32,
+    ) -> Vec<UpcomingDeletion> {
+        let now = Utc::now();
+        let horizon = now + Duration::days(i64::from(within_days));
+        let notice = Duration::days(i64::from(self.deletion_notice_days));
+
+        let mut report: Vec<UpcomingDeletion> = studies
+            .iter()
+            .filter_map(|study| {
+                let deletion = study_schedule(&self.study_rules, study)
+                    .into_iter()
+                    .find(|s| s.stage == LifecycleStage::PendingDeletion)
+                    .filter(|d| d.due_at <= horizon)?;
+                let noticed_at = self
+                    .deletion_notices
+                    .get(&study.study_uid)
+                    .copied()
+                    .unwrap_or(now);
+                Some(UpcomingDeletion {
+                    study_uid: study.study_uid.clone(),
+                    executes_at: deletion.due_at.max(noticed_at + notice),
+                    due_at: deletion.due_at,
+                    rule_id: deletion.rule_id,
+                    file_count: study.file_paths.len(),
+                    legal_hold: self.legal_holds.get(&study.study_uid).cloned(),
+                })
+            })
+            .collect();
+        report.sort_by_key(|d| d.executes_at);
+        report
+    }
+
+    /// 关联依附文件（KOS/显示状态）与其引用的图像文件
+    ///
+    /// 依附文件在任一引用图像仍被保留时不会被删除，
+    /// 引用的图像全部删除后随之删除。
+    pub fn link_dependent_file(&mut self, dependent_path: &str, referenced_paths: &[String]) {
+        let references = self
+            .referenced_files
+            .entry(dependent_path.to_string())
+            .or_default();
+
+        for referenced_path in referenced_paths {
+            references.insert(referenced_path.clone());
+            self.dependent_files
+                .entry(referenced_path.clone())
+                .or_default()
+                .insert(dependent_path.to_string());
+        }
+
+        debug!(
+            "Linked dependent file {} to {} referenced files",
+            dependent_path,
+            referenced_paths.len()
+        );
+    }
+
+    /// 获取引用指定图像文件的依附文件
+    pub fn get_dependent_files(&self, file_path: &str) -> Vec<String> {
+        self.dependent_files
+            .get(file_path)
+            .map(|dependents| dependents.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// 依附文件是否仍被其引用的图像保留
+    fn is_retained_by_references(&self, file_path: &str) -> bool {
+        self.referenced_files
+            .get(file_path)
+            .map(|references| {
+                references.iter().any(|referenced| {
+                    self.file_status_cache
+                        .get(referenced)
+                        .is_some_and(|status| {
+                            status.current_stage != LifecycleStage::PendingDeletion
+                        })
+                })
+            })
+            .unwrap_or(false)
+    }
+
+    /// 解除已删除文件的引用关系，返回引用已全部删除的依附文件
+    fn release_references(&mut self, file_path: &str) -> Vec<String> {
+        let mut orphaned = Vec::new();
+
+        if let Some(dependents) = self.dependent_files.remove(file_path) {
+            for dependent in dependents {
+                if let Some(references) = self.referenced_files.get_mut(&dependent) {
+                    references.remove(file_path);
+                    if references.is_empty() {
+                        self.referenced_files.remove(&dependent);
+                        orphaned.push(dependent);
+                    }
+                }
+            }
+        }
+
+        // 被删除的文件本身是依附文件时，从其引用图像的依附列表中移除
+        if let Some(references) = self.referenced_files.remove(file_path) {
+            for referenced in references {
+                if let Some(dependents) = self.dependent_files.get_mut(&referenced) {
+                    dependents.remove(file_path);
+                }
+            }
+        }
+
+        orphaned
+    }
+
     /// 更新文件访问记录
     pub async fn record_access(&mut self, file_path: &str) -> Result<()> {
         if let Some(status) = self.file_status_cache.get_mut(file_path) {
-            status.last_accessed_at = Some(Utc::now());
+            let now = Utc::now();
+            status.last_accessed_at = Some(now);
             status.access_count += 1;
 
+            let accesses = self
+                .recent_accesses
+                .entry(file_path.to_string())
+                .or_default();
+            accesses.push_back(now);
+            let window_start = now - Duration::days(ACCESS_WINDOW_DAYS);
+            while accesses.front().is_some_and(|t| *t < window_start) {
+                accesses.pop_front();
+            }
+
             debug!(
                 "Recorded access for file: {} (count: {})",
                 file_path, status.access_count
             );
+            self.persist_status(file_path).await?;
         }
         Ok(())
     }
 
+    /// 最近 [`ACCESS_WINDOW_DAYS`] 天内的访问次数
+    pub fn recent_access_count(&self, file_path: &str) -> u64 {
+        let window_start = Utc::now() - Duration::days(ACCESS_WINDOW_DAYS);
+        self.recent_accesses
+            .get(file_path)
+            .map(|accesses| accesses.iter().filter(|t| **t >= window_start).count() as u64)
+            .unwrap_or(0)
+    }
+
     /// 执行生命周期转换
     pub async fn execute_transitions(&mut self) -> Result<Vec<String>> {
         let mut transitions_executed = Vec::new();
         let now = Utc::now();
 
-        for (file_path, status) in &mut self.file_status_cache {
+        let file_paths: Vec<String> = self.file_status_cache.keys().cloned().collect();
+        for file_path in file_paths {
+            let Some(mut status) = self.file_status_cache.get(&file_path).cloned() else {
+                continue;
+            };
+            // 已关联检查的文件由检查级规则管理
+            if status.study_uid.is_some() && !self.study_rules.is_empty() {
+                continue;
+            }
+            // 上次转换的源文件删除前不再转换
+            if self.source_deletions.contains_key(&file_path) {
+                continue;
+            }
+
             // 检查是否需要转换
             if let Some(next_transition) = status.next_transition_at {
                 if next_transition <= now {
                     // 执行转换
-                    if let Ok(transitioned) = self.execute_file_transition(file_path, status).await
-                    {
-                        if transitioned {
-                            transitions_executed.push(file_path.clone());
-                        }
+                    if let Ok(true) = self.execute_file_transition(&file_path, &mut status).await {
+                        transitions_executed.push(file_path.clone());
                     }
                 }
             } else {
                 // 计算下次转换时间
-                self.update_next_transition_time(file_path, status).await?;
+                self.update_next_transition_time(&file_path, &mut status)
+                    .await?;
+                if status.next_transition_at.is_some() {
+                    let mut txn = StateTransaction::new();
+                    txn.put(LIFECYCLE_FILES, &file_path, &status)?;
+                    self.commit(txn).await?;
+                }
             }
+            self.file_status_cache.insert(file_path, status);
         }
 
         if !transitions_executed.is_empty() {
@@ -206,20 +892,28 @@ impl LifecycleManager {
         status: &mut LifecycleStatus,
     ) -> Result<bool> {
         // 获取适用的策略
-        let applicable_rules = self.get_applicable_rules(file_path, status)?;
+        let transitions: Vec<LifecycleTransition> = self
+            .get_applicable_rules(file_path, status)?
+            .into_iter()
+            .flat_map(|rule| rule.transitions.iter().cloned())
+            .collect();
 
-        for rule in applicable_rules {
-            for transition in &rule.transitions {
-                if self.should_transition(status, transition) {
-                    // 执行转换
-                    if let Err(e) = self.transition_file(file_path, status, transition).await {
-                        error!("Failed to transition file {}: {}", file_path, e);
-                        continue;
-                    }
-
-                    info!("Transitioned file {} to {:?}", file_path, transition.stage);
-                    return Ok(true);
+        for transition in &transitions {
+            if transition.stage == LifecycleStage::PendingDeletion
+                && self.is_on_legal_hold(file_path)
+            {
+                debug!("File {} is on legal hold, not deleting", file_path);
+                continue;
+            }
+            if self.should_transition(status, transition) {
+                // 执行转换
+                if let Err(e) = self.transition_file(file_path, status, transition).await {
+                    error!("Failed to transition file {}: {}", file_path, e);
+                    continue;
                 }
+
+                info!("Transitioned file {} to {:?}", file_path, transition.stage);
+                return Ok(true);
             }
         }
 
@@ -230,7 +924,7 @@ impl LifecycleManager {
     fn get_applicable_rules(
         &self,
         file_path: &str,
-        status: &LifecycleStatus,
+        _status: &LifecycleStatus,
     ) -> Result<Vec<&LifecycleRule>> {
         let mut applicable_rules = Vec::new();
 
@@ -287,7 +981,27 @@ impl LifecycleManager {
         days_since_creation >= transition.days_after_creation
     }
 
+    /// 获取目标阶段的存储管理器，指定了目标存储配置时按配置创建
+    async fn target_storage_for(
+        &self,
+        stage: &LifecycleStage,
+        target_storage: Option<&StorageConfig>,
+    ) -> Result<StorageManager> {
+        match target_storage {
+            // 创建新的存储管理器
+            Some(target_config) => StorageManager::new(target_config.clone()).await,
+            None => self.storage_managers.get(stage).cloned().ok_or_else(|| {
+                PacsError::Config("Target storage stage not configured".to_string())
+            }),
+        }
+    }
+
     /// 转换文件到新的存储阶段
+    ///
+    /// 两阶段执行：复制前持久化转换记录，写入目标后读回并校验SHA-256，
+    /// 校验通过才在同一事务中更新文件位置、清除转换记录；源文件延迟到
+    /// [`LifecycleManager::purge_transitioned_sources`] 再次校验后删除。
+    /// 复制失败或校验不一致时删除目标副本，文件仍留在源阶段
     async fn transition_file(
         &mut self,
         file_path: &str,
@@ -297,47 +1011,222 @@ impl LifecycleManager {
         let current_storage = self
             .storage_managers
             .get(&status.current_stage)
-            .ok_or_else(|| PacsError::configuration("Current storage stage not configured"))?;
+            .cloned()
+            .ok_or_else(|| PacsError::Config("Current storage stage not configured".to_string()))?;
+        let target_storage = self
+            .target_storage_for(&transition.stage, transition.target_storage.as_ref())
+            .await?;
 
-        let target_storage = if let Some(target_config) = &transition.target_storage {
-            // 创建新的存储管理器
-            StorageManager::new(target_config.clone()).await?
-        } else {
-            self.storage_managers
-                .get(&transition.stage)
-                .ok_or_else(|| PacsError::configuration("Target storage stage not configured"))?
-                .clone()
+        let pending = PendingTransition {
+            file_path: file_path.to_string(),
+            from_stage: status.current_stage.clone(),
+            to_stage: transition.stage.clone(),
+            target_storage: transition.target_storage.clone(),
+            started_at: Utc::now(),
         };
+        let mut txn = StateTransaction::new();
+        txn.put(LIFECYCLE_TRANSITIONS, file_path, &pending)?;
+        self.commit(txn).await?;
 
-        // 读取文件数据
-        let file_data = current_storage.get_file(file_path).await?;
+        // 读取文件数据，存储到目标位置后读回校验
+        let copied = async {
+            let file_data = current_storage.get_file(file_path).await?;
+            let sha256 = calculate_sha256(&file_data);
+            target_storage.store_file(&file_data, file_path).await?;
+            let verified = verify_copy(&target_storage, file_path, &sha256).await?;
+            Ok::<_, PacsError>((sha256, verified))
+        }
+        .await;
 
-        // 存储到目标位置
-        let new_path = if transition.target_storage.is_some() {
-            // 如果是新的存储配置，可能需要调整路径
-            file_path.to_string()
-        } else {
-            file_path.to_string()
+        let verified = match copied {
+            Ok((sha256, true)) => Ok(sha256),
+            Ok((_, false)) => {
+                let message = format!(
+                    "Checksum mismatch for {} after copy from {:?} to {:?} storage",
+                    file_path, pending.from_stage, pending.to_stage
+                );
+                self.alert(file_path, &message).await;
+                Err(PacsError::Storage(message))
+            }
+            Err(e) => Err(e),
+        };
+        let mut txn = StateTransaction::new();
+        txn.delete(LIFECYCLE_TRANSITIONS, file_path);
+        let sha256 = match verified {
+            Ok(sha256) => sha256,
+            Err(e) => {
+                // 删除可能不完整或损坏的目标副本，下次重新转换
+                if let Err(cleanup) = target_storage.delete_file(file_path).await {
+                    warn!(
+                        "Failed to remove unverified copy of {} from {:?} storage: {}",
+                        file_path, transition.stage, cleanup
+                    );
+                }
+                self.commit(txn).await?;
+                return Err(e);
+            }
         };
 
-        target_storage.store_file(&file_data, &new_path).await?;
-
-        // 删除原文件（可选，根据策略决定）
-        if transition.stage != LifecycleStage::Online {
-            // 在实际应用中，这里可能需要更复杂的逻辑来确保数据完整性
-            current_storage.delete_file(file_path).await?;
-        }
-
-        // 更新状态
+        // 更新文件位置，转换到在线存储时保留源文件，其余延迟删除源文件
+        let now = Utc::now();
+        let source_deletion =
+            (transition.stage != LifecycleStage::Online).then(|| PendingSourceDeletion {
+                file_path: file_path.to_string(),
+                source_stage: status.current_stage.clone(),
+                target_stage: transition.stage.clone(),
+                target_storage: transition.target_storage.clone(),
+                sha256,
+                verified_at: now,
+                delete_after: now + self.source_deletion_delay,
+            });
         status.current_stage = transition.stage.clone();
         status.next_transition_at = None;
+        txn.put(LIFECYCLE_FILES, file_path, status)?;
+        if let Some(deletion) = &source_deletion {
+            txn.put(LIFECYCLE_SOURCE_DELETIONS, file_path, deletion)?;
+        }
+        self.commit(txn).await?;
 
+        if let Some(deletion) = source_deletion {
+            debug!(
+                "Verified copy of {} in {:?} storage, source deletion after {}",
+                file_path, deletion.target_stage, deletion.delete_after
+            );
+            self.source_deletions
+                .insert(file_path.to_string(), deletion);
+        }
         Ok(())
     }
 
+    /// 删除延迟到期的源文件，返回删除的源文件数
+    ///
+    /// 删除前再次读回目标副本校验；目标副本缺失或不一致时回滚到源文件
+    /// （文件位置恢复为源阶段并删除目标副本）并告警
+    pub async fn purge_transitioned_sources(&mut self) -> Result<usize> {
+        let now = Utc::now();
+        let due: Vec<PendingSourceDeletion> = self
+            .source_deletions
+            .values()
+            .filter(|deletion| deletion.delete_after <= now)
+            .cloned()
+            .collect();
+
+        let mut purged = 0;
+        for deletion in due {
+            match self.purge_source(&deletion).await {
+                Ok(true) => purged += 1,
+                Ok(false) => {}
+                Err(e) => error!(
+                    "Failed to delete source of transitioned file {}: {}",
+                    deletion.file_path, e
+                ),
+            }
+        }
+
+        if purged > 0 {
+            info!("Deleted {} transitioned source files", purged);
+        }
+        Ok(purged)
+    }
+
+    /// 校验目标副本后删除源文件，回滚时返回false
+    async fn purge_source(&mut self, deletion: &PendingSourceDeletion) -> Result<bool> {
+        let file_path = deletion.file_path.as_str();
+        let source = self
+            .storage_managers
+            .get(&deletion.source_stage)
+            .cloned()
+            .ok_or_else(|| {
+                PacsError::Config(format!(
+                    "Storage for stage {:?} not configured",
+                    deletion.source_stage
+                ))
+            })?;
+        let target = self
+            .target_storage_for(&deletion.target_stage, deletion.target_storage.as_ref())
+            .await?;
+
+        let mut txn = StateTransaction::new();
+        txn.delete(LIFECYCLE_SOURCE_DELETIONS, file_path);
+
+        let verified = target.file_exists(file_path).await?
+            && verify_copy(&target, file_path, &deletion.sha256).await?;
+        if verified {
+            if source.file_exists(file_path).await? {
+                source.delete_file(file_path).await?;
+            }
+            self.commit(txn).await?;
+            self.source_deletions.remove(file_path);
+            debug!(
+                "Deleted {:?} source of {} after verifying {:?} copy",
+                deletion.source_stage, file_path, deletion.target_stage
+            );
+            return Ok(true);
+        }
+
+        if !source.file_exists(file_path).await? {
+            // 两边都没有完好的副本，保留记录等待人工处理
+            let message = format!(
+                "Copy of {} in {:?} storage failed verification and no {:?} source remains",
+                file_path, deletion.target_stage, deletion.source_stage
+            );
+            self.alert(file_path, &message).await;
+            return Err(PacsError::Storage(message));
+        }
+
+        let message = format!(
+            "Copy of {} in {:?} storage failed verification before source deletion, rolled back to {:?}",
+            file_path, deletion.target_stage, deletion.source_stage
+        );
+        self.alert(file_path, &message).await;
+        if let Err(e) = target.delete_file(file_path).await {
+            warn!(
+                "Failed to remove corrupt copy of {} from {:?} storage: {}",
+                file_path, deletion.target_stage, e
+            );
+        }
+        if let Some(status) = self.file_status_cache.get_mut(file_path) {
+            status.current_stage = deletion.source_stage.clone();
+            status.next_transition_at = None;
+            txn.put(LIFECYCLE_FILES, file_path, status)?;
+        }
+        self.commit(txn).await?;
+        self.source_deletions.remove(file_path);
+        Ok(false)
+    }
+
+    /// 获取等待删除源文件的转换
+    pub fn pending_source_deletions(&self) -> Vec<PendingSourceDeletion> {
+        let mut deletions: Vec<PendingSourceDeletion> =
+            self.source_deletions.values().cloned().collect();
+        deletions.sort_by_key(|d| d.delete_after);
+        deletions
+    }
+
+    /// 记录转换校验失败并发出严重告警
+    async fn alert(&self, file_path: &str, message: &str) {
+        error!("{}", message);
+        let Some(sink) = &self.alert_sink else {
+            return;
+        };
+        let now = Utc::now();
+        sink.raise_alert(Alert {
+            id: format!("lifecycle_verification_{}_{}", file_path, now.timestamp()),
+            rule_name: "lifecycle_transition_verification".to_string(),
+            level: AlertLevel::Critical,
+            message: message.to_string(),
+            current_value: 0.0,
+            threshold: 0.0,
+            start_time: now,
+            end_time: None,
+            active: true,
+        })
+        .await;
+    }
+
     /// 更新下次转换时间
     async fn update_next_transition_time(
-        &mut self,
+        &self,
         file_path: &str,
         status: &mut LifecycleStatus,
     ) -> Result<()> {
@@ -362,29 +1251,28 @@ impl LifecycleManager {
         Ok(())
     }
 
-    /// 启动自动生命周期管理
-    pub async fn start_auto_management(&mut self) -> Result<()> {
-        if !self.auto_management_enabled {
+    /// 默认的生命周期任务定义：每小时整点执行
+    pub fn default_job_definition() -> JobDefinition {
+        JobDefinition::new("lifecycle", DEFAULT_LIFECYCLE_SCHEDULE)
+    }
+
+    /// 注册生命周期定时任务（执行阶段转换并清理过期文件）
+    ///
+    /// 自动管理被禁用时不注册，返回是否已注册
+    pub async fn schedule_jobs(
+        manager: Arc<Mutex<Self>>,
+        scheduler: &JobScheduler,
+        definition: JobDefinition,
+    ) -> Result<bool> {
+        if !manager.lock().await.auto_management_enabled {
             info!("Auto lifecycle management is disabled");
-            return Ok(());
+            return Ok(false);
         }
 
-        info!("Starting auto lifecycle management");
-
-        let mut interval = interval(tokio::time::Duration::from_secs(3600)); // 每小时检查一次
-
-        loop {
-            interval.tick().await;
-
-            if let Err(e) = self.execute_transitions().await {
-                error!("Error executing lifecycle transitions: {}", e);
-            }
-
-            // 清理过期文件
-            if let Err(e) = self.cleanup_expired_files().await {
-                error!("Error cleaning up expired files: {}", e);
-            }
-        }
+        scheduler
+            .register(definition, Arc::new(LifecycleJob { manager }))
+            .await?;
+        Ok(true)
     }
 
     /// 清理过期文件
@@ -397,18 +1285,48 @@ impl LifecycleManager {
                 // 检查是否已经过了保留期
                 if let Some(transition_time) = status.next_transition_at {
                     if transition_time <= now {
+                        if self.is_on_legal_hold(file_path) {
+                            debug!("Retaining file on legal hold: {}", file_path);
+                            continue;
+                        }
+                        // 引用的图像仍被保留时，依附文件一并保留
+                        if self.is_retained_by_references(file_path) {
+                            debug!(
+                                "Retaining dependent file with live references: {}",
+                                file_path
+                            );
+                            continue;
+                        }
                         files_to_remove.push(file_path.clone());
                     }
                 }
             }
         }
 
-        for file_path in files_to_remove {
+        while let Some(file_path) = files_to_remove.pop() {
             if let Err(e) = self.remove_file(&file_path).await {
                 error!("Failed to remove expired file {}: {}", file_path, e);
             } else {
                 info!("Removed expired file: {}", file_path);
                 self.file_status_cache.remove(&file_path);
+                self.recent_accesses.remove(&file_path);
+                let mut txn = StateTransaction::new();
+                txn.delete(LIFECYCLE_FILES, &file_path);
+                if self.source_deletions.remove(&file_path).is_some() {
+                    txn.delete(LIFECYCLE_SOURCE_DELETIONS, &file_path);
+                }
+                self.commit(txn).await?;
+
+                // 引用的图像全部删除后，依附文件随之删除
+                for dependent in self.release_references(&file_path) {
+                    if !files_to_remove.contains(&dependent) {
+                        info!(
+                            "Removing dependent file with its referenced images: {}",
+                            dependent
+                        );
+                        files_to_remove.push(dependent);
+                    }
+                }
             }
         }
 
@@ -431,6 +1349,20 @@ impl LifecycleManager {
         Ok(())
     }
 
+    /// 记录文件已被外部（如归档管理器）移动到指定阶段
+    pub fn mark_stage(&mut self, file_path: &str, stage: LifecycleStage) {
+        if let Some(status) = self.file_status_cache.get_mut(file_path) {
+            debug!("File {} moved to {:?}", file_path, stage);
+            status.current_stage = stage;
+            status.next_transition_at = None;
+        }
+    }
+
+    /// 获取指定阶段的存储管理器
+    pub fn stage_storage(&self, stage: &LifecycleStage) -> Option<StorageManager> {
+        self.storage_managers.get(stage).cloned()
+    }
+
     /// 获取文件状态
     pub fn get_file_status(&self, file_path: &str) -> Option<&LifecycleStatus> {
         self.file_status_cache.get(file_path)
@@ -504,8 +1436,142 @@ impl LifecycleManager {
     }
 }
 
+/// 计算数据的SHA-256（十六进制）
+fn calculate_sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// 读回存储中的副本并与期望的SHA-256比较
+async fn verify_copy(storage: &StorageManager, file_path: &str, sha256: &str) -> Result<bool> {
+    let data = storage.get_file(file_path).await?;
+    Ok(calculate_sha256(&data) == sha256)
+}
+
 impl Default for LifecycleManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// 生命周期定时任务
+pub struct LifecycleJob {
+    /// 生命周期管理器
+    manager: Arc<Mutex<LifecycleManager>>,
+}
+
+#[async_trait]
+impl ScheduledJob for LifecycleJob {
+    async fn run(&self) -> Result<String> {
+        let mut manager = self.manager.lock().await;
+        let studies = manager.evaluate_studies().await?;
+        let transitioned = manager.execute_transitions().await?;
+        let purged = manager.purge_transitioned_sources().await?;
+        manager.cleanup_expired_files().await?;
+        Ok(format!(
+            "{} files transitioned, {} study files transitioned, {} study files marked for deletion, {} transitioned sources deleted",
+            transitioned.len(),
+            studies.files_transitioned,
+            studies.files_marked_for_deletion,
+            purged
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageType;
+
+    /// 记录告警的接收者
+    #[derive(Default)]
+    struct RecordingSink {
+        alerts: std::sync::Mutex<Vec<Alert>>,
+    }
+
+    #[async_trait]
+    impl LifecycleAlertSink for RecordingSink {
+        async fn raise_alert(&self, alert: Alert) {
+            self.alerts.lock().unwrap().push(alert);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verified_transition_with_delayed_source_deletion() {
+        let root = std::env::temp_dir().join(format!(
+            "pacs-lifecycle-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let local = |dir: &str| StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(root.join(dir).to_string_lossy().to_string()),
+            object_store_config: None,
+        };
+        let online = StorageManager::new(local("online")).await.unwrap();
+        let nearline = StorageManager::new(local("nearline")).await.unwrap();
+
+        let sink = Arc::new(RecordingSink::default());
+        let store = Arc::new(StateStore::open(root.join("state")).await.unwrap());
+        let mut manager = LifecycleManager::new()
+            .with_state_store(store.clone())
+            .with_alert_sink(sink.clone());
+        manager.add_storage_manager(LifecycleStage::Online, online.clone());
+        manager.add_storage_manager(LifecycleStage::Nearline, nearline.clone());
+        manager.set_source_deletion_delay(Duration::zero());
+
+        let transition = LifecycleTransition {
+            stage: LifecycleStage::Nearline,
+            days_after_creation: 0,
+            target_storage: None,
+        };
+        for name in ["good.dcm", "corrupt.dcm"] {
+            online.store_file(name.as_bytes(), name).await.unwrap();
+            manager.register_file(name, None).await.unwrap();
+            let mut status = manager.get_file_status(name).cloned().unwrap();
+            manager
+                .transition_file(name, &mut status, &transition)
+                .await
+                .unwrap();
+            manager.file_status_cache.insert(name.to_string(), status);
+            // 校验通过后位置已更新，源文件在延迟到期前保留
+            assert!(online.file_exists(name).await.unwrap());
+            assert!(nearline.file_exists(name).await.unwrap());
+        }
+        assert_eq!(manager.pending_source_deletions().len(), 2);
+
+        // 删除源文件前目标副本损坏：回滚到在线存储并告警
+        nearline
+            .store_file(b"bit rot", "corrupt.dcm")
+            .await
+            .unwrap();
+
+        // 重启后从状态存储恢复等待删除的源文件
+        let mut manager = LifecycleManager::new()
+            .with_state_store(store)
+            .with_alert_sink(sink.clone());
+        manager.add_storage_manager(LifecycleStage::Online, online.clone());
+        manager.add_storage_manager(LifecycleStage::Nearline, nearline.clone());
+        manager.load_state().await.unwrap();
+        assert_eq!(manager.purge_transitioned_sources().await.unwrap(), 1);
+
+        assert!(!online.file_exists("good.dcm").await.unwrap());
+        assert_eq!(
+            manager.get_file_status("good.dcm").unwrap().current_stage,
+            LifecycleStage::Nearline
+        );
+        assert!(online.file_exists("corrupt.dcm").await.unwrap());
+        assert!(!nearline.file_exists("corrupt.dcm").await.unwrap());
+        assert_eq!(
+            manager
+                .get_file_status("corrupt.dcm")
+                .unwrap()
+                .current_stage,
+            LifecycleStage::Online
+        );
+        assert!(manager.pending_source_deletions().is_empty());
+        let alerts = sink.alerts.lock().unwrap().clone();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].level, AlertLevel::Critical);
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+}
@@ -1,13 +1,15 @@
 //! 存储空间监控
 
-use crate::storage::{StorageConfig, StorageManager, StorageStats, StorageType};
+use crate::lifecycle::LifecycleAlertSink;
+use crate::storage::StorageManager;
+use async_trait::async_trait;
 use chrono::{DateTime, Duration, Utc};
-use pacs_core::{PacsError, Result};
+use pacs_core::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tokio::time::{interval, sleep};
+use tokio::time::interval;
 use tracing::{debug, error, info, warn};
 
 /// 监控指标类型
@@ -157,7 +159,7 @@ pub struct StorageMonitor {
     /// 活跃告警
     active_alerts: Arc<RwLock<HashMap<String, Alert>>>,
     /// 告警历史
-    alert_history: Vec<Alert>,
+    alert_history: Arc<RwLock<Vec<Alert>>>,
 }
 
 impl StorageMonitor {
@@ -169,7 +171,7 @@ impl StorageMonitor {
             metrics_history: Arc::new(RwLock::new(Vec::new())),
             performance_metrics: Arc::new(RwLock::new(HashMap::new())),
             active_alerts: Arc::new(RwLock::new(HashMap::new())),
-            alert_history: Vec::new(),
+            alert_history: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
@@ -463,7 +465,7 @@ impl StorageMonitor {
     }
 
     /// 清理过期指标
-    async fn cleanup_expired_metrics(&mut self) -> Result<()> {
+    async fn cleanup_expired_metrics(&self) -> Result<()> {
         let cutoff_time = Utc::now() - Duration::hours(self.config.retention_hours as i64);
 
         let mut metrics_guard = self.metrics_history.write().await;
@@ -484,9 +486,10 @@ impl StorageMonitor {
             }
         }
 
+        let mut alert_history_guard = self.alert_history.write().await;
         for alert_id in alerts_to_remove {
             if let Some(alert) = active_alerts_guard.remove(&alert_id) {
-                self.alert_history.push(alert);
+                alert_history_guard.push(alert);
             }
         }
 
@@ -537,6 +540,11 @@ impl StorageMonitor {
         Ok(active_alerts_guard.values().cloned().collect())
     }
 
+    /// 获取已解决并移出活跃列表的告警
+    pub async fn get_alert_history(&self) -> Vec<Alert> {
+        self.alert_history.read().await.clone()
+    }
+
     /// 获取性能指标
     pub async fn get_performance_metrics(&self, storage_name: &str) -> Option<PerformanceMetrics> {
         let metrics_guard = self.performance_metrics.read().await;
@@ -581,3 +589,14 @@ impl StorageMonitor {
         }
     }
 }
+
+#[async_trait]
+impl LifecycleAlertSink for StorageMonitor {
+    async fn raise_alert(&self, alert: Alert) {
+        warn!("Alert triggered: {}", alert.message);
+        self.active_alerts
+            .write()
+            .await
+            .insert(alert.id.clone(), alert);
+    }
+}
@@ -0,0 +1,599 @@
+//! 透明召回
+//!
+//! 读取已迁移到近线/归档层的文件时，按生命周期状态和归档记录定位对象，
+//! 取回（归档对象带自描述头部时先解压校验）并暂存到在线存储。
+//! 快速层同步取回；慢速层在后台召回，期间调用方得到“召回中”。
+//! 暂存副本在保留期满后由定时任务清理，权威副本始终保留在原存储层；
+//! 配置状态存储时暂存记录随之持久化，重启后到期的副本仍会被清理。
+
+use crate::archive::{ArchiveManager, ArchivedObject};
+use crate::compression::{decode_archive_object, ArchiveObjectHeader};
+use crate::lifecycle::{LifecycleManager, LifecycleStage};
+use crate::scheduler::{JobDefinition, JobScheduler, ScheduledJob};
+use crate::state::{StateRecovery, StateStore, StateTransaction};
+use crate::storage::StorageManager;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use pacs_core::{ObjectAvailability, ObjectRecall, PacsError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, error, info, warn};
+
+/// 清理过期暂存副本的任务名称
+pub const RECALL_EVICTION_JOB: &str = "recall:evict";
+
+/// 清理过期暂存副本的默认计划：每15分钟
+pub const DEFAULT_RECALL_EVICTION_SCHEDULE: &str = "*/15 * * * *";
+
+/// 状态存储中暂存副本记录的集合名
+const STAGED_COLLECTION: &str = "recall_staged";
+
+/// 召回配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecallConfig {
+    /// 是否将召回的对象回迁到在线存储保留一段时间
+    pub restage: bool,
+    /// 回迁保留时长（小时）
+    pub restage_hours: u32,
+    /// 不回迁时暂存副本的保留时长（分钟），供收到“召回中”的调用方重试读取
+    pub pickup_minutes: u32,
+    /// 慢速层：在后台召回，调用方收到“召回中”
+    pub slow_tiers: Vec<LifecycleStage>,
+    /// 建议调用方重试的间隔（秒）
+    pub retry_after_secs: u64,
+}
+
+impl Default for RecallConfig {
+    fn default() -> Self {
+        Self {
+            restage: true,
+            restage_hours: 72,
+            pickup_minutes: 60,
+            slow_tiers: vec![LifecycleStage::Archive],
+            retry_after_secs: 30,
+        }
+    }
+}
+
+impl RecallConfig {
+    /// 暂存副本的保留时长
+    fn staging_period(&self) -> Duration {
+        if self.restage {
+            Duration::hours(i64::from(self.restage_hours))
+        } else {
+            Duration::minutes(i64::from(self.pickup_minutes))
+        }
+    }
+}
+
+/// 对象的召回来源
+#[derive(Clone)]
+enum RecallSource {
+    /// 生命周期管理的存储层（与在线存储使用相同的对象键）
+    Tier(LifecycleStage, StorageManager),
+    /// 归档管理器写入的归档对象
+    Archive(ArchivedObject),
+}
+
+impl RecallSource {
+    /// 来源所在的存储层
+    fn stage(&self) -> LifecycleStage {
+        match self {
+            RecallSource::Tier(stage, _) => stage.clone(),
+            RecallSource::Archive(_) => LifecycleStage::Archive,
+        }
+    }
+
+    /// 读取对象，归档对象解压并校验原始大小与哈希
+    async fn fetch(&self, key: &str) -> Result<Vec<u8>> {
+        let data = match self {
+            RecallSource::Tier(_, storage) => storage.get_file(key).await?,
+            RecallSource::Archive(archived) => {
+                StorageManager::new(archived.storage.clone())
+                    .await?
+                    .get_file(&archived.archive_path)
+                    .await?
+            }
+        };
+
+        if ArchiveObjectHeader::parse(&data)?.is_some() {
+            Ok(decode_archive_object(&data)?.1)
+        } else {
+            Ok(data)
+        }
+    }
+}
+
+/// 召回记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecallRecord {
+    /// 对象键
+    pub key: String,
+    /// 来源存储层
+    pub source_stage: LifecycleStage,
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    /// 失败原因（进行中为None）
+    pub error: Option<String>,
+}
+
+/// 暂存在在线存储中的召回副本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedObject {
+    /// 对象键
+    pub key: String,
+    /// 来源存储层
+    pub source_stage: LifecycleStage,
+    /// 暂存时间
+    pub staged_at: DateTime<Utc>,
+    /// 过期时间
+    pub expires_at: DateTime<Utc>,
+}
+
+/// 召回服务
+pub struct RecallService {
+    /// 在线存储
+    online: StorageManager,
+    /// 生命周期管理器
+    lifecycle: Option<Arc<Mutex<LifecycleManager>>>,
+    /// 归档管理器
+    archive: Option<Arc<Mutex<ArchiveManager>>>,
+    /// 召回配置
+    config: RecallConfig,
+    /// 进行中或失败的召回
+    recalls: Arc<RwLock<HashMap<String, RecallRecord>>>,
+    /// 暂存副本
+    staged: Arc<RwLock<HashMap<String, StagedObject>>>,
+    /// 持久化状态存储
+    state_store: Option<Arc<StateStore>>,
+}
+
+impl RecallService {
+    /// 创建召回服务，召回的对象暂存到 `online` 存储
+    pub fn new(online: StorageManager, config: RecallConfig) -> Self {
+        Self {
+            online,
+            lifecycle: None,
+            archive: None,
+            config,
+            recalls: Arc::new(RwLock::new(HashMap::new())),
+            staged: Arc::new(RwLock::new(HashMap::new())),
+            state_store: None,
+        }
+    }
+
+    /// 设置持久化状态存储，暂存副本记录随之写入
+    pub fn with_state_store(mut self, store: Arc<StateStore>) -> Self {
+        self.state_store = Some(store);
+        self
+    }
+
+    /// 从状态存储恢复暂存副本记录
+    pub async fn load_state(&self) -> Result<StateRecovery> {
+        let Some(store) = &self.state_store else {
+            return Ok(StateRecovery::default());
+        };
+
+        let staged: Vec<(String, StagedObject)> = store.load(STAGED_COLLECTION).await?;
+        let recovery = StateRecovery {
+            loaded: staged.len(),
+            ..Default::default()
+        };
+        self.staged.write().await.extend(staged);
+
+        info!("Loaded recall state: {} staged objects", recovery.loaded);
+        Ok(recovery)
+    }
+
+    /// 设置生命周期管理器，按文件的生命周期阶段定位近线/归档层
+    pub fn with_lifecycle_manager(mut self, lifecycle: Arc<Mutex<LifecycleManager>>) -> Self {
+        self.lifecycle = Some(lifecycle);
+        self
+    }
+
+    /// 设置归档管理器，按归档记录定位归档对象
+    pub fn with_archive_manager(mut self, archive: Arc<Mutex<ArchiveManager>>) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// 召回配置
+    pub fn config(&self) -> &RecallConfig {
+        &self.config
+    }
+
+    /// 将在线存储下的本地路径转换为对象键
+    pub fn storage_key(&self, path: &Path) -> String {
+        self.online.config().object_key(&path.to_string_lossy())
+    }
+
+    /// 确保对象键对应的对象在在线存储中可读
+    pub async fn recall(&self, key: &str) -> Result<ObjectAvailability> {
+        if self.online.file_exists(key).await? {
+            self.record_access(key).await;
+            return Ok(ObjectAvailability::Online);
+        }
+
+        if let Some(record) = self.recalls.read().await.get(key).cloned() {
+            match record.error {
+                None => return Ok(self.in_progress(&record)),
+                Some(error) => {
+                    // 报告一次失败后清除记录，下次请求重新召回
+                    self.recalls.write().await.remove(key);
+                    return Err(PacsError::Storage(format!(
+                        "Recall of {} from {:?} failed: {}",
+                        key, record.source_stage, error
+                    )));
+                }
+            }
+        }
+
+        let Some(source) = self.locate(key).await else {
+            return Ok(ObjectAvailability::NotFound);
+        };
+        let stage = source.stage();
+
+        if !self.config.slow_tiers.contains(&stage) {
+            debug!("Recalling {} from {:?}", key, stage);
+            let data = source.fetch(key).await?;
+            stage_object(
+                &self.online,
+                &self.staged,
+                self.state_store.as_deref(),
+                &self.config,
+                key,
+                &stage,
+                &data,
+            )
+            .await?;
+            self.record_access(key).await;
+            return Ok(ObjectAvailability::Online);
+        }
+
+        let record = {
+            let mut recalls = self.recalls.write().await;
+            if let Some(existing) = recalls.get(key) {
+                return Ok(self.in_progress(existing));
+            }
+            let record = RecallRecord {
+                key: key.to_string(),
+                source_stage: stage.clone(),
+                started_at: Utc::now(),
+                error: None,
+            };
+            recalls.insert(key.to_string(), record.clone());
+            record
+        };
+
+        info!("Started recall of {} from {:?}", key, stage);
+        self.spawn_recall(key.to_string(), source);
+        self.record_access(key).await;
+        Ok(self.in_progress(&record))
+    }
+
+    /// 进行中和失败的召回
+    pub async fn active_recalls(&self) -> Vec<RecallRecord> {
+        let mut recalls: Vec<RecallRecord> = self.recalls.read().await.values().cloned().collect();
+        recalls.sort_by_key(|r| r.started_at);
+        recalls
+    }
+
+    /// 当前暂存的召回副本
+    pub async fn staged_objects(&self) -> Vec<StagedObject> {
+        let mut staged: Vec<StagedObject> = self.staged.read().await.values().cloned().collect();
+        staged.sort_by_key(|s| s.expires_at);
+        staged
+    }
+
+    /// 删除过期的暂存副本，返回删除数量
+    ///
+    /// 只有在原存储层仍能定位到对象时才删除在线副本，避免删掉唯一的副本
+    pub async fn evict_expired(&self) -> Result<usize> {
+        let now = Utc::now();
+        let expired: Vec<StagedObject> = self
+            .staged
+            .read()
+            .await
+            .values()
+            .filter(|staged| staged.expires_at <= now)
+            .cloned()
+            .collect();
+
+        let mut evicted = 0;
+        for staged in expired {
+            if self.locate(&staged.key).await.is_none() {
+                warn!(
+                    "Keeping staged copy of {}: no copy found in {:?}",
+                    staged.key, staged.source_stage
+                );
+                self.forget_staged(&staged.key).await?;
+                continue;
+            }
+
+            match self.online.delete_file(&staged.key).await {
+                Ok(()) => {
+                    self.forget_staged(&staged.key).await?;
+                    evicted += 1;
+                }
+                Err(e) => error!("Failed to evict staged copy of {}: {}", staged.key, e),
+            }
+        }
+
+        if evicted > 0 {
+            info!("Evicted {} expired recalled objects", evicted);
+        }
+        Ok(evicted)
+    }
+
+    /// 注册清理过期暂存副本的定时任务
+    pub async fn schedule_jobs(service: Arc<Self>, scheduler: &JobScheduler) -> Result<()> {
+        scheduler
+            .register(
+                JobDefinition::new(RECALL_EVICTION_JOB, DEFAULT_RECALL_EVICTION_SCHEDULE),
+                Arc::new(RecallEvictionJob { service }),
+            )
+            .await
+    }
+
+    /// 移除暂存副本记录
+    async fn forget_staged(&self, key: &str) -> Result<()> {
+        if let Some(store) = &self.state_store {
+            let mut txn = StateTransaction::new();
+            txn.delete(STAGED_COLLECTION, key);
+            store.commit(txn).await?;
+        }
+        self.staged.write().await.remove(key);
+        Ok(())
+    }
+
+    /// 定位不在在线存储中的对象
+    async fn locate(&self, key: &str) -> Option<RecallSource> {
+        if let Some(lifecycle) = &self.lifecycle {
+            let lifecycle = lifecycle.lock().await;
+            if let Some(status) = lifecycle.get_file_status(key) {
+                if status.current_stage != LifecycleStage::Online {
+                    if let Some(storage) = lifecycle.stage_storage(&status.current_stage) {
+                        return Some(RecallSource::Tier(status.current_stage.clone(), storage));
+                    }
+                }
+            }
+        }
+
+        if let Some(archive) = &self.archive {
+            if let Some(archived) = archive.lock().await.archived_location(key) {
+                return Some(RecallSource::Archive(archived));
+            }
+        }
+
+        None
+    }
+
+    /// 在后台从慢速层召回对象
+    fn spawn_recall(&self, key: String, source: RecallSource) {
+        let online = self.online.clone();
+        let staged = self.staged.clone();
+        let state_store = self.state_store.clone();
+        let recalls = self.recalls.clone();
+        let config = self.config.clone();
+
+        tokio::spawn(async move {
+            let stage = source.stage();
+            let result = match source.fetch(&key).await {
+                Ok(data) => {
+                    stage_object(
+                        &online,
+                        &staged,
+                        state_store.as_deref(),
+                        &config,
+                        &key,
+                        &stage,
+                        &data,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+
+            let mut recalls = recalls.write().await;
+            match result {
+                Ok(()) => {
+                    info!("Recall of {} from {:?} completed", key, stage);
+                    recalls.remove(&key);
+                }
+                Err(e) => {
+                    error!("Recall of {} from {:?} failed: {}", key, stage, e);
+                    if let Some(record) = recalls.get_mut(&key) {
+                        record.error = Some(e.to_string());
+                    }
+                }
+            }
+        });
+    }
+
+    /// 记录访问，供生命周期和归档条件统计
+    async fn record_access(&self, key: &str) {
+        if let Some(lifecycle) = &self.lifecycle {
+            if let Err(e) = lifecycle.lock().await.record_access(key).await {
+                debug!("Failed to record access for {}: {}", key, e);
+            }
+        }
+    }
+
+    /// 召回进行中的可用性
+    fn in_progress(&self, record: &RecallRecord) -> ObjectAvailability {
+        ObjectAvailability::RecallInProgress {
+            started_at: record.started_at,
+            retry_after_secs: self.config.retry_after_secs,
+        }
+    }
+}
+
+#[async_trait]
+impl ObjectRecall for RecallService {
+    async fn ensure_available(&self, path: &Path) -> Result<ObjectAvailability> {
+        self.recall(&self.storage_key(path)).await
+    }
+}
+
+/// 将召回的数据写入在线存储并登记暂存副本
+async fn stage_object(
+    online: &StorageManager,
+    staged: &RwLock<HashMap<String, StagedObject>>,
+    state_store: Option<&StateStore>,
+    config: &RecallConfig,
+    key: &str,
+    source_stage: &LifecycleStage,
+    data: &[u8],
+) -> Result<()> {
+    online.store_file(data, key).await?;
+
+    let now = Utc::now();
+    let object = StagedObject {
+        key: key.to_string(),
+        source_stage: source_stage.clone(),
+        staged_at: now,
+        expires_at: now + config.staging_period(),
+    };
+    // 记录写入失败时在线副本仍可读，但不会被定时任务清理
+    if let Some(store) = state_store {
+        let mut txn = StateTransaction::new();
+        txn.put(STAGED_COLLECTION, key, &object)?;
+        store.commit(txn).await?;
+    }
+    staged.write().await.insert(key.to_string(), object);
+    Ok(())
+}
+
+/// 清理过期暂存副本的定时任务
+pub struct RecallEvictionJob {
+    /// 召回服务
+    service: Arc<RecallService>,
+}
+
+#[async_trait]
+impl ScheduledJob for RecallEvictionJob {
+    async fn run(&self) -> Result<String> {
+        let evicted = self.service.evict_expired().await?;
+        Ok(format!("{} staged objects evicted", evicted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{CompressionAlgorithm, CompressionSettings};
+    use crate::storage::{StorageConfig, StorageType};
+
+    fn local_config(root: &Path) -> StorageConfig {
+        StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(root.to_string_lossy().to_string()),
+            object_store_config: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recall_from_archive_and_nearline() {
+        let root = std::env::temp_dir().join(format!(
+            "pacs-recall-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let online = StorageManager::new(local_config(&root.join("online")))
+            .await
+            .unwrap();
+        let nearline = StorageManager::new(local_config(&root.join("nearline")))
+            .await
+            .unwrap();
+
+        // 近线层：与在线存储使用相同的键
+        let mut lifecycle = LifecycleManager::new();
+        lifecycle.add_storage_manager(LifecycleStage::Nearline, nearline.clone());
+        lifecycle
+            .register_file("1.2/1.2.3/near.dcm", None)
+            .await
+            .unwrap();
+        lifecycle.mark_stage("1.2/1.2.3/near.dcm", LifecycleStage::Nearline);
+        nearline
+            .store_file(b"nearline bytes", "1.2/1.2.3/near.dcm")
+            .await
+            .unwrap();
+
+        // 归档层：通过归档管理器压缩归档
+        let mut archive = ArchiveManager::new();
+        archive.add_storage_manager("online".to_string(), online.clone());
+        let mut policy = ArchiveManager::create_default_policy(local_config(&root.join("archive")));
+        policy.compression_settings = Some(CompressionSettings::new(CompressionAlgorithm::Zstd, 3));
+        let policy_name = policy.name.clone();
+        archive.add_policy(policy);
+        let archived: Vec<u8> = b"archived dicom ".repeat(100);
+        online
+            .store_file(&archived, "1.2/1.2.3/old.dcm")
+            .await
+            .unwrap();
+        archive
+            .archive_file("1.2/1.2.3/old.dcm", &policy_name)
+            .await
+            .unwrap();
+        assert!(!online.file_exists("1.2/1.2.3/old.dcm").await.unwrap());
+
+        let state = Arc::new(StateStore::open(root.join("state")).await.unwrap());
+        let service = RecallService::new(online.clone(), RecallConfig::default())
+            .with_lifecycle_manager(Arc::new(Mutex::new(lifecycle)))
+            .with_archive_manager(Arc::new(Mutex::new(archive)))
+            .with_state_store(state.clone());
+
+        // 快速层同步取回
+        let near_path = root.join("online").join("1.2/1.2.3/near.dcm");
+        assert_eq!(
+            service.ensure_available(&near_path).await.unwrap(),
+            ObjectAvailability::Online
+        );
+        assert_eq!(
+            online.get_file("1.2/1.2.3/near.dcm").await.unwrap(),
+            b"nearline bytes"
+        );
+
+        // 慢速层先报告召回中，完成后可读
+        let availability = service.recall("1.2/1.2.3/old.dcm").await.unwrap();
+        assert!(matches!(
+            availability,
+            ObjectAvailability::RecallInProgress { .. }
+        ));
+        for _ in 0..100 {
+            if service.active_recalls().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            service.recall("1.2/1.2.3/old.dcm").await.unwrap(),
+            ObjectAvailability::Online
+        );
+        assert_eq!(
+            online.get_file("1.2/1.2.3/old.dcm").await.unwrap(),
+            archived
+        );
+        assert_eq!(
+            service.recall("1.2/1.2.3/missing.dcm").await.unwrap(),
+            ObjectAvailability::NotFound
+        );
+        assert_eq!(service.staged_objects().await.len(), 2);
+
+        // 暂存记录重启后恢复
+        let restarted =
+            RecallService::new(online.clone(), RecallConfig::default()).with_state_store(state);
+        assert_eq!(restarted.load_state().await.unwrap().loaded, 2);
+        let keys = |staged: Vec<StagedObject>| -> Vec<String> {
+            staged.into_iter().map(|s| s.key).collect()
+        };
+        assert_eq!(
+            keys(restarted.staged_objects().await),
+            keys(service.staged_objects().await)
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
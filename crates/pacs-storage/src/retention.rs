@@ -0,0 +1,447 @@
+//! 检查级保留策略
+//!
+//! 按检查的DICOM元数据（模态、检查时患者年龄、检查日期、检查部位、自定义标签）
+//! 决定整个检查的阶段转换时间，例如"乳腺摄影保留10年、儿科保留到21岁后N年、其余保留7年"。
+//! 多条规则同时匹配一个检查时，每个阶段取各规则中最晚的时间，即保留期取最长；
+//! 任一匹配规则因缺少元数据（如出生日期）无法计算某阶段的时间时，该阶段不执行。
+//! 处于法律保留（legal hold）的检查不会被删除。
+
+use crate::lifecycle::LifecycleStage;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use pacs_core::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 默认保留年数
+pub const DEFAULT_RETENTION_YEARS: u32 = 7;
+
+/// 乳腺摄影默认保留年数
+pub const MAMMOGRAPHY_RETENTION_YEARS: u32 = 10;
+
+/// 儿科检查默认保留到的患者年龄
+pub const PEDIATRIC_RETENTION_AGE: u32 = 21;
+
+/// 检查的生命周期元数据（通常来自数据库索引）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StudyMetadata {
+    /// 检查实例UID
+    pub study_uid: String,
+    /// 检查日期
+    pub study_date: Option<NaiveDate>,
+    /// 患者出生日期
+    pub patient_birth_date: Option<NaiveDate>,
+    /// 检查包含的模态
+    pub modalities: Vec<String>,
+    /// 检查部位
+    pub body_parts: Vec<String>,
+    /// 自定义标签
+    pub tags: HashMap<String, String>,
+    /// 检查的文件（存储键）
+    pub file_paths: Vec<String>,
+}
+
+impl StudyMetadata {
+    /// 检查时患者的周岁年龄
+    pub fn patient_age_at_study(&self) -> Option<u32> {
+        let birth = self.patient_birth_date?;
+        let study = self.study_date?;
+        let mut age = study.year() - birth.year();
+        if (study.month(), study.day()) < (birth.month(), birth.day()) {
+            age -= 1;
+        }
+        u32::try_from(age).ok()
+    }
+}
+
+/// 检查元数据来源
+#[async_trait]
+pub trait StudyMetadataSource: Send + Sync {
+    /// 获取全部检查的生命周期元数据
+    async fn studies(&self) -> Result<Vec<StudyMetadata>>;
+}
+
+/// 检查过滤器（设置的条件须全部满足，列表条件匹配其中任一项）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StudyFilter {
+    /// 模态（不区分大小写）
+    #[serde(default)]
+    pub modalities: Vec<String>,
+    /// 检查时患者最小年龄（含）
+    pub min_patient_age: Option<u32>,
+    /// 检查时患者最大年龄（含）
+    pub max_patient_age: Option<u32>,
+    /// 检查日期下限（含）
+    pub study_date_from: Option<NaiveDate>,
+    /// 检查日期上限（含）
+    pub study_date_to: Option<NaiveDate>,
+    /// 检查部位（不区分大小写）
+    #[serde(default)]
+    pub body_parts: Vec<String>,
+    /// 自定义标签（须全部相等）
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+impl StudyFilter {
+    /// 检查是否匹配，缺少条件所需的元数据时视为不匹配
+    pub fn matches(&self, study: &StudyMetadata) -> bool {
+        let any_of = |wanted: &[String], values: &[String]| {
+            wanted.is_empty()
+                || wanted
+                    .iter()
+                    .any(|w| values.iter().any(|v| v.eq_ignore_ascii_case(w)))
+        };
+        if !any_of(&self.modalities, &study.modalities)
+            || !any_of(&self.body_parts, &study.body_parts)
+        {
+            return false;
+        }
+
+        if self.min_patient_age.is_some() || self.max_patient_age.is_some() {
+            let Some(age) = study.patient_age_at_study() else {
+                return false;
+            };
+            if self.min_patient_age.is_some_and(|min| age < min)
+                || self.max_patient_age.is_some_and(|max| age > max)
+            {
+                return false;
+            }
+        }
+
+        if self.study_date_from.is_some() || self.study_date_to.is_some() {
+            let Some(date) = study.study_date else {
+                return false;
+            };
+            if self.study_date_from.is_some_and(|from| date < from)
+                || self.study_date_to.is_some_and(|to| date > to)
+            {
+                return false;
+            }
+        }
+
+        self.tags
+            .iter()
+            .all(|(name, value)| study.tags.get(name) == Some(value))
+    }
+}
+
+/// 阶段转换时间的计算基准
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StudyAge {
+    /// 检查日期后的天数
+    DaysAfterStudy(u32),
+    /// 检查日期后的年数
+    YearsAfterStudy(u32),
+    /// 患者达到指定年龄后再过若干年
+    PatientAge {
+        /// 患者年龄
+        age: u32,
+        /// 之后的附加年数
+        additional_years: u32,
+    },
+}
+
+impl StudyAge {
+    /// 计算到期时间，缺少所需元数据时返回None
+    pub fn due_at(&self, study: &StudyMetadata) -> Option<DateTime<Utc>> {
+        let date = match self {
+            StudyAge::DaysAfterStudy(days) => study
+                .study_date?
+                .checked_add_days(chrono::Days::new(u64::from(*days)))?,
+            StudyAge::YearsAfterStudy(years) => study
+                .study_date?
+                .checked_add_months(Months::new(years.checked_mul(12)?))?,
+            StudyAge::PatientAge {
+                age,
+                additional_years,
+            } => study
+                .patient_birth_date?
+                .checked_add_months(Months::new((age + additional_years).checked_mul(12)?))?,
+        };
+        Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+    }
+}
+
+/// 检查级阶段转换
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StudyTransition {
+    /// 目标阶段
+    pub stage: LifecycleStage,
+    /// 转换时间
+    pub after: StudyAge,
+}
+
+/// 检查级生命周期规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StudyLifecycleRule {
+    /// 规则ID
+    pub id: String,
+    /// 规则名称
+    pub name: String,
+    /// 检查过滤器
+    pub filter: StudyFilter,
+    /// 阶段转换
+    pub transitions: Vec<StudyTransition>,
+    /// 是否启用
+    pub enabled: bool,
+}
+
+/// 检查某一阶段的计划转换
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledStage {
+    /// 目标阶段
+    pub stage: LifecycleStage,
+    /// 到期时间
+    pub due_at: DateTime<Utc>,
+    /// 决定该时间的规则ID（时间最晚的规则）
+    pub rule_id: String,
+}
+
+/// 法律保留
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegalHold {
+    /// 检查实例UID
+    pub study_uid: String,
+    /// 保留原因（如诉讼案号）
+    pub reason: String,
+    /// 设置人
+    pub placed_by: String,
+    /// 设置时间
+    pub placed_at: DateTime<Utc>,
+}
+
+/// 待审阅的即将删除检查
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpcomingDeletion {
+    /// 检查实例UID
+    pub study_uid: String,
+    /// 保留期到期时间
+    pub due_at: DateTime<Utc>,
+    /// 最早执行删除的时间（到期且公示期满）
+    pub executes_at: DateTime<Utc>,
+    /// 决定保留期的规则ID
+    pub rule_id: String,
+    /// 文件数
+    pub file_count: usize,
+    /// 法律保留（存在时不会删除）
+    pub legal_hold: Option<LegalHold>,
+}
+
+/// 一次检查级策略评估的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StudyLifecycleRun {
+    /// 评估的检查数
+    pub studies_evaluated: usize,
+    /// 转换到其他层级的文件数
+    pub files_transitioned: usize,
+    /// 标记为待删除的文件数
+    pub files_marked_for_deletion: usize,
+    /// 因法律保留未删除的检查数
+    pub studies_on_hold: usize,
+    /// 已到期但公示期未满的检查数
+    pub studies_awaiting_notice: usize,
+}
+
+/// 阶段的先后顺序
+pub(crate) fn stage_rank(stage: &LifecycleStage) -> u8 {
+    match stage {
+        LifecycleStage::Online => 0,
+        LifecycleStage::Nearline => 1,
+        LifecycleStage::Archive => 2,
+        LifecycleStage::PendingDeletion => 3,
+    }
+}
+
+/// 计算检查各阶段的转换时间，按阶段先后排列
+pub fn study_schedule(rules: &[StudyLifecycleRule], study: &StudyMetadata) -> Vec<ScheduledStage> {
+    let mut stages: HashMap<u8, Option<ScheduledStage>> = HashMap::new();
+    for rule in rules
+        .iter()
+        .filter(|rule| rule.enabled && rule.filter.matches(study))
+    {
+        for transition in &rule.transitions {
+            let due_at = transition.after.due_at(study);
+            let entry = stages
+                .entry(stage_rank(&transition.stage))
+                .or_insert_with(|| {
+                    due_at.map(|due_at| ScheduledStage {
+                        stage: transition.stage.clone(),
+                        due_at,
+                        rule_id: rule.id.clone(),
+                    })
+                });
+            match (entry.as_mut(), due_at) {
+                // 无法计算的规则使该阶段不执行
+                (_, None) => *entry = None,
+                (Some(scheduled), Some(due_at)) if due_at > scheduled.due_at => {
+                    scheduled.due_at = due_at;
+                    scheduled.rule_id = rule.id.clone();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut schedule: Vec<ScheduledStage> = stages.into_values().flatten().collect();
+    schedule.sort_by_key(|s| stage_rank(&s.stage));
+    schedule
+}
+
+/// 默认的检查级规则：一年后归档；乳腺摄影保留10年、
+/// 儿科检查保留到患者21岁、其余检查保留7年（取最长）
+pub fn default_study_rules() -> Vec<StudyLifecycleRule> {
+    let deletion = |after| StudyTransition {
+        stage: LifecycleStage::PendingDeletion,
+        after,
+    };
+    vec![
+        StudyLifecycleRule {
+            id: "study_default".to_string(),
+            name: "Archive after 1 year, delete after 7 years".to_string(),
+            filter: StudyFilter::default(),
+            transitions: vec![
+                StudyTransition {
+                    stage: LifecycleStage::Archive,
+                    after: StudyAge::YearsAfterStudy(1),
+                },
+                deletion(StudyAge::YearsAfterStudy(DEFAULT_RETENTION_YEARS)),
+            ],
+            enabled: true,
+        },
+        StudyLifecycleRule {
+            id: "study_mammography".to_string(),
+            name: "Keep mammography 10 years".to_string(),
+            filter: StudyFilter {
+                modalities: vec!["MG".to_string()],
+                ..Default::default()
+            },
+            transitions: vec![deletion(StudyAge::YearsAfterStudy(
+                MAMMOGRAPHY_RETENTION_YEARS,
+            ))],
+            enabled: true,
+        },
+        StudyLifecycleRule {
+            id: "study_pediatric".to_string(),
+            name: "Keep pediatric studies until age 21".to_string(),
+            filter: StudyFilter {
+                max_patient_age: Some(17),
+                ..Default::default()
+            },
+            transitions: vec![deletion(StudyAge::PatientAge {
+                age: PEDIATRIC_RETENTION_AGE,
+                additional_years: 0,
+            })],
+            enabled: true,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn study(modality: &str, birth: &str, date: &str) -> StudyMetadata {
+        StudyMetadata {
+            study_uid: format!("1.2.{}", modality),
+            study_date: NaiveDate::parse_from_str(date, "%Y%m%d").ok(),
+            patient_birth_date: NaiveDate::parse_from_str(birth, "%Y%m%d").ok(),
+            modalities: vec![modality.to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn deletion_year(rules: &[StudyLifecycleRule], study: &StudyMetadata) -> Option<i32> {
+        study_schedule(rules, study)
+            .into_iter()
+            .find(|s| s.stage == LifecycleStage::PendingDeletion)
+            .map(|s| s.due_at.year())
+    }
+
+    #[test]
+    fn test_study_retention_takes_longest_matching_rule() {
+        let rules = default_study_rules();
+
+        let adult_ct = study("CT", "19700101", "20200315");
+        assert_eq!(adult_ct.patient_age_at_study(), Some(50));
+        assert_eq!(deletion_year(&rules, &adult_ct), Some(2027));
+
+        let mammography = study("MG", "19700101", "20200315");
+        assert_eq!(deletion_year(&rules, &mammography), Some(2030));
+
+        // 9岁时检查：保留到21岁（2031年）长于7年
+        let pediatric = study("DX", "20100601", "20200315");
+        assert_eq!(pediatric.patient_age_at_study(), Some(9));
+        assert_eq!(deletion_year(&rules, &pediatric), Some(2031));
+
+        // 17岁时检查：7年（2027年）长于到21岁（2024年）
+        let teenager = study("DX", "20030101", "20200315");
+        assert_eq!(deletion_year(&rules, &teenager), Some(2027));
+
+        // 缺少出生日期时年龄条件不匹配，按默认规则
+        let unknown_age = study("DX", "", "20200315");
+        assert_eq!(deletion_year(&rules, &unknown_age), Some(2027));
+
+        // 缺少检查日期时无法计算保留期，不删除
+        let undated = study("CT", "19700101", "");
+        assert_eq!(deletion_year(&rules, &undated), None);
+    }
+
+    #[tokio::test]
+    async fn test_study_deletion_waits_for_notice_and_legal_hold() {
+        use crate::lifecycle::LifecycleManager;
+
+        let mut manager = LifecycleManager::new();
+        manager.add_study_rule(StudyLifecycleRule {
+            id: "short".to_string(),
+            name: "Short retention".to_string(),
+            filter: StudyFilter::default(),
+            transitions: vec![StudyTransition {
+                stage: LifecycleStage::PendingDeletion,
+                after: StudyAge::DaysAfterStudy(1),
+            }],
+            enabled: true,
+        });
+        let mut expired = study("CT", "19700101", "20200315");
+        expired.file_paths = vec!["a.dcm".to_string()];
+        let studies = vec![expired.clone()];
+
+        // 首次进入删除窗口：只记录公示，报告中按公示期推迟执行
+        let run = manager.apply_study_policies(&studies).await.unwrap();
+        assert_eq!(run.studies_awaiting_notice, 1);
+        assert_eq!(run.files_marked_for_deletion, 0);
+        let report = manager.upcoming_deletions_for(&studies, 0);
+        assert_eq!(report.len(), 1);
+        assert!(report[0].executes_at > Utc::now());
+
+        // 公示期已满但处于法律保留：不删除
+        manager.set_deletion_notice_days(0);
+        manager
+            .place_legal_hold(LegalHold {
+                study_uid: expired.study_uid.clone(),
+                reason: "litigation".to_string(),
+                placed_by: "admin".to_string(),
+                placed_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        let run = manager.apply_study_policies(&studies).await.unwrap();
+        assert_eq!(run.studies_on_hold, 1);
+        assert_eq!(
+            manager.get_file_status("a.dcm").unwrap().current_stage,
+            LifecycleStage::Online
+        );
+
+        // 解除保留后标记为待删除
+        manager
+            .release_legal_hold(&expired.study_uid)
+            .await
+            .unwrap();
+        let run = manager.apply_study_policies(&studies).await.unwrap();
+        assert_eq!(run.files_marked_for_deletion, 1);
+        assert_eq!(
+            manager.get_file_status("a.dcm").unwrap().current_stage,
+            LifecycleStage::PendingDeletion
+        );
+    }
+}
@@ -0,0 +1,653 @@
+//! 定时任务调度
+//!
+//! 为备份、归档和生命周期等后台任务提供统一调度：按标准cron表达式和时区计算运行时间，
+//! 持久化上次/下次运行时间，同一任务不会重叠运行，可限制任务只在维护窗口内启动，
+//! 并支持管理员手动立即运行。
+
+use crate::storage::StorageManager;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use croner::Cron;
+use pacs_core::{PacsError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
+
+/// 默认时区
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+/// 调度器状态的默认存储路径
+pub const DEFAULT_SCHEDULER_STATE_KEY: &str = "scheduler/jobs.json";
+
+/// 调度循环的最长休眠时间（秒）
+const MAX_IDLE_SECONDS: i64 = 60;
+
+/// 在维护窗口内查找下次运行时间时最多尝试的次数
+const MAX_WINDOW_SEARCH: usize = 1000;
+
+/// 维护窗口（任务所在时区的本地时间）
+///
+/// 结束时间早于开始时间表示跨越午夜，如 22:00-04:00；开始与结束相同表示全天
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    /// 开始时间
+    pub start: NaiveTime,
+    /// 结束时间（不含）
+    pub end: NaiveTime,
+}
+
+impl MaintenanceWindow {
+    /// 创建维护窗口
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    /// 解析 `HH:MM-HH:MM` 格式的维护窗口
+    pub fn parse(value: &str) -> Result<Self> {
+        let (start, end) = value.split_once('-').ok_or_else(|| {
+            PacsError::Config(format!(
+                "Maintenance window must be formatted as HH:MM-HH:MM: {}",
+                value
+            ))
+        })?;
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|e| {
+                PacsError::Config(format!("Invalid maintenance window time {}: {}", time, e))
+            })
+        };
+        Ok(Self::new(parse_time(start)?, parse_time(end)?))
+    }
+
+    /// 指定本地时间是否在窗口内
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start == self.end {
+            true
+        } else if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// 指定时间之后下一次窗口开始的时间
+    fn next_start<T: TimeZone>(&self, after: &DateTime<T>) -> Option<DateTime<T>> {
+        let mut date = after.date_naive();
+        if date.and_time(self.start) <= after.naive_local() {
+            date = date.succ_opt()?;
+        }
+
+        // 窗口开始时间落在夏令时跳变的空档时，顺延一小时
+        let start = date.and_time(self.start);
+        let timezone = after.timezone();
+        timezone.from_local_datetime(&start).earliest().or_else(|| {
+            timezone
+                .from_local_datetime(&(start + Duration::hours(1)))
+                .earliest()
+        })
+    }
+}
+
+/// 任务定义
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobDefinition {
+    /// 任务名称（唯一）
+    pub name: String,
+    /// 标准cron表达式（分 时 日 月 周）
+    pub schedule: String,
+    /// IANA时区名称
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// 维护窗口，任务只在窗口内启动
+    #[serde(default)]
+    pub maintenance_window: Option<MaintenanceWindow>,
+}
+
+impl JobDefinition {
+    /// 创建UTC时区、无维护窗口的任务定义
+    pub fn new(name: &str, schedule: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            schedule: schedule.to_string(),
+            timezone: default_timezone(),
+            maintenance_window: None,
+        }
+    }
+
+    /// 设置时区
+    pub fn with_timezone(mut self, timezone: &str) -> Self {
+        self.timezone = timezone.to_string();
+        self
+    }
+
+    /// 设置维护窗口
+    pub fn with_maintenance_window(mut self, window: MaintenanceWindow) -> Self {
+        self.maintenance_window = Some(window);
+        self
+    }
+}
+
+/// 默认时区
+fn default_timezone() -> String {
+    DEFAULT_TIMEZONE.to_string()
+}
+
+/// 解析后的任务计划
+#[derive(Debug, Clone)]
+pub struct JobSchedule {
+    /// cron表达式
+    cron: Cron,
+    /// 时区
+    timezone: Tz,
+    /// 维护窗口
+    maintenance_window: Option<MaintenanceWindow>,
+}
+
+impl JobSchedule {
+    /// 从任务定义解析计划
+    pub fn from_definition(definition: &JobDefinition) -> Result<Self> {
+        let cron = Cron::new(&definition.schedule).parse().map_err(|e| {
+            PacsError::Config(format!(
+                "Invalid cron expression {:?} for job {}: {}",
+                definition.schedule, definition.name, e
+            ))
+        })?;
+        let timezone: Tz = definition.timezone.parse().map_err(|_| {
+            PacsError::Config(format!(
+                "Unknown timezone {} for job {}",
+                definition.timezone, definition.name
+            ))
+        })?;
+
+        Ok(Self {
+            cron,
+            timezone,
+            maintenance_window: definition.maintenance_window,
+        })
+    }
+
+    /// 指定时间之后的下一次运行时间（跳过维护窗口之外的触发点）
+    pub fn next_run_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut cursor = after.with_timezone(&self.timezone);
+        for _ in 0..MAX_WINDOW_SEARCH {
+            let next = self.cron.find_next_occurrence(&cursor, false).ok()?;
+            match &self.maintenance_window {
+                Some(window) if !window.contains(next.time()) => {
+                    // 直接跳到下一个窗口开始前，避免逐个检查窗口外的触发点
+                    cursor = window.next_start(&next)? - Duration::seconds(1);
+                }
+                _ => return Some(next.with_timezone(&Utc)),
+            }
+        }
+        None
+    }
+
+    /// 指定时间是否在维护窗口内
+    pub fn in_window(&self, at: DateTime<Utc>) -> bool {
+        self.maintenance_window
+            .is_none_or(|window| window.contains(at.with_timezone(&self.timezone).time()))
+    }
+}
+
+/// 任务运行结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JobRunStatus {
+    /// 成功
+    Succeeded,
+    /// 失败
+    Failed,
+    /// 进程在任务运行中退出
+    Interrupted,
+}
+
+/// 任务触发方式
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JobTrigger {
+    /// 按计划触发
+    Scheduled,
+    /// 手动触发
+    Manual,
+}
+
+/// 任务状态（持久化）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobState {
+    /// 任务定义
+    pub definition: JobDefinition,
+    /// 是否正在运行
+    pub running: bool,
+    /// 最近一次触发方式
+    pub last_trigger: Option<JobTrigger>,
+    /// 最近一次开始时间
+    pub last_started_at: Option<DateTime<Utc>>,
+    /// 最近一次结束时间
+    pub last_finished_at: Option<DateTime<Utc>>,
+    /// 最近一次运行结果
+    pub last_status: Option<JobRunStatus>,
+    /// 最近一次运行的摘要或错误信息
+    pub last_message: Option<String>,
+    /// 下次计划运行时间
+    pub next_run_at: Option<DateTime<Utc>>,
+    /// 累计运行次数
+    pub run_count: u64,
+    /// 累计失败次数
+    pub failure_count: u64,
+}
+
+impl JobState {
+    /// 创建新任务的初始状态
+    fn new(definition: JobDefinition, next_run_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            definition,
+            running: false,
+            last_trigger: None,
+            last_started_at: None,
+            last_finished_at: None,
+            last_status: None,
+            last_message: None,
+            next_run_at,
+            run_count: 0,
+            failure_count: 0,
+        }
+    }
+}
+
+/// 可调度的任务
+#[async_trait]
+pub trait ScheduledJob: Send + Sync {
+    /// 执行一次任务，返回结果摘要
+    async fn run(&self) -> Result<String>;
+}
+
+/// 已注册的任务
+struct RegisteredJob {
+    /// 解析后的计划
+    schedule: JobSchedule,
+    /// 任务实现
+    job: Arc<dyn ScheduledJob>,
+    /// 任务状态
+    state: JobState,
+}
+
+/// 任务调度器
+///
+/// 配置状态存储后，每次任务开始和结束都会写入状态文件；进程重启后先调用
+/// [`JobScheduler::load_state`] 再注册任务，即可恢复上次/下次运行时间，
+/// 停机期间错过的运行会在恢复后（维护窗口内）补跑一次
+pub struct JobScheduler {
+    /// 已注册的任务
+    jobs: Mutex<HashMap<String, RegisteredJob>>,
+    /// 从状态存储加载、尚未注册的任务状态
+    restored: Mutex<HashMap<String, JobState>>,
+    /// 状态存储及路径
+    state_storage: Option<(Arc<StorageManager>, String)>,
+}
+
+impl JobScheduler {
+    /// 创建不持久化状态的调度器
+    pub fn new() -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            restored: Mutex::new(HashMap::new()),
+            state_storage: None,
+        }
+    }
+
+    /// 设置状态存储
+    pub fn with_state_storage(mut self, storage: Arc<StorageManager>, key: &str) -> Self {
+        self.state_storage = Some((storage, key.to_string()));
+        self
+    }
+
+    /// 从状态存储加载任务状态，返回加载的任务数
+    pub async fn load_state(&self) -> Result<usize> {
+        let Some((storage, key)) = &self.state_storage else {
+            return Ok(0);
+        };
+
+        let data = match storage.get_file(key).await {
+            Ok(data) => data,
+            Err(PacsError::NotFound(_)) => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let states: Vec<JobState> = serde_json::from_slice(&data)?;
+        let count = states.len();
+
+        let mut restored = self.restored.lock().await;
+        for state in states {
+            restored.insert(state.definition.name.clone(), state);
+        }
+        info!("Loaded state of {} scheduled jobs", count);
+        Ok(count)
+    }
+
+    /// 注册任务，同名任务会被替换
+    ///
+    /// 计划、时区和维护窗口与已保存状态一致时沿用其运行记录和下次运行时间
+    pub async fn register(
+        &self,
+        definition: JobDefinition,
+        job: Arc<dyn ScheduledJob>,
+    ) -> Result<()> {
+        let schedule = JobSchedule::from_definition(&definition)?;
+        let now = Utc::now();
+
+        let restored = self.restored.lock().await.remove(&definition.name);
+        let state = match restored {
+            Some(mut state)
+                if state.definition.schedule == definition.schedule
+                    && state.definition.timezone == definition.timezone
+                    && state.definition.maintenance_window == definition.maintenance_window =>
+            {
+                if state.running {
+                    warn!(
+                        "Scheduled job {} was interrupted by a restart",
+                        definition.name
+                    );
+                    state.running = false;
+                    state.last_status = Some(JobRunStatus::Interrupted);
+                    state.failure_count += 1;
+                }
+                if state.next_run_at.is_none() {
+                    state.next_run_at = schedule.next_run_after(now);
+                }
+                state
+            }
+            Some(previous) => {
+                let mut state = JobState::new(definition.clone(), schedule.next_run_after(now));
+                state.last_trigger = previous.last_trigger;
+                state.last_started_at = previous.last_started_at;
+                state.last_finished_at = previous.last_finished_at;
+                state.last_status = previous.last_status;
+                state.last_message = previous.last_message;
+                state.run_count = previous.run_count;
+                state.failure_count = previous.failure_count;
+                state
+            }
+            None => JobState::new(definition.clone(), schedule.next_run_after(now)),
+        };
+
+        info!(
+            "Registered scheduled job {} ({} {}), next run at {:?}",
+            definition.name, definition.schedule, definition.timezone, state.next_run_at
+        );
+        self.jobs.lock().await.insert(
+            definition.name.clone(),
+            RegisteredJob {
+                schedule,
+                job,
+                state,
+            },
+        );
+        self.persist_state().await;
+        Ok(())
+    }
+
+    /// 获取全部任务状态
+    pub async fn job_statuses(&self) -> Vec<JobState> {
+        let mut states: Vec<JobState> = self
+            .jobs
+            .lock()
+            .await
+            .values()
+            .map(|job| job.state.clone())
+            .collect();
+        states.sort_by(|a, b| a.definition.name.cmp(&b.definition.name));
+        states
+    }
+
+    /// 获取指定任务状态
+    pub async fn job_status(&self, name: &str) -> Option<JobState> {
+        self.jobs
+            .lock()
+            .await
+            .get(name)
+            .map(|job| job.state.clone())
+    }
+
+    /// 立即运行任务（忽略维护窗口），任务正在运行时返回错误
+    pub async fn run_now(self: &Arc<Self>, name: &str) -> Result<JobState> {
+        self.start_job(name, JobTrigger::Manual).await
+    }
+
+    /// 启动所有到期的任务，返回启动的任务名称
+    ///
+    /// 到期但不在维护窗口内的任务顺延到下一个窗口内的触发点
+    pub async fn run_due(self: &Arc<Self>) -> Vec<String> {
+        let now = Utc::now();
+        let mut due = Vec::new();
+        let mut deferred = false;
+        {
+            let mut jobs = self.jobs.lock().await;
+            for (name, job) in jobs.iter_mut() {
+                if job.state.running || job.state.next_run_at.is_none_or(|next| next > now) {
+                    continue;
+                }
+                if job.schedule.in_window(now) {
+                    due.push(name.clone());
+                } else {
+                    job.state.next_run_at = job.schedule.next_run_after(now);
+                    debug!(
+                        "Job {} is outside its maintenance window, deferred to {:?}",
+                        name, job.state.next_run_at
+                    );
+                    deferred = true;
+                }
+            }
+        }
+        if deferred {
+            self.persist_state().await;
+        }
+
+        let mut started = Vec::new();
+        for name in due {
+            match self.start_job(&name, JobTrigger::Scheduled).await {
+                Ok(_) => started.push(name),
+                Err(e) => warn!("Failed to start scheduled job {}: {}", name, e),
+            }
+        }
+        started
+    }
+
+    /// 运行调度循环
+    pub async fn start(self: Arc<Self>) {
+        info!("Starting job scheduler");
+
+        loop {
+            self.run_due().await;
+
+            let now = Utc::now();
+            let next_due = self
+                .jobs
+                .lock()
+                .await
+                .values()
+                .filter(|job| !job.state.running)
+                .filter_map(|job| job.state.next_run_at)
+                .min();
+            let idle = next_due
+                .map(|next| (next - now).num_seconds().clamp(1, MAX_IDLE_SECONDS))
+                .unwrap_or(MAX_IDLE_SECONDS);
+            tokio::time::sleep(std::time::Duration::from_secs(idle as u64)).await;
+        }
+    }
+
+    /// 标记任务开始并在后台运行
+    async fn start_job(self: &Arc<Self>, name: &str, trigger: JobTrigger) -> Result<JobState> {
+        let (job, state) = {
+            let mut jobs = self.jobs.lock().await;
+            let registered = jobs
+                .get_mut(name)
+                .ok_or_else(|| PacsError::NotFound(format!("Scheduled job {}", name)))?;
+            if registered.state.running {
+                return Err(PacsError::Validation(format!(
+                    "Job {} is already running",
+                    name
+                )));
+            }
+
+            let now = Utc::now();
+            registered.state.running = true;
+            registered.state.last_trigger = Some(trigger.clone());
+            registered.state.last_started_at = Some(now);
+            if trigger == JobTrigger::Scheduled
+                || registered.state.next_run_at.is_some_and(|next| next <= now)
+            {
+                registered.state.next_run_at = registered.schedule.next_run_after(now);
+            }
+            (registered.job.clone(), registered.state.clone())
+        };
+        self.persist_state().await;
+
+        info!("Starting job {} ({:?})", name, trigger);
+        let scheduler = self.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let result = job.run().await;
+            scheduler.finish_job(&name, result).await;
+        });
+        Ok(state)
+    }
+
+    /// 记录任务运行结果
+    async fn finish_job(&self, name: &str, result: Result<String>) {
+        {
+            let mut jobs = self.jobs.lock().await;
+            let Some(registered) = jobs.get_mut(name) else {
+                return;
+            };
+
+            let state = &mut registered.state;
+            state.running = false;
+            state.last_finished_at = Some(Utc::now());
+            state.run_count += 1;
+            match result {
+                Ok(summary) => {
+                    info!("Job {} succeeded: {}", name, summary);
+                    state.last_status = Some(JobRunStatus::Succeeded);
+                    state.last_message = Some(summary);
+                }
+                Err(e) => {
+                    error!("Job {} failed: {}", name, e);
+                    state.last_status = Some(JobRunStatus::Failed);
+                    state.last_message = Some(e.to_string());
+                    state.failure_count += 1;
+                }
+            }
+        }
+        self.persist_state().await;
+    }
+
+    /// 写入状态存储，失败时只记录日志
+    async fn persist_state(&self) {
+        let Some((storage, key)) = &self.state_storage else {
+            return;
+        };
+
+        let states = self.job_statuses().await;
+        let result = async {
+            let data = serde_json::to_vec_pretty(&states)?;
+            storage.store_file(&data, key).await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to persist scheduler state to {}: {}", key, e);
+        }
+    }
+}
+
+impl Default for JobScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Notify;
+
+    /// 等待通知后结束的任务
+    struct BlockingJob {
+        /// 放行通知
+        release: Arc<Notify>,
+    }
+
+    #[async_trait]
+    impl ScheduledJob for BlockingJob {
+        async fn run(&self) -> Result<String> {
+            self.release.notified().await;
+            Ok("done".to_string())
+        }
+    }
+
+    #[test]
+    fn test_next_run_with_timezone_and_window() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+
+        // 上海时间每天02:30，即UTC 18:30
+        let daily = JobSchedule::from_definition(
+            &JobDefinition::new("daily", "30 2 * * *").with_timezone("Asia/Shanghai"),
+        )
+        .unwrap();
+        assert_eq!(
+            daily.next_run_after(at("2024-03-01T12:00:00Z")),
+            Some(at("2024-03-01T18:30:00Z"))
+        );
+
+        // 每15分钟一次，但只在01:00-05:00之间
+        let windowed = JobSchedule::from_definition(
+            &JobDefinition::new("archive", "*/15 * * * *")
+                .with_maintenance_window(MaintenanceWindow::parse("01:00-05:00").unwrap()),
+        )
+        .unwrap();
+        assert_eq!(
+            windowed.next_run_after(at("2024-03-01T04:50:00Z")),
+            Some(at("2024-03-02T01:00:00Z"))
+        );
+        assert_eq!(
+            windowed.next_run_after(at("2024-03-01T02:05:00Z")),
+            Some(at("2024-03-01T02:15:00Z"))
+        );
+        assert!(!windowed.in_window(at("2024-03-01T05:00:00Z")));
+
+        let overnight = MaintenanceWindow::parse("22:00-04:00").unwrap();
+        assert!(overnight.contains(NaiveTime::from_hms_opt(23, 0, 0).unwrap()));
+        assert!(!overnight.contains(NaiveTime::from_hms_opt(12, 0, 0).unwrap()));
+        assert!(JobSchedule::from_definition(&JobDefinition::new("bad", "61 * * * *")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_now_prevents_overlap() {
+        let scheduler = Arc::new(JobScheduler::new());
+        let release = Arc::new(Notify::new());
+        scheduler
+            .register(
+                JobDefinition::new("backup", "0 2 * * *"),
+                Arc::new(BlockingJob {
+                    release: release.clone(),
+                }),
+            )
+            .await
+            .unwrap();
+
+        let state = scheduler.run_now("backup").await.unwrap();
+        assert!(state.running);
+        assert!(scheduler.run_now("backup").await.is_err());
+        assert!(scheduler.run_now("missing").await.is_err());
+
+        release.notify_one();
+        for _ in 0..100 {
+            if !scheduler.job_status("backup").await.unwrap().running {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let state = scheduler.job_status("backup").await.unwrap();
+        assert_eq!(state.last_status, Some(JobRunStatus::Succeeded));
+        assert_eq!(state.run_count, 1);
+        assert_eq!(state.last_trigger, Some(JobTrigger::Manual));
+    }
+}
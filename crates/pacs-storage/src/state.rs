@@ -0,0 +1,360 @@
+//! 持久化状态存储
+//!
+//! 生命周期状态、归档任务和备份历史保存在本地目录的嵌入式存储中，
+//! 进程重启后据此恢复每个文件所在的层级，并处理中断的转换。
+//!
+//! 每次提交的一组操作序列化为一行JSON追加到日志并同步到磁盘，整行写入才算提交；
+//! 打开时加载快照并重放日志，崩溃时写了一半的末行被截断丢弃。
+//! 日志条数达到阈值后写出新快照（临时文件加重命名）并清空日志，
+//! 快照写出后、日志清空前崩溃时重放的操作是幂等的。
+
+use pacs_core::{PacsError, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// 快照文件名
+const SNAPSHOT_FILE: &str = "state.json";
+
+/// 日志文件名
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// 触发压缩的日志条数
+const COMPACT_THRESHOLD: usize = 1000;
+
+/// 状态变更操作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum StateOp {
+    /// 写入或替换记录
+    Put {
+        collection: String,
+        key: String,
+        value: Value,
+    },
+    /// 删除记录
+    Delete { collection: String, key: String },
+}
+
+/// 日志条目
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    /// 同一事务的操作
+    ops: Vec<StateOp>,
+}
+
+/// 状态事务
+///
+/// 一组变更在 [`StateStore::commit`] 时作为整体写入，要么全部生效，要么全部不生效
+#[derive(Debug, Clone, Default)]
+pub struct StateTransaction {
+    /// 待提交的操作
+    ops: Vec<StateOp>,
+}
+
+impl StateTransaction {
+    /// 创建空事务
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 写入或替换记录
+    pub fn put<T: Serialize>(&mut self, collection: &str, key: &str, value: &T) -> Result<()> {
+        self.ops.push(StateOp::Put {
+            collection: collection.to_string(),
+            key: key.to_string(),
+            value: serde_json::to_value(value)?,
+        });
+        Ok(())
+    }
+
+    /// 删除记录
+    pub fn delete(&mut self, collection: &str, key: &str) {
+        self.ops.push(StateOp::Delete {
+            collection: collection.to_string(),
+            key: key.to_string(),
+        });
+    }
+
+    /// 是否没有任何操作
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// 中断状态的恢复结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateRecovery {
+    /// 加载的记录数
+    pub loaded: usize,
+    /// 已完成（继续提交）的中断操作数
+    pub rolled_forward: usize,
+    /// 已回滚的中断操作数
+    pub rolled_back: usize,
+    /// 无法恢复、需要人工处理的中断操作数
+    pub unresolved: usize,
+}
+
+/// 内存中的状态及日志计数
+struct StoreState {
+    /// 集合名到键值记录
+    collections: BTreeMap<String, BTreeMap<String, Value>>,
+    /// 快照之后的日志条数
+    journal_entries: usize,
+}
+
+impl StoreState {
+    fn apply(&mut self, ops: &[StateOp]) {
+        for op in ops {
+            match op {
+                StateOp::Put {
+                    collection,
+                    key,
+                    value,
+                } => {
+                    self.collections
+                        .entry(collection.clone())
+                        .or_default()
+                        .insert(key.clone(), value.clone());
+                }
+                StateOp::Delete { collection, key } => {
+                    if let Some(records) = self.collections.get_mut(collection) {
+                        records.remove(key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 嵌入式状态存储
+pub struct StateStore {
+    /// 存储目录
+    dir: PathBuf,
+    /// 当前状态
+    state: Mutex<StoreState>,
+}
+
+impl StateStore {
+    /// 打开（不存在时创建）状态存储，加载快照并重放日志
+    pub async fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let collections = match tokio::fs::read(dir.join(SNAPSHOT_FILE)).await {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut state = StoreState {
+            collections,
+            journal_entries: 0,
+        };
+
+        let journal_path = dir.join(JOURNAL_FILE);
+        let journal = match tokio::fs::read(&journal_path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut valid_len = 0;
+        for line in journal.split_inclusive(|b| *b == b'\n') {
+            let entry = line
+                .strip_suffix(b"\n")
+                .and_then(|line| serde_json::from_slice::<JournalEntry>(line).ok());
+            let Some(entry) = entry else {
+                break;
+            };
+            state.apply(&entry.ops);
+            state.journal_entries += 1;
+            valid_len += line.len();
+        }
+        if valid_len < journal.len() {
+            warn!(
+                "Discarding {} bytes of incomplete state journal in {}",
+                journal.len() - valid_len,
+                dir.display()
+            );
+            let file = OpenOptions::new().write(true).open(&journal_path).await?;
+            file.set_len(valid_len as u64).await?;
+            file.sync_all().await?;
+        }
+
+        info!(
+            "Opened state store {} ({} journal entries replayed)",
+            dir.display(),
+            state.journal_entries
+        );
+        Ok(Self {
+            dir,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// 存储目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 读取集合中的全部记录，按键排序
+    pub async fn load<T: DeserializeOwned>(&self, collection: &str) -> Result<Vec<(String, T)>> {
+        let state = self.state.lock().await;
+        let Some(records) = state.collections.get(collection) else {
+            return Ok(Vec::new());
+        };
+        records
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), serde_json::from_value(value.clone())?)))
+            .collect()
+    }
+
+    /// 读取单条记录
+    pub async fn get<T: DeserializeOwned>(&self, collection: &str, key: &str) -> Result<Option<T>> {
+        let state = self.state.lock().await;
+        state
+            .collections
+            .get(collection)
+            .and_then(|records| records.get(key))
+            .map(|value| serde_json::from_value(value.clone()))
+            .transpose()
+            .map_err(PacsError::from)
+    }
+
+    /// 提交事务：写入日志并同步到磁盘后更新内存状态
+    pub async fn commit(&self, transaction: StateTransaction) -> Result<()> {
+        if transaction.is_empty() {
+            return Ok(());
+        }
+
+        let entry = JournalEntry {
+            ops: transaction.ops,
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        let mut state = self.state.lock().await;
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(JOURNAL_FILE))
+            .await?;
+        let committed_len = journal.metadata().await?.len();
+        let written = async {
+            journal.write_all(&line).await?;
+            journal.sync_data().await
+        }
+        .await;
+        if let Err(e) = written {
+            // 截掉写了一半的行，避免之后追加的事务在重放时被一并丢弃
+            let _ = journal.set_len(committed_len).await;
+            return Err(e.into());
+        }
+
+        state.apply(&entry.ops);
+        state.journal_entries += 1;
+
+        if state.journal_entries >= COMPACT_THRESHOLD {
+            self.write_snapshot(&mut state).await?;
+        }
+        Ok(())
+    }
+
+    /// 立即将当前状态写为快照并清空日志
+    pub async fn compact(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        self.write_snapshot(&mut state).await
+    }
+
+    async fn write_snapshot(&self, state: &mut StoreState) -> Result<()> {
+        let snapshot_path = self.dir.join(SNAPSHOT_FILE);
+        let partial_path = self.dir.join(format!("{}.partial", SNAPSHOT_FILE));
+
+        let mut file = tokio::fs::File::create(&partial_path).await?;
+        file.write_all(&serde_json::to_vec(&state.collections)?)
+            .await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&partial_path, &snapshot_path).await?;
+
+        let journal = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(self.dir.join(JOURNAL_FILE))
+            .await?;
+        journal.sync_all().await?;
+
+        debug!(
+            "Compacted state store {} ({} journal entries)",
+            self.dir.display(),
+            state.journal_entries
+        );
+        state.journal_entries = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_state_store_replay_and_torn_write() {
+        let dir = std::env::temp_dir().join(format!(
+            "pacs-state-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+
+        let store = StateStore::open(&dir).await.unwrap();
+        let mut txn = StateTransaction::new();
+        txn.put("files", "a.dcm", &"Online").unwrap();
+        txn.put("files", "b.dcm", &"Online").unwrap();
+        store.commit(txn).await.unwrap();
+        let mut txn = StateTransaction::new();
+        txn.put("files", "a.dcm", &"Archive").unwrap();
+        txn.delete("files", "b.dcm");
+        store.commit(txn).await.unwrap();
+        store.compact().await.unwrap();
+        let mut txn = StateTransaction::new();
+        txn.put("files", "c.dcm", &"Nearline").unwrap();
+        store.commit(txn).await.unwrap();
+        drop(store);
+
+        // 模拟提交过程中崩溃留下的半行
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))
+            .await
+            .unwrap();
+        journal
+            .write_all(br#"{"ops":[{"op":"delete","collection":"files","#)
+            .await
+            .unwrap();
+        drop(journal);
+
+        let store = StateStore::open(&dir).await.unwrap();
+        let files: Vec<(String, String)> = store.load("files").await.unwrap();
+        assert_eq!(
+            files,
+            vec![
+                ("a.dcm".to_string(), "Archive".to_string()),
+                ("c.dcm".to_string(), "Nearline".to_string()),
+            ]
+        );
+
+        // 截断后的日志可以继续追加
+        let mut txn = StateTransaction::new();
+        txn.delete("files", "c.dcm");
+        store.commit(txn).await.unwrap();
+        drop(store);
+        let store = StateStore::open(&dir).await.unwrap();
+        assert_eq!(store.get::<String>("files", "c.dcm").await.unwrap(), None);
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+}
@@ -1,11 +1,17 @@
 //! 影像存储管理
 
+use crate::backend::{create_backend, ObjectMetadata, ObjectPage, StorageBackend};
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use object_store::{path::Path as ObjectPath, GetOptions, ObjectStore, PutOptions};
-use pacs_core::{PacsError, Result};
+use futures::stream::BoxStream;
+use pacs_core::Result;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::ops::Range;
 use std::sync::Arc;
+use tokio::io::AsyncRead;
+
+/// 字节流，用于在不整体载入内存的情况下读取对象
+pub type ByteStream = BoxStream<'static, Result<Bytes>>;
 
 /// 存储类型
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -14,6 +20,8 @@ pub enum StorageType {
     Local,
     /// 对象存储 (S3, GCS, Azure等)
     ObjectStorage,
+    /// 内存存储（用于测试）
+    Memory,
 }
 
 /// 存储配置
@@ -27,6 +35,25 @@ pub struct StorageConfig {
     pub object_store_config: Option<ObjectStoreConfig>,
 }
 
+impl StorageConfig {
+    /// 将本地存储目录下的文件路径转换为对象键
+    ///
+    /// 不在本地存储目录下的路径去掉开头的分隔符后原样作为对象键
+    pub fn object_key(&self, path: &str) -> String {
+        let path = path.replace('\\', "/");
+        let relative = self
+            .local_path
+            .as_deref()
+            .map(|root| root.replace('\\', "/"))
+            .and_then(|root| {
+                path.strip_prefix(root.trim_end_matches('/'))
+                    .map(str::to_string)
+            })
+            .unwrap_or(path);
+        relative.trim_start_matches('/').to_string()
+    }
+}
+
 /// 对象存储配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectStoreConfig {
@@ -76,247 +103,160 @@ pub struct StorageStats {
     pub last_updated: DateTime<Utc>,
 }
 
+/// 读取的字节范围
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ByteRange {
+    /// 区间 `[start, end)`
+    Bounded(Range<u64>),
+    /// 从指定偏移到末尾
+    From(u64),
+    /// 末尾的若干字节
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// 按对象大小解析为具体区间，范围不可满足时返回None
+    pub fn resolve(&self, size: u64) -> Option<Range<u64>> {
+        let range = match self {
+            ByteRange::Bounded(range) => range.start..range.end.min(size),
+            ByteRange::From(start) => *start..size,
+            ByteRange::Suffix(length) => size.saturating_sub(*length)..size,
+        };
+        (range.start < range.end).then_some(range)
+    }
+
+    /// 解析HTTP `Range` 头中的单个 `bytes=` 范围，多个范围或格式错误时返回None
+    pub fn parse_header(value: &str) -> Option<Self> {
+        let spec = value.trim().strip_prefix("bytes=")?;
+        if spec.contains(',') {
+            return None;
+        }
+        let (start, end) = spec.split_once('-')?;
+        match (start.trim(), end.trim()) {
+            ("", suffix) => Some(ByteRange::Suffix(suffix.parse().ok()?)),
+            (start, "") => Some(ByteRange::From(start.parse().ok()?)),
+            (start, end) => {
+                let start: u64 = start.parse().ok()?;
+                let end: u64 = end.parse().ok()?;
+                // HTTP范围的结束偏移包含在内
+                (start <= end).then(|| ByteRange::Bounded(start..end.saturating_add(1)))
+            }
+        }
+    }
+}
+
+/// 带范围信息的读取结果
+pub struct RangedStream {
+    /// 范围内的字节流
+    pub stream: ByteStream,
+    /// 实际返回的区间
+    pub range: Range<u64>,
+    /// 对象总大小
+    pub total_size: u64,
+}
+
 /// 存储管理器
+///
+/// 按配置选择 [`StorageBackend`] 并委托所有对象操作
+#[derive(Clone)]
 pub struct StorageManager {
     config: StorageConfig,
-    local_path: Option<String>,
-    object_store: Option<Arc<dyn ObjectStore>>,
+    backend: Arc<dyn StorageBackend>,
 }
 
 impl StorageManager {
     /// 创建新的存储管理器
     pub async fn new(config: StorageConfig) -> Result<Self> {
-        let local_path = config.local_path.clone();
-        let object_store = match &config.storage_type {
-            StorageType::ObjectStorage => {
-                if let Some(os_config) = &config.object_store_config {
-                    Some(Self::create_object_store(os_config).await?)
-                } else {
-                    return Err(PacsError::Config(
-                        "Missing object store configuration".to_string(),
-                    ));
-                }
-            }
-            StorageType::Local => None,
-        };
-
-        Ok(Self {
-            config,
-            local_path,
-            object_store,
-        })
+        let backend = create_backend(&config)?;
+        Ok(Self { config, backend })
     }
 
-    /// 创建对象存储客户端
-    async fn create_object_store(config: &ObjectStoreConfig) -> Result<Arc<dyn ObjectStore>> {
-        if let Some(aws_config) = &config.aws {
-            #[cfg(feature = "aws")]
-            use object_store::aws::AmazonS3Builder;
-
-            let mut builder = AmazonS3Builder::new()
-                .with_bucket_name(&aws_config.bucket)
-                .with_region(&aws_config.region)
-                .with_access_key_id(&aws_config.access_key_id)
-                .with_secret_access_key(&aws_config.secret_access_key);
+    /// 使用指定后端创建存储管理器
+    pub fn with_backend(config: StorageConfig, backend: Arc<dyn StorageBackend>) -> Self {
+        Self { config, backend }
+    }
 
-            if let Some(endpoint) = &aws_config.endpoint {
-                builder = builder.with_endpoint(endpoint);
-            }
+    /// 获取存储配置
+    pub fn config(&self) -> &StorageConfig {
+        &self.config
+    }
 
-            Ok(Arc::new(builder.build()?))
-        } else if let Some(_gcs_config) = &config.gcs {
-            return Err(PacsError::Config(
-                "Google Cloud Storage not yet implemented".to_string(),
-            ));
-        } else if let Some(_azure_config) = &config.azure {
-            return Err(PacsError::Config(
-                "Azure Blob Storage not yet implemented".to_string(),
-            ));
-        } else {
-            return Err(PacsError::Config(
-                "No valid object store configuration found".to_string(),
-            ));
-        }
+    /// 获取存储后端
+    pub fn backend(&self) -> &Arc<dyn StorageBackend> {
+        &self.backend
     }
 
-    /// 存储DICOM文件
+    /// 存储DICOM文件，返回对象键
     pub async fn store_file(&self, data: &[u8], path: &str) -> Result<String> {
-        match &self.config.storage_type {
-            StorageType::Local => {
-                let base_path = self.local_path.as_ref().ok_or_else(|| {
-                    PacsError::Config("Local storage path not configured".to_string())
-                })?;
-                let full_path = Path::new(base_path).join(path);
-
-                if let Some(parent) = full_path.parent() {
-                    tokio::fs::create_dir_all(parent).await?;
-                }
-
-                tokio::fs::write(&full_path, data).await?;
-                Ok(full_path.to_string_lossy().to_string())
-            }
-            StorageType::ObjectStorage => {
-                let store = self
-                    .object_store
-                    .as_ref()
-                    .ok_or_else(|| PacsError::Config("Object store not initialized".to_string()))?;
-
-                let object_path = ObjectPath::from(path);
-                store
-                    .put_opts(&object_path, data.into(), PutOptions::default())
-                    .await?;
-                Ok(path.to_string())
-            }
-        }
+        self.backend
+            .put(path, Bytes::copy_from_slice(data))
+            .await
+            .map(|object| object.key)
     }
 
     /// 获取文件
     pub async fn get_file(&self, path: &str) -> Result<Vec<u8>> {
-        match &self.config.storage_type {
-            StorageType::Local => {
-                let base_path = self.local_path.as_ref().ok_or_else(|| {
-                    PacsError::Config("Local storage path not configured".to_string())
-                })?;
-                let full_path = Path::new(base_path).join(path);
-                let data = tokio::fs::read(full_path).await?;
-                Ok(data)
-            }
-            StorageType::ObjectStorage => {
-                let store = self
-                    .object_store
-                    .as_ref()
-                    .ok_or_else(|| PacsError::Config("Object store not initialized".to_string()))?;
-
-                let object_path = ObjectPath::from(path);
-                let result = store.get_opts(&object_path, GetOptions::default()).await?;
-                let data = result.bytes().await?;
-                Ok(data.to_vec())
-            }
-        }
+        Ok(self.backend.get(path).await?.to_vec())
     }
 
     /// 检查文件是否存在
     pub async fn file_exists(&self, path: &str) -> Result<bool> {
-        match &self.config.storage_type {
-            StorageType::Local => {
-                let base_path = self.local_path.as_ref().ok_or_else(|| {
-                    PacsError::Config("Local storage path not configured".to_string())
-                })?;
-                let full_path = Path::new(base_path).join(path);
-                Ok(tokio::fs::metadata(full_path).await.is_ok())
-            }
-            StorageType::ObjectStorage => {
-                let store = self
-                    .object_store
-                    .as_ref()
-                    .ok_or_else(|| PacsError::Config("Object store not initialized".to_string()))?;
-
-                let object_path = ObjectPath::from(path);
-                Ok(store.head(&object_path).await.is_ok())
-            }
-        }
+        Ok(self.backend.head(path).await?.is_some())
     }
 
     /// 删除文件
     pub async fn delete_file(&self, path: &str) -> Result<()> {
-        match &self.config.storage_type {
-            StorageType::Local => {
-                let base_path = self.local_path.as_ref().ok_or_else(|| {
-                    PacsError::Config("Local storage path not configured".to_string())
-                })?;
-                let full_path = Path::new(base_path).join(path);
-                tokio::fs::remove_file(full_path).await?;
-                Ok(())
-            }
-            StorageType::ObjectStorage => {
-                let store = self
-                    .object_store
-                    .as_ref()
-                    .ok_or_else(|| PacsError::Config("Object store not initialized".to_string()))?;
-
-                let object_path = ObjectPath::from(path);
-                store.delete(&object_path).await?;
-                Ok(())
-            }
-        }
+        self.backend.delete(path).await
     }
 
-    /// 获取存储统计信息
-    pub async fn get_storage_stats(&self) -> Result<StorageStats> {
-        match &self.config.storage_type {
-            StorageType::Local => {
-                let base_path = self.local_path.as_ref().ok_or_else(|| {
-                    PacsError::Config("Local storage path not configured".to_string())
-                })?;
-
-                let (total_files, total_size) = self.scan_local_directory(base_path).await?;
-
-                // 获取可用空间
-                let available_space = match tokio::fs::metadata(base_path).await {
-                    Ok(_) => {
-                        // 在Unix系统上，我们需要获取文件系统的信息
-                        #[cfg(unix)]
-                        {
-                            use std::os::unix::fs::MetadataExt;
-                            match tokio::fs::metadata(base_path).await {
-                                Ok(meta) => Some(meta.available()),
-                                Err(_) => None,
-                            }
-                        }
-                        #[cfg(not(unix))]
-                        {
-                            None
-                        }
-                    }
-                    Err(_) => None,
-                };
-
-                Ok(StorageStats {
-                    total_files,
-                    total_size,
-                    available_space,
-                    last_updated: Utc::now(),
-                })
-            }
-            StorageType::ObjectStorage => {
-                // 对象存储的统计信息获取比较复杂，这里提供简化版本
-                Ok(StorageStats {
-                    total_files: 0,
-                    total_size: 0,
-                    available_space: None,
-                    last_updated: Utc::now(),
-                })
-            }
-        }
+    /// 列出前缀下的所有文件
+    pub async fn list_files(&self, prefix: Option<&str>) -> Result<Vec<ObjectMetadata>> {
+        self.backend.list(prefix).await
     }
 
-    /// 扫描本地目录获取统计信息
-    fn scan_local_directory(
+    /// 分页列出文件，`start_after` 为上一页返回的起始键
+    pub async fn list_files_page(
         &self,
-        dir_path: &str,
-    ) -> impl std::future::Future<Output = Result<(u64, u64)>> + '_ {
-        async move {
-            let mut total_files = 0u64;
-            let mut total_size = 0u64;
-
-            let mut entries = tokio::fs::read_dir(dir_path).await?;
-
-            while let Some(entry) = entries.next_entry().await? {
-                let path = entry.path();
-                if path.is_dir() {
-                    let (files, size) = self.scan_local_directory(path.to_str().unwrap()).await?;
-                    total_files += files;
-                    total_size += size;
-                } else {
-                    total_files += 1;
-                    if let Ok(metadata) = entry.metadata().await {
-                        total_size += metadata.len();
-                    }
-                }
-            }
+        prefix: Option<&str>,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Result<ObjectPage> {
+        self.backend.list_page(prefix, start_after, limit).await
+    }
 
-            Ok((total_files, total_size))
-        }
+    /// 以流方式存储文件，返回写入的字节数
+    ///
+    /// 本地存储先写入临时文件再重命名，对象存储使用分段上传，
+    /// 失败时中止上传，不会留下不完整的对象
+    pub async fn store_stream<R>(&self, mut reader: R, path: &str) -> Result<u64>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        self.backend
+            .put_stream(path, &mut reader)
+            .await
+            .map(|object| object.size)
+    }
+
+    /// 以流方式读取整个文件
+    pub async fn get_stream(&self, path: &str) -> Result<ByteStream> {
+        self.backend.get_stream(path).await
+    }
+
+    /// 以流方式读取文件的指定字节范围
+    pub async fn get_range_stream(&self, path: &str, range: ByteRange) -> Result<RangedStream> {
+        self.backend.get_range_stream(path, range).await
+    }
+
+    /// 获取存储统计信息
+    pub async fn get_storage_stats(&self) -> Result<StorageStats> {
+        let objects = self.backend.list(None).await?;
+        Ok(StorageStats {
+            total_files: objects.len() as u64,
+            total_size: objects.iter().map(|object| object.size).sum(),
+            available_space: None,
+            last_updated: Utc::now(),
+        })
     }
 
     /// 获取存储类型
@@ -324,3 +264,38 @@ impl StorageManager {
         &self.config.storage_type
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_range_resolve() {
+        assert_eq!(ByteRange::Bounded(10..20).resolve(100), Some(10..20));
+        assert_eq!(ByteRange::Bounded(90..200).resolve(100), Some(90..100));
+        assert_eq!(ByteRange::Bounded(100..200).resolve(100), None);
+        assert_eq!(ByteRange::From(40).resolve(100), Some(40..100));
+        assert_eq!(ByteRange::Suffix(30).resolve(100), Some(70..100));
+        assert_eq!(ByteRange::Suffix(300).resolve(100), Some(0..100));
+        assert_eq!(ByteRange::Suffix(0).resolve(100), None);
+    }
+
+    #[test]
+    fn test_byte_range_parse_header() {
+        assert_eq!(
+            ByteRange::parse_header("bytes=0-99"),
+            Some(ByteRange::Bounded(0..100))
+        );
+        assert_eq!(
+            ByteRange::parse_header("bytes=40-"),
+            Some(ByteRange::From(40))
+        );
+        assert_eq!(
+            ByteRange::parse_header("bytes=-30"),
+            Some(ByteRange::Suffix(30))
+        );
+        assert_eq!(ByteRange::parse_header("bytes=20-10"), None);
+        assert_eq!(ByteRange::parse_header("bytes=0-1,5-6"), None);
+        assert_eq!(ByteRange::parse_header("items=0-1"), None);
+    }
+}
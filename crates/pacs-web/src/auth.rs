@@ -7,6 +7,7 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use pacs_core::{error::PacsError, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -168,6 +169,7 @@ impl AuthService {
 
         let user = users
             .get(&request.username)
+            .cloned()
             .ok_or_else(|| PacsError::Validation("Invalid username or password".to_string()))?;
 
         if !user.is_active {
@@ -183,7 +185,7 @@ impl AuthService {
         }
 
         // 生成JWT token
-        let token = self.generate_token(user).await?;
+        let token = self.generate_token(&user).await?;
         let expires_at = chrono::Utc::now() + chrono::Duration::hours(self.token_expiry_hours);
 
         // 更新最后登录时间
@@ -225,7 +227,7 @@ impl AuthService {
         // 这里为了演示，简单编码claims
         let token = format!(
             "{}.{}.{}",
-            base64::encode(serde_json::to_string(&claims)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_string(&claims)?),
             "signature", // 模拟签名
             "header"     // 模拟头部
         );
@@ -242,7 +244,8 @@ impl AuthService {
             return Err(PacsError::Validation("Invalid token format".to_string()));
         }
 
-        let claims_data = base64::decode(parts[0])
+        let claims_data = URL_SAFE_NO_PAD
+            .decode(parts[0])
             .map_err(|_| PacsError::Validation("Invalid token encoding".to_string()))?;
 
         let claims: Claims = serde_json::from_slice(&claims_data)
@@ -278,7 +281,7 @@ pub async fn auth_middleware(
     State(auth_service): State<Arc<AuthService>>,
     request: Request,
     next: Next,
-) -> Result<Response, pacs_core::error::PacsError> {
+) -> std::result::Result<Response, pacs_core::error::PacsError> {
     // 从请求头获取token
     let auth_header = request
         .headers()
@@ -361,23 +364,3 @@ pub async fn get_all_users_handler(
     let users = auth_service.get_all_users().await;
     Ok(Json(users))
 }
-
-// 简单的base64编码解码（用于演示）
-mod base64 {
-    use std::collections::HashMap;
-
-    pub fn encode(input: String) -> String {
-        // 简化的base64编码（仅用于演示）
-        // 实际应用中应该使用标准的base64库
-        format!("BASE64({})", input.len())
-    }
-
-    pub fn decode(input: &str) -> Result<Vec<u8>, &'static str> {
-        // 简化的base64解码（仅用于演示）
-        if input.starts_with("BASE64(") && input.ends_with(")") {
-            Ok(vec![0u8; 100]) // 模拟解码结果
-        } else {
-            Err("Invalid base64 format")
-        }
-    }
-}
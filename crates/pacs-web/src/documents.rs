@@ -0,0 +1,141 @@
+//! 封装文档（Encapsulated PDF/CDA）接口
+
+use axum::{
+    body::Bytes,
+    extract::{Extension, Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use pacs_core::{error::PacsError, Result};
+use pacs_database::{DatabasePool, DatabaseQueries};
+use pacs_dicom::{
+    EncapsulatedDocumentHandler, EncapsulatedPdfRequest, IngestOutcome, IngestPipeline,
+    IngestSource,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::auth::User;
+
+/// 上传文档参数
+#[derive(Debug, Deserialize)]
+pub struct UploadDocumentParams {
+    /// 文档标题
+    pub title: Option<String>,
+}
+
+/// 获取实例的封装文档
+pub async fn get_instance_document(
+    Path(sop_instance_uid): Path<String>,
+    Extension(db): Extension<Arc<DatabasePool>>,
+) -> Result<Response> {
+    info!("Getting encapsulated document: {}", sop_instance_uid);
+
+    let instance = DatabaseQueries::new(&db)
+        .get_instance_by_uid(&sop_instance_uid)
+        .await?
+        .ok_or_else(|| PacsError::NotFound(format!("Instance {}", sop_instance_uid)))?;
+
+    let data = tokio::fs::read(&instance.file_path)
+        .await
+        .map_err(|e| PacsError::Io(format!("Failed to read {}: {}", instance.file_path, e)))?;
+    let document = EncapsulatedDocumentHandler::extract_bytes(&data)?;
+
+    let disposition = format!(
+        "inline; filename=\"{}.{}\"",
+        sop_instance_uid,
+        document.file_extension()
+    );
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, document.mime_type.clone()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        document.data,
+    )
+        .into_response())
+}
+
+/// 上传PDF并封装为检查的新Encapsulated PDF实例
+pub async fn upload_study_document(
+    Path(study_uid): Path<String>,
+    Query(params): Query<UploadDocumentParams>,
+    Extension(db): Extension<Arc<DatabasePool>>,
+    Extension(pipeline): Extension<Arc<IngestPipeline>>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse> {
+    info!("Uploading document to study: {}", study_uid);
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    if !content_type.starts_with("application/pdf") {
+        return Err(PacsError::Validation(format!(
+            "Unsupported content type: {}",
+            content_type
+        )));
+    }
+
+    let queries = DatabaseQueries::new(&db);
+    let study = queries
+        .get_study_by_uid(&study_uid)
+        .await?
+        .ok_or_else(|| PacsError::NotFound(format!("Study {}", study_uid)))?;
+    let patient = queries
+        .get_patient_by_id(&study.patient_id)
+        .await?
+        .ok_or_else(|| PacsError::NotFound(format!("Patient of study {}", study_uid)))?;
+
+    let request = EncapsulatedPdfRequest {
+        study_instance_uid: study.study_uid.clone(),
+        patient_id: patient.patient_id,
+        patient_name: patient.name,
+        patient_birth_date: patient.birth_date.map(|d| d.format("%Y%m%d").to_string()),
+        patient_sex: patient.sex.map(|s| {
+            match s {
+                pacs_core::Sex::Male => "M",
+                pacs_core::Sex::Female => "F",
+                pacs_core::Sex::Other => "O",
+            }
+            .to_string()
+        }),
+        accession_number: Some(study.accession_number),
+        document_title: params
+            .title
+            .unwrap_or_else(|| "Scanned Document".to_string()),
+    };
+
+    let data = EncapsulatedDocumentHandler::wrap_pdf_bytes(&body, &request)?;
+    let source = IngestSource::Upload {
+        username: user.map(|Extension(user)| user.username),
+    };
+
+    match pipeline.ingest(&data, &source, None).await? {
+        IngestOutcome::Stored(instance) => Ok((
+            StatusCode::CREATED,
+            Json(json!({
+                "study_instance_uid": study_uid,
+                "series_instance_uid": instance.metadata.series_instance_uid,
+                "sop_instance_uid": instance.sop_instance_uid,
+                "document_title": request.document_title,
+                "size": instance.file_size
+            })),
+        )),
+        IngestOutcome::Duplicate { sop_instance_uid } => {
+            warn!(
+                "Generated document instance already exists: {}",
+                sop_instance_uid
+            );
+            Err(PacsError::Internal(format!(
+                "Duplicate SOP Instance UID: {}",
+                sop_instance_uid
+            )))
+        }
+    }
+}
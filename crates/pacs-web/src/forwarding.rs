@@ -0,0 +1,94 @@
+//! 自动转发队列管理接口（仅管理员）
+
+use axum::{
+    extract::{Extension, Query},
+    response::{IntoResponse, Json},
+};
+use pacs_core::{error::PacsError, Result};
+use pacs_dicom::ForwardingRouter;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::{User, UserRole};
+
+/// 失败任务查询参数
+#[derive(Debug, Deserialize)]
+pub struct ForwardFailureParams {
+    /// 目的地名称（为空时查询全部）
+    pub destination: Option<String>,
+    /// 返回条数
+    pub limit: Option<i64>,
+}
+
+/// 校验当前用户为管理员
+pub(crate) fn require_admin(user: &User) -> Result<()> {
+    if user.role != UserRole::Admin {
+        return Err(PacsError::Permission("Admin access required".to_string()));
+    }
+    Ok(())
+}
+
+/// 获取各目的地的转发队列深度与失败数
+pub async fn get_forward_queue(
+    Extension(user): Extension<User>,
+    Extension(router): Extension<Arc<ForwardingRouter>>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let status = router.queue_status().await?;
+    Ok(Json(json!({
+        "destinations": status,
+        "rules": router.get_rules().await
+    })))
+}
+
+/// 获取最终失败的转发任务
+pub async fn get_forward_failures(
+    Extension(user): Extension<User>,
+    Extension(router): Extension<Arc<ForwardingRouter>>,
+    Query(params): Query<ForwardFailureParams>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let tasks = router
+        .get_failed_tasks(params.destination.as_deref(), params.limit.unwrap_or(100))
+        .await?;
+    let tasks: Vec<_> = tasks
+        .into_iter()
+        .map(|task| {
+            json!({
+                "id": task.id,
+                "destination": task.destination,
+                "rule_name": task.rule_name,
+                "study_instance_uid": task.study_uid,
+                "sop_instance_uid": task.sop_instance_uid,
+                "attempts": task.attempts,
+                "last_error": task.last_error,
+                "updated_at": task.updated_at
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "count": tasks.len(),
+        "tasks": tasks
+    })))
+}
+
+/// 将最终失败的转发任务重新排队
+pub async fn retry_forward_failures(
+    Extension(user): Extension<User>,
+    Extension(router): Extension<Arc<ForwardingRouter>>,
+    Query(params): Query<ForwardFailureParams>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+    info!(
+        "{} requeued failed forward tasks (destination: {:?})",
+        user.username, params.destination
+    );
+
+    let requeued = router.retry_failed(params.destination.as_deref()).await?;
+    Ok(Json(json!({ "requeued": requeued })))
+}
@@ -1,16 +1,18 @@
 //! HTTP处理器
 
 use axum::{
-    extract::{Path, Query, State},
-    http::{header, HeaderMap, HeaderValue, StatusCode},
+    extract::{Extension, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue},
     response::{IntoResponse, Json},
     routing::get,
     Router,
 };
-use pacs_core::{error::PacsError, Result};
+use pacs_core::Result;
+use pacs_database::{DatabasePool, DatabaseQueries, DbInstanceRelationship};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::{error, info, warn};
 
 /// API根路径处理器
@@ -174,6 +176,80 @@ pub async fn get_instances(Query(params): Query<InstanceQueryParams>) -> Result<
     })))
 }
 
+/// 检查关键图像处理器
+pub async fn get_study_key_images(
+    Path(study_uid): Path<String>,
+    Extension(db): Extension<Arc<DatabasePool>>,
+) -> Result<impl IntoResponse> {
+    info!("Getting key images for study: {}", study_uid);
+
+    let relationships = DatabaseQueries::new(&db)
+        .get_key_images_by_study_uid(&study_uid)
+        .await?;
+    let key_images: Vec<Value> = relationships.iter().map(relationship_to_json).collect();
+
+    Ok(Json(json!({
+        "study_instance_uid": study_uid,
+        "key_images": key_images,
+        "total": key_images.len()
+    })))
+}
+
+/// 序列显示状态处理器
+pub async fn get_series_presentation_states(
+    Path(series_uid): Path<String>,
+    Extension(db): Extension<Arc<DatabasePool>>,
+) -> Result<impl IntoResponse> {
+    info!("Getting presentation states for series: {}", series_uid);
+
+    let relationships = DatabaseQueries::new(&db)
+        .get_presentation_states_by_series_uid(&series_uid)
+        .await?;
+
+    // 同一显示状态通常引用序列中的多幅图像，按显示状态聚合
+    let mut presentation_states: Vec<Value> = Vec::new();
+    let mut index_by_source: HashMap<&str, usize> = HashMap::new();
+    for relationship in &relationships {
+        match index_by_source.get(relationship.source_sop_instance_uid.as_str()) {
+            Some(&index) => {
+                if let Some(images) =
+                    presentation_states[index]["referenced_sop_instance_uids"].as_array_mut()
+                {
+                    images.push(json!(relationship.referenced_sop_instance_uid));
+                }
+            }
+            None => {
+                index_by_source.insert(
+                    relationship.source_sop_instance_uid.as_str(),
+                    presentation_states.len(),
+                );
+                presentation_states.push(json!({
+                    "sop_instance_uid": relationship.source_sop_instance_uid,
+                    "content_label": relationship.label,
+                    "referenced_sop_instance_uids": [relationship.referenced_sop_instance_uid],
+                }));
+            }
+        }
+    }
+
+    Ok(Json(json!({
+        "series_instance_uid": series_uid,
+        "presentation_states": presentation_states,
+        "total": presentation_states.len()
+    })))
+}
+
+/// 实例关系转换为JSON
+fn relationship_to_json(relationship: &DbInstanceRelationship) -> Value {
+    json!({
+        "source_sop_instance_uid": relationship.source_sop_instance_uid,
+        "document_title": relationship.label,
+        "series_instance_uid": relationship.referenced_series_uid,
+        "sop_class_uid": relationship.referenced_sop_class_uid,
+        "sop_instance_uid": relationship.referenced_sop_instance_uid,
+    })
+}
+
 /// 查询参数结构体
 #[derive(Debug, Deserialize)]
 pub struct PatientQueryParams {
@@ -211,25 +287,3 @@ pub struct InstanceQueryParams {
     pub limit: Option<usize>,
     pub offset: Option<usize>,
 }
-
-/// 错误处理
-impl IntoResponse for PacsError {
-    fn into_response(self) -> axum::response::Response {
-        let (status, error_message) = match self {
-            PacsError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            PacsError::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
-            PacsError::Database(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            PacsError::Io(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            PacsError::Dicom(msg) => (StatusCode::BAD_REQUEST, msg),
-            PacsError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-        };
-
-        let body = Json(json!({
-            "error": true,
-            "message": error_message,
-            "status": status.as_u16()
-        }));
-
-        (status, body).into_response()
-    }
-}
@@ -0,0 +1,116 @@
+//! DICOM介质/目录导入管理接口（仅管理员）
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use pacs_core::{PacsError, Result};
+use pacs_dicom::{DicomImporter, ImportSource, ReconciliationPolicy};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::auth::User;
+use crate::forwarding::require_admin;
+
+/// 创建导入任务请求
+#[derive(Debug, Deserialize)]
+pub struct ImportRequest {
+    /// 导入来源（服务器本地的DICOMDIR文件或目录）
+    pub source: ImportSource,
+    /// 患者信息调和策略
+    #[serde(default)]
+    pub reconciliation: ReconciliationPolicy,
+}
+
+/// 创建导入任务并在后台执行，立即返回任务记录
+pub async fn start_import(
+    Extension(user): Extension<User>,
+    Extension(importer): Extension<Arc<DicomImporter>>,
+    Json(request): Json<ImportRequest>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+    let path = match &request.source {
+        ImportSource::Dicomdir(path) | ImportSource::Directory(path) => path,
+    };
+    if tokio::fs::metadata(path).await.is_err() {
+        return Err(PacsError::Validation(format!(
+            "Import source not found: {}",
+            path.display()
+        )));
+    }
+
+    let job = importer
+        .create_job(request.source, request.reconciliation)
+        .await?;
+    info!(
+        "{} started import job {} ({:?})",
+        user.username, job.id, job.source
+    );
+
+    let mut running = job.clone();
+    tokio::spawn(async move {
+        if let Err(e) = importer.run_job(&mut running).await {
+            warn!("Import job {} failed: {}", running.id, e);
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// 续传未完成的导入任务（后台执行）
+pub async fn resume_import(
+    Extension(user): Extension<User>,
+    Extension(importer): Extension<Arc<DicomImporter>>,
+    Path(job_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let job = importer.load_job(&job_id).await?;
+    if job.is_finished() {
+        return Err(PacsError::Validation(format!(
+            "Import job {} is already finished",
+            job_id
+        )));
+    }
+    info!("{} resumed import job {}", user.username, job_id);
+
+    tokio::spawn(async move {
+        if let Err(e) = importer.resume(&job_id).await {
+            warn!("Import job {} failed: {}", job_id, e);
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// 获取全部导入任务
+pub async fn list_imports(
+    Extension(user): Extension<User>,
+    Extension(importer): Extension<Arc<DicomImporter>>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let jobs = importer.list_jobs().await?;
+    Ok(Json(json!({
+        "count": jobs.len(),
+        "jobs": jobs
+    })))
+}
+
+/// 获取导入任务详情，包含逐文件错误报告
+pub async fn get_import(
+    Extension(user): Extension<User>,
+    Extension(importer): Extension<Arc<DicomImporter>>,
+    Path(job_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let job = importer.load_job(&job_id).await?;
+    Ok(Json(json!({
+        "report": job.get_error_report(),
+        "job": job
+    })))
+}
@@ -0,0 +1,56 @@
+//! 定时任务管理接口（仅管理员）
+
+use axum::{
+    extract::{Extension, Path},
+    response::{IntoResponse, Json},
+};
+use pacs_core::{error::PacsError, Result};
+use pacs_storage::JobScheduler;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::User;
+use crate::forwarding::require_admin;
+
+/// 获取全部定时任务的状态
+pub async fn list_jobs(
+    Extension(user): Extension<User>,
+    Extension(scheduler): Extension<Arc<JobScheduler>>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let jobs = scheduler.job_statuses().await;
+    Ok(Json(json!({
+        "count": jobs.len(),
+        "jobs": jobs
+    })))
+}
+
+/// 获取指定定时任务的状态
+pub async fn get_job(
+    Extension(user): Extension<User>,
+    Extension(scheduler): Extension<Arc<JobScheduler>>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let job = scheduler
+        .job_status(&name)
+        .await
+        .ok_or_else(|| PacsError::NotFound(format!("Scheduled job {}", name)))?;
+    Ok(Json(job))
+}
+
+/// 立即运行定时任务（忽略维护窗口，任务正在运行时拒绝）
+pub async fn run_job_now(
+    Extension(user): Extension<User>,
+    Extension(scheduler): Extension<Arc<JobScheduler>>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+    info!("{} requested immediate run of job {}", user.username, name);
+
+    let job = scheduler.run_now(&name).await?;
+    Ok(Json(job))
+}
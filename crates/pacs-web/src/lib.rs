@@ -1,7 +1,15 @@
 //! # PACS Web模块
 
 pub mod auth;
+pub mod documents;
+pub mod forwarding;
 pub mod handlers;
+pub mod imports;
+pub mod jobs;
+pub mod lifecycle;
+mod multipart;
+pub mod rejections;
 pub mod server;
 pub mod static_files;
+pub mod study_operations;
 pub mod wado;
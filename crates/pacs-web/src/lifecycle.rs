@@ -0,0 +1,114 @@
+//! 检查级生命周期管理接口（仅管理员）：即将删除的检查审阅与法律保留
+
+use axum::{
+    extract::{Extension, Path, Query},
+    response::{IntoResponse, Json},
+};
+use chrono::Utc;
+use pacs_core::{error::PacsError, Result};
+use pacs_storage::{LegalHold, LifecycleManager};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::auth::User;
+use crate::forwarding::require_admin;
+
+/// 默认的删除审阅时间范围（天）
+const DEFAULT_REVIEW_DAYS: u32 = 30;
+
+/// 即将删除检查查询参数
+#[derive(Debug, Deserialize)]
+pub struct UpcomingDeletionParams {
+    /// 查询未来多少天内到期的检查
+    pub within_days: Option<u32>,
+}
+
+/// 设置法律保留请求
+#[derive(Debug, Deserialize)]
+pub struct LegalHoldRequest {
+    /// 保留原因（如诉讼案号）
+    pub reason: String,
+}
+
+/// 获取即将到达保留期限、等待删除审阅的检查
+pub async fn get_upcoming_deletions(
+    Extension(user): Extension<User>,
+    Extension(manager): Extension<Arc<Mutex<LifecycleManager>>>,
+    Query(params): Query<UpcomingDeletionParams>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let within_days = params.within_days.unwrap_or(DEFAULT_REVIEW_DAYS);
+    let deletions = manager.lock().await.upcoming_deletions(within_days).await?;
+    Ok(Json(json!({
+        "within_days": within_days,
+        "count": deletions.len(),
+        "studies": deletions
+    })))
+}
+
+/// 获取全部法律保留
+pub async fn list_legal_holds(
+    Extension(user): Extension<User>,
+    Extension(manager): Extension<Arc<Mutex<LifecycleManager>>>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let holds = manager.lock().await.legal_holds();
+    Ok(Json(json!({
+        "count": holds.len(),
+        "holds": holds
+    })))
+}
+
+/// 对检查设置法律保留，保留期间检查不会被删除
+pub async fn place_legal_hold(
+    Extension(user): Extension<User>,
+    Extension(manager): Extension<Arc<Mutex<LifecycleManager>>>,
+    Path(study_uid): Path<String>,
+    Json(request): Json<LegalHoldRequest>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+    if request.reason.trim().is_empty() {
+        return Err(PacsError::Validation(
+            "Legal hold reason is required".to_string(),
+        ));
+    }
+    info!(
+        "{} requested legal hold on study {}: {}",
+        user.username, study_uid, request.reason
+    );
+
+    let hold = LegalHold {
+        study_uid,
+        reason: request.reason,
+        placed_by: user.username.clone(),
+        placed_at: Utc::now(),
+    };
+    manager.lock().await.place_legal_hold(hold.clone()).await?;
+    Ok(Json(hold))
+}
+
+/// 解除检查的法律保留
+pub async fn release_legal_hold(
+    Extension(user): Extension<User>,
+    Extension(manager): Extension<Arc<Mutex<LifecycleManager>>>,
+    Path(study_uid): Path<String>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+    info!(
+        "{} requested release of legal hold on study {}",
+        user.username, study_uid
+    );
+
+    let hold = manager
+        .lock()
+        .await
+        .release_legal_hold(&study_uid)
+        .await?
+        .ok_or_else(|| PacsError::NotFound(format!("Legal hold on study {}", study_uid)))?;
+    Ok(Json(hold))
+}
@@ -0,0 +1,214 @@
+//! multipart/related请求体的流式解析
+//!
+//! 按边界逐个读出各部分，部分内容直接写入调用方提供的输出，
+//! 内存中只保留当前数据块以及跨块匹配边界所需的尾部字节
+
+use axum::body::Bytes;
+use futures::{Stream, StreamExt};
+use pacs_core::{error::PacsError, Result};
+use std::fmt::Display;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// 部分头的最大长度
+const MAX_HEADER_SIZE: usize = 16 * 1024;
+
+/// multipart/related流式读取器
+pub(crate) struct MultipartReader<S> {
+    /// 请求体数据流
+    stream: S,
+    /// 尚未处理的字节
+    buffer: Vec<u8>,
+    /// 部分之间的分隔符（`CRLF--boundary`）
+    delimiter: Vec<u8>,
+    /// 是否已越过首个边界
+    started: bool,
+    /// 是否已读到结束边界
+    finished: bool,
+}
+
+impl<S, E> MultipartReader<S>
+where
+    S: Stream<Item = std::result::Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    /// 创建读取器
+    pub(crate) fn new(stream: S, boundary: &str) -> Self {
+        Self {
+            stream,
+            // 首个边界前没有CRLF，预置后与其他分隔符统一处理
+            buffer: b"\r\n".to_vec(),
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// 读取下一个部分的头
+    ///
+    /// 没有更多部分时返回None，否则返回部分声明的Content-Type（小写）
+    pub(crate) async fn next_part(&mut self) -> Result<Option<Option<String>>> {
+        if self.finished {
+            return Ok(None);
+        }
+        if !self.started {
+            // 跳过首个边界之前的前导内容
+            loop {
+                if let Some(position) = find_bytes(&self.buffer, &self.delimiter) {
+                    self.buffer.drain(..position + self.delimiter.len());
+                    break;
+                }
+                let keep = self.delimiter.len() - 1;
+                if self.buffer.len() > keep {
+                    self.buffer.drain(..self.buffer.len() - keep);
+                }
+                self.fill_or_malformed().await?;
+            }
+            self.started = true;
+        }
+
+        while self.buffer.len() < 2 {
+            self.fill_or_malformed().await?;
+        }
+        // 结束边界
+        if self.buffer.starts_with(b"--") {
+            self.finished = true;
+            return Ok(None);
+        }
+
+        // 边界行之后是部分头，以空行结束；没有部分头时空行紧随边界行
+        let headers_end = loop {
+            if let Some(position) = find_bytes(&self.buffer, b"\r\n\r\n") {
+                break position;
+            }
+            if self.buffer.len() > MAX_HEADER_SIZE {
+                return Err(malformed());
+            }
+            self.fill_or_malformed().await?;
+        };
+        let headers = String::from_utf8_lossy(&self.buffer[..headers_end]);
+        let content_type = headers.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("content-type")
+                .then(|| value.trim().to_ascii_lowercase())
+        });
+        self.buffer.drain(..headers_end + 4);
+
+        Ok(Some(content_type))
+    }
+
+    /// 将当前部分的内容写入输出并关闭输出，返回写入的字节数
+    ///
+    /// 输出提前关闭时继续读取并丢弃剩余内容，使后续部分仍可读取
+    pub(crate) async fn read_part<W>(&mut self, writer: &mut W) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut written = 0u64;
+        let mut writer_open = true;
+        loop {
+            let (content_end, consumed) = match find_bytes(&self.buffer, &self.delimiter) {
+                Some(position) => (position, position + self.delimiter.len()),
+                // 保留可能是分隔符前缀的尾部字节
+                None => {
+                    let end = self.buffer.len().saturating_sub(self.delimiter.len() - 1);
+                    (end, end)
+                }
+            };
+
+            if writer_open && content_end > 0 {
+                match writer.write_all(&self.buffer[..content_end]).await {
+                    Ok(()) => written += content_end as u64,
+                    Err(_) => writer_open = false,
+                }
+            }
+            let complete = consumed > content_end;
+            self.buffer.drain(..consumed);
+
+            if complete {
+                if writer_open {
+                    let _ = writer.shutdown().await;
+                }
+                return Ok(written);
+            }
+            self.fill_or_malformed().await?;
+        }
+    }
+
+    /// 从数据流读取下一块，数据流提前结束时返回错误
+    async fn fill_or_malformed(&mut self) -> Result<()> {
+        match self.stream.next().await {
+            Some(Ok(chunk)) => {
+                self.buffer.extend_from_slice(&chunk);
+                Ok(())
+            }
+            Some(Err(e)) => Err(PacsError::Validation(format!(
+                "Failed to read request body: {}",
+                e
+            ))),
+            None => Err(malformed()),
+        }
+    }
+}
+
+/// 请求体格式错误
+fn malformed() -> PacsError {
+    PacsError::Validation("Malformed multipart body".to_string())
+}
+
+/// 查找字节序列
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    /// 将请求体按固定大小切块，覆盖边界跨块的情况
+    fn chunked(
+        body: &'static [u8],
+        size: usize,
+    ) -> impl Stream<Item = std::result::Result<Bytes, Infallible>> + Unpin {
+        futures::stream::iter(
+            body.chunks(size)
+                .map(|chunk| Ok(Bytes::from_static(chunk)))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_read_multipart_related_in_chunks() {
+        let body: &'static [u8] = b"preamble\r\n--abc\r\nContent-Type: application/dicom\r\n\r\nFIRST\r\n--abc\r\n\r\nSECOND\r\n\r\n--abc\r\nContent-Type: application/pdf\r\n\r\nPDF\r\n--abc--\r\n";
+        for size in [1, 3, 7, body.len()] {
+            let mut reader = MultipartReader::new(chunked(body, size), "abc");
+            let mut parts = Vec::new();
+            while let Some(content_type) = reader.next_part().await.unwrap() {
+                let mut content = Vec::new();
+                reader.read_part(&mut content).await.unwrap();
+                parts.push((content_type, content));
+            }
+            assert_eq!(
+                parts,
+                vec![
+                    (Some("application/dicom".to_string()), b"FIRST".to_vec()),
+                    (None, b"SECOND\r\n".to_vec()),
+                    (Some("application/pdf".to_string()), b"PDF".to_vec()),
+                ]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncated_multipart_is_rejected() {
+        let mut reader = MultipartReader::new(chunked(b"--abc\r\n\r\nTRUNCATED", 4), "abc");
+        assert_eq!(reader.next_part().await.unwrap(), Some(None));
+        assert!(reader.read_part(&mut Vec::new()).await.is_err());
+
+        let mut reader = MultipartReader::new(chunked(b"no boundary here", 4), "abc");
+        assert!(reader.next_part().await.is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! 实例拒绝（IOCM）管理接口（仅管理员）
+
+use axum::{
+    extract::{Extension, Query},
+    response::{IntoResponse, Json},
+};
+use pacs_core::Result;
+use pacs_dicom::{IngestPipeline, InstanceRejectionRequest, InstanceRejectionService};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::User;
+use crate::forwarding::require_admin;
+
+/// 拒绝记录查询参数
+#[derive(Debug, Deserialize)]
+pub struct RejectionListParams {
+    /// 按检查UID过滤
+    pub study_instance_uid: Option<String>,
+    /// 返回条数
+    pub limit: Option<i64>,
+}
+
+/// 拒绝实例：生成拒绝说明并立即隐藏实例，宽限期后物理删除
+pub async fn reject_instances(
+    Extension(user): Extension<User>,
+    Extension(service): Extension<Arc<InstanceRejectionService>>,
+    Extension(pipeline): Extension<Arc<IngestPipeline>>,
+    Json(request): Json<InstanceRejectionRequest>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+    info!(
+        "{} requested rejection of {} instance(s) in study {} ({})",
+        user.username,
+        request.sop_instance_uids.len(),
+        request.study_instance_uid,
+        request.reason.code_meaning()
+    );
+
+    let report = service.reject(&pipeline, &request, &user.username).await?;
+    Ok(Json(report))
+}
+
+/// 获取拒绝记录
+pub async fn list_rejections(
+    Extension(user): Extension<User>,
+    Extension(service): Extension<Arc<InstanceRejectionService>>,
+    Query(params): Query<RejectionListParams>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let rejections = service
+        .list_rejections(
+            params.study_instance_uid.as_deref(),
+            params.limit.unwrap_or(100),
+        )
+        .await?;
+    let rejections: Vec<_> = rejections
+        .into_iter()
+        .map(|r| {
+            json!({
+                "sop_instance_uid": r.sop_instance_uid,
+                "study_instance_uid": r.study_uid,
+                "series_instance_uid": r.series_uid,
+                "sop_class_uid": r.sop_class_uid,
+                "reason_code": r.reason_code,
+                "rejection_note_uid": r.rejection_note_uid,
+                "rejected_by": r.rejected_by,
+                "rejected_at": r.rejected_at,
+                "purge_after": r.purge_after,
+                "purged_at": r.purged_at
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "count": rejections.len(),
+        "rejections": rejections
+    })))
+}
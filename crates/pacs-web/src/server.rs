@@ -1,15 +1,22 @@
 //! Web服务器
 
 use axum::{
-    extract::DefaultBodyLimit,
+    extract::{DefaultBodyLimit, Extension},
     http::StatusCode,
     response::IntoResponse,
     routing::{delete, get, post, put},
     Router,
 };
-use pacs_core::Result;
+use pacs_core::{ObjectRecall, PacsError, Result};
+use pacs_database::DatabasePool;
+use pacs_dicom::{
+    DicomImporter, ForwardingRouter, IngestPipeline, InstanceRejectionService,
+    StudyOperationService,
+};
+use pacs_storage::{JobScheduler, LifecycleManager, StorageManager};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use tower::ServiceBuilder;
 use tower_http::{
     cors::{Any, CorsLayer},
@@ -20,7 +27,21 @@ use tracing::info;
 use crate::auth::{
     auth_middleware, get_all_users_handler, get_current_user, login_handler, AuthService,
 };
-use crate::handlers::{api_root, get_instances, get_patients, get_series, get_studies, health};
+use crate::documents::{get_instance_document, upload_study_document};
+use crate::forwarding::{get_forward_failures, get_forward_queue, retry_forward_failures};
+use crate::handlers::{
+    api_root, get_instances, get_patients, get_series, get_series_presentation_states, get_studies,
+    get_study_key_images, health,
+};
+use crate::imports::{get_import, list_imports, resume_import, start_import};
+use crate::jobs::{get_job, list_jobs, run_job_now};
+use crate::lifecycle::{
+    get_upcoming_deletions, list_legal_holds, place_legal_hold, release_legal_hold,
+};
+use crate::rejections::{list_rejections, reject_instances};
+use crate::study_operations::{
+    execute_study_operation, list_study_operations, revert_study_operation,
+};
 use crate::wado::{qido_rs, stow_rs, wado_rs};
 
 pub struct WebServer {
@@ -36,18 +57,114 @@ impl WebServer {
         Self { addr, app }
     }
 
+    /// 挂载数据库连接池，供需要查询数据库的处理器使用
+    pub fn with_database(mut self, db: Arc<DatabasePool>) -> Self {
+        self.app = self.app.layer(Extension(db));
+        self
+    }
+
+    /// 挂载入库管道，供上传接口写入新实例
+    pub fn with_ingest_pipeline(mut self, pipeline: Arc<IngestPipeline>) -> Self {
+        self.app = self.app.layer(Extension(pipeline));
+        self
+    }
+
+    /// 挂载转发路由器，供管理员查看转发队列
+    pub fn with_forwarding_router(mut self, router: Arc<ForwardingRouter>) -> Self {
+        self.app = self.app.layer(Extension(router));
+        self
+    }
+
+    /// 挂载检查操作服务，供管理员拆分/合并/移动检查
+    pub fn with_study_operations(mut self, service: Arc<StudyOperationService>) -> Self {
+        self.app = self.app.layer(Extension(service));
+        self
+    }
+
+    /// 挂载批量导入器，供管理员从服务器本地的介质或目录导入影像
+    pub fn with_importer(mut self, importer: Arc<DicomImporter>) -> Self {
+        self.app = self.app.layer(Extension(importer));
+        self
+    }
+
+    /// 挂载实例拒绝服务，供管理员拒绝错误影像（需同时挂载入库管道）
+    pub fn with_rejection_service(mut self, service: Arc<InstanceRejectionService>) -> Self {
+        self.app = self.app.layer(Extension(service));
+        self
+    }
+
+    /// 挂载定时任务调度器，供管理员查看任务状态和手动运行任务
+    pub fn with_job_scheduler(mut self, scheduler: Arc<JobScheduler>) -> Self {
+        self.app = self.app.layer(Extension(scheduler));
+        self
+    }
+
+    /// 挂载实例文件所在的存储管理器，WADO-RS通过它流式读取实例对象
+    pub fn with_storage_manager(mut self, storage: Arc<StorageManager>) -> Self {
+        self.app = self.app.layer(Extension(storage));
+        self
+    }
+
+    /// 挂载对象召回服务，WADO-RS读取归档或近线层级的文件时透明召回
+    pub fn with_object_recall(mut self, recall: Arc<dyn ObjectRecall>) -> Self {
+        self.app = self.app.layer(Extension(recall));
+        self
+    }
+
+    /// 挂载生命周期管理器，供管理员审阅即将删除的检查和管理法律保留
+    pub fn with_lifecycle_manager(mut self, manager: Arc<Mutex<LifecycleManager>>) -> Self {
+        self.app = self.app.layer(Extension(manager));
+        self
+    }
+
+    /// 取出完整路由，供进程内调用（启动检查、测试）
+    pub fn into_router(self) -> Router {
+        self.app
+    }
+
     fn create_app(auth_service: Arc<AuthService>) -> Router {
         Router::new()
-            // 认证路由（无需token）
-            .route("/auth/login", post(login_handler))
-            .with_state(auth_service.clone())
             // 需要认证的路由
             .route("/auth/me", get(get_current_user))
+            .route("/admin/forwarding/queue", get(get_forward_queue))
+            .route("/admin/forwarding/failures", get(get_forward_failures))
+            .route(
+                "/admin/forwarding/failures/retry",
+                post(retry_forward_failures),
+            )
+            .route(
+                "/admin/studies/operations",
+                get(list_study_operations).post(execute_study_operation),
+            )
+            .route(
+                "/admin/studies/operations/:operation_id/revert",
+                post(revert_study_operation),
+            )
+            .route("/admin/imports", get(list_imports).post(start_import))
+            .route("/admin/imports/:job_id", get(get_import))
+            .route("/admin/imports/:job_id/resume", post(resume_import))
+            .route("/admin/instances/reject", post(reject_instances))
+            .route("/admin/rejections", get(list_rejections))
+            .route("/admin/jobs", get(list_jobs))
+            .route("/admin/jobs/:name", get(get_job))
+            .route("/admin/jobs/:name/run", post(run_job_now))
+            .route(
+                "/admin/lifecycle/upcoming-deletions",
+                get(get_upcoming_deletions),
+            )
+            .route("/admin/lifecycle/legal-holds", get(list_legal_holds))
+            .route(
+                "/admin/lifecycle/legal-holds/:study_uid",
+                put(place_legal_hold).delete(release_legal_hold),
+            )
             .with_state(auth_service.clone())
             .layer(axum::middleware::from_fn_with_state(
                 auth_service.clone(),
                 auth_middleware,
             ))
+            // 认证路由（无需token，须在认证中间件之后注册）
+            .route("/auth/login", post(login_handler))
+            .with_state(auth_service.clone())
             // 根路径
             .route("/", get(api_root))
             // 健康检查
@@ -79,7 +196,7 @@ impl WebServer {
         let listener = tokio::net::TcpListener::bind(self.addr).await?;
         axum::serve(listener, self.app)
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to start web server: {}", e))?;
+            .map_err(|e| PacsError::Internal(format!("Failed to start web server: {}", e)))?;
 
         Ok(())
     }
@@ -93,6 +210,13 @@ fn api_routes() -> Router<Arc<AuthService>> {
         .route("/studies", get(get_studies))
         .route("/series", get(get_series))
         .route("/instances", get(get_instances))
+        .route("/studies/:study_uid/key-images", get(get_study_key_images))
+        .route("/studies/:study_uid/documents", post(upload_study_document))
+        .route("/instances/:sop_uid/document", get(get_instance_document))
+        .route(
+            "/series/:series_uid/presentation-states",
+            get(get_series_presentation_states),
+        )
 }
 
 /// DICOMweb 路由
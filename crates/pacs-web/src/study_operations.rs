@@ -0,0 +1,106 @@
+//! 检查拆分/合并/移动管理接口（仅管理员）
+
+use axum::{
+    extract::{Extension, Path, Query},
+    response::{IntoResponse, Json},
+};
+use pacs_core::Result;
+use pacs_dicom::{StudyOperation, StudyOperationService};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::auth::User;
+use crate::forwarding::require_admin;
+
+/// 执行检查操作请求
+#[derive(Debug, Deserialize)]
+pub struct StudyOperationRequest {
+    /// 操作内容
+    #[serde(flatten)]
+    pub operation: StudyOperation,
+    /// 操作原因（记录在审计表中）
+    pub reason: Option<String>,
+}
+
+/// 操作列表查询参数
+#[derive(Debug, Deserialize)]
+pub struct StudyOperationListParams {
+    /// 返回条数
+    pub limit: Option<i64>,
+}
+
+/// 执行检查拆分/合并/移动操作
+pub async fn execute_study_operation(
+    Extension(user): Extension<User>,
+    Extension(service): Extension<Arc<StudyOperationService>>,
+    Json(request): Json<StudyOperationRequest>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+    info!(
+        "{} requested study operation {}",
+        user.username,
+        request.operation.operation_type()
+    );
+
+    let report = service
+        .execute(
+            &request.operation,
+            &user.username,
+            request.reason.as_deref(),
+        )
+        .await?;
+    Ok(Json(report))
+}
+
+/// 撤销检查操作
+pub async fn revert_study_operation(
+    Path(operation_id): Path<Uuid>,
+    Extension(user): Extension<User>,
+    Extension(service): Extension<Arc<StudyOperationService>>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+    info!(
+        "{} requested revert of study operation {}",
+        user.username, operation_id
+    );
+
+    let report = service.revert(&operation_id, &user.username).await?;
+    Ok(Json(report))
+}
+
+/// 获取检查操作审计记录
+pub async fn list_study_operations(
+    Extension(user): Extension<User>,
+    Extension(service): Extension<Arc<StudyOperationService>>,
+    Query(params): Query<StudyOperationListParams>,
+) -> Result<impl IntoResponse> {
+    require_admin(&user)?;
+
+    let operations = service.list_operations(params.limit.unwrap_or(100)).await?;
+    let operations: Vec<_> = operations
+        .into_iter()
+        .map(|op| {
+            json!({
+                "id": op.id,
+                "operation_type": op.operation_type,
+                "parameters": serde_json::from_str::<serde_json::Value>(&op.parameters).ok(),
+                "performed_by": op.performed_by,
+                "reason": op.reason,
+                "status": op.status,
+                "rejection_note_uid": op.rejection_note_uid,
+                "error": op.error,
+                "reverted_by": op.reverted_by,
+                "reverted_at": op.reverted_at,
+                "created_at": op.created_at
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "count": operations.len(),
+        "operations": operations
+    })))
+}
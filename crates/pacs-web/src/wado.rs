@@ -2,58 +2,144 @@
 
 use axum::{
     body::{Body, Bytes},
-    extract::{Path, Query, State},
+    extract::{Extension, Path, Query, State},
     http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use pacs_core::{error::PacsError, Result};
+use futures::{stream, StreamExt, TryStreamExt};
+use pacs_core::{error::PacsError, uid::validate_uid, ObjectAvailability, ObjectRecall, Result};
+use pacs_database::{DatabasePool, DatabaseQueries, InstanceSearch};
+use pacs_dicom::query::{self, QueryLevel};
+use pacs_dicom::{IngestOutcome, IngestPipeline, IngestSource};
+use pacs_storage::{ByteRange, LocalBackend, StorageConfig, StorageManager, StorageType};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio_util::io::StreamReader;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+use crate::auth::User;
+use crate::multipart::MultipartReader;
+
+/// multipart部分写入入库管道时的管道缓冲区大小
+const PART_PIPE_CAPACITY: usize = 64 * 1024;
+
 /// QIDO-RS - DICOM查询服务
 ///
-/// 实现DICOMweb的查询操作，支持搜索患者、检查、序列和实例
-pub async fn qido_rs(Query(params): Query<QidoParams>) -> Result<impl IntoResponse> {
+/// 实现DICOMweb的查询操作，支持搜索患者、检查、序列和实例。
+/// 结果从数据库实例表聚合，已拒绝（IOCM）的实例不会返回
+pub async fn qido_rs(
+    Query(params): Query<QidoParams>,
+    Extension(db): Extension<Arc<DatabasePool>>,
+) -> Result<impl IntoResponse> {
     info!("QIDO-RS query: {:?}", params);
 
-    match params.level.as_deref() {
-        Some("patient") | Some("PATIENT") => query_patients(&params).await,
-        Some("study") | Some("STUDY") => query_studies(&params).await,
-        Some("series") | Some("SERIES") => query_series(&params).await,
-        Some("instance") | Some("INSTANCE") => query_instances(&params).await,
-        _ => {
-            // 默认查询检查级别
-            query_studies(&params).await
-        }
+    let level = match params.level.as_deref() {
+        Some("patient") | Some("PATIENT") => QueryLevel::Patient,
+        Some("series") | Some("SERIES") => QueryLevel::Series,
+        Some("instance") | Some("INSTANCE") => QueryLevel::Image,
+        // 默认查询检查级别
+        _ => QueryLevel::Study,
+    };
+
+    let mut search = InstanceSearch {
+        patient_id: params.patient_id.clone(),
+        study_uid: params.study_instance_uid.clone(),
+        accession_number: params.accession_number.clone(),
+        series_uid: params.series_instance_uid.clone(),
+        modality: params.modality.clone(),
+        sop_instance_uid: params.sop_instance_uid.clone(),
+        ..Default::default()
+    };
+    // 实例级直接在数据库分页，其他级别在聚合后分页
+    let offset = params.offset.unwrap_or(0);
+    let limit = params.limit.unwrap_or(usize::MAX);
+    if level == QueryLevel::Image {
+        search.limit = params.limit.map(|limit| limit as i64);
+        search.offset = params.offset.map(|offset| offset as i64);
     }
+
+    let matches = DatabaseQueries::new(&db).search_instances(&search).await?;
+    let identifiers = query::group_matches(level, &matches);
+    let result: Vec<Value> = if level == QueryLevel::Image {
+        identifiers.iter().map(query::identifier_to_json).collect()
+    } else {
+        identifiers
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(query::identifier_to_json)
+            .collect()
+    };
+
+    Ok(Json(result))
 }
 
 /// WADO-RS - DICOM检索服务
 ///
-/// 实现DICOMweb的检索操作，支持检索DICOM对象和元数据
+/// 实现DICOMweb的检索操作，支持检索DICOM对象和元数据。
+/// DICOM对象通过存储管理器流式返回，实例级请求支持单个Range
 pub async fn wado_rs(
     Path(path_params): Path<WadoPathParams>,
     Query(params): Query<WadoParams>,
+    pipeline: Option<Extension<Arc<IngestPipeline>>>,
+    recall: Option<Extension<Arc<dyn ObjectRecall>>>,
+    storage: Option<Extension<Arc<StorageManager>>>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse> {
     info!("WADO-RS retrieve: {:?}, params: {:?}", path_params, params);
 
+    // 路径参数会参与构造存储路径，只接受合法UID（数字和点）
+    validate_uid(&path_params.study_uid)?;
+    for uid in [&path_params.series_uid, &path_params.instance_uid]
+        .into_iter()
+        .flatten()
+    {
+        validate_uid(uid)?;
+    }
+
     // 根据请求类型返回不同内容
     match params.request_type.as_deref() {
         Some("metadata") => retrieve_metadata(&path_params).await,
         Some("bulkdata") => retrieve_bulkdata(&path_params, &params).await,
-        None | Some("") => retrieve_dicom_object(&path_params).await,
+        None | Some("") => match pipeline {
+            Some(Extension(pipeline)) => {
+                let recall = recall.map(|Extension(recall)| recall);
+                let storage = match storage {
+                    Some(Extension(storage)) => storage,
+                    None => Arc::new(local_storage(pipeline.storage_dir())),
+                };
+                stream_dicom_objects(
+                    &path_params,
+                    &pipeline,
+                    &storage,
+                    recall.as_deref(),
+                    &headers,
+                )
+                .await
+            }
+            None => retrieve_dicom_object(&path_params).await,
+        },
         _ => Err(PacsError::Validation("Invalid request type".to_string())),
     }
 }
 
 /// STOW-RS - DICOM存储服务
 ///
-/// 实现DICOMweb的存储操作，支持存储DICOM文件
-pub async fn stow_rs(headers: HeaderMap, body: Bytes) -> Result<impl IntoResponse> {
+/// 实现DICOMweb的存储操作，支持存储DICOM文件。
+/// 单个 `application/dicom` 对象以流方式写入入库管道，
+/// `multipart/related` 请求体边读边拆分，各对象逐个以流方式写入同一管道，
+/// 与C-STORE一样登记到数据库
+pub async fn stow_rs(
+    pipeline: Option<Extension<Arc<IngestPipeline>>>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse> {
     info!(
         "STOW-RS store request, content-type: {:?}",
         headers.get(header::CONTENT_TYPE)
@@ -73,8 +159,22 @@ pub async fn stow_rs(headers: HeaderMap, body: Bytes) -> Result<impl IntoRespons
         ));
     }
 
-    // TODO: 解析和存储DICOM文件
-    let stored_instances = store_dicom_data(&body, content_type).await?;
+    let Some(Extension(pipeline)) = pipeline else {
+        return Err(PacsError::Internal(
+            "Ingest pipeline not configured".to_string(),
+        ));
+    };
+    let source = IngestSource::Upload {
+        username: user.map(|Extension(user)| user.username),
+    };
+
+    let stored_instances = if content_type.starts_with("application/dicom") {
+        vec![store_dicom_stream(body, &pipeline, &source).await?]
+    } else {
+        let boundary = multipart_boundary(content_type)
+            .ok_or_else(|| PacsError::Validation("Missing multipart boundary".to_string()))?;
+        store_multipart_parts(body, &boundary, &pipeline, &source).await?
+    };
 
     Ok(Json(json!({
         "status": "success",
@@ -131,71 +231,6 @@ pub struct StoredInstance {
     pub error_message: Option<String>,
 }
 
-// ========== 查询实现 ==========
-
-async fn query_patients(params: &QidoParams) -> Result<Value> {
-    // TODO: 从数据库查询患者数据
-    let patients = vec![json!({
-        "00100010": {"vr": "PN", "Value": [{"Alphabetic": "Doe^John"}]},
-        "00100020": {"vr": "LO", "Value": ["PAT001"]},
-        "00100030": {"vr": "DA", "Value": ["19800101"]},
-        "00100040": {"vr": "CS", "Value": ["M"]},
-    })];
-
-    Ok(json!(patients))
-}
-
-async fn query_studies(params: &QidoParams) -> Result<Value> {
-    // TODO: 从数据库查询检查数据
-    let studies = vec![json!({
-        "0020000D": {"vr": "UI", "Value": ["1.2.3.4.5.6.7.8.9.1"]},
-        "00080020": {"vr": "DA", "Value": ["20231015"]},
-        "00080030": {"vr": "TM", "Value": ["143000"]},
-        "00080050": {"vr": "SH", "Value": ["ACC001"]},
-        "00100010": {"vr": "PN", "Value": [{"Alphabetic": "Doe^John"}]},
-        "00100020": {"vr": "LO", "Value": ["PAT001"]},
-        "00081030": {"vr": "LO", "Value": ["CT Chest"]},
-        "00201206": {"vr": "IS", "Value": ["2"]},
-        "00201208": {"vr": "IS", "Value": ["250"]},
-    })];
-
-    Ok(json!(studies))
-}
-
-async fn query_series(params: &QidoParams) -> Result<Value> {
-    // TODO: 从数据库查询序列数据
-    let series = vec![json!({
-        "0020000E": {"vr": "UI", "Value": ["1.2.3.4.5.6.7.8.9.1.1"]},
-        "00200011": {"vr": "IS", "Value": ["1"]},
-        "0008103E": {"vr": "LO", "Value": ["Axial CT"]},
-        "00080060": {"vr": "CS", "Value": ["CT"]},
-        "00180015": {"vr": "CS", "Value": ["CHEST"]},
-        "00201209": {"vr": "IS", "Value": ["125"]},
-    })];
-
-    Ok(json!(series))
-}
-
-async fn query_instances(params: &QidoParams) -> Result<Value> {
-    // TODO: 从数据库查询实例数据
-    let instances = vec![json!({
-        "00080018": {"vr": "UI", "Value": ["1.2.3.4.5.6.7.8.9.1.1.1"]},
-        "00080016": {"vr": "UI", "Value": ["1.2.840.10008.5.1.4.1.1.2"]},
-        "00200013": {"vr": "IS", "Value": ["1"]},
-        "00280010": {"vr": "US", "Value": [512]},
-        "00280011": {"vr": "US", "Value": [512]},
-        "00280100": {"vr": "US", "Value": [16]},
-        "00280101": {"vr": "US", "Value": [12]},
-        "00280102": {"vr": "US", "Value": [11]},
-        "00280103": {"vr": "US", "Value": [0]},
-        "00280004": {"vr": "CS", "Value": ["MONOCHROME2"]},
-        "00280002": {"vr": "US", "Value": [1]},
-        "00280006": {"vr": "US", "Value": [0]},
-    })];
-
-    Ok(json!(instances))
-}
-
 // ========== WADO-RS实现 ==========
 
 async fn retrieve_metadata(path_params: &WadoPathParams) -> Result<Response> {
@@ -244,30 +279,448 @@ async fn retrieve_dicom_object(path_params: &WadoPathParams) -> Result<Response>
     Ok(response)
 }
 
+/// 从入库管道的存储文件流式返回DICOM对象
+///
+/// 实例级请求返回单个 `application/dicom` 对象，检查/序列级请求
+/// 以 `multipart/related` 逐个流式输出实例文件；已拒绝（IOCM）的实例不会返回。
+/// 配置了召回服务时先确保文件在线，慢速层级的召回进行中返回503
+async fn stream_dicom_objects(
+    path_params: &WadoPathParams,
+    pipeline: &IngestPipeline,
+    storage: &StorageManager,
+    recall: Option<&dyn ObjectRecall>,
+    headers: &HeaderMap,
+) -> Result<Response> {
+    if let (Some(series_uid), Some(instance_uid)) =
+        (&path_params.series_uid, &path_params.instance_uid)
+    {
+        if !pipeline
+            .is_instance_retrievable(&path_params.study_uid, instance_uid)
+            .await?
+        {
+            return Err(PacsError::NotFound(format!("Instance {}", instance_uid)));
+        }
+        let file_path = pipeline
+            .instance_path(&path_params.study_uid, series_uid, instance_uid)
+            .await?;
+        if let Some(recall) = recall {
+            if let Some(response) = ensure_online(recall, std::slice::from_ref(&file_path)).await? {
+                return Ok(response);
+            }
+        }
+        let key = object_key(pipeline, &file_path)?;
+        let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+        return stream_instance_object(storage, &key, instance_uid, range).await;
+    }
+
+    let files = pipeline
+        .list_retrievable_instance_files(&path_params.study_uid, path_params.series_uid.as_deref())
+        .await?;
+    if files.is_empty() {
+        return Err(PacsError::NotFound(format!(
+            "No instances found for study {}",
+            path_params.study_uid
+        )));
+    }
+    if let Some(recall) = recall {
+        let paths: Vec<PathBuf> = files.iter().map(|f| f.file_path.clone()).collect();
+        if let Some(response) = ensure_online(recall, &paths).await? {
+            return Ok(response);
+        }
+    }
+    // 召回后仍不在存储中的对象（已删除或无法定位）不返回，避免响应中途中断
+    let mut keys = Vec::with_capacity(files.len());
+    for file in &files {
+        let key = object_key(pipeline, &file.file_path)?;
+        if storage.backend().head(&key).await?.is_none() {
+            warn!(
+                "Skipping instance {} of study {}: object {} not found",
+                file.sop_instance_uid, path_params.study_uid, key
+            );
+            continue;
+        }
+        keys.push(key);
+    }
+    if keys.is_empty() {
+        return Err(PacsError::NotFound(format!(
+            "No instances found for study {}",
+            path_params.study_uid
+        )));
+    }
+
+    let boundary = Uuid::new_v4().simple().to_string();
+    let content_type = format!(
+        "multipart/related; type=\"application/dicom\"; boundary={}",
+        boundary
+    );
+    let closing = Bytes::from(format!("\r\n--{}--\r\n", boundary));
+    let storage = storage.clone();
+    let parts = stream::iter(keys)
+        .then(move |key: String| {
+            let storage = storage.clone();
+            let part_header = Bytes::from(format!(
+                "\r\n--{}\r\nContent-Type: application/dicom\r\n\r\n",
+                boundary
+            ));
+            async move {
+                let object = storage.get_stream(&key).await?;
+                Ok::<_, PacsError>(stream::once(async move { Ok(part_header) }).chain(object))
+            }
+        })
+        .try_flatten()
+        .chain(stream::once(async move { Ok(closing) }));
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from_stream(parts))
+        .unwrap())
+}
+
+/// 未挂载存储管理器时，以入库管道的存储目录创建本地存储
+fn local_storage(root: &std::path::Path) -> StorageManager {
+    StorageManager::with_backend(
+        StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(root.to_string_lossy().into_owned()),
+            object_store_config: None,
+        },
+        Arc::new(LocalBackend::new(root)),
+    )
+}
+
+/// 实例文件路径转换为相对存储根目录的对象键
+fn object_key(pipeline: &IngestPipeline, file_path: &std::path::Path) -> Result<String> {
+    let relative = file_path
+        .strip_prefix(pipeline.storage_dir())
+        .map_err(|_| {
+            PacsError::Storage(format!(
+                "Instance file outside storage root: {}",
+                file_path.display()
+            ))
+        })?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    Ok(parts.join("/"))
+}
+
+/// 确保所有文件在线
+///
+/// 对每个文件都发起可用性检查，使慢速层级的召回同时启动；
+/// 任一文件仍在召回中时返回带 `Retry-After` 的503响应
+async fn ensure_online(recall: &dyn ObjectRecall, paths: &[PathBuf]) -> Result<Option<Response>> {
+    let mut retry_after: Option<u64> = None;
+    let mut pending = 0;
+    for path in paths {
+        if let ObjectAvailability::RecallInProgress {
+            retry_after_secs, ..
+        } = recall.ensure_available(path).await?
+        {
+            pending += 1;
+            retry_after = Some(retry_after.map_or(retry_after_secs, |r| r.max(retry_after_secs)));
+        }
+    }
+
+    let Some(retry_after) = retry_after else {
+        return Ok(None);
+    };
+    info!(
+        "WADO-RS retrieve deferred: {} of {} objects are being recalled",
+        pending,
+        paths.len()
+    );
+    let body = json!({
+        "status": "recall in progress",
+        "pending": pending,
+        "total": paths.len(),
+        "retry_after": retry_after,
+    });
+    Ok(Some(
+        Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::RETRY_AFTER, retry_after)
+            .body(Body::from(body.to_string()))
+            .unwrap(),
+    ))
+}
+
+/// 流式返回单个实例对象，支持单个字节范围请求
+async fn stream_instance_object(
+    storage: &StorageManager,
+    key: &str,
+    instance_uid: &str,
+    range: Option<&str>,
+) -> Result<Response> {
+    let total_size = storage
+        .backend()
+        .head(key)
+        .await?
+        .ok_or_else(|| PacsError::NotFound(format!("Instance {}", instance_uid)))?
+        .size;
+
+    let Some(range) = range else {
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/dicom")
+            .header(header::CONTENT_LENGTH, total_size)
+            .header(header::ACCEPT_RANGES, "bytes")
+            .body(Body::from_stream(storage.get_stream(key).await?))
+            .unwrap());
+    };
+
+    let range = ByteRange::parse_header(range).filter(|range| range.resolve(total_size).is_some());
+    let Some(range) = range else {
+        return Ok(Response::builder()
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", total_size))
+            .body(Body::empty())
+            .unwrap());
+    };
+
+    let ranged = storage.get_range_stream(key, range).await?;
+    Ok(Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_TYPE, "application/dicom")
+        .header(
+            header::CONTENT_LENGTH,
+            ranged.range.end - ranged.range.start,
+        )
+        .header(
+            header::CONTENT_RANGE,
+            format!(
+                "bytes {}-{}/{}",
+                ranged.range.start,
+                ranged.range.end - 1,
+                ranged.total_size
+            ),
+        )
+        .body(Body::from_stream(ranged.stream))
+        .unwrap())
+}
+
 // ========== STOW-RS实现 ==========
 
-async fn store_dicom_data(data: &Bytes, content_type: &str) -> Result<Vec<StoredInstance>> {
+/// 将请求体以流方式写入入库管道
+async fn store_dicom_stream(
+    body: Body,
+    pipeline: &IngestPipeline,
+    source: &IngestSource,
+) -> Result<StoredInstance> {
+    let reader = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+
+    match pipeline.ingest_stream(reader, source, None).await {
+        Ok(outcome) => Ok(stored_instance(outcome)),
+        Err(e) => {
+            error!("STOW-RS ingest failed: {}", e);
+            Err(e)
+        }
+    }
+}
+
+/// 逐个写入multipart/related请求中的DICOM对象，单个对象失败不影响其他对象
+///
+/// 请求体按边界流式拆分，每个部分经管道直接写入入库管道，不在内存中缓存完整请求
+async fn store_multipart_parts(
+    body: Body,
+    boundary: &str,
+    pipeline: &IngestPipeline,
+    source: &IngestSource,
+) -> Result<Vec<StoredInstance>> {
+    let mut parts = MultipartReader::new(body.into_data_stream(), boundary);
+    let mut stored_instances = Vec::new();
+    while let Some(part_type) = parts.next_part().await? {
+        if let Some(part_type) = part_type.filter(|t| !t.starts_with("application/dicom")) {
+            parts.read_part(&mut tokio::io::sink()).await?;
+            stored_instances.push(failed_instance(format!(
+                "Unsupported part content type: {}",
+                part_type
+            )));
+            continue;
+        }
+
+        let (mut writer, reader) = tokio::io::duplex(PART_PIPE_CAPACITY);
+        let (copied, outcome) = tokio::join!(
+            parts.read_part(&mut writer),
+            pipeline.ingest_stream(reader, source, None)
+        );
+        copied?;
+        let stored = match outcome {
+            Ok(outcome) => stored_instance(outcome),
+            Err(e) => {
+                error!("STOW-RS ingest failed: {}", e);
+                failed_instance(e.to_string())
+            }
+        };
+        stored_instances.push(stored);
+    }
     info!(
-        "Storing DICOM data, content_type: {}, size: {} bytes",
-        content_type,
-        data.len()
+        "Stored {} DICOM parts from multipart request",
+        stored_instances.len()
     );
 
-    // TODO: 解析DICOM文件并存储
-    // 这里简单返回模拟的存储结果
-    let instance = StoredInstance {
-        study_instance_uid: "1.2.3.4.5.6.7.8.9.1".to_string(),
-        series_instance_uid: "1.2.3.4.5.6.7.8.9.1.1".to_string(),
-        sop_instance_uid: format!(
-            "{}.{}",
-            "1.2.3.4.5.6.7.8.9.1.1.1",
-            Uuid::new_v4().to_string().replace("-", "")[..32].to_string()
-        ),
-        sop_class_uid: "1.2.840.10008.5.1.4.1.1.2".to_string(),
-        transfer_syntax_uid: "1.2.840.10008.1.2.1".to_string(),
+    Ok(stored_instances)
+}
+
+/// 写入失败的存储结果
+fn failed_instance(error_message: String) -> StoredInstance {
+    StoredInstance {
+        study_instance_uid: String::new(),
+        series_instance_uid: String::new(),
+        sop_instance_uid: String::new(),
+        sop_class_uid: String::new(),
+        transfer_syntax_uid: String::new(),
+        success: false,
+        error_message: Some(error_message),
+    }
+}
+
+/// 入库结果转换为存储结果
+fn stored_instance(outcome: IngestOutcome) -> StoredInstance {
+    let instance = match outcome {
+        IngestOutcome::Stored(instance) => instance,
+        IngestOutcome::Duplicate { sop_instance_uid } => {
+            return StoredInstance {
+                study_instance_uid: String::new(),
+                series_instance_uid: String::new(),
+                sop_instance_uid,
+                sop_class_uid: String::new(),
+                transfer_syntax_uid: String::new(),
+                success: false,
+                error_message: Some("Instance already exists".to_string()),
+            };
+        }
+    };
+
+    let metadata = &instance.metadata;
+    StoredInstance {
+        study_instance_uid: metadata.study_instance_uid.clone().unwrap_or_default(),
+        series_instance_uid: metadata.series_instance_uid.clone().unwrap_or_default(),
+        sop_instance_uid: instance.sop_instance_uid.clone(),
+        sop_class_uid: metadata.sop_class_uid.clone().unwrap_or_default(),
+        transfer_syntax_uid: metadata.transfer_syntax_uid.clone().unwrap_or_default(),
         success: true,
         error_message: None,
-    };
+    }
+}
+
+/// 从Content-Type中读取multipart边界
+fn multipart_boundary(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+            .filter(|boundary| !boundary.is_empty())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wado_params() -> WadoParams {
+        WadoParams {
+            request_type: None,
+            media_type: None,
+            transfer_syntax: None,
+            quality: None,
+        }
+    }
+
+    async fn retrieve(
+        pipeline: &Arc<IngestPipeline>,
+        path_params: WadoPathParams,
+        headers: HeaderMap,
+    ) -> Response {
+        wado_rs(
+            Path(path_params),
+            Query(wado_params()),
+            Some(Extension(pipeline.clone())),
+            None,
+            None,
+            headers,
+        )
+        .await
+        .into_response()
+    }
 
-    Ok(vec![instance])
+    #[test]
+    fn test_multipart_boundary() {
+        let content_type = "multipart/related; type=\"application/dicom\"; boundary=\"abc\"";
+        assert_eq!(multipart_boundary(content_type).unwrap(), "abc");
+        assert_eq!(multipart_boundary("multipart/related; boundary="), None);
+    }
+
+    #[tokio::test]
+    async fn test_traversal_uid_is_rejected() {
+        let dir = std::env::temp_dir().join(format!("pacs-wado-{}", Uuid::new_v4()));
+        let pipeline = Arc::new(IngestPipeline::new(&dir));
+
+        for (study_uid, series_uid, instance_uid) in [
+            ("../../etc", Some("1.2"), Some("1.2.3")),
+            ("1.2.3", Some(".."), Some("passwd")),
+            ("1.2.3", Some("1.2"), Some("../../../etc/passwd")),
+            ("1.2.3/..", None, None),
+        ] {
+            let response = retrieve(
+                &pipeline,
+                WadoPathParams {
+                    study_uid: study_uid.to_string(),
+                    series_uid: series_uid.map(str::to_string),
+                    instance_uid: instance_uid.map(str::to_string),
+                },
+                HeaderMap::new(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_instance_range_is_served_from_storage() {
+        let dir = std::env::temp_dir().join(format!("pacs-wado-{}", Uuid::new_v4()));
+        let pipeline = Arc::new(IngestPipeline::new(&dir));
+        let path_params = || WadoPathParams {
+            study_uid: "1.2.3".to_string(),
+            series_uid: Some("1.2.3.4".to_string()),
+            instance_uid: Some("1.2.3.4.5".to_string()),
+        };
+        let file_path = pipeline
+            .instance_path("1.2.3", "1.2.3.4", "1.2.3.4.5")
+            .await
+            .unwrap();
+        tokio::fs::create_dir_all(file_path.parent().unwrap())
+            .await
+            .unwrap();
+        let content: Vec<u8> = (0..=255).collect();
+        tokio::fs::write(&file_path, &content).await.unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=10-19"));
+        let response = retrieve(&pipeline, path_params(), headers).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 10-19/256");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], &content[10..20]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=300-"));
+        let response = retrieve(&pipeline, path_params(), headers).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */256");
+
+        let response = retrieve(&pipeline, path_params(), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], &content[..]);
+
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
 }
@@ -1,11 +1,35 @@
 //! PACS服务器主程序
 
 use clap::Parser;
+use pacs_admin::alerting::{AlertManager, DefaultNotificationSender};
+use pacs_admin::backup::{PointInTimeRestoreService, SnapshotBackupJob};
+use pacs_admin::dose::DoseRegistry;
+use pacs_admin::lifecycle::DatabaseStudyMetadataSource;
+use pacs_admin::monitoring::{NotificationConfig, SystemMonitor};
 use pacs_core::Result;
-use pacs_dicom::{DicomServer, DicomServerConfig};
-use tracing::{error, info};
+use pacs_database::{DatabasePool, DatabaseQueries};
+use pacs_dicom::{
+    DatabaseRelocationListener, DicomImporter, DicomScu, DicomServer, DicomServerConfig,
+    ForwardingConfig, ForwardingRouter, IngestPipeline, InstanceRejectionService, IocmConfig,
+    PrefetchService, ScuConfig, StudyOperationService, StudyStabilityTracker,
+};
+use pacs_integration::Hl7Interface;
+use pacs_storage::{
+    default_study_rules, BackupManager, JobDefinition, JobScheduler, KeyRing, LayoutManager,
+    LifecycleManager, LifecycleStage, RecallConfig, RecallService, StateStore, StorageConfig,
+    StorageLayout, StorageManager, StorageType,
+};
+use pacs_web::server::WebServer;
+use settings::{ForwardingSettings, ServerSettings};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
 use tracing_subscriber;
 
+mod settings;
+
 /// PACS服务器命令行参数
 #[derive(Parser, Debug)]
 #[command(name = "pacs-server")]
@@ -23,10 +47,30 @@ struct Args {
     #[arg(short, long, default_value = "./data/dicom")]
     storage_dir: String,
 
+    /// Web服务端口（DICOMweb和REST接口）
+    #[arg(long, default_value = "8080")]
+    web_port: u16,
+
     /// 配置文件路径
     #[arg(short, long)]
     config: Option<String>,
 
+    /// PostgreSQL连接URL，未配置时不登记实例索引
+    #[arg(long)]
+    database_url: Option<String>,
+
+    /// 备份目标目录，配置后按计划备份存储目录
+    #[arg(long)]
+    backup_dir: Option<String>,
+
+    /// 归档层目录，配置后按默认的检查级保留规则归档和删除检查（需要数据库）
+    #[arg(long)]
+    archive_dir: Option<String>,
+
+    /// 数据库最大连接数
+    #[arg(long, default_value = "10")]
+    database_max_connections: u32,
+
     /// 日志级别
     #[arg(short, long, default_value = "info")]
     log_level: String,
@@ -43,6 +87,12 @@ async fn main() -> Result<()> {
 
     info!("启动PACS服务器...");
 
+    let settings = ServerSettings::load(args.config.as_deref())?;
+    // 机构UID根须在创建任何生成UID的服务之前设置
+    if let Some(org_root) = &settings.uid.org_root {
+        pacs_core::uid::set_default_root(org_root)?;
+    }
+
     // 创建服务器配置
     let server_config = DicomServerConfig {
         ae_title: args.ae_title.clone(),
@@ -55,15 +105,508 @@ async fn main() -> Result<()> {
     info!("  AE标题: {}", server_config.ae_title);
     info!("  监听端口: {}", server_config.port);
     info!("  存储目录: {}", server_config.storage_dir);
+    info!("  Web端口: {}", args.web_port);
+    info!("  UID根: {}", pacs_core::uid::default_generator().root());
+
+    // 连接数据库，入库实例登记到索引，检查级生命周期规则和转发队列依赖数据库
+    let database = match &args.database_url {
+        Some(database_url) => {
+            let database =
+                Arc::new(DatabasePool::new(database_url, args.database_max_connections).await?);
+            DatabaseQueries::new(&database).create_tables().await?;
+            info!("  数据库: 已连接");
+            Some(database)
+        }
+        None => {
+            warn!("未配置数据库，入库实例不会登记到数据库");
+            None
+        }
+    };
+
+    // 存储布局管理器记录入库对象，重启后恢复对象目录并继续中断的布局迁移；
+    // 迁移移动对象时同步更新数据库中的文件路径
+    let layout_storage = Arc::new(
+        StorageManager::new(StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(args.storage_dir.clone()),
+            object_store_config: None,
+        })
+        .await?,
+    );
+    let layout_state =
+        Arc::new(StateStore::open(Path::new(&args.storage_dir).join(".layout")).await?);
+    let mut layout_manager =
+        LayoutManager::new(layout_storage.clone(), StorageLayout::hierarchical())?
+            .with_state_store(layout_state);
+    if let Some(database) = &database {
+        layout_manager = layout_manager.with_relocation_listener(Arc::new(
+            DatabaseRelocationListener::new(database.clone(), &args.storage_dir),
+        ));
+    }
+    let layout_manager = Arc::new(layout_manager);
+    layout_manager.load_state().await?;
+
+    // 定时任务调度器，任务状态保存在存储目录中，重启后保留上次/下次运行时间
+    let scheduler = Arc::new(
+        JobScheduler::new().with_state_storage(layout_storage.clone(), SCHEDULER_STATE_KEY),
+    );
+    scheduler.load_state().await?;
+    if let Some(backup_dir) = &args.backup_dir {
+        schedule_backups(&scheduler, &args.storage_dir, backup_dir, database.clone()).await?;
+    }
+    let lifecycle = match &database {
+        Some(database) => Some(
+            schedule_lifecycle(
+                &scheduler,
+                &args.storage_dir,
+                args.archive_dir.as_deref(),
+                &layout_storage,
+                database.clone(),
+            )
+            .await?,
+        ),
+        None => {
+            if args.archive_dir.is_some() {
+                warn!("未配置数据库，无法按检查评估生命周期，忽略归档目录");
+            }
+            None
+        }
+    };
+    tokio::spawn(scheduler.clone().start());
+
+    // 创建入库管道，C-STORE、Web上传和批量导入共用
+    let mut pipeline =
+        IngestPipeline::new(&args.storage_dir).with_layout_manager(layout_manager.clone());
+    let services = match (&database, &lifecycle) {
+        (Some(database), Some((_, recall_service))) => {
+            let system_monitor =
+                SystemMonitor::new().map_err(|e| pacs_core::PacsError::Internal(e.to_string()))?;
+            let alert_manager = Arc::new(AlertManager::new(
+                Arc::new(DefaultNotificationSender),
+                Arc::new(system_monitor),
+            ));
+            let dose_registry = Arc::new(
+                DoseRegistry::new(alert_manager, NotificationConfig::default())
+                    .with_database(database.clone()),
+            );
+
+            // IOCM拒绝服务处理入库的拒绝说明，并定期清除到期的已拒绝实例
+            let rejection_service = Arc::new(
+                InstanceRejectionService::new(
+                    database.clone(),
+                    &args.storage_dir,
+                    IocmConfig::default(),
+                )
+                .with_layout_manager(layout_manager.clone()),
+            );
+            rejection_service.start();
+
+            // 自动转发路由器按配置的规则为入库实例排队，后台按目的地投递
+            let forwarding_router = start_forwarding(
+                &settings.forwarding,
+                &args.ae_title,
+                database.clone(),
+                recall_service.clone(),
+            )
+            .await;
+
+            // 稳定性跟踪器在静默期后或收到MPPS完成后判定检查接收完毕
+            let stability_tracker = Arc::new(StudyStabilityTracker::new(
+                database.clone(),
+                settings.stability.to_config(),
+            ));
+            stability_tracker.start();
+
+            pipeline = pipeline
+                .with_database(database.clone())
+                .with_listener(dose_registry)
+                .with_listener(rejection_service.clone())
+                .with_listener(forwarding_router.clone())
+                .with_listener(stability_tracker.clone());
+
+            // 历史检查预取：新检查到达或收到HL7检查申请时从远端PACS取回相关历史检查
+            let prefetch_service = settings.prefetch.clone().map(|config| {
+                let service = Arc::new(PrefetchService::new(
+                    database.clone(),
+                    local_scu(&args.ae_title),
+                    config,
+                ));
+                service.start();
+                info!(
+                    "  历史检查预取: {} 个远端PACS",
+                    service.config().archives.len()
+                );
+                service
+            });
+            if let Some(service) = &prefetch_service {
+                pipeline = pipeline.with_listener(service.clone());
+            }
+
+            Some(DatabaseServices {
+                database: database.clone(),
+                rejection_service,
+                forwarding_router,
+                stability_tracker,
+                prefetch_service,
+            })
+        }
+        _ => None,
+    };
+    let pipeline = Arc::new(pipeline);
+
+    // 检查操作服务供管理员拆分/合并/移动检查，改写后的实例经由入库管道登记
+    let study_operations = services.as_ref().map(|services| {
+        Arc::new(StudyOperationService::new(
+            services.database.clone(),
+            pipeline.clone(),
+        ))
+    });
+
+    // 批量导入器经由入库管道导入服务器本地的介质或目录，任务记录保存在存储目录下
+    let importer = Arc::new(DicomImporter::new(
+        pipeline.clone(),
+        Path::new(&args.storage_dir).join(".imports"),
+    ));
+
+    // HL7接口接收HIS/RIS的检查申请，转交预取服务
+    if let Some(port) = settings.hl7.port {
+        let mut hl7 = Hl7Interface::new();
+        match services.as_ref().and_then(|s| s.prefetch_service.clone()) {
+            Some(prefetch_service) => hl7 = hl7.with_order_listener(prefetch_service),
+            None => warn!("未启用历史检查预取，HL7检查申请不会触发预取"),
+        }
+        let hl7 = Arc::new(hl7);
+        let addr = format!("0.0.0.0:{}", port);
+        tokio::spawn(async move {
+            if let Err(e) = hl7.serve(&addr).await {
+                error!("HL7接口退出: {}", e);
+            }
+        });
+        info!("  HL7端口: {}", port);
+    }
 
     // 创建并启动DICOM服务器
-    let server = DicomServer::new(server_config).await?;
+    let mut server = DicomServer::new(server_config)
+        .await?
+        .with_ingest_pipeline(pipeline.clone());
+    if let Some(services) = &services {
+        server = server
+            .with_database(services.database.clone())
+            .with_stability_tracker(services.stability_tracker.clone());
+    }
+
+    // 创建Web服务器，与DICOM服务器共用入库管道、存储和数据库
+    let mut web_server = WebServer::new(SocketAddr::from(([0, 0, 0, 0], args.web_port)))
+        .with_ingest_pipeline(pipeline.clone())
+        .with_importer(importer)
+        .with_storage_manager(layout_storage)
+        .with_job_scheduler(scheduler);
+    if let Some(services) = &services {
+        web_server = web_server
+            .with_database(services.database.clone())
+            .with_rejection_service(services.rejection_service.clone())
+            .with_forwarding_router(services.forwarding_router.clone());
+    }
+    if let Some(study_operations) = study_operations {
+        web_server = web_server.with_study_operations(study_operations);
+    }
+    if let Some((lifecycle_manager, recall_service)) = lifecycle {
+        web_server = web_server
+            .with_lifecycle_manager(lifecycle_manager)
+            .with_object_recall(recall_service);
+    }
 
-    // 启动服务器
-    if let Err(e) = server.start().await {
+    // 启动服务器，任一服务退出即停止
+    let result = tokio::select! {
+        result = server.start() => result,
+        result = web_server.run() => result,
+    };
+    if let Err(e) = result {
         error!("服务器启动失败: {}", e);
         return Err(e);
     }
 
     Ok(())
 }
+
+/// 调度器状态在存储目录中的键
+const SCHEDULER_STATE_KEY: &str = ".scheduler/jobs.json";
+
+/// 依赖数据库的入库监听服务，同时挂载到Web服务器的管理接口
+struct DatabaseServices {
+    /// 数据库连接池
+    database: Arc<DatabasePool>,
+    /// IOCM实例拒绝服务
+    rejection_service: Arc<InstanceRejectionService>,
+    /// 自动转发路由器
+    forwarding_router: Arc<ForwardingRouter>,
+    /// 检查稳定性跟踪器，同时接收MPPS完成通知
+    stability_tracker: Arc<StudyStabilityTracker>,
+    /// 历史检查预取服务（配置文件启用时创建）
+    prefetch_service: Option<Arc<PrefetchService>>,
+}
+
+/// 以本机AE标题作为发送方的DICOM客户端
+fn local_scu(ae_title: &str) -> DicomScu {
+    DicomScu::new(ScuConfig {
+        calling_ae_title: ae_title.to_string(),
+        ..ScuConfig::default()
+    })
+}
+
+/// 创建并启动自动转发路由器，加载配置文件中的目的地和转发规则
+///
+/// 以本机AE标题作为发送方，已迁移到归档层的源文件在发送前透明召回
+async fn start_forwarding(
+    settings: &ForwardingSettings,
+    ae_title: &str,
+    database: Arc<DatabasePool>,
+    recall: Arc<RecallService>,
+) -> Arc<ForwardingRouter> {
+    let router = ForwardingRouter::new(database, local_scu(ae_title), ForwardingConfig::default())
+        .with_object_recall(recall);
+    for destination in &settings.destinations {
+        router.add_destination(destination.clone()).await;
+    }
+    for rule in &settings.rules {
+        router.add_rule(rule.clone()).await;
+    }
+    let router = Arc::new(router);
+    router.start();
+    info!(
+        "  自动转发: {} 个目的地, {} 条规则",
+        settings.destinations.len(),
+        settings.rules.len()
+    );
+    router
+}
+
+/// 创建生命周期管理器和召回服务并注册定时任务
+///
+/// 检查元数据取自数据库；配置归档目录时启用默认的检查级保留规则，
+/// 已迁移到归档层的实例在检索时透明召回
+async fn schedule_lifecycle(
+    scheduler: &JobScheduler,
+    storage_dir: &str,
+    archive_dir: Option<&str>,
+    online: &StorageManager,
+    database: Arc<DatabasePool>,
+) -> Result<(Arc<Mutex<LifecycleManager>>, Arc<RecallService>)> {
+    let metadata_source = DatabaseStudyMetadataSource::new(database).with_storage_root(storage_dir);
+    let lifecycle_state =
+        Arc::new(StateStore::open(Path::new(storage_dir).join(".lifecycle")).await?);
+    let mut manager = LifecycleManager::new()
+        .with_state_store(lifecycle_state)
+        .with_study_metadata_source(Arc::new(metadata_source));
+    manager.add_storage_manager(LifecycleStage::Online, online.clone());
+    match archive_dir {
+        Some(archive_dir) => {
+            let archive = StorageManager::new(StorageConfig {
+                storage_type: StorageType::Local,
+                local_path: Some(archive_dir.to_string()),
+                object_store_config: None,
+            })
+            .await?;
+            manager.add_storage_manager(LifecycleStage::Archive, archive);
+            for rule in default_study_rules() {
+                manager.add_study_rule(rule);
+            }
+            info!("  归档目录: {}", archive_dir);
+        }
+        None => info!("  归档目录: 未配置，不执行检查级保留规则"),
+    }
+    manager.load_state().await?;
+    let manager = Arc::new(Mutex::new(manager));
+    LifecycleManager::schedule_jobs(
+        manager.clone(),
+        scheduler,
+        LifecycleManager::default_job_definition(),
+    )
+    .await?;
+
+    let recall_state = Arc::new(StateStore::open(Path::new(storage_dir).join(".recall")).await?);
+    let recall = RecallService::new(online.clone(), RecallConfig::default())
+        .with_lifecycle_manager(manager.clone())
+        .with_state_store(recall_state);
+    recall.load_state().await?;
+    let recall = Arc::new(recall);
+    RecallService::schedule_jobs(recall.clone(), scheduler).await?;
+
+    Ok((manager, recall))
+}
+
+/// 创建备份管理器并注册定时备份和恢复演练任务
+///
+/// 配置了数据库时备份附带数据库快照，以便按时间点同时恢复对象和索引
+async fn schedule_backups(
+    scheduler: &JobScheduler,
+    storage_dir: &str,
+    backup_dir: &str,
+    database: Option<Arc<DatabasePool>>,
+) -> Result<()> {
+    let local_storage = |path: &str| StorageConfig {
+        storage_type: StorageType::Local,
+        local_path: Some(path.to_string()),
+        object_store_config: None,
+    };
+    let mut config =
+        BackupManager::create_default_config(local_storage(storage_dir), local_storage(backup_dir));
+    let key_ring = KeyRing::from_environment()?;
+    if key_ring.is_none() {
+        warn!("未配置备份密钥，备份将不加密");
+        config.encryption_enabled = false;
+    }
+
+    let backup_state = Arc::new(StateStore::open(Path::new(storage_dir).join(".backup")).await?);
+    let mut manager = BackupManager::new().with_state_store(backup_state);
+    manager.load_state().await?;
+
+    // 带数据库快照的备份由快照任务按同一计划执行，不再注册普通备份任务
+    let snapshot_schedule = match &database {
+        Some(_) => config.schedule.take(),
+        None => None,
+    };
+    let config_name = config.name.clone();
+    let backup_type = config.backup_type.clone();
+    manager.add_config(config)?;
+    let manager = Arc::new(Mutex::new(manager));
+    let registered = BackupManager::schedule_jobs(manager.clone(), scheduler).await?;
+
+    if let (Some(database), Some(schedule)) = (database, snapshot_schedule) {
+        let mut service = PointInTimeRestoreService::new(manager, database);
+        if let Some(key_ring) = key_ring {
+            service = service.with_key_ring(key_ring);
+        }
+        scheduler
+            .register(
+                JobDefinition::new(&format!("backup:{}", config_name), &schedule),
+                Arc::new(SnapshotBackupJob::new(
+                    Arc::new(service),
+                    &config_name,
+                    backup_type,
+                )),
+            )
+            .await?;
+        info!(
+            "  备份: 已注册 {} 个定时任务（含数据库快照）",
+            registered + 1
+        );
+    } else {
+        info!("  备份: 已注册 {} 个定时任务", registered);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    /// 配置文件中的转发规则在启动时加载，管理员转发接口可用（需设置PACS_TEST_DATABASE_URL）
+    #[tokio::test]
+    async fn test_forwarding_routes_respond_after_startup() {
+        let Ok(database_url) = std::env::var("PACS_TEST_DATABASE_URL") else {
+            return;
+        };
+        let database = Arc::new(DatabasePool::new(&database_url, 2).await.unwrap());
+        DatabaseQueries::new(&database)
+            .create_tables()
+            .await
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("pacs-server-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let config_path = dir.join("pacs.toml");
+        tokio::fs::write(
+            &config_path,
+            r#"
+[[forwarding.destinations]]
+name = "research"
+max_concurrency = 2
+enabled = true
+remote = { ae_title = "RESEARCH", host = "127.0.0.1", port = 11113 }
+
+[[forwarding.rules]]
+name = "ct-to-research"
+enabled = true
+destinations = ["research"]
+conditions = [{ type = "modality", values = ["CT"] }]
+
+[uid]
+org_root = "1.2.826.0.1.3680043.10.1"
+"#,
+        )
+        .await
+        .unwrap();
+        let settings = ServerSettings::load(config_path.to_str()).unwrap();
+        assert_eq!(
+            settings.uid.org_root.as_deref(),
+            Some("1.2.826.0.1.3680043.10.1")
+        );
+
+        let online = StorageManager::new(StorageConfig {
+            storage_type: StorageType::Local,
+            local_path: Some(dir.to_string_lossy().to_string()),
+            object_store_config: None,
+        })
+        .await
+        .unwrap();
+        let recall = Arc::new(RecallService::new(online, RecallConfig::default()));
+        let router =
+            start_forwarding(&settings.forwarding, "PACS_TEST", database.clone(), recall).await;
+        let app = WebServer::new(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .with_database(database)
+            .with_forwarding_router(router)
+            .into_router();
+        // 默认用户由认证服务在后台初始化
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let login = app
+            .clone()
+            .oneshot(
+                Request::post("/auth/login")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"username":"admin","password":"admin"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(login.status(), StatusCode::OK);
+        let login: serde_json::Value =
+            serde_json::from_slice(&to_bytes(login.into_body(), usize::MAX).await.unwrap())
+                .unwrap();
+        let token = login["token"].as_str().unwrap().to_string();
+
+        let get = |uri: &str| {
+            Request::get(uri)
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let queue = app
+            .clone()
+            .oneshot(get("/admin/forwarding/queue"))
+            .await
+            .unwrap();
+        assert_eq!(queue.status(), StatusCode::OK);
+        let queue: serde_json::Value =
+            serde_json::from_slice(&to_bytes(queue.into_body(), usize::MAX).await.unwrap())
+                .unwrap();
+        assert_eq!(queue["rules"][0]["name"], "ct-to-research");
+        assert!(queue["destinations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|d| d["destination"] == "research"));
+
+        let failures = app
+            .oneshot(get("/admin/forwarding/failures?destination=research"))
+            .await
+            .unwrap();
+        assert_eq!(failures.status(), StatusCode::OK);
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+}
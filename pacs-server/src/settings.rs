@@ -0,0 +1,124 @@
+//! 服务器配置文件
+//!
+//! 由 `--config` 指定，格式按扩展名识别（TOML/YAML/JSON）。各节均可省略，
+//! 省略时使用默认值或不启用对应功能。
+
+use pacs_core::{PacsError, Result};
+use pacs_dicom::{ForwardingDestination, ForwardingRule, PrefetchConfig, StabilityConfig};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// 服务器配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    /// 自动转发配置
+    pub forwarding: ForwardingSettings,
+    /// 检查稳定性检测配置
+    pub stability: StabilitySettings,
+    /// 历史检查预取配置，省略时不启用预取
+    pub prefetch: Option<PrefetchConfig>,
+    /// HL7接口配置
+    pub hl7: Hl7Settings,
+    /// UID生成配置
+    pub uid: UidSettings,
+}
+
+/// 自动转发配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ForwardingSettings {
+    /// 转发目的地
+    pub destinations: Vec<ForwardingDestination>,
+    /// 转发规则，按名称引用目的地
+    pub rules: Vec<ForwardingRule>,
+}
+
+/// 检查稳定性检测配置，时间均以秒为单位
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StabilitySettings {
+    /// 默认静默期
+    pub default_quiet_period_secs: u64,
+    /// 按模态覆盖的静默期
+    pub modality_quiet_period_secs: HashMap<String, u64>,
+    /// 收到MPPS完成后的宽限期
+    pub mpps_grace_period_secs: u64,
+}
+
+impl Default for StabilitySettings {
+    fn default() -> Self {
+        let config = StabilityConfig::default();
+        Self {
+            default_quiet_period_secs: config.default_quiet_period.as_secs(),
+            modality_quiet_period_secs: HashMap::new(),
+            mpps_grace_period_secs: config.mpps_grace_period.as_secs(),
+        }
+    }
+}
+
+impl StabilitySettings {
+    /// 转换为稳定性检测配置，模态统一为大写
+    pub fn to_config(&self) -> StabilityConfig {
+        StabilityConfig {
+            default_quiet_period: Duration::from_secs(self.default_quiet_period_secs),
+            modality_quiet_periods: self
+                .modality_quiet_period_secs
+                .iter()
+                .map(|(modality, secs)| (modality.to_uppercase(), Duration::from_secs(*secs)))
+                .collect(),
+            mpps_grace_period: Duration::from_secs(self.mpps_grace_period_secs),
+            ..StabilityConfig::default()
+        }
+    }
+}
+
+/// HL7接口配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Hl7Settings {
+    /// MLLP监听端口，未配置时不接收HL7消息
+    pub port: Option<u16>,
+}
+
+/// UID生成配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UidSettings {
+    /// 机构UID根，生成的新UID（检查拆分/合并等）以此为前缀，未配置时使用内置根
+    pub org_root: Option<String>,
+}
+
+impl ServerSettings {
+    /// 读取配置文件，未指定时使用默认配置
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let settings: Self = config::Config::builder()
+            .add_source(config::File::with_name(path))
+            .build()
+            .and_then(config::Config::try_deserialize)
+            .map_err(|e| PacsError::Config(format!("Failed to load {}: {}", path, e)))?;
+        settings.forwarding.validate()?;
+        Ok(settings)
+    }
+}
+
+impl ForwardingSettings {
+    /// 校验规则引用的目的地均已配置
+    fn validate(&self) -> Result<()> {
+        for rule in &self.rules {
+            for name in &rule.destinations {
+                if !self.destinations.iter().any(|d| &d.name == name) {
+                    return Err(PacsError::Config(format!(
+                        "Forwarding rule {} references unknown destination {}",
+                        rule.name, name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}